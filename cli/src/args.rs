@@ -0,0 +1,294 @@
+use anchor_lang::prelude::Pubkey;
+use std::{collections::HashMap, path::PathBuf, str::FromStr};
+
+pub const USAGE: &str = "\
+Usage: escrow [--url <URL>] [--keypair <PATH>] <COMMAND>
+
+Commands:
+  make --mint-a <MINT> --mint-b <MINT> --amount <N> --receive <N> [--seed <N>]
+  take <ESCROW> [--max-receive <N>]
+  refund <ESCROW>
+  list [--maker <PUBKEY>]
+
+Options:
+  -u, --url <URL>       RPC URL or localnet, devnet, testnet, mainnet-beta [default: localnet]
+  -k, --keypair <PATH>  Signer keypair file [default: ~/.config/solana/id.json]
+  -h, --help            Print this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    // seed 为空时使用当前时间生成
+    Make {
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        amount: u64,
+        receive: u64,
+        seed: Option<u64>,
+    },
+    // max_receive 为空时使用查询到的当前价格
+    Take {
+        escrow: Pubkey,
+        max_receive: Option<u64>,
+    },
+    Refund {
+        escrow: Pubkey,
+    },
+    // maker 为空时列出 keypair 创建的托管
+    List {
+        maker: Option<Pubkey>,
+    },
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub url: String,
+    pub keypair: PathBuf,
+    pub command: Command,
+}
+
+// 解析不包含程序名的命令行参数, 选项可以写在子命令前后, 也可以写成 --name=value
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+    let mut positionals = Vec::new();
+    let mut options = HashMap::new();
+    let mut help = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let name = match arg.as_str() {
+            "-h" | "--help" => {
+                help = true;
+                continue;
+            }
+            "-u" => "url".to_string(),
+            "-k" => "keypair".to_string(),
+            _ => match arg.strip_prefix("--") {
+                Some(name) => name.to_string(),
+                None => {
+                    positionals.push(arg);
+                    continue;
+                }
+            },
+        };
+
+        let (name, value) = match name.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for --{name}"))?;
+                (name, value)
+            }
+        };
+        if options.insert(name.clone(), value).is_some() {
+            return Err(format!("--{name} given more than once"));
+        }
+    }
+
+    let mut options = Options(options);
+    let url = cluster_url(
+        &options
+            .take("url")
+            .unwrap_or_else(|| "localnet".to_string()),
+    );
+    let keypair = options
+        .take("keypair")
+        .map(PathBuf::from)
+        .unwrap_or_else(default_keypair);
+
+    // --help 忽略其他参数
+    let command = if help {
+        Command::Help
+    } else {
+        parse_command(&positionals, &mut options)?
+    };
+    if let (false, Some(name)) = (help, options.0.keys().next()) {
+        return Err(format!("unexpected option --{name}"));
+    }
+
+    Ok(Cli {
+        url,
+        keypair,
+        command,
+    })
+}
+
+fn parse_command(positionals: &[String], options: &mut Options) -> Result<Command, String> {
+    let (name, rest) = positionals
+        .split_first()
+        .ok_or_else(|| "missing command".to_string())?;
+
+    let command = match name.as_str() {
+        "make" => Command::Make {
+            mint_a: options.required("mint-a")?,
+            mint_b: options.required("mint-b")?,
+            amount: options.required("amount")?,
+            receive: options.required("receive")?,
+            seed: options.optional("seed")?,
+        },
+        "take" => Command::Take {
+            escrow: single_address(rest)?,
+            max_receive: options.optional("max-receive")?,
+        },
+        "refund" => Command::Refund {
+            escrow: single_address(rest)?,
+        },
+        "list" => Command::List {
+            maker: options.optional("maker")?,
+        },
+        _ => return Err(format!("unknown command `{name}`")),
+    };
+
+    // take 和 refund 的托管地址已经在 single_address 中检查过
+    if matches!(command, Command::Make { .. } | Command::List { .. }) && !rest.is_empty() {
+        return Err(format!("unexpected argument `{}`", rest[0]));
+    }
+
+    Ok(command)
+}
+
+fn single_address(rest: &[String]) -> Result<Pubkey, String> {
+    match rest {
+        [escrow] => parse_value("escrow", escrow),
+        [] => Err("missing escrow address".to_string()),
+        [_, extra, ..] => Err(format!("unexpected argument `{extra}`")),
+    }
+}
+
+struct Options(HashMap<String, String>);
+
+impl Options {
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name)
+    }
+
+    fn optional<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.take(name)
+            .map(|value| parse_value(name, &value))
+            .transpose()
+    }
+
+    fn required<T: FromStr>(&mut self, name: &str) -> Result<T, String> {
+        self.optional(name)?
+            .ok_or_else(|| format!("missing required option --{name}"))
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for {name}"))
+}
+
+// 和 solana CLI 一样支持集群简称
+pub fn cluster_url(url: &str) -> String {
+    match url {
+        "localnet" | "l" => "http://127.0.0.1:8899",
+        "devnet" | "d" => "https://api.devnet.solana.com",
+        "testnet" | "t" => "https://api.testnet.solana.com",
+        "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+    .to_string()
+}
+
+fn default_keypair() -> PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/solana/id.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Cli, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_make_with_global_options_anywhere() {
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let cli = parse_str(&format!(
+            "-u devnet make --mint-a {mint_a} --mint-b={mint_b} --amount 1000 --receive 500 --keypair maker.json"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            cli,
+            Cli {
+                url: "https://api.devnet.solana.com".to_string(),
+                keypair: PathBuf::from("maker.json"),
+                command: Command::Make {
+                    mint_a,
+                    mint_b,
+                    amount: 1_000,
+                    receive: 500,
+                    seed: None,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn parses_escrow_commands() {
+        let escrow = Pubkey::new_unique();
+
+        assert_eq!(
+            parse_str(&format!("take {escrow} --max-receive 10"))
+                .unwrap()
+                .command,
+            Command::Take {
+                escrow,
+                max_receive: Some(10),
+            }
+        );
+        assert_eq!(
+            parse_str(&format!("refund {escrow}")).unwrap().command,
+            Command::Refund { escrow }
+        );
+        assert_eq!(
+            parse_str(&format!("list --maker {escrow}"))
+                .unwrap()
+                .command,
+            Command::List {
+                maker: Some(escrow)
+            }
+        );
+        assert_eq!(
+            parse_str("list").unwrap().command,
+            Command::List { maker: None }
+        );
+        assert_eq!(parse_str("take --help").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let escrow = Pubkey::new_unique();
+
+        assert_eq!(parse_str("").unwrap_err(), "missing command");
+        assert_eq!(parse_str("close").unwrap_err(), "unknown command `close`");
+        assert_eq!(parse_str("take").unwrap_err(), "missing escrow address");
+        assert_eq!(
+            parse_str("refund not-a-key").unwrap_err(),
+            "invalid value `not-a-key` for escrow"
+        );
+        assert_eq!(
+            parse_str(&format!("refund {escrow} --force yes")).unwrap_err(),
+            "unexpected option --force"
+        );
+        assert_eq!(
+            parse_str(&format!("make --mint-a {escrow}")).unwrap_err(),
+            "missing required option --mint-b"
+        );
+        assert_eq!(
+            parse_str("list --maker").unwrap_err(),
+            "missing value for --maker"
+        );
+    }
+
+    #[test]
+    fn keeps_custom_urls() {
+        assert_eq!(cluster_url("http://10.0.0.1:8899"), "http://10.0.0.1:8899");
+        assert_eq!(cluster_url("m"), "https://api.mainnet-beta.solana.com");
+    }
+}
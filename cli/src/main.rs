@@ -0,0 +1,307 @@
+// 托管程序的命令行工具, 用于运维和演示, 交易的构造和发送都由 escrow-sdk 完成
+
+mod args;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use args::{Cli, Command, USAGE};
+use blueshift_anchor_escrow::{pda::find_escrow_address, state::Escrow};
+use escrow_sdk::EscrowClient;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{read_keypair_file, Signature, Signer},
+};
+use std::{
+    error::Error,
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn main() -> ExitCode {
+    let cli = match args::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    // SdkError 的 Display 会把程序错误码解码为 errors.rs 中的错误信息
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let client = EscrowClient::new(RpcClient::new_with_commitment(
+        cli.url.clone(),
+        CommitmentConfig::confirmed(),
+    ));
+    let signer = || {
+        read_keypair_file(&cli.keypair)
+            .map_err(|error| format!("failed to read keypair {}: {error}", cli.keypair.display()))
+    };
+
+    match cli.command {
+        Command::Make {
+            mint_a,
+            mint_b,
+            amount,
+            receive,
+            seed,
+        } => {
+            let maker = signer()?;
+            let seed = seed.unwrap_or_else(default_seed);
+            let token_program_a = client.rpc().get_account(&mint_a)?.owner;
+            let (escrow, vault) = escrow_accounts(&maker.pubkey(), seed, &mint_a, &token_program_a);
+
+            let signature = client.make(&maker, &mint_a, &mint_b, seed, receive, amount)?;
+
+            println!("escrow: {escrow}");
+            println!("seed:   {seed}");
+            println!("vault:  {vault}");
+            print_links(&cli.url, &signature, &escrow);
+        }
+        Command::Take {
+            escrow,
+            max_receive,
+        } => {
+            let taker = signer()?;
+            let state = client.fetch_escrow(&escrow)?;
+            // 默认接受当前价格, 荷兰拍卖的价格只会下降, 发送时的价格不会超过这里的计算结果
+            let max_receive = match max_receive {
+                Some(max_receive) => max_receive,
+                None => state.pro_rata(state.amount, state.current_receive(unix_timestamp())?)?,
+            };
+
+            let signature = client.take(&taker, &escrow, max_receive)?;
+
+            println!("took {} of mint {}", state.amount, state.mint_a);
+            print_links(&cli.url, &signature, &escrow);
+        }
+        Command::Refund { escrow } => {
+            let maker = signer()?;
+            let state = client.fetch_escrow(&escrow)?;
+
+            let signature = client.refund(&maker, &escrow)?;
+
+            println!("refunded {} of mint {}", state.amount, state.mint_a);
+            print_links(&cli.url, &signature, &escrow);
+        }
+        Command::List { maker } => {
+            let maker = match maker {
+                Some(maker) => maker,
+                None => signer()?.pubkey(),
+            };
+
+            let escrows = client.escrows_by_maker(&maker)?;
+            if escrows.is_empty() {
+                println!("no escrows for maker {maker}");
+            }
+            for (address, escrow) in escrows {
+                println!("{}", describe(&address, &escrow));
+            }
+        }
+        Command::Help => println!("{USAGE}"),
+    }
+
+    Ok(())
+}
+
+// 托管 PDA 和存放 token A 的 vault, vault 是 escrow 在 mint A 的 token 程序下的 ATA
+fn escrow_accounts(
+    maker: &Pubkey,
+    seed: u64,
+    mint_a: &Pubkey,
+    token_program_a: &Pubkey,
+) -> (Pubkey, Pubkey) {
+    let (escrow, _) = find_escrow_address(maker, seed);
+    let vault = get_associated_token_address_with_program_id(&escrow, mint_a, token_program_a);
+    (escrow, vault)
+}
+
+fn default_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the unix epoch")
+        .as_millis() as u64
+}
+
+fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the unix epoch")
+        .as_secs() as i64
+}
+
+fn print_links(url: &str, signature: &Signature, escrow: &Pubkey) {
+    println!("tx:     {}", explorer_link(&format!("tx/{signature}"), url));
+    println!(
+        "view:   {}",
+        explorer_link(&format!("address/{escrow}"), url)
+    );
+}
+
+// solana explorer 的链接, 非公共集群使用 customUrl 指向 RPC 节点
+fn explorer_link(path: &str, url: &str) -> String {
+    let base = format!("https://explorer.solana.com/{path}");
+    match url {
+        "https://api.mainnet-beta.solana.com" => base,
+        "https://api.devnet.solana.com" => format!("{base}?cluster=devnet"),
+        "https://api.testnet.solana.com" => format!("{base}?cluster=testnet"),
+        url => format!("{base}?cluster=custom&customUrl={}", percent_encode(url)),
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+// list 输出的一行, mint_b 为 Pubkey::default() 的托管用 SOL 支付
+fn describe(address: &Pubkey, escrow: &Escrow) -> String {
+    let wants = if escrow.is_sol_mode() {
+        format!("{} lamports", escrow.receive)
+    } else {
+        format!("{} of {}", escrow.receive, escrow.mint_b)
+    };
+    let expiry = match escrow.expiry {
+        0 => "never".to_string(),
+        expiry => expiry.to_string(),
+    };
+    format!(
+        "{address} seed={} offers {} of {} for {wants} (deposited {}, expires {expiry}, {:?})",
+        escrow.seed, escrow.amount, escrow.mint_a, escrow.deposited, escrow.status
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{AccountDeserialize, AccountSerialize, Discriminator};
+    use anchor_spl::{associated_token::get_associated_token_address, token::spl_token};
+    use blueshift_anchor_escrow::state::{EscrowStatus, PaymentMint};
+
+    fn escrow(mint_b: Pubkey) -> Escrow {
+        Escrow {
+            version: Escrow::VERSION,
+            seed: 7,
+            maker: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            receive_to: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b,
+            receive: 500,
+            deposited: 1_000,
+            amount: 600,
+            expiry: 0,
+            start_time: 0,
+            created_at: 0,
+            commit_delay_slots: 0,
+            reservation_window: 0,
+            allowed_taker: Pubkey::default(),
+            refund_delegate: Pubkey::default(),
+            arbiter: Pubkey::default(),
+            taker_allowlist_root: [0; 32],
+            hashlock: [0; 32],
+            decay_start: 0,
+            decay_end: 0,
+            end_receive: 0,
+            alt_payments: [PaymentMint::default(); 3],
+            locked_taker: Pubkey::default(),
+            locked_at: 0,
+            reserved_taker: Pubkey::default(),
+            reservation_expiry: 0,
+            bond: 0,
+            recurring: false,
+            max_refills: 0,
+            status: EscrowStatus::PartiallyFilled,
+            status_before_freeze: EscrowStatus::PartiallyFilled,
+            bump: 255,
+            vault: Pubkey::default(),
+            price_feed: Pubkey::default(),
+            quote_exponent: 0,
+            max_staleness: 0,
+            max_deviation_bps: 0,
+            collection: Pubkey::default(),
+            enforce_royalties: false,
+            tag: 0,
+            pair_indexed: false,
+            cancel_reason: 0,
+            cancelled_slot: 0,
+            direct_only: false,
+            maker_paused: false,
+            stream_window: 0,
+            stream_receive: 0,
+            paid_b: 0,
+            stream_deadline: 0,
+            allow_operators: false,
+        }
+    }
+
+    #[test]
+    fn derives_escrow_and_vault() {
+        let (maker, mint_a) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let (escrow, vault) = escrow_accounts(&maker, 42, &mint_a, &spl_token::ID);
+        assert_eq!(
+            escrow,
+            Pubkey::find_program_address(
+                &[b"escrow", maker.as_ref(), &42u64.to_le_bytes()],
+                &blueshift_anchor_escrow::ID
+            )
+            .0
+        );
+        assert_eq!(vault, get_associated_token_address(&escrow, &mint_a));
+    }
+
+    #[test]
+    fn lists_escrows_with_one_byte_discriminator() {
+        let original = escrow(Pubkey::new_unique());
+        let mut data = Vec::new();
+        original.try_serialize(&mut data).unwrap();
+        assert_eq!(data[0], Escrow::DISCRIMINATOR[0]);
+
+        // 和 escrows_by_maker 一样从 RPC 返回的完整账户数据反序列化
+        let decoded = Escrow::try_deserialize(&mut data.as_slice()).unwrap();
+        let address = Pubkey::new_unique();
+        assert_eq!(
+            describe(&address, &decoded),
+            format!(
+                "{address} seed=7 offers 600 of {} for 500 of {} (deposited 1000, expires never, PartiallyFilled)",
+                original.mint_a, original.mint_b
+            )
+        );
+
+        let sol = escrow(Pubkey::default());
+        assert!(describe(&address, &sol).contains("for 500 lamports"));
+    }
+
+    #[test]
+    fn links_to_the_right_cluster() {
+        assert_eq!(
+            explorer_link("tx/abc", "https://api.devnet.solana.com"),
+            "https://explorer.solana.com/tx/abc?cluster=devnet"
+        );
+        assert_eq!(
+            explorer_link("address/abc", "https://api.mainnet-beta.solana.com"),
+            "https://explorer.solana.com/address/abc"
+        );
+        assert_eq!(
+            explorer_link("tx/abc", "http://127.0.0.1:8899"),
+            "https://explorer.solana.com/tx/abc?cluster=custom&customUrl=http%3A%2F%2F127.0.0.1%3A8899"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// 需要本地验证节点并在声明的程序 ID 上部署托管程序, 协议配置由 escrow-sdk 的 localnet 测试或 TS 测试初始化
+// 通过 `cargo test -p escrow-cli --features localnet` 运行
+#![cfg(feature = "localnet")]
+
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{program_pack::Pack, system_instruction},
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address, spl_associated_token_account::instruction as ata_instruction,
+    },
+    token::spl_token,
+};
+use blueshift_anchor_escrow::pda;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{write_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use std::{path::Path, process::Command};
+
+const RPC_URL: &str = "http://127.0.0.1:8899";
+
+fn send(rpc: &RpcClient, ixs: &[Instruction], payer: &Keypair, signers: &[&Keypair]) {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&payer.pubkey()),
+        &all_signers,
+        rpc.get_latest_blockhash().unwrap(),
+    );
+    rpc.send_and_confirm_transaction(&tx).unwrap();
+}
+
+// 创建 mint 并给 owner 的 ATA 铸造 amount 个 token, 铸币权限属于 owner
+fn funded_mint(rpc: &RpcClient, owner: &Keypair, amount: u64) -> Pubkey {
+    let mint = Keypair::new();
+    let ixs = [
+        system_instruction::create_account(
+            &owner.pubkey(),
+            &mint.pubkey(),
+            rpc.get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+                .unwrap(),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &owner.pubkey(),
+            None,
+            6,
+        )
+        .unwrap(),
+        ata_instruction::create_associated_token_account(
+            &owner.pubkey(),
+            &owner.pubkey(),
+            &mint.pubkey(),
+            &spl_token::ID,
+        ),
+        spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &get_associated_token_address(&owner.pubkey(), &mint.pubkey()),
+            &owner.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap(),
+    ];
+    send(rpc, &ixs, owner, &[&mint]);
+    mint.pubkey()
+}
+
+// 运行 escrow 命令, 返回退出状态和标准输出
+fn escrow(keypair: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_escrow"))
+        .args(["--url", RPC_URL, "--keypair", keypair.to_str().unwrap()])
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap() + &String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn make_list_and_refund_from_the_command_line() {
+    let rpc = RpcClient::new_with_commitment(RPC_URL.to_string(), CommitmentConfig::confirmed());
+    let maker = Keypair::new();
+    let signature = rpc
+        .request_airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL)
+        .unwrap();
+    while !rpc.confirm_transaction(&signature).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    let keypair = std::env::temp_dir().join(format!("escrow-cli-{}.json", maker.pubkey()));
+    write_keypair_file(&maker, &keypair).unwrap();
+
+    let mint_a = funded_mint(&rpc, &maker, 1_000);
+    let mint_b = funded_mint(&rpc, &maker, 0);
+    let (escrow_address, _) = pda::find_escrow_address(&maker.pubkey(), 9);
+
+    let (ok, output) = escrow(
+        &keypair,
+        &[
+            "make",
+            "--mint-a",
+            &mint_a.to_string(),
+            "--mint-b",
+            &mint_b.to_string(),
+            "--amount",
+            "1000",
+            "--receive",
+            "500",
+            "--seed",
+            "9",
+        ],
+    );
+    assert!(ok, "{output}");
+    assert!(output.contains(&format!("escrow: {escrow_address}")));
+    assert!(output.contains("https://explorer.solana.com/tx/"));
+
+    let (ok, output) = escrow(&keypair, &["list"]);
+    assert!(ok, "{output}");
+    assert!(output.contains(&format!("{escrow_address} seed=9 offers 1000 of {mint_a}")));
+
+    // maker 不能成交自己的托管, 程序错误被解码为可读的信息
+    let (ok, output) = escrow(&keypair, &["take", &escrow_address.to_string()]);
+    assert!(!ok);
+    assert!(
+        output.contains("Maker cannot take their own escrow"),
+        "{output}"
+    );
+
+    let (ok, output) = escrow(&keypair, &["refund", &escrow_address.to_string()]);
+    assert!(ok, "{output}");
+    assert!(rpc.get_account(&escrow_address).is_err());
+
+    std::fs::remove_file(keypair).unwrap();
+}
@@ -0,0 +1,24 @@
+// 长时间运行的模糊测试: `fuzz_escrow [runs] [steps]`, 每一轮使用新的随机 seed 和新的验证节点
+// 失败时 panic 信息中包含 seed, 可以用 `escrow_fuzz::run(seed, steps)` 复现
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let runs: u64 = args
+        .next()
+        .map_or(100, |runs| runs.parse().expect("runs must be a number"));
+    let steps: usize = args
+        .next()
+        .map_or(200, |steps| steps.parse().expect("steps must be a number"));
+    let base = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the unix epoch")
+        .as_secs();
+
+    for run in 0..runs {
+        let seed = base.wrapping_add(run);
+        println!("run {run}: seed {seed}, {steps} steps");
+        escrow_fuzz::run(seed, steps).await;
+    }
+}
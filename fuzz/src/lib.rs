@@ -0,0 +1,551 @@
+// 随机生成 make / take / refund 的指令序列, 每笔交易之后检查全局不变量
+// 一部分指令会被替换成恶意的账户组合(冒充 maker, 换掉 mint, 把 token 转入攻击者的 ATA), 这些交易必须失败
+// 需要 sbf 版本的托管程序: 先 `anchor build`, 再设置 SBF_OUT_DIR 指向 target/deploy 运行
+
+use anchor_lang::{
+    solana_program::{instruction::Instruction, program_pack::Pack, system_instruction},
+    system_program, AccountDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        self, get_associated_token_address,
+        spl_associated_token_account::instruction as ata_instruction,
+    },
+    memo,
+    token::spl_token,
+};
+use blueshift_anchor_escrow::{accounts, instruction, pda, state::Escrow, ID};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+// 每个钱包初始持有的 token A 和 token B
+const INITIAL_BALANCE: u64 = 1_000;
+// seed 的取值范围很小, 让同一个地址反复被创建, 成交和退还
+pub const SEEDS: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Actor {
+    Alice,
+    Bob,
+    Mallory,
+}
+
+const ACTORS: [Actor; 3] = [Actor::Alice, Actor::Bob, Actor::Mallory];
+
+impl Actor {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    // 固定地选出另一个钱包, 用来冒充 maker 或接收被转走的 token
+    fn other(self) -> Actor {
+        ACTORS[(self.index() + 1) % ACTORS.len()]
+    }
+}
+
+// 对正常账户组合的恶意替换
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    None,
+    // 用另一个钱包冒充托管的 maker
+    WrongMaker,
+    // 把 token B 换成 token A(refund 把 token A 换成 token B), 其他账户也按换过的 mint 派生
+    WrongMint,
+    // 把应该转给 maker 的 token 转入另一个钱包的 ATA
+    AttackerAta,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Make {
+        maker: Actor,
+        seed: u64,
+        amount: u64,
+        receive: u64,
+    },
+    Take {
+        taker: Actor,
+        maker: Actor,
+        seed: u64,
+        mutation: Mutation,
+    },
+    Refund {
+        maker: Actor,
+        seed: u64,
+        mutation: Mutation,
+    },
+}
+
+impl Action {
+    // 数量可能为 0 或超过余额, 这样的交易应该失败, 同样需要满足不变量
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let actor = |rng: &mut dyn rand::RngCore| ACTORS[rng.gen_range(0..ACTORS.len())];
+        let mutation = |rng: &mut dyn rand::RngCore| match rng.gen_range(0..6) {
+            0 => Mutation::WrongMaker,
+            1 => Mutation::WrongMint,
+            2 => Mutation::AttackerAta,
+            _ => Mutation::None,
+        };
+
+        match rng.gen_range(0..3) {
+            0 => Action::Make {
+                maker: actor(rng),
+                seed: rng.gen_range(0..SEEDS),
+                amount: rng.gen_range(0..=INITIAL_BALANCE / 2),
+                receive: rng.gen_range(0..=INITIAL_BALANCE / 2),
+            },
+            1 => Action::Take {
+                taker: actor(rng),
+                maker: actor(rng),
+                seed: rng.gen_range(0..SEEDS),
+                mutation: mutation(rng),
+            },
+            _ => Action::Refund {
+                maker: actor(rng),
+                seed: rng.gen_range(0..SEEDS),
+                mutation: mutation(rng),
+            },
+        }
+    }
+
+    fn mutation(&self) -> Mutation {
+        match self {
+            Action::Make { .. } => Mutation::None,
+            Action::Take { mutation, .. } | Action::Refund { mutation, .. } => *mutation,
+        }
+    }
+}
+
+// 同一个 seed 总是生成同一个序列, 失败时用打印出的 seed 复现
+pub fn actions(seed: u64, steps: usize) -> Vec<Action> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..steps).map(|_| Action::random(&mut rng)).collect()
+}
+
+pub struct Harness {
+    ctx: ProgramTestContext,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    wallets: [Keypair; 3],
+    // 已发送的交易数, 作为计算单元价格写入交易, 防止相同的指令在同一个 blockhash 内被去重
+    sent: u64,
+}
+
+impl Harness {
+    // 三个钱包各持有 INITIAL_BALANCE 个 token A 和 token B, 手续费为 0, 因此两种 token 的总量都守恒
+    pub async fn new() -> Self {
+        let program_test = ProgramTest::new("blueshift_anchor_escrow", ID, None);
+        let ctx = program_test.start_with_context().await;
+        let mut harness = Harness {
+            ctx,
+            mint_a: Pubkey::default(),
+            mint_b: Pubkey::default(),
+            wallets: [Keypair::new(), Keypair::new(), Keypair::new()],
+            sent: 0,
+        };
+
+        harness.mint_a = harness.create_mint().await;
+        harness.mint_b = harness.create_mint().await;
+        for actor in ACTORS {
+            let wallet = harness.wallet(actor).pubkey();
+            let payer = harness.ctx.payer.pubkey();
+            let mut ixs = vec![system_instruction::transfer(
+                &payer,
+                &wallet,
+                LAMPORTS_PER_SOL,
+            )];
+            for mint in [harness.mint_a, harness.mint_b] {
+                ixs.push(ata_instruction::create_associated_token_account(
+                    &payer,
+                    &wallet,
+                    &mint,
+                    &spl_token::ID,
+                ));
+                ixs.push(
+                    spl_token::instruction::mint_to(
+                        &spl_token::ID,
+                        &mint,
+                        &get_associated_token_address(&wallet, &mint),
+                        &payer,
+                        &[],
+                        INITIAL_BALANCE,
+                    )
+                    .unwrap(),
+                );
+            }
+            harness.send(ixs, &[]).await.unwrap();
+        }
+
+        let initialize_config = ix(
+            accounts::InitializeConfig {
+                admin: harness.ctx.payer.pubkey(),
+                config: pda::find_config_address().0,
+                fee_authority: pda::find_fee_authority_address().0,
+                system_program: system_program::ID,
+            },
+            instruction::InitializeConfig { fee_bps: 0 },
+        );
+        harness.send(vec![initialize_config], &[]).await.unwrap();
+
+        harness
+    }
+
+    // 执行一个动作, 返回交易是否成功; 带有恶意替换的交易成功时 panic, context 写入 panic 信息
+    pub async fn apply(&mut self, action: Action, context: &str) -> bool {
+        let (instruction, signer) = self.instruction(action);
+        let signer = self.wallet(signer).insecure_clone();
+        let succeeded = self.send(vec![instruction], &[&signer]).await.is_ok();
+
+        assert!(
+            !(succeeded && action.mutation() != Mutation::None),
+            "{context}: malicious account set was accepted"
+        );
+        succeeded
+    }
+
+    // 每笔交易之后的全局不变量
+    pub async fn check_invariants(&mut self, context: &str) {
+        // token 只在钱包的 ATA 和 vault 之间流动, 总量不变
+        let owners: Vec<Pubkey> = ACTORS
+            .iter()
+            .map(|actor| self.wallet(*actor).pubkey())
+            .chain(self.escrow_addresses())
+            .collect();
+        for (mint, name) in [(self.mint_a, "token A"), (self.mint_b, "token B")] {
+            let mut total = 0;
+            for owner in &owners {
+                total += self
+                    .token_amount(&get_associated_token_address(owner, &mint))
+                    .await
+                    .unwrap_or(0);
+            }
+            assert_eq!(
+                total,
+                INITIAL_BALANCE * ACTORS.len() as u64,
+                "{context}: {name} is not conserved"
+            );
+        }
+
+        // escrow 和 vault 同时存在或同时关闭, vault 中的 token A 就是托管出售的数量
+        for escrow in self.escrow_addresses() {
+            let state = self.escrow(&escrow).await;
+            let vault = self
+                .token_amount(&get_associated_token_address(&escrow, &self.mint_a))
+                .await;
+            match (state, vault) {
+                (Some(state), Some(vault)) => assert_eq!(
+                    state.amount, vault,
+                    "{context}: vault of escrow {escrow} does not hold the escrowed amount"
+                ),
+                (None, None) => {}
+                (Some(_), None) => panic!("{context}: escrow {escrow} exists without a vault"),
+                (None, Some(_)) => {
+                    panic!("{context}: vault of escrow {escrow} survived the escrow")
+                }
+            }
+        }
+    }
+
+    fn wallet(&self, actor: Actor) -> &Keypair {
+        &self.wallets[actor.index()]
+    }
+
+    fn escrow_address(&self, maker: Actor, seed: u64) -> Pubkey {
+        pda::find_escrow_address(&self.wallet(maker).pubkey(), seed).0
+    }
+
+    fn escrow_addresses(&self) -> Vec<Pubkey> {
+        ACTORS
+            .iter()
+            .flat_map(|maker| (0..SEEDS).map(|seed| self.escrow_address(*maker, seed)))
+            .collect()
+    }
+
+    // 构造动作对应的指令和签名的钱包
+    fn instruction(&self, action: Action) -> (Instruction, Actor) {
+        let ata = |owner: &Pubkey, mint: &Pubkey| get_associated_token_address(owner, mint);
+
+        match action {
+            Action::Make {
+                maker,
+                seed,
+                amount,
+                receive,
+            } => {
+                let escrow = self.escrow_address(maker, seed);
+                let maker_key = self.wallet(maker).pubkey();
+                let accounts = accounts::Make {
+                    maker: maker_key,
+                    rent_payer: None,
+                    escrow,
+                    mint_a: self.mint_a,
+                    mint_b: self.mint_b,
+                    maker_ata_a: Some(ata(&maker_key, &self.mint_a)),
+                    vault: ata(&escrow, &self.mint_a),
+                    config: pda::find_config_address().0,
+                    mint_allowlist: pda::find_mint_allowlist_address().0,
+                    mint_blocklist: pda::find_mint_blocklist_address().0,
+                    exempt_list: None,
+                    treasury: None,
+                    stats: pda::find_stats_address().0,
+                    registry: pda::find_registry_address(&maker_key).0,
+                    associated_token_program: associated_token::ID,
+                    token_program_a: spl_token::ID,
+                    token_program_b: spl_token::ID,
+                    system_program: system_program::ID,
+                    event_authority: event_authority(),
+                    program: ID,
+                };
+                let args = instruction::Make {
+                    seed,
+                    receive,
+                    amount,
+                    expiry: 0,
+                    allowed_taker: Pubkey::default(),
+                    taker_allowlist_root: [0; 32],
+                    hashlock: [0; 32],
+                    start_time: 0,
+                    reject_freezable: false,
+                    receive_to: Pubkey::default(),
+                    refund_delegate: Pubkey::default(),
+                    commit_delay_slots: 0,
+                    arbiter: Pubkey::default(),
+                    reservation_window: 0,
+                    alt_payments: vec![],
+                    recurring: false,
+                    max_refills: 0,
+                };
+                (ix(accounts, args), maker)
+            }
+            Action::Take {
+                taker,
+                maker,
+                seed,
+                mutation,
+            } => {
+                let escrow = self.escrow_address(maker, seed);
+                let taker_key = self.wallet(taker).pubkey();
+                let impostor = match mutation {
+                    Mutation::WrongMaker => maker.other(),
+                    _ => maker,
+                };
+                let maker_key = self.wallet(impostor).pubkey();
+                let mint_b = match mutation {
+                    Mutation::WrongMint => self.mint_a,
+                    _ => self.mint_b,
+                };
+                let maker_ata_b = match mutation {
+                    Mutation::AttackerAta => ata(&self.wallet(maker.other()).pubkey(), &mint_b),
+                    _ => ata(&maker_key, &mint_b),
+                };
+                let accounts = accounts::Take {
+                    taker: taker_key,
+                    payer: taker_key,
+                    maker: maker_key,
+                    rent_payer: maker_key,
+                    receive_to: maker_key,
+                    escrow,
+                    mint_a: self.mint_a,
+                    mint_b,
+                    vault: ata(&escrow, &self.mint_a),
+                    taker_ata_a: Some(ata(&taker_key, &self.mint_a)),
+                    taker_token_a: None,
+                    maker_ata_a: ata(&maker_key, &self.mint_a),
+                    taker_ata_b: ata(&taker_key, &mint_b),
+                    maker_ata_b: Some(maker_ata_b),
+                    unwrap_ata_b: None,
+                    config: pda::find_config_address().0,
+                    mint_blocklist: pda::find_mint_blocklist_address().0,
+                    stats: pda::find_stats_address().0,
+                    taker_stats: None,
+                    maker_stats: None,
+                    fee_authority: pda::find_fee_authority_address().0,
+                    fee_vault_b: None,
+                    referrer_ata_b: None,
+                    receipt: None,
+                    registry: pda::find_registry_address(&maker_key).0,
+                    pair_index: None,
+                    price_feed: None,
+                    instructions_sysvar: None,
+                    associated_token_program: Some(associated_token::ID),
+                    token_program_a: spl_token::ID,
+                    token_program_b: spl_token::ID,
+                    memo_program: memo::ID,
+                    system_program: Some(system_program::ID),
+                    event_authority: event_authority(),
+                    program: ID,
+                };
+                let args = instruction::Take {
+                    max_receive: u64::MAX,
+                    expected_amount_a: 0,
+                    proof: vec![],
+                    preimage: vec![],
+                    write_receipt: false,
+                };
+                (ix(accounts, args), taker)
+            }
+            Action::Refund {
+                maker,
+                seed,
+                mutation,
+            } => {
+                let escrow = self.escrow_address(maker, seed);
+                let signer = match mutation {
+                    Mutation::WrongMaker => maker.other(),
+                    _ => maker,
+                };
+                let signer_key = self.wallet(signer).pubkey();
+                let mint_a = match mutation {
+                    Mutation::WrongMint => self.mint_b,
+                    _ => self.mint_a,
+                };
+                let maker_ata_a = match mutation {
+                    Mutation::AttackerAta => ata(&self.wallet(maker.other()).pubkey(), &mint_a),
+                    _ => ata(&signer_key, &mint_a),
+                };
+                let accounts = accounts::Refund {
+                    authority: signer_key,
+                    maker: signer_key,
+                    rent_payer: self.wallet(maker).pubkey(),
+                    escrow,
+                    mint_a,
+                    vault: ata(&escrow, &mint_a),
+                    maker_ata_a,
+                    stats: pda::find_stats_address().0,
+                    registry: pda::find_registry_address(&signer_key).0,
+                    pair_index: None,
+                    associated_token_program: associated_token::ID,
+                    token_program: spl_token::ID,
+                    memo_program: memo::ID,
+                    system_program: system_program::ID,
+                    event_authority: event_authority(),
+                    program: ID,
+                };
+                (ix(accounts, instruction::Refund { force: false }), signer)
+            }
+        }
+    }
+
+    async fn send(
+        &mut self,
+        mut ixs: Vec<Instruction>,
+        signers: &[&Keypair],
+    ) -> Result<(), solana_program_test::BanksClientError> {
+        self.sent += 1;
+        ixs.insert(
+            0,
+            ComputeBudgetInstruction::set_compute_unit_price(self.sent),
+        );
+
+        let mut all_signers = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&self.ctx.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    async fn create_mint(&mut self) -> Pubkey {
+        let mint = Keypair::new();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let payer = self.ctx.payer.pubkey();
+        let ixs = vec![
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &payer,
+                None,
+                6,
+            )
+            .unwrap(),
+        ];
+        self.send(ixs, &[&mint]).await.unwrap();
+        mint.pubkey()
+    }
+
+    async fn escrow(&mut self, address: &Pubkey) -> Option<Escrow> {
+        let account = self.ctx.banks_client.get_account(*address).await.unwrap()?;
+        Some(Escrow::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    async fn token_amount(&mut self, address: &Pubkey) -> Option<u64> {
+        let account = self.ctx.banks_client.get_account(*address).await.unwrap()?;
+        Some(
+            spl_token::state::Account::unpack(&account.data)
+                .unwrap()
+                .amount,
+        )
+    }
+}
+
+// 执行 steps 个随机动作, 违反不变量时 panic 并打印复现用的 seed 和动作
+pub async fn run(seed: u64, steps: usize) {
+    let mut harness = Harness::new().await;
+
+    for (step, action) in actions(seed, steps).into_iter().enumerate() {
+        let context = format!("seed {seed}, step {step}, {action:?}");
+        harness.apply(action, &context).await;
+        harness.check_invariants(&context).await;
+    }
+}
+
+fn ix(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &ID).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequences_are_reproducible() {
+        assert_eq!(actions(7, 50), actions(7, 50));
+        assert_ne!(actions(7, 50), actions(8, 50));
+    }
+
+    #[test]
+    fn sequences_cover_every_action_and_mutation() {
+        let actions = actions(1, 500);
+
+        assert!(actions.iter().any(|a| matches!(a, Action::Make { .. })));
+        assert!(actions.iter().any(|a| matches!(a, Action::Take { .. })));
+        assert!(actions.iter().any(|a| matches!(a, Action::Refund { .. })));
+        for mutation in [
+            Mutation::None,
+            Mutation::WrongMaker,
+            Mutation::WrongMint,
+            Mutation::AttackerAta,
+        ] {
+            assert!(actions.iter().any(|a| a.mutation() == mutation));
+        }
+        assert!(actions
+            .iter()
+            .all(|a| !matches!(a, Action::Make { seed, .. } if *seed >= SEEDS)));
+    }
+}
@@ -0,0 +1,10 @@
+// 需要 sbf 版本的托管程序, 通过 `cargo test-sbf` 运行; 更长的运行使用 fuzz_escrow
+#![cfg(feature = "test-sbf")]
+
+// 几个固定的 seed, 每次运行的序列相同, CI 中保持稳定
+#[tokio::test]
+async fn short_random_sequences_keep_invariants() {
+    for seed in [1, 2, 3] {
+        escrow_fuzz::run(seed, 40).await;
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{
+    errors::EscrowError,
+    state::{Config, Escrow},
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+};
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
+
+// direct_only 的托管只能由交易的顶层指令成交, 防止其他程序把成交包在自己的指令中前后夹击
+// 通过 CPI 调用时托管程序必须是顶层指令的直接被调用者, 从 instructions sysvar 读取顶层指令, 它的程序在 Config::trusted_routers 中时才放行;
+// 更深的嵌套无法确认中间的程序, 即使顶层是可信路由也拒绝. 不传 instructions sysvar 时只允许顶层调用
+pub fn check_direct_call(
+    escrow: &Escrow,
+    config: &Config,
+    instructions: Option<&AccountInfo>,
+) -> Result<()> {
+    let stack_height = get_stack_height();
+    if !escrow.direct_only || stack_height == TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(());
+    }
+    require!(
+        stack_height == TRANSACTION_LEVEL_STACK_HEIGHT + 1,
+        EscrowError::CpiNotAllowed
+    );
+
+    let instructions = instructions.ok_or(EscrowError::CpiNotAllowed)?;
+    let current = load_current_index_checked(instructions)? as usize;
+    let caller = load_instruction_at_checked(current, instructions)?.program_id;
+    require!(
+        config.is_trusted_router(&caller),
+        EscrowError::CpiNotAllowed
+    );
+
+    Ok(())
+}
@@ -0,0 +1,463 @@
+use crate::{
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_ALLOWLIST_SEED, MINT_BLOCKLIST_SEED,
+        REGISTRY_SEED, STATS_SEED,
+    },
+    state::PaymentMint,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::instruction::{AccountMeta, Instruction},
+};
+use anchor_spl::{associated_token, memo, token::spl_token};
+
+// 不依赖 anchor 客户端的指令构造函数, 给只使用 solana-sdk 的机器人使用
+// 只覆盖最常见的用法: 两个 mint 都由 SPL Token 管理, maker 自己支付租金并接收 token B, 不收取协议手续费
+// 其他情况(Token-2022, relayer, referrer 等)使用 anchor 生成的 accounts 和 instruction 模块构造
+
+// mainnet, devnet 或 localnet feature 选中的程序 ID, 部署在对应集群上时作为 program_id 传入
+pub use crate::ID;
+
+// 和 lib.rs 中指令的 discriminator 一致
+const MAKE_DISCRIMINATOR: u8 = 0;
+const TAKE_DISCRIMINATOR: u8 = 1;
+const REFUND_DISCRIMINATOR: u8 = 2;
+
+// 托管 PDA 地址, 种子中的 seed 使用小端字节序
+pub fn escrow_pda(program_id: &Pubkey, maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[ESCROW_SEED, maker.as_ref(), &seed.to_le_bytes()],
+        program_id,
+    )
+    .0
+}
+
+// 托管资金的 ATA 地址
+pub fn vault_ata(escrow: &Pubkey, mint_a: &Pubkey) -> Pubkey {
+    associated_token::get_associated_token_address(escrow, mint_a)
+}
+
+fn config_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id).0
+}
+
+fn fee_authority_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[FEE_AUTHORITY_SEED], program_id).0
+}
+
+fn stats_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[STATS_SEED], program_id).0
+}
+
+fn mint_allowlist_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[MINT_ALLOWLIST_SEED], program_id).0
+}
+
+fn mint_blocklist_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[MINT_BLOCKLIST_SEED], program_id).0
+}
+
+fn registry_pda(program_id: &Pubkey, maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[REGISTRY_SEED, maker.as_ref()], program_id).0
+}
+
+// #[event_cpi] 记录事件使用的 PDA
+fn event_authority_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], program_id).0
+}
+
+// 指令数据: 1 字节的 discriminator 加上 borsh 序列化的参数
+fn instruction_data(discriminator: u8, args: impl AnchorSerialize) -> Vec<u8> {
+    let mut data = vec![discriminator];
+    args.serialize(&mut data)
+        .expect("serializing into a Vec cannot fail");
+    data
+}
+
+// anchor 用程序自己的地址表示没有传入的 Option 账户
+fn none(program_id: &Pubkey) -> AccountMeta {
+    AccountMeta::new_readonly(*program_id, false)
+}
+
+// 固定价格的托管, 没有过期时间和 taker 限制; 不传挂单费账户, 收取挂单费时会失败
+pub fn make_ix(
+    program_id: Pubkey,
+    maker: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    seed: u64,
+    receive: u64,
+    amount: u64,
+) -> Instruction {
+    let escrow = escrow_pda(&program_id, &maker, seed);
+
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(maker, true),
+            AccountMeta::new(maker, true), // rent_payer
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(mint_a, false),
+            AccountMeta::new_readonly(mint_b, false),
+            AccountMeta::new(
+                associated_token::get_associated_token_address(&maker, &mint_a),
+                false,
+            ),
+            AccountMeta::new(vault_ata(&escrow, &mint_a), false),
+            AccountMeta::new_readonly(config_pda(&program_id), false),
+            AccountMeta::new_readonly(mint_allowlist_pda(&program_id), false),
+            AccountMeta::new_readonly(mint_blocklist_pda(&program_id), false),
+            none(&program_id), // exempt_list
+            none(&program_id), // treasury
+            AccountMeta::new(stats_pda(&program_id), false),
+            AccountMeta::new(registry_pda(&program_id, &maker), false),
+            AccountMeta::new_readonly(associated_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(event_authority_pda(&program_id), false),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+        data: instruction_data(
+            MAKE_DISCRIMINATOR,
+            (
+                seed,
+                receive,
+                amount,
+                0i64,                      // expiry
+                Pubkey::default(),         // allowed_taker
+                [0u8; 32],                 // taker_allowlist_root
+                [0u8; 32],                 // hashlock
+                0i64,                      // start_time
+                false,                     // reject_freezable
+                Pubkey::default(),         // receive_to
+                Pubkey::default(),         // refund_delegate
+                0u64,                      // commit_delay_slots
+                Pubkey::default(),         // arbiter
+                0i64,                      // reservation_window
+                Vec::<PaymentMint>::new(), // alt_payments
+                false,                     // recurring
+                0u8,                       // max_refills
+            ),
+        ),
+    }
+}
+
+// 成交没有 allowlist 和 hashlock 的托管, taker 自己支付 ATA 租金
+// max_receive 和 expected_amount_a 的含义和 take 指令相同; 不传成交统计, 启用手续费档位时会失败
+#[allow(clippy::too_many_arguments)]
+pub fn take_ix(
+    program_id: Pubkey,
+    taker: Pubkey,
+    maker: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    seed: u64,
+    max_receive: u64,
+    expected_amount_a: u64,
+) -> Instruction {
+    let escrow = escrow_pda(&program_id, &maker, seed);
+
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(taker, true),
+            AccountMeta::new(taker, true), // payer
+            AccountMeta::new(maker, false),
+            AccountMeta::new(maker, false), // rent_payer
+            AccountMeta::new(maker, false), // receive_to
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(mint_a, false),
+            AccountMeta::new_readonly(mint_b, false),
+            AccountMeta::new(vault_ata(&escrow, &mint_a), false),
+            AccountMeta::new(
+                associated_token::get_associated_token_address(&taker, &mint_a),
+                false,
+            ),
+            none(&program_id), // taker_token_a
+            AccountMeta::new(
+                associated_token::get_associated_token_address(&maker, &mint_a),
+                false,
+            ),
+            AccountMeta::new(
+                associated_token::get_associated_token_address(&taker, &mint_b),
+                false,
+            ),
+            AccountMeta::new(
+                associated_token::get_associated_token_address(&maker, &mint_b),
+                false,
+            ),
+            none(&program_id), // unwrap_ata_b
+            AccountMeta::new_readonly(config_pda(&program_id), false),
+            AccountMeta::new_readonly(mint_blocklist_pda(&program_id), false),
+            AccountMeta::new(stats_pda(&program_id), false),
+            none(&program_id), // taker_stats
+            none(&program_id), // maker_stats
+            AccountMeta::new_readonly(fee_authority_pda(&program_id), false),
+            none(&program_id), // fee_vault_b
+            none(&program_id), // referrer_ata_b
+            none(&program_id), // receipt
+            AccountMeta::new(registry_pda(&program_id, &maker), false),
+            none(&program_id), // pair_index
+            none(&program_id), // price_feed
+            none(&program_id), // instructions_sysvar
+            AccountMeta::new_readonly(associated_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(memo::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(event_authority_pda(&program_id), false),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+        data: instruction_data(
+            TAKE_DISCRIMINATOR,
+            (
+                max_receive,
+                expected_amount_a,
+                Vec::<[u8; 32]>::new(), // proof
+                Vec::<u8>::new(),       // preimage
+                false,                  // write_receipt
+            ),
+        ),
+    }
+}
+
+// 退还 maker 自己支付租金的托管, vault 被冻结时失败(force 为 false)
+pub fn refund_ix(program_id: Pubkey, maker: Pubkey, mint_a: Pubkey, seed: u64) -> Instruction {
+    let escrow = escrow_pda(&program_id, &maker, seed);
+
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(maker, true), // authority
+            AccountMeta::new_readonly(maker, false),
+            AccountMeta::new(maker, false), // rent_payer
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(mint_a, false),
+            AccountMeta::new(vault_ata(&escrow, &mint_a), false),
+            AccountMeta::new(
+                associated_token::get_associated_token_address(&maker, &mint_a),
+                false,
+            ),
+            AccountMeta::new(stats_pda(&program_id), false),
+            AccountMeta::new(registry_pda(&program_id, &maker), false),
+            none(&program_id), // pair_index
+            AccountMeta::new_readonly(associated_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(memo::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(event_authority_pda(&program_id), false),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+        data: instruction_data(REFUND_DISCRIMINATOR, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accounts, instruction};
+    use anchor_lang::InstructionData;
+
+    // 用 anchor 生成的 accounts 和 instruction 构造同一条指令
+    fn anchor_ix(
+        program_id: Pubkey,
+        accounts: impl ToAccountMetas,
+        data: impl InstructionData,
+    ) -> Instruction {
+        Instruction {
+            program_id,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+
+    fn ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        associated_token::get_associated_token_address(owner, mint)
+    }
+
+    // anchor 生成的 accounts 用 crate::ID 表示没有传入的 Option 账户, 因此比较时使用相同的程序地址
+    fn program_id() -> Pubkey {
+        crate::ID
+    }
+
+    #[test]
+    fn escrow_pda_uses_little_endian_seed() {
+        let program_id = Pubkey::new_unique();
+        let maker = Pubkey::new_unique();
+        let seed = 0x0102_0304_0506_0708;
+
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"escrow", maker.as_ref(), &[8, 7, 6, 5, 4, 3, 2, 1]],
+            &program_id,
+        );
+        assert_eq!(escrow_pda(&program_id, &maker, seed), expected);
+    }
+
+    #[test]
+    fn make_matches_anchor() {
+        let program_id = program_id();
+        let (maker, mint_a, mint_b) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let escrow = escrow_pda(&program_id, &maker, 42);
+
+        let expected = anchor_ix(
+            program_id,
+            accounts::Make {
+                maker,
+                rent_payer: None,
+                escrow,
+                mint_a,
+                mint_b,
+                maker_ata_a: Some(ata(&maker, &mint_a)),
+                vault: ata(&escrow, &mint_a),
+                config: config_pda(&program_id),
+                mint_allowlist: mint_allowlist_pda(&program_id),
+                mint_blocklist: mint_blocklist_pda(&program_id),
+                exempt_list: None,
+                treasury: None,
+                stats: stats_pda(&program_id),
+                registry: registry_pda(&program_id, &maker),
+                associated_token_program: associated_token::ID,
+                token_program_a: spl_token::ID,
+                token_program_b: spl_token::ID,
+                system_program: system_program::ID,
+                event_authority: event_authority_pda(&program_id),
+                program: program_id,
+            },
+            instruction::Make {
+                seed: 42,
+                receive: 500,
+                amount: 1_000,
+                expiry: 0,
+                allowed_taker: Pubkey::default(),
+                taker_allowlist_root: [0; 32],
+                hashlock: [0; 32],
+                start_time: 0,
+                reject_freezable: false,
+                receive_to: Pubkey::default(),
+                refund_delegate: Pubkey::default(),
+                commit_delay_slots: 0,
+                arbiter: Pubkey::default(),
+                reservation_window: 0,
+                alt_payments: vec![],
+                recurring: false,
+                max_refills: 0,
+            },
+        );
+
+        assert_eq!(
+            make_ix(program_id, maker, mint_a, mint_b, 42, 500, 1_000),
+            expected
+        );
+    }
+
+    #[test]
+    fn take_matches_anchor() {
+        let program_id = program_id();
+        let (taker, maker, mint_a, mint_b) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let escrow = escrow_pda(&program_id, &maker, 42);
+
+        let expected = anchor_ix(
+            program_id,
+            accounts::Take {
+                taker,
+                payer: taker,
+                maker,
+                rent_payer: maker,
+                receive_to: maker,
+                escrow,
+                mint_a,
+                mint_b,
+                vault: ata(&escrow, &mint_a),
+                taker_ata_a: Some(ata(&taker, &mint_a)),
+                taker_token_a: None,
+                maker_ata_a: ata(&maker, &mint_a),
+                taker_ata_b: ata(&taker, &mint_b),
+                maker_ata_b: Some(ata(&maker, &mint_b)),
+                unwrap_ata_b: None,
+                config: config_pda(&program_id),
+                mint_blocklist: mint_blocklist_pda(&program_id),
+                stats: stats_pda(&program_id),
+                taker_stats: None,
+                maker_stats: None,
+                fee_authority: fee_authority_pda(&program_id),
+                fee_vault_b: None,
+                referrer_ata_b: None,
+                receipt: None,
+                registry: registry_pda(&program_id, &maker),
+                pair_index: None,
+                price_feed: None,
+                instructions_sysvar: None,
+                associated_token_program: Some(associated_token::ID),
+                token_program_a: spl_token::ID,
+                token_program_b: spl_token::ID,
+                memo_program: memo::ID,
+                system_program: Some(system_program::ID),
+                event_authority: event_authority_pda(&program_id),
+                program: program_id,
+            },
+            instruction::Take {
+                max_receive: u64::MAX,
+                expected_amount_a: 1_000,
+                proof: Vec::new(),
+                preimage: Vec::new(),
+                write_receipt: false,
+            },
+        );
+
+        assert_eq!(
+            take_ix(
+                program_id,
+                taker,
+                maker,
+                mint_a,
+                mint_b,
+                42,
+                u64::MAX,
+                1_000
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn refund_matches_anchor() {
+        let program_id = program_id();
+        let (maker, mint_a) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let escrow = escrow_pda(&program_id, &maker, 42);
+
+        let expected = anchor_ix(
+            program_id,
+            accounts::Refund {
+                authority: maker,
+                maker,
+                rent_payer: maker,
+                escrow,
+                mint_a,
+                vault: ata(&escrow, &mint_a),
+                maker_ata_a: ata(&maker, &mint_a),
+                stats: stats_pda(&program_id),
+                registry: registry_pda(&program_id, &maker),
+                pair_index: None,
+                associated_token_program: associated_token::ID,
+                token_program: spl_token::ID,
+                memo_program: memo::ID,
+                system_program: system_program::ID,
+                event_authority: event_authority_pda(&program_id),
+                program: program_id,
+            },
+            instruction::Refund { force: false },
+        );
+
+        assert_eq!(refund_ix(program_id, maker, mint_a, 42), expected);
+    }
+}
@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 
-#[error_code]
+// 错误码固定从 6000 开始, 按声明顺序递增; 客户端按数值解码, 新增错误只能追加在末尾, 不能删除或重新排序
+#[error_code(offset = 6000)]
 pub enum EscrowError {
     #[msg("Invalid amount")]
     InvalidAmount,
@@ -10,4 +11,258 @@ pub enum EscrowError {
     InvalidMintA,
     #[msg("Invalid mint b")]
     InvalidMintB,
+    #[msg("Invalid expiry")]
+    InvalidExpiry,
+    #[msg("Escrow has no expiry")]
+    NoExpiry,
+    #[msg("Escrow has not expired yet")]
+    EscrowNotExpired,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Taker is not allowed to take this escrow")]
+    UnauthorizedTaker,
+    #[msg("Mint a and mint b must be different")]
+    IdenticalMints,
+    #[msg("Maker cannot take their own escrow")]
+    SelfTrade,
+    #[msg("Invalid dutch auction parameters")]
+    InvalidDutchAuction,
+    #[msg("Dutch auction escrows cannot be repriced")]
+    DutchAuctionReprice,
+    #[msg("Token B amount exceeds the taker's maximum")]
+    SlippageExceeded,
+    #[msg("Token A amount differs from the taker's expectation")]
+    VaultAmountMismatch,
+    #[msg("Invalid fee")]
+    InvalidFee,
+    #[msg("Invalid admin")]
+    InvalidAdmin,
+    #[msg("Fee vault is required when a fee is charged")]
+    MissingFeeVault,
+    #[msg("Protocol is paused")]
+    ProtocolPaused,
+    #[msg("Invalid pending admin")]
+    InvalidPendingAdmin,
+    #[msg("Taker cannot refer their own trade")]
+    SelfReferral,
+    #[msg("Taker is not on the allowlist")]
+    NotOnAllowlist,
+    #[msg("Invalid counter offer")]
+    InvalidCounterOffer,
+    #[msg("Invalid preimage")]
+    InvalidPreimage,
+    #[msg("Offer has not started yet")]
+    OfferNotStarted,
+    #[msg("Start time must be before the expiry")]
+    InvalidStartTime,
+    #[msg("Seed does not match the maker counter")]
+    InvalidSeed,
+    #[msg("Vault is empty")]
+    EmptyVault,
+    #[msg("Taker does not hold enough token B")]
+    InsufficientTakerBalance,
+    #[msg("Maker does not hold enough token A")]
+    InsufficientMakerBalance,
+    #[msg("Mint is non-transferable")]
+    NonTransferableMint,
+    #[msg("Mint has a freeze authority")]
+    FreezableMintRejected,
+    #[msg("Vault is frozen by the mint's freeze authority")]
+    VaultFrozen,
+    #[msg("Escrow is not paid in SOL")]
+    NotSolEscrow,
+    #[msg("Maker token account is required")]
+    MissingMakerAta,
+    #[msg("Unwrap account is required when token B is native SOL")]
+    MissingUnwrapAccount,
+    #[msg("Token B is not native SOL")]
+    NotNativeMint,
+    #[msg("Invalid rent payer")]
+    InvalidRentPayer,
+    #[msg("Invalid payout wallet")]
+    InvalidReceiveTo,
+    #[msg("Taker token A account is required")]
+    MissingTakerAccount,
+    #[msg("Taker token account must not have a delegate or close authority")]
+    DelegatedTakerAccount,
+    #[msg("Receipt account must be passed exactly when a receipt is requested")]
+    InvalidReceiptAccount,
+    #[msg("Only the maker or the taker can close the receipt")]
+    UnauthorizedReceiptCloser,
+    #[msg("Maker registry is full")]
+    RegistryFull,
+    #[msg("Maker registry still lists open escrows")]
+    RegistryNotEmpty,
+    #[msg("Mint is not on the allowlist")]
+    MintNotAllowed,
+    #[msg("Mint is blocked")]
+    MintBlocked,
+    #[msg("Escrow is frozen by the admin")]
+    EscrowFrozen,
+    #[msg("Escrow is not in a status that allows this instruction")]
+    InvalidEscrowStatus,
+    #[msg("Escrows do not trade opposite sides of the same mint pair")]
+    MintsDoNotMatch,
+    #[msg("Escrow prices do not cross")]
+    PricesDoNotCross,
+    #[msg("Hashlocked or allowlisted escrows cannot be matched")]
+    UnmatchableEscrow,
+    #[msg("Batch is empty or larger than the batch size limit")]
+    InvalidBatchSize,
+    #[msg("Batch accounts do not match the entries in order")]
+    InvalidBatchAccounts,
+    #[msg("Batch contains the same seed twice")]
+    DuplicateBatchSeed,
+    #[msg("Only the maker or its refund delegate can refund")]
+    UnauthorizedRefund,
+    #[msg("Order is not signed by the taker through the preceding ed25519 instruction")]
+    InvalidOrderSignature,
+    #[msg("Signed order has expired")]
+    OrderExpired,
+    #[msg("Taker has not delegated enough token B to the program")]
+    InsufficientDelegation,
+    #[msg("Escrow requires commit_take and reveal_take")]
+    CommitRequired,
+    #[msg("Revealed salt does not match the commit")]
+    CommitMismatch,
+    #[msg("Commit delay has not elapsed")]
+    CommitTooEarly,
+    #[msg("Commit delay is larger than the limit")]
+    InvalidCommitDelay,
+    #[msg("Arbiter must be a third party and cannot be combined with commit-reveal or a hashlock")]
+    InvalidArbiter,
+    #[msg("Escrow with an arbiter can only be taken through lock_take")]
+    ArbiterRequired,
+    #[msg("Only the arbiter or both parties together can release a locked escrow")]
+    UnauthorizedRelease,
+    #[msg("Reservation window is larger than the limit or cannot be combined with an arbiter or commit-reveal")]
+    InvalidReservationWindow,
+    #[msg("Escrow does not accept reservations")]
+    ReservationsDisabled,
+    #[msg("Escrow is reserved by a taker")]
+    EscrowReserved,
+    #[msg("Escrow has no reservation")]
+    NotReserved,
+    #[msg("Reservation bond must be greater than zero")]
+    InvalidBond,
+    #[msg("Reservation has expired")]
+    ReservationExpired,
+    #[msg("Reservation has not expired yet")]
+    ReservationNotExpired,
+    #[msg("Payment mints must be distinct, priced above zero and at most three besides mint_b")]
+    InvalidPaymentMints,
+    #[msg("Basket must hold between one and four mints")]
+    InvalidBasketSize,
+    #[msg("Basket accounts are missing or out of order")]
+    InvalidBasketAccounts,
+    #[msg("Basket mints must be distinct and differ from mint_b")]
+    DuplicateBasketMint,
+    #[msg("Recurring escrow needs refills, a token account and a fixed price without an arbiter or hashlock")]
+    InvalidRecurring,
+    #[msg("Fee tiers must be at most four, ordered by min_trades with non-increasing fees")]
+    InvalidFeeTiers,
+    #[msg("Taker stats must be passed while fee tiers are enabled")]
+    MissingUserStats,
+    #[msg("Treasury does not match config or is unset while a listing fee is charged")]
+    InvalidTreasury,
+    #[msg("Treasury account must be passed while a listing fee is due")]
+    MissingTreasury,
+    #[msg("Abandon period must not be negative and the cranker share must not exceed 100%")]
+    InvalidAbandonPolicy,
+    #[msg("Escrow has not been abandoned long enough or sweeping is disabled")]
+    EscrowNotAbandoned,
+    #[msg("Escrow account still exists, only orphaned vaults can be recovered")]
+    EscrowNotOrphaned,
+    #[msg("Token account does not belong to the expected owner and mint")]
+    InvalidAtaAccount,
+    #[msg("Associated token and system programs must be passed to create a missing token account")]
+    MissingAtaPrograms,
+    #[msg("Vault does not match the address recorded in the escrow")]
+    InvalidVault,
+    #[msg("Escrow account is not an older layout version that can be migrated")]
+    EscrowNotLegacy,
+    #[msg("Escrow account uses an older layout version, call migrate_escrow first")]
+    MigrationRequired,
+    #[msg("Account is not owned by the expected token program")]
+    WrongTokenProgram,
+    #[msg("Taker token B account does not belong to mint b")]
+    TakerAtaBWrongMint,
+    #[msg("Escrow has expired")]
+    EscrowExpired,
+    #[msg("Invalid oracle pricing parameters")]
+    InvalidOracleConfig,
+    #[msg("Price feed does not match the escrow or is not a verified Pyth price update")]
+    InvalidPriceFeed,
+    #[msg("Oracle price is older than the escrow allows")]
+    StalePrice,
+    #[msg("Oracle price confidence interval is too wide")]
+    PriceConfidenceTooWide,
+    #[msg("Escrow is priced by an oracle and can only be filled through take")]
+    OraclePriced,
+    #[msg("Escrow price deviates from the oracle price by more than the allowed band")]
+    PriceOutOfBand,
+    #[msg("Escrow has an oracle price band and can only be filled through take")]
+    PriceBandRequiresTake,
+    #[msg("Swap program is not the router allowed by the config")]
+    InvalidSwapRouter,
+    #[msg("Swap did not add enough token B to the taker account to pay for the take")]
+    InsufficientSwapOutput,
+    #[msg("Escrow accepts any NFT of a collection and can only be filled through take_collection")]
+    CollectionOffer,
+    #[msg("Escrow does not accept a collection")]
+    NotCollectionOffer,
+    #[msg("Account is not the Metaplex metadata of the NFT mint")]
+    InvalidNftMetadata,
+    #[msg("NFT is not a verified member of the escrow collection")]
+    CollectionNotVerified,
+    #[msg("Mint is not an NFT with zero decimals and a supply of one")]
+    NotAnNft,
+    #[msg("Programmable NFT transfer accounts are missing or do not match the mint")]
+    InvalidPnftAccounts,
+    #[msg("Programmable NFT escrows cannot be recurring or use an arbiter")]
+    UnsupportedPnftEscrow,
+    #[msg("Escrows that enforce royalties can only be filled through take")]
+    RoyaltiesRequireTake,
+    #[msg("Royalty accounts are missing or do not match the verified creators")]
+    InvalidRoyaltyAccounts,
+    #[msg("Royalties are only supported on token escrows without a collection or arbiter")]
+    RoyaltiesUnsupported,
+    #[msg("Reserved argument bytes must be zero")]
+    ReservedArgsNotZero,
+    #[msg("Maker token A balance is empty, nothing to deposit")]
+    EmptyMakerBalance,
+    #[msg("Pair index mode requires the pair index account")]
+    MissingPairIndex,
+    #[msg("Cancel reason code is not a known CancelReason")]
+    InvalidCancelReason,
+    #[msg("Cancelled escrow is still within its retention window")]
+    CancelRetentionActive,
+    #[msg("Direct-only escrow cannot be taken through CPI by an untrusted program")]
+    CpiNotAllowed,
+    #[msg("Direct-only escrow requires the instructions sysvar account")]
+    MissingInstructionsSysvar,
+    #[msg("Trusted routers exceed the limit or include the escrow program")]
+    InvalidTrustedRouters,
+    #[msg("Maker has paused fills for this escrow")]
+    OfferPausedByMaker,
+    #[msg("Stream window must be between 0 and MAX_STREAM_WINDOW")]
+    InvalidStreamWindow,
+    #[msg("Escrow does not allow streaming settlement")]
+    StreamingDisabled,
+    #[msg("Hashlocked escrows cannot be settled in installments")]
+    StreamingUnsupported,
+    #[msg("First installment is below the minimum share of the stream")]
+    InitialPaymentTooSmall,
+    #[msg("Stream deadline has passed")]
+    StreamDeadlinePassed,
+    #[msg("Stream is still active until its deadline")]
+    StreamActive,
+    #[msg("Operators exceed the limit")]
+    InvalidOperators,
+    #[msg("Escrow does not allow operator refunds")]
+    OperatorsNotAllowed,
+    #[msg("Signer is not a registered operator or an operator program's PDA")]
+    UnauthorizedOperator,
+    #[msg("Recurring escrows can only be filled through take")]
+    RecurringRequiresTake,
 }
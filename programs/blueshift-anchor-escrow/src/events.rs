@@ -0,0 +1,368 @@
+use crate::state::{CancelReason, EscrowStatus};
+use anchor_lang::prelude::*;
+
+// 创建托管时触发
+#[event]
+pub struct MakeEvent {
+    pub escrow: Pubkey,
+    // 指令执行后托管的状态, 托管被关闭时为关闭前的状态
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // maker 转出的 token A 数量
+    pub amount: u64,
+    // vault 实际收到的 token A 数量, mint A 带有 TransferFee 扩展时小于 amount
+    pub net_amount: u64,
+    // 期望收到的 token B 数量
+    pub receive: u64,
+    pub timestamp: i64,
+}
+
+// 支付给一个 creator 的版税
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RoyaltyPayment {
+    pub creator: Pubkey,
+    pub amount: u64,
+}
+
+// 托管(全部或部分)成交时触发
+#[event]
+pub struct TakeEvent {
+    pub escrow: Pubkey,
+    // 指令执行后托管的状态, 托管被关闭时为关闭前的状态
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 从 vault 转给 taker 的 token A 数量
+    pub amount_a: u64,
+    // taker 实际收到的 token A 数量, mint A 带有 TransferFee 扩展时小于 amount_a
+    pub net_amount_a: u64,
+    // 成交价格, 即 taker 支付的 token B 数量(包含手续费, 不包含 tip)
+    pub amount_b: u64,
+    // maker 实际收到的 token B 数量, 即 amount_b + tip - fee 减去版税再扣除 mint B 的转账手续费
+    pub net_amount_b: u64,
+    // 收取的 token B 手续费总额(包含 referral_fee), 转给 maker 的是 amount_b + tip - fee 减去版税
+    pub fee: u64,
+    // 手续费中分给 referrer 的部分
+    pub referral_fee: u64,
+    // 从 amount_b 中支付给 mint A 的 verified creator 的版税, 只有 enforce_royalties 的托管不为空
+    pub royalties: Vec<RoyaltyPayment>,
+    // taker 在 amount_b 之外额外支付给 maker 的小费, 只有 take_v2 可以支付
+    pub tip: u64,
+    // 成交时对应 deposited 个 token A 的价格, 荷兰拍卖时为衰减后的价格
+    pub effective_receive: u64,
+    // HTLC 托管成交时公开的 preimage, 另一条链上的交易对手据此解锁资金; 普通托管为空
+    pub preimage: Vec<u8>,
+    pub timestamp: i64,
+}
+
+// 托管被退还时触发
+#[event]
+pub struct RefundEvent {
+    pub escrow: Pubkey,
+    // 指令执行后托管的状态, 托管被关闭时为关闭前的状态
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 退还给 maker 的 token A 数量, 强制退还冻结的 vault 时为 0
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+// maker 修改期望收到的 token B 数量时触发
+#[event]
+pub struct UpdateEvent {
+    pub escrow: Pubkey,
+    // 指令执行后托管的状态, 托管被关闭时为关闭前的状态
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub old_receive: u64,
+    pub new_receive: u64,
+    // 新价格对应的 token A 数量(即当前仍在出售的数量)
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+// maker 修改托管的 refund_delegate 时触发
+#[event]
+pub struct DelegateEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    // Pubkey::default() 表示没有 delegate
+    pub old_delegate: Pubkey,
+    pub new_delegate: Pubkey,
+    pub timestamp: i64,
+}
+
+// maker 把托管的 token A 移入新 seed 的托管重新定价时触发, 之后旧托管不再存在
+#[event]
+pub struct RelistEvent {
+    pub old_escrow: Pubkey,
+    pub escrow: Pubkey,
+    // 新托管的状态
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 从旧 vault 转出的 token A 数量
+    pub amount: u64,
+    // 新 vault 实际收到的 token A 数量, mint A 带有 TransferFee 扩展时小于 amount
+    pub net_amount: u64,
+    pub old_receive: u64,
+    pub new_receive: u64,
+    pub timestamp: i64,
+}
+
+// 两个方向相反的托管被撮合时触发, 两个托管都全部成交并关闭
+#[event]
+pub struct MatchEvent {
+    // 出售 token A 的托管
+    pub escrow_x: Pubkey,
+    // 出售 token B 的托管
+    pub escrow_y: Pubkey,
+    pub maker_x: Pubkey,
+    pub maker_y: Pubkey,
+    pub matcher: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // escrow_x 的 vault 为 escrow_y 一方转出的 token A 数量, 包含协议手续费
+    pub amount_a: u64,
+    // escrow_y 一方实际收到的 token A 数量, 即 amount_a - fee_a 再扣除 mint A 的转账手续费
+    pub net_amount_a: u64,
+    // escrow_y 的 vault 为 escrow_x 一方转出的 token B 数量, 包含协议手续费
+    pub amount_b: u64,
+    // escrow_x 一方实际收到的 token B 数量
+    pub net_amount_b: u64,
+    // 从 amount_a 和 amount_b 中扣除的协议手续费
+    pub fee_a: u64,
+    pub fee_b: u64,
+    // 价格交叉产生的差额, 传入撮合者账户时归撮合者, 否则退还给对应的 maker
+    pub surplus_a: u64,
+    pub surplus_b: u64,
+    pub timestamp: i64,
+}
+
+// maker 把托管交给新的 maker 时触发, 托管按新 maker 派生新的地址, 之后旧托管不再存在
+#[event]
+pub struct TransferMakerEvent {
+    pub old_escrow: Pubkey,
+    pub escrow: Pubkey,
+    // 新托管的状态, 和旧托管相同
+    pub status: EscrowStatus,
+    pub old_maker: Pubkey,
+    pub new_maker: Pubkey,
+    pub mint_a: Pubkey,
+    // 从旧 vault 转出的 token A 数量
+    pub amount: u64,
+    // 新 vault 实际收到的 token A 数量, mint A 带有 TransferFee 扩展时小于 amount
+    pub net_amount: u64,
+    pub timestamp: i64,
+}
+
+// 有仲裁人的托管被 lock_take 锁定时触发, 托管进入 Locked 状态
+#[event]
+pub struct LockEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub arbiter: Pubkey,
+    pub mint_b: Pubkey,
+    // taker 转入 vault_b 的 token B 数量
+    pub amount_b: u64,
+    // vault_b 实际收到的 token B 数量, mint B 带有 TransferFee 扩展时小于 amount_b
+    pub net_amount_b: u64,
+    pub timestamp: i64,
+}
+
+// 锁定的托管被 release_to_taker 或 release_to_maker 结算时触发, 托管和两个 vault 都被关闭
+#[event]
+pub struct ReleaseEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub arbiter: Pubkey,
+    // 签名结算的账户: 仲裁人, 或者 maker 和 taker 之一
+    pub authority: Pubkey,
+    // true 表示交易完成(token A 给 taker, token B 给 receive_to), false 表示双方各自取回
+    pub to_taker: bool,
+    // 从 vault 转出的 token A 数量
+    pub amount_a: u64,
+    // 从 vault_b 转出的 token B 数量, 包含手续费
+    pub amount_b: u64,
+    // 交易完成时收取的 token B 手续费, 退回时为 0
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+// taker 通过 reserve 预约托管时触发, 保证金已经存入 escrow 账户
+#[event]
+pub struct ReserveEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    // 存入的 SOL 保证金(lamports)
+    pub bond: u64,
+    // 预约的截止时间戳, 之前只有 taker 可以 settle
+    pub reservation_expiry: i64,
+    pub timestamp: i64,
+}
+
+// 预约过期被 expire_reservation 结束时触发, 保证金已经转给 maker; 按时 settle 时只触发 TakeEvent
+#[event]
+pub struct ReservationExpiredEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    // 没有按时 settle 的 taker
+    pub taker: Pubkey,
+    // 转给 maker 的保证金(lamports)
+    pub bond: u64,
+    pub timestamp: i64,
+}
+
+// 循环托管成交后从 maker 的 ATA 重新存入时触发, 和 TakeEvent 在同一条指令中
+#[event]
+pub struct RefillEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    // 从 maker 的 ATA 转出的 token A 数量
+    pub amount: u64,
+    // vault 实际收到的数量, 也是新的 deposited 和 amount
+    pub net_amount: u64,
+    // 之后还可以重新存入的次数
+    pub refills_left: u8,
+    pub timestamp: i64,
+}
+
+// make_basket 创建一篮子托管时触发
+#[event]
+pub struct MakeBasketEvent {
+    pub basket: Pubkey,
+    pub maker: Pubkey,
+    pub mint_b: Pubkey,
+    // 存入的 mint, 和 amounts 按相同的顺序对应
+    pub mints: Vec<Pubkey>,
+    // 每个 vault 实际收到的数量
+    pub amounts: Vec<u64>,
+    // 期望收到的 token B 数量
+    pub receive: u64,
+    pub timestamp: i64,
+}
+
+// take_basket 取走全部 leg 时触发
+#[event]
+pub struct TakeBasketEvent {
+    pub basket: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_b: Pubkey,
+    // 每个 vault 转给 taker 的数量, 包含别人直接转入 vault 的部分
+    pub amounts: Vec<u64>,
+    // taker 支付的 token B 数量, 包含手续费
+    pub amount_b: u64,
+    // maker 实际收到的 token B 数量
+    pub net_amount_b: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+// refund_basket 退还全部 leg 时触发
+#[event]
+pub struct RefundBasketEvent {
+    pub basket: Pubkey,
+    pub maker: Pubkey,
+    // 每个 vault 退还给 maker 的数量, 空的 vault 为 0
+    pub amounts: Vec<u64>,
+    pub timestamp: i64,
+}
+
+// sweep_abandoned 清理被放弃的托管时触发
+#[event]
+pub struct SweepAbandonedEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub cranker: Pubkey,
+    pub mint_a: Pubkey,
+    // 退还给 maker 的 token A 数量
+    pub amount: u64,
+    // escrow 和 vault 的租金中分给 cranker 和 rent_payer 的 lamports
+    pub cranker_lamports: u64,
+    pub rent_payer_lamports: u64,
+    pub timestamp: i64,
+}
+
+// recover_vault 取回孤立 vault 中的 token A 时触发
+#[event]
+pub struct RecoverVaultEvent {
+    // 已经关闭的托管账户地址
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    // 退还给 maker 的 token A 数量
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+// maker 设置或取消固定价格托管的价格区间时触发
+#[event]
+pub struct PriceBandEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    // Pubkey::default() 和 0 表示取消价格区间
+    pub price_feed: Pubkey,
+    pub max_deviation_bps: u16,
+    pub timestamp: i64,
+}
+
+// maker 开启或关闭托管的版税支付时触发
+#[event]
+pub struct RoyaltyEnforcementEvent {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub enforce_royalties: bool,
+    pub timestamp: i64,
+}
+
+// 托管被 cancel 取消时触发, 按 reason 区分撤回报价, 过期退还和改价
+#[event]
+pub struct CancelEvent {
+    pub escrow: Pubkey,
+    // 指令执行后托管的状态, 保留托管时为 Cancelled, 托管被关闭时为关闭前的状态
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 退还给 maker 的 token A 数量, 强制退还冻结的 vault 时为 0
+    pub amount: u64,
+    pub reason: CancelReason,
+    // 为 true 时托管账户保留到 close_cancelled 关闭
+    pub retained: bool,
+    pub timestamp: i64,
+}
+
+// take_streaming 和 stream_payment 每支付一期触发一次, paid_b 达到 stream_receive 时托管被关闭
+#[event]
+pub struct StreamPaymentEvent {
+    pub escrow: Pubkey,
+    // 指令执行后托管的状态, 付清后托管被关闭时为关闭前的状态 Streaming
+    pub status: EscrowStatus,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 本期释放给 taker 的 token A 数量和 taker 实际收到的数量
+    pub amount_a: u64,
+    pub net_amount_a: u64,
+    // 本期 taker 支付的 token B 数量(包括手续费)和 receive_to 实际收到的数量
+    pub amount_b: u64,
+    pub net_amount_b: u64,
+    pub fee: u64,
+    // 支付本期之后的累计支付和锁定的总额
+    pub paid_b: u64,
+    pub stream_receive: u64,
+    pub stream_deadline: i64,
+    pub timestamp: i64,
+}
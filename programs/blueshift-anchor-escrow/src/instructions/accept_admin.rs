@@ -0,0 +1,27 @@
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    // 签名账户, 必须是 propose_admin 指定的新管理员
+    pub pending_admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = config.pending_admin != Pubkey::default() @ EscrowError::InvalidPendingAdmin,
+        constraint = config.pending_admin == pending_admin.key() @ EscrowError::InvalidPendingAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(ctx: Context<AcceptAdmin>) -> Result<()> {
+    // 交换管理员并清空待处理的转移
+    let config = &mut ctx.accounts.config;
+    config.admin = config.pending_admin;
+    config.pending_admin = Pubkey::default();
+
+    Ok(())
+}
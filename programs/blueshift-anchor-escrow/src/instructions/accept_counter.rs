@@ -0,0 +1,374 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::TakeEvent,
+    pda::{
+        CONFIG_SEED, COUNTER_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED,
+        PAIR_INDEX_SEED, REGISTRY_SEED, STATS_SEED,
+    },
+    state::{Config, CounterOffer, Escrow, GlobalStats, MakerRegistry, MintBlocklist, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 账户列表和 Take 基本相同, 区别是由 maker 签名, token B 通过 counter_offer 的授权转出
+// remaining_accounts 和 Take 相同
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AcceptCounter<'info> {
+    // 签名账户, 托管账户的创建者, 支付 taker 缺少的 ATA 的租金
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 token B 的钱包, 只作为 maker_ata_b 的 authority, 由 has_one 约束为 escrow.receive_to
+    pub receive_to: UncheckedAccount<'info>,
+
+    // 提出还价的 taker, 关闭 counter_offer 后租金还给这个账户
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+
+    // 托管账户的数据账户, 成交后关闭, 同一托管的其他还价随之失效, 只能由各自的 taker 取消
+    #[account(
+      mut,
+      close = rent_payer,
+      seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+      bump = escrow.bump,
+      has_one = maker @ EscrowError::InvalidMaker,
+      has_one = rent_payer @ EscrowError::InvalidRentPayer,
+      has_one = receive_to @ EscrowError::InvalidReceiveTo,
+      has_one = mint_a @ EscrowError::InvalidMintA,
+      has_one = mint_b @ EscrowError::InvalidMintB,
+      constraint = !escrow.is_arbitrated() @ EscrowError::ArbiterRequired, // 还价同样要通过 lock_take 锁定后由仲裁人结算
+      constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+      constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved,
+  )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    // 被接受的还价, maker 签名即表示同意这个价格和 taker, 因此不再检查 allowed_taker 和允许列表
+    #[account(
+      mut,
+      close = taker,
+      seeds = [COUNTER_SEED, escrow.key().as_ref(), taker.key().as_ref()],
+      bump = counter_offer.bump,
+      has_one = escrow @ EscrowError::InvalidCounterOffer,
+      has_one = taker @ EscrowError::InvalidCounterOffer,
+      has_one = mint_b @ EscrowError::InvalidCounterOffer,
+  )]
+    pub counter_offer: Box<Account<'info, CounterOffer>>,
+
+    // Token A 和 Token B 的 mint 账户, 分别由各自的 token 程序管理
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+      mut,
+      address = escrow.vault @ EscrowError::InvalidVault,
+      token::mint = mint_a,
+      token::authority = escrow,
+      token::token_program = token_program_a
+  )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 取款者的 Token A 的 ATA 账户, 用来接收 Token A
+    #[account(
+      init_if_needed,
+      payer = maker,
+      associated_token::mint = mint_a,
+      associated_token::authority = taker,
+      associated_token::token_program = token_program_a
+  )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 托管账户创建者的 Token A 的 ATA 账户, 用来退还 vault 中超出 escrow.amount 的多余代币
+    #[account(
+      init_if_needed,
+      payer = maker,
+      associated_token::mint = mint_a,
+      associated_token::authority = maker,
+      associated_token::token_program = token_program_a
+  )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 取款者的 Token B 的 ATA 账户, 已经在 propose_counter 中授权给 counter_offer
+    #[account(
+      mut,
+      associated_token::mint = mint_b,
+      associated_token::authority = taker,
+      associated_token::token_program = token_program_b
+  )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // receive_to 的 Token B 的 ATA 账户, 用来接收 Token B
+    #[account(
+      init_if_needed,
+      payer = maker,
+      associated_token::mint = mint_b,
+      associated_token::authority = receive_to,
+      associated_token::token_program = token_program_b
+  )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 读取手续费比例并检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 Token B 手续费的 ATA 账户, 手续费为 0 时可以不传
+    #[account(
+      mut,
+      associated_token::mint = mint_b,
+      associated_token::authority = fee_authority,
+      associated_token::token_program = token_program_b
+  )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 成交关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+      mut,
+      seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+      bump = pair_index.bump,
+  )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理 mint_a 和 vault 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>, // 管理 mint_b 的 token 程序
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptCounter<'info> {
+    // 以 counter_offer 作为 delegate 从 taker 的 Token B 账户中转出 amount 个代币, 返回 mint B 扣留的转账手续费
+    fn transfer_from_taker(
+        &self,
+        to: AccountInfo<'info>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let escrow_key = self.escrow.key();
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            COUNTER_SEED,
+            escrow_key.as_ref(),
+            self.taker.to_account_info().key.as_ref(),
+            &[self.counter_offer.bump],
+        ]];
+
+        transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to,
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.counter_offer.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_b.decimals,
+        )
+    }
+
+    // 把 Token B 转账给 maker, 手续费转给协议, 手续费为 0 时不做额外的 CPI
+    // 返回 maker 扣除 mint B 转账手续费后实际收到的数量
+    fn pay_maker(
+        &self,
+        maker_amount: u64,
+        fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let withheld = self.transfer_from_taker(
+            self.maker_ata_b.to_account_info(),
+            maker_amount,
+            remaining_accounts,
+        )?;
+
+        if fee > 0 {
+            let fee_vault_b = self
+                .fee_vault_b
+                .as_ref()
+                .ok_or(EscrowError::MissingFeeVault)?;
+            self.transfer_from_taker(fee_vault_b.to_account_info(), fee, remaining_accounts)?;
+        }
+
+        maker_amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 从 vault 中取出 Token A 转账给 taker, 多余代币退还给 maker, 然后关闭 vault
+    // 返回 taker 扣除 mint A 转账手续费后实际收到的数量
+    fn withdraw_and_close_vault(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to: self.taker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            self.escrow.amount,
+            self.mint_a.decimals,
+        )?;
+
+        let surplus = self
+            .vault
+            .amount
+            .checked_sub(self.escrow.amount)
+            .ok_or(EscrowError::MathOverflow)?;
+        if surplus > 0 {
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program_a.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        to: self.maker_ata_a.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        authority: self.escrow.to_account_info(),
+                    },
+                    &signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                surplus,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program_a.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        self.escrow
+            .amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, AcceptCounter<'info>>) -> Result<()> {
+    // 还价针对的是托管中剩余的全部 token A
+    let amount_a = ctx.accounts.escrow.amount;
+    let amount_b = ctx.accounts.counter_offer.receive;
+
+    // vault 中没有 token A 时(例如被 permanent delegate 销毁)不能让 taker 先支付 token B
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+
+    // 版税需要 take 传入的 metadata 和 creator 账户
+    require!(
+        !ctx.accounts.escrow.enforce_royalties,
+        EscrowError::RoyaltiesRequireTake
+    );
+    // 循环托管成交后需要 take 重新存入并保留 escrow, 这里成交完就关闭, 会留下 maker ATA 上的授权
+    require!(
+        !ctx.accounts.escrow.recurring,
+        EscrowError::RecurringRequiresTake
+    );
+    // direct_only 的托管不能通过 CPI 成交, 这里没有 instructions sysvar, 可信路由也只能使用 take
+    caller::check_direct_call(&ctx.accounts.escrow, &ctx.accounts.config, None)?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+    // 还价也要在有效期内接受, 过期之后只能退还
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()],
+    )?;
+
+    // 和 take 一样收取协议手续费
+    let fee = ctx.accounts.config.fee_for(amount_b)?;
+    let maker_amount = amount_b.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+
+    let net_amount_b = ctx
+        .accounts
+        .pay_maker(maker_amount, fee, ctx.remaining_accounts)?;
+    let net_amount_a = ctx
+        .accounts
+        .withdraw_and_close_vault(ctx.remaining_accounts)?;
+    ctx.accounts.stats.record_take();
+    MakerRegistry::remove_seed(&ctx.accounts.registry, ctx.accounts.escrow.seed)?;
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+    }
+
+    emit_cpi!(TakeEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        taker: ctx.accounts.taker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        amount_a,
+        net_amount_a,
+        amount_b,
+        net_amount_b,
+        fee,
+        referral_fee: 0,
+        royalties: Vec::new(),
+        tip: 0,
+        // 还价成交时记录的是还价价格
+        effective_receive: amount_b,
+        preimage: Vec::new(),
+        timestamp: now,
+    });
+
+    // 指令执行完毕后 anchor 自动关闭 escrow 和 counter_offer 数据账户
+
+    Ok(())
+}
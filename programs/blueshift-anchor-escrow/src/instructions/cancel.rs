@@ -0,0 +1,49 @@
+use crate::{
+    events::CancelEvent,
+    instructions::refund::{check_refund_authority, withdraw, Refund},
+    state::{CancelReason, EscrowStatus},
+};
+use anchor_lang::prelude::*;
+
+// cancel 使用和 refund 相同的账户列表, 和 refund 一样退还 token A, 只是在事件中记录取消的原因
+// retain 为 true 时保留 escrow 数据账户, 状态改为 Cancelled 并记录原因和 slot, 浏览器可以在链上读取;
+// CANCEL_RETENTION_SLOTS 之后任何人都可以通过 close_cancelled 关闭它, 租金还给 rent_payer
+pub fn handler<'info>(
+    mut ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+    reason: u8,
+    retain: bool,
+) -> Result<()> {
+    check_refund_authority(ctx.accounts)?;
+    let reason = CancelReason::try_from(reason)?;
+    let rent_payer = ctx.accounts.rent_payer.to_account_info();
+    let amount = withdraw(&mut ctx, false, rent_payer)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    if retain {
+        // 冻结前的状态也改为 Cancelled, 之后 advance_status 和 unfreeze 都不会让托管重新可以成交
+        escrow.status = EscrowStatus::Cancelled;
+        escrow.status_before_freeze = EscrowStatus::Cancelled;
+        escrow.cancel_reason = reason as u8;
+        escrow.cancelled_slot = Clock::get()?.slot;
+    }
+
+    emit_cpi!(CancelEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.escrow.mint_b,
+        amount,
+        reason,
+        retained: retain,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    if !retain {
+        ctx.accounts
+            .escrow
+            .close(ctx.accounts.rent_payer.to_account_info())?;
+    }
+
+    Ok(())
+}
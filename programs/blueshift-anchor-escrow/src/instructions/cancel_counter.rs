@@ -0,0 +1,57 @@
+use crate::{pda::COUNTER_SEED, state::CounterOffer};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{revoke, Revoke, TokenAccount, TokenInterface};
+
+#[derive(Accounts)]
+pub struct CancelCounter<'info> {
+    // 签名账户, 提出还价的 taker
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    // 还价账户, 关闭后租金还给 taker; 托管已经被成交或退还时也可以取消
+    #[account(
+        mut,
+        close = taker,
+        seeds = [COUNTER_SEED, counter_offer.escrow.as_ref(), taker.key().as_ref()],
+        bump = counter_offer.bump,
+        has_one = taker
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+
+    // 取款者的 Token B 账户, 用来撤销对 counter_offer 的授权
+    #[account(
+        mut,
+        token::mint = counter_offer.mint_b,
+        token::authority = taker,
+        token::token_program = token_program
+    )]
+    pub taker_ata_b: InterfaceAccount<'info, TokenAccount>,
+
+    // 账户所需要的程序
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CancelCounter<'info> {
+    // 只有当前的 delegate 还是这个 counter_offer 时才撤销, 避免清掉 taker 之后做的其他授权
+    fn revoke_counter(&self) -> Result<()> {
+        if self.taker_ata_b.delegate != Some(self.counter_offer.key()).into() {
+            return Ok(());
+        }
+
+        revoke(CpiContext::new(
+            self.token_program.to_account_info(),
+            Revoke {
+                source: self.taker_ata_b.to_account_info(),
+                authority: self.taker.to_account_info(),
+            },
+        ))
+    }
+}
+
+pub fn handler(ctx: Context<CancelCounter>) -> Result<()> {
+    ctx.accounts.revoke_counter()?;
+
+    // 指令执行完毕后 anchor 自动关闭 counter_offer 数据账户
+
+    Ok(())
+}
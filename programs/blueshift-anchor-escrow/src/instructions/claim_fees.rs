@@ -0,0 +1,94 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, FEE_AUTHORITY_SEED},
+    state::Config,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+#[derive(Accounts)]
+pub struct ClaimFees<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 要提取的手续费的 mint 账户, 支持 SPL Token 和 Token-2022
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    // 协议的手续费 ATA 账户
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    // 接收手续费的账户, 由 admin 指定, 可以是任意属于同一个 mint 的 token 账户
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    // 账户所需要的程序
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ClaimFees<'info> {
+    // 从手续费 ATA 中转出 amount 个代币, 由 fee_authority PDA 签名
+    fn claim(&mut self, amount: u64) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] =
+            [&[FEE_AUTHORITY_SEED, &[self.config.fee_authority_bump]]];
+
+        transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.fee_vault.to_account_info(),
+                    to: self.destination.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    authority: self.fee_authority.to_account_info(),
+                },
+                &signer_seeds,
+            ),
+            amount,
+            self.mint.decimals,
+        )?;
+
+        Ok(())
+    }
+}
+
+pub fn handler(ctx: Context<ClaimFees>, amount: u64) -> Result<()> {
+    // u64::MAX 表示提取全部手续费
+    let amount = if amount == u64::MAX {
+        ctx.accounts.fee_vault.amount
+    } else {
+        amount
+    };
+
+    require_gt!(amount, 0, EscrowError::InvalidAmount);
+    require_gte!(
+        ctx.accounts.fee_vault.amount,
+        amount,
+        EscrowError::InvalidAmount
+    );
+
+    ctx.accounts.claim(amount)
+}
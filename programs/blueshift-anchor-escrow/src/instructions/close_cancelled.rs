@@ -0,0 +1,44 @@
+use crate::{
+    errors::EscrowError,
+    pda::ESCROW_SEED,
+    state::{Escrow, EscrowStatus},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct CloseCancelled<'info> {
+    // 签名账户, 任何人都可以在保留期结束后调用
+    pub closer: Signer<'info>,
+
+    // 支付 escrow 租金的账户, 关闭后租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    // cancel 保留的托管, token A 已经退还, vault 已经关闭
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        constraint = escrow.status == EscrowStatus::Cancelled @ EscrowError::InvalidEscrowStatus
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<CloseCancelled>) -> Result<()> {
+    // 保留期内浏览器可以读取取消的原因, 之后才能关闭
+    let escrow = &ctx.accounts.escrow;
+    let closable_at = escrow
+        .cancelled_slot
+        .saturating_add(Escrow::CANCEL_RETENTION_SLOTS);
+    require_gte!(
+        Clock::get()?.slot,
+        closable_at,
+        EscrowError::CancelRetentionActive
+    );
+
+    // 指令执行完毕后 anchor 自动关闭 escrow 数据账户
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::{pda::COMMIT_SEED, state::TakeCommit};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct CloseCommit<'info> {
+    // 签名账户, 做出承诺的 taker
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    // 承诺账户, 关闭后租金还给 taker; 托管被别人成交或被退还之后 taker 用它取回租金
+    #[account(
+        mut,
+        close = taker,
+        seeds = [COMMIT_SEED, taker.key().as_ref(), commit.hash.as_ref()],
+        bump = commit.bump,
+        has_one = taker
+    )]
+    pub commit: Account<'info, TakeCommit>,
+}
+
+pub fn handler(_ctx: Context<CloseCommit>) -> Result<()> {
+    // 指令执行完毕后 anchor 自动关闭承诺账户
+
+    Ok(())
+}
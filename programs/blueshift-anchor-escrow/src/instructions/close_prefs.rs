@@ -0,0 +1,24 @@
+use crate::{pda::PREFS_SEED, state::MakerPrefs};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct ClosePrefs<'info> {
+    // 签名账户, 默认参数所属的 maker, 关闭后租金还给它
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        close = maker,
+        seeds = [PREFS_SEED, maker.key().as_ref()],
+        bump = prefs.bump,
+        has_one = maker,
+    )]
+    pub prefs: Account<'info, MakerPrefs>,
+}
+
+pub fn handler(_ctx: Context<ClosePrefs>) -> Result<()> {
+    // 指令执行完毕后 anchor 自动关闭 prefs 数据账户, 已经创建的托管不受影响
+
+    Ok(())
+}
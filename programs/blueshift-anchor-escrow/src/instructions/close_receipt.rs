@@ -0,0 +1,32 @@
+use crate::{errors::EscrowError, pda::RECEIPT_SEED, state::TradeReceipt};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct CloseReceipt<'info> {
+    // 签名账户, 成交的 maker 或 taker
+    #[account(
+        constraint = closer.key() == receipt.maker || closer.key() == receipt.taker
+            @ EscrowError::UnauthorizedReceiptCloser
+    )]
+    pub closer: Signer<'info>,
+
+    // 支付了租金的 taker, 关闭后租金还给它
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+
+    // 成交记录, 关闭后租金还给 taker
+    #[account(
+        mut,
+        close = taker,
+        seeds = [RECEIPT_SEED, receipt.escrow.as_ref()],
+        bump = receipt.bump,
+        has_one = taker
+    )]
+    pub receipt: Account<'info, TradeReceipt>,
+}
+
+pub fn handler(_ctx: Context<CloseReceipt>) -> Result<()> {
+    // 指令执行完毕后 anchor 自动关闭 receipt 数据账户
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use crate::{errors::EscrowError, pda::REGISTRY_SEED, state::MakerRegistry};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct CloseRegistry<'info> {
+    // 签名账户, 索引所属的 maker, 关闭后租金还给它
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    // maker 的托管索引, 所有托管都关闭后才能关闭
+    #[account(
+        mut,
+        close = maker,
+        seeds = [REGISTRY_SEED, maker.key().as_ref()],
+        bump = registry.bump,
+        has_one = maker,
+        constraint = registry.seeds.is_empty() @ EscrowError::RegistryNotEmpty
+    )]
+    pub registry: Account<'info, MakerRegistry>,
+}
+
+pub fn handler(_ctx: Context<CloseRegistry>) -> Result<()> {
+    // 指令执行完毕后 anchor 自动关闭 registry 数据账户, 下一次 make 会重新创建
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use crate::{pda::COMMIT_SEED, state::TakeCommit};
+use anchor_lang::prelude::*;
+
+// commit-reveal 的第一步: taker 记录 TakeCommit::hash(taker, escrow, salt), 不暴露要成交的托管
+// 托管开启 commit-reveal 后, 至少经过 escrow.commit_delay_slots 个 slot 才能用 salt 调用 reveal_take
+// 监听交易的机器人看到 reveal_take 时再 commit 已经来不及, 同一个托管的其他承诺在它成交后失去作用
+#[derive(Accounts)]
+#[instruction(hash: [u8; 32])]
+pub struct CommitTake<'info> {
+    // 签名账户, 之后 reveal_take 的 taker, 支付承诺账户的租金
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    // 承诺账户, 相同的 hash 已经承诺过时 init 失败
+    #[account(
+        init,
+        payer = taker,
+        space = TakeCommit::INIT_SPACE + TakeCommit::DISCRIMINATOR.len(),
+        seeds = [COMMIT_SEED, taker.key().as_ref(), hash.as_ref()],
+        bump,
+    )]
+    pub commit: Account<'info, TakeCommit>,
+
+    // 创建承诺账户需要系统程序
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<CommitTake>, hash: [u8; 32]) -> Result<()> {
+    ctx.accounts.commit.set_inner(TakeCommit {
+        taker: ctx.accounts.taker.key(),
+        hash,
+        slot: Clock::get()?.slot,
+        bump: ctx.bumps.commit,
+    });
+
+    Ok(())
+}
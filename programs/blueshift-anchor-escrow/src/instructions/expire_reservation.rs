@@ -0,0 +1,48 @@
+use crate::{
+    errors::EscrowError, events::ReservationExpiredEvent, instructions::reserve::pay_bond,
+    pda::ESCROW_SEED, state::Escrow,
+};
+use anchor_lang::prelude::*;
+
+// 预约过了截止时间仍然没有 settle 时, 任何人都可以结束预约: 保证金转给 maker, 托管重新开放给所有 taker
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ExpireReservation<'info> {
+    // 签名账户, 任何人都可以调用, 不需要是 maker
+    pub cranker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 接收保证金, 由 escrow 的 seeds 和 has_one 约束
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 托管账户的数据账户, 清除预约之后保持打开
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<ExpireReservation>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let taker = ctx.accounts.escrow.reserved_taker;
+    let bond = ctx.accounts.escrow.expire_reservation(now)?;
+
+    pay_bond(
+        &ctx.accounts.escrow.to_account_info(),
+        &ctx.accounts.maker.to_account_info(),
+        bond,
+    )?;
+
+    emit_cpi!(ReservationExpiredEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        taker,
+        bond,
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use crate::{results::EscrowView, state::Escrow};
+use anchor_lang::prelude::*;
+
+// 只读指令, 供钱包用 simulateTransaction 预览托管, 不需要签名也不修改任何账户
+#[derive(Accounts)]
+pub struct GetEscrow<'info> {
+    // 托管账户的数据账户, Account 会校验 owner 和 1 字节的 discriminator
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<GetEscrow>) -> Result<EscrowView> {
+    let escrow = &ctx.accounts.escrow;
+    let now = Clock::get()?.unix_timestamp;
+
+    // 和 take 一样按当前价格折算剩余 token A 需要支付的 token B
+    let current_receive = escrow.current_receive(now)?;
+    let amount_b = escrow.pro_rata(escrow.amount, current_receive)?;
+
+    Ok(EscrowView {
+        address: escrow.key(),
+        escrow: (**escrow).clone(),
+        current_receive,
+        amount_b,
+        started: now >= escrow.start_time,
+        expired: escrow.is_expired(now),
+        timestamp: now,
+    })
+}
@@ -0,0 +1,32 @@
+use crate::{pda::USER_STATS_SEED, state::UserStats};
+use anchor_lang::prelude::*;
+
+// 钱包为自己创建成交统计账户; taker 的统计账户在 take 中自动创建, maker 需要先调用本指令
+#[derive(Accounts)]
+pub struct InitUserStats<'info> {
+    // 签名账户, 统计账户所属的钱包, 支付租金
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    #[account(
+        init,
+        payer = wallet,
+        space = UserStats::INIT_SPACE + UserStats::DISCRIMINATOR.len(),
+        seeds = [USER_STATS_SEED, wallet.key().as_ref()],
+        bump,
+    )]
+    pub user_stats: Account<'info, UserStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<InitUserStats>) -> Result<()> {
+    ctx.accounts.user_stats.set_inner(UserStats {
+        wallet: ctx.accounts.wallet.key(),
+        trades: 0,
+        volume_b: 0,
+        bump: ctx.bumps.user_stats,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, FEE_AUTHORITY_SEED},
+    state::{Config, FeeTier, BPS_DENOMINATOR, MAX_FEE_TIERS, MAX_OPERATORS, MAX_TRUSTED_ROUTERS},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    // 签名账户, 成为协议的管理员并支付 config 账户的租金
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    // 全局唯一的协议配置账户
+    #[account(
+        init,
+        payer = admin,
+        space = Config::INIT_SPACE + Config::DISCRIMINATOR.len(),
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: 只用来派生 bump, 手续费 ATA 的 authority, 不存储数据
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<InitializeConfig>, fee_bps: u16) -> Result<()> {
+    // 手续费比例不能超过 100%
+    require_gte!(BPS_DENOMINATOR, fee_bps as u64, EscrowError::InvalidFee);
+
+    ctx.accounts.config.set_inner(Config {
+        admin: ctx.accounts.admin.key(),
+        pending_admin: Pubkey::default(),
+        fee_bps,
+        referral_bps: 0,
+        paused: false,
+        fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+        fee_tier_count: 0,
+        listing_fee_lamports: 0,
+        treasury: Pubkey::default(),
+        abandon_seconds: 0, // 默认不启用, 由 set_abandon_policy 设置
+        abandon_cranker_bps: 0,
+        swap_router: Pubkey::default(), // 默认不允许 take_with_swap, 由 set_swap_router 设置
+        fee_on_tips: false,             // 默认不对小费收取手续费, 由 set_fee_on_tips 设置
+        trusted_routers: [Pubkey::default(); MAX_TRUSTED_ROUTERS], // 默认没有可信路由, 由 set_trusted_routers 设置
+        trusted_router_count: 0,
+        operators: [Pubkey::default(); MAX_OPERATORS], // 默认没有运营方, 由 set_operators 设置
+        operator_count: 0,
+        fee_authority_bump: ctx.bumps.fee_authority,
+        bump: ctx.bumps.config,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::LockEvent,
+    pda::{CONFIG_SEED, ESCROW_SEED, MINT_BLOCKLIST_SEED},
+    state::{Config, Escrow, EscrowStatus, MintBlocklist},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+// 有仲裁人的托管的 take: taker 按当前价格把 token B 转入 escrow 拥有的 vault_b, token A 仍然留在 vault 中
+// 之后由仲裁人或双方共同通过 release_to_taker 完成交易, 或者通过 release_to_maker 各自取回, 见 release
+// 手续费在交易完成时才收取, 退回时 taker 取回全部 token B
+// remaining_accounts: mint B 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct LockTake<'info> {
+    // 签名账户, 锁定 token B 的 taker, 支付 vault_b 的租金
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束
+    pub maker: UncheckedAccount<'info>,
+
+    // 托管账户的数据账户, 锁定后保持打开, 由 release 关闭
+    // 仲裁人不能锁定: 否则仲裁人既是 taker 又决定结算方向
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        has_one = mint_b @ EscrowError::InvalidMintB,
+        constraint = escrow.is_arbitrated() @ EscrowError::InvalidEscrowStatus,
+        constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+        constraint = taker.key() != escrow.arbiter @ EscrowError::InvalidArbiter,
+        constraint = escrow.status != EscrowStatus::Locked @ EscrowError::InvalidEscrowStatus, // 已经被锁定
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 锁定前检查其中确实有 token A
+    #[account(
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program_a
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // taker 支付 token B 的账户, 由 taker 签名转出
+    #[account(
+        mut,
+        token::mint = mint_b,
+        token::authority = taker,
+        token::token_program = token_program_b
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 锁定 token B 的第二个 vault, 和 vault 一样由 escrow 拥有, 结算时关闭
+    #[account(
+        init,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_b
+    )]
+    pub vault_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>,
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, LockTake<'info>>,
+    max_receive: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<()> {
+    // direct_only 的托管不能通过 CPI 成交, 这里没有 instructions sysvar, 可信路由也只能使用 take
+    caller::check_direct_call(&ctx.accounts.escrow, &ctx.accounts.config, None)?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()],
+    )?;
+
+    require!(
+        ctx.accounts
+            .escrow
+            .is_on_allowlist(&ctx.accounts.taker.key(), &proof),
+        EscrowError::NotOnAllowlist
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_started(now)?;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    // 有仲裁人的托管不能部分成交, 锁定的是剩余全部 token A 的价格
+    let amount_b = ctx
+        .accounts
+        .escrow
+        .receive_for(ctx.accounts.escrow.amount)?;
+    require_gte!(max_receive, amount_b, EscrowError::SlippageExceeded);
+
+    // 锁定之后 taker 只能等待结算, vault 中必须有可以交付的 token A
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    let balance_b = ctx.accounts.taker_ata_b.amount;
+    if balance_b < amount_b {
+        msg!("Taker is short {} token B", amount_b - balance_b);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+
+    let withheld = transfer::transfer_checked(
+        CpiContext::new(
+            ctx.accounts.token_program_b.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.taker_ata_b.to_account_info(),
+                to: ctx.accounts.vault_b.to_account_info(),
+                mint: ctx.accounts.mint_b.to_account_info(),
+                authority: ctx.accounts.taker.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount_b,
+        ctx.accounts.mint_b.decimals,
+    )?;
+    let net_amount_b = amount_b
+        .checked_sub(withheld)
+        .ok_or(EscrowError::MathOverflow)?;
+
+    let taker = ctx.accounts.taker.key();
+    ctx.accounts.escrow.lock(taker, now);
+
+    emit_cpi!(LockEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        taker,
+        arbiter: ctx.accounts.escrow.arbiter,
+        mint_b: ctx.accounts.mint_b.key(),
+        amount_b,
+        net_amount_b,
+        timestamp: now,
+    });
+
+    Ok(())
+}
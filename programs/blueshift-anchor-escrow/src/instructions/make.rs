@@ -1,112 +1,752 @@
-use crate::{errors::EscrowError, state::Escrow};
-use anchor_lang::prelude::*;
+use crate::{
+    errors::EscrowError,
+    events::MakeEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, EXEMPT_LIST_SEED, MINT_ALLOWLIST_SEED, MINT_BLOCKLIST_SEED,
+        REGISTRY_SEED, STATS_SEED,
+    },
+    pnft::{self, PnftAccounts, PnftTransfer},
+    results::MakeResult,
+    state::{
+        Config, Escrow, EscrowStatus, ExemptList, GlobalStats, MakerRegistry, MintAllowlist,
+        MintBlocklist, PaymentMint,
+    },
+    transfer,
+};
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+    token::spl_token::native_mint,
+    token_interface::{
+        approve_checked, sync_native, ApproveChecked, Mint, SyncNative, TokenAccount,
+        TokenInterface, TransferChecked,
+    },
 };
 
 // 定义 make 所需的账户列表
+// mint A 带有 transfer hook 时, 在 remaining_accounts 中传入 hook 需要的额外账户, 见 transfer::transfer_checked
+// mint A 是 pNFT 时, remaining_accounts 先传入 Token Metadata 转账需要的账户, 见 pnft.rs
+#[event_cpi] // 自动添加 event_authority 和 program 账户, 用于通过 CPI 记录事件
 #[derive(Accounts)]
 #[instruction(seed: u64)] // 用来获取指令中的参数, 这里只获取了 seed 传参
 pub struct Make<'info> {
     // 签名账户, 即创建托管的账户
+    // 可以是其他程序通过 invoke_signed 签名的 PDA, 此时由 rent_payer 支付租金, PDA 只需要持有 token A
     #[account(mut)]
     pub maker: Signer<'info>,
 
+    // 支付 escrow 和 vault 租金的账户, 例如为新用户赞助租金的后端; 不传时由 maker 自己支付, 见 Make::payer
+    // maker 仍然作为 authority 签名并存入 token A; 旧客户端在这里传入 maker 自己, 结果相同
+    #[account(mut)]
+    pub rent_payer: Option<Signer<'info>>,
+
     // 初始化托管 PDA 数据账户, 主要用来存放所需要的数据
+    // 地址上已经有别人转入的 lamports 时, anchor 改为补足租金后 allocate 和 assign, 这些 lamports 关闭时一起还给 rent_payer
     #[account(
         init,
-        payer = maker, // 指定创建账户所花费用的支付者
+        payer = rent_payer.as_ref().unwrap_or(&maker), // 指定创建账户所花费用的支付者, 和 Make::payer 相同
         space = Escrow::INIT_SPACE + Escrow::DISCRIMINATOR.len(), // 默认的 8 个字节的判别符空间 8 替换为 Escrow::DISCRIMINATOR.len()
-        seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()], // 通过 maker 和自定义传入的 seed 来生成 pda
+        seeds = [ESCROW_SEED, maker.key().as_ref(), seed.to_le_bytes().as_ref()], // 通过 maker 和自定义传入的 seed 来生成 pda
         bump,
     )]
     pub escrow: Account<'info, Escrow>,
 
     // 存入的 Token A 的 mint 账户
     #[account(
-        mint::token_program = token_program // 约束 mint_a 的 token_program 必须是 token_program(SPL Token Program)
+        mint::token_program = token_program_a // 约束 mint_a 由 token_program_a 管理(SPL Token 或 Token-2022)
     )]
     pub mint_a: InterfaceAccount<'info, Mint>,
 
     // 换取的 Token B 的 mint 账户
     #[account(
-        mint::token_program = token_program
+        mint::token_program = token_program_b
     )]
     pub mint_b: InterfaceAccount<'info, Mint>,
 
     // 创建者所存入的 Token A 的 ATA 账户
+    // mint A 是 native mint 时总是直接从 maker 的 SOL 余额包装存入, 不使用这个账户, 可以不传; 其他 mint 必须传
     #[account(
         mut,
         associated_token::mint = mint_a, // 约束 ATA 账户是和 mint_a 绑定的,
         associated_token::authority = maker, // 约束这是创建者的 ATA 账户
-        associated_token::token_program = token_program // 约束 associated token program 创建账户应该使用那个 token program 来管理这个账户
+        associated_token::token_program = token_program_a // 约束 associated token program 创建账户应该使用那个 token program 来管理这个账户
     )]
-    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
 
     // 创建和初始化资金托管 ATA 账户, 关联 mint_a 账户, 用来存取 token_a
     // 不需要 init, 因为 ATA 账户的大小是固定的(固定的几个字段, 如: amount, owner 等), Associated Token Program 会自动分配大小
+    // 任何人都可以提前为 escrow 地址创建 ATA, 使用 init_if_needed 防止 make 因此失败; 已有的代币的处理见 create
     #[account(
-        init,
-        payer = maker,
+        init_if_needed,
+        payer = rent_payer.as_ref().unwrap_or(&maker),
         associated_token::mint = mint_a,
         associated_token::authority = escrow, // 约束这是 escrow 的 ATA 账户
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_a
     )]
     pub vault: InterfaceAccount<'info, TokenAccount>,
 
+    // 协议配置账户, 用来检查协议是否暂停并读取挂单费
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 白名单, 由 seeds 约束地址; 从未设置过时是空的系统账户, 见 MintAllowlist::check
+    #[account(seeds = [MINT_ALLOWLIST_SEED], bump)]
+    pub mint_allowlist: UncheckedAccount<'info>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 可选的挂单费豁免列表, maker 在列表中时不收取挂单费, 见 ExemptList
+    #[account(seeds = [EXEMPT_LIST_SEED], bump = exempt_list.bump)]
+    pub exempt_list: Option<Box<Account<'info, ExemptList>>>,
+
+    /// CHECK: 接收挂单费的账户, 由 address 约束为 config.treasury; 不收取挂单费时可以不传
+    #[account(mut, address = config.treasury @ EscrowError::InvalidTreasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    // 全局统计账户, 第一次使用时由 rent_payer 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = rent_payer.as_ref().unwrap_or(&maker),
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    // maker 的托管索引, 第一次 make 时由 rent_payer 支付租金创建
+    // 已经存在时 space 取当前大小, 通过 init_if_needed 对空间的检查, 增长由 register_seed 扩容
+    #[account(
+        init_if_needed,
+        payer = rent_payer.as_ref().unwrap_or(&maker),
+        space = registry.data_len().max(MakerRegistry::space(0)),
+        seeds = [REGISTRY_SEED, maker.key().as_ref()],
+        bump,
+    )]
+    pub registry: Box<Account<'info, MakerRegistry>>,
+
     // Programs
     pub associated_token_program: Program<'info, AssociatedToken>, // ATA 程序(因为需要定义 ATA 账户, 所以必须显示定义 AssociatedTokenAccount 程序)
-    pub token_program: Interface<'info, TokenInterface>,           // SPL Token 程序
-    pub system_program: Program<'info, System>,                    // 系统程序
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理 mint_a 和 vault 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>, // 管理 mint_b 的 token 程序, 可以和 token_program_a 不同
+    pub system_program: Program<'info, System>,            // 系统程序
 }
 
 impl<'info> Make<'info> {
+    // 支付租金和挂单费的账户, 没有传入 rent_payer 时是 maker, 关闭托管时租金还给它
+    pub fn payer(&self) -> &Signer<'info> {
+        self.rent_payer.as_ref().unwrap_or(&self.maker)
+    }
+
     // 为 escrow 数据账户填充所需要的数据
-    fn populate_escrow(&mut self, seed: u64, amount: u64, bump: u8) -> Result<()> {
+    fn populate_escrow(
+        &mut self,
+        seed: u64,
+        receive: u64,
+        deposited: u64,
+        expiry: i64,
+        allowed_taker: Pubkey,
+        bump: u8,
+    ) -> Result<()> {
         self.escrow.set_inner(Escrow {
-            seed,                      // 自定义种子
-            maker: self.maker.key(),   // 托管账户创建者地址
-            mint_a: self.mint_a.key(), // token A 的 mint 账户地址
-            mint_b: self.mint_b.key(), // token B 的 mint 账户地址
-            receive: amount,           // 期望接收的 token B 数量
-            bump,                      // 缓存的 bump 值
+            version: Escrow::VERSION,           // 当前的账户布局版本
+            seed,                               // 自定义种子
+            maker: self.maker.key(),            // 托管账户创建者地址
+            rent_payer: self.payer().key(),     // 关闭时租金的去向
+            receive_to: self.maker.key(),       // 默认由 maker 接收 token B, 由 create 设置
+            mint_a: self.mint_a.key(),          // token A 的 mint 账户地址
+            mint_b: self.mint_b.key(),          // token B 的 mint 账户地址
+            receive,                            // 期望接收的 token B 数量
+            deposited,                          // 存入的 token A 数量
+            amount: deposited,                  // 当前出售的 token A 数量
+            expiry,                             // 过期时间戳
+            allowed_taker,                      // 指定的 taker
+            refund_delegate: Pubkey::default(), // 默认没有退还密钥, 由 create 设置
+            arbiter: Pubkey::default(),         // 默认没有仲裁人, 由 create 设置
+            decay_start: 0,                     // 默认为固定价格, 荷兰拍卖由 make_dutch 设置
+            decay_end: 0,
+            end_receive: 0,
+            alt_payments: [PaymentMint::default(); 3], // 默认只接受 mint_b, 由 create 设置
+            locked_taker: Pubkey::default(),           // 新创建的托管没有被锁定
+            locked_at: 0,
+            reserved_taker: Pubkey::default(), // 新创建的托管没有被预约
+            reservation_expiry: 0,
+            bond: 0,
+            recurring: false, // 默认成交后关闭, 由 create 设置
+            max_refills: 0,
+            status: EscrowStatus::Open, // 新创建的托管没有还价和成交
+            status_before_freeze: EscrowStatus::Open,
+            taker_allowlist_root: [0; 32], // 默认不限制, 由 create 设置
+            hashlock: [0; 32],             // 默认不是 HTLC, 由 create 设置
+            start_time: 0,                 // 默认立即可以成交, 由 create 设置
+            created_at: Clock::get()?.unix_timestamp,
+            commit_delay_slots: 0,   // 默认不启用 commit-reveal, 由 create 设置
+            reservation_window: 0,   // 默认不允许预约, 由 create 设置
+            bump,                    // 缓存的 bump 值
+            vault: self.vault.key(), // take 和 refund 按这个地址校验 vault
+            price_feed: Pubkey::default(), // 默认为固定价格, 按 Pyth 价格成交由 make_oracle 设置
+            quote_exponent: 0,
+            max_staleness: 0,
+            max_deviation_bps: 0, // 默认不检查价格区间, 由 set_price_band 设置
+            collection: Pubkey::default(), // 默认只接受 mint_b, collection 托管由 make_collection 设置
+            enforce_royalties: false,      // 默认不支付版税, 由 set_royalty_enforcement 开启
+            tag: 0,                        // 默认没有标签, 由 make_v2 设置
+            pair_indexed: false,           // 默认不计入 PairIndex, 由 make_v2 设置
+            cancel_reason: 0,              // 只在 cancel 保留托管时设置
+            cancelled_slot: 0,
+            direct_only: false,  // 默认允许通过 CPI 成交, 由 set_direct_only 设置
+            maker_paused: false, // 由 pause_offer 和 resume_offer 切换
+            stream_window: 0,    // 由 set_stream_window 设置
+            stream_receive: 0,
+            paid_b: 0,
+            stream_deadline: 0,
+            allow_operators: false, // 默认不允许运营方代为退还, 由 make_v2 设置
         });
 
         Ok(())
     }
 
-    // 把 token A 转账到 vault ATA 账户中
-    fn deposit_tokens(&self, amount: u64) -> Result<()> {
-        transfer_checked(
+    // 把 seed 加入 maker 的索引, 空间不够时由 rent_payer 补足租金后扩容
+    fn register_seed(&mut self, seed: u64, bump: u8) -> Result<()> {
+        require_gt!(
+            MakerRegistry::MAX_SEEDS,
+            self.registry.seeds.len(),
+            EscrowError::RegistryFull
+        );
+
+        let space = MakerRegistry::space(self.registry.seeds.len() + 1);
+        let info = self.registry.to_account_info();
+        if info.data_len() < space {
+            let lamports = Rent::get()?
+                .minimum_balance(space)
+                .saturating_sub(info.lamports());
+            if lamports > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        self.system_program.to_account_info(),
+                        Transfer {
+                            from: self.payer().to_account_info(),
+                            to: info.clone(),
+                        },
+                    ),
+                    lamports,
+                )?;
+            }
+            info.resize(space)?;
+        }
+
+        self.registry.maker = self.maker.key();
+        self.registry.bump = bump;
+        self.registry.seeds.push(seed);
+
+        Ok(())
+    }
+
+    // 由 rent_payer 和租金一起向 treasury 支付挂单费; 挂单费为 0 或 maker 被豁免时不调用系统程序
+    fn charge_listing_fee(&self) -> Result<()> {
+        let fee = self.config.listing_fee_lamports;
+        let exempt = self
+            .exempt_list
+            .as_ref()
+            .is_some_and(|exempt_list| exempt_list.is_exempt(&self.maker.key()));
+        if fee == 0 || exempt {
+            return Ok(());
+        }
+
+        let treasury = self.treasury.as_ref().ok_or(EscrowError::MissingTreasury)?;
+        system_program::transfer(
             CpiContext::new(
-                self.token_program.to_account_info(),
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.payer().to_account_info(),
+                    to: treasury.to_account_info(),
+                },
+            ),
+            fee,
+        )
+    }
+
+    // mint A 是否为 native mint(wSOL)
+    fn is_native(&self) -> bool {
+        self.mint_a.key() == native_mint::ID
+    }
+
+    // maker 可以存入的 token A 数量, native mint 时是 maker 的 SOL 余额
+    fn balance_a(&self) -> Result<u64> {
+        if self.is_native() {
+            return Ok(self.maker.lamports());
+        }
+
+        let maker_ata_a = self
+            .maker_ata_a
+            .as_ref()
+            .ok_or(EscrowError::MissingMakerAta)?;
+        Ok(maker_ata_a.amount)
+    }
+
+    // 把 token A 转账到 vault ATA 账户中, remaining_accounts 是 mint A 的 transfer hook 需要的额外账户
+    // pNFT 通过 Token Metadata 转入, vault 的 token record 由 rent_payer 支付租金创建
+    fn deposit_tokens(
+        &self,
+        amount: u64,
+        pnft: Option<&PnftAccounts<'_, 'info>>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.is_native() {
+            return self.wrap_sol(amount);
+        }
+
+        let maker_ata_a = self
+            .maker_ata_a
+            .as_ref()
+            .ok_or(EscrowError::MissingMakerAta)?;
+        if let Some(pnft) = pnft {
+            return pnft.transfer(
+                PnftTransfer {
+                    token: maker_ata_a.to_account_info(),
+                    token_owner: self.maker.to_account_info(),
+                    destination_token: self.vault.to_account_info(),
+                    destination_owner: self.escrow.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.maker.to_account_info(),
+                    payer: self.payer().to_account_info(),
+                    system_program: self.system_program.to_account_info(),
+                    token_program: self.token_program_a.to_account_info(),
+                    associated_token_program: self.associated_token_program.to_account_info(),
+                },
+                amount,
+                &[],
+            );
+        }
+
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_a.to_account_info(),
                 TransferChecked {
-                    from: self.maker_ata_a.to_account_info(),
+                    from: maker_ata_a.to_account_info(),
                     mint: self.mint_a.to_account_info(),
                     to: self.vault.to_account_info(),
                     authority: self.maker.to_account_info(),
                 },
-            ),
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
             amount,
             self.mint_a.decimals,
         )?;
 
         Ok(())
     }
+
+    // 把之后每次重新存入的 refill 个 token A 授权给 escrow PDA, 会覆盖 maker ATA 上已有的 delegate
+    fn approve_refills(&self, refill: u64, max_refills: u8) -> Result<()> {
+        let maker_ata_a = self
+            .maker_ata_a
+            .as_ref()
+            .ok_or(EscrowError::MissingMakerAta)?;
+        let allowance = refill
+            .checked_mul(max_refills as u64)
+            .ok_or(EscrowError::MathOverflow)?;
+
+        approve_checked(
+            CpiContext::new(
+                self.token_program_a.to_account_info(),
+                ApproveChecked {
+                    to: maker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    delegate: self.escrow.to_account_info(),
+                    authority: self.maker.to_account_info(),
+                },
+            ),
+            allowance,
+            self.mint_a.decimals,
+        )
+    }
+
+    // 把 maker 的 lamports 直接转入 vault, 再 sync_native 让 vault 的 amount 包含这些 lamports
+    fn wrap_sol(&self, amount: u64) -> Result<()> {
+        system_program::transfer(
+            CpiContext::new(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.maker.to_account_info(),
+                    to: self.vault.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        sync_native(CpiContext::new(
+            self.token_program_a.to_account_info(),
+            SyncNative {
+                account: self.vault.to_account_info(),
+            },
+        ))
+    }
+}
+
+// 荷兰拍卖的价格衰减参数, receive 会在 [decay_start, decay_end] 内线性下降到 end_receive
+pub(crate) struct DutchAuction {
+    pub decay_start: i64,
+    pub decay_end: i64,
+    pub end_receive: u64,
+}
+
+// 按 Pyth 价格成交的参数, taker 支付的 token B 数量在成交时由 price_feed 的价格计算
+pub(crate) struct OraclePeg {
+    pub price_feed: Pubkey,
+    pub quote_exponent: i32,
+    pub max_staleness: i64,
+}
+
+// 托管的定价方式, 除了 Fixed 都只能通过对应的 make_dutch, make_oracle 和 make_collection 创建
+pub(crate) enum PricingMode {
+    // 按 receive 固定价格成交
+    Fixed,
+    // receive 按 DutchAuction 的参数线性衰减
+    Dutch(DutchAuction),
+    // 按 Pyth 价格成交, 不使用 receive
+    Oracle(OraclePeg),
+    // mint_b 是 Metaplex collection NFT 的 mint, taker 用 collection 中的任意一个 NFT 成交
+    Collection,
+}
+
+// make_v2 的参数, 以后新增的可选参数只加入这个结构, 不再改变指令的签名; 字段的含义见 create
+// Option 为 None 和 make 中对应参数的默认值(0 或 Pubkey::default())相同, 因此 Default 就是最简单的固定价格托管
+// reserved 留给以后的字段, 必须全部为 0: 旧客户端序列化的数据长度不变, 新字段为 0 时和现在的行为相同
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct MakeArgs {
+    // 必须是第一个字段, Make 的 #[instruction(seed: u64)] 只读取参数的前 8 个字节
+    pub seed: u64,
+    pub receive: u64,
+    pub amount: u64,
+    pub expiry: Option<i64>,
+    pub allowed_taker: Option<Pubkey>,
+    pub taker_allowlist_root: Option<[u8; 32]>,
+    pub hashlock: Option<[u8; 32]>,
+    pub start_time: Option<i64>,
+    pub reject_freezable: Option<bool>,
+    pub receive_to: Option<Pubkey>,
+    pub refund_delegate: Option<Pubkey>,
+    pub commit_delay_slots: Option<u64>,
+    pub arbiter: Option<Pubkey>,
+    pub reservation_window: Option<i64>,
+    pub alt_payments: Vec<PaymentMint>,
+    pub recurring: bool,
+    pub max_refills: u8,
+    // 以下两个参数只有 make_v2 使用, 见 make_v2::handler; 从 reserved 中分出, 数据长度不变
+    pub pair_index: bool,
+    pub tag: u8,
+    // 只有 make_v2 使用, 允许 Config::operators 中的运营方代为 refund, 见 operator_refund; 同样从 reserved 中分出
+    pub allow_operators: bool,
+    pub reserved: [u8; 29],
+}
+
+// make 和 make_v2 共用, make 把位置参数原样放入 MakeArgs, make_v2 先用 MakerPrefs 填充没有指定的参数
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+    args: MakeArgs,
+) -> Result<MakeResult> {
+    require!(args.reserved == [0; 29], EscrowError::ReservedArgsNotZero);
+
+    let result = MakeResult {
+        escrow: ctx.accounts.escrow.key(),
+        bump: ctx.bumps.escrow,
+    };
+    create(ctx, args, PricingMode::Fixed)?;
+
+    Ok(result)
 }
 
-pub fn handler(ctx: Context<Make>, seed: u64, receive: u64, amount: u64) -> Result<()> {
-    // 验证存入的 token A 和期望换取的 token B 的数量都必须大于 0
-    require_gt!(receive, 0, EscrowError::InvalidAmount);
+// make, make_auto, make_dutch, make_oracle 和 make_collection 共用的创建流程, args 中为 None 的参数使用默认值
+// reject_freezable 为 true 时拒绝任何一个设置了 freeze authority 的 mint, 不写入托管数据
+// receive_to 为 None 时由 maker 自己接收 token B
+// refund_delegate 为 None 时只有 maker 可以退还
+// commit_delay_slots 为 None 或 0 时不启用 commit-reveal
+// arbiter 为 None 时没有仲裁人, taker 直接成交
+// reservation_window 为 None 或 0 时不允许预约
+// alt_payments 为空时只接受 mint_b, 否则最多再接受 3 个 mint, 每个都有自己的 receive
+// recurring 为 true 时成交后最多重新存入 max_refills 次, 为 false 时 max_refills 必须为 0
+// amount 为 Escrow::FULL_BALANCE 时存入 maker ATA 的全部余额, receive 仍然是这些 token A 的总价
+// pair_index, tag 和 allow_operators 只有 make_v2 使用, 这里不读取
+pub(crate) fn create<'info>(
+    ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+    args: MakeArgs,
+    pricing: PricingMode,
+) -> Result<()> {
+    let expiry = args.expiry.unwrap_or_default();
+    let allowed_taker = args.allowed_taker.unwrap_or_default();
+    let taker_allowlist_root = args.taker_allowlist_root.unwrap_or_default();
+    let hashlock = args.hashlock.unwrap_or_default();
+    let start_time = args.start_time.unwrap_or_default();
+    let reject_freezable = args.reject_freezable.unwrap_or_default();
+    let receive_to = args.receive_to.unwrap_or_default();
+    let refund_delegate = args.refund_delegate.unwrap_or_default();
+    let commit_delay_slots = args.commit_delay_slots.unwrap_or_default();
+    let arbiter = args.arbiter.unwrap_or_default();
+    let reservation_window = args.reservation_window.unwrap_or_default();
+    let MakeArgs {
+        seed,
+        receive,
+        amount,
+        alt_payments,
+        recurring,
+        max_refills,
+        ..
+    } = args;
+    let (dutch, oracle, collection) = match pricing {
+        PricingMode::Fixed => (None, None, false),
+        PricingMode::Dutch(dutch) => (Some(dutch), None, false),
+        PricingMode::Oracle(oracle) => (None, Some(oracle), false),
+        PricingMode::Collection => (None, None, true),
+    };
+
+    // 验证存入的 token A 和期望换取的 token B 的数量都必须大于 0; 按 Pyth 价格成交时不使用 receive
+    require!(receive > 0 || oracle.is_some(), EscrowError::InvalidAmount);
     require_gt!(amount, 0, EscrowError::InvalidAmount);
 
+    // 不允许用 token 换取它自己
+    require_keys_neq!(
+        ctx.accounts.mint_a.key(),
+        ctx.accounts.mint_b.key(),
+        EscrowError::IdenticalMints
+    );
+
+    // 其他支付 mint 不能重复, 也不能是 mint_a 或 mint_b; 荷兰拍卖的价格只对 mint_b 衰减, 因此不能同时使用
+    let mut mints = vec![ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()];
+    require!(
+        alt_payments.len() <= Escrow::MAX_ALT_PAYMENTS
+            && (alt_payments.is_empty() || dutch.is_none()),
+        EscrowError::InvalidPaymentMints
+    );
+    for payment in &alt_payments {
+        require!(
+            payment.is_set() && payment.receive > 0 && !mints.contains(&payment.mint),
+            EscrowError::InvalidPaymentMints
+        );
+        mints.push(payment.mint);
+    }
+
+    // 启用白名单时只能托管列表中的 mint
+    MintAllowlist::check(&ctx.accounts.mint_allowlist, &mints)?;
+    MintBlocklist::check(&ctx.accounts.mint_blocklist, &mints)?;
+
+    // 不可转账的 token 存不进 vault, 作为 token B 时托管也永远无法成交, 只能退还
+    require!(
+        !transfer::is_non_transferable(&ctx.accounts.mint_a.to_account_info())?
+            && !transfer::is_non_transferable(&ctx.accounts.mint_b.to_account_info())?,
+        EscrowError::NonTransferableMint
+    );
+
+    // freeze authority 可以冻结 vault, 之后 take 和 refund 都无法转出 token A
+    if reject_freezable {
+        require!(
+            ctx.accounts.mint_a.freeze_authority.is_none()
+                && ctx.accounts.mint_b.freeze_authority.is_none(),
+            EscrowError::FreezableMintRejected
+        );
+    }
+
+    // 过期时间为 0 表示永不过期, 否则必须晚于当前时间
+    require!(
+        expiry == 0 || expiry > Clock::get()?.unix_timestamp,
+        EscrowError::InvalidExpiry
+    );
+
+    // 设置了过期时间时, 开始时间必须早于过期时间, 否则托管永远无法成交
+    require!(
+        expiry == 0 || start_time < expiry,
+        EscrowError::InvalidStartTime
+    );
+
+    // HTLC 必须设置过期时间, 超时后 maker 才能取回 token A
+    require!(
+        hashlock == [0; 32] || expiry != 0,
+        EscrowError::InvalidExpiry
+    );
+
+    // 等待时间过长时 taker 的承诺长时间占用资金, 托管实际上无法成交
+    require_gte!(
+        Escrow::MAX_COMMIT_DELAY_SLOTS,
+        commit_delay_slots,
+        EscrowError::InvalidCommitDelay
+    );
+
+    // 仲裁人决定资金的去向, 不能是接收资金的一方; release_to_taker 不检查 preimage 和 commit, 因此不能和它们同时使用
+    if arbiter != Pubkey::default() {
+        let receiver = if receive_to == Pubkey::default() {
+            ctx.accounts.maker.key()
+        } else {
+            receive_to
+        };
+        require!(
+            arbiter != ctx.accounts.maker.key()
+                && arbiter != receiver
+                && hashlock == [0; 32]
+                && commit_delay_slots == 0,
+            EscrowError::InvalidArbiter
+        );
+    }
+
+    // 预约期间 settle 走 take 的流程, 不能用于只能通过 lock_take 或 reveal_take 成交的托管
+    require!(
+        (0..=Escrow::MAX_RESERVATION_WINDOW).contains(&reservation_window)
+            && (reservation_window == 0
+                || (arbiter == Pubkey::default() && commit_delay_slots == 0)),
+        EscrowError::InvalidReservationWindow
+    );
+
+    // 重新存入需要从 maker 的 ATA 转出, 不能用于直接包装 SOL 的 native mint
+    // 荷兰拍卖, 仲裁人和 HTLC 的条款只适用于一次成交: 衰减结束后价格不再变化, 锁定后不经过 take, preimage 成交时已经公开
+    require!(
+        recurring == (max_refills > 0)
+            && (!recurring
+                || (!ctx.accounts.is_native()
+                    && dutch.is_none()
+                    && arbiter == Pubkey::default()
+                    && hashlock == [0; 32])),
+        EscrowError::InvalidRecurring
+    );
+
+    // pNFT 只能通过 Token Metadata 转出 vault: 重新存入和仲裁人的 release_to_taker 都使用 transfer_checked, 不能用于 pNFT
+    let (pnft, remaining_accounts) =
+        pnft::split(ctx.remaining_accounts, &ctx.accounts.mint_a.key())?;
+    require!(
+        pnft.is_none() || (!recurring && arbiter == Pubkey::default()),
+        EscrowError::UnsupportedPnftEscrow
+    );
+
+    // 荷兰拍卖的价格只能从 receive 衰减到一个大于 0 的 end_receive
+    if let Some(dutch) = &dutch {
+        require!(
+            dutch.decay_end > dutch.decay_start
+                && dutch.end_receive > 0
+                && dutch.end_receive <= receive,
+            EscrowError::InvalidDutchAuction
+        );
+    }
+
+    // 价格只对 mint_b 计算, 不能和其他支付 mint 同时使用; 过旧的价格不能反映当前的市场价格
+    if let Some(oracle) = &oracle {
+        require!(
+            oracle.price_feed != Pubkey::default()
+                && (1..=Escrow::MAX_ORACLE_STALENESS).contains(&oracle.max_staleness)
+                && alt_payments.is_empty(),
+            EscrowError::InvalidOracleConfig
+        );
+    }
+
+    // collection NFT 本身也是 NFT, 防止把普通 token 的 mint 当作 collection
+    if collection {
+        require!(
+            ctx.accounts.mint_b.decimals == 0 && ctx.accounts.mint_b.supply == 1,
+            EscrowError::NotAnNft
+        );
+    }
+
+    // 余额不足时在写入托管数据和存入 token A 之前返回可读的错误
+    // Escrow::FULL_BALANCE 按执行时的余额存入; SOL 模式的余额是 maker 的 lamports, 还要支付租金, 不能全部存入
+    let balance_a = ctx.accounts.balance_a()?;
+    let amount = if amount == Escrow::FULL_BALANCE {
+        require!(!ctx.accounts.is_native(), EscrowError::InvalidAmount);
+        require_gt!(balance_a, 0, EscrowError::EmptyMakerBalance);
+        balance_a
+    } else {
+        if balance_a < amount {
+            msg!("Maker is short {} token A", amount - balance_a);
+            return err!(EscrowError::InsufficientMakerBalance);
+        }
+        amount
+    };
+
+    // mint A 带有 TransferFee 扩展时 vault 只收到扣除转账手续费后的数量, 托管按实际存入的数量记录
+    let fee = transfer::fee_for(&ctx.accounts.mint_a.to_account_info(), amount)?;
+    let net_amount = amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+    require_gt!(net_amount, 0, EscrowError::InvalidAmount);
+
     // 存数据
-    ctx.accounts
-        .populate_escrow(seed, receive, ctx.bumps.escrow)?;
+    ctx.accounts.populate_escrow(
+        seed,
+        receive,
+        net_amount,
+        expiry,
+        allowed_taker,
+        ctx.bumps.escrow,
+    )?;
+
+    ctx.accounts.escrow.taker_allowlist_root = taker_allowlist_root;
+    ctx.accounts.escrow.hashlock = hashlock;
+    ctx.accounts.escrow.start_time = start_time;
+    if receive_to != Pubkey::default() {
+        ctx.accounts.escrow.receive_to = receive_to;
+    }
+    ctx.accounts.escrow.refund_delegate = refund_delegate;
+    ctx.accounts.escrow.commit_delay_slots = commit_delay_slots;
+    ctx.accounts.escrow.arbiter = arbiter;
+    ctx.accounts.escrow.reservation_window = reservation_window;
+    ctx.accounts.escrow.recurring = recurring;
+    ctx.accounts.escrow.max_refills = max_refills;
+    for (slot, payment) in ctx
+        .accounts
+        .escrow
+        .alt_payments
+        .iter_mut()
+        .zip(alt_payments)
+    {
+        *slot = payment;
+    }
+
+    // 荷兰拍卖额外记录价格衰减参数
+    if let Some(dutch) = dutch {
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.decay_start = dutch.decay_start;
+        escrow.decay_end = dutch.decay_end;
+        escrow.end_receive = dutch.end_receive;
+    }
+
+    // 按 Pyth 价格成交时记录 price feed 和价格的检查参数
+    if let Some(oracle) = oracle {
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.price_feed = oracle.price_feed;
+        escrow.quote_exponent = oracle.quote_exponent;
+        escrow.max_staleness = oracle.max_staleness;
+    }
+
+    // collection 托管记录 collection, 成交时检查 taker 的 NFT 的 Metadata
+    if collection {
+        ctx.accounts.escrow.collection = ctx.accounts.mint_b.key();
+    }
 
     // 存入 token A
-    ctx.accounts.deposit_tokens(amount)?;
+    // vault 可能已经被别人创建并转入了代币(wSOL 的 vault 也可能直接收到 lamports), 这些代币不计入 amount, 不会被 taker 买走:
+    // 全部成交关闭 vault 时作为多余代币退还给 maker, refund 时和存入的代币一起退还
+    ctx.accounts
+        .deposit_tokens(amount, pnft.as_ref(), remaining_accounts)?;
+    if recurring {
+        ctx.accounts.approve_refills(net_amount, max_refills)?;
+    }
+
+    ctx.accounts.charge_listing_fee()?;
+
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+    ctx.accounts.stats.record_make();
+    ctx.accounts.register_seed(seed, ctx.bumps.registry)?;
+
+    // 转账成功后再记录事件, 保证事件反映的是实际发生的转账
+    emit_cpi!(MakeEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        amount,
+        net_amount,
+        receive,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
 
     Ok(())
 }
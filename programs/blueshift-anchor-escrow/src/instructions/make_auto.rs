@@ -0,0 +1,65 @@
+// 嵌套 Make 账户列表时还需要 derive(Accounts) 为它生成的 MakeBumps 等类型, 因此整体导入
+use crate::instructions::make::*;
+use crate::{errors::EscrowError, pda::COUNTER_SEED, state::MakerCounter};
+use anchor_lang::prelude::*;
+
+// 在 Make 的账户列表之外增加 maker 的计数器, escrow 仍然由 Make 中的 seed 约束派生
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeAuto<'info> {
+    pub make: Make<'info>,
+
+    // 每个 maker 一个计数器, 第一次调用 make_auto 时由 rent_payer 支付租金创建
+    // seed 必须是计数器的当前值, 并发的两个交易中后执行的会失败, 客户端重新读取计数器即可
+    #[account(
+        init_if_needed,
+        payer = make.payer(),
+        space = MakerCounter::INIT_SPACE + MakerCounter::DISCRIMINATOR.len(),
+        seeds = [COUNTER_SEED, make.maker.key().as_ref()],
+        bump,
+        constraint = maker_counter.next_seed == seed @ EscrowError::InvalidSeed,
+    )]
+    pub maker_counter: Account<'info, MakerCounter>,
+
+    // 创建计数器需要系统程序
+    pub system_program: Program<'info, System>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, MakeAuto<'info>>,
+    seed: u64,
+    receive: u64,
+    amount: u64,
+    expiry: i64,
+    allowed_taker: Pubkey,
+    taker_allowlist_root: [u8; 32],
+    hashlock: [u8; 32],
+    start_time: i64,
+) -> Result<()> {
+    // 计数器递增, 保证每个 seed 只会被 make_auto 使用一次
+    let maker_counter = &mut ctx.accounts.maker_counter;
+    maker_counter.maker = ctx.accounts.make.maker.key();
+    maker_counter.bump = ctx.bumps.maker_counter;
+    maker_counter.next_seed = seed.checked_add(1).ok_or(EscrowError::MathOverflow)?;
+
+    // 其余流程和 make 完全相同
+    let make_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.make,
+        ctx.remaining_accounts,
+        ctx.bumps.make,
+    );
+    let args = MakeArgs {
+        seed,
+        receive,
+        amount,
+        expiry: Some(expiry),
+        allowed_taker: Some(allowed_taker),
+        taker_allowlist_root: Some(taker_allowlist_root),
+        hashlock: Some(hashlock),
+        start_time: Some(start_time),
+        ..Default::default()
+    };
+    create(make_ctx, args, PricingMode::Fixed)
+}
@@ -0,0 +1,207 @@
+use crate::{
+    errors::EscrowError,
+    events::MakeBasketEvent,
+    pda::{BASKET_SEED, CONFIG_SEED, MINT_ALLOWLIST_SEED, MINT_BLOCKLIST_SEED},
+    state::{BasketLeg, Config, EscrowBasket, MintAllowlist, MintBlocklist, MAX_BASKET_LEGS},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+// 创建一篮子托管: maker 存入最多 MAX_BASKET_LEGS 种 token, 换取 receive 个 token B, 见 EscrowBasket
+// remaining_accounts: 按 amounts 的顺序每种 token 传入 [mint, maker 的 token 账户, vault] 3 个账户
+// vault 是 basket 的 ATA, 由本指令创建; 所有 mint 必须属于 token_program_a, 不支持带 transfer hook 的 mint
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeBasket<'info> {
+    // 签名账户, 存入所有 token 并支付 basket 和 vault 的租金
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        init,
+        payer = maker,
+        space = EscrowBasket::INIT_SPACE + EscrowBasket::DISCRIMINATOR.len(),
+        seeds = [BASKET_SEED, maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub basket: Box<Account<'info, EscrowBasket>>,
+
+    // 换取的 Token B 的 mint 账户
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 白名单, 由 seeds 约束地址; 从未设置过时是空的系统账户, 见 MintAllowlist::check
+    #[account(seeds = [MINT_ALLOWLIST_SEED], bump)]
+    pub mint_allowlist: UncheckedAccount<'info>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理篮子中所有 mint 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeBasket<'info> {
+    // 检查一种 token 的账户并把 amount 存入它的 vault, 返回 vault 实际收到的数量
+    fn deposit_leg(&self, accounts: &'info [AccountInfo<'info>], amount: u64) -> Result<u64> {
+        let (mint, maker_token, vault) = (&accounts[0], &accounts[1], &accounts[2]);
+        let mint_account = InterfaceAccount::<Mint>::try_from(mint)?;
+        require_keys_eq!(
+            *mint.owner,
+            self.token_program_a.key(),
+            EscrowError::InvalidBasketAccounts
+        );
+        require!(
+            !transfer::is_non_transferable(mint)?,
+            EscrowError::NonTransferableMint
+        );
+
+        let maker_account = InterfaceAccount::<TokenAccount>::try_from(maker_token)?;
+        require_keys_eq!(
+            maker_account.mint,
+            mint.key(),
+            EscrowError::InvalidBasketAccounts
+        );
+        if maker_account.amount < amount {
+            msg!(
+                "Maker is short {} of mint {}",
+                amount - maker_account.amount,
+                mint.key()
+            );
+            return err!(EscrowError::InsufficientMakerBalance);
+        }
+
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address_with_program_id(
+                &self.basket.key(),
+                &mint.key(),
+                self.token_program_a.key
+            ),
+            EscrowError::InvalidBasketAccounts
+        );
+        associated_token::create(CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            associated_token::Create {
+                payer: self.maker.to_account_info(),
+                associated_token: vault.clone(),
+                authority: self.basket.to_account_info(),
+                mint: mint.clone(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.token_program_a.to_account_info(),
+            },
+        ))?;
+
+        let fee = transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: maker_token.clone(),
+                    mint: mint.clone(),
+                    to: vault.clone(),
+                    authority: self.maker.to_account_info(),
+                },
+            ),
+            amount,
+            mint_account.decimals,
+        )?;
+
+        amount
+            .checked_sub(fee)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, MakeBasket<'info>>,
+    seed: u64,
+    receive: u64,
+    amounts: Vec<u64>,
+) -> Result<()> {
+    require!(
+        !amounts.is_empty() && amounts.len() <= MAX_BASKET_LEGS,
+        EscrowError::InvalidBasketSize
+    );
+    require_eq!(
+        ctx.remaining_accounts.len(),
+        amounts.len() * 3,
+        EscrowError::InvalidBasketAccounts
+    );
+    require_gt!(receive, 0, EscrowError::InvalidAmount);
+    require!(
+        amounts.iter().all(|amount| *amount > 0),
+        EscrowError::InvalidAmount
+    );
+
+    // 所有 mint 互不相同, 也不能是 mint B; 白名单和黑名单检查包含 mint B
+    let mint_b = ctx.accounts.mint_b.key();
+    let mints: Vec<Pubkey> = ctx
+        .remaining_accounts
+        .chunks_exact(3)
+        .map(|accounts| accounts[0].key())
+        .collect();
+    for (i, mint) in mints.iter().enumerate() {
+        require!(
+            *mint != mint_b && !mints[..i].contains(mint),
+            EscrowError::DuplicateBasketMint
+        );
+    }
+    let checked = [mints.as_slice(), &[mint_b]].concat();
+    MintAllowlist::check(&ctx.accounts.mint_allowlist, &checked)?;
+    MintBlocklist::check(&ctx.accounts.mint_blocklist, &checked)?;
+    require!(
+        !transfer::is_non_transferable(&ctx.accounts.mint_b.to_account_info())?,
+        EscrowError::NonTransferableMint
+    );
+
+    let mut legs = [BasketLeg::default(); MAX_BASKET_LEGS];
+    for (i, (accounts, amount)) in ctx
+        .remaining_accounts
+        .chunks_exact(3)
+        .zip(&amounts)
+        .enumerate()
+    {
+        let net_amount = ctx.accounts.deposit_leg(accounts, *amount)?;
+        require_gt!(net_amount, 0, EscrowError::InvalidAmount);
+        legs[i] = BasketLeg {
+            mint: mints[i],
+            amount: net_amount,
+        };
+    }
+
+    let maker = ctx.accounts.maker.key();
+    ctx.accounts.basket.set_inner(EscrowBasket {
+        seed,
+        maker,
+        mint_b,
+        receive,
+        legs,
+        leg_count: amounts.len() as u8,
+        bump: ctx.bumps.basket,
+    });
+
+    emit_cpi!(MakeBasketEvent {
+        basket: ctx.accounts.basket.key(),
+        maker,
+        mint_b,
+        mints,
+        amounts: legs[..amounts.len()].iter().map(|leg| leg.amount).collect(),
+        receive,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
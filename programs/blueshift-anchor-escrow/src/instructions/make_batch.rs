@@ -0,0 +1,396 @@
+use crate::{
+    errors::EscrowError,
+    events::MakeEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, MINT_ALLOWLIST_SEED, MINT_BLOCKLIST_SEED, REGISTRY_SEED,
+        STATS_SEED,
+    },
+    realloc,
+    state::{
+        Config, Escrow, EscrowStatus, GlobalStats, MakerRegistry, MintAllowlist, MintBlocklist,
+        PaymentMint,
+    },
+    transfer,
+};
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Allocate, Assign, CreateAccount, Transfer},
+};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+// 一笔交易中最多创建的托管数
+// 每一档需要额外的 2 个账户和约 35k CU, 不使用地址查找表时一笔交易大约只能放下 5 档, 10 档需要查找表和更高的计算预算
+pub const MAX_BATCH_SIZE: usize = 10;
+
+// 批量挂单中的一档价格, 期限和 taker 等条款都使用 make 的默认值
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MakeEntry {
+    // 这一档托管的 seed
+    pub seed: u64,
+    // 期望收到的 token B 数量
+    pub receive: u64,
+    // 存入的 token A 数量
+    pub amount: u64,
+}
+
+// 用同一个 mint 对一次创建多个托管, 例如做市商同时挂出多档价格; 任何一档失败时整笔交易回滚
+// anchor 的 init 不能循环, 每一档的账户在 remaining_accounts 中按 entries 的顺序传入:
+// [escrow_0, vault_0, escrow_1, vault_1, ...], 都是可写的空账户, 之后是 mint A 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MakeBatch<'info> {
+    // 签名账户, 所有托管的创建者
+    pub maker: Signer<'info>,
+
+    // 支付所有 escrow 和 vault 租金的账户; 没有赞助时传 maker 自己
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    // 存入的 Token A 的 mint 账户
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    // 换取的 Token B 的 mint 账户
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 创建者所存入的 Token A 的 ATA 账户, 和 make 不同, native mint 也必须从已经包装的 wSOL 账户存入
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 白名单, 由 seeds 约束地址; 从未设置过时是空的系统账户, 见 MintAllowlist::check
+    #[account(seeds = [MINT_ALLOWLIST_SEED], bump)]
+    pub mint_allowlist: UncheckedAccount<'info>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 全局统计账户, 第一次使用时由 rent_payer 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    // maker 的托管索引, 所有新 seed 按 entries 的顺序加入
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = registry.data_len().max(MakerRegistry::space(0)),
+        seeds = [REGISTRY_SEED, maker.key().as_ref()],
+        bump,
+    )]
+    pub registry: Box<Account<'info, MakerRegistry>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>,
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeBatch<'info> {
+    // 两个 mint 对所有档位都相同, 只检查一次
+    fn check_mints(&self) -> Result<()> {
+        let mints = [self.mint_a.key(), self.mint_b.key()];
+        require_keys_neq!(mints[0], mints[1], EscrowError::IdenticalMints);
+
+        MintAllowlist::check(&self.mint_allowlist, &mints)?;
+        MintBlocklist::check(&self.mint_blocklist, &mints)?;
+
+        require!(
+            !transfer::is_non_transferable(&self.mint_a.to_account_info())?
+                && !transfer::is_non_transferable(&self.mint_b.to_account_info())?,
+            EscrowError::NonTransferableMint
+        );
+
+        Ok(())
+    }
+
+    // 和 anchor 的 init 一样创建 escrow PDA; 地址上已经有 lamports 时 create_account 会失败, 改为补足租金后 allocate 和 assign
+    fn create_escrow(&self, escrow: &AccountInfo<'info>, seed: u64, bump: u8) -> Result<()> {
+        let seed = seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] =
+            &[&[ESCROW_SEED, self.maker.key.as_ref(), seed.as_ref(), &[bump]]];
+        let space = Escrow::INIT_SPACE + Escrow::DISCRIMINATOR.len();
+        let rent = Rent::get()?.minimum_balance(space);
+        let system_program = self.system_program.to_account_info();
+
+        if escrow.lamports() == 0 {
+            return system_program::create_account(
+                CpiContext::new_with_signer(
+                    system_program,
+                    CreateAccount {
+                        from: self.rent_payer.to_account_info(),
+                        to: escrow.clone(),
+                    },
+                    signer_seeds,
+                ),
+                rent,
+                space as u64,
+                &crate::ID,
+            );
+        }
+
+        let shortfall = rent.saturating_sub(escrow.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    Transfer {
+                        from: self.rent_payer.to_account_info(),
+                        to: escrow.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                Allocate {
+                    account_to_allocate: escrow.clone(),
+                },
+                signer_seeds,
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system_program,
+                Assign {
+                    account_to_assign: escrow.clone(),
+                },
+                signer_seeds,
+            ),
+            &crate::ID,
+        )
+    }
+
+    // 创建 escrow 的 vault ATA, 和 make 一样允许已经被别人提前创建, 其中的代币不计入 amount
+    fn create_vault(&self, escrow: &AccountInfo<'info>, vault: &AccountInfo<'info>) -> Result<()> {
+        associated_token::create_idempotent(CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            associated_token::Create {
+                payer: self.rent_payer.to_account_info(),
+                associated_token: vault.clone(),
+                authority: escrow.clone(),
+                mint: self.mint_a.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.token_program_a.to_account_info(),
+            },
+        ))
+    }
+
+    // 把 token A 从 maker 转入 vault, 返回 vault 实际收到的数量
+    fn deposit_tokens(
+        &self,
+        vault: &AccountInfo<'info>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let fee = transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.maker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    to: vault.clone(),
+                    authority: self.maker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_a.decimals,
+        )?;
+
+        amount
+            .checked_sub(fee)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 把所有新 seed 加入 maker 的索引, 一次扩容到需要的大小
+    fn register_seeds(&mut self, seeds: &[u64], bump: u8) -> Result<()> {
+        let len = self.registry.seeds.len() + seeds.len();
+        require_gte!(MakerRegistry::MAX_SEEDS, len, EscrowError::RegistryFull);
+
+        let space = MakerRegistry::space(len);
+        if self.registry.to_account_info().data_len() < space {
+            realloc::resize(
+                &self.registry.to_account_info(),
+                &self.rent_payer.to_account_info(),
+                &self.system_program.to_account_info(),
+                space,
+            )?;
+        }
+
+        self.registry.maker = self.maker.key();
+        self.registry.bump = bump;
+        self.registry.seeds.extend_from_slice(seeds);
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, MakeBatch<'info>>,
+    entries: Vec<MakeEntry>,
+) -> Result<()> {
+    require!(
+        !entries.is_empty() && entries.len() <= MAX_BATCH_SIZE,
+        EscrowError::InvalidBatchSize
+    );
+    require_gte!(
+        ctx.remaining_accounts.len(),
+        entries.len() * 2,
+        EscrowError::InvalidBatchAccounts
+    );
+    let (levels, hook_accounts) = ctx.remaining_accounts.split_at(entries.len() * 2);
+
+    ctx.accounts.check_mints()?;
+
+    for (i, entry) in entries.iter().enumerate() {
+        require_gt!(entry.receive, 0, EscrowError::InvalidAmount);
+        require_gt!(entry.amount, 0, EscrowError::InvalidAmount);
+        require!(
+            entries[..i].iter().all(|other| other.seed != entry.seed),
+            EscrowError::DuplicateBatchSeed
+        );
+    }
+
+    // 余额不足时在创建任何账户之前返回可读的错误
+    let total = entries
+        .iter()
+        .try_fold(0u64, |total, entry| total.checked_add(entry.amount))
+        .ok_or(EscrowError::MathOverflow)?;
+    let balance_a = ctx.accounts.maker_ata_a.amount;
+    if balance_a < total {
+        msg!("Maker is short {} token A", total - balance_a);
+        return err!(EscrowError::InsufficientMakerBalance);
+    }
+
+    let maker = ctx.accounts.maker.key();
+    let mint_a = ctx.accounts.mint_a.key();
+    let mint_b = ctx.accounts.mint_b.key();
+    let now = Clock::get()?.unix_timestamp;
+
+    for (entry, accounts) in entries.iter().zip(levels.chunks_exact(2)) {
+        let (escrow, vault) = (&accounts[0], &accounts[1]);
+
+        // 账户必须按 entries 的顺序传入, 地址和 seed 对不上时拒绝, 而不是为错误的 seed 创建托管
+        let (address, bump) = Pubkey::find_program_address(
+            &[ESCROW_SEED, maker.as_ref(), &entry.seed.to_le_bytes()],
+            &crate::ID,
+        );
+        require_keys_eq!(escrow.key(), address, EscrowError::InvalidBatchAccounts);
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address_with_program_id(
+                &address,
+                &mint_a,
+                ctx.accounts.token_program_a.key
+            ),
+            EscrowError::InvalidBatchAccounts
+        );
+
+        ctx.accounts.create_escrow(escrow, entry.seed, bump)?;
+        ctx.accounts.create_vault(escrow, vault)?;
+        let net_amount = ctx
+            .accounts
+            .deposit_tokens(vault, entry.amount, hook_accounts)?;
+        require_gt!(net_amount, 0, EscrowError::InvalidAmount);
+
+        // 账户由本程序手动创建, 直接写入带 discriminator 的托管数据
+        let state = Escrow {
+            version: Escrow::VERSION,
+            seed: entry.seed,
+            maker,
+            rent_payer: ctx.accounts.rent_payer.key(),
+            receive_to: maker,
+            mint_a,
+            mint_b,
+            receive: entry.receive,
+            deposited: net_amount,
+            amount: net_amount,
+            expiry: 0,
+            start_time: 0,
+            created_at: now,
+            commit_delay_slots: 0,
+            reservation_window: 0,
+            allowed_taker: Pubkey::default(),
+            refund_delegate: Pubkey::default(),
+            arbiter: Pubkey::default(),
+            taker_allowlist_root: [0; 32],
+            hashlock: [0; 32],
+            decay_start: 0,
+            decay_end: 0,
+            end_receive: 0,
+            alt_payments: [PaymentMint::default(); 3],
+            locked_taker: Pubkey::default(),
+            locked_at: 0,
+            reserved_taker: Pubkey::default(),
+            reservation_expiry: 0,
+            bond: 0,
+            recurring: false,
+            max_refills: 0,
+            status: EscrowStatus::Open,
+            status_before_freeze: EscrowStatus::Open,
+            bump,
+            vault: vault.key(),
+            price_feed: Pubkey::default(),
+            quote_exponent: 0,
+            max_staleness: 0,
+            max_deviation_bps: 0,
+            collection: Pubkey::default(),
+            enforce_royalties: false,
+            tag: 0,
+            pair_indexed: false,
+            cancel_reason: 0,
+            cancelled_slot: 0,
+            direct_only: false,
+            maker_paused: false,
+            stream_window: 0,
+            stream_receive: 0,
+            paid_b: 0,
+            stream_deadline: 0,
+            allow_operators: false,
+        };
+        state.try_serialize(&mut &mut escrow.try_borrow_mut_data()?[..])?;
+
+        ctx.accounts.stats.record_make();
+
+        emit_cpi!(MakeEvent {
+            escrow: escrow.key(),
+            status: state.status,
+            maker,
+            mint_a,
+            mint_b,
+            amount: entry.amount,
+            net_amount,
+            receive: entry.receive,
+            timestamp: now,
+        });
+    }
+
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+    let seeds: Vec<u64> = entries.iter().map(|entry| entry.seed).collect();
+    ctx.accounts.register_seeds(&seeds, ctx.bumps.registry)?;
+
+    Ok(())
+}
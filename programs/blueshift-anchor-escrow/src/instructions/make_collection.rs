@@ -0,0 +1,18 @@
+use crate::instructions::make::{create, Make, MakeArgs, PricingMode};
+use anchor_lang::prelude::*;
+
+// collection 托管使用和 make 相同的账户列表, mint_b 传入 Metaplex collection NFT 的 mint
+// taker 通过 take_collection 用 collection 中任意一个经过验证的 NFT 换取 amount 个 token A, receive 固定为 1
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+    seed: u64,
+    amount: u64,
+) -> Result<()> {
+    let args = MakeArgs {
+        seed,
+        receive: 1,
+        amount,
+        ..Default::default()
+    };
+    create(ctx, args, PricingMode::Collection)
+}
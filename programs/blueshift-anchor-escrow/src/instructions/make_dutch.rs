@@ -0,0 +1,26 @@
+use crate::instructions::make::{create, DutchAuction, Make, MakeArgs, PricingMode};
+use anchor_lang::prelude::*;
+
+// 荷兰拍卖使用和 make 相同的账户列表, 只是额外记录价格衰减参数
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+    seed: u64,
+    start_receive: u64,
+    end_receive: u64,
+    amount: u64,
+    decay_start: i64,
+    decay_end: i64,
+) -> Result<()> {
+    let args = MakeArgs {
+        seed,
+        receive: start_receive,
+        amount,
+        ..Default::default()
+    };
+    let dutch = DutchAuction {
+        decay_start,
+        decay_end,
+        end_receive,
+    };
+    create(ctx, args, PricingMode::Dutch(dutch))
+}
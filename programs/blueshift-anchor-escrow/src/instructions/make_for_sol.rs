@@ -0,0 +1,195 @@
+use crate::{
+    errors::EscrowError,
+    events::MakeEvent,
+    pda::{CONFIG_SEED, ESCROW_SEED, MINT_ALLOWLIST_SEED, MINT_BLOCKLIST_SEED},
+    state::{Config, Escrow, EscrowStatus, MintAllowlist, MintBlocklist, PaymentMint},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+// 和 Make 相同, 只是没有 mint B: taker 用 SOL 支付, escrow.mint_b 记录为 Pubkey::default()
+// mint A 带有 transfer hook 时, 在 remaining_accounts 中传入 hook 需要的额外账户
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeForSol<'info> {
+    // 签名账户, 即创建托管的账户
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 见 make
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    // 托管 PDA 数据账户, 种子和 make 相同, 两种模式共用同一个地址空间
+    #[account(
+        init,
+        payer = rent_payer,
+        space = Escrow::INIT_SPACE + Escrow::DISCRIMINATOR.len(),
+        seeds = [ESCROW_SEED, maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // 存入的 Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    // 创建者所存入的 Token A 的 ATA 账户
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+
+    // 资金托管 ATA 账户, 和 make 一样允许已经被别人提前创建, 其中的代币不计入 amount
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 白名单, 由 seeds 约束地址; 从未设置过时是空的系统账户, 见 MintAllowlist::check
+    #[account(seeds = [MINT_ALLOWLIST_SEED], bump)]
+    pub mint_allowlist: UncheckedAccount<'info>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, MakeForSol<'info>>,
+    seed: u64,
+    receive_lamports: u64,
+    amount: u64,
+) -> Result<()> {
+    require_gt!(receive_lamports, 0, EscrowError::InvalidAmount);
+    require_gt!(amount, 0, EscrowError::InvalidAmount);
+
+    // 不可转账的 token 存不进 vault
+    require!(
+        !transfer::is_non_transferable(&ctx.accounts.mint_a.to_account_info())?,
+        EscrowError::NonTransferableMint
+    );
+
+    // 启用白名单时 mint A 必须在列表中, taker 支付的 SOL 不是 mint, 不做检查
+    MintAllowlist::check(&ctx.accounts.mint_allowlist, &[ctx.accounts.mint_a.key()])?;
+    MintBlocklist::check(&ctx.accounts.mint_blocklist, &[ctx.accounts.mint_a.key()])?;
+
+    // 余额不足时在写入托管数据和存入 token A 之前返回可读的错误
+    let balance_a = ctx.accounts.maker_ata_a.amount;
+    if balance_a < amount {
+        msg!("Maker is short {} token A", amount - balance_a);
+        return err!(EscrowError::InsufficientMakerBalance);
+    }
+
+    // 托管按 vault 实际收到的数量记录, 见 make
+    let fee = transfer::fee_for(&ctx.accounts.mint_a.to_account_info(), amount)?;
+    let net_amount = amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+    require_gt!(net_amount, 0, EscrowError::InvalidAmount);
+
+    // receive 以 lamports 为单位, 其余字段和固定价格的 make 一致
+    ctx.accounts.escrow.set_inner(Escrow {
+        version: Escrow::VERSION,
+        seed,
+        maker: ctx.accounts.maker.key(),
+        rent_payer: ctx.accounts.rent_payer.key(),
+        receive_to: ctx.accounts.maker.key(), // take_for_sol 直接把 lamports 转给 maker
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: Pubkey::default(),
+        receive: receive_lamports,
+        deposited: net_amount,
+        amount: net_amount,
+        expiry: 0,
+        start_time: 0,
+        created_at: Clock::get()?.unix_timestamp,
+        commit_delay_slots: 0,
+        reservation_window: 0,
+        allowed_taker: Pubkey::default(),
+        refund_delegate: Pubkey::default(),
+        arbiter: Pubkey::default(),
+        taker_allowlist_root: [0; 32],
+        hashlock: [0; 32],
+        decay_start: 0,
+        decay_end: 0,
+        end_receive: 0,
+        alt_payments: [PaymentMint::default(); 3],
+        locked_taker: Pubkey::default(),
+        locked_at: 0,
+        reserved_taker: Pubkey::default(),
+        reservation_expiry: 0,
+        bond: 0,
+        recurring: false,
+        max_refills: 0,
+        status: EscrowStatus::Open,
+        status_before_freeze: EscrowStatus::Open,
+        bump: ctx.bumps.escrow,
+        vault: ctx.accounts.vault.key(),
+        price_feed: Pubkey::default(),
+        quote_exponent: 0,
+        max_staleness: 0,
+        max_deviation_bps: 0,
+        collection: Pubkey::default(),
+        enforce_royalties: false,
+        tag: 0,
+        pair_indexed: false,
+        cancel_reason: 0,
+        cancelled_slot: 0,
+        direct_only: false,
+        maker_paused: false,
+        stream_window: 0,
+        stream_receive: 0,
+        paid_b: 0,
+        stream_deadline: 0,
+        allow_operators: false,
+    });
+
+    // 存入 token A
+    transfer::transfer_checked(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.maker_ata_a.to_account_info(),
+                mint: ctx.accounts.mint_a.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.maker.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount,
+        ctx.accounts.mint_a.decimals,
+    )?;
+
+    emit_cpi!(MakeEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: Pubkey::default(),
+        amount,
+        net_amount,
+        receive: receive_lamports,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
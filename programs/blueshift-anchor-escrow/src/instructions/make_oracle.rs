@@ -0,0 +1,26 @@
+use crate::instructions::make::{create, Make, MakeArgs, OraclePeg, PricingMode};
+use anchor_lang::prelude::*;
+
+// 按 Pyth 价格成交的托管使用和 make 相同的账户列表, 不记录 receive
+// taker 支付的 token B 在成交时由 price_feed 的价格计算, 见 Escrow::oracle_receive
+// quote_exponent 是 price_feed 的价格指数, max_staleness 是成交时价格最多已经发布的秒数
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+    seed: u64,
+    amount: u64,
+    price_feed: Pubkey,
+    quote_exponent: i32,
+    max_staleness: i64,
+) -> Result<()> {
+    let args = MakeArgs {
+        seed,
+        amount,
+        ..Default::default()
+    };
+    let oracle = OraclePeg {
+        price_feed,
+        quote_exponent,
+        max_staleness,
+    };
+    create(ctx, args, PricingMode::Oracle(oracle))
+}
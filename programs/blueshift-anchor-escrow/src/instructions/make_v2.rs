@@ -0,0 +1,81 @@
+// 嵌套 Make 账户列表时还需要 derive(Accounts) 为它生成的 MakeBumps 等类型, 因此整体导入
+use crate::instructions::make::*;
+use crate::{
+    errors::EscrowError,
+    pda::{PAIR_INDEX_SEED, PREFS_SEED},
+    results::MakeResult,
+    state::{MakerPrefs, PairIndex},
+};
+use anchor_lang::prelude::*;
+
+// make_v2 的账户是 make 的全部账户加上可选的 MakerPrefs 和 PairIndex, 都不传时和 make 完全相同
+#[derive(Accounts)]
+pub struct MakeV2<'info> {
+    pub make: Make<'info>,
+
+    // maker 的默认挂单参数, 只能是这个 maker 自己的, 由 seeds 约束
+    #[account(seeds = [PREFS_SEED, make.maker.key().as_ref()], bump = prefs.bump)]
+    pub prefs: Option<Account<'info, MakerPrefs>>,
+
+    // 交易对的索引, pair_index 为 true 时必须传入, 第一次使用时由 rent_payer 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = make.payer(),
+        space = PairIndex::INIT_SPACE + PairIndex::DISCRIMINATOR.len(),
+        seeds = [PAIR_INDEX_SEED, make.mint_a.key().as_ref(), make.mint_b.key().as_ref()],
+        bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 创建 pair_index 需要, 和 make.system_program 是同一个账户
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, MakeV2<'info>>,
+    mut args: MakeArgs,
+) -> Result<MakeResult> {
+    require!(
+        !args.pair_index || ctx.accounts.pair_index.is_some(),
+        EscrowError::MissingPairIndex
+    );
+
+    // 只填充为 None 的参数, 指定为 Some 的值(包括 Some(0) 和 Some(false))始终优先
+    if let Some(prefs) = &ctx.accounts.prefs {
+        if args.expiry.is_none() && prefs.expiry_duration > 0 {
+            let expiry = Clock::get()?
+                .unix_timestamp
+                .checked_add(prefs.expiry_duration)
+                .ok_or(EscrowError::MathOverflow)?;
+            args.expiry = Some(expiry);
+        }
+        args.receive_to = args.receive_to.or(Some(prefs.receive_to));
+        args.reject_freezable = args.reject_freezable.or(Some(prefs.reject_freezable));
+    }
+    let (pair_index, tag, allow_operators) = (args.pair_index, args.tag, args.allow_operators);
+
+    // 其余流程和 make 完全相同, remaining_accounts 原样传给 make
+    let make_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.make,
+        ctx.remaining_accounts,
+        ctx.bumps.make,
+    );
+    let result = crate::instructions::make::handler(make_ctx, args)?;
+
+    let escrow = &mut ctx.accounts.make.escrow;
+    escrow.tag = tag;
+    escrow.allow_operators = allow_operators;
+    // 不传 pair_index 参数时即使传入了索引账户也不计入
+    if let (true, Some(index)) = (pair_index, ctx.accounts.pair_index.as_mut()) {
+        index.mint_a = escrow.mint_a;
+        index.mint_b = escrow.mint_b;
+        if let Some(bump) = ctx.bumps.pair_index {
+            index.bump = bump;
+        }
+        index.record_make(escrow.key(), escrow.receive, escrow.deposited);
+        escrow.pair_indexed = true;
+    }
+
+    Ok(result)
+}
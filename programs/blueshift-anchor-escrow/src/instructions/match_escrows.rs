@@ -0,0 +1,515 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::MatchEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED,
+        REGISTRY_SEED, STATS_SEED,
+    },
+    state::{Config, Escrow, GlobalStats, MakerRegistry, MintBlocklist, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 撮合两个方向相反的托管: escrow_x 用 token A 换 token B, escrow_y 用 token B 换 token A
+// 价格交叉时两个托管都全部成交, 撮合者不需要持有任何库存, 两个 vault 直接转给对方的 maker
+// 价格交叉产生的差额传入撮合者的账户时归撮合者, 否则退还给对应的 maker
+// 两边都按 Config::fee_for 收取协议手续费, 和 take 一样由收款的 maker 承担; 撮合不记录 UserStats, 启用档位时也按基础比例
+// 四个 maker 的 ATA 必须已经存在, 撮合者可以在同一笔交易中先用 ATA 程序的 create_idempotent 创建
+// remaining_accounts: mint A 和 mint B 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MatchEscrows<'info> {
+    // 签名账户, 发起撮合的第三方, 第一次使用时支付统计账户的租金
+    #[account(mut)]
+    pub matcher: Signer<'info>,
+
+    /// CHECK: escrow_x 的创建者, 由 escrow_x 的 seeds 约束
+    pub maker_x: UncheckedAccount<'info>,
+
+    /// CHECK: escrow_y 的创建者, 由 escrow_y 的 seeds 约束
+    pub maker_y: UncheckedAccount<'info>,
+
+    // 两个托管各自的租金支付者, 关闭时租金还给它们
+    #[account(mut)]
+    pub rent_payer_x: SystemAccount<'info>,
+    #[account(mut)]
+    pub rent_payer_y: SystemAccount<'info>,
+
+    // 出售 token A, 换取 token B 的托管
+    #[account(
+        mut,
+        close = rent_payer_x,
+        seeds = [ESCROW_SEED, maker_x.key().as_ref(), escrow_x.seed.to_le_bytes().as_ref()],
+        bump = escrow_x.bump,
+        constraint = escrow_x.maker == maker_x.key() @ EscrowError::InvalidMaker,
+        constraint = escrow_x.rent_payer == rent_payer_x.key() @ EscrowError::InvalidRentPayer,
+        constraint = escrow_x.mint_a == mint_a.key() @ EscrowError::InvalidMintA,
+        constraint = escrow_x.mint_b == mint_b.key() @ EscrowError::InvalidMintB,
+        constraint = escrow_x.status.is_fillable() @ EscrowError::EscrowFrozen,
+    )]
+    pub escrow_x: Box<Account<'info, Escrow>>,
+
+    // 出售 token B, 换取 token A 的托管
+    #[account(
+        mut,
+        close = rent_payer_y,
+        seeds = [ESCROW_SEED, maker_y.key().as_ref(), escrow_y.seed.to_le_bytes().as_ref()],
+        bump = escrow_y.bump,
+        constraint = escrow_y.maker == maker_y.key() @ EscrowError::InvalidMaker,
+        constraint = escrow_y.rent_payer == rent_payer_y.key() @ EscrowError::InvalidRentPayer,
+        constraint = escrow_y.mint_a == mint_b.key() && escrow_y.mint_b == mint_a.key() @ EscrowError::MintsDoNotMatch,
+        constraint = escrow_y.maker != escrow_x.maker @ EscrowError::SelfTrade,
+        constraint = escrow_y.status.is_fillable() @ EscrowError::EscrowFrozen,
+    )]
+    pub escrow_y: Box<Account<'info, Escrow>>,
+
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // escrow_x 存放 token A 的 vault
+    #[account(
+        mut,
+        address = escrow_x.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow_x,
+        token::token_program = token_program_a
+    )]
+    pub vault_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // escrow_y 存放 token B 的 vault
+    #[account(
+        mut,
+        address = escrow_y.vault @ EscrowError::InvalidVault,
+        token::mint = mint_b,
+        token::authority = escrow_y,
+        token::token_program = token_program_b
+    )]
+    pub vault_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // maker_x 的 token A ATA, 接收 vault_x 中剩余的 token A
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker_x,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_x_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // escrow_x 的 receive_to(默认是 maker_x)的 token B ATA, 接收 escrow_x 要求的 token B
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = escrow_x.receive_to,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_x_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // escrow_y 的 receive_to(默认是 maker_y)的 token A ATA, 接收 escrow_y 要求的 token A
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow_y.receive_to,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_y_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // maker_y 的 token B ATA, 接收 vault_y 中剩余的 token B
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker_y,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_y_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 撮合者接收差额的账户, 不传时对应的差额退还给 maker
+    #[account(
+        mut,
+        token::mint = mint_a,
+        token::authority = matcher,
+        token::token_program = token_program_a
+    )]
+    pub matcher_ata_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        token::mint = mint_b,
+        token::authority = matcher,
+        token::token_program = token_program_b
+    )]
+    pub matcher_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 协议配置账户, 用来检查协议是否暂停和计算手续费
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 token A 和 token B 手续费的 ATA 账户, 对应的手续费为 0 时可以不传
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program_a
+    )]
+    pub fee_vault_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program_b
+    )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 全局统计账户, 第一次使用时由 matcher 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = matcher,
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker_x 的托管索引, 由 seeds 约束地址, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker_x.key().as_ref()], bump)]
+    pub registry_x: UncheckedAccount<'info>,
+
+    /// CHECK: maker_y 的托管索引, 由 seeds 约束地址, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker_y.key().as_ref()], bump)]
+    pub registry_y: UncheckedAccount<'info>,
+
+    // escrow_x 计入了交易对索引时必须传入, 成交后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow_x.mint_a.as_ref(), escrow_x.mint_b.as_ref()],
+        bump = pair_index_x.bump,
+    )]
+    pub pair_index_x: Option<Box<Account<'info, PairIndex>>>,
+
+    // escrow_y 的交易对索引, 和 pair_index_x 相同
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow_y.mint_a.as_ref(), escrow_y.mint_b.as_ref()],
+        bump = pair_index_y.bump,
+    )]
+    pub pair_index_y: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理 mint_a 和 vault_x 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>, // 管理 mint_b 和 vault_y 的 token 程序
+    pub memo_program: Program<'info, Memo>,                // 接收方账户要求 memo 时使用
+    pub system_program: Program<'info, System>,
+}
+
+// 一个托管的 vault 结算时用到的账户
+struct Side<'a, 'info> {
+    escrow: &'a Account<'info, Escrow>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    mint: &'a InterfaceAccount<'info, Mint>,
+    token_program: AccountInfo<'info>,
+    memo_program: AccountInfo<'info>,
+    maker_ata: AccountInfo<'info>,
+    rent_payer: AccountInfo<'info>,
+    fee_vault: Option<AccountInfo<'info>>,
+}
+
+impl<'info> Side<'_, 'info> {
+    // 从 vault 转出 amount 给 to, 返回 mint 扣留的转账手续费; amount 为 0 时不做任何 CPI
+    fn send(
+        &self,
+        to: &AccountInfo<'info>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        let seed = self.escrow.seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            ESCROW_SEED,
+            self.escrow.maker.as_ref(),
+            seed.as_ref(),
+            &[self.escrow.bump],
+        ]];
+
+        transfer::memo_if_required(&self.memo_program, to, &self.escrow.key())?;
+        transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.clone(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    to: to.clone(),
+                    authority: self.escrow.to_account_info(),
+                },
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint.decimals,
+        )
+    }
+
+    // 把 paid 中扣除协议手续费 fee 的部分转给对手方, fee 转入手续费 ATA
+    // surplus 转给撮合者(没有传入撮合者账户时留给 maker), vault 中剩下的全部退还给 maker 后关闭 vault
+    // 返回对手方扣除协议手续费和转账手续费后实际收到的数量
+    fn settle(
+        &self,
+        counterparty: &AccountInfo<'info>,
+        paid: u64,
+        fee: u64,
+        matcher: Option<AccountInfo<'info>>,
+        surplus: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let received = paid.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+        let withheld = self.send(counterparty, received, remaining_accounts)?;
+        if fee > 0 {
+            let fee_vault = self
+                .fee_vault
+                .as_ref()
+                .ok_or(EscrowError::MissingFeeVault)?;
+            self.send(fee_vault, fee, remaining_accounts)?;
+        }
+
+        let bonus = match &matcher {
+            Some(matcher) => {
+                self.send(matcher, surplus, remaining_accounts)?;
+                surplus
+            }
+            None => 0,
+        };
+
+        // 别人直接转入 vault 的多余代币和 take 一样退还给 maker
+        let refund = self
+            .vault
+            .amount
+            .checked_sub(paid)
+            .and_then(|rest| rest.checked_sub(bonus))
+            .ok_or(EscrowError::MathOverflow)?;
+        self.send(&self.maker_ata, refund, remaining_accounts)?;
+
+        let seed = self.escrow.seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            ESCROW_SEED,
+            self.escrow.maker.as_ref(),
+            seed.as_ref(),
+            &[self.escrow.bump],
+        ]];
+        close_account(CpiContext::new_with_signer(
+            self.token_program.clone(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                destination: self.rent_payer.clone(),
+                authority: self.escrow.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        received
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+impl<'info> MatchEscrows<'info> {
+    fn side_x(&self) -> Side<'_, 'info> {
+        Side {
+            escrow: &self.escrow_x,
+            vault: &self.vault_x,
+            mint: &self.mint_a,
+            token_program: self.token_program_a.to_account_info(),
+            memo_program: self.memo_program.to_account_info(),
+            maker_ata: self.maker_x_ata_a.to_account_info(),
+            rent_payer: self.rent_payer_x.to_account_info(),
+            fee_vault: self.fee_vault_a.as_ref().map(|ata| ata.to_account_info()),
+        }
+    }
+
+    fn side_y(&self) -> Side<'_, 'info> {
+        Side {
+            escrow: &self.escrow_y,
+            vault: &self.vault_y,
+            mint: &self.mint_b,
+            token_program: self.token_program_b.to_account_info(),
+            memo_program: self.memo_program.to_account_info(),
+            maker_ata: self.maker_y_ata_b.to_account_info(),
+            rent_payer: self.rent_payer_y.to_account_info(),
+            fee_vault: self.fee_vault_b.as_ref().map(|ata| ata.to_account_info()),
+        }
+    }
+
+    // 两个托管都必须可以被对方的 maker 直接成交
+    fn check_matchable(&self, now: i64) -> Result<()> {
+        for escrow in [&self.escrow_x, &self.escrow_y] {
+            // HTLC 需要 preimage, 默克尔允许列表需要证明, 撮合时都无法提供
+            require!(
+                !escrow.is_hashlocked() && escrow.taker_allowlist_root == [0; 32],
+                EscrowError::UnmatchableEscrow
+            );
+            // 开启 commit-reveal 的托管只能由提前承诺的 taker 成交
+            require!(!escrow.requires_commit(), EscrowError::CommitRequired);
+            // 有仲裁人的托管需要锁定后等待结算, 不能直接撮合
+            require!(!escrow.is_arbitrated(), EscrowError::ArbiterRequired);
+            // 预约期间只有 reserved_taker 可以成交
+            require!(!escrow.is_reserved(), EscrowError::EscrowReserved);
+            escrow.check_started(now)?;
+            escrow.check_not_expired(now)?;
+        }
+        require!(
+            self.escrow_x.is_taker_allowed(&self.escrow_y.maker)
+                && self.escrow_y.is_taker_allowed(&self.escrow_x.maker),
+            EscrowError::UnauthorizedTaker
+        );
+
+        MintBlocklist::check(
+            &self.mint_blocklist,
+            &[self.mint_a.key(), self.mint_b.key()],
+        )?;
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, MatchEscrows<'info>>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.check_matchable(now)?;
+
+    // wSOL 的 vault 先同步直接转入的 lamports; 冻结的 vault 无法转出, 返回可读的错误
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_a.to_account_info(),
+        &mut ctx.accounts.vault_x,
+    )?;
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_b.to_account_info(),
+        &mut ctx.accounts.vault_y,
+    )?;
+    require!(
+        !ctx.accounts.vault_x.is_frozen() && !ctx.accounts.vault_y.is_frozen(),
+        EscrowError::VaultFrozen
+    );
+
+    // 按各自的当前价格计算两个托管全部成交需要的数量, 对方出售的数量必须足够
+    let (x, y) = (&ctx.accounts.escrow_x, &ctx.accounts.escrow_y);
+    // 价格区间需要 take 传入的 price_feed 账户检查
+    require!(
+        !x.has_price_band() && !y.has_price_band(),
+        EscrowError::PriceBandRequiresTake
+    );
+    // 版税需要 take 传入的 metadata 和 creator 账户
+    require!(
+        !x.enforce_royalties && !y.enforce_royalties,
+        EscrowError::RoyaltiesRequireTake
+    );
+    // 循环托管成交后需要 take 重新存入并保留 escrow, 撮合完就关闭, 会留下 maker ATA 上的授权
+    require!(
+        !x.recurring && !y.recurring,
+        EscrowError::RecurringRequiresTake
+    );
+    // direct_only 的托管不能通过 CPI 撮合, 这里没有 instructions sysvar
+    caller::check_direct_call(x, &ctx.accounts.config, None)?;
+    caller::check_direct_call(y, &ctx.accounts.config, None)?;
+    // 任何一方的 maker 暂停时都不能撮合
+    x.check_not_maker_paused()?;
+    y.check_not_maker_paused()?;
+    let x_wants_b = x.pro_rata(x.amount, x.current_receive(now)?)?;
+    let y_wants_a = y.pro_rata(y.amount, y.current_receive(now)?)?;
+    require!(
+        x_wants_b <= y.amount && y_wants_a <= x.amount,
+        EscrowError::PricesDoNotCross
+    );
+    let surplus_a = x.amount - y_wants_a;
+    let surplus_b = y.amount - x_wants_b;
+    // escrow_y 的 maker 收到 token A, escrow_x 的 maker 收到 token B, 手续费分别从中扣除
+    let fee_a = ctx.accounts.config.fee_for(y_wants_a)?;
+    let fee_b = ctx.accounts.config.fee_for(x_wants_b)?;
+
+    let matcher_ata_a = ctx
+        .accounts
+        .matcher_ata_a
+        .as_ref()
+        .map(|ata| ata.to_account_info());
+    let matcher_ata_b = ctx
+        .accounts
+        .matcher_ata_b
+        .as_ref()
+        .map(|ata| ata.to_account_info());
+    let net_amount_a = ctx.accounts.side_x().settle(
+        &ctx.accounts.maker_y_ata_a.to_account_info(),
+        y_wants_a,
+        fee_a,
+        matcher_ata_a,
+        surplus_a,
+        ctx.remaining_accounts,
+    )?;
+    let net_amount_b = ctx.accounts.side_y().settle(
+        &ctx.accounts.maker_x_ata_b.to_account_info(),
+        x_wants_b,
+        fee_b,
+        matcher_ata_b,
+        surplus_b,
+        ctx.remaining_accounts,
+    )?;
+
+    // 两个托管都算作一次成交
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+    ctx.accounts.stats.record_take();
+    ctx.accounts.stats.record_take();
+    MakerRegistry::remove_seed(&ctx.accounts.registry_x, ctx.accounts.escrow_x.seed)?;
+    MakerRegistry::remove_seed(&ctx.accounts.registry_y, ctx.accounts.escrow_y.seed)?;
+    if ctx.accounts.escrow_x.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index_x
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow_x.key());
+    }
+    if ctx.accounts.escrow_y.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index_y
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow_y.key());
+    }
+
+    emit_cpi!(MatchEvent {
+        escrow_x: ctx.accounts.escrow_x.key(),
+        escrow_y: ctx.accounts.escrow_y.key(),
+        maker_x: ctx.accounts.maker_x.key(),
+        maker_y: ctx.accounts.maker_y.key(),
+        matcher: ctx.accounts.matcher.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        amount_a: y_wants_a,
+        net_amount_a,
+        amount_b: x_wants_b,
+        net_amount_b,
+        fee_a,
+        fee_b,
+        surplus_a,
+        surplus_b,
+        timestamp: now,
+    });
+
+    // 指令执行完毕后 anchor 自动关闭两个 escrow 数据账户
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use crate::{errors::EscrowError, realloc, state::Escrow};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_interface::{Mint, TokenInterface},
+};
+
+// 把旧版本的托管迁移到当前的账户布局: 扩容到 Escrow::SPACE, 写入 version 并为新字段填入默认值
+// 旧版本的数据不能作为 Account<Escrow> 读取, 迁移之前 take 和 refund 等指令都返回 MigrationRequired, 迁移之后和新托管完全相同
+// 任何人都可以调用, 扩容的租金由 payer 支付, 通常是 maker 自己
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+    // 签名账户, 支付扩容所需的租金
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: 旧版本的托管账户, 由 Escrow::try_deserialize_legacy 检查 discriminator 和长度
+    #[account(mut, owner = crate::ID)]
+    pub escrow: UncheckedAccount<'info>,
+
+    // 托管的 token A 的 mint 账户, 必须和旧数据中的 mint_a 一致, 它的 token 程序决定 vault 的地址
+    #[account(mint::token_program = token_program)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    // 账户所需要的程序
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<MigrateEscrow>) -> Result<()> {
+    let escrow = ctx.accounts.escrow.to_account_info();
+
+    // 已经是当前版本的托管长度不同, 不能再次迁移
+    let mut state = Escrow::try_deserialize_legacy(&escrow.try_borrow_data()?)?;
+    require_keys_eq!(
+        state.mint_a,
+        ctx.accounts.mint_a.key(),
+        EscrowError::InvalidMintA
+    );
+
+    // 加入 vault 字段之前创建的托管, vault 总是 escrow 持有的 mint_a 的 ATA
+    if state.vault == Pubkey::default() {
+        state.vault = get_associated_token_address_with_program_id(
+            escrow.key,
+            &state.mint_a,
+            ctx.accounts.token_program.key,
+        );
+    }
+    state.version = Escrow::VERSION;
+
+    realloc::resize(
+        &escrow,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        Escrow::SPACE,
+    )?;
+    state.try_serialize(&mut &mut escrow.try_borrow_mut_data()?[..])?;
+
+    Ok(())
+}
@@ -1,9 +1,148 @@
 // 声明所有的指令
+pub mod accept_admin;
+pub mod accept_counter;
+pub mod cancel;
+pub mod cancel_counter;
+pub mod claim_fees;
+pub mod close_cancelled;
+pub mod close_commit;
+pub mod close_prefs;
+pub mod close_receipt;
+pub mod close_registry;
+pub mod commit_take;
+pub mod expire_reservation;
+pub mod get_escrow;
+pub mod init_user_stats;
+pub mod initialize_config;
+pub mod lock_take;
 pub mod make;
+pub mod make_auto;
+pub mod make_basket;
+pub mod make_batch;
+pub mod make_collection;
+pub mod make_dutch;
+pub mod make_for_sol;
+pub mod make_oracle;
+pub mod make_v2;
+pub mod match_escrows;
+pub mod migrate_escrow;
+pub mod operator_refund;
+pub mod propose_admin;
+pub mod propose_counter;
+pub mod recover_vault;
 pub mod refund;
+pub mod refund_basket;
+pub mod refund_batch;
+pub mod refund_expired;
+pub mod release;
+pub mod relist;
+pub mod reserve;
+pub mod reveal_take;
+pub mod set_abandon_policy;
+pub mod set_allowed_mints;
+pub mod set_delegate;
+pub mod set_direct_only;
+pub mod set_fee;
+pub mod set_fee_on_tips;
+pub mod set_fee_tiers;
+pub mod set_frozen;
+pub mod set_listing_fee;
+pub mod set_maker_paused;
+pub mod set_operators;
+pub mod set_paused;
+pub mod set_prefs;
+pub mod set_price_band;
+pub mod set_referral;
+pub mod set_royalty_enforcement;
+pub mod set_stream_window;
+pub mod set_swap_router;
+pub mod set_trusted_routers;
+pub mod settle;
+pub mod stream_payment;
+pub mod sweep_abandoned;
 pub mod take;
+pub mod take_basket;
+pub mod take_collection;
+pub mod take_for_sol;
+pub mod take_partial;
+pub mod take_streaming;
+pub mod take_with_sig;
+pub mod take_with_swap;
+pub mod top_up;
+pub mod transfer_maker;
+pub mod update_blocklist;
+pub mod update_exempt_list;
+pub mod update_receive;
+pub mod withdraw_partial;
 
 // 导出所有的指令
+pub use accept_admin::*;
+pub use accept_counter::*;
+pub use cancel_counter::*;
+pub use claim_fees::*;
+pub use close_cancelled::*;
+pub use close_commit::*;
+pub use close_prefs::*;
+pub use close_receipt::*;
+pub use close_registry::*;
+pub use commit_take::*;
+pub use expire_reservation::*;
+pub use get_escrow::*;
+pub use init_user_stats::*;
+pub use initialize_config::*;
+pub use lock_take::*;
 pub use make::*;
+pub use make_auto::*;
+pub use make_basket::*;
+pub use make_batch::*;
+pub use make_for_sol::*;
+pub use make_v2::*;
+pub use match_escrows::*;
+pub use migrate_escrow::*;
+pub use operator_refund::*;
+pub use propose_admin::*;
+pub use propose_counter::*;
+pub use recover_vault::*;
 pub use refund::*;
+pub use refund_basket::*;
+pub use refund_batch::*;
+pub use refund_expired::*;
+pub use release::*;
+pub use relist::*;
+pub use reserve::*;
+pub use reveal_take::*;
+pub use set_abandon_policy::*;
+pub use set_allowed_mints::*;
+pub use set_delegate::*;
+pub use set_direct_only::*;
+pub use set_fee::*;
+pub use set_fee_on_tips::*;
+pub use set_fee_tiers::*;
+pub use set_frozen::*;
+pub use set_listing_fee::*;
+pub use set_maker_paused::*;
+pub use set_operators::*;
+pub use set_paused::*;
+pub use set_prefs::*;
+pub use set_price_band::*;
+pub use set_referral::*;
+pub use set_royalty_enforcement::*;
+pub use set_stream_window::*;
+pub use set_swap_router::*;
+pub use set_trusted_routers::*;
+pub use settle::*;
+pub use sweep_abandoned::*;
 pub use take::*;
+pub use take_basket::*;
+pub use take_collection::*;
+pub use take_for_sol::*;
+pub use take_partial::*;
+pub use take_streaming::*;
+pub use take_with_sig::*;
+pub use take_with_swap::*;
+pub use top_up::*;
+pub use transfer_maker::*;
+pub use update_blocklist::*;
+pub use update_exempt_list::*;
+pub use update_receive::*;
+pub use withdraw_partial::*;
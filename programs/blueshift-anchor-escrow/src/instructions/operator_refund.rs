@@ -0,0 +1,36 @@
+// 嵌套 Refund 账户列表时还需要 derive(Accounts) 为它生成的 RefundBumps 等类型, 因此整体导入
+use crate::instructions::refund::*;
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+// operator_refund 的账户是 refund 的全部账户加上协议配置
+// refund.authority 是运营方的钱包或运营方程序的签名 PDA, 见 Config::check_operator
+// token A 仍然退还到 maker 的 ATA, 租金还给 rent_payer, 运营方拿不到托管中的任何资产
+#[derive(Accounts)]
+pub struct OperatorRefund<'info> {
+    pub refund: Refund<'info>,
+
+    // 协议配置账户, 记录允许的运营方
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+// 运营方代 maker 退还托管, 例如定期清理过期或失效的报价; maker 必须在 make_v2 时设置 allow_operators
+// 和 refund 一样, HTLC 和分期成交中的托管要等到 maker 自己也可以退还时才行; 不放弃冻结的 vault
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, OperatorRefund<'info>>) -> Result<()> {
+    require!(
+        ctx.accounts.refund.escrow.allow_operators,
+        EscrowError::OperatorsNotAllowed
+    );
+    ctx.accounts
+        .config
+        .check_operator(&ctx.accounts.refund.authority.key())?;
+
+    let refund_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.refund,
+        ctx.remaining_accounts,
+        ctx.bumps.refund,
+    );
+    refund_to_maker(refund_ctx, false)
+}
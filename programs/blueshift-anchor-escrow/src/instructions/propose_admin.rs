@@ -0,0 +1,25 @@
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    // 签名账户, 必须是当前的协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+    // 只记录待接受的管理员, 新管理员签名 accept_admin 之前当前管理员的权限不变
+    // 传入 Pubkey::default() 可以取消待处理的转移
+    ctx.accounts.config.pending_admin = new_admin;
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, COUNTER_SEED},
+    state::{Config, CounterOffer, Escrow, EscrowStatus},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{approve, Approve, Mint, TokenAccount, TokenInterface},
+};
+
+#[derive(Accounts)]
+pub struct ProposeCounter<'info> {
+    // 签名账户, 提出还价的 taker, 支付 counter_offer 的租金
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    // 被还价的托管账户, maker 不能还价自己的托管; 还价后状态前进到 PendingCounter
+    // 和 accept_counter 一样只接受可以成交且没有被预约的托管, 否则冻结的托管的 status_before_freeze 会被改写
+    #[account(
+        mut,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+        has_one = mint_b @ EscrowError::InvalidMintB,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    // 每个 taker 对每个托管只能有一个还价, 不同的 taker 可以同时还价
+    #[account(
+        init,
+        payer = taker,
+        space = CounterOffer::INIT_SPACE + CounterOffer::DISCRIMINATOR.len(),
+        seeds = [COUNTER_SEED, escrow.key().as_ref(), taker.key().as_ref()],
+        bump,
+    )]
+    pub counter_offer: Box<Account<'info, CounterOffer>>,
+
+    // Token B 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 取款者的 Token B 的 ATA 账户, 代币不会转出, 只授权给 counter_offer
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeCounter<'info> {
+    // 把 receive 个 token B 的转账权限授权给 counter_offer PDA, maker 接受时由 PDA 签名转账
+    // 一个 token 账户只能有一个 delegate, 同一个 token B 账户的新还价会覆盖旧的授权
+    fn approve_counter(&self, receive: u64) -> Result<()> {
+        approve(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                Approve {
+                    to: self.taker_ata_b.to_account_info(),
+                    delegate: self.counter_offer.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            ),
+            receive,
+        )
+    }
+}
+
+pub fn handler(ctx: Context<ProposeCounter>, receive: u64) -> Result<()> {
+    require_gt!(receive, 0, EscrowError::InvalidAmount);
+
+    ctx.accounts.counter_offer.set_inner(CounterOffer {
+        escrow: ctx.accounts.escrow.key(),
+        taker: ctx.accounts.taker.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        receive,
+        bump: ctx.bumps.counter_offer,
+    });
+    ctx.accounts
+        .escrow
+        .advance_status(EscrowStatus::PendingCounter);
+
+    ctx.accounts.approve_counter(receive)
+}
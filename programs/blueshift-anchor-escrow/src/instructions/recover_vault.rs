@@ -0,0 +1,149 @@
+use crate::{
+    errors::EscrowError,
+    events::RecoverVaultEvent,
+    pda::{ESCROW_SEED, REGISTRY_SEED, STATS_SEED},
+    state::{GlobalStats, MakerRegistry},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 取回孤立的 vault: escrow 数据账户已经不存在(例如只部分执行的自定义交易关闭了它), vault 却仍然持有 token A
+// 没有 escrow 数据就没有缓存的 bump, 由 seeds 重新派生; 只有 seeds 中的 maker 可以取回
+// remaining_accounts: mint A 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct RecoverVault<'info> {
+    // 签名账户, escrow seeds 中的 maker, 收回 token A 和 vault 的租金
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    /// CHECK: 已经关闭的托管账户, 只用它的地址作为 vault 的 authority 和 PDA 签名
+    /// 地址由 seeds 约束, 不传 bump 时 anchor 用 find_program_address 重新派生; 必须没有数据并且不属于本程序
+    #[account(
+        seeds = [ESCROW_SEED, maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        constraint = escrow.data_is_empty() && escrow.owner != &crate::ID @ EscrowError::EscrowNotOrphaned
+    )]
+    pub escrow: UncheckedAccount<'info>,
+
+    // Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    // 孤立的 vault, authority 仍然是托管账户的 PDA
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    // 创建者的 Token A 的 ATA 账户, 不存在时由 maker 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 可能仍然记录着这个 seed, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>, // maker_ata_a 要求 memo 时使用
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RecoverVault<'info> {
+    // 把 vault 中的 token A 退还给 maker 并关闭 vault, 租金也还给 maker
+    fn refund_and_close_vault(
+        &self,
+        seed: u64,
+        bump: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.key.as_ref(),
+            &seed.to_le_bytes()[..],
+            &[bump],
+        ]];
+
+        // 只有托管账户中的 token A 大于 0 时, 才需要转账
+        if self.vault.amount > 0 {
+            transfer::memo_if_required(
+                &self.memo_program.to_account_info(),
+                &self.maker_ata_a.to_account_info(),
+                &self.escrow.key(),
+            )?;
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        to: self.maker_ata_a.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        authority: self.escrow.to_account_info(),
+                    },
+                    &signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                self.vault.amount,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        // 原来的 rent_payer 记录在已经关闭的 escrow 中, 无法得知, vault 的租金还给 maker
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.maker.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RecoverVault<'info>>,
+    seed: u64,
+) -> Result<()> {
+    let amount = ctx.accounts.vault.amount;
+    ctx.accounts
+        .refund_and_close_vault(seed, ctx.bumps.escrow, ctx.remaining_accounts)?;
+    // 关闭 escrow 的指令没有同时关闭 vault, 取回时才计入一次退还
+    ctx.accounts.stats.record_refund();
+    MakerRegistry::remove_seed(&ctx.accounts.registry, seed)?;
+
+    emit_cpi!(RecoverVaultEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
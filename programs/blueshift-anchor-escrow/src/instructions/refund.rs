@@ -1,40 +1,63 @@
-use crate::{errors::EscrowError, state::Escrow};
+use crate::{
+    errors::EscrowError,
+    events::RefundEvent,
+    pda::{ESCROW_SEED, PAIR_INDEX_SEED, REGISTRY_SEED, STATS_SEED},
+    pnft::{self, PnftTransfer},
+    state::{Escrow, EscrowStatus, GlobalStats, MakerRegistry, PairIndex},
+    transfer,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
+    memo::Memo,
     token_interface::{
-        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
-        TransferChecked,
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
     },
 };
 
+// remaining_accounts: mint A 的 transfer hook 额外账户; mint A 是 pNFT 时在最前面传入 Token Metadata 转账需要的账户, 见 pnft.rs
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Refund<'info> {
-    // 签名账户, 即创建托管的账户
+    // 签名账户, maker 本人或托管记录的 refund_delegate, 可以是通过 invoke_signed 签名的 PDA; 由 handler 校验,
+    // operator_refund 嵌套这个账户列表时是 Config::operators 中的运营方, refund_expired 嵌套时是任意 cranker
+    // 支付重新创建 maker_ata_a 和统计账户的租金
     #[account(mut)]
-    pub maker: Signer<'info>,
+    pub authority: Signer<'info>,
+
+    /// CHECK: 托管的创建者, 由 escrow 的 seeds 和 has_one 约束; 无论谁签名, token A 总是退还到它的 ATA
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
 
     // 托管账户的数据账户, 此时不需要 init, 因为这个账户在 make 阶段已经初始化了
+    // refund 在 handler 中关闭它, 租金还给 rent_payer; cancel 使用相同的账户, 可以选择保留
     #[account(
         mut,
-        close = maker,
-        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
         has_one = maker @ EscrowError::InvalidMaker,
-        has_one = mint_a @ EscrowError::InvalidMintA
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        // 分期成交中的托管也可以 refund, 截止之前由 check_maker_can_withdraw 拒绝
+        constraint = escrow.status.is_refundable() || escrow.status == EscrowStatus::Streaming @ EscrowError::InvalidEscrowStatus,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved // 保证金在 escrow 中, 预约结束之后才能退还
     )]
     pub escrow: Account<'info, Escrow>,
 
     // Token A 的 mint 账户
-    #[account(mint::token_program = token_program)]
+    #[account(owner = token_program.key() @ EscrowError::WrongTokenProgram)]
     pub mint_a: InterfaceAccount<'info, Mint>,
 
-    // 托管资金 ATA 账户
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验, 不重新派生 ATA 地址
     #[account(
         mut,
-        associated_token::mint = mint_a,
-        associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        address = escrow.vault @ EscrowError::InvalidVault,
+        owner = token_program.key() @ EscrowError::WrongTokenProgram,
+        token::mint = mint_a,
+        token::authority = escrow
     )]
     pub vault: InterfaceAccount<'info, TokenAccount>,
 
@@ -43,39 +66,152 @@ pub struct Refund<'info> {
     // 所以 refund 时 maker_ata_a 不一定存在, 需要在不存在时重新创建
     #[account(
         init_if_needed,
-        payer = maker,
+        payer = authority,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
         associated_token::token_program = token_program
     )]
     pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
 
+    // 全局统计账户, 第一次使用时由 authority 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 退还后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
     // 账户所需要的程序
     pub associated_token_program: Program<'info, AssociatedToken>, // init_if_needed 需要 ATA 程序
     pub token_program: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>, // maker_ata_a 要求 memo 时使用
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<Refund>) -> Result<()> {
+// force: vault 被冻结时放弃 vault 中的 token A, 只关闭 escrow 数据账户取回租金; vault 没有冻结时不影响
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, Refund<'info>>, force: bool) -> Result<()> {
+    check_refund_authority(ctx.accounts)?;
+    refund_to_maker(ctx, force)
+}
+
+// refund 和 cancel 只接受 maker 本人或托管记录的 refund_delegate 签名
+pub(crate) fn check_refund_authority(accounts: &Refund) -> Result<()> {
+    require!(
+        accounts
+            .escrow
+            .is_refund_authority(&accounts.authority.key()),
+        EscrowError::UnauthorizedRefund
+    );
+    Ok(())
+}
+
+// 退还全部 token A 并关闭 escrow, 租金还给 rent_payer; refund, operator_refund 和 refund_expired 共用, 调用方负责校验签名账户
+pub(crate) fn refund_to_maker<'info>(
+    mut ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+    force: bool,
+) -> Result<()> {
+    let rent_payer = ctx.accounts.rent_payer.to_account_info();
+    let amount = withdraw(&mut ctx, force, rent_payer)?;
+
+    emit_cpi!(RefundEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.escrow.mint_b,
+        amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    ctx.accounts
+        .escrow
+        .close(ctx.accounts.rent_payer.to_account_info())
+}
+
+// 退还 vault 中的 token A 并关闭 vault, 更新统计和索引, 返回退还的数量; escrow 数据账户由调用方关闭或保留
+// vault 的租金转给 vault_rent_to, refund 和 cancel 是 rent_payer, sweep_abandoned 先转入 escrow 再和 cranker 分配
+pub(crate) fn withdraw<'info>(
+    ctx: &mut Context<'_, '_, '_, 'info, Refund<'info>>,
+    force: bool,
+    vault_rent_to: AccountInfo<'info>,
+) -> Result<u64> {
+    // HTLC 托管只能在过期之后退还
+    ctx.accounts
+        .escrow
+        .check_maker_can_withdraw(Clock::get()?.unix_timestamp)?;
+
+    // vault 被冻结时既不能转出也不能关闭, 只有 force 时才继续, 冻结的 vault 和其中的 token A 留在链上
+    // pNFT 的 vault 始终被 Token Metadata 冻结, 由它解冻并转回 maker, 不算作冻结
+    let (pnft, remaining_accounts) =
+        pnft::split(ctx.remaining_accounts, &ctx.accounts.mint_a.key())?;
+    let frozen = ctx.accounts.vault.is_frozen() && pnft.is_none();
+    require!(!frozen || force, EscrowError::VaultFrozen);
+
+    // wSOL 的 vault 先同步直接转入的 lamports, 一起退还给 maker
+    if !frozen {
+        transfer::sync_native_if_needed(
+            &ctx.accounts.token_program.to_account_info(),
+            &mut ctx.accounts.vault,
+        )?;
+    }
+
     let vault = &ctx.accounts.vault;
     let maker = &ctx.accounts.maker;
     let escrow = &ctx.accounts.escrow;
     let mint_a = &ctx.accounts.mint_a;
     let escrow_seed_le_bytes = escrow.seed.to_le_bytes();
-    let amount = vault.amount;
+    let amount = if frozen { 0 } else { vault.amount };
     let decimals = mint_a.decimals;
 
     // 将 vault 的 token A 转账给 maker
     let signer_seeds: &[&[&[u8]]] = &[&[
-        b"escrow",
+        ESCROW_SEED,
         maker.to_account_info().key.as_ref(),
         escrow_seed_le_bytes.as_ref(),
         &[escrow.bump],
     ]];
 
     // 只有托管账户中的 token A 大于 0 时, 才需要转账
-    if amount > 0 {
-        transfer_checked(
+    // mint A 带有 transfer hook 时, hook 需要的额外账户通过 remaining_accounts 传入
+    // pNFT 由 Token Metadata 转回, maker_ata_a 的 token record 由 authority 支付租金创建
+    if let Some(pnft) = pnft.as_ref().filter(|_| amount > 0) {
+        pnft.transfer(
+            PnftTransfer {
+                token: ctx.accounts.vault.to_account_info(),
+                token_owner: ctx.accounts.escrow.to_account_info(),
+                destination_token: ctx.accounts.maker_ata_a.to_account_info(),
+                destination_owner: ctx.accounts.maker.to_account_info(),
+                mint: ctx.accounts.mint_a.to_account_info(),
+                authority: ctx.accounts.escrow.to_account_info(),
+                payer: ctx.accounts.authority.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+                associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+            },
+            amount,
+            signer_seeds,
+        )?;
+    } else if amount > 0 {
+        transfer::memo_if_required(
+            &ctx.accounts.memo_program.to_account_info(),
+            &ctx.accounts.maker_ata_a.to_account_info(),
+            &ctx.accounts.escrow.key(),
+        )?;
+        transfer::transfer_checked(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 TransferChecked {
@@ -85,22 +221,37 @@ pub fn handler(ctx: Context<Refund>) -> Result<()> {
                     mint: ctx.accounts.mint_a.to_account_info(),
                 },
                 signer_seeds,
-            ),
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
             amount,
             decimals,
         )?;
     };
 
     // 关闭 vault 账户
-    close_account(CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        CloseAccount {
-            account: ctx.accounts.vault.to_account_info(),
-            authority: ctx.accounts.escrow.to_account_info(),
-            destination: ctx.accounts.maker.to_account_info(),
-        },
-        signer_seeds,
-    ))?;
+    if !frozen {
+        close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.escrow.to_account_info(),
+                destination: vault_rent_to,
+            },
+            signer_seeds,
+        ))?;
+    }
 
-    Ok(())
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+    ctx.accounts.stats.record_refund();
+    MakerRegistry::remove_seed(&ctx.accounts.registry, ctx.accounts.escrow.seed)?;
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+    }
+
+    Ok(amount)
 }
@@ -0,0 +1,55 @@
+use crate::{
+    errors::EscrowError, events::RefundBasketEvent, instructions::take_basket::BasketSweep,
+    pda::BASKET_SEED, state::EscrowBasket,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, memo::Memo, token_interface::TokenInterface};
+
+// 退还一篮子托管中的全部 token 并关闭所有 vault 和 basket, 空的 vault 也必须传入并关闭
+// remaining_accounts: 按 basket.legs 的顺序每种 token 传入 [mint, vault, maker 的 ATA] 3 个账户, 见 BasketSweep
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefundBasket<'info> {
+    // 签名账户, 一篮子托管的创建者, 支付重新创建 ATA 的租金
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        close = maker,
+        seeds = [BASKET_SEED, maker.key().as_ref(), basket.seed.to_le_bytes().as_ref()],
+        bump = basket.bump,
+        has_one = maker @ EscrowError::InvalidMaker
+    )]
+    pub basket: Box<Account<'info, EscrowBasket>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>, // 管理篮子中所有 mint 的 token 程序
+    pub memo_program: Program<'info, Memo>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, RefundBasket<'info>>) -> Result<()> {
+    let maker = ctx.accounts.maker.to_account_info();
+    let amounts = BasketSweep {
+        basket: &ctx.accounts.basket,
+        recipient: maker.clone(),
+        payer: maker.clone(),
+        maker,
+        token_program: ctx.accounts.token_program.to_account_info(),
+        associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        memo_program: ctx.accounts.memo_program.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+    }
+    .sweep(ctx.remaining_accounts)?;
+
+    emit_cpi!(RefundBasketEvent {
+        basket: ctx.accounts.basket.key(),
+        maker: ctx.accounts.maker.key(),
+        amounts,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
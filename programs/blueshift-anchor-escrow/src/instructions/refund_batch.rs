@@ -0,0 +1,221 @@
+use crate::{
+    errors::EscrowError,
+    events::RefundEvent,
+    pda::{ESCROW_SEED, REGISTRY_SEED, STATS_SEED},
+    state::{Escrow, GlobalStats, MakerRegistry, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 一笔交易中最多退还的托管数, 由 compute_units 测试按默认的 200k 计算预算确定
+pub const MAX_REFUND_BATCH_SIZE: usize = 8;
+
+// 一次退还 maker 的多个同一 mint A 的托管, 任何一个托管验证失败时整笔交易回滚
+// remaining_accounts: [escrow_0, vault_0, escrow_1, vault_1, ...] 共 count 对, 都是可写账户, 之后是 mint A 的 transfer hook 额外账户
+// 所有托管必须由同一个 rent_payer 支付租金; 和 refund 不同, 不支持 force, vault 被冻结的托管需要单独退还
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefundBatch<'info> {
+    // 签名账户, 所有托管的创建者
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    // 所有托管的租金支付者, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    // Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    // 创建者接收 Token A 的 ATA 账户, 和 refund 一样不存在时重新创建
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 全局统计账户, 第一次使用时由 maker 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // mint A 的一个交易对索引, 计入了索引的托管退还后从中移除, 这些托管的 mint B 必须和它相同; 没有计入索引的托管不需要传入
+    // 托管的 mint B 可以不同, 因此不能按某一个托管的 seeds 约束, 只检查 mint A; PairIndex 只能由本程序在 PDA 上创建
+    #[account(
+        mut,
+        constraint = pair_index.mint_a == mint_a.key() @ EscrowError::InvalidMintA,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RefundBatch<'info> {
+    // 和 Refund 的账户约束相同的检查, anchor 的约束不能用于 remaining_accounts
+    fn check_escrow(
+        &self,
+        escrow: &Account<'info, Escrow>,
+        vault: &InterfaceAccount<'info, TokenAccount>,
+        now: i64,
+    ) -> Result<()> {
+        require_keys_eq!(escrow.maker, self.maker.key(), EscrowError::InvalidMaker);
+        require_keys_eq!(
+            escrow.rent_payer,
+            self.rent_payer.key(),
+            EscrowError::InvalidRentPayer
+        );
+        require_keys_eq!(escrow.mint_a, self.mint_a.key(), EscrowError::InvalidMintA);
+        require!(
+            escrow.status.is_refundable(),
+            EscrowError::InvalidEscrowStatus
+        );
+        require!(!escrow.is_reserved(), EscrowError::EscrowReserved);
+        escrow.check_maker_can_withdraw(now)?;
+
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address_with_program_id(
+                &escrow.key(),
+                &self.mint_a.key(),
+                self.token_program.key
+            ),
+            EscrowError::InvalidBatchAccounts
+        );
+        require!(!vault.is_frozen(), EscrowError::VaultFrozen);
+
+        Ok(())
+    }
+
+    // 把 vault 中的 token A 全部退还给 maker 并关闭 vault, 返回退还的数量
+    fn drain_vault(
+        &self,
+        escrow: &Account<'info, Escrow>,
+        vault: &InterfaceAccount<'info, TokenAccount>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let seed = escrow.seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            ESCROW_SEED,
+            self.maker.key.as_ref(),
+            seed.as_ref(),
+            &[escrow.bump],
+        ]];
+
+        let amount = vault.amount;
+        if amount > 0 {
+            transfer::memo_if_required(
+                &self.memo_program.to_account_info(),
+                &self.maker_ata_a.to_account_info(),
+                &escrow.key(),
+            )?;
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        to: self.maker_ata_a.to_account_info(),
+                        authority: escrow.to_account_info(),
+                    },
+                    signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                amount,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: vault.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+                authority: escrow.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        Ok(amount)
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RefundBatch<'info>>,
+    count: u8,
+) -> Result<()> {
+    let count = count as usize;
+    require!(
+        count > 0 && count <= MAX_REFUND_BATCH_SIZE,
+        EscrowError::InvalidBatchSize
+    );
+    require_gte!(
+        ctx.remaining_accounts.len(),
+        count * 2,
+        EscrowError::InvalidBatchAccounts
+    );
+    let (pairs, hook_accounts) = ctx.remaining_accounts.split_at(count * 2);
+    let now = Clock::get()?.unix_timestamp;
+
+    for pair in pairs.chunks_exact(2) {
+        // 同一个托管传入两次时, 第二次读取的是已经关闭的账户, 反序列化失败
+        let escrow = Account::<Escrow>::try_from(&pair[0])?;
+        let mut vault = InterfaceAccount::<TokenAccount>::try_from(&pair[1])?;
+        ctx.accounts.check_escrow(&escrow, &vault, now)?;
+
+        // wSOL 的 vault 先同步直接转入的 lamports, 一起退还给 maker
+        transfer::sync_native_if_needed(&ctx.accounts.token_program.to_account_info(), &mut vault)?;
+        let amount = ctx.accounts.drain_vault(&escrow, &vault, hook_accounts)?;
+
+        ctx.accounts.stats.record_refund();
+        MakerRegistry::remove_seed(&ctx.accounts.registry, escrow.seed)?;
+        if escrow.pair_indexed {
+            let index = ctx
+                .accounts
+                .pair_index
+                .as_mut()
+                .filter(|index| index.mint_b == escrow.mint_b)
+                .ok_or(EscrowError::MissingPairIndex)?;
+            index.record_close(&escrow.key());
+        }
+
+        emit_cpi!(RefundEvent {
+            escrow: escrow.key(),
+            status: escrow.status,
+            maker: ctx.accounts.maker.key(),
+            mint_a: ctx.accounts.mint_a.key(),
+            mint_b: escrow.mint_b,
+            amount,
+            timestamp: now,
+        });
+
+        escrow.close(ctx.accounts.rent_payer.to_account_info())?;
+    }
+
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use crate::errors::EscrowError;
+// 嵌套 Refund 账户列表时还需要 derive(Accounts) 为它生成的 RefundBumps 等类型, 因此整体导入
+use crate::instructions::refund::*;
+use anchor_lang::prelude::*;
+
+// refund_expired 的账户就是 refund 的全部账户
+// refund.authority 是任意 cranker, 不需要是 maker; token A 仍然退还到 maker 的 ATA, 租金还给 rent_payer
+// maker 的 ATA 可能已经被关闭, 此时由 cranker 支付租金重新创建
+#[derive(Accounts)]
+pub struct RefundExpired<'info> {
+    pub refund: Refund<'info>,
+}
+
+impl<'info> RefundExpired<'info> {
+    // 验证托管设置了过期时间并且已经过期; 分期成交中的托管不接受 cranker 退还
+    fn check_expired(&self) -> Result<()> {
+        let escrow = &self.refund.escrow;
+        require!(
+            escrow.status.is_refundable(),
+            EscrowError::InvalidEscrowStatus
+        );
+        require!(escrow.expiry != 0, EscrowError::NoExpiry);
+        require_gt!(
+            Clock::get()?.unix_timestamp,
+            escrow.expiry,
+            EscrowError::EscrowNotExpired
+        );
+
+        Ok(())
+    }
+}
+
+// 过期的托管任何人都可以退还给 maker, 转账和关闭与 refund 完全相同; 不放弃冻结的 vault
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, RefundExpired<'info>>) -> Result<()> {
+    ctx.accounts.check_expired()?;
+
+    let refund_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.refund,
+        ctx.remaining_accounts,
+        ctx.bumps.refund,
+    );
+    refund_to_maker(refund_ctx, false)
+}
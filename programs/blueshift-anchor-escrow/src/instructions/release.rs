@@ -0,0 +1,419 @@
+use crate::{
+    errors::EscrowError,
+    events::ReleaseEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, PAIR_INDEX_SEED, REGISTRY_SEED, STATS_SEED,
+        USER_STATS_SEED,
+    },
+    state::{Config, Escrow, EscrowStatus, GlobalStats, MakerRegistry, PairIndex, UserStats},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// release_to_taker 和 release_to_maker 共用的账户列表, 结算 lock_take 锁定的托管并关闭 escrow, vault 和 vault_b
+// release_to_taker 完成交易: token A 给 taker, token B 扣除手续费后给 receive_to, 需要 taker_ata_a 和 maker_ata_b
+// release_to_maker 各自取回: token A 还给 maker, token B 全部还给 taker, 需要 maker_ata_a 和 taker_ata_b
+// 所有去向都由 ATA 约束固定在 maker, receive_to 和 taker 名下, 仲裁人只能选择方向, 不能把资金转给自己
+// remaining_accounts: 先传 mint A 再传 mint B 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Release<'info> {
+    // 签名账户, 仲裁人, 或者 maker 和 taker 之一; 支付需要创建的 ATA 和统计账户的租金
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    // 双方共同结算时, 另一方的签名; 仲裁人结算和超时退回时不需要
+    pub co_signer: Option<Signer<'info>>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 token B 的钱包, 只作为 maker_ata_b 的 authority, 由 has_one 约束为 escrow.receive_to
+    pub receive_to: UncheckedAccount<'info>,
+
+    /// CHECK: 锁定 token B 的 taker, 由地址约束为 escrow.locked_taker; vault_b 是它支付的租金, 关闭时还给它
+    #[account(mut, address = escrow.locked_taker @ EscrowError::UnauthorizedTaker)]
+    pub taker: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = receive_to @ EscrowError::InvalidReceiveTo,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        has_one = mint_b @ EscrowError::InvalidMintB,
+        constraint = escrow.status == EscrowStatus::Locked @ EscrowError::InvalidEscrowStatus,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管 token A 的 vault
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program_a
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // lock_take 锁定 token B 的 vault_b
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_b
+    )]
+    pub vault_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // release_to_taker: taker 接收 token A 的 ATA
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_a
+    )]
+    pub taker_ata_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // release_to_taker: receive_to 接收 token B 的 ATA
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint_b,
+        associated_token::authority = receive_to,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // release_to_maker: maker 取回 token A 的 ATA
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_ata_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // release_to_maker: taker 取回 token B 的 ATA
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_b
+    )]
+    pub taker_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 协议配置账户, 读取手续费比例; release_to_taker 在协议暂停时不能执行
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 token B 手续费的 ATA 账户, 手续费为 0 或者 release_to_maker 时可以不传
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program_b
+    )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 全局统计账户, 第一次使用时由 authority 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    // taker 的成交统计, 只有 release_to_taker 使用, 第一次传入时由 authority 支付租金创建
+    // 启用手续费档位时 release_to_taker 必须传入, 按它选择手续费比例, 见 take
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = UserStats::INIT_SPACE + UserStats::DISCRIMINATOR.len(),
+        seeds = [USER_STATS_SEED, taker.key().as_ref()],
+        bump,
+    )]
+    pub taker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    // maker 的成交统计, 必须已经通过 init_user_stats 创建; 不传时不计入
+    #[account(
+        mut,
+        seeds = [USER_STATS_SEED, maker.key().as_ref()],
+        bump = maker_stats.bump,
+    )]
+    pub maker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 释放后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>,
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>, // 接收方账户要求 memo 时使用
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Release<'info> {
+    // 用 escrow 的签名从 from 转出 amount 个 token, 为 0 时不做任何 CPI
+    fn pay(
+        &self,
+        token_program: &Interface<'info, TokenInterface>,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        to: AccountInfo<'info>,
+        mint: &InterfaceAccount<'info, Mint>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &to,
+            &self.escrow.key(),
+        )?;
+        transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: from.to_account_info(),
+                    to,
+                    mint: mint.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            mint.decimals,
+        )?;
+
+        Ok(())
+    }
+
+    // 关闭 escrow 拥有的 vault, 租金还给 destination
+    fn close_vault(
+        &self,
+        token_program: &Interface<'info, TokenInterface>,
+        vault: &InterfaceAccount<'info, TokenAccount>,
+        destination: AccountInfo<'info>,
+    ) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+        close_account(CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            CloseAccount {
+                account: vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination,
+            },
+            &signer_seeds,
+        ))
+    }
+}
+
+// to_taker 为 true 时是 release_to_taker, 否则是 release_to_maker
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Release<'info>>,
+    to_taker: bool,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let co_signer = ctx.accounts.co_signer.as_ref().map(|signer| signer.key());
+    ctx.accounts.escrow.check_release(
+        &ctx.accounts.authority.key(),
+        co_signer.as_ref(),
+        to_taker,
+        now,
+    )?;
+    // 暂停时不能完成交易, 但和 refund 一样仍然可以退回
+    if to_taker {
+        ctx.accounts.config.check_not_paused()?;
+    }
+
+    // wSOL 的 vault 先同步直接转入的 lamports
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_a.to_account_info(),
+        &mut ctx.accounts.vault,
+    )?;
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_b.to_account_info(),
+        &mut ctx.accounts.vault_b,
+    )?;
+
+    // 锁定之后两个 vault 中的全部代币都属于这笔交易, 一起转给结算的一方
+    let amount_a = ctx.accounts.vault.amount;
+    let amount_b = ctx.accounts.vault_b.amount;
+    let (destination_a, destination_b) = if to_taker {
+        (
+            ctx.accounts
+                .taker_ata_a
+                .as_ref()
+                .ok_or(EscrowError::MissingTakerAccount)?
+                .to_account_info(),
+            ctx.accounts
+                .maker_ata_b
+                .as_ref()
+                .ok_or(EscrowError::MissingMakerAta)?
+                .to_account_info(),
+        )
+    } else {
+        (
+            ctx.accounts
+                .maker_ata_a
+                .as_ref()
+                .ok_or(EscrowError::MissingMakerAta)?
+                .to_account_info(),
+            ctx.accounts
+                .taker_ata_b
+                .as_ref()
+                .ok_or(EscrowError::MissingTakerAccount)?
+                .to_account_info(),
+        )
+    };
+
+    // 手续费只在交易完成时从 vault_b 中收取, 退回时 taker 取回全部 token B; 启用档位时和 take 一样按 taker 之前的成交次数选择比例
+    let fee = if to_taker {
+        ctx.accounts.config.taker_fee_for(
+            amount_b,
+            ctx.accounts.taker_stats.as_deref().map(|stats| &**stats),
+        )?
+    } else {
+        0
+    };
+    if fee > 0 {
+        let fee_vault_b = ctx
+            .accounts
+            .fee_vault_b
+            .as_ref()
+            .ok_or(EscrowError::MissingFeeVault)?
+            .to_account_info();
+        ctx.accounts.pay(
+            &ctx.accounts.token_program_b,
+            &ctx.accounts.vault_b,
+            fee_vault_b,
+            &ctx.accounts.mint_b,
+            fee,
+            ctx.remaining_accounts,
+        )?;
+    }
+
+    let accounts = &ctx.accounts;
+    accounts.pay(
+        &accounts.token_program_a,
+        &accounts.vault,
+        destination_a,
+        &accounts.mint_a,
+        amount_a,
+        ctx.remaining_accounts,
+    )?;
+    accounts.pay(
+        &accounts.token_program_b,
+        &accounts.vault_b,
+        destination_b,
+        &accounts.mint_b,
+        amount_b.checked_sub(fee).ok_or(EscrowError::MathOverflow)?,
+        ctx.remaining_accounts,
+    )?;
+
+    // vault 的租金还给 rent_payer, vault_b 的租金还给 lock_take 时支付它的 taker
+    accounts.close_vault(
+        &accounts.token_program_a,
+        &accounts.vault,
+        accounts.rent_payer.to_account_info(),
+    )?;
+    accounts.close_vault(
+        &accounts.token_program_b,
+        &accounts.vault_b,
+        accounts.taker.to_account_info(),
+    )?;
+
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+    if to_taker {
+        ctx.accounts.stats.record_take();
+        let taker = ctx.accounts.taker.key();
+        if let (Some(taker_stats), Some(bump)) =
+            (ctx.accounts.taker_stats.as_mut(), ctx.bumps.taker_stats)
+        {
+            taker_stats.record_taker_trade(taker, bump, amount_b);
+        }
+        if let Some(maker_stats) = ctx.accounts.maker_stats.as_mut() {
+            maker_stats.record_trade(amount_b);
+        }
+    } else {
+        ctx.accounts.stats.record_refund();
+    }
+    MakerRegistry::remove_seed(&ctx.accounts.registry, ctx.accounts.escrow.seed)?;
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+    }
+
+    emit_cpi!(ReleaseEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        taker: ctx.accounts.taker.key(),
+        arbiter: ctx.accounts.escrow.arbiter,
+        authority: ctx.accounts.authority.key(),
+        to_taker,
+        amount_a,
+        amount_b,
+        fee,
+        timestamp: now,
+    });
+
+    // 指令执行完毕后 anchor 自动关闭 escrow 数据账户
+
+    Ok(())
+}
@@ -0,0 +1,282 @@
+use crate::{
+    errors::EscrowError,
+    events::RelistEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, MINT_ALLOWLIST_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED,
+        REGISTRY_SEED,
+    },
+    realloc,
+    state::{Config, Escrow, EscrowStatus, MakerRegistry, MintAllowlist, MintBlocklist, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 在一笔交易中把旧托管的 token A 直接移入新 seed 的托管, 用新的价格重新挂单, token A 不经过 maker 的钱包
+// 新账户的租金由 rent_payer 先垫付, 旧的 escrow 和 vault 关闭后租金还给它, 净成本只有 registry 可能的扩容
+// remaining_accounts: mint A 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(new_seed: u64)]
+pub struct Relist<'info> {
+    // 签名账户, 旧托管和新托管的创建者
+    pub maker: Signer<'info>,
+
+    // 旧托管的租金支付者, 同时支付新托管的租金; 没有赞助时就是 maker 自己
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    // 旧托管账户, 指令结束时关闭
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    // 旧托管的 vault, token A 全部转入 new_vault 后关闭
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 新托管账户, 和 make 一样由 maker 和 new_seed 派生
+    #[account(
+        init,
+        payer = rent_payer,
+        space = Escrow::INIT_SPACE + Escrow::DISCRIMINATOR.len(),
+        seeds = [ESCROW_SEED, maker.key().as_ref(), new_seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub new_escrow: Box<Account<'info, Escrow>>,
+
+    // 新托管的 vault
+    #[account(
+        init,
+        payer = rent_payer,
+        associated_token::mint = mint_a,
+        associated_token::authority = new_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub new_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 白名单, 由 seeds 约束地址; 从未设置过时是空的系统账户, 见 MintAllowlist::check
+    #[account(seeds = [MINT_ALLOWLIST_SEED], bump)]
+    pub mint_allowlist: UncheckedAccount<'info>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // maker 的托管索引, 旧 seed 替换为新 seed; 托管早于索引创建时由 rent_payer 创建
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = registry.data_len().max(MakerRegistry::space(0)),
+        seeds = [REGISTRY_SEED, maker.key().as_ref()],
+        bump,
+    )]
+    pub registry: Box<Account<'info, MakerRegistry>>,
+
+    // 托管计入了交易对索引时必须传入, 用新托管替换旧托管; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Relist<'info> {
+    // 新托管的 mint 和旧托管相同, 但仍然要遵守当前的白名单和黑名单; SOL 模式的托管只检查 mint A
+    fn check_mints(&self) -> Result<()> {
+        let mints = if self.escrow.is_sol_mode() {
+            vec![self.mint_a.key()]
+        } else {
+            vec![self.mint_a.key(), self.escrow.mint_b]
+        };
+        MintAllowlist::check(&self.mint_allowlist, &mints)?;
+        MintBlocklist::check(&self.mint_blocklist, &mints)?;
+
+        Ok(())
+    }
+
+    // 把旧 vault 的 token A 全部转入新 vault 并关闭旧 vault, 返回新 vault 实际收到的数量
+    fn move_vault(&self, remaining_accounts: &[AccountInfo<'info>]) -> Result<u64> {
+        let seed = self.escrow.seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            seed.as_ref(),
+            &[self.escrow.bump],
+        ]];
+
+        // mint A 带有 TransferFee 扩展时, vault 之间的转账同样会被扣留手续费
+        let amount = self.vault.amount;
+        let fee = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    to: self.new_vault.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_a.decimals,
+        )?;
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+                authority: self.escrow.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        amount
+            .checked_sub(fee)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 在 maker 的索引中用新 seed 替换旧 seed; 旧 seed 不在索引中时需要扩容
+    fn replace_seed(&mut self, new_seed: u64, bump: u8) -> Result<()> {
+        let old_seed = self.escrow.seed;
+        self.registry.seeds.retain(|open| *open != old_seed);
+        require_gt!(
+            MakerRegistry::MAX_SEEDS,
+            self.registry.seeds.len(),
+            EscrowError::RegistryFull
+        );
+
+        let space = MakerRegistry::space(self.registry.seeds.len() + 1);
+        if self.registry.to_account_info().data_len() < space {
+            realloc::resize(
+                &self.registry.to_account_info(),
+                &self.rent_payer.to_account_info(),
+                &self.system_program.to_account_info(),
+                space,
+            )?;
+        }
+
+        self.registry.maker = self.maker.key();
+        self.registry.bump = bump;
+        self.registry.seeds.push(new_seed);
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Relist<'info>>,
+    new_seed: u64,
+    new_receive: u64,
+) -> Result<()> {
+    require_gt!(new_receive, 0, EscrowError::InvalidAmount);
+
+    // 荷兰拍卖的价格由衰减参数决定, 和 update_receive 一样不能重新定价
+    require!(
+        !ctx.accounts.escrow.is_dutch(),
+        EscrowError::DutchAuctionReprice
+    );
+
+    // HTLC 托管和 refund 一样只能在过期之后取回, 而过期的托管不能原样重新挂单, 因此 HTLC 托管不能 relist
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_maker_can_withdraw(now)?;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    ctx.accounts.check_mints()?;
+
+    // wSOL 的 vault 先同步直接转入的 lamports, 一起移入新 vault
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program.to_account_info(),
+        &mut ctx.accounts.vault,
+    )?;
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    let amount = ctx.accounts.vault.amount;
+    let net_amount = ctx.accounts.move_vault(ctx.remaining_accounts)?;
+    require_gt!(net_amount, 0, EscrowError::InvalidAmount);
+
+    // 除了 seed, 价格和数量, 新托管沿用旧托管的所有条款, 状态重新从 Open 开始
+    // 其他支付 mint 保持原有单价
+    let old = &ctx.accounts.escrow;
+    let new_escrow = Escrow {
+        seed: new_seed,
+        receive: new_receive,
+        deposited: net_amount,
+        amount: net_amount,
+        created_at: now,
+        alt_payments: old.rebased_payments(net_amount)?,
+        status: EscrowStatus::Open,
+        status_before_freeze: EscrowStatus::Open,
+        bump: ctx.bumps.new_escrow,
+        vault: ctx.accounts.new_vault.key(),
+        ..(***old).clone()
+    };
+    ctx.accounts.new_escrow.set_inner(new_escrow);
+
+    ctx.accounts.replace_seed(new_seed, ctx.bumps.registry)?;
+    // 新托管沿用 pair_indexed, 在交易对索引中用新托管替换旧托管, 计数不变
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+        index.record_make(ctx.accounts.new_escrow.key(), new_receive, net_amount);
+    }
+
+    emit_cpi!(RelistEvent {
+        old_escrow: ctx.accounts.escrow.key(),
+        escrow: ctx.accounts.new_escrow.key(),
+        status: ctx.accounts.new_escrow.status,
+        maker: ctx.accounts.maker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.new_escrow.mint_b,
+        amount,
+        net_amount,
+        old_receive: ctx.accounts.escrow.receive,
+        new_receive,
+        timestamp: now,
+    });
+
+    // 指令执行完毕后 anchor 自动关闭旧的 escrow 数据账户
+
+    Ok(())
+}
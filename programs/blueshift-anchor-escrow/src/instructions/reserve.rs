@@ -0,0 +1,116 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::ReserveEvent,
+    pda::{CONFIG_SEED, ESCROW_SEED, MINT_BLOCKLIST_SEED},
+    state::{Config, Escrow, MintBlocklist},
+};
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
+
+// 延迟成交的第一步: taker 先支付 SOL 保证金锁定当前的托管, 之后在 escrow.reservation_window 秒内通过 settle 按 take 的流程成交
+// 预约期间其他 taker 不能成交, maker 也不能退还或修改条件; 过期后任何人都可以 expire_reservation, 保证金归 maker
+// 保证金直接存入 escrow 账户, 和租金放在一起, 见 pay_bond
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Reserve<'info> {
+    // 签名账户, 预约托管的 taker, 支付保证金
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束
+    pub maker: UncheckedAccount<'info>,
+
+    // 托管账户的数据账户, 记录预约的 taker 和保证金
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 转入保证金需要系统程序
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<Reserve>, bond_lamports: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+    // 预约会挡住其他 taker 和 maker, 所以和成交一样检查: 不能成交的 taker 也不能预约
+    caller::check_direct_call(&ctx.accounts.escrow, &ctx.accounts.config, None)?;
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.escrow.mint_a, ctx.accounts.escrow.mint_b],
+    )?;
+
+    require!(
+        ctx.accounts
+            .escrow
+            .is_on_allowlist(&ctx.accounts.taker.key(), &proof),
+        EscrowError::NotOnAllowlist
+    );
+
+    // 到达开始时间之前和过期之后不能成交, 也不能预约
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_started(now)?;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    let taker = ctx.accounts.taker.key();
+    ctx.accounts.escrow.reserve(taker, bond_lamports, now)?;
+
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.taker.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+            },
+        ),
+        bond_lamports,
+    )?;
+
+    emit_cpi!(ReserveEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        taker,
+        bond: bond_lamports,
+        reservation_expiry: ctx.accounts.escrow.reservation_expiry,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// 从 escrow 账户中取出保证金转给 to, settle 和 expire_reservation 共用
+// escrow 只有租金和保证金两部分 lamports(加上别人直接转入的), 取出之后仍然必须免租金
+pub(crate) fn pay_bond<'info>(
+    escrow: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    bond: u64,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(escrow.data_len());
+    let remaining = escrow
+        .lamports()
+        .checked_sub(bond)
+        .ok_or(EscrowError::MathOverflow)?;
+    require_gte!(remaining, rent, EscrowError::MathOverflow);
+
+    escrow.sub_lamports(bond)?;
+    to.add_lamports(bond)?;
+
+    Ok(())
+}
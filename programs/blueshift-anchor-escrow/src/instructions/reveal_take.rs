@@ -0,0 +1,61 @@
+// 嵌套 Take 账户列表时还需要 derive(Accounts) 为它生成的 TakeBumps 等类型, 因此整体导入
+use crate::instructions::take::*;
+use crate::{pda::COMMIT_SEED, results::TakeResult, state::TakeCommit};
+use anchor_lang::prelude::*;
+
+// commit-reveal 的第二步: 在 Take 的账户列表之外增加 taker 之前 commit_take 创建的承诺账户
+// salt 和承诺匹配并且等待时间已过之后, 按 take 的流程成交, 承诺账户的租金还给 taker
+#[derive(Accounts)]
+pub struct RevealTake<'info> {
+    pub take: Take<'info>,
+
+    // 只有 taker 自己的承诺可以使用, 地址由 taker 和承诺中的哈希派生
+    // close 约束只能指向同一层的账户, 因此在 handler 中关闭
+    #[account(
+        mut,
+        seeds = [COMMIT_SEED, take.taker.key().as_ref(), commit.hash.as_ref()],
+        bump = commit.bump,
+        constraint = commit.taker == take.taker.key(),
+    )]
+    pub commit: Account<'info, TakeCommit>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RevealTake<'info>>,
+    salt: [u8; 32],
+    max_receive: u64,
+    expected_amount_a: u64,
+    proof: Vec<[u8; 32]>,
+    preimage: Vec<u8>,
+    write_receipt: bool,
+) -> Result<TakeResult> {
+    let escrow = ctx.accounts.take.escrow.key();
+    ctx.accounts.take.escrow.check_reveal(
+        &escrow,
+        &ctx.accounts.commit,
+        &salt,
+        Clock::get()?.slot,
+    )?;
+
+    // 承诺只能使用一次, 租金还给 taker
+    let taker = ctx.accounts.take.taker.to_account_info();
+    ctx.accounts.commit.close(taker)?;
+
+    // 其余流程和 take 完全相同
+    let take_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.take,
+        ctx.remaining_accounts,
+        ctx.bumps.take,
+    );
+    fill(
+        take_ctx,
+        max_receive,
+        expected_amount_a,
+        proof,
+        preimage,
+        write_receipt,
+        0,
+    )
+}
@@ -0,0 +1,40 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, BPS_DENOMINATOR},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetAbandonPolicy<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(
+    ctx: Context<SetAbandonPolicy>,
+    abandon_seconds: i64,
+    abandon_cranker_bps: u16,
+) -> Result<()> {
+    // abandon_seconds 为 0 时关闭清理, cranker 的分成不能超过全部租金
+    require_gte!(abandon_seconds, 0, EscrowError::InvalidAbandonPolicy);
+    require_gte!(
+        BPS_DENOMINATOR,
+        abandon_cranker_bps as u64,
+        EscrowError::InvalidAbandonPolicy
+    );
+
+    ctx.accounts.config.abandon_seconds = abandon_seconds;
+    ctx.accounts.config.abandon_cranker_bps = abandon_cranker_bps;
+
+    Ok(())
+}
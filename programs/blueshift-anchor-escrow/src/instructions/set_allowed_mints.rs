@@ -0,0 +1,52 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, MINT_ALLOWLIST_SEED},
+    realloc,
+    state::{Config, MintAllowlist},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetAllowedMints<'info> {
+    // 签名账户, 必须是协议管理员, 支付白名单扩容的租金
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    // mint 白名单, 第一次调用时创建
+    // 已经存在时 space 取当前大小, 通过 init_if_needed 对空间的检查, 大小由 handler 按新列表调整, 见 realloc::resize
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = mint_allowlist.data_len().max(MintAllowlist::space(0)),
+        seeds = [MINT_ALLOWLIST_SEED],
+        bump,
+    )]
+    pub mint_allowlist: Account<'info, MintAllowlist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<SetAllowedMints>, enabled: bool, mints: Vec<Pubkey>) -> Result<()> {
+    // 整个列表一起替换, 已经创建的托管不受影响
+    realloc::resize(
+        &ctx.accounts.mint_allowlist.to_account_info(),
+        &ctx.accounts.admin.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        MintAllowlist::space(mints.len()),
+    )?;
+
+    let mint_allowlist = &mut ctx.accounts.mint_allowlist;
+    mint_allowlist.enabled = enabled;
+    mint_allowlist.bump = ctx.bumps.mint_allowlist;
+    mint_allowlist.mints = mints;
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::{errors::EscrowError, events::DelegateEvent, pda::ESCROW_SEED, state::Escrow};
+use anchor_lang::prelude::*;
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    // 签名账户, 只有托管的创建者可以修改 refund_delegate, delegate 自己不能转授
+    pub maker: Signer<'info>,
+
+    // 托管账户的数据账户, 只修改 refund_delegate
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+// new_delegate 为 Pubkey::default() 时撤销 delegate
+pub fn handler(ctx: Context<SetDelegate>, new_delegate: Pubkey) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    let old_delegate = escrow.refund_delegate;
+    escrow.refund_delegate = new_delegate;
+
+    emit_cpi!(DelegateEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        old_delegate,
+        new_delegate,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
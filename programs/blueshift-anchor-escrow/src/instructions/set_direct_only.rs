@@ -0,0 +1,24 @@
+use crate::{errors::EscrowError, pda::ESCROW_SEED, state::Escrow};
+use anchor_lang::prelude::*;
+
+// 开启后托管只能由交易的顶层指令成交, 通过 CPI 成交时外层程序必须在 Config::trusted_routers 中, 见 caller.rs
+// take 需要额外传入 instructions sysvar; 其他成交指令不读取它, 只能在顶层调用
+#[derive(Accounts)]
+pub struct SetDirectOnly<'info> {
+    // 签名账户, 只有托管的创建者可以修改
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<SetDirectOnly>, direct_only: bool) -> Result<()> {
+    ctx.accounts.escrow.direct_only = direct_only;
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, BPS_DENOMINATOR},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetFee<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(ctx: Context<SetFee>, fee_bps: u16) -> Result<()> {
+    // 手续费比例不能超过 100%
+    require_gte!(BPS_DENOMINATOR, fee_bps as u64, EscrowError::InvalidFee);
+
+    ctx.accounts.config.fee_bps = fee_bps;
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetFeeOnTips<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(ctx: Context<SetFeeOnTips>, fee_on_tips: bool) -> Result<()> {
+    ctx.accounts.config.fee_on_tips = fee_on_tips;
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, FeeTier, BPS_DENOMINATOR, MAX_FEE_TIERS},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+// 替换全部手续费档位, 传入空列表时关闭档位
+pub fn handler(ctx: Context<SetFeeTiers>, tiers: Vec<FeeTier>) -> Result<()> {
+    require_gte!(MAX_FEE_TIERS, tiers.len(), EscrowError::InvalidFeeTiers);
+    // 成交次数越多手续费越低, 每个档位都不能超过 100%
+    require!(
+        tiers
+            .iter()
+            .all(|tier| tier.fee_bps as u64 <= BPS_DENOMINATOR)
+            && tiers.windows(2).all(|pair| {
+                pair[0].min_trades < pair[1].min_trades && pair[0].fee_bps >= pair[1].fee_bps
+            }),
+        EscrowError::InvalidFeeTiers
+    );
+
+    let config = &mut ctx.accounts.config;
+    config.fee_tiers = [FeeTier::default(); MAX_FEE_TIERS];
+    config.fee_tiers[..tiers.len()].copy_from_slice(&tiers);
+    config.fee_tier_count = tiers.len() as u8;
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, Escrow},
+};
+use anchor_lang::prelude::*;
+
+// freeze_escrow 和 unfreeze_escrow 共用的账户列表
+#[derive(Accounts)]
+pub struct SetFrozen<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    // 要冻结或解冻的托管, 管理员不能修改其他字段, 也不能转出 vault 中的 token
+    #[account(mut)]
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<SetFrozen>, frozen: bool) -> Result<()> {
+    // 冻结只阻止成交, maker 仍然可以通过 refund 取回 token A
+    if frozen {
+        ctx.accounts.escrow.freeze()
+    } else {
+        ctx.accounts.escrow.unfreeze()
+    }
+}
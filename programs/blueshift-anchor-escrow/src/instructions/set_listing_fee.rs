@@ -0,0 +1,34 @@
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetListingFee<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(
+    ctx: Context<SetListingFee>,
+    listing_fee_lamports: u64,
+    treasury: Pubkey,
+) -> Result<()> {
+    // 收取挂单费时必须有接收的账户, 挂单费为 0 时 treasury 不会被使用
+    require!(
+        listing_fee_lamports == 0 || treasury != Pubkey::default(),
+        EscrowError::InvalidTreasury
+    );
+
+    ctx.accounts.config.listing_fee_lamports = listing_fee_lamports;
+    ctx.accounts.config.treasury = treasury;
+
+    Ok(())
+}
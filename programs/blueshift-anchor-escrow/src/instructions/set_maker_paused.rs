@@ -0,0 +1,24 @@
+use crate::{errors::EscrowError, pda::ESCROW_SEED, state::Escrow};
+use anchor_lang::prelude::*;
+
+// pause_offer 和 resume_offer 共用的账户列表
+#[derive(Accounts)]
+pub struct SetMakerPaused<'info> {
+    // 签名账户, 只有托管的创建者可以暂停或恢复
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<SetMakerPaused>, paused: bool) -> Result<()> {
+    // 暂停只阻止这个托管的成交, refund 和 cancel 不受影响, 协议的 pause 不会修改这个标志
+    ctx.accounts.escrow.maker_paused = paused;
+
+    Ok(())
+}
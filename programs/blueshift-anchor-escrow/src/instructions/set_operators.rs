@@ -0,0 +1,37 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, MAX_OPERATORS},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetOperators<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+// 替换全部运营方, 传入空列表时关闭 operator_refund; 只影响 maker 设置了 allow_operators 的托管
+pub fn handler(ctx: Context<SetOperators>, operators: Vec<Pubkey>) -> Result<()> {
+    require_gte!(
+        MAX_OPERATORS,
+        operators.len(),
+        EscrowError::InvalidOperators
+    );
+
+    let config = &mut ctx.accounts.config;
+    config.operators = [Pubkey::default(); MAX_OPERATORS];
+    config.operators[..operators.len()].copy_from_slice(&operators);
+    config.operator_count = operators.len() as u8;
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+// pause 和 unpause 共用的账户列表
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+    // 暂停只阻止 make 和 take, refund 等退出操作不受影响
+    ctx.accounts.config.paused = paused;
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use crate::{errors::EscrowError, pda::PREFS_SEED, state::MakerPrefs};
+use anchor_lang::prelude::*;
+
+// maker 创建或覆盖自己的默认挂单参数, 之后 make_v2 传入 prefs 时用它们填充没有指定的参数
+#[derive(Accounts)]
+pub struct SetPrefs<'info> {
+    // 签名账户, 默认参数所属的 maker, 第一次设置时支付租金
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = MakerPrefs::INIT_SPACE + MakerPrefs::DISCRIMINATOR.len(),
+        seeds = [PREFS_SEED, maker.key().as_ref()],
+        bump,
+    )]
+    pub prefs: Account<'info, MakerPrefs>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// 参数的含义见 MakerPrefs, 每次调用都替换全部字段
+pub fn handler(
+    ctx: Context<SetPrefs>,
+    expiry_duration: i64,
+    receive_to: Pubkey,
+    reject_freezable: bool,
+) -> Result<()> {
+    // 有效期从 make_v2 执行时开始计算, 负数得到的过期时间早于创建时间
+    require_gte!(expiry_duration, 0, EscrowError::InvalidExpiry);
+
+    ctx.accounts.prefs.set_inner(MakerPrefs {
+        maker: ctx.accounts.maker.key(),
+        expiry_duration,
+        receive_to,
+        reject_freezable,
+        bump: ctx.bumps.prefs,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use crate::{errors::EscrowError, events::PriceBandEvent, pda::ESCROW_SEED, state::Escrow};
+use anchor_lang::prelude::*;
+
+// 为固定价格的托管设置 Pyth 价格区间, take 时 receive 折算的单价偏离预言机价格超过 max_deviation_bps 时拒绝成交
+// 价格区间只是防止填错价格的保护, 成交时仍然按 receive 支付; 和 make 放在同一个交易中可以保证托管从创建开始就受到保护
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetPriceBand<'info> {
+    // 签名账户, 只有托管的创建者可以修改价格区间
+    pub maker: Signer<'info>,
+
+    // 托管账户的数据账户, 只修改预言机相关的字段
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+// max_deviation_bps 为 0 时取消价格区间, 其他参数被忽略
+// price_feed, quote_exponent 和 max_staleness 的含义和 make_oracle 相同
+pub fn handler(
+    ctx: Context<SetPriceBand>,
+    price_feed: Pubkey,
+    quote_exponent: i32,
+    max_staleness: i64,
+    max_deviation_bps: u16,
+) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+    require!(!escrow.is_oracle_priced(), EscrowError::OraclePriced);
+
+    if max_deviation_bps == 0 {
+        escrow.price_feed = Pubkey::default();
+        escrow.quote_exponent = 0;
+        escrow.max_staleness = 0;
+    } else {
+        // 价格区间只对 mint_b 计算, 其他支付 mint, SOL 模式, collection 托管和需要仲裁人锁定的托管都不经过 take 的检查
+        require!(
+            price_feed != Pubkey::default()
+                && (1..=Escrow::MAX_ORACLE_STALENESS).contains(&max_staleness)
+                && !escrow.is_sol_mode()
+                && !escrow.is_collection_offer()
+                && !escrow.is_arbitrated()
+                && escrow.alt_payments.iter().all(|payment| !payment.is_set()),
+            EscrowError::InvalidOracleConfig
+        );
+        escrow.price_feed = price_feed;
+        escrow.quote_exponent = quote_exponent;
+        escrow.max_staleness = max_staleness;
+    }
+    escrow.max_deviation_bps = max_deviation_bps;
+
+    emit_cpi!(PriceBandEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        price_feed: ctx.accounts.escrow.price_feed,
+        max_deviation_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, BPS_DENOMINATOR},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetReferral<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler(ctx: Context<SetReferral>, referral_bps: u16) -> Result<()> {
+    // referrer 最多分得全部手续费
+    require_gte!(
+        BPS_DENOMINATOR,
+        referral_bps as u64,
+        EscrowError::InvalidFee
+    );
+
+    ctx.accounts.config.referral_bps = referral_bps;
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use crate::{
+    errors::EscrowError,
+    events::RoyaltyEnforcementEvent,
+    metadata::{NftMetadata, METADATA_SEED, TOKEN_METADATA_ID},
+    pda::ESCROW_SEED,
+    state::Escrow,
+};
+use anchor_lang::prelude::*;
+
+// 出售 NFT 的托管开启后, take 从 taker 支付的 token B 中按 mint A 的 Metadata 向 verified creator 支付版税
+// 版税由 maker 承担, 其余成交指令(take_partial, take_with_sig, take_with_swap, accept_counter 和 match_escrows)不可用
+// 没有开启的托管(包括所有的 fungible 托管)成交时不读取 Metadata
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetRoyaltyEnforcement<'info> {
+    // 签名账户, 只有托管的创建者可以修改
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// CHECK: mint A 的 Metaplex Metadata 账户, 由 seeds 约束地址, 开启时由 NftMetadata::load 检查 owner 和数据
+    #[account(
+        seeds = [METADATA_SEED, TOKEN_METADATA_ID.as_ref(), escrow.mint_a.as_ref()],
+        seeds::program = TOKEN_METADATA_ID,
+        bump,
+    )]
+    pub nft_metadata: UncheckedAccount<'info>,
+}
+
+pub fn handler(ctx: Context<SetRoyaltyEnforcement>, enforce_royalties: bool) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow;
+
+    if enforce_royalties {
+        // 版税只从 take 支付的 mint_b 中扣除, SOL 模式, collection 托管和需要仲裁人锁定的托管都不经过 take
+        require!(
+            !escrow.is_sol_mode() && !escrow.is_collection_offer() && !escrow.is_arbitrated(),
+            EscrowError::RoyaltiesUnsupported
+        );
+        // 提前检查 mint A 确实有 Metadata, 避免开启之后所有的 take 都失败
+        NftMetadata::load(&ctx.accounts.nft_metadata)?;
+    }
+    escrow.enforce_royalties = enforce_royalties;
+
+    emit_cpi!(RoyaltyEnforcementEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        enforce_royalties,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
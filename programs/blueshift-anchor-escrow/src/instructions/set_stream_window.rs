@@ -0,0 +1,38 @@
+use crate::{
+    errors::EscrowError,
+    pda::ESCROW_SEED,
+    state::{Escrow, EscrowStatus},
+};
+use anchor_lang::prelude::*;
+
+// 设置后 taker 可以通过 take_streaming 锁定价格并分期支付, 见 take_streaming.rs; 0 表示不允许
+#[derive(Accounts)]
+pub struct SetStreamWindow<'info> {
+    // 签名账户, 只有托管的创建者可以修改
+    pub maker: Signer<'info>,
+
+    // 分期成交开始后截止时间已经确定, 不能再修改
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = escrow.status != EscrowStatus::Streaming @ EscrowError::InvalidEscrowStatus
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+pub fn handler(ctx: Context<SetStreamWindow>, stream_window: i64) -> Result<()> {
+    require!(
+        (0..=Escrow::MAX_STREAM_WINDOW).contains(&stream_window),
+        EscrowError::InvalidStreamWindow
+    );
+    // HTLC 托管公开 preimage 之后必须一次交付全部 token A
+    require!(
+        stream_window == 0 || !ctx.accounts.escrow.is_hashlocked(),
+        EscrowError::StreamingUnsupported
+    );
+    ctx.accounts.escrow.stream_window = stream_window;
+
+    Ok(())
+}
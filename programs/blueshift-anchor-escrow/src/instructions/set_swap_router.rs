@@ -0,0 +1,27 @@
+use crate::{errors::EscrowError, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetSwapRouter<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+// swap_router 为 Pubkey::default() 时停用 take_with_swap
+// 不能是托管程序自己, 否则 taker 可以通过路由数据调用任意托管指令
+pub fn handler(ctx: Context<SetSwapRouter>, swap_router: Pubkey) -> Result<()> {
+    require_keys_neq!(swap_router, crate::ID, EscrowError::InvalidSwapRouter);
+
+    ctx.accounts.config.swap_router = swap_router;
+
+    Ok(())
+}
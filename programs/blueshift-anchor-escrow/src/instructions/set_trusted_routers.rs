@@ -0,0 +1,42 @@
+use crate::{
+    errors::EscrowError,
+    pda::CONFIG_SEED,
+    state::{Config, MAX_TRUSTED_ROUTERS},
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetTrustedRouters<'info> {
+    // 签名账户, 必须是协议管理员
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+// 替换全部可信路由, 传入空列表时 direct_only 的托管只能在顶层成交
+// 不能包含托管程序自己, 否则任何 CPI 都会被当作可信路由的调用
+pub fn handler(ctx: Context<SetTrustedRouters>, routers: Vec<Pubkey>) -> Result<()> {
+    require_gte!(
+        MAX_TRUSTED_ROUTERS,
+        routers.len(),
+        EscrowError::InvalidTrustedRouters
+    );
+    require!(
+        !routers.contains(&crate::ID),
+        EscrowError::InvalidTrustedRouters
+    );
+
+    let config = &mut ctx.accounts.config;
+    config.trusted_routers = [Pubkey::default(); MAX_TRUSTED_ROUTERS];
+    config.trusted_routers[..routers.len()].copy_from_slice(&routers);
+    config.trusted_router_count = routers.len() as u8;
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// 嵌套 Take 账户列表时还需要 derive(Accounts) 为它生成的 TakeBumps 等类型, 因此整体导入
+use crate::instructions::take::*;
+use crate::{instructions::reserve::pay_bond, results::TakeResult};
+use anchor_lang::prelude::*;
+
+// 延迟成交的第二步: reserve 预约托管的 taker 在截止时间之前按 take 的流程成交, 保证金退还给 taker
+// 账户列表和 take 完全相同, taker 必须是 escrow.reserved_taker
+#[derive(Accounts)]
+pub struct Settle<'info> {
+    pub take: Take<'info>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Settle<'info>>,
+    max_receive: u64,
+    expected_amount_a: u64,
+    proof: Vec<[u8; 32]>,
+    preimage: Vec<u8>,
+    write_receipt: bool,
+) -> Result<TakeResult> {
+    let now = Clock::get()?.unix_timestamp;
+    let taker = ctx.accounts.take.taker.key();
+    let bond = ctx.accounts.take.escrow.settle_reservation(&taker, now)?;
+
+    // 先取出保证金, escrow 关闭时剩下的租金还给 rent_payer
+    pay_bond(
+        &ctx.accounts.take.escrow.to_account_info(),
+        &ctx.accounts.take.taker.to_account_info(),
+        bond,
+    )?;
+
+    // 其余流程和 take 完全相同
+    let take_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.take,
+        ctx.remaining_accounts,
+        ctx.bumps.take,
+    );
+    fill(
+        take_ctx,
+        max_receive,
+        expected_amount_a,
+        proof,
+        preimage,
+        write_receipt,
+        0,
+    )
+}
@@ -0,0 +1,33 @@
+use crate::{
+    errors::EscrowError,
+    instructions::take_streaming::{pay_installment, TakeStreaming},
+    state::EscrowStatus,
+};
+use anchor_lang::prelude::*;
+
+// 分期成交开始之后 locked_taker 支付下一期, 使用 take_streaming 的账户列表
+// 截止之后不能继续支付, 剩余的 token A 由 maker 通过 refund 取回
+// 协议暂停时仍然可以支付: 分期成交已经开始, 暂停不能让 taker 错过 stream_deadline
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeStreaming<'info>>,
+    amount_b: u64,
+) -> Result<()> {
+    let escrow = &ctx.accounts.escrow;
+    require!(
+        escrow.status == EscrowStatus::Streaming,
+        EscrowError::InvalidEscrowStatus
+    );
+    require_keys_eq!(
+        ctx.accounts.taker.key(),
+        escrow.locked_taker,
+        EscrowError::UnauthorizedTaker
+    );
+    let now = Clock::get()?.unix_timestamp;
+    require_gte!(
+        escrow.stream_deadline,
+        now,
+        EscrowError::StreamDeadlinePassed
+    );
+
+    pay_installment(ctx, amount_b, now)
+}
@@ -0,0 +1,84 @@
+// 嵌套 Refund 账户列表时还需要 derive(Accounts) 为它生成的 RefundBumps 等类型, 因此整体导入
+use crate::instructions::refund::*;
+use crate::{errors::EscrowError, events::SweepAbandonedEvent, pda::CONFIG_SEED, state::Config};
+use anchor_lang::prelude::*;
+
+// 清理创建超过 config.abandon_seconds 的托管, 和 refund 一样把 token A 全部退还给 maker
+// 不同的是回收的租金按 config.abandon_cranker_bps 分给 cranker 一部分, 作为清理的奖励
+// refund.authority 是 cranker, 任何人都可以在托管被放弃后调用, 不需要是 maker; maker 的 ATA 被关闭时由它支付租金重新创建
+// remaining_accounts 和 refund 相同: mint A 的 transfer hook 额外账户, pNFT 时在最前面传入 Token Metadata 转账需要的账户
+#[derive(Accounts)]
+pub struct SweepAbandoned<'info> {
+    pub refund: Refund<'info>,
+
+    // 协议配置账户, 提供放弃的时长和 cranker 的分成比例
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, SweepAbandoned<'info>>) -> Result<()> {
+    // 只有创建时间足够久的托管才能被任何人清理
+    let now = Clock::get()?.unix_timestamp;
+    let escrow = &ctx.accounts.refund.escrow;
+    require!(
+        ctx.accounts.config.is_abandoned(escrow.created_at, now),
+        EscrowError::EscrowNotAbandoned
+    );
+    // refund 也接受分期成交中的托管, 清理只针对仍然可以退还的托管
+    require!(
+        escrow.status.is_refundable(),
+        EscrowError::InvalidEscrowStatus
+    );
+
+    let config = &ctx.accounts.config;
+    let refund_ctx = Context::new(
+        ctx.program_id,
+        &mut ctx.accounts.refund,
+        ctx.remaining_accounts,
+        ctx.bumps.refund,
+    );
+    sweep(refund_ctx, config, now)
+}
+
+// 和 refund 共用退还 token A 的路径, 不放弃冻结的 vault, maker 总是收到全部 token A
+// vault 的租金先转入 escrow, 分给 cranker 之后剩余的租金和 escrow 一起还给 rent_payer
+// 只有两个账户的租金参与分成, make 之前别人转入 escrow 地址的 lamports 全部还给 rent_payer
+fn sweep<'info>(
+    mut ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+    config: &Config,
+    now: i64,
+) -> Result<()> {
+    let escrow = ctx.accounts.escrow.to_account_info();
+    let before = escrow.lamports();
+    let amount = withdraw(&mut ctx, false, escrow.clone())?;
+
+    // withdraw 关闭 vault 时把它的租金转入 escrow, 前后的差额就是 vault 的租金
+    let vault_rent = escrow
+        .lamports()
+        .checked_sub(before)
+        .ok_or(EscrowError::MathOverflow)?;
+    let reclaimed_rent = Rent::get()?
+        .minimum_balance(escrow.data_len())
+        .checked_add(vault_rent)
+        .ok_or(EscrowError::MathOverflow)?;
+
+    // 从回收的租金里把 cranker 的分成直接转给 cranker
+    let cranker_lamports = config.cranker_share_for(reclaimed_rent)?;
+    escrow.sub_lamports(cranker_lamports)?;
+    ctx.accounts.authority.add_lamports(cranker_lamports)?;
+
+    emit_cpi!(SweepAbandonedEvent {
+        escrow: ctx.accounts.escrow.key(),
+        maker: ctx.accounts.maker.key(),
+        cranker: ctx.accounts.authority.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        amount,
+        cranker_lamports,
+        rent_payer_lamports: escrow.lamports(),
+        timestamp: now,
+    });
+
+    ctx.accounts
+        .escrow
+        .close(ctx.accounts.rent_payer.to_account_info())
+}
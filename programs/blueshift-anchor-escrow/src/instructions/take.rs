@@ -1,152 +1,1045 @@
-use crate::{errors::EscrowError, state::Escrow};
-use anchor_lang::prelude::*;
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::{RefillEvent, RoyaltyPayment, TakeEvent},
+    metadata::NftMetadata,
+    oracle::OraclePrice,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED,
+        RECEIPT_SEED, REGISTRY_SEED, STATS_SEED, USER_STATS_SEED,
+    },
+    pnft::{self, PnftAccounts, PnftTransfer},
+    results::TakeResult,
+    state::{
+        Config, Escrow, GlobalStats, MakerRegistry, MintBlocklist, PairIndex, TradeReceipt,
+        UserStats,
+    },
+    transfer,
+};
+use anchor_lang::{prelude::*, solana_program::program_option::COption, system_program};
 use anchor_spl::{
-    associated_token::AssociatedToken,
+    associated_token::{self, AssociatedToken},
+    memo::Memo,
+    token::spl_token::native_mint,
     token_interface::{
-        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
-        TransferChecked,
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
     },
 };
 
+// remaining_accounts: 先传 mint A 再传 mint B 的 transfer hook 额外账户, 两个 mint 都没有 hook 时为空
+// mint A 是 pNFT 时在最前面传入 Token Metadata 转账需要的账户, 见 pnft.rs; taker 必须用 ATA 接收
+// enforce_royalties 的托管在 pNFT 账户之后依次传入 mint A 的 Metadata 和每个分到版税的 verified creator 的 token B ATA,
+// 顺序和 Metadata 中的 creator 相同, 见 split_royalties; 之后才是 transfer hook 的额外账户
+// 接收 token 的 ATA 不使用 init_if_needed, 由 prepare_atas 手动检查, 只有需要创建时才要求 ATA 程序和系统程序
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Take<'info> {
-    // 签名账户, 是取走托管资金 Token A 的账户
+    // 签名账户, 是取走托管资金 Token A 的账户, 也是 token B 的转出 authority
     #[account(mut)]
     pub taker: Signer<'info>,
 
-    // 托管账户的创建者, 须要把 Token B 转账给这个账户
+    // 支付创建 ATA 的租金, 例如替 taker 提交交易的 relayer; 没有 relayer 时传 taker 自己
+    // 只用来付租金, token B 始终从 taker 的账户转出
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束
+    /// 不要求是系统账户: maker 可以是其他程序拥有的 PDA(例如 DAO 的金库), token 都转入它的 ATA
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 token B 的钱包, 只作为 maker_ata_b 的 authority 和 wSOL 解包后的去向, 由 has_one 约束为 escrow.receive_to
     #[account(mut)]
-    pub maker: SystemAccount<'info>,
+    pub receive_to: UncheckedAccount<'info>,
 
     // 托管账户的数据账户, 此时不需要 init, 因为这个账户在 make 阶段已经初始化了
+    // 成交后由 fill 关闭, 租金还给支付租金的账户; 循环托管重新存入时保持打开
     #[account(
       mut,
-      close = maker, // 关闭数据账户, 租金归 maker 所有
-      seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()], // 数据账户的种子
+      seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()], // 数据账户的种子
       bump = escrow.bump, // 数据账户的 bump 值
       has_one = maker @ EscrowError::InvalidMaker, // 验证数据账户的 maker 是否是 maker
+      has_one = rent_payer @ EscrowError::InvalidRentPayer,
+      has_one = receive_to @ EscrowError::InvalidReceiveTo,
       has_one = mint_a @ EscrowError::InvalidMintA, // 验证数据账户的 mint_a 是否是 mint_a
-      has_one = mint_b @ EscrowError::InvalidMintB, // 验证数据账户的 mint_b 是否是 mint_b
+      constraint = escrow.accepts_mint_b(&mint_b.key()) @ EscrowError::InvalidMintB, // mint_b 是 escrow.mint_b 或 alt_payments 中的一个
+      constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker, // 私有托管只允许指定的 taker
+      constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade, // maker 不能成交自己的托管, 防止刷量
+      constraint = !escrow.is_arbitrated() @ EscrowError::ArbiterRequired, // 有仲裁人的托管只能通过 lock_take 锁定
+      constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen, // 冻结期间只能退还
   )]
     pub escrow: Box<Account<'info, Escrow>>, // 使用 Box 减少 stack 的大小
 
-    // Token A 和 Token B 的 mint 账户
+    // Token A 和 Token B 的 mint 账户, 分别由各自的 token 程序管理
+    // mint_b 是 taker 选择的支付 mint, 下面的 token B 账户都属于它
+    // make_for_sol 创建的托管 mint_b 为 Pubkey::default(), 不可能是 mint 账户, 因此会被 accepts_mint_b 拒绝
+    // owner 约束代替 mint::token_program, 传错 token 程序时返回 WrongTokenProgram
+    #[account(owner = token_program_a.key() @ EscrowError::WrongTokenProgram)]
     pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    // 防止链上已存在的相同 mint 的托管被成交, 不依赖前端检查
+    #[account(
+      owner = token_program_b.key() @ EscrowError::WrongTokenProgram,
+      constraint = mint_a.key() != mint_b.key() @ EscrowError::IdenticalMints,
+  )]
     pub mint_b: Box<InterfaceAccount<'info, Mint>>,
 
-    // 托管资金 ATA 账户
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验, 不重新派生 ATA 地址
     #[account(
       mut,
-      associated_token::mint = mint_a,
-      associated_token::authority = escrow,
-      associated_token::token_program = token_program
+      address = escrow.vault @ EscrowError::InvalidVault,
+      owner = token_program_a.key() @ EscrowError::WrongTokenProgram,
+      token::mint = mint_a,
+      token::authority = escrow
   )]
     pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    // 取款者的 Token A 的 ATA 账户, 用来接收 Token A, init_if_needed 表示如果不存在则创建(有可能 taker 没有这个账户)
+    /// CHECK: 取款者的 Token A 的 ATA 账户, 用来接收 Token A, 不存在时创建(有可能 taker 没有这个账户), 见 prepare_atas
+    /// 传入 taker_token_a 时不需要传
+    #[account(mut)]
+    pub taker_ata_a: Option<UncheckedAccount<'info>>,
+
+    // taker 已有的任意 Token A 账户(例如托管机构使用的非 ATA 账户), 传入时代替 taker_ata_a 接收 Token A
+    // 必须属于 taker, 防止把 token A 转给别人; 设置了 delegate 或 close authority 的账户可能被第三方转走或关闭, 直接拒绝
     #[account(
-      init_if_needed,
-      payer = taker,
-      associated_token::mint = mint_a,
-      associated_token::authority = taker,
-      associated_token::token_program = token_program
+      mut,
+      token::mint = mint_a,
+      token::authority = taker,
+      token::token_program = token_program_a,
+      constraint = taker_token_a.delegate.is_none() @ EscrowError::DelegatedTakerAccount,
+      constraint = taker_token_a.close_authority.is_none() @ EscrowError::DelegatedTakerAccount,
   )]
-    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub taker_token_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: 托管账户创建者的 Token A 的 ATA 账户, 用来退还 vault 中超出 escrow.amount 的多余代币, 见 prepare_atas
+    #[account(mut)]
+    pub maker_ata_a: UncheckedAccount<'info>,
 
-    // 取款者的 Token B 的 ATA 账户, 用来把 Token B 转账给 maker
+    // 取款者的 Token B 账户, 用来把 Token B 转账给 maker
+    // 由 taker 签名转出, 因此不要求是 ATA, taker 拥有的任意 Token B 账户都可以
+    // taker 选择 alt_payments 中的 mint 时最容易传错账户, mint 不一致时返回 TakerAtaBWrongMint
     #[account(
       mut,
-      associated_token::mint = mint_b,
-      associated_token::authority = taker,
-      associated_token::token_program = token_program
+      owner = token_program_b.key() @ EscrowError::WrongTokenProgram,
+      constraint = taker_ata_b.mint == mint_b.key() @ EscrowError::TakerAtaBWrongMint,
+      token::authority = taker
   )]
     pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    // 托管账户创建者的 Token B 的 ATA 账户, 用来接收所希望换取的 Token B
+    /// CHECK: receive_to(默认是 maker)的 Token B 的 ATA 账户, 用来接收所希望换取的 Token B, 见 prepare_atas
+    /// 传入 unwrap_ata_b 解包 wSOL 时 receive_to 直接收到 SOL, 不需要传
+    #[account(mut)]
+    pub maker_ata_b: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: 可选, 只能在 token B 是 wSOL 时传入: escrow 的 wSOL ATA, 作为临时账户转入 maker 的部分后立即关闭, 把 wSOL 换成 SOL
+    /// maker 自己的 ATA 只有 maker 能关闭, 所以使用 escrow 作为 authority 的账户; 租金由 payer 支付, 关闭时连同 SOL 一起还给 payer,
+    /// 再由 payer 把 amount_b 个 lamports 转给 receive_to. 不传时 wSOL 和其他 token 一样转入 maker_ata_b
+    #[account(
+      mut,
+      constraint = mint_b.key() == native_mint::ID @ EscrowError::NotNativeMint,
+  )]
+    pub unwrap_ata_b: Option<UncheckedAccount<'info>>,
+
+    // 协议配置账户, 读取手续费比例并检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 全局统计账户, make 总是先创建它, 因此成交时一定已经存在, 不需要系统程序
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    // taker 的成交统计, 第一次传入时由 taker 支付租金创建, 传入时需要系统程序; 启用手续费档位时必须传入, 按它选择手续费比例
     #[account(
       init_if_needed,
       payer = taker,
+      space = UserStats::INIT_SPACE + UserStats::DISCRIMINATOR.len(),
+      seeds = [USER_STATS_SEED, taker.key().as_ref()],
+      bump,
+  )]
+    pub taker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    // maker 的成交统计, maker 不签名, 因此必须已经通过 init_user_stats 创建; 不传时不计入
+    #[account(
+      mut,
+      seeds = [USER_STATS_SEED, maker.key().as_ref()],
+      bump = maker_stats.bump,
+  )]
+    pub maker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 Token B 手续费的 ATA 账户, 手续费为 0 时可以不传
+    #[account(
+      mut,
       associated_token::mint = mint_b,
-      associated_token::authority = maker,
-      associated_token::token_program = token_program
+      associated_token::authority = fee_authority,
+      associated_token::token_program = token_program_b
+  )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 转介成交的聚合器等的 Token B 账户, 传入时分得一部分手续费, 不能属于 taker 自己
+    #[account(
+      mut,
+      token::mint = mint_b,
+      token::token_program = token_program_b,
+      constraint = referrer_ata_b.owner != taker.key() @ EscrowError::SelfReferral,
+  )]
+    pub referrer_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 成交记录, write_receipt 为 true 时必须传入, 同时需要系统程序; 租金由 taker 支付, 之后 maker 或 taker 可以通过 close_receipt 取回
+    // 地址由 escrow 派生, 同一个 seed 重新创建的托管再次写入记录之前需要先关闭旧的记录
+    #[account(
+      init,
+      payer = taker,
+      space = TradeReceipt::INIT_SPACE + TradeReceipt::DISCRIMINATOR.len(),
+      seeds = [RECEIPT_SEED, escrow.key().as_ref()],
+      bump,
   )]
-    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub receipt: Option<Box<Account<'info, TradeReceipt>>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 托管关闭后从索引中移除; 没有计入时可以不传
+    #[account(
+      mut,
+      seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+      bump = pair_index.bump,
+  )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    /// CHECK: 按 Pyth 价格成交的托管必须传入 make_oracle 记录的 PriceUpdateV2 账户, 由 OraclePrice::load 检查 owner 和数据; 其他托管不传
+    #[account(address = escrow.price_feed @ EscrowError::InvalidPriceFeed)]
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: instructions sysvar, direct_only 的托管必须传入, 通过 CPI 成交时用来读取外层的程序, 见 caller.rs; 其他托管不传
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
 
     // 账户所需要的程序
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub system_program: Program<'info, System>,
+    // 接收 token 的 ATA 都已经存在, 并且不传 taker_stats 和 receipt 时, ATA 程序和系统程序都可以不传, 减少交易大小
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理 mint_a 和 vault 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>, // 管理 mint_b 的 token 程序
+    pub memo_program: Program<'info, Memo>,                // 接收方账户要求 memo 时使用
+    pub system_program: Option<Program<'info, System>>,
+}
+
+// 读取 token 账户的数据, 调用之前必须已经检查过 owner 程序
+fn read_token_account(info: &AccountInfo) -> Result<TokenAccount> {
+    TokenAccount::try_deserialize(&mut &info.try_borrow_data()?[..])
+}
+
+// 从 remaining_accounts 中分出版税的账户, 返回每个 creator 的版税, 对应的 ATA 和剩余的 transfer hook 额外账户
+// Metadata 由 Token Metadata 程序拥有并且记录的 mint 是 mint A, 地址不再重新派生
+fn split_royalties<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    mint_a: &Pubkey,
+    amount_b: u64,
+) -> Result<(
+    Vec<RoyaltyPayment>,
+    &'a [AccountInfo<'info>],
+    &'a [AccountInfo<'info>],
+)> {
+    let (metadata, rest) = remaining_accounts
+        .split_first()
+        .ok_or(EscrowError::InvalidRoyaltyAccounts)?;
+    let metadata = NftMetadata::load(metadata)?;
+    require_keys_eq!(metadata.mint, *mint_a, EscrowError::InvalidRoyaltyAccounts);
+
+    let royalties = metadata.royalties(amount_b);
+    require_gte!(
+        rest.len(),
+        royalties.len(),
+        EscrowError::InvalidRoyaltyAccounts
+    );
+    let (creator_atas, rest) = rest.split_at(royalties.len());
+    Ok((royalties, creator_atas, rest))
 }
 
 impl<'info> Take<'info> {
-    // 把 Token B 转账给 maker
-    fn transfer_to_maker(&mut self) -> Result<()> {
-        transfer_checked(
+    // 确保 ata 是 authority 持有的 mint 的 token 账户
+    // 已经存在时只检查 owner 程序, mint 和 authority, 不重新派生 ATA 地址: token 只会转给 authority, 省去一次 find_program_address
+    // 不存在时由 payer 支付租金创建, ATA 程序会检查地址是否正确派生, 此时必须传入 ATA 程序和系统程序
+    fn ensure_ata(
+        &self,
+        ata: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+    ) -> Result<()> {
+        // 不存在的账户属于系统程序, 可能已经有别人转入的 lamports, ATA 程序会补足租金
+        if ata.owner == &System::id() {
+            let (Some(associated_token_program), Some(system_program)) = (
+                self.associated_token_program.as_ref(),
+                self.system_program.as_ref(),
+            ) else {
+                return err!(EscrowError::MissingAtaPrograms);
+            };
+            return associated_token::create(CpiContext::new(
+                associated_token_program.to_account_info(),
+                associated_token::Create {
+                    payer: self.payer.to_account_info(),
+                    associated_token: ata.clone(),
+                    authority: authority.clone(),
+                    mint: mint.clone(),
+                    system_program: system_program.to_account_info(),
+                    token_program: token_program.clone(),
+                },
+            ));
+        }
+
+        require_keys_eq!(
+            *ata.owner,
+            token_program.key(),
+            EscrowError::InvalidAtaAccount
+        );
+        let account = read_token_account(ata)?;
+        require!(
+            account.mint == mint.key() && account.owner == authority.key(),
+            EscrowError::InvalidAtaAccount
+        );
+
+        Ok(())
+    }
+
+    // 在转账之前检查或创建所有接收 token 的 ATA
+    fn prepare_atas(&self) -> Result<()> {
+        let mint_a = self.mint_a.to_account_info();
+        let mint_b = self.mint_b.to_account_info();
+        let token_program_a = self.token_program_a.to_account_info();
+        let token_program_b = self.token_program_b.to_account_info();
+
+        if let Some(taker_ata_a) = &self.taker_ata_a {
+            self.ensure_ata(
+                taker_ata_a,
+                &self.taker.to_account_info(),
+                &mint_a,
+                &token_program_a,
+            )?;
+        }
+        self.ensure_ata(
+            &self.maker_ata_a,
+            &self.maker.to_account_info(),
+            &mint_a,
+            &token_program_a,
+        )?;
+        if let Some(maker_ata_b) = &self.maker_ata_b {
+            self.ensure_ata(
+                maker_ata_b,
+                &self.receive_to.to_account_info(),
+                &mint_b,
+                &token_program_b,
+            )?;
+        }
+        if let Some(unwrap_ata_b) = &self.unwrap_ata_b {
+            self.ensure_ata(
+                unwrap_ata_b,
+                &self.escrow.to_account_info(),
+                &mint_b,
+                &token_program_b,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // 把 Token B 转账给 maker, 返回 maker 扣除 mint B 转账手续费后实际收到的数量
+    fn transfer_to_maker(
+        &mut self,
+        amount_b: u64,
+        signer_seeds: &[&[&[u8]]],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        if self.unwrap_ata_b.is_some() {
+            return self.unwrap_to_maker(amount_b, signer_seeds);
+        }
+
+        let maker_ata_b = self
+            .maker_ata_b
+            .as_ref()
+            .ok_or(EscrowError::MissingMakerAta)?;
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &maker_ata_b.to_account_info(),
+            &self.escrow.key(),
+        )?;
+
+        let withheld = transfer::transfer_checked(
             CpiContext::new(
-                self.token_program.to_account_info(),
+                self.token_program_b.to_account_info(),
                 TransferChecked {
                     from: self.taker_ata_b.to_account_info(),
-                    to: self.maker_ata_b.to_account_info(),
+                    to: maker_ata_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount_b,
+            self.mint_b.decimals,
+        )?;
+
+        amount_b
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 传入 unwrap_ata_b 时, 先把 wSOL 转入 escrow 拥有的临时账户再关闭给 payer, 然后由 payer 转给 receive_to
+    // receive_to 收到的是 amount_b 个 lamports 而不是 wSOL, 临时账户的租金回到支付它的 payer
+    fn unwrap_to_maker(&mut self, amount_b: u64, signer_seeds: &[&[&[u8]]]) -> Result<u64> {
+        let unwrap_ata_b = self
+            .unwrap_ata_b
+            .as_ref()
+            .ok_or(EscrowError::MissingUnwrapAccount)?;
+        let system = self
+            .system_program
+            .as_ref()
+            .ok_or(EscrowError::MissingAtaPrograms)?;
+
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: unwrap_ata_b.to_account_info(),
                     mint: self.mint_b.to_account_info(),
                     authority: self.taker.to_account_info(),
                 },
             ),
-            self.escrow.receive,
+            amount_b,
+            self.mint_b.decimals,
+        )?;
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program_b.to_account_info(),
+            CloseAccount {
+                account: unwrap_ata_b.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.payer.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        system_program::transfer(
+            CpiContext::new(
+                system.to_account_info(),
+                system_program::Transfer {
+                    from: self.payer.to_account_info(),
+                    to: self.receive_to.to_account_info(),
+                },
+            ),
+            amount_b,
+        )?;
+
+        // native mint 没有转账手续费
+        Ok(amount_b)
+    }
+
+    // 把手续费转入协议的手续费 ATA, 手续费为 0 时不做任何 CPI
+    fn transfer_fee(&mut self, fee: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        let fee_vault_b = self
+            .fee_vault_b
+            .as_ref()
+            .ok_or(EscrowError::MissingFeeVault)?;
+
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: fee_vault_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            fee,
             self.mint_b.decimals,
         )?;
 
         Ok(())
     }
 
-    // 从 vault 中取出 Token A 转账给 taker 并关闭 vault
-    fn withdraw_and_close_vault(&mut self) -> Result<()> {
-        // 由于是从 vault PDA 账户中转账, 因此需要提供 PDA 的签名 seeds
-        let signer_seeds: [&[&[u8]]; 1] = [&[
-            b"escrow",
-            self.maker.to_account_info().key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.bump],
-        ]];
+    // 把手续费中 referrer 的部分转给 referrer, 为 0 时不做任何 CPI
+    fn transfer_referral(
+        &mut self,
+        referral_fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        // referral_fee 只有在传入 referrer 账户时才会大于 0
+        let Some(referrer_ata_b) = self.referrer_ata_b.as_ref() else {
+            return Ok(());
+        };
+        if referral_fee == 0 {
+            return Ok(());
+        }
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &referrer_ata_b.to_account_info(),
+            &self.escrow.key(),
+        )?;
 
-        // 把 Token A 转账给 taker
-        transfer_checked(
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: referrer_ata_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            referral_fee,
+            self.mint_b.decimals,
+        )?;
+
+        Ok(())
+    }
+
+    // 把版税转给每个 creator 的 token B ATA, ATA 必须已经存在; 为 0 的版税不做任何 CPI
+    fn pay_royalties(
+        &mut self,
+        royalties: &[RoyaltyPayment],
+        creator_atas: &[AccountInfo<'info>],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        for (royalty, creator_ata) in royalties.iter().zip(creator_atas) {
+            require_keys_eq!(
+                *creator_ata.owner,
+                self.token_program_b.key(),
+                EscrowError::InvalidRoyaltyAccounts
+            );
+            let account = read_token_account(creator_ata)?;
+            require!(
+                account.mint == self.mint_b.key() && account.owner == royalty.creator,
+                EscrowError::InvalidRoyaltyAccounts
+            );
+            if royalty.amount == 0 {
+                continue;
+            }
+
+            transfer::memo_if_required(
+                &self.memo_program.to_account_info(),
+                creator_ata,
+                &self.escrow.key(),
+            )?;
+
+            transfer::transfer_checked(
+                CpiContext::new(
+                    self.token_program_b.to_account_info(),
+                    TransferChecked {
+                        from: self.taker_ata_b.to_account_info(),
+                        to: creator_ata.clone(),
+                        mint: self.mint_b.to_account_info(),
+                        authority: self.taker.to_account_info(),
+                    },
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                royalty.amount,
+                self.mint_b.decimals,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // 接收 Token A 的账户, 优先使用 taker 指定的账户, 否则使用 ATA
+    fn taker_destination_a(&self) -> Result<AccountInfo<'info>> {
+        self.taker_token_a
+            .as_ref()
+            .map(|account| account.to_account_info())
+            .or_else(|| self.taker_ata_a.as_ref().map(|ata| ata.to_account_info()))
+            .ok_or(EscrowError::MissingTakerAccount.into())
+    }
+
+    // 从 vault 中取出 Token A 转账给 taker, 返回 taker 扣除 mint A 转账手续费后实际收到的数量
+    fn withdraw_from_vault(
+        &mut self,
+        signer_seeds: &[&[&[u8]]],
+        pnft: Option<&PnftAccounts<'_, 'info>>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        // 两次转账共用的账户只转换一次, 数量都使用 fill 开始时读取的 vault 数据, CPI 之后不重新读取
+        let token_program_a = self.token_program_a.to_account_info();
+        let memo_program = self.memo_program.to_account_info();
+        let vault = self.vault.to_account_info();
+        let mint_a = self.mint_a.to_account_info();
+        let escrow = self.escrow.to_account_info();
+
+        // 把 escrow 中记录的 Token A 数量转账给 taker
+        let taker_destination_a = self.taker_destination_a()?;
+
+        // pNFT 由 Token Metadata 转出, 它只有 1 个, vault 中不会有多余代币, 也没有转账手续费
+        if let Some(pnft) = pnft {
+            let (Some(associated_token_program), Some(system_program)) = (
+                self.associated_token_program.as_ref(),
+                self.system_program.as_ref(),
+            ) else {
+                return err!(EscrowError::MissingAtaPrograms);
+            };
+            pnft.transfer(
+                PnftTransfer {
+                    token: vault,
+                    token_owner: escrow.clone(),
+                    destination_token: taker_destination_a,
+                    destination_owner: self.taker.to_account_info(),
+                    mint: mint_a,
+                    authority: escrow,
+                    payer: self.payer.to_account_info(),
+                    system_program: system_program.to_account_info(),
+                    token_program: token_program_a,
+                    associated_token_program: associated_token_program.to_account_info(),
+                },
+                self.escrow.amount,
+                signer_seeds,
+            )?;
+            return Ok(self.escrow.amount);
+        }
+
+        transfer::memo_if_required(&memo_program, &taker_destination_a, escrow.key)?;
+        let withheld = transfer::transfer_checked(
             CpiContext::new_with_signer(
-                self.token_program.to_account_info(),
+                token_program_a.clone(),
                 TransferChecked {
-                    from: self.vault.to_account_info(),
-                    to: self.taker_ata_a.to_account_info(),
-                    mint: self.mint_a.to_account_info(),
-                    authority: self.escrow.to_account_info(),
+                    from: vault.clone(),
+                    to: taker_destination_a,
+                    mint: mint_a.clone(),
+                    authority: escrow.clone(),
                 },
-                &signer_seeds,
-            ),
-            self.vault.amount,
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            self.escrow.amount,
             self.mint_a.decimals,
         )?;
 
-        // 关闭 vault 账户
+        // 别人直接转入 vault 的多余代币退还给 maker, 而不是送给 taker
+        let surplus = self
+            .vault
+            .amount
+            .checked_sub(self.escrow.amount)
+            .ok_or(EscrowError::MathOverflow)?;
+        if surplus > 0 {
+            transfer::memo_if_required(&memo_program, &self.maker_ata_a, escrow.key)?;
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program_a,
+                    TransferChecked {
+                        from: vault,
+                        to: self.maker_ata_a.to_account_info(),
+                        mint: mint_a,
+                        authority: escrow,
+                    },
+                    signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                surplus,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        self.escrow
+            .amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 关闭已经转空的 vault 账户
+    fn close_vault(&mut self, signer_seeds: &[&[&[u8]]]) -> Result<()> {
         close_account(CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
+            self.token_program_a.to_account_info(),
             CloseAccount {
                 account: self.vault.to_account_info(),
                 authority: self.escrow.to_account_info(),
-                destination: self.maker.to_account_info(), // 关闭账户后的租金去向, 还给 maker
+                destination: self.rent_payer.to_account_info(), // 关闭账户后的租金去向, 还给支付租金的账户
             },
-            &signer_seeds,
-        ))?;
+            signer_seeds,
+        ))
+    }
 
-        Ok(())
+    // 循环托管可以重新存入时返回数量: maker 的 ATA 授权给 escrow 的额度和余额都必须足够, 否则正常关闭
+    fn refill_amount(&self) -> Result<Option<u64>> {
+        let Some(amount) = self.escrow.refill_amount() else {
+            return Ok(None);
+        };
+        let maker_ata_a = read_token_account(&self.maker_ata_a)?;
+        let approved = maker_ata_a.delegate == COption::Some(self.escrow.key())
+            && maker_ata_a.delegated_amount >= amount;
+
+        Ok(
+            (approved && maker_ata_a.amount >= amount && !maker_ata_a.is_frozen())
+                .then_some(amount),
+        )
+    }
+
+    // 用 escrow PDA 的 delegate 额度从 maker 的 ATA 转入 amount 个 token A, 返回 vault 实际收到的数量
+    fn refill_vault(
+        &mut self,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.maker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    to: self.vault.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_a.decimals,
+        )?;
+        let net_amount = amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow)?;
+
+        self.escrow.refill(net_amount);
+        Ok(net_amount)
     }
 }
 
-pub fn handler(ctx: Context<Take>) -> Result<()> {
-    // 转账 Token B 给 maker
-    ctx.accounts.transfer_to_maker()?;
+// take_v2 的参数, 和 MakeArgs 一样新增的参数只加入这个结构, reserved 必须全部为 0
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct TakeArgs {
+    // taker 能接受支付的 token B 的上限, None 表示不限制(和 take 的 u64::MAX 相同)
+    pub max_receive: Option<u64>,
+    // 必须和 vault 中剩余的 token A 相等, None 表示接受任意数量(和 take 的 0 相同)
+    pub expected_amount_a: Option<u64>,
+    pub proof: Vec<[u8; 32]>,
+    pub preimage: Vec<u8>,
+    pub write_receipt: bool,
+    // 在成交价格之外额外支付给 maker 的 token B, 0 表示不支付; 从 reserved 中划出, 不改变数据的长度
+    pub tip: u64,
+    pub reserved: [u8; 24],
+}
+
+// take 和 take_v2 共用, take 把位置参数原样放入 TakeArgs
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+    args: TakeArgs,
+) -> Result<TakeResult> {
+    require!(args.reserved == [0; 24], EscrowError::ReservedArgsNotZero);
+    // 开启 commit-reveal 的托管只能通过 reveal_take 成交
+    require!(
+        !ctx.accounts.escrow.requires_commit(),
+        EscrowError::CommitRequired
+    );
+    // 预约期间 reserved_taker 也必须通过 settle 成交, 保证金才能退还给它
+    require!(
+        !ctx.accounts.escrow.is_reserved(),
+        EscrowError::EscrowReserved
+    );
+
+    fill(
+        ctx,
+        args.max_receive.unwrap_or(u64::MAX),
+        args.expected_amount_a.unwrap_or_default(),
+        args.proof,
+        args.preimage,
+        args.write_receipt,
+        args.tip,
+    )
+}
+
+// take, reveal_take 和 settle 共用的成交流程, tip 由 taker 在成交价格之外支付给 maker
+pub(crate) fn fill<'info>(
+    ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+    max_receive: u64,
+    expected_amount_a: u64,
+    proof: Vec<[u8; 32]>,
+    preimage: Vec<u8>,
+    write_receipt: bool,
+    tip: u64,
+) -> Result<TakeResult> {
+    // 不需要记录时传入的 receipt 也会被 init 创建, 因此要求两者一致
+    require!(
+        ctx.accounts.receipt.is_some() == write_receipt,
+        EscrowError::InvalidReceiptAccount
+    );
+
+    // direct_only 的托管只能由顶层指令或可信的路由程序成交
+    require!(
+        !ctx.accounts.escrow.direct_only || ctx.accounts.instructions_sysvar.is_some(),
+        EscrowError::MissingInstructionsSysvar
+    );
+    caller::check_direct_call(
+        &ctx.accounts.escrow,
+        &ctx.accounts.config,
+        ctx.accounts.instructions_sysvar.as_deref(),
+    )?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()],
+    )?;
+
+    // 设置了允许列表时, taker 必须提供自己在列表中的默克尔证明
+    require!(
+        ctx.accounts
+            .escrow
+            .is_on_allowlist(&ctx.accounts.taker.key(), &proof),
+        EscrowError::NotOnAllowlist
+    );
+
+    // HTLC 托管必须公开和 hashlock 匹配的 preimage
+    ctx.accounts.escrow.check_preimage(&preimage)?;
+
+    // 到达开始时间之前和过期之后不能成交
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_started(now)?;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    // 按 Pyth 价格成交或设置了价格区间的托管必须传入 price_feed
+    let price = if ctx.accounts.escrow.requires_price_feed() {
+        let price_feed = ctx
+            .accounts
+            .price_feed
+            .as_ref()
+            .ok_or(EscrowError::InvalidPriceFeed)?;
+        Some(OraclePrice::load(&price_feed.to_account_info())?)
+    } else {
+        None
+    };
+    let (decimals_a, decimals_b) = (ctx.accounts.mint_a.decimals, ctx.accounts.mint_b.decimals);
 
-    // 从 vault 中取出 Token A 转账给 taker 并关闭 vault
-    ctx.accounts.withdraw_and_close_vault()?;
+    // 需要支付的 Token B 按 taker 选择的 mint 的当前单价和剩余出售的 token A 折算(固定价格且未部分成交时就是 receive)
+    // 按 Pyth 价格成交的托管只接受 mint_b, 单价由 price_feed 当前的价格计算
+    let effective_receive = match &price {
+        Some(price) if ctx.accounts.escrow.is_oracle_priced() => ctx
+            .accounts
+            .escrow
+            .oracle_receive(price, now, decimals_a, decimals_b)?,
+        _ => ctx
+            .accounts
+            .escrow
+            .receive_in(&ctx.accounts.mint_b.key(), now)?
+            .ok_or(EscrowError::InvalidMintB)?,
+    };
 
-    // 指令执行完毕后 anchor 自动关闭 escrow 数据账户
+    // 价格区间只检查 maker 设置的单价, 在区间内时仍然按 receive 成交
+    if let Some(price) = &price {
+        if ctx.accounts.escrow.has_price_band() {
+            ctx.accounts.escrow.check_price_band(
+                price,
+                effective_receive,
+                now,
+                decimals_a,
+                decimals_b,
+            )?;
+        }
+    }
+    let amount_a = ctx.accounts.escrow.amount;
+    let amount_b = ctx.accounts.escrow.pro_rata(amount_a, effective_receive)?;
+
+    // 价格可能被 maker 修改或随时间变化, 超过 taker 能接受的上限时拒绝成交; u64::MAX 表示不限制
+    require_gte!(max_receive, amount_b, EscrowError::SlippageExceeded);
+
+    // 防止 maker 在报价后取回部分代币或者仿冒的托管只有少量代币; 0 表示接受 vault 中的任意数量
+    require!(
+        expected_amount_a == 0 || expected_amount_a == amount_a,
+        EscrowError::VaultAmountMismatch
+    );
+
+    // wSOL 的 vault 先同步直接转入的 lamports, 它们和其他多余代币一样退还给 maker
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_a.to_account_info(),
+        &mut ctx.accounts.vault,
+    )?;
+
+    // vault 中没有 token A 时(例如被 permanent delegate 销毁)不能让 taker 先支付 token B
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+
+    // vault 被 mint 的 freeze authority 冻结后无法转出, 返回可读的错误而不是 token 程序的 AccountFrozen
+    // pNFT 的 vault 始终被 Token Metadata 冻结, 由它解冻并转出
+    let (pnft, remaining_accounts) =
+        pnft::split(ctx.remaining_accounts, &ctx.accounts.mint_a.key())?;
+    require!(
+        !ctx.accounts.vault.is_frozen() || pnft.is_some(),
+        EscrowError::VaultFrozen
+    );
+
+    // 小费不受 max_receive 限制, taker 实际支付成交价格加上小费
+    let paid_b = amount_b.checked_add(tip).ok_or(EscrowError::MathOverflow)?;
+
+    // 余额不足时提前返回可读的错误, 而不是在 CPI 中失败并得到 spl-token 的 insufficient funds
+    let balance_b = ctx.accounts.taker_ata_b.amount;
+    if balance_b < paid_b {
+        msg!("Taker is short {} token B", paid_b - balance_b);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+
+    // 版税按成交价格 amount_b 计算, 和手续费一样由 maker 承担
+    let (royalties, creator_atas, remaining_accounts) = if ctx.accounts.escrow.enforce_royalties {
+        split_royalties(remaining_accounts, &ctx.accounts.mint_a.key(), amount_b)?
+    } else {
+        (Vec::new(), &[][..], remaining_accounts)
+    };
+    let royalty: u64 = royalties.iter().map(|payment| payment.amount).sum();
+
+    // 手续费从 taker 支付的 Token B 中扣除, maker 收到扣除手续费和版税之后的剩余部分(包括小费)
+    // 手续费只按成交价格计算, 开启 fee_on_tips 时小费也计入; 启用档位时按 taker 本次成交之前的次数选择比例, 本次成交不计入
+    // 传入 referrer 时手续费再拆分给 referrer, 各部分之和正好等于 paid_b
+    let config = &ctx.accounts.config;
+    let fee_base = if config.fee_on_tips { paid_b } else { amount_b };
+    let fee = config.taker_fee_for(
+        fee_base,
+        ctx.accounts.taker_stats.as_deref().map(|stats| &**stats),
+    )?;
+    let referral_fee = match ctx.accounts.referrer_ata_b {
+        Some(_) => ctx.accounts.config.referral_for(fee)?,
+        None => 0,
+    };
+    let protocol_fee = fee
+        .checked_sub(referral_fee)
+        .ok_or(EscrowError::MathOverflow)?;
+    let maker_amount = paid_b
+        .checked_sub(fee)
+        .and_then(|amount| amount.checked_sub(royalty))
+        .ok_or(EscrowError::MathOverflow)?;
+
+    // escrow PDA 的签名 seeds 只构造一次, 由 escrow 签名的 CPI 共用
+    let maker = ctx.accounts.maker.key();
+    let seed_bytes = ctx.accounts.escrow.seed.to_le_bytes();
+    let bump = [ctx.accounts.escrow.bump];
+    let signer_seeds: [&[&[u8]]; 1] = [&[ESCROW_SEED, maker.as_ref(), &seed_bytes, &bump]];
+
+    // 转账 Token B 给 maker, 手续费转给协议和 referrer, 版税转给 creator
+    ctx.accounts.prepare_atas()?;
+    let net_amount_b =
+        ctx.accounts
+            .transfer_to_maker(maker_amount, &signer_seeds, remaining_accounts)?;
+    ctx.accounts
+        .transfer_fee(protocol_fee, remaining_accounts)?;
+    ctx.accounts
+        .transfer_referral(referral_fee, remaining_accounts)?;
+    ctx.accounts
+        .pay_royalties(&royalties, creator_atas, remaining_accounts)?;
+
+    // 从 vault 中取出 Token A 转账给 taker; 循环托管重新存入并保持 vault 打开, 否则关闭 vault
+    // 是否重新存入按指令开始时 maker ATA 的额度和余额判断
+    let refill = ctx.accounts.refill_amount()?;
+    let net_amount_a =
+        ctx.accounts
+            .withdraw_from_vault(&signer_seeds, pnft.as_ref(), remaining_accounts)?;
+    let refilled = match refill {
+        Some(amount) => Some((
+            amount,
+            ctx.accounts
+                .refill_vault(amount, &signer_seeds, remaining_accounts)?,
+        )),
+        None => {
+            ctx.accounts.close_vault(&signer_seeds)?;
+            None
+        }
+    };
+
+    let escrow = ctx.accounts.escrow.key();
+    let taker = ctx.accounts.taker.key();
+    let mint_a = ctx.accounts.mint_a.key();
+    let mint_b = ctx.accounts.mint_b.key();
+
+    // escrow 和 vault 关闭之后, 成交记录仍然保留在链上供对账使用
+    if let (Some(receipt), Some(bump)) = (ctx.accounts.receipt.as_mut(), ctx.bumps.receipt) {
+        receipt.set_inner(TradeReceipt {
+            escrow,
+            maker,
+            taker,
+            mint_a,
+            mint_b,
+            amount_a,
+            amount_b,
+            fee,
+            timestamp: now,
+            bump,
+        });
+    }
+
+    ctx.accounts.stats.record_take();
+    // 手续费已经按更新之前的统计计算, 统计账户在所有转账之后才更新
+    if let (Some(taker_stats), Some(bump)) =
+        (ctx.accounts.taker_stats.as_mut(), ctx.bumps.taker_stats)
+    {
+        taker_stats.record_taker_trade(taker, bump, amount_b);
+    }
+    if let Some(maker_stats) = ctx.accounts.maker_stats.as_mut() {
+        maker_stats.record_trade(amount_b);
+    }
+
+    emit_cpi!(TakeEvent {
+        escrow,
+        status: ctx.accounts.escrow.status,
+        maker,
+        taker,
+        mint_a,
+        mint_b,
+        amount_a,
+        net_amount_a,
+        amount_b,
+        net_amount_b,
+        fee,
+        referral_fee,
+        royalties,
+        tip,
+        effective_receive,
+        preimage,
+        timestamp: now,
+    });
+
+    match refilled {
+        Some((amount, net_amount)) => emit_cpi!(RefillEvent {
+            escrow,
+            maker,
+            amount,
+            net_amount,
+            refills_left: ctx.accounts.escrow.max_refills,
+            timestamp: now,
+        }),
+        None => {
+            MakerRegistry::remove_seed(&ctx.accounts.registry, ctx.accounts.escrow.seed)?;
+            if ctx.accounts.escrow.pair_indexed {
+                let index = ctx
+                    .accounts
+                    .pair_index
+                    .as_mut()
+                    .ok_or(EscrowError::MissingPairIndex)?;
+                index.record_close(&escrow);
+            }
+            ctx.accounts
+                .escrow
+                .close(ctx.accounts.rent_payer.to_account_info())?;
+        }
+    }
 
-    Ok(())
+    // 通过 return data 返回实际的转账数量, 供 CPI 调用方(聚合器, 路由)读取
+    Ok(TakeResult {
+        amount_a_out: net_amount_a,
+        amount_b_in: paid_b,
+        fee,
+    })
 }
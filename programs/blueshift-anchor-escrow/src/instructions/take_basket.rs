@@ -0,0 +1,291 @@
+use crate::{
+    errors::EscrowError,
+    events::TakeBasketEvent,
+    pda::{BASKET_SEED, CONFIG_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED},
+    state::{Config, EscrowBasket, MintBlocklist},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken},
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 支付 receive 个 token B, 取走一篮子托管中的全部 token, 关闭所有 vault 和 basket
+// remaining_accounts: 按 basket.legs 的顺序每种 token 传入 [mint, vault, taker 的 ATA] 3 个账户, 见 BasketSweep
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TakeBasket<'info> {
+    // 签名账户, 支付 token B 并接收所有 token, 支付自己 ATA 的租金
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 一篮子托管的创建者, 由 basket 的 seeds 和 has_one 约束; basket 和 vault 的租金还给它
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = maker,
+        seeds = [BASKET_SEED, maker.key().as_ref(), basket.seed.to_le_bytes().as_ref()],
+        bump = basket.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_b @ EscrowError::InvalidMintB,
+        constraint = taker.key() != basket.maker @ EscrowError::SelfTrade,
+    )]
+    pub basket: Box<Account<'info, EscrowBasket>>,
+
+    #[account(mint::token_program = token_program_b)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // taker 支付 token B 的账户, 由 taker 签名转出
+    #[account(
+        mut,
+        token::mint = mint_b,
+        token::authority = taker,
+        token::token_program = token_program_b
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // maker 接收 token B 的 ATA 账户
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 读取手续费比例并检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 Token B 手续费的 ATA 账户, 手续费为 0 时可以不传
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program_b
+    )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理篮子中所有 mint 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>,
+    pub system_program: Program<'info, System>,
+}
+
+// take_basket 和 refund_basket 共用: 把每个 vault 中的 token 全部转给 recipient 的 ATA 并关闭 vault
+// 每种 token 的账户是 [mint, vault, recipient 的 ATA], 都必须和 basket.legs 的顺序一致
+// 空的 vault 不转账, 只关闭; recipient 的 ATA 不存在时由 payer 创建
+pub(crate) struct BasketSweep<'a, 'info> {
+    pub basket: &'a Account<'info, EscrowBasket>,
+    pub recipient: AccountInfo<'info>,
+    pub payer: AccountInfo<'info>,
+    // vault 关闭后的租金去向, 总是 maker
+    pub maker: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+    pub memo_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+impl<'info> BasketSweep<'_, 'info> {
+    // 返回每个 vault 转出的数量, 顺序和 basket.legs 相同
+    pub fn sweep(&self, accounts: &'info [AccountInfo<'info>]) -> Result<Vec<u64>> {
+        let basket = self.basket;
+        require_eq!(
+            accounts.len(),
+            basket.legs().len() * 3,
+            EscrowError::InvalidBasketAccounts
+        );
+
+        let seed = basket.seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            BASKET_SEED,
+            basket.maker.as_ref(),
+            seed.as_ref(),
+            &[basket.bump],
+        ]];
+
+        let mut amounts = Vec::with_capacity(basket.legs().len());
+        for (leg, accounts) in basket.legs().iter().zip(accounts.chunks_exact(3)) {
+            let (mint, vault, destination) = (&accounts[0], &accounts[1], &accounts[2]);
+            require_keys_eq!(mint.key(), leg.mint, EscrowError::InvalidBasketAccounts);
+            require_keys_eq!(
+                vault.key(),
+                get_associated_token_address_with_program_id(
+                    &basket.key(),
+                    &leg.mint,
+                    self.token_program.key
+                ),
+                EscrowError::InvalidBasketAccounts
+            );
+            require_keys_eq!(
+                destination.key(),
+                get_associated_token_address_with_program_id(
+                    self.recipient.key,
+                    &leg.mint,
+                    self.token_program.key
+                ),
+                EscrowError::InvalidBasketAccounts
+            );
+
+            let mint_account = InterfaceAccount::<Mint>::try_from(mint)?;
+            let mut vault_account = InterfaceAccount::<TokenAccount>::try_from(vault)?;
+            require!(!vault_account.is_frozen(), EscrowError::VaultFrozen);
+
+            // wSOL 的 vault 先同步直接转入的 lamports, 和其他直接转入的 token 一起转出
+            transfer::sync_native_if_needed(&self.token_program, &mut vault_account)?;
+            let amount = vault_account.amount;
+            if amount > 0 {
+                if destination.data_is_empty() {
+                    associated_token::create(CpiContext::new(
+                        self.associated_token_program.clone(),
+                        associated_token::Create {
+                            payer: self.payer.clone(),
+                            associated_token: destination.clone(),
+                            authority: self.recipient.clone(),
+                            mint: mint.clone(),
+                            system_program: self.system_program.clone(),
+                            token_program: self.token_program.clone(),
+                        },
+                    ))?;
+                }
+                transfer::memo_if_required(&self.memo_program, destination, &basket.key())?;
+                transfer::transfer_checked(
+                    CpiContext::new_with_signer(
+                        self.token_program.clone(),
+                        TransferChecked {
+                            from: vault.clone(),
+                            mint: mint.clone(),
+                            to: destination.clone(),
+                            authority: basket.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    amount,
+                    mint_account.decimals,
+                )?;
+            }
+
+            close_account(CpiContext::new_with_signer(
+                self.token_program.clone(),
+                CloseAccount {
+                    account: vault.clone(),
+                    destination: self.maker.clone(),
+                    authority: basket.to_account_info(),
+                },
+                signer_seeds,
+            ))?;
+            amounts.push(amount);
+        }
+
+        Ok(amounts)
+    }
+}
+
+impl<'info> TakeBasket<'info> {
+    // 从 taker 转出 amount 个 token B 到 to, 返回 to 扣除转账手续费后实际收到的数量
+    fn pay(&self, to: &InterfaceAccount<'info, TokenAccount>, amount: u64) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &to.to_account_info(),
+            &self.basket.key(),
+        )?;
+        let withheld = transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            ),
+            amount,
+            self.mint_b.decimals,
+        )?;
+
+        amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, TakeBasket<'info>>,
+    max_receive: u64,
+) -> Result<()> {
+    let basket = &ctx.accounts.basket;
+    let mut mints: Vec<Pubkey> = basket.legs().iter().map(|leg| leg.mint).collect();
+    mints.push(basket.mint_b);
+    MintBlocklist::check(&ctx.accounts.mint_blocklist, &mints)?;
+
+    // 一篮子托管只能整体成交, 价格就是 receive
+    let amount_b = basket.receive;
+    require_gte!(max_receive, amount_b, EscrowError::SlippageExceeded);
+    let balance_b = ctx.accounts.taker_ata_b.amount;
+    if balance_b < amount_b {
+        msg!("Taker is short {} token B", amount_b - balance_b);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+
+    // 和 take 一样从 token B 中扣除手续费, 不支持 referrer
+    let fee = ctx.accounts.config.fee_for(amount_b)?;
+    let maker_amount = amount_b.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+    let net_amount_b = ctx.accounts.pay(&ctx.accounts.maker_ata_b, maker_amount)?;
+    if fee > 0 {
+        let fee_vault_b = ctx
+            .accounts
+            .fee_vault_b
+            .as_ref()
+            .ok_or(EscrowError::MissingFeeVault)?;
+        ctx.accounts.pay(fee_vault_b, fee)?;
+    }
+
+    let amounts = BasketSweep {
+        basket: &ctx.accounts.basket,
+        recipient: ctx.accounts.taker.to_account_info(),
+        payer: ctx.accounts.taker.to_account_info(),
+        maker: ctx.accounts.maker.to_account_info(),
+        token_program: ctx.accounts.token_program_a.to_account_info(),
+        associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        memo_program: ctx.accounts.memo_program.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+    }
+    .sweep(ctx.remaining_accounts)?;
+
+    emit_cpi!(TakeBasketEvent {
+        basket: ctx.accounts.basket.key(),
+        maker: ctx.accounts.maker.key(),
+        taker: ctx.accounts.taker.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        amounts,
+        amount_b,
+        net_amount_b,
+        fee,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    // 指令执行完毕后 anchor 自动关闭 basket 数据账户
+    Ok(())
+}
@@ -0,0 +1,342 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::TakeEvent,
+    metadata::{NftMetadata, METADATA_SEED, TOKEN_METADATA_ID},
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED, REGISTRY_SEED, STATS_SEED,
+    },
+    state::{Config, Escrow, GlobalStats, MakerRegistry, MintBlocklist, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 成交 make_collection 创建的托管, taker 用 collection 中任意一个经过验证的 NFT 换取全部 token A
+// NFT 不能拆分, 因此不收取协议手续费; NFT 转给 receive_to 的 ATA
+// Programmable NFT 的 token 账户被冻结, 只能通过 Token Metadata 程序转账, 这里的 transfer_checked 会失败
+// remaining_accounts: mint A 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TakeCollection<'info> {
+    // 签名账户, 转出 NFT 并取走 token A
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 NFT 的钱包, 只作为 receive_to_nft 的 authority, 由 has_one 约束为 escrow.receive_to
+    pub receive_to: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = receive_to @ EscrowError::InvalidReceiveTo,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        constraint = escrow.is_collection_offer() @ EscrowError::NotCollectionOffer, // 其他托管必须通过 take 或 take_for_sol 成交
+        constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program_a
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 取款者的 Token A 的 ATA 账户, 不存在时由 taker 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_a
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 托管账户创建者的 Token A 的 ATA 账户, 用来退还 vault 中超出 escrow.amount 的多余代币
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // taker 支付的 NFT 的 mint, 必须是 decimals 为 0 并且只有 1 个的 mint
+    #[account(
+        mint::token_program = token_program_b,
+        constraint = nft_mint.decimals == 0 && nft_mint.supply == 1 @ EscrowError::NotAnNft,
+    )]
+    pub nft_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: nft_mint 的 Metaplex Metadata 账户, 由 seeds 约束地址, 由 NftMetadata::load 检查 owner 和数据
+    #[account(
+        seeds = [METADATA_SEED, TOKEN_METADATA_ID.as_ref(), nft_mint.key().as_ref()],
+        seeds::program = TOKEN_METADATA_ID,
+        bump,
+    )]
+    pub nft_metadata: UncheckedAccount<'info>,
+
+    // taker 持有 NFT 的 token 账户, 不要求是 ATA
+    #[account(
+        mut,
+        token::mint = nft_mint,
+        token::authority = taker,
+        token::token_program = token_program_b
+    )]
+    pub taker_nft: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // receive_to 接收 NFT 的 ATA 账户, 不存在时由 taker 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = nft_mint,
+        associated_token::authority = receive_to,
+        associated_token::token_program = token_program_b
+    )]
+    pub receive_to_nft: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 成交关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理 mint_a 和 vault 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>, // 管理 nft_mint 的 token 程序
+    pub memo_program: Program<'info, Memo>,                // 接收方账户要求 memo 时使用
+    pub system_program: Program<'info, System>,            // init_if_needed 需要系统程序
+}
+
+impl<'info> TakeCollection<'info> {
+    // 把 NFT 从 taker 转给 receive_to
+    fn transfer_nft(&self) -> Result<()> {
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &self.receive_to_nft.to_account_info(),
+            &self.escrow.key(),
+        )?;
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_nft.to_account_info(),
+                    to: self.receive_to_nft.to_account_info(),
+                    mint: self.nft_mint.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            ),
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    // 从 vault 中取出 Token A 转账给 taker 并关闭 vault, 和 take_for_sol 完全相同
+    fn withdraw_and_close_vault(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &self.taker_ata_a.to_account_info(),
+            &self.escrow.key(),
+        )?;
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to: self.taker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            self.escrow.amount,
+            self.mint_a.decimals,
+        )?;
+
+        // 别人直接转入 vault 的多余代币退还给 maker
+        let surplus = self
+            .vault
+            .amount
+            .checked_sub(self.escrow.amount)
+            .ok_or(EscrowError::MathOverflow)?;
+        if surplus > 0 {
+            transfer::memo_if_required(
+                &self.memo_program.to_account_info(),
+                &self.maker_ata_a.to_account_info(),
+                &self.escrow.key(),
+            )?;
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program_a.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        to: self.maker_ata_a.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        authority: self.escrow.to_account_info(),
+                    },
+                    &signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                surplus,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program_a.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        self.escrow
+            .amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeCollection<'info>>,
+    expected_amount_a: u64,
+) -> Result<()> {
+    // direct_only 的托管不能通过 CPI 成交, 这里没有 instructions sysvar, 可信路由也只能使用 take
+    caller::check_direct_call(&ctx.accounts.escrow, &ctx.accounts.config, None)?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.nft_mint.key()],
+    )?;
+
+    // 过期之后只能退还
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    // NFT 的 Metadata 必须属于 nft_mint, 并且 collection authority 已经验证了它属于托管的 collection
+    // 未验证的 collection 字段任何人都可以填写, 不能作为依据
+    let metadata = NftMetadata::load(&ctx.accounts.nft_metadata)?;
+    require_keys_eq!(
+        metadata.mint,
+        ctx.accounts.nft_mint.key(),
+        EscrowError::InvalidNftMetadata
+    );
+    require!(
+        metadata.verified_collection == Some(ctx.accounts.escrow.collection),
+        EscrowError::CollectionNotVerified
+    );
+
+    // maker 可以通过 withdraw_partial 减少出售的数量, 由 taker 限制
+    let amount_a = ctx.accounts.escrow.amount;
+    require!(
+        expected_amount_a == 0 || expected_amount_a == amount_a,
+        EscrowError::VaultAmountMismatch
+    );
+
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    ctx.accounts.transfer_nft()?;
+    let net_amount_a = ctx
+        .accounts
+        .withdraw_and_close_vault(ctx.remaining_accounts)?;
+    // escrow 由 close 约束关闭, 从 maker 的索引和交易对索引中移除
+    ctx.accounts.stats.record_take();
+    MakerRegistry::remove_seed(&ctx.accounts.registry, ctx.accounts.escrow.seed)?;
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+    }
+
+    // mint_b 是 taker 支付的 NFT 的 mint, 而不是 collection
+    emit_cpi!(TakeEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        taker: ctx.accounts.taker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.nft_mint.key(),
+        amount_a,
+        net_amount_a,
+        amount_b: 1,
+        net_amount_b: 1,
+        fee: 0,
+        referral_fee: 0,
+        royalties: Vec::new(),
+        tip: 0,
+        effective_receive: ctx.accounts.escrow.receive,
+        preimage: Vec::new(),
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::TakeEvent,
+    pda::{CONFIG_SEED, ESCROW_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED, STATS_SEED},
+    state::{Config, Escrow, GlobalStats, MintBlocklist, PairIndex},
+    transfer,
+};
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 成交 make_for_sol 创建的托管, taker 直接用 SOL 支付, 不收取协议手续费
+// remaining_accounts: mint A 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TakeForSol<'info> {
+    // 签名账户, 支付 lamports 并取走 token A
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 接收 lamports, 由 escrow 的 seeds 和 has_one 约束
+    /// 系统程序的 transfer 可以转入任何可写账户, 因此 maker 可以是其他程序拥有的 PDA
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        constraint = escrow.is_sol_mode() @ EscrowError::NotSolEscrow, // 用 token B 支付的托管必须通过 take 成交
+        constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 取款者的 Token A 的 ATA 账户, 不存在时由 taker 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 托管账户创建者的 Token A 的 ATA 账户, 用来退还 vault 中超出 escrow.amount 的多余代币
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    // 托管计入了交易对索引时必须传入, 成交关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>, // 接收方账户要求 memo 时使用
+    pub system_program: Program<'info, System>, // 转账 lamports 和 init_if_needed 都需要系统程序
+}
+
+impl<'info> TakeForSol<'info> {
+    // 从 vault 中取出 Token A 转账给 taker 并关闭 vault, 和 take 完全相同
+    fn withdraw_and_close_vault(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &self.taker_ata_a.to_account_info(),
+            &self.escrow.key(),
+        )?;
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to: self.taker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            self.escrow.amount,
+            self.mint_a.decimals,
+        )?;
+
+        // 别人直接转入 vault 的多余代币退还给 maker
+        let surplus = self
+            .vault
+            .amount
+            .checked_sub(self.escrow.amount)
+            .ok_or(EscrowError::MathOverflow)?;
+        if surplus > 0 {
+            transfer::memo_if_required(
+                &self.memo_program.to_account_info(),
+                &self.maker_ata_a.to_account_info(),
+                &self.escrow.key(),
+            )?;
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        to: self.maker_ata_a.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        authority: self.escrow.to_account_info(),
+                    },
+                    &signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                surplus,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        self.escrow
+            .amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeForSol<'info>>,
+    max_receive: u64,
+    expected_amount_a: u64,
+) -> Result<()> {
+    // direct_only 的托管不能通过 CPI 成交, 这里没有 instructions sysvar, 可信路由也只能使用 take
+    caller::check_direct_call(&ctx.accounts.escrow, &ctx.accounts.config, None)?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(&ctx.accounts.mint_blocklist, &[ctx.accounts.mint_a.key()])?;
+
+    // 过期之后只能退还
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    // maker 可以通过 update_receive 和 withdraw_partial 修改价格, 和 take 一样由 taker 限制
+    let amount_a = ctx.accounts.escrow.amount;
+    let receive_lamports = ctx
+        .accounts
+        .escrow
+        .pro_rata(amount_a, ctx.accounts.escrow.receive)?;
+    require_gte!(max_receive, receive_lamports, EscrowError::SlippageExceeded);
+    require!(
+        expected_amount_a == 0 || expected_amount_a == amount_a,
+        EscrowError::VaultAmountMismatch
+    );
+
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    let balance = ctx.accounts.taker.lamports();
+    if balance < receive_lamports {
+        msg!("Taker is short {} lamports", receive_lamports - balance);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+
+    // 通过系统程序把 lamports 转给 maker
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.taker.to_account_info(),
+                to: ctx.accounts.maker.to_account_info(),
+            },
+        ),
+        receive_lamports,
+    )?;
+
+    let net_amount_a = ctx
+        .accounts
+        .withdraw_and_close_vault(ctx.remaining_accounts)?;
+    // escrow 由 close 约束关闭, 计入统计并从交易对索引中移除
+    ctx.accounts.stats.record_take();
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+    }
+
+    // mint_b 为 Pubkey::default() 表示以 lamports 支付
+    emit_cpi!(TakeEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        taker: ctx.accounts.taker.key(),
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: Pubkey::default(),
+        amount_a,
+        net_amount_a,
+        amount_b: receive_lamports,
+        net_amount_b: receive_lamports,
+        fee: 0,
+        referral_fee: 0,
+        royalties: Vec::new(),
+        tip: 0,
+        effective_receive: ctx.accounts.escrow.receive,
+        preimage: Vec::new(),
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,550 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::TakeEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED,
+        REGISTRY_SEED, STATS_SEED, USER_STATS_SEED,
+    },
+    state::{
+        Config, Escrow, EscrowStatus, GlobalStats, MakerRegistry, MintBlocklist, PairIndex,
+        UserStats,
+    },
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 账户列表和 Take 相同, 区别是 escrow 不会自动关闭, 只有全部成交时才会在 handler 中手动关闭
+// remaining_accounts 也和 Take 相同
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TakePartial<'info> {
+    // 签名账户, 是取走部分托管资金 Token A 的账户
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束, 可以是其他程序拥有的 PDA, 见 take
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 token B 的钱包, 只作为 maker_ata_b 的 authority, 由 has_one 约束为 escrow.receive_to
+    pub receive_to: UncheckedAccount<'info>,
+
+    // 托管账户的数据账户, 没有 close 约束, 因为部分成交后托管仍然有效
+    #[account(
+      mut,
+      seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+      bump = escrow.bump,
+      has_one = maker @ EscrowError::InvalidMaker,
+      has_one = rent_payer @ EscrowError::InvalidRentPayer,
+      has_one = receive_to @ EscrowError::InvalidReceiveTo,
+      has_one = mint_a @ EscrowError::InvalidMintA,
+      has_one = mint_b @ EscrowError::InvalidMintB,
+      constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker,
+      constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+      constraint = !escrow.is_arbitrated() @ EscrowError::ArbiterRequired,
+      constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+      constraint = !escrow.requires_commit() @ EscrowError::CommitRequired, // 开启 commit-reveal 时只能通过 reveal_take 全部成交
+      constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved, // 预约期间只有 reserved_taker 可以通过 settle 成交
+  )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    // Token A 和 Token B 的 mint 账户, 分别由各自的 token 程序管理
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+      mint::token_program = token_program_b,
+      constraint = mint_a.key() != mint_b.key() @ EscrowError::IdenticalMints,
+  )]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+      mut,
+      address = escrow.vault @ EscrowError::InvalidVault,
+      token::mint = mint_a,
+      token::authority = escrow,
+      token::token_program = token_program_a
+  )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 取款者的 Token A 的 ATA 账户, 用来接收 Token A
+    #[account(
+      init_if_needed,
+      payer = taker,
+      associated_token::mint = mint_a,
+      associated_token::authority = taker,
+      associated_token::token_program = token_program_a
+  )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 托管账户创建者的 Token A 的 ATA 账户, 最后一次成交时用来退还 vault 中的多余代币
+    #[account(
+      init_if_needed,
+      payer = taker,
+      associated_token::mint = mint_a,
+      associated_token::authority = maker,
+      associated_token::token_program = token_program_a
+  )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 取款者的 Token B 的 ATA 账户, 用来把 Token B 转账给 maker
+    #[account(
+      mut,
+      associated_token::mint = mint_b,
+      associated_token::authority = taker,
+      associated_token::token_program = token_program_b
+  )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // receive_to 的 Token B 的 ATA 账户, 用来接收 Token B
+    #[account(
+      init_if_needed,
+      payer = taker,
+      associated_token::mint = mint_b,
+      associated_token::authority = receive_to,
+      associated_token::token_program = token_program_b
+  )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 读取手续费比例并检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // taker 的成交统计, 第一次传入时由 taker 支付租金创建; 启用手续费档位时必须传入, 按它选择手续费比例, 见 take
+    #[account(
+      init_if_needed,
+      payer = taker,
+      space = UserStats::INIT_SPACE + UserStats::DISCRIMINATOR.len(),
+      seeds = [USER_STATS_SEED, taker.key().as_ref()],
+      bump,
+  )]
+    pub taker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    // maker 的成交统计, 必须已经通过 init_user_stats 创建; 不传时不计入
+    #[account(
+      mut,
+      seeds = [USER_STATS_SEED, maker.key().as_ref()],
+      bump = maker_stats.bump,
+  )]
+    pub maker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 Token B 手续费的 ATA 账户, 手续费为 0 时可以不传
+    #[account(
+      mut,
+      associated_token::mint = mint_b,
+      associated_token::authority = fee_authority,
+      associated_token::token_program = token_program_b
+  )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 转介成交的聚合器等的 Token B 账户, 传入时分得一部分手续费, 不能属于 taker 自己
+    #[account(
+      mut,
+      token::mint = mint_b,
+      token::token_program = token_program_b,
+      constraint = referrer_ata_b.owner != taker.key() @ EscrowError::SelfReferral,
+  )]
+    pub referrer_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 最后一次成交关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+      mut,
+      seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+      bump = pair_index.bump,
+  )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>, // 管理 mint_a 和 vault 的 token 程序
+    pub token_program_b: Interface<'info, TokenInterface>, // 管理 mint_b 的 token 程序
+    pub memo_program: Program<'info, Memo>,                // 接收方账户要求 memo 时使用
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakePartial<'info> {
+    // 计算本次实际成交的 token A 数量
+    // 如果成交后剩余的 token A 不值 1 个最小单位的 token B(粉尘), 则本次一并取走, 避免 vault 中留下无法成交的余额
+    fn fill_amount(&self, amount_a: u64, receive: u64) -> Result<u64> {
+        require_gt!(amount_a, 0, EscrowError::InvalidAmount);
+        require_gte!(self.escrow.amount, amount_a, EscrowError::InvalidAmount);
+
+        let remaining = self.escrow.amount - amount_a;
+        let remaining_value = (remaining as u128)
+            .checked_mul(receive as u128)
+            .ok_or(EscrowError::MathOverflow)?;
+
+        if remaining_value < self.escrow.deposited as u128 {
+            Ok(self.escrow.amount)
+        } else {
+            Ok(amount_a)
+        }
+    }
+
+    // 把按比例计算出的 Token B 转账给 maker, 返回 maker 扣除 mint B 转账手续费后实际收到的数量
+    fn transfer_to_maker(
+        &mut self,
+        amount_b: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &self.maker_ata_b.to_account_info(),
+            &self.escrow.key(),
+        )?;
+
+        let withheld = transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: self.maker_ata_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount_b,
+            self.mint_b.decimals,
+        )?;
+
+        amount_b
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 把手续费转入协议的手续费 ATA, 手续费为 0 时不做任何 CPI
+    fn transfer_fee(&mut self, fee: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        let fee_vault_b = self
+            .fee_vault_b
+            .as_ref()
+            .ok_or(EscrowError::MissingFeeVault)?;
+
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: fee_vault_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            fee,
+            self.mint_b.decimals,
+        )?;
+
+        Ok(())
+    }
+
+    // 把手续费中 referrer 的部分转给 referrer, 为 0 时不做任何 CPI
+    fn transfer_referral(
+        &mut self,
+        referral_fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        // referral_fee 只有在传入 referrer 账户时才会大于 0
+        let Some(referrer_ata_b) = self.referrer_ata_b.as_ref() else {
+            return Ok(());
+        };
+        if referral_fee == 0 {
+            return Ok(());
+        }
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &referrer_ata_b.to_account_info(),
+            &self.escrow.key(),
+        )?;
+
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: referrer_ata_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            referral_fee,
+            self.mint_b.decimals,
+        )?;
+
+        Ok(())
+    }
+
+    // 从 vault 中取出 amount_a 个 Token A 转账给 taker, 全部成交后关闭 vault 和 escrow
+    // 返回 taker 扣除 mint A 转账手续费后实际收到的数量
+    fn withdraw(
+        &mut self,
+        amount_a: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        // 转账前记录 vault 余额, 因为 CPI 之后 self.vault 中缓存的 amount 不会自动刷新
+        let vault_amount = self.vault.amount;
+        let remaining = self.escrow.amount - amount_a;
+        self.escrow.amount = remaining;
+        if remaining > 0 {
+            self.escrow.advance_status(EscrowStatus::PartiallyFilled);
+        }
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &self.taker_ata_a.to_account_info(),
+            &self.escrow.key(),
+        )?;
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to: self.taker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount_a,
+            self.mint_a.decimals,
+        )?;
+
+        if remaining == 0 {
+            // 别人直接转入 vault 的多余代币退还给 maker
+            let surplus = vault_amount
+                .checked_sub(amount_a)
+                .ok_or(EscrowError::MathOverflow)?;
+            if surplus > 0 {
+                transfer::memo_if_required(
+                    &self.memo_program.to_account_info(),
+                    &self.maker_ata_a.to_account_info(),
+                    &self.escrow.key(),
+                )?;
+                transfer::transfer_checked(
+                    CpiContext::new_with_signer(
+                        self.token_program_a.to_account_info(),
+                        TransferChecked {
+                            from: self.vault.to_account_info(),
+                            to: self.maker_ata_a.to_account_info(),
+                            mint: self.mint_a.to_account_info(),
+                            authority: self.escrow.to_account_info(),
+                        },
+                        &signer_seeds,
+                    )
+                    .with_remaining_accounts(remaining_accounts.to_vec()),
+                    surplus,
+                    self.mint_a.decimals,
+                )?;
+            }
+
+            // 关闭 vault 账户, 租金还给支付租金的账户
+            close_account(CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                CloseAccount {
+                    account: self.vault.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                    destination: self.rent_payer.to_account_info(),
+                },
+                &signer_seeds,
+            ))?;
+
+            // 手动关闭 escrow 数据账户, 租金还给支付租金的账户
+            // 部分成交不计入, 最后一次成交关闭托管时计入一次
+            self.stats.record_take();
+            MakerRegistry::remove_seed(&self.registry, self.escrow.seed)?;
+            if self.escrow.pair_indexed {
+                let index = self
+                    .pair_index
+                    .as_mut()
+                    .ok_or(EscrowError::MissingPairIndex)?;
+                index.record_close(&self.escrow.key());
+            }
+            self.escrow.close(self.rent_payer.to_account_info())?;
+        }
+
+        amount_a
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakePartial<'info>>,
+    amount_a: u64,
+    max_receive: u64,
+    proof: Vec<[u8; 32]>,
+    preimage: Vec<u8>,
+) -> Result<()> {
+    // 设置了允许列表时, taker 必须提供自己在列表中的默克尔证明
+    require!(
+        ctx.accounts
+            .escrow
+            .is_on_allowlist(&ctx.accounts.taker.key(), &proof),
+        EscrowError::NotOnAllowlist
+    );
+
+    // HTLC 托管必须公开和 hashlock 匹配的 preimage
+    ctx.accounts.escrow.check_preimage(&preimage)?;
+
+    // 到达开始时间之前和过期之后不能成交
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.escrow.check_started(now)?;
+    ctx.accounts.escrow.check_not_expired(now)?;
+
+    // 价格区间需要 take 传入的 price_feed 账户检查
+    require!(
+        !ctx.accounts.escrow.has_price_band(),
+        EscrowError::PriceBandRequiresTake
+    );
+    // 版税需要 take 传入的 metadata 和 creator 账户
+    require!(
+        !ctx.accounts.escrow.enforce_royalties,
+        EscrowError::RoyaltiesRequireTake
+    );
+    // 循环托管成交后需要 take 重新存入并保留 escrow, 最后一次部分成交会直接关闭, 留下 maker ATA 上的授权
+    require!(
+        !ctx.accounts.escrow.recurring,
+        EscrowError::RecurringRequiresTake
+    );
+    // direct_only 的托管不能通过 CPI 成交, 这里没有 instructions sysvar, 可信路由也只能使用 take
+    caller::check_direct_call(&ctx.accounts.escrow, &ctx.accounts.config, None)?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()],
+    )?;
+
+    // 计算本次成交的 token A 数量和按当前单价需要支付的 token B 数量
+    let effective_receive = ctx.accounts.escrow.current_receive(now)?;
+    let amount_a = ctx.accounts.fill_amount(amount_a, effective_receive)?;
+    let amount_b = ctx.accounts.escrow.pro_rata(amount_a, effective_receive)?;
+    // maker 可以随时通过 update_receive 提高价格, fill_amount 扫尾也会增加支付的数量, 超出 taker 愿意支付的上限时拒绝
+    require_gte!(max_receive, amount_b, EscrowError::SlippageExceeded);
+
+    // 向上取整保证了这里不会为 0, 显式检查防止 taker 不付出 token B 就拿走 token A
+    require_gt!(amount_b, 0, EscrowError::InvalidAmount);
+
+    // wSOL 的 vault 先同步直接转入的 lamports, 最后一次成交时和其他多余代币一样退还给 maker
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_a.to_account_info(),
+        &mut ctx.accounts.vault,
+    )?;
+
+    // vault 中没有 token A 时(例如被 permanent delegate 销毁)不能让 taker 先支付 token B
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+
+    // vault 被 mint 的 freeze authority 冻结后无法转出, 返回可读的错误而不是 token 程序的 AccountFrozen
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    // 余额不足时提前返回可读的错误, 而不是在 CPI 中失败并得到 spl-token 的 insufficient funds
+    let balance_b = ctx.accounts.taker_ata_b.amount;
+    if balance_b < amount_b {
+        msg!("Taker is short {} token B", amount_b - balance_b);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+
+    // 手续费从 taker 支付的 Token B 中扣除, maker 收到剩余部分; 启用档位时和 take 一样按 taker 之前的成交次数选择比例
+    // 传入 referrer 时手续费再拆分给 referrer, 三部分之和正好等于 amount_b
+    let fee = ctx.accounts.config.taker_fee_for(
+        amount_b,
+        ctx.accounts.taker_stats.as_deref().map(|stats| &**stats),
+    )?;
+    let referral_fee = match ctx.accounts.referrer_ata_b {
+        Some(_) => ctx.accounts.config.referral_for(fee)?,
+        None => 0,
+    };
+    let protocol_fee = fee
+        .checked_sub(referral_fee)
+        .ok_or(EscrowError::MathOverflow)?;
+    let maker_amount = amount_b.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+
+    // 转账 Token B 给 maker, 手续费转给协议和 referrer
+    let net_amount_b = ctx
+        .accounts
+        .transfer_to_maker(maker_amount, ctx.remaining_accounts)?;
+    ctx.accounts
+        .transfer_fee(protocol_fee, ctx.remaining_accounts)?;
+    ctx.accounts
+        .transfer_referral(referral_fee, ctx.remaining_accounts)?;
+
+    // 从 vault 中取出 Token A 转账给 taker
+    let net_amount_a = ctx.accounts.withdraw(amount_a, ctx.remaining_accounts)?;
+
+    // 每一次部分成交都计入一次成交
+    let taker = ctx.accounts.taker.key();
+    if let (Some(taker_stats), Some(bump)) =
+        (ctx.accounts.taker_stats.as_mut(), ctx.bumps.taker_stats)
+    {
+        taker_stats.record_taker_trade(taker, bump, amount_b);
+    }
+    if let Some(maker_stats) = ctx.accounts.maker_stats.as_mut() {
+        maker_stats.record_trade(amount_b);
+    }
+
+    emit_cpi!(TakeEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        taker,
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        amount_a,
+        net_amount_a,
+        amount_b,
+        net_amount_b,
+        fee,
+        referral_fee,
+        royalties: Vec::new(),
+        tip: 0,
+        effective_receive,
+        preimage,
+        timestamp: now,
+    });
+
+    Ok(())
+}
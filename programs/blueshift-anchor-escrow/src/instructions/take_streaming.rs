@@ -0,0 +1,445 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::StreamPaymentEvent,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED, PAIR_INDEX_SEED,
+        REGISTRY_SEED, STATS_SEED, USER_STATS_SEED,
+    },
+    state::{Config, Escrow, GlobalStats, MakerRegistry, MintBlocklist, PairIndex, UserStats},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// take_streaming 和 stream_payment 共用的账户列表
+// take_streaming 按当前价格把剩余的全部 token A 锁定给 taker 并支付第一期, 之后 taker 通过 stream_payment 分期支付
+// 每一期的 token B 扣除手续费后转给 receive_to, 同时从 vault 中按比例释放 token A, 付清 stream_receive 时关闭 vault 和 escrow
+// taker 超过 stream_deadline 没有付清时, maker 通过 refund 取回还没有释放的 token A, 已经支付的 token B 不退还
+// remaining_accounts: mint A 和 mint B 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TakeStreaming<'info> {
+    // 签名账户, 分期支付的 taker, 支付需要创建的 ATA 的租金
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 付清后关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 token B 的钱包, 只作为 maker_ata_b 的 authority, 由 has_one 约束为 escrow.receive_to
+    pub receive_to: UncheckedAccount<'info>,
+
+    // 托管账户的数据账户, 没有 close 约束, 只有付清时才在 handler 中手动关闭
+    // 开始和后续支付的状态要求不同, 在各自的 handler 中检查
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = receive_to @ EscrowError::InvalidReceiveTo,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        has_one = mint_b @ EscrowError::InvalidMintB,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    // Token A 和 Token B 的 mint 账户, 分别由各自的 token 程序管理
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mint::token_program = token_program_b,
+        constraint = mint_a.key() != mint_b.key() @ EscrowError::IdenticalMints,
+    )]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 分期成交期间保存还没有释放的 token A
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program_a
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // taker 接收 token A 的 ATA 账户
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_a
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // maker 的 Token A 的 ATA 账户, 付清时用来退还 vault 中的多余代币
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // taker 支付 token B 的 ATA 账户
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_b
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // receive_to 接收 token B 的 ATA 账户
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = receive_to,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议配置账户, 读取手续费比例
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // taker 的成交统计, 第一次传入时由 taker 支付租金创建; 启用手续费档位时必须传入, 每一期都按它选择手续费比例, 见 take
+    #[account(
+        init_if_needed,
+        payer = taker,
+        space = UserStats::INIT_SPACE + UserStats::DISCRIMINATOR.len(),
+        seeds = [USER_STATS_SEED, taker.key().as_ref()],
+        bump,
+    )]
+    pub taker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    // maker 的成交统计, 必须已经通过 init_user_stats 创建; 不传时不计入
+    #[account(
+        mut,
+        seeds = [USER_STATS_SEED, maker.key().as_ref()],
+        bump = maker_stats.bump,
+    )]
+    pub maker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 Token B 手续费的 ATA 账户, 手续费为 0 时可以不传
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program_b
+    )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 付清关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>,
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeStreaming<'info> {
+    // taker 签名转出 amount 个 token B, 为 0 时不做任何 CPI; 返回 to 扣除 mint B 转账手续费后实际收到的数量
+    fn pay_b(
+        &self,
+        to: AccountInfo<'info>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        let withheld = transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to,
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_b.decimals,
+        )?;
+
+        amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 用 escrow 的签名从 vault 转出 amount 个 token A, 为 0 时不做任何 CPI; 返回 to 实际收到的数量
+    fn pay_a(
+        &self,
+        to: AccountInfo<'info>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to,
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_a.decimals,
+        )?;
+
+        amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 付清之后把别人直接转入 vault 的多余代币退还给 maker, 关闭 vault 和 escrow, 租金还给 rent_payer
+    fn close(&mut self, surplus: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        self.pay_a(
+            self.maker_ata_a.to_account_info(),
+            surplus,
+            remaining_accounts,
+        )?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+        close_account(CpiContext::new_with_signer(
+            self.token_program_a.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        self.stats.record_take();
+        MakerRegistry::remove_seed(&self.registry, self.escrow.seed)?;
+        if self.escrow.pair_indexed {
+            let index = self
+                .pair_index
+                .as_mut()
+                .ok_or(EscrowError::MissingPairIndex)?;
+            index.record_close(&self.escrow.key());
+        }
+        self.escrow.close(self.rent_payer.to_account_info())
+    }
+}
+
+// 开始分期成交: 锁定剩余的全部 token A 和按当前价格计算的 stream_receive, 然后支付第一期 initial_b
+// stream_receive 超过 max_receive 时失败, initial_b 至少是 stream_receive 的 MIN_STREAM_INITIAL_BPS
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeStreaming<'info>>,
+    initial_b: u64,
+    max_receive: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<()> {
+    let escrow = &ctx.accounts.escrow;
+    let taker = ctx.accounts.taker.key();
+    require!(escrow.allows_streaming(), EscrowError::StreamingDisabled);
+    require!(escrow.status.is_fillable(), EscrowError::EscrowFrozen);
+    require!(
+        escrow.is_taker_allowed(&taker),
+        EscrowError::UnauthorizedTaker
+    );
+    require!(
+        escrow.is_on_allowlist(&taker, &proof),
+        EscrowError::NotOnAllowlist
+    );
+    require!(!escrow.is_arbitrated(), EscrowError::ArbiterRequired);
+    // 开启 commit-reveal 时只能通过 reveal_take 成交, 预约期间只有 reserved_taker 可以通过 settle 成交
+    require!(!escrow.requires_commit(), EscrowError::CommitRequired);
+    require!(!escrow.is_reserved(), EscrowError::EscrowReserved);
+
+    let now = Clock::get()?.unix_timestamp;
+    escrow.check_started(now)?;
+    escrow.check_not_expired(now)?;
+
+    // 价格区间和版税都需要 take 传入的额外账户, 循环托管需要 take 重新存入
+    require!(!escrow.has_price_band(), EscrowError::PriceBandRequiresTake);
+    require!(!escrow.enforce_royalties, EscrowError::RoyaltiesRequireTake);
+    require!(!escrow.recurring, EscrowError::RecurringRequiresTake);
+    // direct_only 的托管不能通过 CPI 成交, 这里没有 instructions sysvar, 可信路由也只能使用 take
+    caller::check_direct_call(escrow, &ctx.accounts.config, None)?;
+    // maker 暂停期间不能开始分期成交, 已经开始的分期成交不受影响
+    escrow.check_not_maker_paused()?;
+
+    // 任何一个 mint 被封禁后托管只能退还
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()],
+    )?;
+
+    // vault 中没有 token A 时(例如被 permanent delegate 销毁)不能让 taker 先支付 token B
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+
+    // 荷兰拍卖按开始时的价格锁定, 之后的分期不再衰减
+    let stream_receive = escrow.pro_rata(escrow.amount, escrow.current_receive(now)?)?;
+    require_gte!(max_receive, stream_receive, EscrowError::SlippageExceeded);
+
+    ctx.accounts
+        .escrow
+        .start_stream(taker, stream_receive, now)?;
+    require_gte!(
+        initial_b,
+        ctx.accounts.escrow.min_stream_initial()?,
+        EscrowError::InitialPaymentTooSmall
+    );
+
+    pay_installment(ctx, initial_b, now)
+}
+
+// take_streaming 和 stream_payment 共用的一期支付: taker 支付 amount_b 个 token B, 按比例释放 token A, 付清时关闭托管
+pub(crate) fn pay_installment<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeStreaming<'info>>,
+    amount_b: u64,
+    now: i64,
+) -> Result<()> {
+    let amount_a = ctx.accounts.escrow.stream_release_for(amount_b)?;
+
+    // 余额不足时提前返回可读的错误, 而不是在 CPI 中失败并得到 spl-token 的 insufficient funds
+    let balance_b = ctx.accounts.taker_ata_b.amount;
+    if balance_b < amount_b {
+        msg!("Taker is short {} token B", amount_b - balance_b);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+
+    // 每一期分别收取手续费, maker 收到剩余部分; 启用档位时和 take 一样按 taker 之前的成交次数选择比例
+    let fee = ctx.accounts.config.taker_fee_for(
+        amount_b,
+        ctx.accounts.taker_stats.as_deref().map(|stats| &**stats),
+    )?;
+    let maker_amount = amount_b.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+    let accounts = &ctx.accounts;
+    let net_amount_b = accounts.pay_b(
+        accounts.maker_ata_b.to_account_info(),
+        maker_amount,
+        ctx.remaining_accounts,
+    )?;
+    if fee > 0 {
+        let fee_vault_b = accounts
+            .fee_vault_b
+            .as_ref()
+            .ok_or(EscrowError::MissingFeeVault)?
+            .to_account_info();
+        accounts.pay_b(fee_vault_b, fee, ctx.remaining_accounts)?;
+    }
+
+    // 转账前记录 vault 余额, 因为 CPI 之后 vault 中缓存的 amount 不会自动刷新
+    let vault_amount = accounts.vault.amount;
+    let net_amount_a = accounts.pay_a(
+        accounts.taker_ata_a.to_account_info(),
+        amount_a,
+        ctx.remaining_accounts,
+    )?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.amount = escrow
+        .amount
+        .checked_sub(amount_a)
+        .ok_or(EscrowError::MathOverflow)?;
+    escrow.paid_b = escrow
+        .paid_b
+        .checked_add(amount_b)
+        .ok_or(EscrowError::MathOverflow)?;
+    let paid_in_full = escrow.paid_b == escrow.stream_receive;
+
+    emit_cpi!(StreamPaymentEvent {
+        escrow: escrow.key(),
+        status: escrow.status,
+        maker: escrow.maker,
+        taker: ctx.accounts.taker.key(),
+        mint_a: escrow.mint_a,
+        mint_b: escrow.mint_b,
+        amount_a,
+        net_amount_a,
+        amount_b,
+        net_amount_b,
+        fee,
+        paid_b: escrow.paid_b,
+        stream_receive: escrow.stream_receive,
+        stream_deadline: escrow.stream_deadline,
+        timestamp: now,
+    });
+
+    // 付清时才计入一次成交, 成交量是全部 stream_receive; 没有付清的分期成交不计入
+    // taker 的统计账户可能是这一期 init_if_needed 创建的, 每一期都写入 wallet 和 bump
+    let (taker, stream_receive) = (ctx.accounts.taker.key(), escrow.stream_receive);
+    if let (Some(taker_stats), Some(bump)) =
+        (ctx.accounts.taker_stats.as_mut(), ctx.bumps.taker_stats)
+    {
+        taker_stats.wallet = taker;
+        taker_stats.bump = bump;
+        if paid_in_full {
+            taker_stats.record_trade(stream_receive);
+        }
+    }
+    if let (true, Some(maker_stats)) = (paid_in_full, ctx.accounts.maker_stats.as_mut()) {
+        maker_stats.record_trade(stream_receive);
+    }
+
+    if paid_in_full {
+        let surplus = vault_amount
+            .checked_sub(amount_a)
+            .ok_or(EscrowError::MathOverflow)?;
+        ctx.accounts.close(surplus, ctx.remaining_accounts)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,493 @@
+use crate::{
+    caller,
+    errors::EscrowError,
+    events::TakeEvent,
+    order,
+    pda::{
+        CONFIG_SEED, ESCROW_SEED, FEE_AUTHORITY_SEED, MINT_BLOCKLIST_SEED, ORDER_SEED,
+        PAIR_INDEX_SEED, REGISTRY_SEED, STATS_SEED, TAKE_DELEGATE_SEED, USER_STATS_SEED,
+    },
+    results::TakeResult,
+    state::{
+        Config, Escrow, GlobalStats, MakerRegistry, MintBlocklist, PairIndex, TakeOrder, UserStats,
+    },
+    transfer,
+};
+use anchor_lang::{prelude::*, solana_program::program_option::COption};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    memo::Memo,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 由 relayer 提交的 take, taker 只在链下签名订单, 不需要签名交易也不需要持有 SOL
+// 交易中紧挨着的前一条指令必须是 ed25519 程序对 order::order_message 的验证, 签名者是 taker
+// taker 事先用 SPL approve 把 token B 授权给 take_delegate PDA, 成交时程序用 PDA 的签名转出 token B
+// relayer 支付交易费和所有新账户的租金; 和 take 不同, 不支持 referrer, 成交记录, wSOL 解包和 taker 指定的 token A 账户,
+// 设置了允许列表或 hashlock 的托管需要 taker 提供证明, 只能通过 take 成交
+// remaining_accounts: 先传 mint A 再传 mint B 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(max_receive: u64, expiry: i64, nonce: u64)]
+pub struct TakeWithSig<'info> {
+    // 签名账户, 提交交易的 relayer, 支付交易费和租金
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    /// CHECK: 签名订单的 taker, 由 ed25519 指令验证, 不需要签名交易
+    pub taker: UncheckedAccount<'info>,
+
+    /// CHECK: 托管账户的创建者, 由 escrow 的 seeds 和 has_one 约束, 和 take 一样可以是其他程序拥有的 PDA
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    /// CHECK: 接收 token B 的钱包, 只作为 maker_ata_b 的 authority, 由 has_one 约束为 escrow.receive_to
+    pub receive_to: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = receive_to @ EscrowError::InvalidReceiveTo,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        has_one = mint_b @ EscrowError::InvalidMintB,
+        constraint = escrow.is_taker_allowed(&taker.key()) @ EscrowError::UnauthorizedTaker,
+        constraint = taker.key() != escrow.maker @ EscrowError::SelfTrade,
+        constraint = !escrow.is_arbitrated() @ EscrowError::ArbiterRequired,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+        constraint = !escrow.requires_commit() @ EscrowError::CommitRequired,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved,
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(mint::token_program = token_program_a)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mint::token_program = token_program_b,
+        constraint = mint_a.key() != mint_b.key() @ EscrowError::IdenticalMints,
+    )]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program_a
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // taker 接收 Token A 的 ATA 账户, 不存在时由 relayer 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_a
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // maker 的 Token A 的 ATA 账户, 用来退还 vault 中超出 escrow.amount 的多余代币
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // taker 支付 Token B 的账户, delegate 必须是 take_delegate, 在 handler 中检查授权数量
+    #[account(
+        mut,
+        token::mint = mint_b,
+        token::authority = taker,
+        token::token_program = token_program_b
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // receive_to 接收 Token B 的 ATA 账户
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        associated_token::mint = mint_b,
+        associated_token::authority = receive_to,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: 只作为 taker_ata_b 的 delegate 签名转账, 由 seeds 约束地址
+    #[account(seeds = [TAKE_DELEGATE_SEED], bump)]
+    pub take_delegate: UncheckedAccount<'info>,
+
+    // 订单的 nonce 账户, 已经存在时 init 失败, 防止 relayer 重放同一个签名
+    #[account(
+        init,
+        payer = relayer,
+        space = TakeOrder::INIT_SPACE + TakeOrder::DISCRIMINATOR.len(),
+        seeds = [ORDER_SEED, taker.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub order: Box<Account<'info, TakeOrder>>,
+
+    // 协议配置账户, 读取手续费比例并检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: mint 黑名单, 由 seeds 约束地址; 从未添加过时是空的系统账户, 见 MintBlocklist::check
+    #[account(seeds = [MINT_BLOCKLIST_SEED], bump)]
+    pub mint_blocklist: UncheckedAccount<'info>,
+
+    // 全局统计账户, 第一次使用时由 relayer 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = GlobalStats::INIT_SPACE + GlobalStats::DISCRIMINATOR.len(),
+        seeds = [STATS_SEED],
+        bump,
+    )]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    // taker 的成交统计, 第一次传入时由 relayer 支付租金创建; 启用手续费档位时必须传入, 按它选择手续费比例, 见 take
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = UserStats::INIT_SPACE + UserStats::DISCRIMINATOR.len(),
+        seeds = [USER_STATS_SEED, taker.key().as_ref()],
+        bump,
+    )]
+    pub taker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    // maker 的成交统计, 必须已经通过 init_user_stats 创建; 不传时不计入
+    #[account(
+        mut,
+        seeds = [USER_STATS_SEED, maker.key().as_ref()],
+        bump = maker_stats.bump,
+    )]
+    pub maker_stats: Option<Box<Account<'info, UserStats>>>,
+
+    /// CHECK: 只作为手续费 ATA 的 authority, 由 seeds 约束地址
+    #[account(seeds = [FEE_AUTHORITY_SEED], bump = config.fee_authority_bump)]
+    pub fee_authority: UncheckedAccount<'info>,
+
+    // 协议接收 Token B 手续费的 ATA 账户, 手续费为 0 时可以不传
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program_b
+    )]
+    pub fee_vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 成交关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    /// CHECK: instructions sysvar, 用来读取前一条 ed25519 指令
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>,
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeWithSig<'info> {
+    // 用 take_delegate 的签名从 taker_ata_b 转出 token B, 返回接收方扣除 mint B 转账手续费后实际收到的数量
+    fn transfer_b(
+        &self,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        amount: u64,
+        delegate_bump: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &to.to_account_info(),
+            &self.escrow.key(),
+        )?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[TAKE_DELEGATE_SEED, &[delegate_bump]]];
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_b.to_account_info(),
+                TransferChecked {
+                    from: self.taker_ata_b.to_account_info(),
+                    to: to.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    authority: self.take_delegate.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_b.decimals,
+        )?;
+
+        amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 和 take 相同: 把 escrow.amount 转给 taker, 多余的退还给 maker, 然后关闭 vault
+    // 返回 taker 扣除 mint A 转账手续费后实际收到的数量
+    fn withdraw_and_close_vault(&self, remaining_accounts: &[AccountInfo<'info>]) -> Result<u64> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        transfer::memo_if_required(
+            &self.memo_program.to_account_info(),
+            &self.taker_ata_a.to_account_info(),
+            &self.escrow.key(),
+        )?;
+        let withheld = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program_a.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to: self.taker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            self.escrow.amount,
+            self.mint_a.decimals,
+        )?;
+
+        let surplus = self
+            .vault
+            .amount
+            .checked_sub(self.escrow.amount)
+            .ok_or(EscrowError::MathOverflow)?;
+        if surplus > 0 {
+            transfer::memo_if_required(
+                &self.memo_program.to_account_info(),
+                &self.maker_ata_a.to_account_info(),
+                &self.escrow.key(),
+            )?;
+            transfer::transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program_a.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        to: self.maker_ata_a.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        authority: self.escrow.to_account_info(),
+                    },
+                    &signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec()),
+                surplus,
+                self.mint_a.decimals,
+            )?;
+        }
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program_a.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        self.escrow
+            .amount
+            .checked_sub(withheld)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+}
+
+// max_receive, expiry 和 nonce 都是 taker 签名的订单内容; expiry 为 0 时订单不过期
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeWithSig<'info>>,
+    max_receive: u64,
+    expiry: i64,
+    nonce: u64,
+) -> Result<TakeResult> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(expiry == 0 || now <= expiry, EscrowError::OrderExpired);
+
+    // 签名覆盖托管地址, 同一个订单不能用来成交其他托管
+    let escrow = ctx.accounts.escrow.key();
+    let taker = ctx.accounts.taker.key();
+    order::check_signed(
+        &ctx.accounts.instructions,
+        &taker,
+        &order::order_message(&escrow, max_receive, expiry, nonce),
+    )?;
+
+    MintBlocklist::check(
+        &ctx.accounts.mint_blocklist,
+        &[ctx.accounts.mint_a.key(), ctx.accounts.mint_b.key()],
+    )?;
+
+    // 订单中没有默克尔证明和 preimage, 只有不设置允许列表和 hashlock 的托管能通过检查
+    require!(
+        ctx.accounts.escrow.is_on_allowlist(&taker, &[]),
+        EscrowError::NotOnAllowlist
+    );
+    ctx.accounts.escrow.check_preimage(&[])?;
+    ctx.accounts.escrow.check_started(now)?;
+    ctx.accounts.escrow.check_not_expired(now)?;
+    // 价格区间需要 take 传入的 price_feed 账户检查
+    require!(
+        !ctx.accounts.escrow.has_price_band(),
+        EscrowError::PriceBandRequiresTake
+    );
+    // 版税需要 take 传入的 metadata 和 creator 账户
+    require!(
+        !ctx.accounts.escrow.enforce_royalties,
+        EscrowError::RoyaltiesRequireTake
+    );
+    // 循环托管成交后需要 take 重新存入并保留 escrow, 这里成交完就关闭, 会留下 maker ATA 上的授权
+    require!(
+        !ctx.accounts.escrow.recurring,
+        EscrowError::RecurringRequiresTake
+    );
+    // direct_only 的托管通过 CPI 成交时外层程序必须是可信的路由
+    caller::check_direct_call(
+        &ctx.accounts.escrow,
+        &ctx.accounts.config,
+        Some(&ctx.accounts.instructions),
+    )?;
+    // maker 暂停期间不能成交
+    ctx.accounts.escrow.check_not_maker_paused()?;
+
+    let effective_receive = ctx.accounts.escrow.current_receive(now)?;
+    let amount_a = ctx.accounts.escrow.amount;
+    let amount_b = ctx.accounts.escrow.pro_rata(amount_a, effective_receive)?;
+    require_gte!(max_receive, amount_b, EscrowError::SlippageExceeded);
+
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program_a.to_account_info(),
+        &mut ctx.accounts.vault,
+    )?;
+    require_gt!(ctx.accounts.vault.amount, 0, EscrowError::EmptyVault);
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    let taker_ata_b = &ctx.accounts.taker_ata_b;
+    if taker_ata_b.amount < amount_b {
+        msg!("Taker is short {} token B", amount_b - taker_ata_b.amount);
+        return err!(EscrowError::InsufficientTakerBalance);
+    }
+    require!(
+        taker_ata_b.delegate == COption::Some(ctx.accounts.take_delegate.key())
+            && taker_ata_b.delegated_amount >= amount_b,
+        EscrowError::InsufficientDelegation
+    );
+
+    // 手续费从 taker 支付的 Token B 中扣除, maker 收到剩余部分; 启用档位时和 take 一样按 taker 之前的成交次数选择比例
+    let fee = ctx.accounts.config.taker_fee_for(
+        amount_b,
+        ctx.accounts.taker_stats.as_deref().map(|stats| &**stats),
+    )?;
+    let maker_amount = amount_b.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+
+    let delegate_bump = ctx.bumps.take_delegate;
+    let net_amount_b = ctx.accounts.transfer_b(
+        &ctx.accounts.maker_ata_b,
+        maker_amount,
+        delegate_bump,
+        ctx.remaining_accounts,
+    )?;
+    if fee > 0 {
+        let fee_vault_b = ctx
+            .accounts
+            .fee_vault_b
+            .as_ref()
+            .ok_or(EscrowError::MissingFeeVault)?;
+        ctx.accounts
+            .transfer_b(fee_vault_b, fee, delegate_bump, ctx.remaining_accounts)?;
+    }
+
+    let net_amount_a = ctx
+        .accounts
+        .withdraw_and_close_vault(ctx.remaining_accounts)?;
+
+    ctx.accounts.order.set_inner(TakeOrder {
+        taker,
+        nonce,
+        escrow,
+        bump: ctx.bumps.order,
+    });
+
+    if let (Some(taker_stats), Some(bump)) =
+        (ctx.accounts.taker_stats.as_mut(), ctx.bumps.taker_stats)
+    {
+        taker_stats.record_taker_trade(taker, bump, amount_b);
+    }
+    if let Some(maker_stats) = ctx.accounts.maker_stats.as_mut() {
+        maker_stats.record_trade(amount_b);
+    }
+
+    ctx.accounts.stats.bump = ctx.bumps.stats;
+    ctx.accounts.stats.record_take();
+    MakerRegistry::remove_seed(&ctx.accounts.registry, ctx.accounts.escrow.seed)?;
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+    }
+
+    emit_cpi!(TakeEvent {
+        escrow,
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        taker,
+        mint_a: ctx.accounts.mint_a.key(),
+        mint_b: ctx.accounts.mint_b.key(),
+        amount_a,
+        net_amount_a,
+        amount_b,
+        net_amount_b,
+        fee,
+        referral_fee: 0,
+        royalties: Vec::new(),
+        tip: 0,
+        effective_receive,
+        preimage: vec![],
+        timestamp: now,
+    });
+
+    Ok(TakeResult {
+        amount_a_out: net_amount_a,
+        amount_b_in: amount_b,
+        fee,
+    })
+}
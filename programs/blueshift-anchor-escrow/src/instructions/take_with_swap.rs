@@ -0,0 +1,96 @@
+// 嵌套 Take 账户列表时还需要 derive(Accounts) 为它生成的 TakeBumps 等类型, 因此整体导入
+use crate::instructions::take::*;
+use crate::{errors::EscrowError, results::TakeResult};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke,
+    },
+};
+
+// taker 没有 mint B 时用其他 token 支付: 先 CPI 兑换路由程序把 token C 换成 token B 存入 taker_ata_b, 再按 take 的流程成交
+// 路由需要的账户全部通过 remaining_accounts 传入, 路由数据原样作为 route_data 传给路由程序
+// remaining_accounts 不再作为 transfer hook 的额外账户, 因此不支持带有 transfer hook 的 mint
+#[derive(Accounts)]
+pub struct TakeWithSwap<'info> {
+    pub take: Take<'info>,
+
+    /// CHECK: 只能是 config 允许的兑换路由程序, 由 address 约束; 没有设置时 take_with_swap 不可用
+    #[account(
+        executable,
+        address = take.config.swap_router @ EscrowError::InvalidSwapRouter,
+        constraint = take.config.swap_router != Pubkey::default() @ EscrowError::InvalidSwapRouter,
+    )]
+    pub swap_program: UncheckedAccount<'info>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TakeWithSwap<'info>>,
+    route_data: Vec<u8>,
+    max_receive: u64,
+    expected_amount_a: u64,
+    proof: Vec<[u8; 32]>,
+    preimage: Vec<u8>,
+    write_receipt: bool,
+) -> Result<TakeResult> {
+    // remaining_accounts 都属于路由, 无法再传入版税需要的 metadata 和 creator 账户
+    require!(
+        !ctx.accounts.take.escrow.enforce_royalties,
+        EscrowError::RoyaltiesRequireTake
+    );
+
+    // 只按 taker_ata_b 的余额变化计算兑换得到的 token B, 不读取路由程序的返回值
+    let balance_before = ctx.accounts.take.taker_ata_b.amount;
+
+    // 路由程序只能使用 taker 在交易中的签名, escrow PDA 没有签名, 无法转出 vault 中的代币
+    let route = ctx.remaining_accounts;
+    let swap = Instruction {
+        program_id: ctx.accounts.swap_program.key(),
+        accounts: route
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: route_data,
+    };
+    let mut infos = route.to_vec();
+    infos.push(ctx.accounts.swap_program.to_account_info());
+    invoke(&swap, &infos)?;
+
+    ctx.accounts.take.taker_ata_b.reload()?;
+    let swapped = ctx
+        .accounts
+        .take
+        .taker_ata_b
+        .amount
+        .saturating_sub(balance_before);
+
+    // 其余流程和 take 完全相同
+    let take_ctx = Context::new(ctx.program_id, &mut ctx.accounts.take, &[], ctx.bumps.take);
+    let result = crate::instructions::take::handler(
+        take_ctx,
+        TakeArgs {
+            max_receive: Some(max_receive),
+            expected_amount_a: Some(expected_amount_a),
+            proof,
+            preimage,
+            write_receipt,
+            tip: 0,
+            reserved: [0; 24],
+        },
+    )?;
+
+    // 兑换得到的 token B 必须足够支付本次成交, 不能动用 taker 原有的余额; 多出的部分留在 taker_ata_b 中
+    require_gte!(
+        swapped,
+        result.amount_b_in,
+        EscrowError::InsufficientSwapOutput
+    );
+
+    Ok(result)
+}
@@ -0,0 +1,151 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, ESCROW_SEED},
+    state::{Config, Escrow, EscrowStatus},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+// remaining_accounts: mint A 的 transfer hook 额外账户
+#[derive(Accounts)]
+pub struct TopUp<'info> {
+    // 签名账户, 只有托管的创建者可以追加 token A
+    pub maker: Signer<'info>,
+
+    // 托管账户的数据账户, 追加后需要更新 amount, deposited 和 receive
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved,
+        constraint = escrow.status != EscrowStatus::Streaming @ EscrowError::InvalidEscrowStatus // 分期成交锁定了数量和价格
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    // 创建者的 Token A 的 ATA 账户, 追加的 token A 从这里转出
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+
+    // 已经存在的托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> TopUp<'info> {
+    // 按原有单价增加 receive(荷兰拍卖同时增加 end_receive, 其他支付 mint 同时增加), 并记录追加的 token A 数量
+    fn scale_escrow(&mut self, additional_amount: u64) -> Result<()> {
+        let additional_receive = self.escrow.receive_for(additional_amount)?;
+        let additional_end_receive = self
+            .escrow
+            .pro_rata(additional_amount, self.escrow.end_receive)?;
+
+        self.escrow.receive = self
+            .escrow
+            .receive
+            .checked_add(additional_receive)
+            .ok_or(EscrowError::MathOverflow)?;
+        self.escrow.end_receive = self
+            .escrow
+            .end_receive
+            .checked_add(additional_end_receive)
+            .ok_or(EscrowError::MathOverflow)?;
+        let deposited = self
+            .escrow
+            .deposited
+            .checked_add(additional_amount)
+            .ok_or(EscrowError::MathOverflow)?;
+        self.escrow.alt_payments = self.escrow.rebased_payments(deposited)?;
+        self.escrow.deposited = deposited;
+        self.escrow.amount = self
+            .escrow
+            .amount
+            .checked_add(additional_amount)
+            .ok_or(EscrowError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    // 把追加的 token A 转账到 vault 中
+    fn deposit_tokens(
+        &self,
+        additional_amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        transfer::transfer_checked(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.maker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    to: self.vault.to_account_info(),
+                    authority: self.maker.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            additional_amount,
+            self.mint_a.decimals,
+        )?;
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TopUp<'info>>,
+    additional_amount: u64,
+) -> Result<()> {
+    // 追加的数量必须大于 0; Escrow::FULL_BALANCE 追加 maker ATA 在执行时的全部余额
+    require_gt!(additional_amount, 0, EscrowError::InvalidAmount);
+    let additional_amount = if additional_amount == Escrow::FULL_BALANCE {
+        let balance = ctx.accounts.maker_ata_a.amount;
+        require_gt!(balance, 0, EscrowError::EmptyMakerBalance);
+        balance
+    } else {
+        additional_amount
+    };
+
+    // 和 make 一样只记录 vault 扣除 mint A 转账手续费后实际收到的数量
+    let fee = transfer::fee_for(&ctx.accounts.mint_a.to_account_info(), additional_amount)?;
+    let net_amount = additional_amount
+        .checked_sub(fee)
+        .ok_or(EscrowError::MathOverflow)?;
+    require_gt!(net_amount, 0, EscrowError::InvalidAmount);
+
+    // 更新数据, 溢出时报错而不是回绕
+    ctx.accounts.scale_escrow(net_amount)?;
+
+    // 存入 token A
+    ctx.accounts
+        .deposit_tokens(additional_amount, ctx.remaining_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use crate::{
+    errors::EscrowError,
+    events::TransferMakerEvent,
+    pda::{CONFIG_SEED, ESCROW_SEED, PAIR_INDEX_SEED, REGISTRY_SEED},
+    realloc,
+    state::{Config, Escrow, MakerRegistry, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+// 把托管交给 new_maker, 例如 maker 更换钱包时不需要先退还再重新挂单
+// escrow 的地址由 maker 派生, 不能原地修改 maker, 因此用同一个 seed 在 new_maker 下创建新的 escrow 和 vault,
+// 用旧的 signer seeds 把 token A 全部移入新 vault; 之后所有指令都按 new_maker 派生地址, 旧 maker 不再有任何权限
+// 新账户的租金由 new_maker 支付, 旧账户的租金还给旧的 rent_payer; 旧托管上未处理的还价不能再被接受, 只能取消
+// remaining_accounts: mint A 的 transfer hook 额外账户
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TransferMaker<'info> {
+    // 签名账户, 当前的 maker
+    pub maker: Signer<'info>,
+
+    // 签名账户, 接手托管的新 maker, 支付新账户的租金
+    #[account(mut)]
+    pub new_maker: Signer<'info>,
+
+    // 旧托管的租金支付者, 旧账户关闭后租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    // 旧托管账户, 指令结束时关闭
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        constraint = escrow.status.is_fillable() @ EscrowError::EscrowFrozen,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    // 旧托管的 vault, token A 全部转入 new_vault 后关闭
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // 新托管账户, 由 new_maker 和旧托管的 seed 派生; new_maker 就是 maker 时地址和旧托管相同, init 失败
+    #[account(
+        init,
+        payer = new_maker,
+        space = Escrow::INIT_SPACE + Escrow::DISCRIMINATOR.len(),
+        seeds = [ESCROW_SEED, new_maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub new_escrow: Box<Account<'info, Escrow>>,
+
+    // 新托管的 vault
+    #[account(
+        init,
+        payer = new_maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = new_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub new_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+
+    // 协议配置账户, 用来检查协议是否暂停
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: 旧 maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 用新托管替换旧托管; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // new_maker 的托管索引, 第一次使用时由 new_maker 支付租金创建
+    #[account(
+        init_if_needed,
+        payer = new_maker,
+        space = new_registry.data_len().max(MakerRegistry::space(0)),
+        seeds = [REGISTRY_SEED, new_maker.key().as_ref()],
+        bump,
+    )]
+    pub new_registry: Box<Account<'info, MakerRegistry>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TransferMaker<'info> {
+    // 把旧 vault 的 token A 全部转入新 vault 并关闭旧 vault, 返回新 vault 实际收到的数量
+    fn move_vault(&self, remaining_accounts: &[AccountInfo<'info>]) -> Result<u64> {
+        let seed = self.escrow.seed.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            seed.as_ref(),
+            &[self.escrow.bump],
+        ]];
+
+        // mint A 带有 TransferFee 扩展时, vault 之间的转账同样会被扣留手续费
+        let amount = self.vault.amount;
+        let fee = transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    to: self.new_vault.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_a.decimals,
+        )?;
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+                authority: self.escrow.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        amount
+            .checked_sub(fee)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    // 把 seed 加入 new_maker 的索引, 空间不够时由 new_maker 补足租金后扩容
+    fn register_seed(&mut self, seed: u64, bump: u8) -> Result<()> {
+        require_gt!(
+            MakerRegistry::MAX_SEEDS,
+            self.new_registry.seeds.len(),
+            EscrowError::RegistryFull
+        );
+
+        let space = MakerRegistry::space(self.new_registry.seeds.len() + 1);
+        if self.new_registry.to_account_info().data_len() < space {
+            realloc::resize(
+                &self.new_registry.to_account_info(),
+                &self.new_maker.to_account_info(),
+                &self.system_program.to_account_info(),
+                space,
+            )?;
+        }
+
+        self.new_registry.maker = self.new_maker.key();
+        self.new_registry.bump = bump;
+        self.new_registry.seeds.push(seed);
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, TransferMaker<'info>>) -> Result<()> {
+    // wSOL 的 vault 先同步直接转入的 lamports, 一起移入新 vault
+    transfer::sync_native_if_needed(
+        &ctx.accounts.token_program.to_account_info(),
+        &mut ctx.accounts.vault,
+    )?;
+    require!(!ctx.accounts.vault.is_frozen(), EscrowError::VaultFrozen);
+
+    let amount = ctx.accounts.vault.amount;
+    let net_amount = ctx.accounts.move_vault(ctx.remaining_accounts)?;
+
+    // 新托管沿用旧托管的所有条款和状态; 由旧 maker 自己接收的 token B 改为由 new_maker 接收
+    // 旧 maker 指定的 refund_delegate 不再有效, 由 new_maker 重新设置
+    let old = &ctx.accounts.escrow;
+    let new_maker = ctx.accounts.new_maker.key();
+    let receive_to = if old.receive_to == old.maker {
+        new_maker
+    } else {
+        old.receive_to
+    };
+    let new_escrow = Escrow {
+        maker: new_maker,
+        rent_payer: new_maker,
+        receive_to,
+        refund_delegate: Pubkey::default(),
+        // vault 中超出 amount 的部分不出售, 转账手续费让新 vault 收到的少于 amount 时按实际数量出售
+        amount: old.amount.min(net_amount),
+        bump: ctx.bumps.new_escrow,
+        vault: ctx.accounts.new_vault.key(),
+        ..(***old).clone()
+    };
+    ctx.accounts.new_escrow.set_inner(new_escrow);
+
+    let seed = ctx.accounts.escrow.seed;
+    MakerRegistry::remove_seed(&ctx.accounts.registry, seed)?;
+    ctx.accounts.register_seed(seed, ctx.bumps.new_registry)?;
+    // 新托管沿用 pair_indexed, 在交易对索引中用新托管替换旧托管, 计数不变
+    if ctx.accounts.escrow.pair_indexed {
+        let index = ctx
+            .accounts
+            .pair_index
+            .as_mut()
+            .ok_or(EscrowError::MissingPairIndex)?;
+        index.record_close(&ctx.accounts.escrow.key());
+        index.record_make(
+            ctx.accounts.new_escrow.key(),
+            ctx.accounts.new_escrow.receive,
+            ctx.accounts.new_escrow.deposited,
+        );
+    }
+
+    emit_cpi!(TransferMakerEvent {
+        old_escrow: ctx.accounts.escrow.key(),
+        escrow: ctx.accounts.new_escrow.key(),
+        status: ctx.accounts.new_escrow.status,
+        old_maker: ctx.accounts.maker.key(),
+        new_maker,
+        mint_a: ctx.accounts.mint_a.key(),
+        amount,
+        net_amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    // 指令执行完毕后 anchor 自动关闭旧的 escrow 数据账户
+
+    Ok(())
+}
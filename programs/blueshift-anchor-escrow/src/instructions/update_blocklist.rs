@@ -0,0 +1,58 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, MINT_BLOCKLIST_SEED},
+    realloc,
+    state::{Config, MintBlocklist},
+};
+use anchor_lang::prelude::*;
+
+// add_blocked_mint 和 remove_blocked_mint 共用的账户列表
+#[derive(Accounts)]
+pub struct UpdateBlocklist<'info> {
+    // 签名账户, 必须是协议管理员, 支付黑名单扩容的租金
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    // mint 黑名单, 第一次调用时创建
+    // 已经存在时 space 取当前大小, 通过 init_if_needed 对空间的检查, 大小由 handler 调整, 见 realloc::resize
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = mint_blocklist.data_len().max(MintBlocklist::space(0)),
+        seeds = [MINT_BLOCKLIST_SEED],
+        bump,
+    )]
+    pub mint_blocklist: Account<'info, MintBlocklist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<UpdateBlocklist>, mint: Pubkey, blocked: bool) -> Result<()> {
+    // 重复添加或移除不在列表中的 mint 不会报错, 紧急封禁时可以放心重试
+    let mut mints = ctx.accounts.mint_blocklist.mints.clone();
+    mints.retain(|listed| *listed != mint);
+    if blocked {
+        mints.push(mint);
+    }
+
+    realloc::resize(
+        &ctx.accounts.mint_blocklist.to_account_info(),
+        &ctx.accounts.admin.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        MintBlocklist::space(mints.len()),
+    )?;
+
+    let mint_blocklist = &mut ctx.accounts.mint_blocklist;
+    mint_blocklist.bump = ctx.bumps.mint_blocklist;
+    mint_blocklist.mints = mints;
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use crate::{
+    errors::EscrowError,
+    pda::{CONFIG_SEED, EXEMPT_LIST_SEED},
+    realloc,
+    state::{Config, ExemptList},
+};
+use anchor_lang::prelude::*;
+
+// add_exempt_wallet 和 remove_exempt_wallet 共用的账户列表
+#[derive(Accounts)]
+pub struct UpdateExemptList<'info> {
+    // 签名账户, 必须是协议管理员, 支付豁免列表扩容的租金
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    // 协议配置账户
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ EscrowError::InvalidAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    // 挂单费豁免列表, 第一次调用时创建
+    // 已经存在时 space 取当前大小, 通过 init_if_needed 对空间的检查, 大小由 handler 调整, 见 realloc::resize
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = exempt_list.data_len().max(ExemptList::space(0)),
+        seeds = [EXEMPT_LIST_SEED],
+        bump,
+    )]
+    pub exempt_list: Account<'info, ExemptList>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<UpdateExemptList>, wallet: Pubkey, exempt: bool) -> Result<()> {
+    // 和 mint 黑名单一样, 重复添加或移除不在列表中的钱包不会报错
+    let mut wallets = ctx.accounts.exempt_list.wallets.clone();
+    wallets.retain(|listed| *listed != wallet);
+    if exempt {
+        wallets.push(wallet);
+    }
+
+    realloc::resize(
+        &ctx.accounts.exempt_list.to_account_info(),
+        &ctx.accounts.admin.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        ExemptList::space(wallets.len()),
+    )?;
+
+    let exempt_list = &mut ctx.accounts.exempt_list;
+    exempt_list.bump = ctx.bumps.exempt_list;
+    exempt_list.wallets = wallets;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use crate::{
+    errors::EscrowError,
+    events::UpdateEvent,
+    pda::ESCROW_SEED,
+    state::{Escrow, EscrowStatus},
+};
+use anchor_lang::prelude::*;
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateReceive<'info> {
+    // 签名账户, 只有托管的创建者可以修改价格
+    pub maker: Signer<'info>,
+
+    // 托管账户的数据账户, 只修改 receive, 不涉及 vault
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved, // 预约的 taker 锁定了价格
+        constraint = escrow.status != EscrowStatus::Streaming @ EscrowError::InvalidEscrowStatus // 分期成交锁定了价格
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+impl<'info> UpdateReceive<'info> {
+    // 新的 receive 对应当前仍在出售的 token A, 因此同时把 deposited 重置为 amount, 保证单价仍然是 receive / deposited
+    // 只修改 mint_b 的价格, 其他支付 mint 按原有单价换算到新的 deposited
+    fn reprice(&mut self, new_receive: u64) -> Result<u64> {
+        let old_receive = self.escrow.receive;
+
+        self.escrow.alt_payments = self.escrow.rebased_payments(self.escrow.amount)?;
+        self.escrow.receive = new_receive;
+        self.escrow.deposited = self.escrow.amount;
+
+        Ok(old_receive)
+    }
+}
+
+pub fn handler(ctx: Context<UpdateReceive>, new_receive: u64) -> Result<()> {
+    // 新的价格必须大于 0
+    require_gt!(new_receive, 0, EscrowError::InvalidAmount);
+
+    // 荷兰拍卖的价格由衰减参数决定, 不能直接修改
+    require!(
+        !ctx.accounts.escrow.is_dutch(),
+        EscrowError::DutchAuctionReprice
+    );
+
+    let old_receive = ctx.accounts.reprice(new_receive)?;
+
+    emit_cpi!(UpdateEvent {
+        escrow: ctx.accounts.escrow.key(),
+        status: ctx.accounts.escrow.status,
+        maker: ctx.accounts.maker.key(),
+        old_receive,
+        new_receive,
+        amount: ctx.accounts.escrow.amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use crate::{
+    errors::EscrowError,
+    events::RefundEvent,
+    pda::{ESCROW_SEED, PAIR_INDEX_SEED, REGISTRY_SEED, STATS_SEED},
+    state::{Escrow, GlobalStats, MakerRegistry, PairIndex},
+    transfer,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct WithdrawPartial<'info> {
+    // 签名账户, 只有托管的创建者可以取回部分 token A
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它
+    #[account(mut)]
+    pub rent_payer: SystemAccount<'info>,
+
+    // 托管账户的数据账户, 没有 close 约束, 只有全部取回时才会在 handler 中手动关闭
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = rent_payer @ EscrowError::InvalidRentPayer,
+        has_one = mint_a @ EscrowError::InvalidMintA,
+        constraint = escrow.status.is_refundable() @ EscrowError::InvalidEscrowStatus,
+        constraint = !escrow.is_reserved() @ EscrowError::EscrowReserved
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // Token A 的 mint 账户
+    #[account(mint::token_program = token_program)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    // 托管资金 ATA 账户, 按 make 时记录的地址校验
+    #[account(
+        mut,
+        address = escrow.vault @ EscrowError::InvalidVault,
+        token::mint = mint_a,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    // 创建者的 Token A 的 ATA 账户, 不存在时重新创建
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+
+    // 全局统计账户, make 总是先创建它, 因此一定已经存在
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Box<Account<'info, GlobalStats>>,
+
+    /// CHECK: maker 的托管索引, 由 seeds 约束地址; 托管早于索引创建时是空的系统账户, 见 MakerRegistry::remove_seed
+    #[account(mut, seeds = [REGISTRY_SEED, maker.key().as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    // 托管计入了交易对索引时必须传入, 全部取回关闭托管后从索引中移除; 没有计入时可以不传
+    #[account(
+        mut,
+        seeds = [PAIR_INDEX_SEED, escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Option<Box<Account<'info, PairIndex>>>,
+
+    // 账户所需要的程序
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawPartial<'info> {
+    // 按原有单价减少 receive(荷兰拍卖同时减少 end_receive, 其他支付 mint 同时减少), 并把剩余数量作为新的定价基准
+    fn shrink_escrow(&mut self, amount_a: u64) -> Result<()> {
+        let remaining = self.escrow.amount - amount_a;
+        let receive = self.escrow.receive_for(remaining)?;
+        let end_receive = self.escrow.pro_rata(remaining, self.escrow.end_receive)?;
+
+        // 向上取整保证了这里不会为 0, 显式检查防止 vault 中还有代币但 receive 为 0
+        require_gt!(receive, 0, EscrowError::InvalidAmount);
+        require!(
+            !self.escrow.is_dutch() || end_receive > 0,
+            EscrowError::InvalidAmount
+        );
+
+        self.escrow.receive = receive;
+        self.escrow.end_receive = end_receive;
+        self.escrow.alt_payments = self.escrow.rebased_payments(remaining)?;
+        self.escrow.deposited = remaining;
+        self.escrow.amount = remaining;
+
+        Ok(())
+    }
+
+    // 把 vault 中的 token A 转账给 maker, transfer hook mint 的额外账户从 remaining_accounts 转发
+    fn withdraw(&self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        transfer::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    to: self.maker_ata_a.to_account_info(),
+                    mint: self.mint_a.to_account_info(),
+                    authority: self.escrow.to_account_info(),
+                },
+                &signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.mint_a.decimals,
+        )?;
+
+        Ok(())
+    }
+
+    // 关闭 vault 和 escrow, 租金还给支付租金的账户
+    fn close_vault_and_escrow(&mut self) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            ESCROW_SEED,
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                destination: self.rent_payer.to_account_info(),
+            },
+            &signer_seeds,
+        ))?;
+
+        self.stats.record_refund();
+        MakerRegistry::remove_seed(&self.registry, self.escrow.seed)?;
+        if self.escrow.pair_indexed {
+            let index = self
+                .pair_index
+                .as_mut()
+                .ok_or(EscrowError::MissingPairIndex)?;
+            index.record_close(&self.escrow.key());
+        }
+        self.escrow.close(self.rent_payer.to_account_info())?;
+
+        Ok(())
+    }
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawPartial<'info>>,
+    amount_a: u64,
+) -> Result<()> {
+    // HTLC 托管只能在过期之后取回
+    ctx.accounts
+        .escrow
+        .check_maker_can_withdraw(Clock::get()?.unix_timestamp)?;
+
+    // 取回的数量必须大于 0 且不能超过仍在出售的数量
+    require_gt!(amount_a, 0, EscrowError::InvalidAmount);
+    require_gte!(
+        ctx.accounts.escrow.amount,
+        amount_a,
+        EscrowError::InvalidAmount
+    );
+
+    if amount_a < ctx.accounts.escrow.amount {
+        // 部分取回, 托管继续有效
+        ctx.accounts.shrink_escrow(amount_a)?;
+        ctx.accounts.withdraw(amount_a, ctx.remaining_accounts)?;
+    } else {
+        // 全部取回, 和 refund 一样取走 vault 中的全部余额(包括别人转入的多余代币)并关闭账户
+        let amount = ctx.accounts.vault.amount;
+        ctx.accounts.withdraw(amount, ctx.remaining_accounts)?;
+        ctx.accounts.close_vault_and_escrow()?;
+
+        emit_cpi!(RefundEvent {
+            escrow: ctx.accounts.escrow.key(),
+            status: ctx.accounts.escrow.status,
+            maker: ctx.accounts.maker.key(),
+            mint_a: ctx.accounts.mint_a.key(),
+            mint_b: ctx.accounts.escrow.mint_b,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+
+    Ok(())
+}
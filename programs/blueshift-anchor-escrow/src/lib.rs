@@ -1,35 +1,799 @@
+// cpi 模块由 #[program] 生成, 参数和指令相同, 无法像指令一样逐个添加 allow
+#![cfg_attr(feature = "cpi", allow(clippy::too_many_arguments))]
+
 use anchor_lang::prelude::*;
 
-// 声明所有的模块
-mod errors;
+// 声明所有的模块, 账户, 事件和错误码公开给通过 CPI 调用本程序的程序使用
+mod caller; // direct_only 托管的 CPI 调用方检查
+#[cfg(any(feature = "client", test))]
+pub mod client; // 不依赖 anchor 客户端的指令构造函数
+pub mod errors;
+pub mod events;
 mod instructions;
-mod state;
+mod merkle;
+pub mod metadata; // Metaplex NFT 的 Metadata 账户的读取
+pub mod oracle; // Pyth 价格账户的读取和折算
+mod order; // take_with_sig 的签名订单
+pub mod pda; // PDA 种子和地址派生函数
+pub mod pnft; // 通过 Token Metadata 转账 programmable NFT
+mod realloc;
+pub mod results; // make 和 take 通过 return data 返回的结果
+pub mod state;
+mod transfer;
 
 // 导入所有的指令
 use instructions::*;
+// 批量指令的大小上限, 供客户端拆分批次
+pub use instructions::{make_batch::MAX_BATCH_SIZE, refund_batch::MAX_REFUND_BATCH_SIZE};
+// make_v2 和 take_v2 的参数, 供 CPI 调用方构造
+pub use instructions::{make::MakeArgs, take::TakeArgs};
+use results::{EscrowView, MakeResult, TakeResult};
+use state::{FeeTier, PaymentMint};
 
-// 运行本地 test 时使用
-// declare_id!("Ew2CvRfUbSSUfe5DurrYJm1xXCxhKskHoB7GkL1Gcggj");
+// 每个集群部署的程序 ID 由 feature 选择, 默认是 localnet; 开启 mainnet 或 devnet 时覆盖默认的 localnet
+// 因此发布时只需要 `--features mainnet`, 不需要关闭默认 feature; IDL 的 address 也来自这里选中的 declare_id
+#[cfg(not(any(feature = "mainnet", feature = "devnet", feature = "localnet")))]
+compile_error!("enable one of the mainnet, devnet or localnet features to select the program ID");
+#[cfg(all(feature = "mainnet", feature = "devnet"))]
+compile_error!("enable only one of the mainnet and devnet features");
 
 // blueshift 平台提交使用
+#[cfg(feature = "mainnet")]
+declare_id!("22222222222222222222222222222222222222222222");
+
+// devnet 部署暂时沿用 blueshift 平台的 ID
+#[cfg(feature = "devnet")]
 declare_id!("22222222222222222222222222222222222222222222");
 
+// 运行本地 test 时使用, 和 Anchor.toml 中的 programs.localnet 一致
+#[cfg(all(
+    feature = "localnet",
+    not(any(feature = "mainnet", feature = "devnet"))
+))]
+declare_id!("Ew2CvRfUbSSUfe5DurrYJm1xXCxhKskHoB7GkL1Gcggj");
+
+// 嵌入部署的程序中, 浏览器和审计者从链上读取联系方式以及源码的地址和版本
+// 作为 CPI 依赖编译时调用方有自己的 security.txt, 因此只有带 entrypoint 的构建才嵌入
+#[cfg(all(feature = "security-txt", not(feature = "no-entrypoint")))]
+solana_security_txt::security_txt! {
+    name: "Blueshift Anchor Escrow",
+    project_url: "https://github.com/BTBMan/blueshift-anchor-escrow",
+    contacts: "link:https://github.com/BTBMan/blueshift-anchor-escrow/security/advisories/new",
+    policy: "https://github.com/BTBMan/blueshift-anchor-escrow/blob/main/SECURITY.md",
+    preferred_languages: "en,zh",
+    source_code: "https://github.com/BTBMan/blueshift-anchor-escrow",
+    source_release: concat!("v", env!("CARGO_PKG_VERSION"))
+}
+
 #[program]
 pub mod blueshift_anchor_escrow {
     use super::*;
 
     #[instruction(discriminator = 0)]
-    pub fn make(ctx: Context<Make>, seed: u64, receive: u64, amount: u64) -> Result<()> {
-        instructions::make::handler(ctx, seed, receive, amount)
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    #[allow(clippy::too_many_arguments)]
+    pub fn make<'info>(
+        ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+        seed: u64,
+        receive: u64,
+        amount: u64,
+        expiry: i64,
+        allowed_taker: Pubkey,
+        taker_allowlist_root: [u8; 32],
+        hashlock: [u8; 32],
+        start_time: i64,
+        reject_freezable: bool,
+        receive_to: Pubkey,
+        refund_delegate: Pubkey,
+        commit_delay_slots: u64,
+        arbiter: Pubkey,
+        reservation_window: i64,
+        alt_payments: Vec<PaymentMint>,
+        recurring: bool,
+        max_refills: u8,
+    ) -> Result<MakeResult> {
+        instructions::make::handler(
+            ctx,
+            MakeArgs {
+                seed,
+                receive,
+                amount,
+                expiry: Some(expiry),
+                allowed_taker: Some(allowed_taker),
+                taker_allowlist_root: Some(taker_allowlist_root),
+                hashlock: Some(hashlock),
+                start_time: Some(start_time),
+                reject_freezable: Some(reject_freezable),
+                receive_to: Some(receive_to),
+                refund_delegate: Some(refund_delegate),
+                commit_delay_slots: Some(commit_delay_slots),
+                arbiter: Some(arbiter),
+                reservation_window: Some(reservation_window),
+                alt_payments,
+                recurring,
+                max_refills,
+                pair_index: false,
+                tag: 0,
+                allow_operators: false,
+                reserved: [0; 29],
+            },
+        )
     }
 
     #[instruction(discriminator = 1)]
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        instructions::take::handler(ctx)
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take<'info>(
+        ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+        max_receive: u64,
+        expected_amount_a: u64,
+        proof: Vec<[u8; 32]>,
+        preimage: Vec<u8>,
+        write_receipt: bool,
+    ) -> Result<TakeResult> {
+        instructions::take::handler(
+            ctx,
+            TakeArgs {
+                max_receive: Some(max_receive),
+                expected_amount_a: Some(expected_amount_a),
+                proof,
+                preimage,
+                write_receipt,
+                tip: 0,
+                reserved: [0; 24],
+            },
+        )
     }
 
     #[instruction(discriminator = 2)]
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        instructions::refund::handler(ctx)
+    pub fn refund<'info>(
+        ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+        force: bool,
+    ) -> Result<()> {
+        instructions::refund::handler(ctx, force)
+    }
+
+    #[instruction(discriminator = 3)]
+    pub fn refund_expired<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundExpired<'info>>,
+    ) -> Result<()> {
+        instructions::refund_expired::handler(ctx)
+    }
+
+    #[instruction(discriminator = 4)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_partial<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakePartial<'info>>,
+        amount_a: u64,
+        max_receive: u64,
+        proof: Vec<[u8; 32]>,
+        preimage: Vec<u8>,
+    ) -> Result<()> {
+        instructions::take_partial::handler(ctx, amount_a, max_receive, proof, preimage)
+    }
+
+    #[instruction(discriminator = 5)]
+    pub fn update_receive(ctx: Context<UpdateReceive>, new_receive: u64) -> Result<()> {
+        instructions::update_receive::handler(ctx, new_receive)
+    }
+
+    #[instruction(discriminator = 6)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn top_up<'info>(
+        ctx: Context<'_, '_, '_, 'info, TopUp<'info>>,
+        additional_amount: u64,
+    ) -> Result<()> {
+        instructions::top_up::handler(ctx, additional_amount)
+    }
+
+    #[instruction(discriminator = 7)]
+    pub fn withdraw_partial<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawPartial<'info>>,
+        amount_a: u64,
+    ) -> Result<()> {
+        instructions::withdraw_partial::handler(ctx, amount_a)
+    }
+
+    #[instruction(discriminator = 8)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn make_dutch<'info>(
+        ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+        seed: u64,
+        start_receive: u64,
+        end_receive: u64,
+        amount: u64,
+        decay_start: i64,
+        decay_end: i64,
+    ) -> Result<()> {
+        instructions::make_dutch::handler(
+            ctx,
+            seed,
+            start_receive,
+            end_receive,
+            amount,
+            decay_start,
+            decay_end,
+        )
+    }
+
+    #[instruction(discriminator = 9)]
+    pub fn initialize_config(ctx: Context<InitializeConfig>, fee_bps: u16) -> Result<()> {
+        instructions::initialize_config::handler(ctx, fee_bps)
+    }
+
+    #[instruction(discriminator = 10)]
+    pub fn set_fee(ctx: Context<SetFee>, fee_bps: u16) -> Result<()> {
+        instructions::set_fee::handler(ctx, fee_bps)
+    }
+
+    #[instruction(discriminator = 11)]
+    pub fn claim_fees(ctx: Context<ClaimFees>, amount: u64) -> Result<()> {
+        instructions::claim_fees::handler(ctx, amount)
+    }
+
+    #[instruction(discriminator = 12)]
+    pub fn pause(ctx: Context<SetPaused>) -> Result<()> {
+        instructions::set_paused::handler(ctx, true)
+    }
+
+    #[instruction(discriminator = 13)]
+    pub fn unpause(ctx: Context<SetPaused>) -> Result<()> {
+        instructions::set_paused::handler(ctx, false)
+    }
+
+    #[instruction(discriminator = 14)]
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::propose_admin::handler(ctx, new_admin)
+    }
+
+    #[instruction(discriminator = 15)]
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin::handler(ctx)
+    }
+
+    #[instruction(discriminator = 16)]
+    pub fn set_referral(ctx: Context<SetReferral>, referral_bps: u16) -> Result<()> {
+        instructions::set_referral::handler(ctx, referral_bps)
+    }
+
+    #[instruction(discriminator = 17)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn propose_counter(ctx: Context<ProposeCounter>, receive: u64) -> Result<()> {
+        instructions::propose_counter::handler(ctx, receive)
+    }
+
+    #[instruction(discriminator = 18)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn accept_counter<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptCounter<'info>>,
+    ) -> Result<()> {
+        instructions::accept_counter::handler(ctx)
+    }
+
+    #[instruction(discriminator = 19)]
+    pub fn cancel_counter(ctx: Context<CancelCounter>) -> Result<()> {
+        instructions::cancel_counter::handler(ctx)
+    }
+
+    #[instruction(discriminator = 20)]
+    #[access_control(ctx.accounts.make.config.check_not_paused())]
+    #[allow(clippy::too_many_arguments)]
+    pub fn make_auto<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeAuto<'info>>,
+        seed: u64,
+        receive: u64,
+        amount: u64,
+        expiry: i64,
+        allowed_taker: Pubkey,
+        taker_allowlist_root: [u8; 32],
+        hashlock: [u8; 32],
+        start_time: i64,
+    ) -> Result<()> {
+        instructions::make_auto::handler(
+            ctx,
+            seed,
+            receive,
+            amount,
+            expiry,
+            allowed_taker,
+            taker_allowlist_root,
+            hashlock,
+            start_time,
+        )
+    }
+
+    #[instruction(discriminator = 21)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn make_for_sol<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeForSol<'info>>,
+        seed: u64,
+        receive_lamports: u64,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::make_for_sol::handler(ctx, seed, receive_lamports, amount)
+    }
+
+    #[instruction(discriminator = 22)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_for_sol<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeForSol<'info>>,
+        max_receive: u64,
+        expected_amount_a: u64,
+    ) -> Result<()> {
+        instructions::take_for_sol::handler(ctx, max_receive, expected_amount_a)
+    }
+
+    #[instruction(discriminator = 23)]
+    pub fn close_receipt(ctx: Context<CloseReceipt>) -> Result<()> {
+        instructions::close_receipt::handler(ctx)
+    }
+
+    #[instruction(discriminator = 24)]
+    pub fn close_registry(ctx: Context<CloseRegistry>) -> Result<()> {
+        instructions::close_registry::handler(ctx)
+    }
+
+    #[instruction(discriminator = 25)]
+    pub fn set_allowed_mints(
+        ctx: Context<SetAllowedMints>,
+        enabled: bool,
+        mints: Vec<Pubkey>,
+    ) -> Result<()> {
+        instructions::set_allowed_mints::handler(ctx, enabled, mints)
+    }
+
+    #[instruction(discriminator = 26)]
+    pub fn add_blocked_mint(ctx: Context<UpdateBlocklist>, mint: Pubkey) -> Result<()> {
+        instructions::update_blocklist::handler(ctx, mint, true)
+    }
+
+    #[instruction(discriminator = 27)]
+    pub fn remove_blocked_mint(ctx: Context<UpdateBlocklist>, mint: Pubkey) -> Result<()> {
+        instructions::update_blocklist::handler(ctx, mint, false)
+    }
+
+    #[instruction(discriminator = 28)]
+    pub fn freeze_escrow(ctx: Context<SetFrozen>) -> Result<()> {
+        instructions::set_frozen::handler(ctx, true)
+    }
+
+    #[instruction(discriminator = 29)]
+    pub fn unfreeze_escrow(ctx: Context<SetFrozen>) -> Result<()> {
+        instructions::set_frozen::handler(ctx, false)
+    }
+
+    #[instruction(discriminator = 30)]
+    pub fn get_escrow(ctx: Context<GetEscrow>) -> Result<EscrowView> {
+        instructions::get_escrow::handler(ctx)
+    }
+
+    #[instruction(discriminator = 31)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn relist<'info>(
+        ctx: Context<'_, '_, '_, 'info, Relist<'info>>,
+        new_seed: u64,
+        new_receive: u64,
+    ) -> Result<()> {
+        instructions::relist::handler(ctx, new_seed, new_receive)
+    }
+
+    #[instruction(discriminator = 32)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn match_escrows<'info>(
+        ctx: Context<'_, '_, '_, 'info, MatchEscrows<'info>>,
+    ) -> Result<()> {
+        instructions::match_escrows::handler(ctx)
+    }
+
+    #[instruction(discriminator = 33)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn make_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeBatch<'info>>,
+        entries: Vec<MakeEntry>,
+    ) -> Result<()> {
+        instructions::make_batch::handler(ctx, entries)
+    }
+
+    #[instruction(discriminator = 34)]
+    pub fn refund_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundBatch<'info>>,
+        count: u8,
+    ) -> Result<()> {
+        instructions::refund_batch::handler(ctx, count)
+    }
+
+    #[instruction(discriminator = 35)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn transfer_maker<'info>(
+        ctx: Context<'_, '_, '_, 'info, TransferMaker<'info>>,
+    ) -> Result<()> {
+        instructions::transfer_maker::handler(ctx)
+    }
+
+    #[instruction(discriminator = 36)]
+    pub fn set_delegate(ctx: Context<SetDelegate>, new_delegate: Pubkey) -> Result<()> {
+        instructions::set_delegate::handler(ctx, new_delegate)
+    }
+
+    #[instruction(discriminator = 37)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_with_sig<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeWithSig<'info>>,
+        max_receive: u64,
+        expiry: i64,
+        nonce: u64,
+    ) -> Result<TakeResult> {
+        instructions::take_with_sig::handler(ctx, max_receive, expiry, nonce)
+    }
+
+    #[instruction(discriminator = 38)]
+    pub fn commit_take(ctx: Context<CommitTake>, hash: [u8; 32]) -> Result<()> {
+        instructions::commit_take::handler(ctx, hash)
+    }
+
+    #[instruction(discriminator = 39)]
+    #[access_control(ctx.accounts.take.config.check_not_paused())]
+    #[allow(clippy::too_many_arguments)]
+    pub fn reveal_take<'info>(
+        ctx: Context<'_, '_, '_, 'info, RevealTake<'info>>,
+        salt: [u8; 32],
+        max_receive: u64,
+        expected_amount_a: u64,
+        proof: Vec<[u8; 32]>,
+        preimage: Vec<u8>,
+        write_receipt: bool,
+    ) -> Result<TakeResult> {
+        instructions::reveal_take::handler(
+            ctx,
+            salt,
+            max_receive,
+            expected_amount_a,
+            proof,
+            preimage,
+            write_receipt,
+        )
+    }
+
+    #[instruction(discriminator = 40)]
+    pub fn close_commit(ctx: Context<CloseCommit>) -> Result<()> {
+        instructions::close_commit::handler(ctx)
+    }
+
+    #[instruction(discriminator = 41)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn lock_take<'info>(
+        ctx: Context<'_, '_, '_, 'info, LockTake<'info>>,
+        max_receive: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        instructions::lock_take::handler(ctx, max_receive, proof)
+    }
+
+    #[instruction(discriminator = 42)]
+    pub fn release_to_taker<'info>(ctx: Context<'_, '_, '_, 'info, Release<'info>>) -> Result<()> {
+        instructions::release::handler(ctx, true)
+    }
+
+    #[instruction(discriminator = 43)]
+    pub fn release_to_maker<'info>(ctx: Context<'_, '_, '_, 'info, Release<'info>>) -> Result<()> {
+        instructions::release::handler(ctx, false)
+    }
+
+    #[instruction(discriminator = 44)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn reserve(ctx: Context<Reserve>, bond_lamports: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        instructions::reserve::handler(ctx, bond_lamports, proof)
+    }
+
+    #[instruction(discriminator = 45)]
+    #[access_control(ctx.accounts.take.config.check_not_paused())]
+    pub fn settle<'info>(
+        ctx: Context<'_, '_, '_, 'info, Settle<'info>>,
+        max_receive: u64,
+        expected_amount_a: u64,
+        proof: Vec<[u8; 32]>,
+        preimage: Vec<u8>,
+        write_receipt: bool,
+    ) -> Result<TakeResult> {
+        instructions::settle::handler(
+            ctx,
+            max_receive,
+            expected_amount_a,
+            proof,
+            preimage,
+            write_receipt,
+        )
+    }
+
+    #[instruction(discriminator = 46)]
+    pub fn expire_reservation(ctx: Context<ExpireReservation>) -> Result<()> {
+        instructions::expire_reservation::handler(ctx)
+    }
+
+    #[instruction(discriminator = 47)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn make_basket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MakeBasket<'info>>,
+        seed: u64,
+        receive: u64,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        instructions::make_basket::handler(ctx, seed, receive, amounts)
+    }
+
+    #[instruction(discriminator = 48)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_basket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TakeBasket<'info>>,
+        max_receive: u64,
+    ) -> Result<()> {
+        instructions::take_basket::handler(ctx, max_receive)
+    }
+
+    #[instruction(discriminator = 49)]
+    pub fn refund_basket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundBasket<'info>>,
+    ) -> Result<()> {
+        instructions::refund_basket::handler(ctx)
+    }
+
+    #[instruction(discriminator = 50)]
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, tiers: Vec<FeeTier>) -> Result<()> {
+        instructions::set_fee_tiers::handler(ctx, tiers)
+    }
+
+    #[instruction(discriminator = 51)]
+    pub fn init_user_stats(ctx: Context<InitUserStats>) -> Result<()> {
+        instructions::init_user_stats::handler(ctx)
+    }
+
+    #[instruction(discriminator = 52)]
+    pub fn set_listing_fee(
+        ctx: Context<SetListingFee>,
+        listing_fee_lamports: u64,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        instructions::set_listing_fee::handler(ctx, listing_fee_lamports, treasury)
+    }
+
+    #[instruction(discriminator = 53)]
+    pub fn add_exempt_wallet(ctx: Context<UpdateExemptList>, wallet: Pubkey) -> Result<()> {
+        instructions::update_exempt_list::handler(ctx, wallet, true)
+    }
+
+    #[instruction(discriminator = 54)]
+    pub fn remove_exempt_wallet(ctx: Context<UpdateExemptList>, wallet: Pubkey) -> Result<()> {
+        instructions::update_exempt_list::handler(ctx, wallet, false)
+    }
+
+    #[instruction(discriminator = 55)]
+    pub fn set_abandon_policy(
+        ctx: Context<SetAbandonPolicy>,
+        abandon_seconds: i64,
+        abandon_cranker_bps: u16,
+    ) -> Result<()> {
+        instructions::set_abandon_policy::handler(ctx, abandon_seconds, abandon_cranker_bps)
+    }
+
+    #[instruction(discriminator = 56)]
+    pub fn sweep_abandoned<'info>(
+        ctx: Context<'_, '_, '_, 'info, SweepAbandoned<'info>>,
+    ) -> Result<()> {
+        instructions::sweep_abandoned::handler(ctx)
+    }
+
+    #[instruction(discriminator = 57)]
+    pub fn recover_vault<'info>(
+        ctx: Context<'_, '_, '_, 'info, RecoverVault<'info>>,
+        seed: u64,
+    ) -> Result<()> {
+        instructions::recover_vault::handler(ctx, seed)
+    }
+
+    #[instruction(discriminator = 58)]
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        instructions::migrate_escrow::handler(ctx)
+    }
+
+    #[instruction(discriminator = 59)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn make_oracle<'info>(
+        ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+        seed: u64,
+        amount: u64,
+        price_feed: Pubkey,
+        quote_exponent: i32,
+        max_staleness: i64,
+    ) -> Result<()> {
+        instructions::make_oracle::handler(
+            ctx,
+            seed,
+            amount,
+            price_feed,
+            quote_exponent,
+            max_staleness,
+        )
+    }
+
+    #[instruction(discriminator = 60)]
+    pub fn set_price_band(
+        ctx: Context<SetPriceBand>,
+        price_feed: Pubkey,
+        quote_exponent: i32,
+        max_staleness: i64,
+        max_deviation_bps: u16,
+    ) -> Result<()> {
+        instructions::set_price_band::handler(
+            ctx,
+            price_feed,
+            quote_exponent,
+            max_staleness,
+            max_deviation_bps,
+        )
+    }
+
+    #[instruction(discriminator = 61)]
+    pub fn set_swap_router(ctx: Context<SetSwapRouter>, swap_router: Pubkey) -> Result<()> {
+        instructions::set_swap_router::handler(ctx, swap_router)
+    }
+
+    #[instruction(discriminator = 62)]
+    #[access_control(ctx.accounts.take.config.check_not_paused())]
+    #[allow(clippy::too_many_arguments)]
+    pub fn take_with_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeWithSwap<'info>>,
+        route_data: Vec<u8>,
+        max_receive: u64,
+        expected_amount_a: u64,
+        proof: Vec<[u8; 32]>,
+        preimage: Vec<u8>,
+        write_receipt: bool,
+    ) -> Result<TakeResult> {
+        instructions::take_with_swap::handler(
+            ctx,
+            route_data,
+            max_receive,
+            expected_amount_a,
+            proof,
+            preimage,
+            write_receipt,
+        )
+    }
+
+    #[instruction(discriminator = 63)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn make_collection<'info>(
+        ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+        seed: u64,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::make_collection::handler(ctx, seed, amount)
+    }
+
+    #[instruction(discriminator = 64)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_collection<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeCollection<'info>>,
+        expected_amount_a: u64,
+    ) -> Result<()> {
+        instructions::take_collection::handler(ctx, expected_amount_a)
+    }
+
+    #[instruction(discriminator = 65)]
+    pub fn set_royalty_enforcement(
+        ctx: Context<SetRoyaltyEnforcement>,
+        enforce_royalties: bool,
+    ) -> Result<()> {
+        instructions::set_royalty_enforcement::handler(ctx, enforce_royalties)
+    }
+
+    #[instruction(discriminator = 66)]
+    #[access_control(ctx.accounts.make.config.check_not_paused())]
+    pub fn make_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeV2<'info>>,
+        args: MakeArgs,
+    ) -> Result<MakeResult> {
+        instructions::make_v2::handler(ctx, args)
+    }
+
+    #[instruction(discriminator = 67)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+        args: TakeArgs,
+    ) -> Result<TakeResult> {
+        instructions::take::handler(ctx, args)
+    }
+
+    #[instruction(discriminator = 68)]
+    pub fn set_fee_on_tips(ctx: Context<SetFeeOnTips>, fee_on_tips: bool) -> Result<()> {
+        instructions::set_fee_on_tips::handler(ctx, fee_on_tips)
+    }
+
+    #[instruction(discriminator = 69)]
+    pub fn set_prefs(
+        ctx: Context<SetPrefs>,
+        expiry_duration: i64,
+        receive_to: Pubkey,
+        reject_freezable: bool,
+    ) -> Result<()> {
+        instructions::set_prefs::handler(ctx, expiry_duration, receive_to, reject_freezable)
+    }
+
+    #[instruction(discriminator = 70)]
+    pub fn close_prefs(ctx: Context<ClosePrefs>) -> Result<()> {
+        instructions::close_prefs::handler(ctx)
+    }
+
+    #[instruction(discriminator = 71)]
+    pub fn cancel<'info>(
+        ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+        reason: u8,
+        retain: bool,
+    ) -> Result<()> {
+        instructions::cancel::handler(ctx, reason, retain)
+    }
+
+    #[instruction(discriminator = 72)]
+    pub fn close_cancelled(ctx: Context<CloseCancelled>) -> Result<()> {
+        instructions::close_cancelled::handler(ctx)
+    }
+
+    #[instruction(discriminator = 73)]
+    pub fn set_direct_only(ctx: Context<SetDirectOnly>, direct_only: bool) -> Result<()> {
+        instructions::set_direct_only::handler(ctx, direct_only)
+    }
+
+    #[instruction(discriminator = 74)]
+    pub fn set_trusted_routers(
+        ctx: Context<SetTrustedRouters>,
+        routers: Vec<Pubkey>,
+    ) -> Result<()> {
+        instructions::set_trusted_routers::handler(ctx, routers)
+    }
+
+    #[instruction(discriminator = 75)]
+    pub fn pause_offer(ctx: Context<SetMakerPaused>) -> Result<()> {
+        instructions::set_maker_paused::handler(ctx, true)
+    }
+
+    #[instruction(discriminator = 76)]
+    pub fn resume_offer(ctx: Context<SetMakerPaused>) -> Result<()> {
+        instructions::set_maker_paused::handler(ctx, false)
+    }
+
+    #[instruction(discriminator = 77)]
+    pub fn set_stream_window(ctx: Context<SetStreamWindow>, stream_window: i64) -> Result<()> {
+        instructions::set_stream_window::handler(ctx, stream_window)
+    }
+
+    #[instruction(discriminator = 78)]
+    #[access_control(ctx.accounts.config.check_not_paused())]
+    pub fn take_streaming<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeStreaming<'info>>,
+        initial_b: u64,
+        max_receive: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        instructions::take_streaming::handler(ctx, initial_b, max_receive, proof)
+    }
+
+    #[instruction(discriminator = 79)]
+    pub fn stream_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeStreaming<'info>>,
+        amount_b: u64,
+    ) -> Result<()> {
+        instructions::stream_payment::handler(ctx, amount_b)
+    }
+
+    #[instruction(discriminator = 80)]
+    pub fn set_operators(ctx: Context<SetOperators>, operators: Vec<Pubkey>) -> Result<()> {
+        instructions::set_operators::handler(ctx, operators)
+    }
+
+    #[instruction(discriminator = 81)]
+    pub fn operator_refund<'info>(
+        ctx: Context<'_, '_, '_, 'info, OperatorRefund<'info>>,
+    ) -> Result<()> {
+        instructions::operator_refund::handler(ctx)
     }
 }
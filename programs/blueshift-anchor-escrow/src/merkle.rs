@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+use solana_sha256_hasher::hashv;
+
+// 叶子和内部节点使用不同的前缀, 防止把内部节点当作叶子来伪造证明(second preimage)
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+// taker 地址对应的叶子哈希
+pub fn leaf_hash(taker: &Pubkey) -> [u8; 32] {
+    hashv(&[LEAF_PREFIX, taker.as_ref()]).to_bytes()
+}
+
+// 两个子节点的父节点哈希, 子节点先排序, 因此证明中不需要记录左右方向
+pub fn node_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+// 验证 leaf 和 proof 能够逐层计算出 root
+pub fn verify(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| node_hash(&node, sibling))
+        == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按层构建默克尔树, 奇数个节点时最后一个节点直接提升到上一层
+    // 返回 root 和每个叶子的证明
+    fn build(takers: &[Pubkey]) -> ([u8; 32], Vec<Vec<[u8; 32]>>) {
+        let mut level: Vec<[u8; 32]> = takers.iter().map(leaf_hash).collect();
+        // 每个叶子在当前层中的位置
+        let mut positions: Vec<usize> = (0..takers.len()).collect();
+        let mut proofs = vec![Vec::new(); takers.len()];
+
+        while level.len() > 1 {
+            for (proof, position) in proofs.iter_mut().zip(positions.iter_mut()) {
+                let sibling = *position ^ 1;
+                if sibling < level.len() {
+                    proof.push(level[sibling]);
+                }
+                *position /= 2;
+            }
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => node_hash(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+
+        (level[0], proofs)
+    }
+
+    fn takers(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn single_leaf_is_the_root() {
+        let takers = takers(1);
+        let (root, proofs) = build(&takers);
+
+        assert_eq!(root, leaf_hash(&takers[0]));
+        assert!(proofs[0].is_empty());
+        assert!(verify(&proofs[0], &root, leaf_hash(&takers[0])));
+    }
+
+    #[test]
+    fn verifies_every_leaf_for_odd_counts() {
+        for count in [3, 5, 7, 9, 301] {
+            let takers = takers(count);
+            let (root, proofs) = build(&takers);
+
+            for (taker, proof) in takers.iter().zip(&proofs) {
+                assert!(verify(proof, &root, leaf_hash(taker)), "count {count}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_an_unlisted_taker() {
+        let takers = takers(5);
+        let (root, proofs) = build(&takers);
+        let outsider = Pubkey::new_unique();
+
+        for proof in &proofs {
+            assert!(!verify(proof, &root, leaf_hash(&outsider)));
+        }
+    }
+
+    #[test]
+    fn rejects_a_proof_for_another_leaf() {
+        let takers = takers(4);
+        let (root, proofs) = build(&takers);
+
+        assert!(!verify(&proofs[0], &root, leaf_hash(&takers[1])));
+    }
+
+    #[test]
+    fn rejects_an_internal_node_posing_as_a_taker() {
+        let takers = takers(4);
+        let (root, proofs) = build(&takers);
+
+        // 第一层的父节点配合剩下的证明可以算出 root
+        let internal = node_hash(&leaf_hash(&takers[0]), &leaf_hash(&takers[1]));
+        assert!(verify(&proofs[0][1..], &root, internal));
+
+        // 但把它当作 taker 地址时会先经过带前缀的叶子哈希, 无法通过验证
+        let fake = Pubkey::new_from_array(internal);
+        assert!(!verify(&proofs[0][1..], &root, leaf_hash(&fake)));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{errors::EscrowError, events::RoyaltyPayment};
+use anchor_lang::prelude::*;
+
+// collection 托管读取 Metaplex Token Metadata 程序的 Metadata 账户, 不依赖 mpl-token-metadata
+// 账户地址是 [METADATA_SEED, TOKEN_METADATA_ID, mint] 在 Token Metadata 程序下派生的 PDA, 只有 owner 是它时才读取
+pub const TOKEN_METADATA_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+pub const METADATA_SEED: &[u8] = b"metadata";
+
+// Metadata 账户的第一个字节是 Key::MetadataV1
+const KEY_METADATA_V1: u8 = 4;
+// Creator 的布局: address(32), verified(bool), share(u8)
+const CREATOR_LEN: usize = 32 + 1 + 1;
+// Metadata 最多记录 5 个 creator
+pub const MAX_CREATORS: usize = 5;
+
+// Metadata 的 token_standard 中 ProgrammableNonFungible 的取值
+pub const TOKEN_STANDARD_PROGRAMMABLE: u8 = 4;
+
+// Metadata 中的 creator, share 是版税中分给它的百分比
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+// take_collection, pNFT 转账和版税用到的 Metadata 字段
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NftMetadata {
+    pub mint: Pubkey,
+    // 二级市场成交时版税占成交价格的比例
+    pub seller_fee_basis_points: u16,
+    pub creators: Vec<Creator>,
+    // 较早创建的 NFT 没有记录 token_standard
+    pub token_standard: Option<u8>,
+    // 经过 collection authority 验证的 collection, 没有 collection 或者未验证时为 None
+    pub verified_collection: Option<Pubkey>,
+}
+
+// 按 borsh 的布局依次读取字段, 数据不够时返回 InvalidNftMetadata
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        require_gte!(self.data.len(), len, EscrowError::InvalidNftMetadata);
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey> {
+        Ok(Pubkey::new_from_array(self.take(32)?.try_into().unwrap()))
+    }
+
+    fn skip_string(&mut self) -> Result<()> {
+        let len = self.u32()? as usize;
+        self.take(len).map(|_| ())
+    }
+
+    // Option 的标记字节; 在这些字段加入之前创建的账户数据提前结束, 按 None 处理
+    fn option(&mut self) -> Result<bool> {
+        if self.data.is_empty() {
+            return Ok(false);
+        }
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => err!(EscrowError::InvalidNftMetadata),
+        }
+    }
+}
+
+impl NftMetadata {
+    // 从 Metadata 账户读取 mint 和 verified collection, 地址由调用方的 seeds 约束检查
+    pub fn load(info: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(
+            *info.owner,
+            TOKEN_METADATA_ID,
+            EscrowError::InvalidNftMetadata
+        );
+        let data = info.try_borrow_data()?;
+        Self::parse(&data)
+    }
+
+    // Metadata 的布局: key, update_authority, mint, data(name, symbol, uri, seller_fee_basis_points, creators),
+    // primary_sale_happened, is_mutable, edition_nonce, token_standard, collection, ...; collection 之后的字段不读取
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data };
+        require_eq!(
+            reader.u8()?,
+            KEY_METADATA_V1,
+            EscrowError::InvalidNftMetadata
+        );
+        reader.take(32)?;
+        let mint = reader.pubkey()?;
+
+        for _ in 0..3 {
+            reader.skip_string()?;
+        }
+        let seller_fee_basis_points = reader.u16()?;
+        let mut creators = Vec::new();
+        if reader.option()? {
+            let len = reader.u32()? as usize;
+            require_gte!(MAX_CREATORS, len, EscrowError::InvalidNftMetadata);
+            for _ in 0..len {
+                let creator = reader.take(CREATOR_LEN)?;
+                creators.push(Creator {
+                    address: Pubkey::new_from_array(creator[..32].try_into().unwrap()),
+                    verified: creator[32] == 1,
+                    share: creator[33],
+                });
+            }
+        }
+        reader.take(2)?;
+        // edition_nonce 和 token_standard 都是 Option<u8>
+        if reader.option()? {
+            reader.u8()?;
+        }
+        let token_standard = if reader.option()? {
+            Some(reader.u8()?)
+        } else {
+            None
+        };
+
+        let verified_collection = if reader.option()? {
+            let verified = reader.u8()? == 1;
+            let key = reader.pubkey()?;
+            verified.then_some(key)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            mint,
+            seller_fee_basis_points,
+            creators,
+            token_standard,
+            verified_collection,
+        })
+    }
+
+    pub fn is_programmable(&self) -> bool {
+        self.token_standard == Some(TOKEN_STANDARD_PROGRAMMABLE)
+    }
+
+    // 成交价格为 amount 时支付给每个 verified creator 的版税, 按 metadata 中的顺序
+    // 版税按 seller_fee_basis_points 计算, 在 verified creator 之间按 share 的比例分配, 向下取整的余数给第一个
+    // 未验证的 creator 任何人都可以填写, 不分配版税; 没有 share 大于 0 的 verified creator 时不支付版税
+    pub fn royalties(&self, amount: u64) -> Vec<RoyaltyPayment> {
+        let creators: Vec<&Creator> = self
+            .creators
+            .iter()
+            .filter(|creator| creator.verified && creator.share > 0)
+            .collect();
+        let shares: u128 = creators.iter().map(|creator| creator.share as u128).sum();
+        if shares == 0 {
+            return Vec::new();
+        }
+
+        // seller_fee_basis_points 不超过 10000, royalty 不会超过 amount
+        let basis_points = self.seller_fee_basis_points.min(10_000) as u128;
+        let royalty = (amount as u128 * basis_points / 10_000) as u64;
+        let mut payments: Vec<RoyaltyPayment> = creators
+            .iter()
+            .map(|creator| RoyaltyPayment {
+                creator: creator.address,
+                amount: (royalty as u128 * creator.share as u128 / shares) as u64,
+            })
+            .collect();
+        let paid: u64 = payments.iter().map(|payment| payment.amount).sum();
+        payments[0].amount += royalty - paid;
+        payments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按 Metadata 的布局构造账户数据, 字符串和 Token Metadata 程序一样填充到固定长度
+    fn metadata(mint: Pubkey, token_standard: u8, collection: Option<(bool, Pubkey)>) -> Vec<u8> {
+        let mut data = vec![KEY_METADATA_V1];
+        data.extend_from_slice(&[7; 32]);
+        data.extend_from_slice(mint.as_ref());
+        for len in [32u32, 10, 200] {
+            data.extend_from_slice(&len.to_le_bytes());
+            data.resize(data.len() + len as usize, 0);
+        }
+        data.extend_from_slice(&500u16.to_le_bytes());
+        // 两个 creator
+        data.push(1);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.resize(data.len() + 2 * CREATOR_LEN, 1);
+        data.extend_from_slice(&[1, 1]);
+        // edition_nonce 为 Some(255)
+        data.extend_from_slice(&[1, 255, 1, token_standard]);
+        match collection {
+            Some((verified, key)) => {
+                data.push(1);
+                data.push(verified as u8);
+                data.extend_from_slice(key.as_ref());
+            }
+            None => data.push(0),
+        }
+        // uses, collection_details 和 programmable_config 都为 None, 之后是账户剩余的空间
+        data.resize(data.len() + 64, 0);
+        data
+    }
+
+    #[test]
+    fn reads_verified_collection() {
+        let (mint, collection) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(
+            NftMetadata::parse(&metadata(mint, 0, Some((true, collection)))).unwrap(),
+            NftMetadata {
+                mint,
+                seller_fee_basis_points: 500,
+                creators: vec![
+                    Creator {
+                        address: Pubkey::new_from_array([1; 32]),
+                        verified: true,
+                        share: 1,
+                    };
+                    2
+                ],
+                token_standard: Some(0),
+                verified_collection: Some(collection),
+            }
+        );
+
+        // 未验证的 collection 和没有 collection 都不算
+        for collection in [Some((false, collection)), None] {
+            let parsed = NftMetadata::parse(&metadata(mint, 0, collection)).unwrap();
+            assert_eq!(parsed.verified_collection, None);
+        }
+    }
+
+    #[test]
+    fn reads_token_standard() {
+        let mint = Pubkey::new_unique();
+        let nft = NftMetadata::parse(&metadata(mint, 0, None)).unwrap();
+        assert!(!nft.is_programmable());
+
+        let pnft = NftMetadata::parse(&metadata(mint, TOKEN_STANDARD_PROGRAMMABLE, None)).unwrap();
+        assert_eq!(pnft.token_standard, Some(TOKEN_STANDARD_PROGRAMMABLE));
+        assert!(pnft.is_programmable());
+    }
+
+    #[test]
+    fn royalties_follow_verified_shares() {
+        let (first, second, stranger) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let creator = |address, verified, share| Creator {
+            address,
+            verified,
+            share,
+        };
+        let mut nft = NftMetadata {
+            mint: Pubkey::new_unique(),
+            seller_fee_basis_points: 500,
+            creators: vec![creator(first, true, 60), creator(second, true, 40)],
+            token_standard: None,
+            verified_collection: None,
+        };
+
+        // 1020 的 5% 是 51, 按 60/40 分别是 30.6 和 20.4, 余数给第一个 creator
+        let payment = |creator, amount| RoyaltyPayment { creator, amount };
+        assert_eq!(
+            nft.royalties(1_020),
+            vec![payment(first, 31), payment(second, 20)]
+        );
+
+        // 未验证的 creator 不分配版税, 它的 share 归 verified creator
+        nft.creators[1].verified = false;
+        nft.creators.push(creator(stranger, false, 0));
+        assert_eq!(nft.royalties(1_020), vec![payment(first, 51)]);
+
+        nft.creators[0].verified = false;
+        assert!(nft.royalties(1_020).is_empty());
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        let data = metadata(Pubkey::new_unique(), 0, Some((true, Pubkey::new_unique())));
+
+        // MasterEdition 等其他账户和被截断的数据都不能读取
+        let mut edition = data.clone();
+        edition[0] = 6;
+        assert_eq!(
+            NftMetadata::parse(&edition).unwrap_err(),
+            EscrowError::InvalidNftMetadata.into()
+        );
+        assert_eq!(
+            NftMetadata::parse(&data[..100]).unwrap_err(),
+            EscrowError::InvalidNftMetadata.into()
+        );
+    }
+}
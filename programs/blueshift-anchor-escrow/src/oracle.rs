@@ -0,0 +1,196 @@
+use crate::errors::EscrowError;
+use anchor_lang::prelude::*;
+
+// 按 Pyth 价格定价的托管读取 pyth-solana-receiver 的 PriceUpdateV2 账户, 不依赖 Pyth 的 SDK
+// 账户由 receiver 程序写入, 只有 owner 是它并且 discriminator 正确时才读取
+pub const PYTH_RECEIVER_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+// sha256("account:PriceUpdateV2") 的前 8 个字节
+pub const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+// PriceUpdateV2 的布局: discriminator, write_authority(32), verification_level(Full 为 1 字节的 1, Partial 为 0 加 1 字节的签名数)
+// 之后是 PriceFeedMessage: feed_id(32), price(i64), conf(u64), exponent(i32), publish_time(i64), ...
+const WRITE_AUTHORITY_END: usize = 8 + 32;
+const VERIFICATION_FULL: u8 = 1;
+// 从 feed_id 开始到 publish_time 结束的长度
+const MESSAGE_LEN: usize = 32 + 8 + 8 + 4 + 8;
+
+// 成交时用到的价格字段, 价格为 price * 10^exponent, conf 是同样单位的置信区间
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    // 从 PriceUpdateV2 账户读取价格, 只接受经过全部 guardian 签名验证(VerificationLevel::Full)的价格
+    pub fn load(info: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*info.owner, PYTH_RECEIVER_ID, EscrowError::InvalidPriceFeed);
+        let data = info.try_borrow_data()?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() > WRITE_AUTHORITY_END + 1 + MESSAGE_LEN
+                && data[..8] == PRICE_UPDATE_V2_DISCRIMINATOR
+                && data[WRITE_AUTHORITY_END] == VERIFICATION_FULL,
+            EscrowError::InvalidPriceFeed
+        );
+
+        // feed_id 之后依次是 price, conf, exponent 和 publish_time
+        let message = &data[WRITE_AUTHORITY_END + 1 + 32..];
+        let field = |start: usize, len: usize| &message[start..start + len];
+        Ok(Self {
+            price: i64::from_le_bytes(field(0, 8).try_into().unwrap()),
+            conf: u64::from_le_bytes(field(8, 8).try_into().unwrap()),
+            exponent: i32::from_le_bytes(field(16, 4).try_into().unwrap()),
+            publish_time: i64::from_le_bytes(field(20, 8).try_into().unwrap()),
+        })
+    }
+
+    // 价格必须在 max_staleness 秒内发布, 为正数, 并且置信区间不超过价格的 max_conf_bps
+    // exponent 必须和 maker 创建托管时记录的 quote_exponent 一致, 防止 feed 的精度改变后按错误的单位成交
+    pub fn check(
+        &self,
+        now: i64,
+        max_staleness: i64,
+        quote_exponent: i32,
+        max_conf_bps: u64,
+    ) -> Result<()> {
+        require!(
+            self.price > 0 && self.exponent == quote_exponent,
+            EscrowError::InvalidPriceFeed
+        );
+        require_gte!(
+            max_staleness,
+            now.saturating_sub(self.publish_time),
+            EscrowError::StalePrice
+        );
+        require_gte!(
+            (self.price as u128) * max_conf_bps as u128,
+            (self.conf as u128) * 10_000,
+            EscrowError::PriceConfidenceTooWide
+        );
+
+        Ok(())
+    }
+
+    // amount_a 个 token A(最小单位)按这个价格折算的 token B 数量(最小单位), 向上取整, 舍入误差总是对 maker 有利
+    // 价格是 1 个完整的 token A 值多少个完整的 token B: amount_b = amount_a * price * 10^(exponent + decimals_b - decimals_a)
+    pub fn quote(&self, amount_a: u64, decimals_a: u8, decimals_b: u8) -> Result<u64> {
+        let scale = self.exponent as i64 + decimals_b as i64 - decimals_a as i64;
+        let power = |exp: i64| {
+            u32::try_from(exp)
+                .ok()
+                .and_then(|exp| 10u128.checked_pow(exp))
+                .ok_or(EscrowError::MathOverflow)
+        };
+        let value = (amount_a as u128)
+            .checked_mul(self.price as u128)
+            .ok_or(EscrowError::MathOverflow)?;
+        let amount_b = if scale >= 0 {
+            value
+                .checked_mul(power(scale)?)
+                .ok_or(EscrowError::MathOverflow)?
+        } else {
+            value.div_ceil(power(-scale)?)
+        };
+
+        u64::try_from(amount_b).map_err(|_| EscrowError::MathOverflow.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按 PriceUpdateV2 的布局构造账户数据
+    fn price_update(price: i64, conf: u64, exponent: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[7; 32]);
+        data.push(VERIFICATION_FULL);
+        data.extend_from_slice(&[9; 32]);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&exponent.to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        // prev_publish_time, ema_price, ema_conf 和 posted_slot
+        data.extend_from_slice(&[0; 32]);
+        data
+    }
+
+    #[test]
+    fn parses_price_update_v2() {
+        let data = price_update(15_012_345_678, 1_000, -8, 1_700_000_000);
+        assert_eq!(
+            OraclePrice::parse(&data).unwrap(),
+            OraclePrice {
+                price: 15_012_345_678,
+                conf: 1_000,
+                exponent: -8,
+                publish_time: 1_700_000_000,
+            }
+        );
+
+        // Partial 验证的价格和其他账户类型都不能读取
+        let mut partial = data.clone();
+        partial[WRITE_AUTHORITY_END] = 0;
+        assert!(OraclePrice::parse(&partial).is_err());
+        let mut other = data;
+        other[0] ^= 1;
+        assert!(OraclePrice::parse(&other).is_err());
+    }
+
+    #[test]
+    fn quotes_across_decimals() {
+        // 150.12345678 USD, 9 位小数的 token A 换 6 位小数的 USDC
+        let price = OraclePrice {
+            price: 15_012_345_678,
+            conf: 0,
+            exponent: -8,
+            publish_time: 0,
+        };
+        assert_eq!(price.quote(1_000_000_000, 9, 6).unwrap(), 150_123_457);
+        assert_eq!(price.quote(2, 9, 6).unwrap(), 1);
+        // 指数为正时直接放大
+        let whole = OraclePrice {
+            exponent: 2,
+            price: 3,
+            ..price
+        };
+        assert_eq!(whole.quote(5, 0, 0).unwrap(), 1_500);
+        assert!(whole.quote(u64::MAX, 0, 30).is_err());
+    }
+
+    #[test]
+    fn rejects_stale_wide_or_mismatched_prices() {
+        let price = OraclePrice {
+            price: 10_000,
+            conf: 100,
+            exponent: -4,
+            publish_time: 1_000,
+        };
+        price.check(1_060, 60, -4, 100).unwrap();
+
+        assert_eq!(
+            price.check(1_061, 60, -4, 100).unwrap_err(),
+            EscrowError::StalePrice.into()
+        );
+        assert_eq!(
+            price.check(1_000, 60, -4, 99).unwrap_err(),
+            EscrowError::PriceConfidenceTooWide.into()
+        );
+        assert_eq!(
+            price.check(1_000, 60, -6, 100).unwrap_err(),
+            EscrowError::InvalidPriceFeed.into()
+        );
+        let negative = OraclePrice { price: -1, ..price };
+        assert_eq!(
+            negative.check(1_000, 60, -4, 100).unwrap_err(),
+            EscrowError::InvalidPriceFeed.into()
+        );
+    }
+}
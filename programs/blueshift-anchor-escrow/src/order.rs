@@ -0,0 +1,183 @@
+use crate::errors::EscrowError;
+use anchor_lang::{prelude::*, solana_program::instruction::Instruction};
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
+
+// taker 签名的订单消息: 程序 ID, 托管地址, max_receive, expiry 和 nonce, 整数都使用小端字节序
+// 消息以程序 ID 开头, 同一个签名不能用在其他程序或其他部署上
+pub const ORDER_MESSAGE_LEN: usize = 32 + 32 + 8 + 8 + 8;
+
+// ed25519 程序的指令数据: 签名个数(u8), 填充(u8), 每个签名 14 字节的偏移量, 之后是偏移量指向的数据
+const OFFSETS_START: usize = 2;
+// 偏移量中的指令索引为 u16::MAX 时, 数据就在 ed25519 指令自己的数据中
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+pub fn order_message(
+    escrow: &Pubkey,
+    max_receive: u64,
+    expiry: i64,
+    nonce: u64,
+) -> [u8; ORDER_MESSAGE_LEN] {
+    let mut message = [0; ORDER_MESSAGE_LEN];
+    message[..32].copy_from_slice(crate::ID.as_ref());
+    message[32..64].copy_from_slice(escrow.as_ref());
+    message[64..72].copy_from_slice(&max_receive.to_le_bytes());
+    message[72..80].copy_from_slice(&expiry.to_le_bytes());
+    message[80..].copy_from_slice(&nonce.to_le_bytes());
+    message
+}
+
+// 要求当前指令的前一条指令是 ed25519 程序对 message 的验证, 并且签名者是 signer
+// 签名本身由 ed25519 程序在交易执行前验证, 验证失败时整笔交易不会执行, 这里只检查它验证的是什么
+pub fn check_signed(instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    require_gt!(current, 0, EscrowError::InvalidOrderSignature);
+
+    let verify = load_instruction_at_checked(current - 1, instructions)?;
+    require!(
+        is_signed_by(&verify, signer, message),
+        EscrowError::InvalidOrderSignature
+    );
+
+    Ok(())
+}
+
+// ed25519 指令只验证了一个签名, 公钥是 signer, 消息是 message
+fn is_signed_by(verify: &Instruction, signer: &Pubkey, message: &[u8]) -> bool {
+    if verify.program_id != solana_sdk_ids::ed25519_program::ID {
+        return false;
+    }
+
+    signed_data(&verify.data)
+        .is_some_and(|(pubkey, signed)| pubkey == signer.as_ref() && signed == message)
+}
+
+// 从 ed25519 指令数据中取出公钥和被签名的消息
+// 偏移量可以指向交易中的其他指令, 那样 ed25519 程序验证的数据和这里读到的不同, 因此要求都在指令自己的数据中
+fn signed_data(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if data.first() != Some(&1) {
+        return None;
+    }
+
+    let offset = |index: usize| {
+        let at = OFFSETS_START + index * 2;
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    // 偏移量依次是 signature, public_key 和 message, 后两个是 (偏移, 指令索引) 或 (偏移, 长度, 指令索引)
+    let signature_instruction = offset(1)?;
+    let public_key_offset = offset(2)? as usize;
+    let public_key_instruction = offset(3)?;
+    let message_offset = offset(4)? as usize;
+    let message_len = offset(5)? as usize;
+    let message_instruction = offset(6)?;
+
+    if [
+        signature_instruction,
+        public_key_instruction,
+        message_instruction,
+    ]
+    .iter()
+    .any(|index| *index != THIS_INSTRUCTION)
+    {
+        return None;
+    }
+
+    Some((
+        data.get(public_key_offset..public_key_offset + 32)?,
+        data.get(message_offset..message_offset + message_len)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 和 solana 的 new_ed25519_instruction 相同的布局: 偏移量之后依次是公钥, 签名和消息
+    // 签名不参与这里的检查, 用 0 填充
+    fn verify_ix(signer: &Pubkey, message: &[u8], instruction_index: u16) -> Instruction {
+        let public_key_offset: u16 = 16;
+        let signature_offset = public_key_offset + 32;
+        let message_offset = signature_offset + 64;
+
+        let mut data = vec![1, 0];
+        for value in [
+            signature_offset,
+            instruction_index,
+            public_key_offset,
+            instruction_index,
+            message_offset,
+            message.len() as u16,
+            instruction_index,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(message);
+
+        Instruction {
+            program_id: solana_sdk_ids::ed25519_program::ID,
+            accounts: vec![],
+            data,
+        }
+    }
+
+    #[test]
+    fn message_binds_every_order_field() {
+        let escrow = Pubkey::new_unique();
+        let message = order_message(&escrow, 500, 1_700_000_000, 7);
+
+        assert_eq!(&message[..32], crate::ID.as_ref());
+        assert_eq!(&message[32..64], escrow.as_ref());
+        for other in [
+            order_message(&Pubkey::new_unique(), 500, 1_700_000_000, 7),
+            order_message(&escrow, 501, 1_700_000_000, 7),
+            order_message(&escrow, 500, 1_700_000_001, 7),
+            order_message(&escrow, 500, 1_700_000_000, 8),
+        ] {
+            assert_ne!(message, other);
+        }
+    }
+
+    #[test]
+    fn accepts_only_the_signer_and_message_being_verified() {
+        let taker = Pubkey::new_unique();
+        let message = order_message(&Pubkey::new_unique(), 500, 0, 1);
+        let verify = verify_ix(&taker, &message, THIS_INSTRUCTION);
+
+        assert!(is_signed_by(&verify, &taker, &message));
+        assert!(!is_signed_by(&verify, &Pubkey::new_unique(), &message));
+        // 同一个签名不能用于另一个托管
+        let other = order_message(&Pubkey::new_unique(), 500, 0, 1);
+        assert!(!is_signed_by(&verify, &taker, &other));
+    }
+
+    #[test]
+    fn rejects_malformed_verify_instructions() {
+        let taker = Pubkey::new_unique();
+        let message = order_message(&Pubkey::new_unique(), 500, 0, 1);
+
+        // 偏移量指向其他指令
+        assert!(!is_signed_by(
+            &verify_ix(&taker, &message, 0),
+            &taker,
+            &message
+        ));
+
+        // 不是 ed25519 程序的指令
+        let mut not_ed25519 = verify_ix(&taker, &message, THIS_INSTRUCTION);
+        not_ed25519.program_id = Pubkey::new_unique();
+        assert!(!is_signed_by(&not_ed25519, &taker, &message));
+
+        // 多个签名时无法确定哪一个是 taker 的
+        let mut two_signatures = verify_ix(&taker, &message, THIS_INSTRUCTION);
+        two_signatures.data[0] = 2;
+        assert!(!is_signed_by(&two_signatures, &taker, &message));
+
+        // 数据被截断
+        let mut truncated = verify_ix(&taker, &message, THIS_INSTRUCTION);
+        truncated.data.truncate(truncated.data.len() - 1);
+        assert!(!is_signed_by(&truncated, &taker, &message));
+        assert!(signed_data(&[1, 0, 0]).is_none());
+    }
+}
@@ -0,0 +1,210 @@
+use anchor_lang::prelude::*;
+
+// 程序中所有 PDA 的种子, 指令的 seeds 约束, PDA 签名和链下派生地址都使用这里的常量
+pub const ESCROW_SEED: &[u8] = b"escrow";
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const FEE_AUTHORITY_SEED: &[u8] = b"fee_authority";
+// maker 的自动 seed 计数器和还价账户共用这个前缀, 后面的种子个数不同, 地址不会冲突
+pub const COUNTER_SEED: &[u8] = b"counter";
+pub const RECEIPT_SEED: &[u8] = b"receipt";
+pub const STATS_SEED: &[u8] = b"stats";
+pub const REGISTRY_SEED: &[u8] = b"registry";
+pub const MINT_ALLOWLIST_SEED: &[u8] = b"mint_allowlist";
+pub const MINT_BLOCKLIST_SEED: &[u8] = b"mint_blocklist";
+pub const ORDER_SEED: &[u8] = b"order";
+pub const TAKE_DELEGATE_SEED: &[u8] = b"take_delegate";
+pub const COMMIT_SEED: &[u8] = b"commit";
+pub const BASKET_SEED: &[u8] = b"basket";
+pub const USER_STATS_SEED: &[u8] = b"user_stats";
+pub const EXEMPT_LIST_SEED: &[u8] = b"exempt_list";
+pub const PREFS_SEED: &[u8] = b"prefs";
+pub const PAIR_INDEX_SEED: &[u8] = b"pair_index";
+// 由运营方程序而不是本程序派生, 运营方程序用它通过 invoke_signed 调用 operator_refund
+pub const OPERATOR_SEED: &[u8] = b"operator";
+
+// 以下函数链上链下都可以使用, 地址都由本程序的 ID 派生
+
+// 托管 PDA, seed 使用小端字节序
+pub fn find_escrow_address(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ESCROW_SEED, maker.as_ref(), &seed.to_le_bytes()],
+        &crate::ID,
+    )
+}
+
+// 全局唯一的协议配置账户
+pub fn find_config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], &crate::ID)
+}
+
+// 所有手续费 ATA 的 authority
+pub fn find_fee_authority_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_AUTHORITY_SEED], &crate::ID)
+}
+
+// 全局唯一的统计账户, 第一次 make, take 或 refund 时创建
+pub fn find_stats_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATS_SEED], &crate::ID)
+}
+
+// maker 的托管索引, 第一次 make 时创建
+pub fn find_registry_address(maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED, maker.as_ref()], &crate::ID)
+}
+
+// 全局唯一的 mint 白名单, 第一次 set_allowed_mints 时创建
+pub fn find_mint_allowlist_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MINT_ALLOWLIST_SEED], &crate::ID)
+}
+
+// 全局唯一的 mint 黑名单, 第一次 add_blocked_mint 时创建
+pub fn find_mint_blocklist_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MINT_BLOCKLIST_SEED], &crate::ID)
+}
+
+// make_auto 使用的 maker 计数器
+pub fn find_maker_counter_address(maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUNTER_SEED, maker.as_ref()], &crate::ID)
+}
+
+// taker 对托管提出的还价
+pub fn find_counter_offer_address(escrow: &Pubkey, taker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUNTER_SEED, escrow.as_ref(), taker.as_ref()], &crate::ID)
+}
+
+// take 时可选写入的成交记录, 每个托管地址同一时间只有一个
+pub fn find_receipt_address(escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_SEED, escrow.as_ref()], &crate::ID)
+}
+
+// take_with_sig 使用过的签名订单, 每个 taker 的 nonce 只能使用一次
+pub fn find_order_address(taker: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ORDER_SEED, taker.as_ref(), &nonce.to_le_bytes()],
+        &crate::ID,
+    )
+}
+
+// taker 通过 SPL approve 授权转出 token B 的 delegate, take_with_sig 用它的签名转账
+pub fn find_take_delegate_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TAKE_DELEGATE_SEED], &crate::ID)
+}
+
+// commit_take 记录的承诺, hash 是 TakeCommit::hash 的结果
+pub fn find_commit_address(taker: &Pubkey, hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COMMIT_SEED, taker.as_ref(), hash], &crate::ID)
+}
+
+// make_basket 创建的一篮子托管, 和 escrow 使用相同的 seed 编码
+pub fn find_basket_address(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[BASKET_SEED, maker.as_ref(), &seed.to_le_bytes()],
+        &crate::ID,
+    )
+}
+
+// 全局唯一的挂单费豁免列表, 第一次 add_exempt_wallet 时创建
+pub fn find_exempt_list_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EXEMPT_LIST_SEED], &crate::ID)
+}
+
+// 钱包的累计成交统计, 第一次作为 taker 成交或调用 init_user_stats 时创建
+pub fn find_user_stats_address(wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[USER_STATS_SEED, wallet.as_ref()], &crate::ID)
+}
+
+// maker 的默认挂单参数, 由 set_prefs 创建
+pub fn find_prefs_address(maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREFS_SEED, maker.as_ref()], &crate::ID)
+}
+
+// (mint_a, mint_b) 交易对的索引, 第一次以 pair_index 模式 make_v2 时创建
+pub fn find_pair_index_address(mint_a: &Pubkey, mint_b: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PAIR_INDEX_SEED, mint_a.as_ref(), mint_b.as_ref()],
+        &crate::ID,
+    )
+}
+
+// 运营方程序的签名 PDA, 地址由 operator_program 派生, 见 Config::check_operator
+pub fn find_operator_authority_address(operator_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OPERATOR_SEED], operator_program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escrow_address_uses_little_endian_seed() {
+        let maker = Pubkey::new_unique();
+
+        let expected = Pubkey::find_program_address(
+            &[b"escrow", maker.as_ref(), &[8, 7, 6, 5, 4, 3, 2, 1]],
+            &crate::ID,
+        );
+        assert_eq!(find_escrow_address(&maker, 0x0102_0304_0506_0708), expected);
+    }
+
+    #[test]
+    fn global_addresses_match_raw_seeds() {
+        assert_eq!(
+            find_config_address(),
+            Pubkey::find_program_address(&[b"config"], &crate::ID)
+        );
+        assert_eq!(
+            find_fee_authority_address(),
+            Pubkey::find_program_address(&[b"fee_authority"], &crate::ID)
+        );
+
+        assert_eq!(
+            find_stats_address(),
+            Pubkey::find_program_address(&[b"stats"], &crate::ID)
+        );
+        assert_eq!(
+            find_mint_allowlist_address(),
+            Pubkey::find_program_address(&[b"mint_allowlist"], &crate::ID)
+        );
+        assert_eq!(
+            find_mint_blocklist_address(),
+            Pubkey::find_program_address(&[b"mint_blocklist"], &crate::ID)
+        );
+
+        let escrow = Pubkey::new_unique();
+        assert_eq!(
+            find_receipt_address(&escrow),
+            Pubkey::find_program_address(&[b"receipt", escrow.as_ref()], &crate::ID)
+        );
+
+        let maker = Pubkey::new_unique();
+        assert_eq!(
+            find_registry_address(&maker),
+            Pubkey::find_program_address(&[b"registry", maker.as_ref()], &crate::ID)
+        );
+    }
+
+    #[test]
+    fn counter_addresses_do_not_collide() {
+        let (maker, escrow, taker) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        assert_eq!(
+            find_maker_counter_address(&maker),
+            Pubkey::find_program_address(&[b"counter", maker.as_ref()], &crate::ID)
+        );
+        assert_eq!(
+            find_counter_offer_address(&escrow, &taker),
+            Pubkey::find_program_address(
+                &[b"counter", escrow.as_ref(), taker.as_ref()],
+                &crate::ID
+            )
+        );
+        assert_ne!(
+            find_maker_counter_address(&maker).0,
+            find_counter_offer_address(&maker, &taker).0
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    errors::EscrowError,
+    metadata::{NftMetadata, TOKEN_METADATA_ID},
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke_signed,
+    },
+};
+
+// Programmable NFT 的 token 账户始终被 Token Metadata 程序冻结, 不能用 transfer_checked 转账, 不依赖 mpl-token-metadata
+// 只能通过 Token Metadata 的 Transfer 指令(TransferV1): 它检查 token record 和 rule set, 解冻来源账户, 转账之后冻结目标账户,
+// 目标账户的 token record 不存在时由 payer 支付租金创建, 因此 make 时 vault 不需要额外的 delegate 或 record 设置
+// make, take 和 refund 的 remaining_accounts 以 Token Metadata 程序开头时按 pNFT 转账 token A, 前 PNFT_ACCOUNTS 个账户依次是:
+// token_metadata_program, metadata(mut), edition, token_record(mut), destination_token_record(mut),
+// sysvar_instructions, authorization_rules_program, authorization_rules
+// 没有 rule set 的 pNFT 用 Token Metadata 程序代替最后两个账户; 之后的账户仍然是 transfer hook 的额外账户
+// 其他成交和取款指令只使用 transfer_checked, pNFT 的 vault 被冻结, 它们返回 VaultFrozen 或者 token 程序的错误
+pub const PNFT_ACCOUNTS: usize = 8;
+
+// TransferV1 的指令数据: Transfer 指令的编号, TransferArgs::V1 的编号, amount(u64), authorization_data(None)
+const TRANSFER_DISCRIMINATOR: u8 = 49;
+const TRANSFER_ARGS_V1: u8 = 0;
+
+// remaining_accounts 中 pNFT 转账的账户, 由 split 分出
+pub struct PnftAccounts<'a, 'info> {
+    accounts: &'a [AccountInfo<'info>; PNFT_ACCOUNTS],
+}
+
+// TransferV1 中由调用方的账户结构提供的账户; Token Metadata 要求两个 token 账户都是 ATA
+pub struct PnftTransfer<'info> {
+    pub token: AccountInfo<'info>,
+    pub token_owner: AccountInfo<'info>,
+    pub destination_token: AccountInfo<'info>,
+    pub destination_owner: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>, // token 账户的 owner, 可以是通过 signer_seeds 签名的 escrow
+    pub payer: AccountInfo<'info>,     // 支付目标 token record 的租金
+    pub system_program: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+}
+
+// 从 remaining_accounts 中分出 pNFT 转账的账户, 同时返回剩余的 transfer hook 额外账户
+// 第一个账户不是 Token Metadata 程序时按普通 token 处理; 否则 mint 的 Metadata 必须是 ProgrammableNonFungible
+pub fn split<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    mint: &Pubkey,
+) -> Result<(Option<PnftAccounts<'a, 'info>>, &'a [AccountInfo<'info>])> {
+    match remaining_accounts.first() {
+        Some(program) if program.key() == TOKEN_METADATA_ID => {}
+        _ => return Ok((None, remaining_accounts)),
+    }
+    require_gte!(
+        remaining_accounts.len(),
+        PNFT_ACCOUNTS,
+        EscrowError::InvalidPnftAccounts
+    );
+    let (accounts, rest) = remaining_accounts.split_at(PNFT_ACCOUNTS);
+    let accounts: &[AccountInfo; PNFT_ACCOUNTS] = accounts.try_into().unwrap();
+
+    // Metadata 的地址和其他账户由 Token Metadata 程序校验, 这里只保证 mint 确实是 pNFT
+    let metadata = NftMetadata::load(&accounts[1])?;
+    require_keys_eq!(metadata.mint, *mint, EscrowError::InvalidPnftAccounts);
+    require!(metadata.is_programmable(), EscrowError::InvalidPnftAccounts);
+
+    Ok((Some(PnftAccounts { accounts }), rest))
+}
+
+// Token Metadata 用程序自己的地址表示省略的可选账户, 它只能作为只读账户传入
+fn meta(info: &AccountInfo, is_writable: bool, is_signer: bool) -> AccountMeta {
+    AccountMeta {
+        pubkey: *info.key,
+        is_signer,
+        is_writable: is_writable && *info.key != TOKEN_METADATA_ID,
+    }
+}
+
+impl<'info> PnftAccounts<'_, 'info> {
+    // 通过 TransferV1 转账 amount 个 token, authority 是 escrow 时传入它的 signer_seeds, 否则传空
+    pub fn transfer(
+        &self,
+        accounts: PnftTransfer<'info>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        // self.accounts 的顺序见 PNFT_ACCOUNTS 的说明
+        let [program, metadata, edition, record, destination_record, ..] = self.accounts;
+        let [.., sysvar_instructions, rules_program, rules] = self.accounts;
+
+        let mut data = vec![TRANSFER_DISCRIMINATOR, TRANSFER_ARGS_V1];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.push(0);
+
+        // 账户顺序和 TransferV1 的定义一致
+        let account_infos = [
+            accounts.token,
+            accounts.token_owner,
+            accounts.destination_token,
+            accounts.destination_owner,
+            accounts.mint,
+            metadata.clone(),
+            edition.clone(),
+            record.clone(),
+            destination_record.clone(),
+            accounts.authority,
+            accounts.payer,
+            accounts.system_program,
+            sysvar_instructions.clone(),
+            accounts.token_program,
+            accounts.associated_token_program,
+            rules_program.clone(),
+            rules.clone(),
+        ];
+        let writable = [0, 2, 5, 7, 8, 10];
+        let signers = [9, 10];
+        let metas = account_infos
+            .iter()
+            .enumerate()
+            .map(|(i, info)| meta(info, writable.contains(&i), signers.contains(&i)))
+            .collect();
+
+        let ix = Instruction {
+            program_id: TOKEN_METADATA_ID,
+            accounts: metas,
+            data,
+        };
+        let mut infos = account_infos.to_vec();
+        infos.push(program.clone());
+        invoke_signed(&ix, &infos, signer_seeds).map_err(Into::into)
+    }
+}
@@ -0,0 +1,36 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
+
+// 把本程序拥有的变长账户调整为 space 字节, 保持免租金
+// 扩容时由 payer 通过系统程序补足租金, 缩小时多余的租金直接还给 payer
+pub fn resize<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = account.lamports();
+
+    if rent > lamports {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            rent - lamports,
+        )?;
+    } else if lamports > rent {
+        account.sub_lamports(lamports - rent)?;
+        payer.add_lamports(lamports - rent)?;
+    }
+
+    account.resize(space)?;
+
+    Ok(())
+}
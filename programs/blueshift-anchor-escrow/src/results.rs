@@ -0,0 +1,44 @@
+use crate::state::Escrow;
+use anchor_lang::prelude::*;
+
+// 指令通过 set_return_data 返回的结果, anchor 会用 borsh 序列化指令 handler 的返回值
+// return data 最多 1024 字节, 返回值不能超过这个大小
+// 通过 CPI 调用时 cpi::make / cpi::take 返回 Return<T>, 调用 .get() 即可解码;
+// 也可以自己用 get_return_data 读取, 需要先检查返回的 program id 是托管程序
+
+// make 的返回值
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MakeResult {
+    // 创建的托管地址
+    pub escrow: Pubkey,
+    // 托管 PDA 的 bump
+    pub bump: u8,
+}
+
+// take 的返回值
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TakeResult {
+    // taker 实际收到的 token A 数量, mint A 带有 TransferFee 扩展时小于 vault 转出的数量
+    pub amount_a_out: u64,
+    // taker 支付的 token B 数量, 包含手续费和小费
+    pub amount_b_in: u64,
+    // 收取的 token B 手续费总额(包含 referral 部分)
+    pub fee: u64,
+}
+
+// get_escrow 的返回值, 除了托管数据还包含按 timestamp 计算的字段
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct EscrowView {
+    pub address: Pubkey,
+    pub escrow: Escrow,
+    // 当前对应 deposited 个 token A 的 receive, 荷兰拍卖会随时间下降
+    pub current_receive: u64,
+    // 按当前价格成交剩余全部 token A 需要支付的 token B 数量, 包含手续费
+    pub amount_b: u64,
+    // 是否已经到达开始时间
+    pub started: bool,
+    // 是否设置了过期时间并且已经过期
+    pub expired: bool,
+    // 计算时使用的链上时间
+    pub timestamp: i64,
+}
@@ -1,18 +1,2601 @@
+use crate::{
+    errors::EscrowError, merkle, oracle::OraclePrice, pda::find_operator_authority_address,
+};
 use anchor_lang::prelude::*;
+use solana_sha256_hasher::{hash, hashv};
 
-#[derive(InitSpace)] // 不需要手动计算空间大小(租金)
-#[account(discriminator = 1)] // 用自定义的标识符 1 代替默认账户名称哈希后的前 8 个字节
+// 不使用 #[account]: 账户的读写由下面手动实现, 旧版本的数据返回 MigrationRequired 而不是 AccountDidNotDeserialize
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)] // 不需要手动计算空间大小(租金)
+#[cfg_attr(test, derive(Debug))] // proptest 生成的值需要实现 Debug
 pub struct Escrow {
+    // 账户布局的版本, 紧跟在 discriminator 之后, 当前为 Escrow::VERSION; 加入这个字段之前创建的托管是版本 1, 没有这个字节
+    pub version: u8,
     // 随机数, 用于生成不同的 Escrow 账户
     pub seed: u64,
     // 托管账户的创建者
     pub maker: Pubkey,
+    // 支付 escrow 和 vault 租金的账户, 关闭时租金还给它; 没有赞助时就是 maker
+    pub rent_payer: Pubkey,
+    // 接收 token B 的钱包, 例如 treasury 的冷钱包; 没有指定时就是 maker
+    pub receive_to: Pubkey,
     // 存入的 token A 的 mint 账户地址
     pub mint_a: Pubkey,
-    // 换取的 token B 的 mint 账户地址
+    // 换取的 token B 的 mint 账户地址, Pubkey::default() 表示 taker 用 SOL 支付, 此时 receive 以 lamports 为单位
     pub mint_b: Pubkey,
     // 创建者希望收到的 Token B 的数量
     pub receive: u64,
+    // 定价基准的 token A 数量, 和 receive 一起决定单价 receive / deposited, 部分成交后不会改变
+    pub deposited: u64,
+    // 当前仍在出售的 token A 的数量, 部分成交后减少; 直接转入 vault 的多余代币不计入其中
+    pub amount: u64,
+    // 过期时间戳(unix 秒), 0 表示永不过期; 过期后任何人都可以把 token A 退还给 maker
+    pub expiry: i64,
+    // 开始时间戳(unix 秒), 在此之前不能成交, 0 表示创建后立即可以成交
+    pub start_time: i64,
+    // 创建时间戳(unix 秒), relist 时重新计时; 超过 config.abandon_seconds 后任何人都可以 sweep_abandoned
+    pub created_at: i64,
+    // commit-reveal 的等待 slot 数, 非 0 时 taker 必须先 commit_take, 至少经过这么多 slot 之后才能 reveal_take 成交; 0 表示不启用
+    pub commit_delay_slots: u64,
+    // 预约的时长(秒), 非 0 时 taker 可以先 reserve 支付 SOL 保证金锁定价格, 在这段时间内再 settle 成交; 0 表示不允许预约
+    pub reservation_window: i64,
+    // 指定的 taker, Pubkey::default() 表示任何人都可以 take
+    pub allowed_taker: Pubkey,
+    // maker 授权的退还密钥, 可以代替 maker 签名 refund, token A 和租金仍然退还给 maker 和 rent_payer; Pubkey::default() 表示没有
+    pub refund_delegate: Pubkey,
+    // 仲裁人, 非 Pubkey::default() 时 taker 只能通过 lock_take 把 token B 锁入 vault_b, 由仲裁人或双方共同决定结算方向
+    pub arbiter: Pubkey,
+    // 允许成交的 taker 列表的默克尔根, 全 0 表示不限制
+    pub taker_allowlist_root: [u8; 32],
+    // 哈希时间锁, 非 0 时 taker 必须提供 sha256 等于它的 preimage 才能成交, 全 0 表示普通托管
+    pub hashlock: [u8; 32],
+    // 荷兰拍卖的价格衰减开始时间, 在此之前价格为 receive
+    pub decay_start: i64,
+    // 荷兰拍卖的价格衰减结束时间, 0 表示固定价格
+    pub decay_end: i64,
+    // 荷兰拍卖结束时的 receive, 衰减结束后价格保持不变
+    pub end_receive: u64,
+    // mint_b 之外 maker 同样接受的支付 mint, 例如 USDC 之外还接受 USDT; 只有 take, reveal_take 和 settle 可以使用
+    // 每一项有自己的 receive(decimals 可能不同), 和 receive 一样对应 deposited 个 token A; mint 为 Pubkey::default() 的项为空
+    pub alt_payments: [PaymentMint; 3],
+    // lock_take 锁定 token B 的 taker, 没有锁定时为 Pubkey::default()
+    pub locked_taker: Pubkey,
+    // lock_take 的时间戳(unix 秒), 超过 ARBITRATION_TIMEOUT 仍未结算时任何一方都可以退回双方的资金
+    pub locked_at: i64,
+    // reserve 预约托管的 taker, 没有预约时为 Pubkey::default(); 预约期间其他人不能成交, maker 也不能退还或修改条件
+    pub reserved_taker: Pubkey,
+    // 预约的截止时间戳(unix 秒), 之前只有 reserved_taker 可以 settle, 之后任何人都可以 expire_reservation
+    pub reservation_expiry: i64,
+    // reserved_taker 存入 escrow 账户的 SOL 保证金(lamports), settle 时退还给 taker, 过期时归 maker
+    pub bond: u64,
+    // 循环托管: take, reveal_take 或 settle 全部成交后从 maker 的 ATA 重新存入 deposited 个 token A, 托管保持打开
+    // make 时通过 approve_checked 把 maker ATA 的额度授权给 escrow PDA; 额度或余额不足时和普通托管一样关闭
+    pub recurring: bool,
+    // 剩余的重新存入次数, 为 0 后下一次成交正常关闭托管
+    pub max_refills: u8,
+    // 托管当前所处的状态, 见 EscrowStatus
+    pub status: EscrowStatus,
+    // 冻结前的状态, 解冻时恢复; 没有冻结时和 status 相同
+    pub status_before_freeze: EscrowStatus,
     // 缓存的 bump 值, 防止动态派生所消耗的计算资源
     pub bump: u8,
+    // 托管资金的 vault 地址(escrow 持有的 mint_a 的 ATA), make 时写入, take 和 refund 直接按地址校验而不重新派生
+    // 版本 1 中最后加入的字段, 更早创建的版本 1 托管没有它, 由 migrate_escrow 派生后写入
+    pub vault: Pubkey,
+    // 按预言机定价或检查价格区间时的 Pyth PriceUpdateV2 账户, take 时必须传入这个账户; Pubkey::default() 表示不读取价格
+    // 以下三个字段在版本 3 中加入
+    pub price_feed: Pubkey,
+    // 价格账户的 exponent, take 时要求一致, 防止 feed 的精度改变后按错误的单位成交
+    pub quote_exponent: i32,
+    // 价格发布之后可以用于成交的最长时间(秒)
+    pub max_staleness: i64,
+    // 版本 4 加入: 大于 0 时 price_feed 只作为固定价格的区间检查, receive 折算的单价偏离预言机价格超过这个比例时拒绝成交
+    pub max_deviation_bps: u16,
+    // 版本 5 加入: collection 托管接受的 Metaplex verified collection(collection NFT 的 mint), 和 mint_b 相同; Pubkey::default() 表示普通托管
+    // taker 通过 take_collection 用 collection 中的任意一个 NFT 成交, receive 固定为 1
+    pub collection: Pubkey,
+    // 版本 6 加入: take 时按 mint A 的 Metaplex metadata 从 token B 中向 verified creator 支付版税, 由 set_royalty_enforcement 设置
+    // 其他成交方式不支付版税, 因此开启后只能通过 take, reveal_take 和 settle 成交
+    pub enforce_royalties: bool,
+    // 版本 7 加入: maker 自定义的分类标签, 用于 get_program_accounts 按 TAG_OFFSET 粗略过滤, 程序不解释它的含义
+    pub tag: u8,
+    // 是否计入 (mint_a, mint_b) 的 PairIndex, take 和 refund 关闭托管时据此更新索引
+    pub pair_indexed: bool,
+    // 版本 8 加入: cancel 保留托管时记录的原因(CancelReason 的取值)和 slot, 供浏览器读取; 没有取消时为 0
+    pub cancel_reason: u8,
+    pub cancelled_slot: u64,
+    // 版本 9 加入: 为 true 时只能由交易的顶层指令成交, 通过 CPI 成交时外层程序必须在 Config::trusted_routers 中, 见 caller.rs
+    pub direct_only: bool,
+    // 版本 10 加入: maker 通过 pause_offer 暂停成交, 托管和租金保留, resume_offer 恢复; 和协议的 Config::paused 相互独立
+    pub maker_paused: bool,
+    // 版本 11 加入: 分期成交的时长(秒), 非 0 时 taker 可以通过 take_streaming 锁定价格并分期支付, 由 set_stream_window 设置; 0 表示不允许
+    pub stream_window: i64,
+    // 分期成交开始时锁定的 token B 总额, 对应开始时剩余的全部 token A; 没有分期成交时为 0
+    pub stream_receive: u64,
+    // 分期成交中 taker 已经支付的 token B, 达到 stream_receive 时托管关闭
+    pub paid_b: u64,
+    // 分期成交的截止时间戳(unix 秒), 之后 taker 不能继续支付, maker 可以 refund 取回还没有释放的 token A
+    pub stream_deadline: i64,
+    // 版本 12 加入: maker 在 make_v2 时选择是否允许 Config::operators 中的运营方代为 refund, 见 operator_refund; 默认不允许
+    pub allow_operators: bool,
+}
+
+// 用自定义的标识符 1 代替默认账户名称哈希后的前 8 个字节
+impl Discriminator for Escrow {
+    const DISCRIMINATOR: &'static [u8] = &[1];
+}
+
+impl Owner for Escrow {
+    fn owner() -> Pubkey {
+        crate::ID
+    }
+}
+
+impl AccountSerialize for Escrow {
+    fn try_serialize<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+        writer
+            .write_all(Self::DISCRIMINATOR)
+            .map_err(|_| ErrorCode::AccountDidNotSerialize)?;
+        AnchorSerialize::serialize(self, writer).map_err(|_| ErrorCode::AccountDidNotSerialize)?;
+        Ok(())
+    }
+}
+
+impl AccountDeserialize for Escrow {
+    fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+        if buf.len() < Self::DISCRIMINATOR.len() {
+            return err!(ErrorCode::AccountDiscriminatorNotFound);
+        }
+        if &buf[..Self::DISCRIMINATOR.len()] != Self::DISCRIMINATOR {
+            return Err(error!(ErrorCode::AccountDiscriminatorMismatch).with_account_name("Escrow"));
+        }
+        Self::try_deserialize_unchecked(buf)
+    }
+
+    // 只接受当前版本的布局, 旧版本必须先通过 migrate_escrow 迁移, 见 Escrow::try_deserialize_v1
+    fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+        if buf.len() != Self::SPACE || buf[Self::DISCRIMINATOR.len()] != Self::VERSION {
+            return err!(EscrowError::MigrationRequired);
+        }
+        let mut data: &[u8] = &buf[Self::DISCRIMINATOR.len()..];
+        AnchorDeserialize::deserialize(&mut data)
+            .map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+    }
+}
+
+// 托管的生命周期状态, 账户存在只说明托管还没有关闭
+// 成交, 还价和部分成交只会让状态前进(Open < PendingCounter < PartiallyFilled < Locked), 冻结由管理员叠加在其上
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EscrowStatus {
+    // 刚创建, 没有还价也没有成交
+    Open,
+    // 收到过还价, 仍然可以直接成交
+    PendingCounter,
+    // 部分成交过, 剩余的 token A 仍在出售
+    PartiallyFilled,
+    // 被管理员冻结, 只能退还
+    Frozen,
+    // 有仲裁人的托管被 lock_take 锁定, taker 的 token B 在 vault_b 中, 只能通过 release_to_taker 或 release_to_maker 结算
+    Locked,
+    // 被 cancel 取消并保留, token A 已经退还, 只能在 CANCEL_RETENTION_SLOTS 之后通过 close_cancelled 关闭
+    Cancelled,
+    // 被 take_streaming 锁定给 locked_taker 分期成交, 只能由它 stream_payment, 超过 stream_deadline 后 maker 可以 refund
+    Streaming,
+}
+
+impl EscrowStatus {
+    // 可以被 take, take_partial, take_for_sol 和 accept_counter 成交的状态
+    // 不使用通配符, 新增状态时必须在这里决定是否允许成交
+    pub fn is_fillable(self) -> bool {
+        match self {
+            EscrowStatus::Open | EscrowStatus::PendingCounter | EscrowStatus::PartiallyFilled => {
+                true
+            }
+            EscrowStatus::Frozen
+            | EscrowStatus::Locked
+            | EscrowStatus::Cancelled
+            | EscrowStatus::Streaming => false,
+        }
+    }
+
+    // 可以被 maker 退还或取回的状态; 任何状态下 maker 都必须能够退出
+    // 锁定后 vault_b 中有 taker 的资金, maker 通过 release_to_maker 退出, 仲裁人不处理时超时后自己就可以调用
+    // 分期成交中的托管只有 refund 在 stream_deadline 之后可以退还, 见 check_maker_can_withdraw
+    pub fn is_refundable(self) -> bool {
+        match self {
+            EscrowStatus::Open
+            | EscrowStatus::PendingCounter
+            | EscrowStatus::PartiallyFilled
+            | EscrowStatus::Frozen => true,
+            EscrowStatus::Locked | EscrowStatus::Cancelled | EscrowStatus::Streaming => false,
+        }
+    }
+
+    // 生命周期中的先后顺序, 冻结不参与比较; 取消是最后的阶段, 之后状态不再改变
+    fn stage(self) -> u8 {
+        match self {
+            EscrowStatus::Open => 0,
+            EscrowStatus::PendingCounter => 1,
+            EscrowStatus::PartiallyFilled => 2,
+            EscrowStatus::Locked => 3,
+            EscrowStatus::Streaming => 4,
+            EscrowStatus::Cancelled => 5,
+            EscrowStatus::Frozen => u8::MAX,
+        }
+    }
+}
+
+// cancel 的原因代码, 指令参数和 Escrow::cancel_reason 中是它的 u8 取值, 顺序不能改变
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum CancelReason {
+    // maker 撤回报价
+    Pulled,
+    // 过期之后退还
+    Expired,
+    // 改价, 之后会按新的价格重新挂单
+    Repriced,
+    // 其他原因
+    Other,
+}
+
+impl TryFrom<u8> for CancelReason {
+    type Error = Error;
+
+    fn try_from(code: u8) -> Result<Self> {
+        match code {
+            0 => Ok(CancelReason::Pulled),
+            1 => Ok(CancelReason::Expired),
+            2 => Ok(CancelReason::Repriced),
+            3 => Ok(CancelReason::Other),
+            _ => err!(EscrowError::InvalidCancelReason),
+        }
+    }
+}
+
+// 托管接受的一种支付 mint 和对应 deposited 个 token A 的固定价格
+#[derive(
+    AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug,
+)]
+pub struct PaymentMint {
+    pub mint: Pubkey,
+    pub receive: u64,
+}
+
+impl PaymentMint {
+    pub fn is_set(&self) -> bool {
+        self.mint != Pubkey::default()
+    }
+}
+
+impl Escrow {
+    // 当前的账户布局版本
+    pub const VERSION: u8 = 12;
+    // 当前版本的账户大小
+    pub const SPACE: usize = Self::DISCRIMINATOR.len() + Self::INIT_SPACE;
+    // 版本 11 没有 allow_operators, 版本 10 没有最后的分期成交字段, 版本 9 没有 maker_paused, 版本 8 没有 direct_only, 版本 7 还没有 cancel_reason 和 cancelled_slot, 版本 6 还没有 tag 和 pair_indexed,
+    // 版本 5 还没有 enforce_royalties, 版本 4 还没有 collection, 版本 3 还没有 max_deviation_bps,
+    // 版本 2 还没有 price_feed, quote_exponent 和 max_staleness
+    pub const V11_SPACE: usize = Self::SPACE - 1;
+    pub const V10_SPACE: usize = Self::V11_SPACE - 8 - 8 - 8 - 8;
+    pub const V9_SPACE: usize = Self::V10_SPACE - 1;
+    pub const V8_SPACE: usize = Self::V9_SPACE - 1;
+    pub const V7_SPACE: usize = Self::V8_SPACE - 1 - 8;
+    pub const V6_SPACE: usize = Self::V7_SPACE - 2;
+    pub const V5_SPACE: usize = Self::V6_SPACE - 1;
+    pub const V4_SPACE: usize = Self::V5_SPACE - 32;
+    pub const V3_SPACE: usize = Self::V4_SPACE - 2;
+    pub const V2_SPACE: usize = Self::V3_SPACE - 32 - 4 - 8;
+    // 版本 1 还没有 version 字节; 加入 vault 之前创建的版本 1 托管还少 vault 的 32 字节
+    pub const V1_SPACE: usize = Self::V2_SPACE - 1;
+    pub const V1_SPACE_WITHOUT_VAULT: usize = Self::V1_SPACE - 32;
+
+    // 账户数据中字段的偏移量, 供 get_program_accounts 的 memcmp 过滤使用; 调整字段顺序时必须同步修改
+    // 1 字节的自定义 discriminator 和 1 字节的 version, 之后是 8 字节的 seed
+    pub const MAKER_OFFSET: usize = Self::DISCRIMINATOR.len() + 1 + 8;
+    // maker 之后是 rent_payer 和 receive_to
+    pub const MINT_A_OFFSET: usize = Self::MAKER_OFFSET + 32 * 3;
+    pub const MINT_B_OFFSET: usize = Self::MINT_A_OFFSET + 32;
+    // vault 是版本 2 的最后一个字段
+    pub const VAULT_OFFSET: usize = Self::V2_SPACE - 32;
+    // price_feed 紧跟在 vault 之后
+    pub const PRICE_FEED_OFFSET: usize = Self::V2_SPACE;
+    // collection 紧跟在 max_deviation_bps 之后, 用来查找某个 collection 的托管
+    pub const COLLECTION_OFFSET: usize = Self::V4_SPACE;
+    // tag 紧跟在 enforce_royalties 之后, 之前的字段都是定长的, 以后的版本只在它之后追加字段
+    pub const TAG_OFFSET: usize = Self::V6_SPACE;
+
+    // cancel 保留托管的最短时间, 按 400ms 的 slot 大约是一分钟, 之后任何人都可以 close_cancelled
+    pub const CANCEL_RETENTION_SLOTS: u64 = 150;
+
+    // commit_delay_slots 的上限, 按 400ms 的 slot 大约是一分钟
+    pub const MAX_COMMIT_DELAY_SLOTS: u64 = 150;
+
+    // 锁定之后仲裁人的处理期限, 超过之后 maker 或 taker 可以单独调用 release_to_maker 退回双方的资金
+    pub const ARBITRATION_TIMEOUT: i64 = 14 * 24 * 60 * 60;
+
+    // alt_payments 的长度, 加上 mint_b 最多接受 4 种支付 mint
+    pub const MAX_ALT_PAYMENTS: usize = 3;
+
+    // reservation_window 的上限, 防止一次预约长时间占用托管
+    pub const MAX_RESERVATION_WINDOW: i64 = 24 * 60 * 60;
+
+    // stream_window 的上限, 分期成交最多锁定托管 90 天
+    pub const MAX_STREAM_WINDOW: i64 = 90 * 24 * 60 * 60;
+
+    // take_streaming 第一期至少支付 stream_receive 的这个比例(bps), 防止用极少的 token B 长时间锁定托管
+    pub const MIN_STREAM_INITIAL_BPS: u16 = 2_500;
+
+    // 预言机价格的置信区间上限, 为价格的 1%; 市场剧烈波动时 Pyth 会放宽置信区间, 此时暂停成交
+    pub const MAX_ORACLE_CONF_BPS: u64 = 100;
+
+    // max_staleness 的上限, 超过一小时的价格已经不能代表成交时的价格
+    pub const MAX_ORACLE_STALENESS: i64 = 60 * 60;
+
+    // make 和 top_up 的 amount 为这个值时存入 maker ATA 在指令执行时的全部余额, 托管记录实际的数量
+    // 客户端不需要先读取余额, 读取之后转入的 token 也会被存入
+    pub const FULL_BALANCE: u64 = u64::MAX;
+
+    // 读取旧版本的托管数据(包含 discriminator), 供 migrate_escrow 和客户端使用, 按长度区分版本
+    // 旧版本的字段是当前版本的前缀: 版本 1 没有 version 字节, 还没有 vault 字段的托管返回的 vault 为 Pubkey::default()
+    // 之后加入的字段都读出默认值, 即按 receive 固定定价的普通托管
+    pub fn try_deserialize_legacy(buf: &[u8]) -> Result<Self> {
+        let disc = Self::DISCRIMINATOR.len();
+        if buf.len() < disc || &buf[..disc] != Self::DISCRIMINATOR {
+            return err!(ErrorCode::AccountDiscriminatorMismatch);
+        }
+
+        // 补上缺少的 version 字节和之后加入的字段, 按当前版本的布局读取
+        let mut data = Vec::with_capacity(Self::INIT_SPACE);
+        match buf.len() {
+            Self::V11_SPACE if buf[disc] == 11 => {}
+            Self::V10_SPACE if buf[disc] == 10 => {}
+            Self::V9_SPACE if buf[disc] == 9 => {}
+            Self::V8_SPACE if buf[disc] == 8 => {}
+            Self::V7_SPACE if buf[disc] == 7 => {}
+            Self::V6_SPACE if buf[disc] == 6 => {}
+            Self::V5_SPACE if buf[disc] == 5 => {}
+            Self::V4_SPACE if buf[disc] == 4 => {}
+            Self::V3_SPACE if buf[disc] == 3 => {}
+            Self::V2_SPACE if buf[disc] == 2 => {}
+            Self::V1_SPACE | Self::V1_SPACE_WITHOUT_VAULT => data.push(1),
+            _ => return err!(EscrowError::EscrowNotLegacy),
+        }
+        data.extend_from_slice(&buf[disc..]);
+        data.resize(Self::INIT_SPACE, 0);
+        AnchorDeserialize::deserialize(&mut data.as_slice())
+            .map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+    }
+
+    // 判断 taker 是否有权成交这个托管
+    pub fn is_taker_allowed(&self, taker: &Pubkey) -> bool {
+        self.allowed_taker == Pubkey::default() || self.allowed_taker == *taker
+    }
+
+    // maker 本人或 refund_delegate 可以签名退还
+    pub fn is_refund_authority(&self, signer: &Pubkey) -> bool {
+        *signer == self.maker
+            || (self.refund_delegate != Pubkey::default() && *signer == self.refund_delegate)
+    }
+
+    // 用默克尔证明判断 taker 是否在允许列表中, 没有设置列表时总是允许
+    pub fn is_on_allowlist(&self, taker: &Pubkey, proof: &[[u8; 32]]) -> bool {
+        self.taker_allowlist_root == [0; 32]
+            || merkle::verify(proof, &self.taker_allowlist_root, merkle::leaf_hash(taker))
+    }
+
+    // 循环托管还可以重新存入时返回需要从 maker 的 ATA 转入的数量, 即上一轮的 deposited
+    pub fn refill_amount(&self) -> Option<u64> {
+        (self.recurring && self.max_refills > 0).then_some(self.deposited)
+    }
+
+    // 成交转出全部 token A 后重新存入, net_amount 是 vault 实际收到的数量, 之后按新的 deposited 定价
+    // 状态不回到 Open, 和其他成交一样只会前进
+    pub fn refill(&mut self, net_amount: u64) {
+        self.deposited = net_amount;
+        self.amount = net_amount;
+        self.max_refills -= 1;
+    }
+
+    // 托管到达开始时间之后才能成交, maker 在等待期间仍然可以退还
+    pub fn check_started(&self, now: i64) -> Result<()> {
+        require_gte!(now, self.start_time, EscrowError::OfferNotStarted);
+
+        Ok(())
+    }
+
+    // 过期之后托管只能退还, 不能再按原来的价格成交
+    pub fn check_not_expired(&self, now: i64) -> Result<()> {
+        require!(!self.is_expired(now), EscrowError::EscrowExpired);
+
+        Ok(())
+    }
+
+    // maker 暂停期间所有成交方式都失败, refund 和 cancel 不受影响
+    pub fn check_not_maker_paused(&self) -> Result<()> {
+        require!(!self.maker_paused, EscrowError::OfferPausedByMaker);
+
+        Ok(())
+    }
+
+    // 是否开启了 commit-reveal, 开启后 take, take_partial, take_with_sig 和 match_escrows 都不能成交
+    // accept_counter 由 maker 主动选择成交对象, 不受影响
+    pub fn requires_commit(&self) -> bool {
+        self.commit_delay_slots > 0
+    }
+
+    // reveal_take 要求 salt 和 commit 中的哈希匹配, 并且 commit 之后至少经过了 commit_delay_slots 个 slot
+    pub fn check_reveal(
+        &self,
+        escrow: &Pubkey,
+        commit: &TakeCommit,
+        salt: &[u8; 32],
+        slot: u64,
+    ) -> Result<()> {
+        require!(
+            TakeCommit::hash(&commit.taker, escrow, salt) == commit.hash,
+            EscrowError::CommitMismatch
+        );
+        let ready = commit
+            .slot
+            .checked_add(self.commit_delay_slots)
+            .ok_or(EscrowError::MathOverflow)?;
+        require_gte!(slot, ready, EscrowError::CommitTooEarly);
+
+        Ok(())
+    }
+
+    // 是否设置了仲裁人, 设置后只能通过 lock_take 成交
+    pub fn is_arbitrated(&self) -> bool {
+        self.arbiter != Pubkey::default()
+    }
+
+    // lock_take 锁定托管, 之后只能结算
+    pub fn lock(&mut self, taker: Pubkey, now: i64) {
+        self.locked_taker = taker;
+        self.locked_at = now;
+        self.advance_status(EscrowStatus::Locked);
+    }
+
+    // 结算锁定的托管: 仲裁人单独签名, 或者 maker 和 taker 共同签名
+    // 超过 ARBITRATION_TIMEOUT 之后 maker 或 taker 单独签名也可以退回双方的资金(to_taker 为 false)
+    // 资金的去向由账户约束固定为 maker, receive_to 和 taker, 签名者只能选择方向
+    pub fn check_release(
+        &self,
+        authority: &Pubkey,
+        co_signer: Option<&Pubkey>,
+        to_taker: bool,
+        now: i64,
+    ) -> Result<()> {
+        if *authority == self.arbiter {
+            return Ok(());
+        }
+
+        let is_party = |key: &Pubkey| *key == self.maker || *key == self.locked_taker;
+        if let Some(co_signer) = co_signer {
+            if is_party(authority) && is_party(co_signer) && authority != co_signer {
+                return Ok(());
+            }
+        }
+
+        let deadline = self
+            .locked_at
+            .checked_add(Self::ARBITRATION_TIMEOUT)
+            .ok_or(EscrowError::MathOverflow)?;
+        require!(
+            !to_taker && is_party(authority) && now > deadline,
+            EscrowError::UnauthorizedRelease
+        );
+
+        Ok(())
+    }
+
+    // maker 创建时是否允许预约
+    pub fn allows_reservations(&self) -> bool {
+        self.reservation_window > 0
+    }
+
+    // maker 是否允许分期成交
+    pub fn allows_streaming(&self) -> bool {
+        self.stream_window > 0
+    }
+
+    // take_streaming 把剩余的全部 token A 按 stream_receive 锁定给 taker, 截止时间为 now + stream_window
+    pub fn start_stream(&mut self, taker: Pubkey, stream_receive: u64, now: i64) -> Result<()> {
+        self.locked_taker = taker;
+        self.locked_at = now;
+        self.stream_receive = stream_receive;
+        self.paid_b = 0;
+        self.stream_deadline = now
+            .checked_add(self.stream_window)
+            .ok_or(EscrowError::MathOverflow)?;
+        self.advance_status(EscrowStatus::Streaming);
+
+        Ok(())
+    }
+
+    // 支付 amount_b 个 token B 释放的 token A 数量: 剩余的 token A 乘以本期占剩余未付 token B 的比例, 向下取整
+    // 舍入误差留在 vault 中对 maker 有利, 最后一期付清时释放剩余的全部 token A, 因此所有期释放的总和正好是开始时的 amount
+    pub fn stream_release_for(&self, amount_b: u64) -> Result<u64> {
+        let unpaid = self
+            .stream_receive
+            .checked_sub(self.paid_b)
+            .ok_or(EscrowError::MathOverflow)?;
+        require!(
+            amount_b > 0 && amount_b <= unpaid,
+            EscrowError::InvalidAmount
+        );
+        if amount_b == unpaid {
+            return Ok(self.amount);
+        }
+
+        let released = (self.amount as u128)
+            .checked_mul(amount_b as u128)
+            .ok_or(EscrowError::MathOverflow)?
+            / unpaid as u128;
+
+        // amount_b < unpaid, 结果小于 amount, 不会溢出
+        Ok(released as u64)
+    }
+
+    // take_streaming 第一期至少支付的 token B: stream_receive 的 MIN_STREAM_INITIAL_BPS, 向上取整
+    pub fn min_stream_initial(&self) -> Result<u64> {
+        let value = (self.stream_receive as u128 * Self::MIN_STREAM_INITIAL_BPS as u128)
+            .div_ceil(BPS_DENOMINATOR as u128);
+
+        // 比例不超过 100%, 结果不超过 stream_receive
+        Ok(value as u64)
+    }
+
+    // 是否有未结束的预约, 包括已经过了截止时间但还没有 expire_reservation 的预约
+    // 预约期间保证金在 escrow 账户中, 关闭 escrow 的指令都必须先等预约结束, 否则保证金会随租金一起转给 rent_payer
+    pub fn is_reserved(&self) -> bool {
+        self.reserved_taker != Pubkey::default()
+    }
+
+    // reserve 记录预约的 taker 和保证金, 截止时间为 now + reservation_window
+    pub fn reserve(&mut self, taker: Pubkey, bond: u64, now: i64) -> Result<()> {
+        require!(
+            self.allows_reservations(),
+            EscrowError::ReservationsDisabled
+        );
+        require!(!self.is_reserved(), EscrowError::EscrowReserved);
+        require_gt!(bond, 0, EscrowError::InvalidBond);
+
+        self.reservation_expiry = now
+            .checked_add(self.reservation_window)
+            .ok_or(EscrowError::MathOverflow)?;
+        self.reserved_taker = taker;
+        self.bond = bond;
+
+        Ok(())
+    }
+
+    // settle 只能由 reserved_taker 在截止时间之前调用, 返回需要退还给 taker 的保证金并清除预约
+    pub fn settle_reservation(&mut self, taker: &Pubkey, now: i64) -> Result<u64> {
+        require!(self.is_reserved(), EscrowError::NotReserved);
+        require_keys_eq!(*taker, self.reserved_taker, EscrowError::UnauthorizedTaker);
+        require_gt!(
+            self.reservation_expiry,
+            now,
+            EscrowError::ReservationExpired
+        );
+
+        Ok(self.clear_reservation())
+    }
+
+    // 截止时间之后任何人都可以结束预约, 返回归 maker 所有的保证金并重新开放托管
+    pub fn expire_reservation(&mut self, now: i64) -> Result<u64> {
+        require!(self.is_reserved(), EscrowError::NotReserved);
+        require_gte!(
+            now,
+            self.reservation_expiry,
+            EscrowError::ReservationNotExpired
+        );
+
+        Ok(self.clear_reservation())
+    }
+
+    fn clear_reservation(&mut self) -> u64 {
+        self.reserved_taker = Pubkey::default();
+        self.reservation_expiry = 0;
+        std::mem::take(&mut self.bond)
+    }
+
+    // 托管进入 status 阶段, 已经处于更靠后的阶段时保持不变
+    // 冻结期间只更新解冻后要恢复的状态
+    pub fn advance_status(&mut self, status: EscrowStatus) {
+        if status.stage() > self.status_before_freeze.stage() {
+            self.status_before_freeze = status;
+        }
+        if self.status != EscrowStatus::Frozen {
+            self.status = self.status_before_freeze;
+        }
+    }
+
+    // 管理员冻结托管, 已经冻结时返回 InvalidEscrowStatus
+    // 锁定的托管不能冻结: 冻结后 maker 可以 refund, vault_b 中 taker 的 token B 会失去结算的途径; 取消的托管已经没有资金
+    // 分期成交中的托管同样不能冻结, 否则 maker 可以在截止之前 refund, taker 不能再支付剩余部分
+    pub fn freeze(&mut self) -> Result<()> {
+        require!(
+            !matches!(
+                self.status,
+                EscrowStatus::Frozen
+                    | EscrowStatus::Locked
+                    | EscrowStatus::Cancelled
+                    | EscrowStatus::Streaming
+            ),
+            EscrowError::InvalidEscrowStatus
+        );
+        self.status = EscrowStatus::Frozen;
+
+        Ok(())
+    }
+
+    // 管理员解冻托管, 恢复冻结前的状态; 没有冻结时返回 InvalidEscrowStatus
+    pub fn unfreeze(&mut self) -> Result<()> {
+        require!(
+            self.status == EscrowStatus::Frozen,
+            EscrowError::InvalidEscrowStatus
+        );
+        self.status = self.status_before_freeze;
+
+        Ok(())
+    }
+
+    // 是否设置了过期时间并且已经过期
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now > self.expiry
+    }
+
+    // 是否为哈希时间锁(HTLC)托管
+    pub fn is_hashlocked(&self) -> bool {
+        self.hashlock != [0; 32]
+    }
+
+    // HTLC 托管要求 taker 提供的 preimage 和 hashlock 匹配
+    pub fn check_preimage(&self, preimage: &[u8]) -> Result<()> {
+        if self.is_hashlocked() {
+            require!(
+                hash(preimage).to_bytes() == self.hashlock,
+                EscrowError::InvalidPreimage
+            );
+        }
+
+        Ok(())
+    }
+
+    // HTLC 托管在过期之前 maker 不能取回 token A
+    // 否则 maker 可以用 preimage 在另一条链上收款之后, 抢在 taker 之前取回这里的资金
+    // 分期成交中的托管在 stream_deadline 之前同样不能取回
+    pub fn check_maker_can_withdraw(&self, now: i64) -> Result<()> {
+        if self.is_hashlocked() {
+            require_gt!(now, self.expiry, EscrowError::EscrowNotExpired);
+        }
+        // 分期成交中的托管在截止之前属于 locked_taker
+        if self.status == EscrowStatus::Streaming {
+            require_gt!(now, self.stream_deadline, EscrowError::StreamActive);
+        }
+
+        Ok(())
+    }
+
+    // 是否由 make_for_sol 创建, 只能通过 take_for_sol 用 SOL 成交
+    pub fn is_sol_mode(&self) -> bool {
+        self.mint_b == Pubkey::default()
+    }
+
+    // 是否为荷兰拍卖模式
+    pub fn is_dutch(&self) -> bool {
+        self.decay_end != 0
+    }
+
+    // 是否由 make_oracle 创建, 按 price_feed 的价格定价
+    pub fn is_oracle_priced(&self) -> bool {
+        self.price_feed != Pubkey::default() && self.max_deviation_bps == 0
+    }
+
+    // take 时是否必须传入 price_feed, 按预言机定价和设置了价格区间时都需要
+    pub fn requires_price_feed(&self) -> bool {
+        self.price_feed != Pubkey::default()
+    }
+
+    // 是否由 make_collection 创建, 只能通过 take_collection 用 collection 中的 NFT 成交
+    pub fn is_collection_offer(&self) -> bool {
+        self.collection != Pubkey::default()
+    }
+
+    // 是否由 set_price_band 设置了价格区间, 按 receive 成交之前和 price_feed 的价格比较
+    pub fn has_price_band(&self) -> bool {
+        self.max_deviation_bps > 0
+    }
+
+    // 检查 deposited 个 token A 换 receive 个 token B 的单价是否在预言机价格的 max_deviation_bps 以内
+    // 只是防止 maker 填错价格的保护, 不改变成交价格; 价格的新鲜度和置信区间要求和 oracle_receive 相同
+    pub fn check_price_band(
+        &self,
+        price: &OraclePrice,
+        receive: u64,
+        now: i64,
+        decimals_a: u8,
+        decimals_b: u8,
+    ) -> Result<()> {
+        let fair = self.oracle_receive(price, now, decimals_a, decimals_b)? as u128;
+        let deviation = (receive as u128).abs_diff(fair);
+        require_gte!(
+            fair * self.max_deviation_bps as u128,
+            deviation * 10_000,
+            EscrowError::PriceOutOfBand
+        );
+
+        Ok(())
+    }
+
+    // 按预言机价格计算 deposited 个 token A 的 receive, 和固定价格的 receive 含义相同
+    // 价格必须足够新并且置信区间足够窄, 见 OraclePrice::check
+    pub fn oracle_receive(
+        &self,
+        price: &OraclePrice,
+        now: i64,
+        decimals_a: u8,
+        decimals_b: u8,
+    ) -> Result<u64> {
+        price.check(
+            now,
+            self.max_staleness,
+            self.quote_exponent,
+            Self::MAX_ORACLE_CONF_BPS,
+        )?;
+        price.quote(self.deposited, decimals_a, decimals_b)
+    }
+
+    // taker 选择用 mint 支付时对应 deposited 个 token A 的 receive, 托管不接受这个 mint 时返回 None
+    // mint_b 按 current_receive 计算(可能是荷兰拍卖), 其他支付 mint 是固定价格
+    pub fn receive_in(&self, mint: &Pubkey, now: i64) -> Result<Option<u64>> {
+        if *mint == self.mint_b {
+            return self.current_receive(now).map(Some);
+        }
+
+        Ok(self
+            .alt_payments
+            .iter()
+            .find(|payment| payment.is_set() && payment.mint == *mint)
+            .map(|payment| payment.receive))
+    }
+
+    // 托管是否接受用 mint 支付
+    pub fn accepts_mint_b(&self, mint: &Pubkey) -> bool {
+        *mint == self.mint_b
+            || self
+                .alt_payments
+                .iter()
+                .any(|payment| payment.is_set() && payment.mint == *mint)
+    }
+
+    // deposited 改为 new_deposited 时, 按原有单价重新计算其他支付 mint 的 receive
+    // top_up, withdraw_partial, update_receive 和 relist 修改定价基准时使用
+    pub fn rebased_payments(&self, new_deposited: u64) -> Result<[PaymentMint; 3]> {
+        let mut payments = self.alt_payments;
+        for payment in payments.iter_mut().filter(|payment| payment.is_set()) {
+            payment.receive = self.pro_rata(new_deposited, payment.receive)?;
+        }
+
+        Ok(payments)
+    }
+
+    // 计算 now 时刻对应 deposited 个 token A 的 receive, 固定价格时就是 receive
+    // 荷兰拍卖在衰减窗口内从 receive 线性下降到 end_receive
+    // 按预言机定价的托管没有固定的 receive, 只能由 take, reveal_take 和 settle 读取价格账户后成交, 其他成交方式都在这里被拒绝
+    // collection 托管支付的是 taker 选择的 NFT 而不是 mint_b, 同样在这里被 take_collection 之外的成交方式拒绝
+    pub fn current_receive(&self, now: i64) -> Result<u64> {
+        require!(!self.is_oracle_priced(), EscrowError::OraclePriced);
+        require!(!self.is_collection_offer(), EscrowError::CollectionOffer);
+        if !self.is_dutch() || now <= self.decay_start {
+            return Ok(self.receive);
+        }
+        if now >= self.decay_end {
+            return Ok(self.end_receive);
+        }
+
+        let elapsed = (now - self.decay_start) as u128;
+        let duration = (self.decay_end - self.decay_start) as u128;
+        let range = self
+            .receive
+            .checked_sub(self.end_receive)
+            .ok_or(EscrowError::MathOverflow)? as u128;
+        let decayed = range
+            .checked_mul(elapsed)
+            .ok_or(EscrowError::MathOverflow)?
+            / duration;
+
+        // decayed < range <= receive, 不会下溢
+        Ok(self.receive - decayed as u64)
+    }
+
+    // 按单价 receive / deposited 计算换取 amount_a 个 token A 需要支付的 token B 数量
+    pub fn receive_for(&self, amount_a: u64) -> Result<u64> {
+        self.pro_rata(amount_a, self.receive)
+    }
+
+    // 计算 amount_a * receive / deposited
+    // 使用 u128 防止中间结果溢出, 并向上取整, 保证舍入误差总是对 maker 有利
+    pub fn pro_rata(&self, amount_a: u64, receive: u64) -> Result<u64> {
+        let numerator = (amount_a as u128)
+            .checked_mul(receive as u128)
+            .ok_or(EscrowError::MathOverflow)?;
+        let amount_b = numerator.div_ceil(self.deposited as u128);
+
+        u64::try_from(amount_b).map_err(|_| EscrowError::MathOverflow.into())
+    }
+}
+
+// 手续费比例的分母, fee_bps 以万分之一为单位
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+// Config 中最多保存的手续费档位数量
+pub const MAX_FEE_TIERS: usize = 4;
+
+// Config 最多记录的可信路由程序个数
+pub const MAX_TRUSTED_ROUTERS: usize = 4;
+
+// Config 最多记录的运营方个数
+pub const MAX_OPERATORS: usize = 4;
+
+// taker 累计成交达到 min_trades 次之后适用的手续费比例
+#[derive(
+    AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug,
+)]
+pub struct FeeTier {
+    pub min_trades: u64,
+    // 单位为 bps, 代替 Config::fee_bps
+    pub fee_bps: u16,
+}
+
+#[derive(InitSpace)]
+#[account(discriminator = 2)] // 和 Escrow 一样使用自定义的 1 字节标识符
+pub struct Config {
+    // 协议管理员, 可以修改手续费等配置
+    pub admin: Pubkey,
+    // 等待接受的新管理员, Pubkey::default() 表示没有待处理的转移
+    pub pending_admin: Pubkey,
+    // 成交时从 token B 中收取的手续费比例, 单位为 bps
+    pub fee_bps: u16,
+    // 传入 referrer 账户时分给 referrer 的手续费比例, 单位为手续费的 bps
+    pub referral_bps: u16,
+    // 紧急暂停开关, 暂停时不能创建和成交托管, 但仍然可以退还
+    pub paused: bool,
+    // take 按 taker 的 UserStats 选择的手续费档位, 按 min_trades 升序, 只有前 fee_tier_count 个有效
+    // fee_tier_count 为 0 时不启用, 所有 taker 都按 fee_bps 收取
+    pub fee_tiers: [FeeTier; MAX_FEE_TIERS],
+    pub fee_tier_count: u8,
+    // make 向 treasury 收取的固定挂单费, 单位为 lamports, 0 表示不收取; 豁免列表中的 maker 不收取, 见 ExemptList
+    pub listing_fee_lamports: u64,
+    // 接收挂单费的账户, 挂单费不为 0 时不能是 Pubkey::default()
+    pub treasury: Pubkey,
+    // 托管创建超过这么多秒后被视为已放弃, 任何人都可以 sweep_abandoned 清理; 0 表示不启用
+    pub abandon_seconds: i64,
+    // sweep_abandoned 回收的租金中分给 cranker 的比例, 单位为 bps, 其余还给 rent_payer; token A 总是全部退还给 maker
+    pub abandon_cranker_bps: u16,
+    // take_with_swap 允许 CPI 的兑换路由程序(例如 Jupiter), Pubkey::default() 表示不允许
+    pub swap_router: Pubkey,
+    // 为 true 时 take 的小费也按 fee_bps 收取手续费, 默认只对成交价格收取
+    pub fee_on_tips: bool,
+    // 可以通过 CPI 成交 direct_only 托管的路由程序, 只有前 trusted_router_count 个有效
+    pub trusted_routers: [Pubkey; MAX_TRUSTED_ROUTERS],
+    pub trusted_router_count: u8,
+    // 可以通过 operator_refund 代 maker 退还托管的运营方, 只有前 operator_count 个有效
+    // 可以是钱包, 也可以是程序: 程序必须用自己派生的 [OPERATOR_SEED] PDA 通过 invoke_signed 签名
+    pub operators: [Pubkey; MAX_OPERATORS],
+    pub operator_count: u8,
+    // fee_authority PDA 的 bump, fee_authority 是所有手续费 ATA 的 authority
+    pub fee_authority_bump: u8,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+impl Config {
+    // 在 #[access_control] 中调用, 协议暂停时拒绝执行指令
+    pub fn check_not_paused(&self) -> Result<()> {
+        require!(!self.paused, EscrowError::ProtocolPaused);
+
+        Ok(())
+    }
+
+    // 计算 amount 个 token B 中应收取的手续费, 向下取整, 舍入误差对 maker 有利
+    pub fn fee_for(&self, amount: u64) -> Result<u64> {
+        bps_of(amount, self.fee_bps)
+    }
+
+    pub fn fee_tiers(&self) -> &[FeeTier] {
+        &self.fee_tiers[..self.fee_tier_count as usize]
+    }
+
+    pub fn is_trusted_router(&self, program: &Pubkey) -> bool {
+        self.trusted_routers[..self.trusted_router_count as usize].contains(program)
+    }
+
+    // operator_refund 的签名账户必须是登记的运营方钱包, 或者登记的运营方程序的 [OPERATOR_SEED] PDA
+    // PDA 只能由派生它的程序签名, 因此不需要读取 instructions sysvar 判断调用方
+    pub fn check_operator(&self, signer: &Pubkey) -> Result<()> {
+        let operators = &self.operators[..self.operator_count as usize];
+        require!(
+            operators.contains(signer)
+                || operators
+                    .iter()
+                    .any(|program| find_operator_authority_address(program).0 == *signer),
+            EscrowError::UnauthorizedOperator
+        );
+        Ok(())
+    }
+
+    // 已经成交过 trades 次的 taker 适用的手续费比例: 满足 min_trades 的最高档位, 一个都不满足时是 fee_bps
+    pub fn tier_fee_bps(&self, trades: u64) -> u16 {
+        self.fee_tiers()
+            .iter()
+            .rev()
+            .find(|tier| trades >= tier.min_trades)
+            .map_or(self.fee_bps, |tier| tier.fee_bps)
+    }
+
+    // 和 fee_for 相同, 但按 taker 的成交次数选择档位
+    pub fn tier_fee_for(&self, amount: u64, trades: u64) -> Result<u64> {
+        bps_of(amount, self.tier_fee_bps(trades))
+    }
+
+    // 成交指令按 taker 本次成交之前的统计计算手续费, 启用档位时必须传入 taker 的统计账户
+    pub fn taker_fee_for(&self, amount: u64, taker_stats: Option<&UserStats>) -> Result<u64> {
+        match (taker_stats, self.fee_tier_count) {
+            (_, 0) => self.fee_for(amount),
+            (Some(taker_stats), _) => self.tier_fee_for(amount, taker_stats.trades),
+            (None, _) => err!(EscrowError::MissingUserStats),
+        }
+    }
+
+    // 计算手续费中分给 referrer 的部分, 向下取整, 结果不会超过 fee
+    pub fn referral_for(&self, fee: u64) -> Result<u64> {
+        bps_of(fee, self.referral_bps)
+    }
+
+    // 创建于 created_at 的托管在 now 时是否已经被放弃, abandon_seconds 为 0 时没有托管会被放弃
+    pub fn is_abandoned(&self, created_at: i64, now: i64) -> bool {
+        self.abandon_seconds > 0 && now.saturating_sub(created_at) > self.abandon_seconds
+    }
+
+    // 计算回收的 lamports 租金中分给 cranker 的部分, 向下取整, 舍入误差对 rent_payer 有利
+    pub fn cranker_share_for(&self, lamports: u64) -> Result<u64> {
+        bps_of(lamports, self.abandon_cranker_bps)
+    }
+}
+
+// 计算 amount 的 bps / 10000, 向下取整
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(EscrowError::MathOverflow)?
+        / BPS_DENOMINATOR as u128;
+
+    Ok(u64::try_from(value).map_err(|_| EscrowError::MathOverflow)?)
+}
+
+#[derive(InitSpace)]
+#[account(discriminator = 3)]
+pub struct CounterOffer {
+    // 被还价的托管账户
+    pub escrow: Pubkey,
+    // 提出还价的 taker
+    pub taker: Pubkey,
+    // taker 用来支付的 token B 的 mint 账户地址
+    pub mint_b: Pubkey,
+    // taker 愿意为托管中剩余的全部 token A 支付的 token B 数量
+    pub receive: u64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+#[derive(InitSpace)]
+#[account(discriminator = 4)]
+pub struct MakerCounter {
+    // 计数器所属的 maker
+    pub maker: Pubkey,
+    // make_auto 下一次使用的 escrow seed, 从 0 开始递增, 小于它的 seed 都已经被 make_auto 用过
+    pub next_seed: u64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+#[derive(InitSpace)]
+#[account(discriminator = 5)]
+pub struct TradeReceipt {
+    // 成交的托管账户, 成交后已经关闭, 只用来派生 receipt 的地址
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    // 成交的 taker, 支付 receipt 的租金, 关闭时租金还给它
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 从 vault 转给 taker 的 token A 数量
+    pub amount_a: u64,
+    // taker 支付的 token B 数量, 包含手续费
+    pub amount_b: u64,
+    // 收取的 token B 手续费总额(包含 referrer 的部分)
+    pub fee: u64,
+    // 成交时间戳(unix 秒)
+    pub timestamp: i64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+// 协议的全局统计, 供看板直接读取; make 和 make_dutch, make_auto 共用 make 的账户, 同样计入, make_batch 每一档各计一次
+// 所有关闭托管的指令都会减少 open_escrows: 成交计入 total_takes(部分成交只在最后一次计入), 退还和 refund_expired, sweep_abandoned, recover_vault 等计入 total_refunds
+// relist 和 transfer_maker 用新托管替换旧托管, 不计入
+#[derive(InitSpace)]
+#[account(discriminator = 6)]
+pub struct GlobalStats {
+    // 创建过的托管总数
+    pub total_escrows_created: u64,
+    // 当前仍未关闭的托管数
+    pub open_escrows: u64,
+    // 成交次数
+    pub total_takes: u64,
+    // 退还次数
+    pub total_refunds: u64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+// 统计只用于展示, 计算失败时记录日志并保持原值, 不能因此让创建或关闭托管的指令失败
+impl GlobalStats {
+    pub fn record_make(&mut self) {
+        self.total_escrows_created = increment(self.total_escrows_created, "total_escrows_created");
+        self.open_escrows = increment(self.open_escrows, "open_escrows");
+    }
+
+    // 统计账户晚于托管创建时 open_escrows 可能已经是 0, 递减不会下溢
+    pub fn record_take(&mut self) {
+        self.total_takes = increment(self.total_takes, "total_takes");
+        self.open_escrows = self.open_escrows.saturating_sub(1);
+    }
+
+    pub fn record_refund(&mut self) {
+        self.total_refunds = increment(self.total_refunds, "total_refunds");
+        self.open_escrows = self.open_escrows.saturating_sub(1);
+    }
+}
+
+fn increment(value: u64, name: &str) -> u64 {
+    value.checked_add(1).unwrap_or_else(|| {
+        msg!("Stats counter {} overflowed", name);
+        value
+    })
+}
+
+// 每个钱包的累计成交统计, take, take_partial 和分期成交按 taker 的 trades 选择 Config 中的手续费档位
+// taker 的统计账户在成交时由 taker 支付租金创建, maker 需要先调用 init_user_stats 创建, 传入成交指令时才会被计入
+// 不同 mint 的数量无法比较, 因此档位只按成交次数选择, volume_b 只用于展示
+#[derive(InitSpace)]
+#[account(discriminator = 13)]
+pub struct UserStats {
+    // 统计账户所属的钱包
+    pub wallet: Pubkey,
+    // 作为 maker 或 taker 参与的成交次数
+    pub trades: u64,
+    // 累计成交的 token B 数量, 不区分 mint
+    pub volume_b: u64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+// 和 GlobalStats 一样, 计算失败时记录日志并保持原值, 不能因此让 take 失败
+impl UserStats {
+    // taker 的统计账户可能是本次 init_if_needed 创建的, 先写入 wallet 和 bump
+    pub fn record_taker_trade(&mut self, wallet: Pubkey, bump: u8, amount_b: u64) {
+        self.wallet = wallet;
+        self.bump = bump;
+        self.record_trade(amount_b);
+    }
+
+    pub fn record_trade(&mut self, amount_b: u64) {
+        self.trades = increment(self.trades, "trades");
+        self.volume_b = self.volume_b.checked_add(amount_b).unwrap_or_else(|| {
+            msg!("Stats counter volume_b overflowed");
+            self.volume_b
+        });
+    }
+}
+
+// maker 仍未关闭的托管的 seed 列表, 让前端不用 getProgramAccounts 就能找到 maker 的所有托管
+// 通过 make(以及 make_dutch, make_auto, make_batch)创建的托管会被加入, 所有关闭这些托管的指令都会把 seed 移除, relist 用新 seed 替换旧 seed, transfer_maker 把 seed 移到新 maker 的索引
+// 空间随 seeds 增长, 移除时不缩小, 空间和租金在 close_registry 时一起取回
+#[account(discriminator = 7)]
+#[cfg_attr(test, derive(Debug))]
+pub struct MakerRegistry {
+    // 索引所属的 maker
+    pub maker: Pubkey,
+    // 缓存的 bump 值
+    pub bump: u8,
+    // 仍未关闭的托管的 seed, 按创建顺序排列
+    pub seeds: Vec<u64>,
+}
+
+impl MakerRegistry {
+    // 同时列出的托管数上限, 超过时 make 返回 RegistryFull
+    pub const MAX_SEEDS: usize = 64;
+
+    // 保存 len 个 seed 需要的账户大小: discriminator, maker, bump, Vec 的 4 字节长度和 seeds
+    pub fn space(len: usize) -> usize {
+        Self::DISCRIMINATOR.len() + 32 + 1 + 4 + 8 * len
+    }
+
+    // 关闭托管的指令调用; 索引不存在时(托管早于索引创建)仍然是空的系统账户, 什么也不做
+    pub fn remove_seed(info: &AccountInfo, seed: u64) -> Result<()> {
+        if info.owner != &crate::ID {
+            return Ok(());
+        }
+
+        let mut registry = MakerRegistry::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        registry.seeds.retain(|open| *open != seed);
+        registry.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])
+    }
+}
+
+// 管理员维护的可交易 mint 列表, enabled 时 make 要求 mint A 和 mint B 都在列表中
+// 只在创建托管时检查, 列表修改前创建的托管仍然可以成交和退还
+#[account(discriminator = 8)]
+#[cfg_attr(test, derive(Debug))]
+pub struct MintAllowlist {
+    // 是否启用白名单, 关闭时列表保留但不检查
+    pub enabled: bool,
+    // 缓存的 bump 值
+    pub bump: u8,
+    // 允许托管的 mint
+    pub mints: Vec<Pubkey>,
+}
+
+impl MintAllowlist {
+    // 保存 len 个 mint 需要的账户大小: discriminator, enabled, bump, Vec 的 4 字节长度和 mints
+    pub fn space(len: usize) -> usize {
+        Self::DISCRIMINATOR.len() + 1 + 1 + 4 + 32 * len
+    }
+
+    // 创建托管的指令调用; 白名单不存在时(从未设置过)仍然是空的系统账户, 视为未启用
+    pub fn check(info: &AccountInfo, mints: &[Pubkey]) -> Result<()> {
+        if info.owner != &crate::ID {
+            return Ok(());
+        }
+
+        let allowlist = MintAllowlist::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        if allowlist.enabled {
+            for mint in mints {
+                if !allowlist.mints.contains(mint) {
+                    msg!("Mint {} is not on the allowlist", mint);
+                    return err!(EscrowError::MintNotAllowed);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// 管理员逐个添加的禁止交易的 mint, 用来快速封禁诈骗 token
+// 所有创建和成交托管的指令都检查, 封禁前创建的托管只能退还
+// 和 MintAllowlist 一样是必须传入的账户, 从未创建黑名单的部署传入的是空的系统账户
+#[account(discriminator = 9)]
+#[cfg_attr(test, derive(Debug))]
+pub struct MintBlocklist {
+    // 缓存的 bump 值
+    pub bump: u8,
+    // 被封禁的 mint
+    pub mints: Vec<Pubkey>,
+}
+
+impl MintBlocklist {
+    // 保存 len 个 mint 需要的账户大小: discriminator, bump, Vec 的 4 字节长度和 mints
+    pub fn space(len: usize) -> usize {
+        Self::DISCRIMINATOR.len() + 1 + 4 + 32 * len
+    }
+
+    // 创建和成交托管的指令调用; 黑名单不存在时(从未添加过)仍然是空的系统账户, 视为空列表
+    pub fn check(info: &AccountInfo, mints: &[Pubkey]) -> Result<()> {
+        if info.owner != &crate::ID {
+            return Ok(());
+        }
+
+        MintBlocklist::try_deserialize(&mut &info.try_borrow_data()?[..])?.check_mints(mints)
+    }
+
+    // 任何一个 mint 被封禁时返回 MintBlocked
+    pub fn check_mints(&self, mints: &[Pubkey]) -> Result<()> {
+        for mint in mints {
+            if self.mints.contains(mint) {
+                msg!("Mint {} is blocked", mint);
+                return err!(EscrowError::MintBlocked);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// 管理员维护的免收挂单费的钱包, 按 maker 检查, 结构和 MintBlocklist 相同
+// 在 make 中是可选账户, 不传时 maker 按 config.listing_fee_lamports 支付挂单费
+#[account(discriminator = 14)]
+#[cfg_attr(test, derive(Debug))]
+pub struct ExemptList {
+    // 缓存的 bump 值
+    pub bump: u8,
+    // 被豁免的钱包
+    pub wallets: Vec<Pubkey>,
+}
+
+impl ExemptList {
+    // 保存 len 个钱包需要的账户大小: discriminator, bump, Vec 的 4 字节长度和 wallets
+    pub fn space(len: usize) -> usize {
+        Self::DISCRIMINATOR.len() + 1 + 4 + 32 * len
+    }
+
+    pub fn is_exempt(&self, wallet: &Pubkey) -> bool {
+        self.wallets.contains(wallet)
+    }
+}
+
+// take_with_sig 成交的签名订单, 地址由 taker 和 nonce 派生, 已经存在时同一个订单不能再次提交
+// 租金由 relayer 支付, 账户不会关闭: 关闭后同一个签名可以在 maker 用相同 seed 重新创建托管时被重放
+#[derive(InitSpace)]
+#[account(discriminator = 10)]
+pub struct TakeOrder {
+    pub taker: Pubkey,
+    pub nonce: u64,
+    // 订单成交的托管账户, 成交后已经关闭
+    pub escrow: Pubkey,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+// commit_take 记录的承诺, 地址由 taker 和哈希派生, 不暴露 taker 要成交的托管
+// reveal_take 成交后关闭, 没有成交的承诺由 taker 通过 close_commit 取回租金
+#[derive(InitSpace)]
+#[account(discriminator = 11)]
+pub struct TakeCommit {
+    pub taker: Pubkey,
+    // sha256(taker ∥ escrow ∥ salt)
+    pub hash: [u8; 32],
+    // commit_take 所在的 slot
+    pub slot: u64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+impl TakeCommit {
+    pub fn hash(taker: &Pubkey, escrow: &Pubkey, salt: &[u8; 32]) -> [u8; 32] {
+        hashv(&[taker.as_ref(), escrow.as_ref(), salt]).to_bytes()
+    }
+}
+
+// 一篮子托管中最多存入的 mint 数, 由 compute_units 测试按 take_basket 在默认的 200k 预算内确定
+// 每个 mint 需要 3 个 remaining_accounts, 还要为 taker 创建 ATA
+pub const MAX_BASKET_LEGS: usize = 4;
+
+// 一篮子托管中的一个 mint 和存入的数量, vault 是 basket 的 ATA
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    // vault 实际收到的数量, 已经扣除 mint 的转账手续费
+    pub amount: u64,
+}
+
+// make_basket 创建的托管: maker 存入多种 token, taker 支付 receive 个 token B 一次取走全部
+// 和 Escrow 分开存放, 不支持部分成交, 期限和 taker 限制等条款, 也不计入 MakerRegistry 和 GlobalStats
+#[derive(InitSpace)]
+#[account(discriminator = 12)]
+pub struct EscrowBasket {
+    pub seed: u64,
+    pub maker: Pubkey,
+    // taker 支付的 token 的 mint 账户地址
+    pub mint_b: Pubkey,
+    // 取走全部 leg 需要支付的 token B 数量
+    pub receive: u64,
+    // 前 leg_count 个有效, 其余为默认值
+    pub legs: [BasketLeg; MAX_BASKET_LEGS],
+    pub leg_count: u8,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+impl EscrowBasket {
+    pub fn legs(&self) -> &[BasketLeg] {
+        &self.legs[..self.leg_count as usize]
+    }
+}
+
+// maker 的默认挂单参数, 由 set_prefs 设置; make_v2 传入时只填充 MakeArgs 中没有指定(None)的字段, 不会覆盖指定的值
+#[derive(InitSpace)]
+#[account(discriminator = 15)]
+pub struct MakerPrefs {
+    // 默认参数所属的 maker
+    pub maker: Pubkey,
+    // 没有指定 expiry 时托管的有效期(秒), 从 make_v2 执行时开始计算; 0 表示不过期
+    pub expiry_duration: i64,
+    // 没有指定 receive_to 时接收 token B 的钱包, Pubkey::default() 表示 maker 自己
+    pub receive_to: Pubkey,
+    // 没有指定 reject_freezable 时是否拒绝设置了 freeze authority 的 mint
+    pub reject_freezable: bool,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+// (mint_a, mint_b) 交易对的索引, 前端不用扫描全部托管就能展示某个交易对的挂单数量和最低价格
+// make_v2 以 pair_index 模式创建托管时计入, 所有关闭托管的指令传入时都会移除, relist 和 transfer_maker 用新托管替换旧托管
+// 索引只是提示: 所有更新都不会失败, 计数在 0 时不再减少, 不传索引的成交和退还不会因此被拒绝
+#[derive(InitSpace)]
+#[account(discriminator = 16)]
+pub struct PairIndex {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    // 计入索引并且仍未关闭的托管数量
+    pub open_escrows: u64,
+    // 计入索引的托管中按 receive / deposited 单价最低的一个, 它关闭后清空, 直到下一次 make 重新记录
+    // 没有时为 Pubkey::default()
+    pub best_escrow: Pubkey,
+    pub best_receive: u64,
+    pub best_deposited: u64,
+    // 缓存的 bump 值
+    pub bump: u8,
+}
+
+impl PairIndex {
+    // 计入新创建的托管, 单价低于当前最低价格时替换 best_escrow; 单价相同时保留先创建的
+    pub fn record_make(&mut self, escrow: Pubkey, receive: u64, deposited: u64) {
+        self.open_escrows = self.open_escrows.saturating_add(1);
+        // 交叉相乘比较 receive / deposited, u64 的乘积不会超过 u128
+        let cheaper = self.best_escrow == Pubkey::default()
+            || (receive as u128) * (self.best_deposited as u128)
+                < (self.best_receive as u128) * (deposited as u128);
+        if cheaper {
+            self.best_escrow = escrow;
+            self.best_receive = receive;
+            self.best_deposited = deposited;
+        }
+    }
+
+    // 计入索引的托管关闭时移除, 它是最低价格的托管时清空 best_escrow
+    pub fn record_close(&mut self, escrow: &Pubkey) {
+        self.open_escrows = self.open_escrows.saturating_sub(1);
+        if self.best_escrow == *escrow {
+            self.best_escrow = Pubkey::default();
+            self.best_receive = 0;
+            self.best_deposited = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Discriminator;
+    use proptest::prelude::*;
+
+    fn pubkey() -> impl Strategy<Value = Pubkey> {
+        any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+    }
+
+    fn status() -> impl Strategy<Value = EscrowStatus> {
+        prop_oneof![
+            Just(EscrowStatus::Open),
+            Just(EscrowStatus::PendingCounter),
+            Just(EscrowStatus::PartiallyFilled),
+            Just(EscrowStatus::Frozen),
+            Just(EscrowStatus::Locked),
+            Just(EscrowStatus::Cancelled),
+            Just(EscrowStatus::Streaming),
+        ]
+    }
+
+    fn payment_mint() -> impl Strategy<Value = PaymentMint> {
+        (pubkey(), any::<u64>()).prop_map(|(mint, receive)| PaymentMint { mint, receive })
+    }
+
+    // 字段数量超过 proptest 元组的上限, 分成几组生成
+    fn escrow() -> impl Strategy<Value = Escrow> {
+        (
+            (
+                any::<u64>(),
+                pubkey(),
+                pubkey(),
+                pubkey(),
+                pubkey(),
+                pubkey(),
+            ),
+            (
+                any::<u64>(),
+                any::<u64>(),
+                any::<u64>(),
+                any::<i64>(),
+                any::<i64>(),
+                any::<u64>(),
+            ),
+            (
+                pubkey(),
+                pubkey(),
+                pubkey(),
+                any::<[u8; 32]>(),
+                any::<[u8; 32]>(),
+            ),
+            (
+                any::<i64>(),
+                any::<i64>(),
+                any::<u64>(),
+                pubkey(),
+                any::<i64>(),
+                status(),
+                status(),
+                any::<u8>(),
+            ),
+            (
+                any::<i64>(),
+                pubkey(),
+                any::<i64>(),
+                any::<u64>(),
+                any::<i64>(),
+            ),
+            [payment_mint(), payment_mint(), payment_mint()],
+            (any::<bool>(), any::<u8>(), pubkey()),
+            (
+                pubkey(),
+                any::<i32>(),
+                any::<i64>(),
+                any::<u16>(),
+                pubkey(),
+                any::<bool>(),
+                any::<u8>(),
+                any::<bool>(),
+                any::<u8>(),
+                any::<u64>(),
+                any::<bool>(),
+                any::<bool>(),
+            ),
+            (
+                any::<i64>(),
+                any::<u64>(),
+                any::<u64>(),
+                any::<i64>(),
+                any::<bool>(),
+            ),
+        )
+            .prop_map(
+                |(
+                    (seed, maker, rent_payer, receive_to, mint_a, mint_b),
+                    (receive, deposited, amount, expiry, start_time, commit_delay_slots),
+                    (allowed_taker, refund_delegate, arbiter, taker_allowlist_root, hashlock),
+                    (
+                        decay_start,
+                        decay_end,
+                        end_receive,
+                        locked_taker,
+                        locked_at,
+                        status,
+                        status_before_freeze,
+                        bump,
+                    ),
+                    (reservation_window, reserved_taker, reservation_expiry, bond, created_at),
+                    alt_payments,
+                    (recurring, max_refills, vault),
+                    (
+                        price_feed,
+                        quote_exponent,
+                        max_staleness,
+                        max_deviation_bps,
+                        collection,
+                        enforce_royalties,
+                        tag,
+                        pair_indexed,
+                        cancel_reason,
+                        cancelled_slot,
+                        direct_only,
+                        maker_paused,
+                    ),
+                    (stream_window, stream_receive, paid_b, stream_deadline, allow_operators),
+                )| Escrow {
+                    version: Escrow::VERSION,
+                    seed,
+                    maker,
+                    rent_payer,
+                    receive_to,
+                    mint_a,
+                    mint_b,
+                    receive,
+                    deposited,
+                    amount,
+                    expiry,
+                    start_time,
+                    created_at,
+                    commit_delay_slots,
+                    reservation_window,
+                    allowed_taker,
+                    refund_delegate,
+                    arbiter,
+                    taker_allowlist_root,
+                    hashlock,
+                    decay_start,
+                    decay_end,
+                    end_receive,
+                    alt_payments,
+                    locked_taker,
+                    locked_at,
+                    reserved_taker,
+                    reservation_expiry,
+                    bond,
+                    recurring,
+                    max_refills,
+                    status,
+                    status_before_freeze,
+                    bump,
+                    vault,
+                    price_feed,
+                    quote_exponent,
+                    max_staleness,
+                    max_deviation_bps,
+                    collection,
+                    enforce_royalties,
+                    tag,
+                    pair_indexed,
+                    cancel_reason,
+                    cancelled_slot,
+                    direct_only,
+                    maker_paused,
+                    stream_window,
+                    stream_receive,
+                    paid_b,
+                    stream_deadline,
+                    allow_operators,
+                },
+            )
+    }
+
+    fn serialize(escrow: &Escrow) -> Vec<u8> {
+        let mut data = Vec::new();
+        escrow.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    proptest! {
+        // make 按 DISCRIMINATOR.len() + INIT_SPACE 分配空间, 序列化结果必须正好填满
+        #[test]
+        fn serialized_escrow_fills_init_space(escrow in escrow()) {
+            let data = serialize(&escrow);
+
+            prop_assert_eq!(data.len(), Escrow::DISCRIMINATOR.len() + Escrow::INIT_SPACE);
+            prop_assert_eq!(&data[..Escrow::DISCRIMINATOR.len()], Escrow::DISCRIMINATOR);
+        }
+
+        // Escrow 没有实现 PartialEq, 通过再次序列化比较字节
+        #[test]
+        fn escrow_round_trips(escrow in escrow()) {
+            let data = serialize(&escrow);
+            let decoded = Escrow::try_deserialize(&mut data.as_slice()).unwrap();
+
+            prop_assert_eq!(serialize(&decoded), data);
+            prop_assert_eq!(decoded.maker, escrow.maker);
+            prop_assert_eq!(decoded.amount, escrow.amount);
+            prop_assert_eq!(decoded.bump, escrow.bump);
+        }
+
+        // 旧版本的长度不同, 直接读取时要求迁移, 通过 try_deserialize_legacy 读取的字段和当前版本相同, 之后加入的字段为默认值
+        #[test]
+        fn legacy_escrow_requires_migration(mut escrow in escrow()) {
+            escrow.price_feed = Pubkey::default();
+            escrow.quote_exponent = 0;
+            escrow.max_staleness = 0;
+            escrow.max_deviation_bps = 0;
+            escrow.collection = Pubkey::default();
+            escrow.enforce_royalties = false;
+            escrow.tag = 0;
+            escrow.pair_indexed = false;
+            escrow.cancel_reason = 0;
+            escrow.cancelled_slot = 0;
+            escrow.direct_only = false;
+            escrow.maker_paused = false;
+            escrow.stream_window = 0;
+            escrow.stream_receive = 0;
+            escrow.paid_b = 0;
+            escrow.stream_deadline = 0;
+            escrow.allow_operators = false;
+            let data = serialize(&escrow);
+
+            // 版本 11 没有 allow_operators, 版本 10 没有最后的分期成交字段, 版本 9 没有 maker_paused, 版本 8 没有 direct_only, 版本 7 还没有 cancel_reason 和 cancelled_slot, 版本 6 还没有 tag 和 pair_indexed,
+            // 版本 5 还没有 enforce_royalties, 版本 4 还没有 collection, 版本 3 还没有 max_deviation_bps, 版本 2 还没有其他预言机字段
+            for (version, len) in [
+                (11, Escrow::V11_SPACE),
+                (10, Escrow::V10_SPACE),
+                (9, Escrow::V9_SPACE),
+                (8, Escrow::V8_SPACE),
+                (7, Escrow::V7_SPACE),
+                (6, Escrow::V6_SPACE),
+                (5, Escrow::V5_SPACE),
+                (4, Escrow::V4_SPACE),
+                (3, Escrow::V3_SPACE),
+                (2, Escrow::V2_SPACE),
+            ] {
+                let mut legacy = data[..len].to_vec();
+                legacy[Escrow::DISCRIMINATOR.len()] = version;
+                let error = Escrow::try_deserialize(&mut legacy.as_slice()).unwrap_err();
+                prop_assert_eq!(error, EscrowError::MigrationRequired.into());
+                let mut decoded = Escrow::try_deserialize_legacy(&legacy).unwrap();
+                prop_assert_eq!(decoded.version, version);
+                decoded.version = Escrow::VERSION;
+                prop_assert_eq!(&serialize(&decoded), &data);
+            }
+
+            // 版本 1 还没有 version 字节
+            let mut v1 = data[..Escrow::V2_SPACE].to_vec();
+            v1.remove(Escrow::DISCRIMINATOR.len());
+            prop_assert_eq!(v1.len(), Escrow::V1_SPACE);
+            let error = Escrow::try_deserialize(&mut v1.as_slice()).unwrap_err();
+            prop_assert_eq!(error, EscrowError::MigrationRequired.into());
+            let mut decoded = Escrow::try_deserialize_legacy(&v1).unwrap();
+            prop_assert_eq!(decoded.version, 1);
+            decoded.version = Escrow::VERSION;
+            prop_assert_eq!(&serialize(&decoded), &data);
+
+            // 加入 vault 字段之前创建的托管读出的 vault 为空, 由 migrate_escrow 填入
+            v1.truncate(Escrow::V1_SPACE_WITHOUT_VAULT);
+            let decoded = Escrow::try_deserialize_legacy(&v1).unwrap();
+            prop_assert_eq!(decoded.vault, Pubkey::default());
+            prop_assert_eq!(decoded.bump, escrow.bump);
+            prop_assert!(Escrow::try_deserialize_legacy(&data).is_err());
+        }
+
+        // 其他账户类型的 discriminator 或任意错误的第一个字节都不能被当作 Escrow 读取
+        #[test]
+        fn non_canonical_discriminator_is_rejected(escrow in escrow(), first in 0u8..=255) {
+            prop_assume!(first != Escrow::DISCRIMINATOR[0]);
+            let mut data = serialize(&escrow);
+            data[0] = first;
+
+            let error = Escrow::try_deserialize(&mut data.as_slice()).unwrap_err();
+            prop_assert_eq!(
+                error,
+                ErrorCode::AccountDiscriminatorMismatch.into()
+            );
+        }
+    }
+
+    #[test]
+    fn discriminators_are_distinct_single_bytes() {
+        let discriminators = [
+            Escrow::DISCRIMINATOR,
+            Config::DISCRIMINATOR,
+            CounterOffer::DISCRIMINATOR,
+            MakerCounter::DISCRIMINATOR,
+            TradeReceipt::DISCRIMINATOR,
+            GlobalStats::DISCRIMINATOR,
+            MakerRegistry::DISCRIMINATOR,
+            MintAllowlist::DISCRIMINATOR,
+            MintBlocklist::DISCRIMINATOR,
+            TakeOrder::DISCRIMINATOR,
+            TakeCommit::DISCRIMINATOR,
+            EscrowBasket::DISCRIMINATOR,
+            UserStats::DISCRIMINATOR,
+            ExemptList::DISCRIMINATOR,
+        ];
+
+        assert_eq!(
+            discriminators,
+            [
+                &[1][..],
+                &[2],
+                &[3],
+                &[4],
+                &[5],
+                &[6],
+                &[7],
+                &[8],
+                &[9],
+                &[10],
+                &[11],
+                &[12],
+                &[13],
+                &[14]
+            ]
+        );
+    }
+
+    #[test]
+    fn registry_space_matches_serialized_size() {
+        for len in [0, 1, MakerRegistry::MAX_SEEDS] {
+            let registry = MakerRegistry {
+                maker: Pubkey::new_unique(),
+                bump: 255,
+                seeds: (0..len as u64).collect(),
+            };
+            let mut data = Vec::new();
+            registry.try_serialize(&mut data).unwrap();
+
+            assert_eq!(data.len(), MakerRegistry::space(len));
+        }
+    }
+
+    #[test]
+    fn basket_space_fits_the_full_cap() {
+        let leg = BasketLeg {
+            mint: Pubkey::new_unique(),
+            amount: u64::MAX,
+        };
+        let basket = EscrowBasket {
+            seed: u64::MAX,
+            maker: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            receive: u64::MAX,
+            legs: [leg; MAX_BASKET_LEGS],
+            leg_count: MAX_BASKET_LEGS as u8,
+            bump: 255,
+        };
+        let mut data = Vec::new();
+        basket.try_serialize(&mut data).unwrap();
+
+        assert_eq!(
+            data.len(),
+            EscrowBasket::DISCRIMINATOR.len() + EscrowBasket::INIT_SPACE
+        );
+        assert_eq!(basket.legs().len(), MAX_BASKET_LEGS);
+        assert_eq!(
+            EscrowBasket {
+                leg_count: 1,
+                ..basket
+            }
+            .legs(),
+            [leg]
+        );
+    }
+
+    #[test]
+    fn mint_allowlist_space_matches_serialized_size() {
+        for len in [0, 1, 10] {
+            let allowlist = MintAllowlist {
+                enabled: true,
+                bump: 255,
+                mints: (0..len).map(|_| Pubkey::new_unique()).collect(),
+            };
+            let mut data = Vec::new();
+            allowlist.try_serialize(&mut data).unwrap();
+
+            assert_eq!(data.len(), MintAllowlist::space(len));
+        }
+    }
+
+    #[test]
+    fn mint_blocklist_rejects_only_listed_mints() {
+        let (blocked, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let blocklist = MintBlocklist {
+            bump: 255,
+            mints: vec![blocked],
+        };
+        let mut data = Vec::new();
+        blocklist.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), MintBlocklist::space(1));
+
+        assert!(blocklist.check_mints(&[other]).is_ok());
+        assert_eq!(
+            blocklist.check_mints(&[other, blocked]).unwrap_err(),
+            EscrowError::MintBlocked.into()
+        );
+    }
+
+    #[test]
+    fn stats_counters_never_fail() {
+        let mut stats = GlobalStats {
+            total_escrows_created: u64::MAX,
+            open_escrows: 0,
+            total_takes: 0,
+            total_refunds: 0,
+            bump: 0,
+        };
+
+        // 溢出的计数器保持不变, 其他计数器照常更新
+        stats.record_make();
+        assert_eq!(stats.total_escrows_created, u64::MAX);
+        assert_eq!(stats.open_escrows, 1);
+
+        stats.record_take();
+        stats.record_refund();
+        assert_eq!(stats.open_escrows, 0);
+        assert_eq!(stats.total_takes, 1);
+        assert_eq!(stats.total_refunds, 1);
+    }
+
+    #[test]
+    fn pair_index_tracks_the_cheapest_escrow() {
+        let (first, cheaper, pricier) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut index = PairIndex {
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            open_escrows: 0,
+            best_escrow: Pubkey::default(),
+            best_receive: 0,
+            best_deposited: 0,
+            bump: 0,
+        };
+
+        // 单价按 receive / deposited 比较: 150 / 100 低于 200 / 100, 300 / 100 不替换
+        index.record_make(first, 200, 100);
+        index.record_make(cheaper, 450, 300);
+        index.record_make(pricier, 300, 100);
+        assert_eq!(index.open_escrows, 3);
+        assert_eq!(
+            (index.best_escrow, index.best_receive, index.best_deposited),
+            (cheaper, 450, 300)
+        );
+
+        // 其他托管关闭时保留最低价格, 最低价格的托管关闭后清空
+        index.record_close(&first);
+        assert_eq!(index.best_escrow, cheaper);
+        index.record_close(&cheaper);
+        assert_eq!(index.open_escrows, 1);
+        assert_eq!(
+            (index.best_escrow, index.best_receive, index.best_deposited),
+            (Pubkey::default(), 0, 0)
+        );
+
+        // 计数少于实际数量时不再减少, 不会让关闭托管的指令失败
+        index.record_close(&pricier);
+        index.record_close(&pricier);
+        assert_eq!(index.open_escrows, 0);
+        index.record_make(pricier, 300, 100);
+        assert_eq!((index.open_escrows, index.best_escrow), (1, pricier));
+    }
+
+    // 固定字段的偏移量, SDK 和索引服务的 memcmp 过滤依赖这些数字
+    #[test]
+    fn escrow_field_offsets_are_pinned() {
+        assert_eq!(Escrow::MAKER_OFFSET, 10);
+        assert_eq!(Escrow::MINT_A_OFFSET, 106);
+        assert_eq!(Escrow::MINT_B_OFFSET, 138);
+
+        let escrow = Escrow {
+            version: Escrow::VERSION,
+            seed: 0,
+            maker: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            receive_to: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            receive: 0,
+            deposited: 0,
+            amount: 0,
+            expiry: 0,
+            start_time: 0,
+            created_at: 0,
+            commit_delay_slots: 0,
+            reservation_window: 0,
+            allowed_taker: Pubkey::default(),
+            refund_delegate: Pubkey::default(),
+            arbiter: Pubkey::default(),
+            taker_allowlist_root: [0; 32],
+            hashlock: [0; 32],
+            decay_start: 0,
+            decay_end: 0,
+            end_receive: 0,
+            alt_payments: [PaymentMint::default(); 3],
+            locked_taker: Pubkey::default(),
+            locked_at: 0,
+            reserved_taker: Pubkey::default(),
+            reservation_expiry: 0,
+            bond: 0,
+            recurring: false,
+            max_refills: 0,
+            status: EscrowStatus::Open,
+            status_before_freeze: EscrowStatus::Open,
+            bump: 0,
+            vault: Pubkey::new_unique(),
+            price_feed: Pubkey::new_unique(),
+            quote_exponent: 0,
+            max_staleness: 0,
+            max_deviation_bps: 0,
+            collection: Pubkey::new_unique(),
+            enforce_royalties: true,
+            tag: 0xa5,
+            pair_indexed: true,
+            cancel_reason: 0,
+            cancelled_slot: 0,
+            direct_only: false,
+            maker_paused: false,
+            stream_window: 0,
+            stream_receive: 0,
+            paid_b: 0,
+            stream_deadline: 0,
+            allow_operators: false,
+        };
+        let data = serialize(&escrow);
+        let field = |offset: usize| &data[offset..offset + 32];
+
+        // tag 之前的字段都是定长的, 它的偏移量不随 Escrow 的取值变化
+        assert_eq!(Escrow::TAG_OFFSET, 742);
+        assert_eq!(data[Escrow::TAG_OFFSET], escrow.tag);
+
+        assert_eq!(field(Escrow::MAKER_OFFSET), escrow.maker.as_ref());
+        assert_eq!(field(Escrow::MINT_A_OFFSET), escrow.mint_a.as_ref());
+        assert_eq!(field(Escrow::MINT_B_OFFSET), escrow.mint_b.as_ref());
+        assert_eq!(field(Escrow::VAULT_OFFSET), escrow.vault.as_ref());
+        assert_eq!(field(Escrow::PRICE_FEED_OFFSET), escrow.price_feed.as_ref());
+        assert_eq!(field(Escrow::COLLECTION_OFFSET), escrow.collection.as_ref());
+        assert_eq!(data.len(), Escrow::SPACE);
+    }
+
+    // 每个状态下依次执行每种操作, 检查得到的状态和是否允许成交或退还
+    #[test]
+    fn status_transitions_are_exhaustive() {
+        use EscrowStatus::*;
+
+        let escrow_in = |status: EscrowStatus, before_freeze: EscrowStatus| Escrow {
+            status,
+            status_before_freeze: before_freeze,
+            ..escrow_with_status()
+        };
+
+        // (起始状态, 冻结前的状态, 推进到的阶段, 之后的状态, 之后冻结前的状态)
+        let cases = [
+            (Open, Open, PendingCounter, PendingCounter, PendingCounter),
+            (
+                Open,
+                Open,
+                PartiallyFilled,
+                PartiallyFilled,
+                PartiallyFilled,
+            ),
+            (
+                PendingCounter,
+                PendingCounter,
+                Open,
+                PendingCounter,
+                PendingCounter,
+            ),
+            (
+                PendingCounter,
+                PendingCounter,
+                PartiallyFilled,
+                PartiallyFilled,
+                PartiallyFilled,
+            ),
+            (
+                PartiallyFilled,
+                PartiallyFilled,
+                PendingCounter,
+                PartiallyFilled,
+                PartiallyFilled,
+            ),
+            (Frozen, Open, PendingCounter, Frozen, PendingCounter),
+            (
+                Frozen,
+                PendingCounter,
+                PartiallyFilled,
+                Frozen,
+                PartiallyFilled,
+            ),
+            (Frozen, PartiallyFilled, Open, Frozen, PartiallyFilled),
+            (Open, Open, Locked, Locked, Locked),
+            (PendingCounter, PendingCounter, Locked, Locked, Locked),
+            (Locked, Locked, PendingCounter, Locked, Locked),
+            (
+                PartiallyFilled,
+                PartiallyFilled,
+                Streaming,
+                Streaming,
+                Streaming,
+            ),
+            (Streaming, Streaming, PartiallyFilled, Streaming, Streaming),
+        ];
+        for (status, before_freeze, stage, expected, expected_before_freeze) in cases {
+            let mut escrow = escrow_in(status, before_freeze);
+            escrow.advance_status(stage);
+            assert_eq!(
+                (escrow.status, escrow.status_before_freeze),
+                (expected, expected_before_freeze),
+                "{status:?} advanced to {stage:?}"
+            );
+        }
+
+        // 冻结再解冻恢复原来的状态, 重复冻结和解冻没有冻结的托管都会失败
+        for status in [Open, PendingCounter, PartiallyFilled] {
+            let mut escrow = escrow_in(status, status);
+            escrow.freeze().unwrap();
+            assert_eq!(escrow.status, Frozen);
+            assert_eq!(
+                escrow.freeze().unwrap_err(),
+                EscrowError::InvalidEscrowStatus.into()
+            );
+            escrow.unfreeze().unwrap();
+            assert_eq!(escrow.status, status);
+            assert_eq!(
+                escrow.unfreeze().unwrap_err(),
+                EscrowError::InvalidEscrowStatus.into()
+            );
+        }
+
+        // 锁定和分期成交中的托管不能冻结, 也不能解冻
+        for status in [Locked, Streaming] {
+            let mut escrow = escrow_in(status, status);
+            assert_eq!(
+                escrow.freeze().unwrap_err(),
+                EscrowError::InvalidEscrowStatus.into()
+            );
+            assert_eq!(
+                escrow.unfreeze().unwrap_err(),
+                EscrowError::InvalidEscrowStatus.into()
+            );
+        }
+
+        for status in [
+            Open,
+            PendingCounter,
+            PartiallyFilled,
+            Frozen,
+            Locked,
+            Streaming,
+        ] {
+            assert_eq!(
+                status.is_fillable(),
+                !matches!(status, Frozen | Locked | Streaming),
+                "{status:?}"
+            );
+            assert_eq!(
+                status.is_refundable(),
+                !matches!(status, Locked | Streaming),
+                "{status:?}"
+            );
+        }
+    }
+
+    fn escrow_with_status() -> Escrow {
+        Escrow {
+            version: Escrow::VERSION,
+            seed: 0,
+            maker: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            receive_to: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            receive: 1,
+            deposited: 1,
+            amount: 1,
+            expiry: 0,
+            start_time: 0,
+            created_at: 0,
+            commit_delay_slots: 0,
+            reservation_window: 0,
+            allowed_taker: Pubkey::default(),
+            refund_delegate: Pubkey::default(),
+            arbiter: Pubkey::default(),
+            taker_allowlist_root: [0; 32],
+            hashlock: [0; 32],
+            decay_start: 0,
+            decay_end: 0,
+            end_receive: 0,
+            alt_payments: [PaymentMint::default(); 3],
+            locked_taker: Pubkey::default(),
+            locked_at: 0,
+            reserved_taker: Pubkey::default(),
+            reservation_expiry: 0,
+            bond: 0,
+            recurring: false,
+            max_refills: 0,
+            status: EscrowStatus::Open,
+            status_before_freeze: EscrowStatus::Open,
+            bump: 0,
+            vault: Pubkey::default(),
+            price_feed: Pubkey::default(),
+            quote_exponent: 0,
+            max_staleness: 0,
+            max_deviation_bps: 0,
+            collection: Pubkey::default(),
+            enforce_royalties: false,
+            tag: 0,
+            pair_indexed: false,
+            cancel_reason: 0,
+            cancelled_slot: 0,
+            direct_only: false,
+            maker_paused: false,
+            stream_window: 0,
+            stream_receive: 0,
+            paid_b: 0,
+            stream_deadline: 0,
+            allow_operators: false,
+        }
+    }
+
+    #[test]
+    fn only_maker_or_delegate_can_refund() {
+        let delegate = Pubkey::new_unique();
+        let escrow = Escrow {
+            refund_delegate: delegate,
+            ..escrow_with_status()
+        };
+        assert!(escrow.is_refund_authority(&escrow.maker));
+        assert!(escrow.is_refund_authority(&delegate));
+        assert!(!escrow.is_refund_authority(&Pubkey::new_unique()));
+
+        // 没有设置 delegate 时 Pubkey::default() 不是退还密钥
+        let escrow = Escrow {
+            refund_delegate: Pubkey::default(),
+            ..escrow
+        };
+        assert!(!escrow.is_refund_authority(&Pubkey::default()));
+    }
+
+    #[test]
+    fn refill_restores_the_deposit_until_exhausted() {
+        let mut escrow = Escrow {
+            recurring: true,
+            max_refills: 1,
+            deposited: 100,
+            amount: 0,
+            ..escrow_with_status()
+        };
+        assert_eq!(escrow.refill_amount(), Some(100));
+
+        // token A 带转账手续费时 vault 实际收到的更少
+        escrow.refill(98);
+        assert_eq!((escrow.deposited, escrow.amount), (98, 98));
+        assert_eq!(escrow.max_refills, 0);
+        assert_eq!(escrow.refill_amount(), None);
+
+        let escrow = Escrow {
+            recurring: false,
+            max_refills: 1,
+            ..escrow
+        };
+        assert_eq!(escrow.refill_amount(), None);
+    }
+
+    #[test]
+    fn fee_tier_applies_from_its_min_trades() {
+        let mut fee_tiers = [FeeTier::default(); MAX_FEE_TIERS];
+        fee_tiers[0] = FeeTier {
+            min_trades: 10,
+            fee_bps: 50,
+        };
+        fee_tiers[1] = FeeTier {
+            min_trades: 100,
+            fee_bps: 25,
+        };
+        let config = Config {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            fee_bps: 100,
+            referral_bps: 0,
+            paused: false,
+            fee_tiers,
+            fee_tier_count: 2,
+            listing_fee_lamports: 0,
+            treasury: Pubkey::default(),
+            abandon_seconds: 0,
+            abandon_cranker_bps: 0,
+            swap_router: Pubkey::default(),
+            fee_on_tips: false,
+            trusted_routers: [Pubkey::default(); MAX_TRUSTED_ROUTERS],
+            trusted_router_count: 0,
+            operators: [Pubkey::default(); MAX_OPERATORS],
+            operator_count: 0,
+            fee_authority_bump: 0,
+            bump: 0,
+        };
+        assert_eq!(config.tier_fee_bps(0), 100);
+        assert_eq!(config.tier_fee_bps(9), 100);
+        assert_eq!(config.tier_fee_bps(10), 50);
+        assert_eq!(config.tier_fee_bps(99), 50);
+        assert_eq!(config.tier_fee_bps(100), 25);
+        assert_eq!(config.tier_fee_bps(u64::MAX), 25);
+        assert_eq!(config.tier_fee_for(10_000, 10).unwrap(), 50);
+
+        // fee_tier_count 之后的档位不生效
+        let config = Config {
+            fee_tier_count: 1,
+            ..config
+        };
+        assert_eq!(config.tier_fee_bps(100), 50);
+        let config = Config {
+            fee_tier_count: 0,
+            ..config
+        };
+        assert_eq!(config.tier_fee_bps(100), 100);
+    }
+
+    #[test]
+    fn only_registered_operators_and_their_program_pdas_can_operate() {
+        let (wallet, program, stranger) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut operators = [Pubkey::default(); MAX_OPERATORS];
+        operators[0] = wallet;
+        operators[1] = program;
+        let config = Config {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            fee_bps: 0,
+            referral_bps: 0,
+            paused: false,
+            fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            fee_tier_count: 0,
+            listing_fee_lamports: 0,
+            treasury: Pubkey::default(),
+            abandon_seconds: 0,
+            abandon_cranker_bps: 0,
+            swap_router: Pubkey::default(),
+            fee_on_tips: false,
+            trusted_routers: [Pubkey::default(); MAX_TRUSTED_ROUTERS],
+            trusted_router_count: 0,
+            operators,
+            operator_count: 2,
+            fee_authority_bump: 0,
+            bump: 0,
+        };
+        let pda = find_operator_authority_address(&program).0;
+        let unauthorized = EscrowError::UnauthorizedOperator.into();
+
+        assert!(config.check_operator(&wallet).is_ok());
+        assert!(config.check_operator(&pda).is_ok());
+        // 只有 PDA 能证明调用方是运营方程序, 程序 ID 和其他程序的 PDA 都不行
+        assert_eq!(config.check_operator(&stranger).unwrap_err(), unauthorized);
+        assert_eq!(
+            config
+                .check_operator(&find_operator_authority_address(&stranger).0)
+                .unwrap_err(),
+            unauthorized
+        );
+        assert_eq!(
+            config.check_operator(&Pubkey::default()).unwrap_err(),
+            unauthorized
+        );
+
+        // operator_count 之后的运营方不生效
+        let config = Config {
+            operator_count: 1,
+            ..config
+        };
+        assert_eq!(config.check_operator(&pda).unwrap_err(), unauthorized);
+    }
+
+    #[test]
+    fn abandoned_after_the_period_with_rounded_down_cranker_share() {
+        let config = Config {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            fee_bps: 0,
+            referral_bps: 0,
+            paused: false,
+            fee_tiers: [FeeTier::default(); MAX_FEE_TIERS],
+            fee_tier_count: 0,
+            listing_fee_lamports: 0,
+            treasury: Pubkey::default(),
+            abandon_seconds: 100,
+            abandon_cranker_bps: 2_500,
+            swap_router: Pubkey::default(),
+            fee_on_tips: false,
+            trusted_routers: [Pubkey::default(); MAX_TRUSTED_ROUTERS],
+            trusted_router_count: 0,
+            operators: [Pubkey::default(); MAX_OPERATORS],
+            operator_count: 0,
+            fee_authority_bump: 0,
+            bump: 0,
+        };
+        assert!(!config.is_abandoned(1_000, 1_100));
+        assert!(config.is_abandoned(1_000, 1_101));
+        assert!(!config.is_abandoned(i64::MAX, i64::MIN));
+        assert_eq!(config.cranker_share_for(4_000_000).unwrap(), 1_000_000);
+        assert_eq!(config.cranker_share_for(3).unwrap(), 0);
+
+        // abandon_seconds 为 0 时不启用
+        let config = Config {
+            abandon_seconds: 0,
+            ..config
+        };
+        assert!(!config.is_abandoned(0, i64::MAX));
+    }
+
+    #[test]
+    fn reveal_requires_matching_salt_after_the_delay() {
+        let escrow = Escrow {
+            commit_delay_slots: 10,
+            ..escrow_with_status()
+        };
+        let address = Pubkey::new_unique();
+        let taker = Pubkey::new_unique();
+        let salt = [7; 32];
+        let commit = TakeCommit {
+            taker,
+            hash: TakeCommit::hash(&taker, &address, &salt),
+            slot: 100,
+            bump: 255,
+        };
+        assert!(escrow.requires_commit());
+        assert_eq!(
+            escrow
+                .check_reveal(&address, &commit, &salt, 109)
+                .unwrap_err(),
+            EscrowError::CommitTooEarly.into()
+        );
+        assert!(escrow.check_reveal(&address, &commit, &salt, 110).is_ok());
+
+        // salt 不同, 或者承诺的是另一个托管
+        assert_eq!(
+            escrow
+                .check_reveal(&address, &commit, &[8; 32], 110)
+                .unwrap_err(),
+            EscrowError::CommitMismatch.into()
+        );
+        assert_eq!(
+            escrow
+                .check_reveal(&Pubkey::new_unique(), &commit, &salt, 110)
+                .unwrap_err(),
+            EscrowError::CommitMismatch.into()
+        );
+    }
+
+    #[test]
+    fn release_requires_the_arbiter_both_parties_or_the_timeout() {
+        let arbiter = Pubkey::new_unique();
+        let taker = Pubkey::new_unique();
+        let mut escrow = Escrow {
+            arbiter,
+            ..escrow_with_status()
+        };
+        escrow.lock(taker, 1_000);
+        assert_eq!(escrow.status, EscrowStatus::Locked);
+        let maker = escrow.maker;
+        let deadline = 1_000 + Escrow::ARBITRATION_TIMEOUT;
+        let unauthorized = |result: Result<()>| {
+            assert_eq!(result.unwrap_err(), EscrowError::UnauthorizedRelease.into());
+        };
+
+        // 仲裁人可以选择任意方向, 双方共同签名也可以
+        for to_taker in [true, false] {
+            assert!(escrow
+                .check_release(&arbiter, None, to_taker, 1_000)
+                .is_ok());
+            assert!(escrow
+                .check_release(&maker, Some(&taker), to_taker, 1_000)
+                .is_ok());
+            assert!(escrow
+                .check_release(&taker, Some(&maker), to_taker, 1_000)
+                .is_ok());
+        }
+
+        // 只有一方签名, 或者同一方签名两次, 或者有外人参与
+        for to_taker in [true, false] {
+            unauthorized(escrow.check_release(&maker, None, to_taker, deadline));
+            unauthorized(escrow.check_release(&taker, None, to_taker, deadline));
+            unauthorized(escrow.check_release(&maker, Some(&maker), to_taker, deadline));
+            let outsider = Pubkey::new_unique();
+            unauthorized(escrow.check_release(&outsider, Some(&maker), to_taker, deadline));
+        }
+
+        // 超时之后任何一方都可以单独退回资金, 但不能单独完成交易
+        for party in [maker, taker] {
+            assert!(escrow
+                .check_release(&party, None, false, deadline + 1)
+                .is_ok());
+            unauthorized(escrow.check_release(&party, None, true, deadline + 1));
+        }
+        unauthorized(escrow.check_release(&Pubkey::new_unique(), None, false, deadline + 1));
+    }
+
+    #[test]
+    fn stream_releases_token_a_pro_rata_rounding_for_the_maker() {
+        let taker = Pubkey::new_unique();
+        let mut escrow = Escrow {
+            amount: 1_000,
+            stream_window: 60,
+            ..escrow_with_status()
+        };
+        escrow.start_stream(taker, 3, 1_000).unwrap();
+        assert_eq!(escrow.status, EscrowStatus::Streaming);
+        assert_eq!(escrow.locked_taker, taker);
+        assert_eq!(escrow.stream_deadline, 1_060);
+        // 3 的 25% 向上取整为 1
+        assert_eq!(escrow.min_stream_initial().unwrap(), 1);
+
+        // 每期 1/3 向下取整, 最后一期释放剩余的全部 token A
+        let mut released = Vec::new();
+        for _ in 0..3 {
+            let amount_a = escrow.stream_release_for(1).unwrap();
+            escrow.amount -= amount_a;
+            escrow.paid_b += 1;
+            released.push(amount_a);
+        }
+        assert_eq!(released, [333, 333, 334]);
+        assert_eq!(escrow.amount, 0);
+
+        // 付清之后, 以及 0 或超过未付部分的支付都被拒绝
+        for amount_b in [0, 1] {
+            assert_eq!(
+                escrow.stream_release_for(amount_b).unwrap_err(),
+                EscrowError::InvalidAmount.into()
+            );
+        }
+        escrow.paid_b = 1;
+        escrow.amount = 667;
+        assert_eq!(
+            escrow.stream_release_for(3).unwrap_err(),
+            EscrowError::InvalidAmount.into()
+        );
+    }
+
+    #[test]
+    fn stream_blocks_maker_withdrawal_until_the_deadline() {
+        let mut escrow = Escrow {
+            stream_window: Escrow::MAX_STREAM_WINDOW,
+            ..escrow_with_status()
+        };
+        escrow.check_maker_can_withdraw(0).unwrap();
+        escrow
+            .start_stream(Pubkey::new_unique(), 100, 1_000)
+            .unwrap();
+        let deadline = 1_000 + Escrow::MAX_STREAM_WINDOW;
+        assert_eq!(
+            escrow.check_maker_can_withdraw(deadline).unwrap_err(),
+            EscrowError::StreamActive.into()
+        );
+        escrow.check_maker_can_withdraw(deadline + 1).unwrap();
+        // 100 的 25% 正好是 25
+        assert_eq!(escrow.min_stream_initial().unwrap(), 25);
+    }
+
+    proptest! {
+        // 任意分期方式释放的 token A 总和都正好是开始时的 amount, 并且任何时候累计释放的比例都不超过累计支付的比例
+        #[test]
+        fn stream_installments_release_exactly_the_locked_amount(
+            amount in 1u64..=u64::MAX,
+            installments in prop::collection::vec(1u64..=1_000_000_000, 1..12),
+        ) {
+            let stream_receive: u64 = installments.iter().sum();
+            let mut escrow = Escrow {
+                amount,
+                stream_window: 1,
+                ..escrow_with_status()
+            };
+            escrow.start_stream(Pubkey::new_unique(), stream_receive, 0).unwrap();
+
+            let mut released = 0u64;
+            for amount_b in installments {
+                let amount_a = escrow.stream_release_for(amount_b).unwrap();
+                escrow.amount -= amount_a;
+                escrow.paid_b += amount_b;
+                released += amount_a;
+                prop_assert!(
+                    released as u128 * stream_receive as u128
+                        <= amount as u128 * escrow.paid_b as u128
+                );
+            }
+            prop_assert_eq!(released, amount);
+            prop_assert_eq!(escrow.amount, 0);
+        }
+    }
+
+    #[test]
+    fn reservation_blocks_others_until_settled_or_expired() {
+        let taker = Pubkey::new_unique();
+        let mut escrow = escrow_with_status();
+        assert_eq!(
+            escrow.reserve(taker, 1, 0).unwrap_err(),
+            EscrowError::ReservationsDisabled.into()
+        );
+
+        escrow.reservation_window = 60;
+        assert_eq!(
+            escrow.reserve(taker, 0, 1_000).unwrap_err(),
+            EscrowError::InvalidBond.into()
+        );
+        escrow.reserve(taker, 500, 1_000).unwrap();
+        assert!(escrow.is_reserved());
+        assert_eq!(escrow.reservation_expiry, 1_060);
+        assert_eq!(
+            escrow
+                .reserve(Pubkey::new_unique(), 500, 1_000)
+                .unwrap_err(),
+            EscrowError::EscrowReserved.into()
+        );
+
+        // 只有 reserved_taker 可以在截止时间之前 settle, 截止之前不能 expire
+        assert_eq!(
+            escrow
+                .settle_reservation(&Pubkey::new_unique(), 1_000)
+                .unwrap_err(),
+            EscrowError::UnauthorizedTaker.into()
+        );
+        assert_eq!(
+            escrow.settle_reservation(&taker, 1_060).unwrap_err(),
+            EscrowError::ReservationExpired.into()
+        );
+        assert_eq!(
+            escrow.expire_reservation(1_059).unwrap_err(),
+            EscrowError::ReservationNotExpired.into()
+        );
+
+        let mut settled = escrow.clone();
+        assert_eq!(settled.settle_reservation(&taker, 1_059).unwrap(), 500);
+        assert!(!settled.is_reserved());
+        assert_eq!(settled.bond, 0);
+
+        // 过期后保证金只能取出一次, 托管可以再次被预约
+        assert_eq!(escrow.expire_reservation(1_060).unwrap(), 500);
+        assert_eq!(
+            escrow.expire_reservation(1_060).unwrap_err(),
+            EscrowError::NotReserved.into()
+        );
+        escrow.reserve(Pubkey::new_unique(), 1, 1_060).unwrap();
+    }
+
+    #[test]
+    fn alt_payment_mints_carry_their_own_price() {
+        let usdt = Pubkey::new_unique();
+        let mut escrow = Escrow {
+            receive: 500,
+            deposited: 1_000,
+            amount: 1_000,
+            ..escrow_with_status()
+        };
+        escrow.alt_payments[0] = PaymentMint {
+            mint: usdt,
+            receive: 500_000,
+        };
+
+        assert!(escrow.accepts_mint_b(&escrow.mint_b));
+        assert!(escrow.accepts_mint_b(&usdt));
+        assert!(!escrow.accepts_mint_b(&Pubkey::new_unique()));
+        // 空的项不代表接受 Pubkey::default()
+        assert!(!escrow.accepts_mint_b(&Pubkey::default()));
+        assert_eq!(escrow.receive_in(&escrow.mint_b, 0).unwrap(), Some(500));
+        assert_eq!(escrow.receive_in(&usdt, 0).unwrap(), Some(500_000));
+        assert_eq!(escrow.receive_in(&Pubkey::new_unique(), 0).unwrap(), None);
+
+        // 定价基准变化时其他支付 mint 保持单价, 空的项不变
+        let rebased = escrow.rebased_payments(400).unwrap();
+        assert_eq!(rebased[0].receive, 200_000);
+        assert_eq!(rebased[1], PaymentMint::default());
+    }
+
+    #[test]
+    fn price_band_only_accepts_prices_near_the_oracle() {
+        // 1 个 token A 值 10 个 token B, 1000 个 token A 的公允价格是 10000 个 token B, 允许偏离 5%
+        let escrow = Escrow {
+            deposited: 1_000,
+            price_feed: Pubkey::new_unique(),
+            quote_exponent: -2,
+            max_staleness: 60,
+            max_deviation_bps: 500,
+            ..escrow_with_status()
+        };
+        let price = OraclePrice {
+            price: 1_000,
+            conf: 0,
+            exponent: -2,
+            publish_time: 0,
+        };
+        assert!(escrow.has_price_band() && !escrow.is_oracle_priced());
+
+        for receive in [9_500, 10_000, 10_500] {
+            escrow.check_price_band(&price, receive, 0, 6, 6).unwrap();
+        }
+        // 填错小数位的价格和刚好超出区间的价格都被拒绝
+        for receive in [10, 9_499, 10_501, 10_000_000] {
+            assert_eq!(
+                escrow
+                    .check_price_band(&price, receive, 0, 6, 6)
+                    .unwrap_err(),
+                EscrowError::PriceOutOfBand.into()
+            );
+        }
+        // 价格本身的检查和 oracle_receive 相同
+        assert_eq!(
+            escrow
+                .check_price_band(&price, 10_000, 61, 6, 6)
+                .unwrap_err(),
+            EscrowError::StalePrice.into()
+        );
+    }
 }
@@ -0,0 +1,144 @@
+use crate::errors::EscrowError;
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    memo::{build_memo, BuildMemo},
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            memo_transfer::memo_required, non_transferable::NonTransferable,
+            transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions,
+        },
+        onchain::{invoke_transfer_checked, invoke_transfer_checked_with_fee},
+    },
+    token_interface::{sync_native, SyncNative, TokenAccount, TransferChecked},
+};
+
+// 读取 Token-2022 mint 的 TransferFee 扩展, spl-token 的 mint 和没有该扩展的 mint 返回 None
+fn fee_config(mint: &AccountInfo) -> Result<Option<TransferFeeConfig>> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(None);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(mint.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+// mint 是否带有 NonTransferable 扩展; spl-token 的 mint 没有扩展数据, 直接返回 false
+pub fn is_non_transferable(mint: &AccountInfo) -> Result<bool> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(false);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(mint.get_extension::<NonTransferable>().is_ok())
+}
+
+// 接收方 token 账户开启了 MemoTransfer 扩展时, Token-2022 要求转账的前一条同级指令是 memo, 否则返回 NoMemo
+// 因此必须在对应的 transfer_checked 之前紧挨着调用; spl-token 的账户和没有开启的账户什么都不做
+pub fn memo_if_required<'info>(
+    memo_program: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    escrow: &Pubkey,
+) -> Result<()> {
+    if to.owner != &spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let required = {
+        let data = to.try_borrow_data()?;
+        let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+        memo_required(&account)
+    };
+    if !required {
+        return Ok(());
+    }
+
+    build_memo(
+        CpiContext::new(memo_program.clone(), BuildMemo {}),
+        format!("escrow {}", escrow).as_bytes(),
+    )
+}
+
+// wSOL 账户中直接转入的 lamports 要 sync_native 之后才会计入 amount, 同步后重新读取账户数据; 其他 mint 的账户什么都不做
+pub fn sync_native_if_needed<'info>(
+    token_program: &AccountInfo<'info>,
+    account: &mut InterfaceAccount<'info, TokenAccount>,
+) -> Result<()> {
+    if !account.is_native() {
+        return Ok(());
+    }
+
+    sync_native(CpiContext::new(
+        token_program.clone(),
+        SyncNative {
+            account: account.to_account_info(),
+        },
+    ))?;
+    account.reload()
+}
+
+// 按当前 epoch 的费率计算转账 amount 时 mint 扣留的手续费, 已包含 maximum_fee 的上限
+fn epoch_fee(config: &TransferFeeConfig, amount: u64) -> Result<u64> {
+    config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(EscrowError::MathOverflow.into())
+}
+
+// 没有 TransferFee 扩展的 mint 手续费为 0
+pub fn fee_for(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    match fee_config(mint)? {
+        Some(config) => epoch_fee(&config, amount),
+        None => Ok(0),
+    }
+}
+
+// 代替 transfer_checked, 返回转账中被扣留的手续费, 接收方实际收到 amount - fee
+// 带 TransferFee 扩展的 mint 使用 transfer_checked_with_fee, 费率和计算结果不一致时直接失败而不是静默少转
+// 带 TransferHook 扩展的 mint 需要的额外账户(hook 程序, ["extra-account-metas", mint] PDA 以及其中列出的账户)
+// 从 ctx.remaining_accounts 中按地址查找, 所以顺序不影响结果, 同一个账户也只需要传一次; 没有 hook 时不需要传
+pub fn transfer_checked<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<u64> {
+    let fee = match fee_config(&ctx.accounts.mint)? {
+        Some(config) => Some(epoch_fee(&config, amount)?),
+        None => None,
+    };
+
+    let TransferChecked {
+        from,
+        mint,
+        to,
+        authority,
+    } = ctx.accounts;
+    match fee {
+        Some(fee) => invoke_transfer_checked_with_fee(
+            ctx.program.key,
+            from,
+            mint,
+            to,
+            authority,
+            &ctx.remaining_accounts,
+            amount,
+            decimals,
+            fee,
+            ctx.signer_seeds,
+        ),
+        None => invoke_transfer_checked(
+            ctx.program.key,
+            from,
+            mint,
+            to,
+            authority,
+            &ctx.remaining_accounts,
+            amount,
+            decimals,
+            ctx.signer_seeds,
+        ),
+    }?;
+
+    Ok(fee.unwrap_or(0))
+}
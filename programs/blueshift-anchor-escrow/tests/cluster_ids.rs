@@ -0,0 +1,82 @@
+// mainnet, devnet 和 localnet feature 选择的程序 ID
+// 默认只检查当前 feature 选中的 ID; 开启 idl-build 时逐个集群重新编译, 检查 IDL 的 address 和 feature 组合的编译错误
+// 后者需要几分钟, 通过 `cargo test -p blueshift-anchor-escrow --features idl-build --test cluster_ids` 运行
+
+const MAINNET: &str = "22222222222222222222222222222222222222222222";
+const DEVNET: &str = "22222222222222222222222222222222222222222222";
+const LOCALNET: &str = "Ew2CvRfUbSSUfe5DurrYJm1xXCxhKskHoB7GkL1Gcggj";
+
+#[test]
+fn active_features_select_the_matching_id() {
+    // 默认的 localnet 在开启 mainnet 或 devnet 时被覆盖
+    let expected = if cfg!(feature = "mainnet") {
+        MAINNET
+    } else if cfg!(feature = "devnet") {
+        DEVNET
+    } else {
+        LOCALNET
+    };
+
+    assert_eq!(blueshift_anchor_escrow::ID.to_string(), expected);
+    assert_eq!(blueshift_anchor_escrow::id(), blueshift_anchor_escrow::ID);
+}
+
+#[cfg(feature = "idl-build")]
+mod matrix {
+    use super::*;
+    use std::process::{Command, Output};
+
+    // 外层的 cargo test 持有 target 目录的锁, 子进程使用单独的目录; 只有本 crate 的 feature 不同, 依赖只编译一次
+    fn cargo(command: &str, features: &str, args: &[&str]) -> Output {
+        Command::new(env!("CARGO"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env(
+                "CARGO_TARGET_DIR",
+                concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/cluster-ids"),
+            )
+            .args([command, "--no-default-features", "--features", features])
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn idl_address_follows_the_cluster_feature() {
+        for (cluster, expected) in [
+            ("mainnet", MAINNET),
+            ("devnet", DEVNET),
+            ("localnet", LOCALNET),
+        ] {
+            // anchor build 生成 IDL 时运行同一个测试, 从输出的 address 段落中读取程序 ID
+            let output = cargo(
+                "test",
+                &format!("{cluster},idl-build"),
+                &[
+                    "--lib",
+                    "--",
+                    "__anchor_private_print_idl_address",
+                    "--nocapture",
+                ],
+            );
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            assert!(output.status.success(), "{cluster}: {stdout}");
+            let address = stdout
+                .split_once("--- IDL begin address ---")
+                .and_then(|(_, rest)| rest.split_once("--- IDL end address ---"))
+                // 和 anchor 解析 IDL 时一样去掉引号和转义字符
+                .map(|(address, _)| address.replace(|c: char| !c.is_alphanumeric(), ""))
+                .unwrap_or_else(|| panic!("{cluster}: no IDL address in the output"));
+            assert_eq!(address, expected, "{cluster}");
+        }
+    }
+
+    #[test]
+    fn exactly_one_cluster_must_be_selected() {
+        for features in ["", "mainnet,devnet"] {
+            let output = cargo("check", features, &["--lib"]);
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(!output.status.success(), "`{features}` should not compile");
+            assert!(stderr.contains("mainnet"), "{stderr}");
+        }
+    }
+}
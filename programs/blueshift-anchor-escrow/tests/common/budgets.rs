@@ -0,0 +1,16 @@
+// 每条指令允许消耗的计算单元上限, 由 compute_units 测试检查
+// 聚合器会在自己的交易中 CPI 调用这些指令, 提高上限之前需要确认调用方的计算预算仍然足够
+pub const MAKE: u64 = 60_000;
+pub const TAKE: u64 = 80_000;
+// 接收 token 的 ATA 都已经存在时的 take, 没有创建账户的 CPI, 防止成交主路径的开销回退
+pub const TAKE_EXISTING: u64 = 60_000;
+pub const REFUND: u64 = 50_000;
+// 两个 vault 各自转给对方的 maker 并关闭, 不创建任何 ATA
+pub const MATCH: u64 = 120_000;
+// 一次退还 MAX_REFUND_BATCH_SIZE 个托管, 必须在不申请额外计算单元的情况下放进默认的 200k 预算
+pub const REFUND_BATCH: u64 = 200_000;
+// 篮子达到 MAX_BASKET_LEGS 时的 make_basket, take_basket 和 refund_basket, 都必须放进默认的 200k 预算
+// take_basket 为 taker 创建每一种 token 的 ATA, 是三者中最贵的
+pub const MAKE_BASKET: u64 = 200_000;
+pub const TAKE_BASKET: u64 = 200_000;
+pub const REFUND_BASKET: u64 = 200_000;
@@ -0,0 +1,957 @@
+// 集成测试共用的 fixture 和指令构造函数
+// anchor 的 CPI 只能在链上执行, 因此加载 sbf 版本的托管程序, 通过 `cargo test-sbf` 运行
+// 每个测试文件只使用其中一部分
+#![allow(dead_code)]
+
+pub mod budgets;
+
+use anchor_lang::{
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::MAX_RETURN_DATA,
+        program_pack::Pack,
+        system_instruction,
+    },
+    system_program, AccountDeserialize, AnchorDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        self, get_associated_token_address,
+        spl_associated_token_account::instruction as ata_instruction,
+    },
+    memo,
+    token::spl_token,
+};
+use blueshift_anchor_escrow::{
+    accounts, instruction,
+    metadata::{METADATA_SEED, TOKEN_METADATA_ID},
+    pda,
+    state::Escrow,
+    ID,
+};
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::AccountSharedData,
+    instruction::InstructionError,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+// maker 存入的 token A 数量和要求的 token B 数量
+pub const AMOUNT: u64 = 1_000;
+pub const RECEIVE: u64 = 500;
+
+// 两个 mint 和三个钱包: maker 持有 token A, taker 持有 token B, stranger 两种都持有, 用来冒充 maker 或 taker
+// ctx.payer 是两个 mint 的铸币权限和协议 admin, 手续费为 0
+pub struct Fixture {
+    pub ctx: ProgramTestContext,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub maker: Keypair,
+    pub taker: Keypair,
+    pub stranger: Keypair,
+}
+
+pub async fn setup() -> Fixture {
+    setup_with_programs(&[]).await
+}
+
+// 额外加载 programs 中的 sbf 程序, 例如部署在 Token Metadata 地址上的 mock
+pub async fn setup_with_programs(programs: &[(&'static str, Pubkey)]) -> Fixture {
+    let mut program_test = ProgramTest::new("blueshift_anchor_escrow", ID, None);
+    for (name, id) in programs {
+        program_test.add_program(name, *id, None);
+    }
+    let mut ctx = program_test.start_with_context().await;
+
+    let mint_a = create_mint(&mut ctx).await;
+    let mint_b = create_mint(&mut ctx).await;
+    let (maker, taker, stranger) = (Keypair::new(), Keypair::new(), Keypair::new());
+    for wallet in [&maker, &taker, &stranger] {
+        let transfer =
+            system_instruction::transfer(&ctx.payer.pubkey(), &wallet.pubkey(), LAMPORTS_PER_SOL);
+        send(&mut ctx, &[transfer], &[]).await.unwrap();
+    }
+    fund_ata(&mut ctx, &mint_a, &maker.pubkey(), AMOUNT).await;
+    fund_ata(&mut ctx, &mint_b, &taker.pubkey(), AMOUNT).await;
+    fund_ata(&mut ctx, &mint_a, &stranger.pubkey(), AMOUNT).await;
+    fund_ata(&mut ctx, &mint_b, &stranger.pubkey(), AMOUNT).await;
+
+    let initialize_config = ix(
+        accounts::InitializeConfig {
+            admin: ctx.payer.pubkey(),
+            config: pda::find_config_address().0,
+            fee_authority: pda::find_fee_authority_address().0,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeConfig { fee_bps: 0 },
+    );
+    send(&mut ctx, &[initialize_config], &[]).await.unwrap();
+
+    Fixture {
+        ctx,
+        mint_a,
+        mint_b,
+        maker,
+        taker,
+        stranger,
+    }
+}
+
+// 由 ctx.payer 支付手续费, signers 只需要传入其他签名者
+pub async fn send(
+    ctx: &mut ProgramTestContext,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let tx =
+        Transaction::new_signed_with_payer(ixs, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    ctx.banks_client.process_transaction(tx).await
+}
+
+// 发送交易并返回消耗的计算单元, 交易失败时 panic
+pub async fn send_measured(
+    ctx: &mut ProgramTestContext,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> u64 {
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let tx =
+        Transaction::new_signed_with_payer(ixs, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    let outcome = ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await
+        .unwrap();
+    outcome.result.unwrap();
+    outcome.metadata.unwrap().compute_units_consumed
+}
+
+// 发送交易并解码托管程序通过 set_return_data 返回的结果, 交易失败时 panic
+pub async fn send_returning<T: AnchorDeserialize>(
+    ctx: &mut ProgramTestContext,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> T {
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let tx =
+        Transaction::new_signed_with_payer(ixs, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    let outcome = ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await
+        .unwrap();
+    outcome.result.unwrap();
+    let return_data = outcome.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, ID);
+    // 运行时会去掉 return data 末尾的 0, 补齐后再解码
+    let mut data = return_data.data;
+    data.resize(MAX_RETURN_DATA, 0);
+    T::deserialize(&mut data.as_slice()).unwrap()
+}
+
+// 断言交易的第一条指令失败并返回 code, 可以传入 EscrowError 或 anchor 的 ErrorCode
+pub fn assert_error(result: Result<(), BanksClientError>, code: impl Into<u32>) {
+    let error = result.expect_err("transaction should fail");
+    assert_eq!(
+        error.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(code.into()))
+    );
+}
+
+pub fn ix(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// 创建 6 位小数的 SPL Token mint, 铸币权限属于 ctx.payer
+pub async fn create_mint(ctx: &mut ProgramTestContext) -> Pubkey {
+    create_mint_with_decimals(ctx, 6).await
+}
+
+// 创建指定小数位的 SPL Token mint, NFT 的 decimals 为 0
+pub async fn create_mint_with_decimals(ctx: &mut ProgramTestContext, decimals: u8) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &ctx.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &ctx.payer.pubkey(),
+            None,
+            decimals,
+        )
+        .unwrap(),
+    ];
+    send(ctx, &ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+// 为 owner 创建 ATA 并铸造 amount 个 token, owner 可以是 off-curve 地址
+pub async fn fund_ata(
+    ctx: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let ata = get_associated_token_address(owner, mint);
+    let mut ixs = vec![ata_instruction::create_associated_token_account(
+        &ctx.payer.pubkey(),
+        owner,
+        mint,
+        &spl_token::ID,
+    )];
+    if amount > 0 {
+        ixs.push(
+            spl_token::instruction::mint_to(
+                &spl_token::ID,
+                mint,
+                &ata,
+                &ctx.payer.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap(),
+        );
+    }
+    send(ctx, &ixs, &[]).await.unwrap();
+    ata
+}
+
+pub async fn fetch_escrow(ctx: &mut ProgramTestContext, address: &Pubkey) -> Option<Escrow> {
+    let account = ctx.banks_client.get_account(*address).await.unwrap()?;
+    Some(Escrow::try_deserialize(&mut account.data.as_slice()).unwrap())
+}
+
+// 不存在的 token 账户余额视为 0
+pub async fn token_balance(ctx: &mut ProgramTestContext, owner: &Pubkey, mint: &Pubkey) -> u64 {
+    let address = get_associated_token_address(owner, mint);
+    match ctx.banks_client.get_account(address).await.unwrap() {
+        Some(account) => {
+            spl_token::state::Account::unpack(&account.data)
+                .unwrap()
+                .amount
+        }
+        None => 0,
+    }
+}
+
+fn event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &ID).0
+}
+
+// 以下函数返回 anchor 生成的 accounts 结构, 负面测试可以在发送前替换其中的账户
+
+// maker 自己支付租金的固定价格托管, 不传 rent_payer
+pub fn make_accounts(fx: &Fixture, maker: &Pubkey, seed: u64) -> accounts::Make {
+    let (escrow, _) = pda::find_escrow_address(maker, seed);
+    accounts::Make {
+        maker: *maker,
+        rent_payer: None,
+        escrow,
+        mint_a: fx.mint_a,
+        mint_b: fx.mint_b,
+        maker_ata_a: Some(get_associated_token_address(maker, &fx.mint_a)),
+        vault: get_associated_token_address(&escrow, &fx.mint_a),
+        config: pda::find_config_address().0,
+        mint_allowlist: pda::find_mint_allowlist_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        exempt_list: None,
+        treasury: None,
+        stats: pda::find_stats_address().0,
+        registry: pda::find_registry_address(maker).0,
+        associated_token_program: associated_token::ID,
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// make_v2 的账户, 不传 prefs 和 pair_index 时和 make 相同
+pub fn make_v2_accounts(fx: &Fixture, maker: &Pubkey, seed: u64) -> accounts::MakeV2 {
+    accounts::MakeV2 {
+        make: make_accounts(fx, maker, seed),
+        prefs: None,
+        pair_index: None,
+        system_program: system_program::ID,
+    }
+}
+
+pub fn make_args(seed: u64, receive: u64, amount: u64) -> instruction::Make {
+    instruction::Make {
+        seed,
+        receive,
+        amount,
+        expiry: 0,
+        allowed_taker: Pubkey::default(),
+        taker_allowlist_root: [0; 32],
+        hashlock: [0; 32],
+        start_time: 0,
+        reject_freezable: false,
+        receive_to: Pubkey::default(),
+        refund_delegate: Pubkey::default(),
+        commit_delay_slots: 0,
+        arbiter: Pubkey::default(),
+        reservation_window: 0,
+        alt_payments: vec![],
+        recurring: false,
+        max_refills: 0,
+    }
+}
+
+// 用 maker 的 AMOUNT 个 token A 创建要求 RECEIVE 个 token B 的托管, 返回托管地址
+pub async fn make(fx: &mut Fixture, seed: u64) -> Pubkey {
+    let make = ix(
+        make_accounts(fx, &fx.maker.pubkey(), seed),
+        make_args(seed, RECEIVE, AMOUNT),
+    );
+    let maker = fx.maker.insecure_clone();
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    pda::find_escrow_address(&maker.pubkey(), seed).0
+}
+
+// taker 自己支付 ATA 租金, 用 ATA 接收 token A 和支付 token B
+pub fn take_accounts(
+    fx: &Fixture,
+    taker: &Pubkey,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::Take {
+    accounts::Take {
+        taker: *taker,
+        payer: *taker,
+        maker: *maker,
+        rent_payer: *maker,
+        receive_to: *maker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        mint_b: fx.mint_b,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        taker_ata_a: Some(get_associated_token_address(taker, &fx.mint_a)),
+        taker_token_a: None,
+        maker_ata_a: get_associated_token_address(maker, &fx.mint_a),
+        taker_ata_b: get_associated_token_address(taker, &fx.mint_b),
+        maker_ata_b: Some(get_associated_token_address(maker, &fx.mint_b)),
+        unwrap_ata_b: None,
+        config: pda::find_config_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        stats: pda::find_stats_address().0,
+        taker_stats: None,
+        maker_stats: None,
+        fee_authority: pda::find_fee_authority_address().0,
+        fee_vault_b: None,
+        referrer_ata_b: None,
+        receipt: None,
+        registry: pda::find_registry_address(maker).0,
+        pair_index: None,
+        price_feed: None,
+        instructions_sysvar: None,
+        associated_token_program: Some(associated_token::ID),
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        memo_program: memo::ID,
+        system_program: Some(system_program::ID),
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+pub fn take_args(max_receive: u64) -> instruction::Take {
+    instruction::Take {
+        max_receive,
+        expected_amount_a: 0,
+        proof: vec![],
+        preimage: vec![],
+        write_receipt: false,
+    }
+}
+
+// signer 签名退还 maker 的 escrow, rent_payer 也是 maker; signer 必须是 maker 或托管的 refund_delegate
+pub fn refund_accounts(
+    fx: &Fixture,
+    signer: &Pubkey,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::Refund {
+    accounts::Refund {
+        authority: *signer,
+        maker: *maker,
+        rent_payer: *maker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        maker_ata_a: get_associated_token_address(maker, &fx.mint_a),
+        stats: pda::find_stats_address().0,
+        registry: pda::find_registry_address(maker).0,
+        pair_index: None,
+        associated_token_program: associated_token::ID,
+        token_program: spl_token::ID,
+        memo_program: memo::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// 任何人都可以清理被放弃的托管, maker 自己支付租金; 账户和 cranker 签名的 refund 相同
+pub fn sweep_abandoned_accounts(
+    fx: &Fixture,
+    cranker: &Pubkey,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::SweepAbandoned {
+    accounts::SweepAbandoned {
+        refund: refund_accounts(fx, cranker, maker, escrow),
+        config: pda::find_config_address().0,
+    }
+}
+
+// maker 自己支付租金, 把 escrow 移到 new_seed 重新挂单
+pub fn relist_accounts(
+    fx: &Fixture,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+    new_seed: u64,
+) -> accounts::Relist {
+    let new_escrow = pda::find_escrow_address(maker, new_seed).0;
+    accounts::Relist {
+        maker: *maker,
+        rent_payer: *maker,
+        escrow: *escrow,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        new_escrow,
+        new_vault: get_associated_token_address(&new_escrow, &fx.mint_a),
+        mint_a: fx.mint_a,
+        config: pda::find_config_address().0,
+        mint_allowlist: pda::find_mint_allowlist_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        registry: pda::find_registry_address(maker).0,
+        pair_index: None,
+        associated_token_program: associated_token::ID,
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// maker 取回 escrow 中的部分或全部 token A, maker 自己支付租金
+pub fn withdraw_partial_accounts(
+    fx: &Fixture,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::WithdrawPartial {
+    accounts::WithdrawPartial {
+        maker: *maker,
+        rent_payer: *maker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        maker_ata_a: get_associated_token_address(maker, &fx.mint_a),
+        stats: pda::find_stats_address().0,
+        registry: pda::find_registry_address(maker).0,
+        pair_index: None,
+        associated_token_program: associated_token::ID,
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// maker 取回 escrow 已经关闭的孤立 vault
+pub fn recover_vault_accounts(fx: &Fixture, maker: &Pubkey, seed: u64) -> accounts::RecoverVault {
+    let escrow = pda::find_escrow_address(maker, seed).0;
+    accounts::RecoverVault {
+        maker: *maker,
+        escrow,
+        mint_a: fx.mint_a,
+        vault: get_associated_token_address(&escrow, &fx.mint_a),
+        maker_ata_a: get_associated_token_address(maker, &fx.mint_a),
+        stats: pda::find_stats_address().0,
+        registry: pda::find_registry_address(maker).0,
+        associated_token_program: associated_token::ID,
+        token_program: spl_token::ID,
+        memo_program: memo::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// maker 一次退还自己支付租金的 escrows, 按顺序在 remaining_accounts 中传入每个 escrow 和它的 vault
+pub fn refund_batch_ix(fx: &Fixture, maker: &Pubkey, escrows: &[Pubkey]) -> Instruction {
+    let mut refund = ix(
+        accounts::RefundBatch {
+            maker: *maker,
+            rent_payer: *maker,
+            mint_a: fx.mint_a,
+            maker_ata_a: get_associated_token_address(maker, &fx.mint_a),
+            stats: pda::find_stats_address().0,
+            registry: pda::find_registry_address(maker).0,
+            pair_index: None,
+            associated_token_program: associated_token::ID,
+            token_program: spl_token::ID,
+            memo_program: memo::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        },
+        instruction::RefundBatch {
+            count: escrows.len() as u8,
+        },
+    );
+    for escrow in escrows {
+        let vault = get_associated_token_address(escrow, &fx.mint_a);
+        refund.accounts.push(AccountMeta::new(*escrow, false));
+        refund.accounts.push(AccountMeta::new(vault, false));
+    }
+    refund
+}
+
+// maker_x 的 escrow_x 出售 token A, maker_y 的 escrow_y 出售 token B, 两者都由 maker 自己支付租金
+// matcher 不接收差额, 四个 maker 的 ATA 必须已经存在
+pub fn match_accounts(
+    fx: &Fixture,
+    matcher: &Pubkey,
+    (maker_x, escrow_x): (&Pubkey, &Pubkey),
+    (maker_y, escrow_y): (&Pubkey, &Pubkey),
+) -> accounts::MatchEscrows {
+    accounts::MatchEscrows {
+        matcher: *matcher,
+        maker_x: *maker_x,
+        maker_y: *maker_y,
+        rent_payer_x: *maker_x,
+        rent_payer_y: *maker_y,
+        escrow_x: *escrow_x,
+        escrow_y: *escrow_y,
+        mint_a: fx.mint_a,
+        mint_b: fx.mint_b,
+        vault_x: get_associated_token_address(escrow_x, &fx.mint_a),
+        vault_y: get_associated_token_address(escrow_y, &fx.mint_b),
+        maker_x_ata_a: get_associated_token_address(maker_x, &fx.mint_a),
+        maker_x_ata_b: get_associated_token_address(maker_x, &fx.mint_b),
+        maker_y_ata_a: get_associated_token_address(maker_y, &fx.mint_a),
+        maker_y_ata_b: get_associated_token_address(maker_y, &fx.mint_b),
+        matcher_ata_a: None,
+        matcher_ata_b: None,
+        config: pda::find_config_address().0,
+        fee_authority: pda::find_fee_authority_address().0,
+        fee_vault_a: None,
+        fee_vault_b: None,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        stats: pda::find_stats_address().0,
+        registry_x: pda::find_registry_address(maker_x).0,
+        registry_y: pda::find_registry_address(maker_y).0,
+        pair_index_x: None,
+        pair_index_y: None,
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        memo_program: memo::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// taker 把 token B 锁入有仲裁人的 escrow 的 vault_b
+pub fn lock_take_accounts(
+    fx: &Fixture,
+    taker: &Pubkey,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::LockTake {
+    accounts::LockTake {
+        taker: *taker,
+        maker: *maker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        mint_b: fx.mint_b,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        taker_ata_b: get_associated_token_address(taker, &fx.mint_b),
+        vault_b: get_associated_token_address(escrow, &fx.mint_b),
+        config: pda::find_config_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        associated_token_program: associated_token::ID,
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// authority 单独签名结算锁定的 escrow, maker 自己支付租金并接收 token B; 两个方向的 ATA 都传入
+pub fn release_accounts(
+    fx: &Fixture,
+    authority: &Pubkey,
+    maker: &Pubkey,
+    taker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::Release {
+    accounts::Release {
+        authority: *authority,
+        co_signer: None,
+        maker: *maker,
+        rent_payer: *maker,
+        receive_to: *maker,
+        taker: *taker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        mint_b: fx.mint_b,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        vault_b: get_associated_token_address(escrow, &fx.mint_b),
+        taker_ata_a: Some(get_associated_token_address(taker, &fx.mint_a)),
+        maker_ata_b: Some(get_associated_token_address(maker, &fx.mint_b)),
+        maker_ata_a: Some(get_associated_token_address(maker, &fx.mint_a)),
+        taker_ata_b: Some(get_associated_token_address(taker, &fx.mint_b)),
+        config: pda::find_config_address().0,
+        fee_authority: pda::find_fee_authority_address().0,
+        fee_vault_b: None,
+        stats: pda::find_stats_address().0,
+        taker_stats: None,
+        maker_stats: None,
+        registry: pda::find_registry_address(maker).0,
+        pair_index: None,
+        associated_token_program: associated_token::ID,
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        memo_program: memo::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// taker 支付保证金预约 maker 的 escrow
+pub fn reserve_accounts(taker: &Pubkey, maker: &Pubkey, escrow: &Pubkey) -> accounts::Reserve {
+    accounts::Reserve {
+        taker: *taker,
+        maker: *maker,
+        escrow: *escrow,
+        config: pda::find_config_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// 任何人在预约过期后结束预约, 保证金转给 maker
+pub fn expire_reservation_accounts(
+    cranker: &Pubkey,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::ExpireReservation {
+    accounts::ExpireReservation {
+        cranker: *cranker,
+        maker: *maker,
+        escrow: *escrow,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// maker 为固定价格的托管设置预言机价格区间
+pub fn set_price_band_accounts(maker: &Pubkey, escrow: &Pubkey) -> accounts::SetPriceBand {
+    accounts::SetPriceBand {
+        maker: *maker,
+        escrow: *escrow,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// maker 为出售 NFT 的托管开启或关闭版税, 需要 mint A 的 Metadata
+pub fn set_royalty_enforcement_accounts(
+    fx: &Fixture,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::SetRoyaltyEnforcement {
+    accounts::SetRoyaltyEnforcement {
+        maker: *maker,
+        escrow: *escrow,
+        nft_metadata: metadata_address(&fx.mint_a),
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// taker 用自己 ATA 中的 nft_mint 成交 collection 托管, NFT 转给 maker 的 ATA
+pub fn take_collection_accounts(
+    fx: &Fixture,
+    taker: &Pubkey,
+    maker: &Pubkey,
+    escrow: &Pubkey,
+    nft_mint: &Pubkey,
+) -> accounts::TakeCollection {
+    accounts::TakeCollection {
+        taker: *taker,
+        maker: *maker,
+        rent_payer: *maker,
+        receive_to: *maker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        taker_ata_a: get_associated_token_address(taker, &fx.mint_a),
+        maker_ata_a: get_associated_token_address(maker, &fx.mint_a),
+        nft_mint: *nft_mint,
+        nft_metadata: metadata_address(nft_mint),
+        taker_nft: get_associated_token_address(taker, nft_mint),
+        receive_to_nft: get_associated_token_address(maker, nft_mint),
+        config: pda::find_config_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        stats: pda::find_stats_address().0,
+        registry: pda::find_registry_address(maker).0,
+        pair_index: None,
+        associated_token_program: associated_token::ID,
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        memo_program: memo::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// taker 分期支付, 自己支付 ATA 租金, 用 ATA 接收 token A 和支付 token B
+pub fn take_streaming_accounts(
+    fx: &Fixture,
+    taker: &Pubkey,
+    escrow: &Pubkey,
+) -> accounts::TakeStreaming {
+    let maker = fx.maker.pubkey();
+    accounts::TakeStreaming {
+        taker: *taker,
+        maker,
+        rent_payer: maker,
+        receive_to: maker,
+        escrow: *escrow,
+        mint_a: fx.mint_a,
+        mint_b: fx.mint_b,
+        vault: get_associated_token_address(escrow, &fx.mint_a),
+        taker_ata_a: get_associated_token_address(taker, &fx.mint_a),
+        maker_ata_a: get_associated_token_address(&maker, &fx.mint_a),
+        taker_ata_b: get_associated_token_address(taker, &fx.mint_b),
+        maker_ata_b: get_associated_token_address(&maker, &fx.mint_b),
+        config: pda::find_config_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        taker_stats: None,
+        maker_stats: None,
+        fee_authority: pda::find_fee_authority_address().0,
+        fee_vault_b: None,
+        stats: pda::find_stats_address().0,
+        registry: pda::find_registry_address(&maker).0,
+        pair_index: None,
+        associated_token_program: associated_token::ID,
+        token_program_a: spl_token::ID,
+        token_program_b: spl_token::ID,
+        system_program: system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+}
+
+// mint 的 Metaplex Metadata 账户地址
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[METADATA_SEED, TOKEN_METADATA_ID.as_ref(), mint.as_ref()],
+        &TOKEN_METADATA_ID,
+    )
+    .0
+}
+
+// 在 mint 的 Metadata 地址上放置一个 Token Metadata 程序拥有的账户, collection 为 (verified, key)
+// token_standard 为 0 时是普通 NFT, 为 TOKEN_STANDARD_PROGRAMMABLE 时是 pNFT
+pub async fn set_metadata(
+    fx: &mut Fixture,
+    mint: &Pubkey,
+    token_standard: u8,
+    collection: Option<(bool, Pubkey)>,
+) {
+    set_metadata_with_creators(fx, mint, token_standard, collection, &[]).await;
+}
+
+// 和 set_metadata 相同, 另外记录 creators 中的 (address, verified, share), seller_fee_basis_points 为 500
+pub async fn set_metadata_with_creators(
+    fx: &mut Fixture,
+    mint: &Pubkey,
+    token_standard: u8,
+    collection: Option<(bool, Pubkey)>,
+    creators: &[(Pubkey, bool, u8)],
+) {
+    let mut data = vec![4]; // Key::MetadataV1
+    data.extend_from_slice(Pubkey::new_unique().as_ref()); // update_authority
+    data.extend_from_slice(mint.as_ref());
+    for field in ["Mad Lad #1", "MAD", "https://example.com/1.json"] {
+        data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        data.extend_from_slice(field.as_bytes());
+    }
+    data.extend_from_slice(&500u16.to_le_bytes()); // seller_fee_basis_points
+    if creators.is_empty() {
+        data.push(0);
+    } else {
+        data.push(1);
+        data.extend_from_slice(&(creators.len() as u32).to_le_bytes());
+        for (address, verified, share) in creators {
+            data.extend_from_slice(address.as_ref());
+            data.extend_from_slice(&[*verified as u8, *share]);
+        }
+    }
+    data.extend_from_slice(&[0, 1]); // primary_sale_happened, is_mutable
+    data.extend_from_slice(&[1, 255, 1, token_standard]); // edition_nonce, token_standard
+    match collection {
+        Some((verified, key)) => {
+            data.extend_from_slice(&[1, verified as u8]);
+            data.extend_from_slice(key.as_ref());
+        }
+        None => data.push(0),
+    }
+    data.resize(data.len() + 64, 0);
+
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(
+        rent.minimum_balance(data.len()),
+        data.len(),
+        &TOKEN_METADATA_ID,
+    );
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(&metadata_address(mint), &account);
+}
+
+// maker 用自己 ATA 中的 token 创建一篮子托管, legs 是每种 token 的 mint 和存入的数量
+pub fn make_basket_ix(
+    fx: &Fixture,
+    maker: &Pubkey,
+    seed: u64,
+    receive: u64,
+    legs: &[(Pubkey, u64)],
+) -> Instruction {
+    let basket = pda::find_basket_address(maker, seed).0;
+    let mut make = ix(
+        accounts::MakeBasket {
+            maker: *maker,
+            basket,
+            mint_b: fx.mint_b,
+            config: pda::find_config_address().0,
+            mint_allowlist: pda::find_mint_allowlist_address().0,
+            mint_blocklist: pda::find_mint_blocklist_address().0,
+            associated_token_program: associated_token::ID,
+            token_program_a: spl_token::ID,
+            token_program_b: spl_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        },
+        instruction::MakeBasket {
+            seed,
+            receive,
+            amounts: legs.iter().map(|(_, amount)| *amount).collect(),
+        },
+    );
+    for (mint, _) in legs {
+        make.accounts.push(AccountMeta::new_readonly(*mint, false));
+        make.accounts.push(AccountMeta::new(
+            get_associated_token_address(maker, mint),
+            false,
+        ));
+        make.accounts.push(AccountMeta::new(
+            get_associated_token_address(&basket, mint),
+            false,
+        ));
+    }
+    make
+}
+
+// 按 take_basket 和 refund_basket 的顺序为每种 token 传入 mint, vault 和 recipient 的 ATA
+fn push_basket_legs(ix: &mut Instruction, basket: &Pubkey, recipient: &Pubkey, mints: &[Pubkey]) {
+    for mint in mints {
+        ix.accounts.push(AccountMeta::new_readonly(*mint, false));
+        ix.accounts.push(AccountMeta::new(
+            get_associated_token_address(basket, mint),
+            false,
+        ));
+        ix.accounts.push(AccountMeta::new(
+            get_associated_token_address(recipient, mint),
+            false,
+        ));
+    }
+}
+
+// taker 不限制价格地取走 maker 的一篮子托管, 手续费为 0 时不传手续费账户
+pub fn take_basket_ix(
+    fx: &Fixture,
+    taker: &Pubkey,
+    maker: &Pubkey,
+    seed: u64,
+    mints: &[Pubkey],
+) -> Instruction {
+    let basket = pda::find_basket_address(maker, seed).0;
+    let mut take = ix(
+        accounts::TakeBasket {
+            taker: *taker,
+            maker: *maker,
+            basket,
+            mint_b: fx.mint_b,
+            taker_ata_b: get_associated_token_address(taker, &fx.mint_b),
+            maker_ata_b: get_associated_token_address(maker, &fx.mint_b),
+            config: pda::find_config_address().0,
+            mint_blocklist: pda::find_mint_blocklist_address().0,
+            fee_authority: pda::find_fee_authority_address().0,
+            fee_vault_b: None,
+            associated_token_program: associated_token::ID,
+            token_program_a: spl_token::ID,
+            token_program_b: spl_token::ID,
+            memo_program: memo::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        },
+        instruction::TakeBasket {
+            max_receive: u64::MAX,
+        },
+    );
+    push_basket_legs(&mut take, &basket, taker, mints);
+    take
+}
+
+// maker 退还自己的一篮子托管, 空的 vault 也要传入
+pub fn refund_basket_ix(maker: &Pubkey, seed: u64, mints: &[Pubkey]) -> Instruction {
+    let basket = pda::find_basket_address(maker, seed).0;
+    let mut refund = ix(
+        accounts::RefundBasket {
+            maker: *maker,
+            basket,
+            associated_token_program: associated_token::ID,
+            token_program: spl_token::ID,
+            memo_program: memo::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        },
+        instruction::RefundBasket {},
+    );
+    push_basket_legs(&mut refund, &basket, maker, mints);
+    refund
+}
@@ -0,0 +1,144 @@
+// 需要 sbf 版本的托管程序, 通过 `cargo test-sbf -- --nocapture` 运行可以在日志中看到计算单元表格
+// 计算单元从 solana-program-test 返回的交易元数据中读取, 上限定义在 common/budgets.rs
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use anchor_spl::associated_token::get_associated_token_address;
+use blueshift_anchor_escrow::{instruction, pda, state::MAX_BASKET_LEGS, MAX_REFUND_BATCH_SIZE};
+use common::*;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn instructions_stay_within_compute_budgets() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+
+    // 每笔交易只包含一条指令, 交易消耗的计算单元就是指令的消耗
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, RECEIVE, AMOUNT / 2),
+    );
+    let make_units = send_measured(&mut fx.ctx, &[make], &[&maker]).await;
+
+    // taker 还没有 token A 的 ATA, maker 还没有 token B 的 ATA, 包含创建 ATA 的开销
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    let take_units = send_measured(&mut fx.ctx, &[take], &[&taker]).await;
+
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 2),
+        make_args(2, RECEIVE, AMOUNT / 2),
+    );
+    send_measured(&mut fx.ctx, &[make], &[&maker]).await;
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 2).0;
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    let refund_units = send_measured(&mut fx.ctx, &[refund], &[&maker]).await;
+
+    // taker 用 token B 创建反方向的托管, 由 ctx.payer 撮合; 双方接收对方 token 的 ATA 已经存在
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 3),
+        make_args(3, RECEIVE, AMOUNT / 2),
+    );
+    send_measured(&mut fx.ctx, &[make], &[&maker]).await;
+    let taker_escrow = pda::find_escrow_address(&taker.pubkey(), 3).0;
+    let mut make_reverse = make_accounts(&fx, &taker.pubkey(), 3);
+    make_reverse.mint_a = fx.mint_b;
+    make_reverse.mint_b = fx.mint_a;
+    make_reverse.maker_ata_a = Some(get_associated_token_address(&taker.pubkey(), &fx.mint_b));
+    make_reverse.vault = get_associated_token_address(&taker_escrow, &fx.mint_b);
+    let make = ix(make_reverse, make_args(3, AMOUNT / 2, RECEIVE));
+    send_measured(&mut fx.ctx, &[make], &[&taker]).await;
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 3).0;
+    let payer = fx.ctx.payer.pubkey();
+    let match_escrows = ix(
+        match_accounts(
+            &fx,
+            &payer,
+            (&maker.pubkey(), &escrow),
+            (&taker.pubkey(), &taker_escrow),
+        ),
+        instruction::MatchEscrows {},
+    );
+    let match_units = send_measured(&mut fx.ctx, &[match_escrows], &[]).await;
+
+    // stranger 用自己的 token A 创建 MAX_REFUND_BATCH_SIZE 个托管, 再一次全部退还
+    let stranger = fx.stranger.insecure_clone();
+    let mut escrows = Vec::new();
+    for seed in 0..MAX_REFUND_BATCH_SIZE as u64 {
+        let make = ix(
+            make_accounts(&fx, &stranger.pubkey(), seed),
+            make_args(seed, RECEIVE, AMOUNT / MAX_REFUND_BATCH_SIZE as u64),
+        );
+        send(&mut fx.ctx, &[make], &[&stranger]).await.unwrap();
+        escrows.push(pda::find_escrow_address(&stranger.pubkey(), seed).0);
+    }
+    let refund_batch = refund_batch_ix(&fx, &stranger.pubkey(), &escrows);
+    let refund_batch_units = send_measured(&mut fx.ctx, &[refund_batch], &[&stranger]).await;
+
+    // maker 取走 stranger 的托管, 双方接收 token 的 ATA 都已经存在, 不传 associated token 和 system 程序
+    let seed = MAX_REFUND_BATCH_SIZE as u64;
+    let make = ix(
+        make_accounts(&fx, &stranger.pubkey(), seed),
+        make_args(seed, RECEIVE, AMOUNT / 2),
+    );
+    send(&mut fx.ctx, &[make], &[&stranger]).await.unwrap();
+    let escrow = pda::find_escrow_address(&stranger.pubkey(), seed).0;
+    let mut accounts = take_accounts(&fx, &maker.pubkey(), &stranger.pubkey(), &escrow);
+    accounts.associated_token_program = None;
+    accounts.system_program = None;
+    let take = ix(accounts, take_args(RECEIVE));
+    let take_existing_units = send_measured(&mut fx.ctx, &[take], &[&maker]).await;
+
+    // maker 用 MAX_BASKET_LEGS 种 token 创建两个篮子, 一个被 taker 取走, 一个退还
+    let mut legs = Vec::new();
+    for _ in 0..MAX_BASKET_LEGS {
+        let mint = create_mint(&mut fx.ctx).await;
+        fund_ata(&mut fx.ctx, &mint, &maker.pubkey(), AMOUNT).await;
+        legs.push((mint, AMOUNT / 2));
+    }
+    let mints: Vec<_> = legs.iter().map(|(mint, _)| *mint).collect();
+    let make_basket = make_basket_ix(&fx, &maker.pubkey(), 1, RECEIVE, &legs);
+    let make_basket_units = send_measured(&mut fx.ctx, &[make_basket], &[&maker]).await;
+    let take_basket = take_basket_ix(&fx, &taker.pubkey(), &maker.pubkey(), 1, &mints);
+    let take_basket_units = send_measured(&mut fx.ctx, &[take_basket], &[&taker]).await;
+    let make_basket = make_basket_ix(&fx, &maker.pubkey(), 2, RECEIVE, &legs);
+    send_measured(&mut fx.ctx, &[make_basket], &[&maker]).await;
+    let refund_basket = refund_basket_ix(&maker.pubkey(), 2, &mints);
+    let refund_basket_units = send_measured(&mut fx.ctx, &[refund_basket], &[&maker]).await;
+
+    let rows = [
+        ("make", make_units, budgets::MAKE),
+        ("take", take_units, budgets::TAKE),
+        ("take_existing", take_existing_units, budgets::TAKE_EXISTING),
+        ("refund", refund_units, budgets::REFUND),
+        ("match", match_units, budgets::MATCH),
+        ("refund_batch", refund_batch_units, budgets::REFUND_BATCH),
+        ("make_basket", make_basket_units, budgets::MAKE_BASKET),
+        ("take_basket", take_basket_units, budgets::TAKE_BASKET),
+        ("refund_basket", refund_basket_units, budgets::REFUND_BASKET),
+    ];
+
+    // 先打印完整的表格再断言, 超出上限时也能看到所有指令的消耗
+    println!("{:<14} {:>10} {:>10}", "ix", "consumed", "budget");
+    for (name, consumed, budget) in rows {
+        println!("{name:<14} {consumed:>10} {budget:>10}");
+    }
+    for (name, consumed, budget) in rows {
+        assert!(
+            consumed <= budget,
+            "{name} consumed {consumed} compute units, budget is {budget}"
+        );
+    }
+    // ATA 都已经存在时只检查账户, 必须比需要创建 ATA 的 take 便宜
+    assert!(
+        take_existing_units < take_units,
+        "take with existing ATAs consumed {take_existing_units}, creating take consumed {take_units}"
+    );
+}
@@ -0,0 +1,144 @@
+// 需要 sbf 版本的托管程序和 mock-router, 通过 `cargo test-sbf` 运行
+// mock-router 把 take 原样通过 CPI 转发给托管程序, 交易的顶层指令属于 mock-router
+// mock-relay 是另一个同样转发的程序, 用来把托管程序放到可信路由之下更深的 CPI 中
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use anchor_lang::{
+    solana_program::{instruction::AccountMeta, sysvar},
+    InstructionData,
+};
+use blueshift_anchor_escrow::{accounts, errors::EscrowError, instruction, pda, ID};
+use common::*;
+use solana_sdk::{instruction::Instruction, pubkey, pubkey::Pubkey, signature::Signer};
+
+const MOCK_ROUTER_ID: Pubkey = pubkey!("H9rbLyowovQAw6yEAjYSBM5buj1uadunqxihTdatwnF9");
+const MOCK_RELAY_ID: Pubkey = pubkey!("C6PWCpjqnBW3X4JsghuCxLoqL5VDkPz1wFcQJdAkEbbM");
+
+// 每个托管出售 300 个 token A, 价格 100, taker 的 token B 足够成交全部托管
+const DIRECT_AMOUNT: u64 = 300;
+const DIRECT_RECEIVE: u64 = 100;
+
+async fn make_direct(fx: &mut Fixture, seed: u64, direct_only: bool) -> Pubkey {
+    let maker = fx.maker.insecure_clone();
+    let make = ix(
+        make_accounts(fx, &maker.pubkey(), seed),
+        make_args(seed, DIRECT_RECEIVE, DIRECT_AMOUNT),
+    );
+    let escrow = pda::find_escrow_address(&maker.pubkey(), seed).0;
+    let set_direct_only = ix(
+        accounts::SetDirectOnly {
+            maker: maker.pubkey(),
+            escrow,
+        },
+        instruction::SetDirectOnly { direct_only },
+    );
+    send(&mut fx.ctx, &[make, set_direct_only], &[&maker])
+        .await
+        .unwrap();
+    escrow
+}
+
+fn take(fx: &Fixture, escrow: &Pubkey, with_sysvar: bool) -> Instruction {
+    ix(
+        accounts::Take {
+            instructions_sysvar: with_sysvar.then_some(sysvar::instructions::ID),
+            ..take_accounts(fx, &fx.taker.pubkey(), &fx.maker.pubkey(), escrow)
+        },
+        take_args(DIRECT_RECEIVE),
+    )
+}
+
+// mock-router 的 forward: 第一个账户是被调用的程序, 之后是转发的账户, 数据是 borsh 编码的 Vec<u8>
+fn forward(inner: Instruction) -> Instruction {
+    forward_with(MOCK_ROUTER_ID, inner)
+}
+
+// mock-relay 的 forward 和 mock-router 的编码相同
+fn forward_with(router: Pubkey, inner: Instruction) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(inner.program_id, false)];
+    accounts.extend(inner.accounts);
+    let mut data = vec![0];
+    data.extend_from_slice(&(inner.data.len() as u32).to_le_bytes());
+    data.extend_from_slice(&inner.data);
+    Instruction {
+        program_id: router,
+        accounts,
+        data,
+    }
+}
+
+fn set_trusted_routers(fx: &Fixture, routers: Vec<Pubkey>) -> Instruction {
+    ix(
+        accounts::SetTrustedRouters {
+            admin: fx.ctx.payer.pubkey(),
+            config: pda::find_config_address().0,
+        },
+        instruction::SetTrustedRouters { routers },
+    )
+}
+
+#[tokio::test]
+async fn direct_only_rejects_untrusted_cpi() {
+    let mut fx = setup_with_programs(&[
+        ("mock_router", MOCK_ROUTER_ID),
+        ("mock_relay", MOCK_RELAY_ID),
+    ])
+    .await;
+    let taker = fx.taker.insecure_clone();
+    let open = make_direct(&mut fx, 1, false).await;
+    let direct = make_direct(&mut fx, 2, true).await;
+    let top_level = make_direct(&mut fx, 3, true).await;
+
+    // 没有开启 direct_only 的托管照常可以通过 CPI 成交, 不需要 instructions sysvar
+    let routed = forward(take(&fx, &open, false));
+    send(&mut fx.ctx, &[routed], &[&taker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &open).await.is_none());
+
+    // 开启后 take 必须传入 instructions sysvar
+    let missing = take(&fx, &direct, false);
+    let result = send(&mut fx.ctx, &[missing], &[&taker]).await;
+    assert_error(result, EscrowError::MissingInstructionsSysvar);
+
+    // 外层程序不是可信路由时拒绝 CPI 成交
+    let routed = forward(take(&fx, &direct, true));
+    let result = send(&mut fx.ctx, &[routed], &[&taker]).await;
+    assert_error(result, EscrowError::CpiNotAllowed);
+
+    // 顶层调用不受影响
+    let direct_take = take(&fx, &top_level, true);
+    send(&mut fx.ctx, &[direct_take], &[&taker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &top_level).await.is_none());
+
+    // 托管程序自己不能成为可信路由
+    let trust_self = set_trusted_routers(&fx, vec![ID]);
+    let result = send(&mut fx.ctx, &[trust_self], &[]).await;
+    assert_error(result, EscrowError::InvalidTrustedRouters);
+
+    // 加入可信路由之后同样的 CPI 成交成功; 换一个 max_receive, 避免和失败的交易完全相同
+    let trust = set_trusted_routers(&fx, vec![MOCK_ROUTER_ID]);
+    send(&mut fx.ctx, &[trust], &[]).await.unwrap();
+
+    // 可信路由再转发给不可信的程序时, 托管程序不是顶层指令的直接被调用者, 仍然拒绝
+    let nested = forward(forward_with(
+        MOCK_RELAY_ID,
+        Instruction {
+            data: take_args(u64::MAX - 1).data(),
+            ..take(&fx, &direct, true)
+        },
+    ));
+    let result = send(&mut fx.ctx, &[nested], &[&taker]).await;
+    assert_error(result, EscrowError::CpiNotAllowed);
+
+    let routed = forward(Instruction {
+        data: take_args(u64::MAX).data(),
+        ..take(&fx, &direct, true)
+    });
+    send(&mut fx.ctx, &[routed], &[&taker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &direct).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        3 * DIRECT_AMOUNT
+    );
+}
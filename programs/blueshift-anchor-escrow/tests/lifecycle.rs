@@ -0,0 +1,3299 @@
+// 需要 sbf 版本的托管程序, 通过 `cargo test-sbf` 运行
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use anchor_lang::{
+    error::ErrorCode,
+    solana_program::{program_pack::Pack, system_instruction},
+    system_program, AccountDeserialize, AccountSerialize, Discriminator,
+};
+use anchor_spl::{associated_token::get_associated_token_address, token::spl_token, token_2022};
+use blueshift_anchor_escrow::{
+    accounts,
+    errors::EscrowError,
+    instruction,
+    oracle::{PRICE_UPDATE_V2_DISCRIMINATOR, PYTH_RECEIVER_ID},
+    pda,
+    results::TakeResult,
+    state::{
+        CancelReason, Escrow, EscrowStatus, FeeTier, MakerRegistry, PairIndex, PaymentMint,
+        UserStats, MAX_BASKET_LEGS,
+    },
+    MakeArgs, TakeArgs, ID,
+};
+use common::*;
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    account::AccountSharedData,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+#[tokio::test]
+async fn make_then_take() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.maker, fx.maker.pubkey());
+    assert_eq!(state.amount, AMOUNT);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+        AMOUNT
+    );
+
+    let take = ix(
+        take_accounts(&fx, &fx.taker.pubkey(), &fx.maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    let taker = fx.taker.insecure_clone();
+    let result: TakeResult = send_returning(&mut fx.ctx, &[take], &[&taker]).await;
+    assert_eq!(
+        result,
+        TakeResult {
+            amount_a_out: AMOUNT,
+            amount_b_in: RECEIVE,
+            fee: 0,
+        }
+    );
+
+    // escrow 和 vault 都被关闭, 双方收到对方的 token
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    let vault = get_associated_token_address(&escrow, &fx.mint_a);
+    assert!(fx
+        .ctx
+        .banks_client
+        .get_account(vault)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_b).await,
+        AMOUNT - RECEIVE
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &fx.maker.pubkey(), &fx.mint_b).await,
+        RECEIVE
+    );
+}
+
+#[tokio::test]
+async fn make_then_refund() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+    assert_eq!(
+        token_balance(&mut fx.ctx, &fx.maker.pubkey(), &fx.mint_a).await,
+        0
+    );
+
+    let refund = ix(
+        refund_accounts(&fx, &fx.maker.pubkey(), &fx.maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    let maker = fx.maker.insecure_clone();
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn take_rejects_token_b_account_of_another_mint() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    // taker 用自己的 token A 账户冒充 token B 账户支付
+    let mut accounts = take_accounts(&fx, &fx.stranger.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.taker_ata_b = get_associated_token_address(&fx.stranger.pubkey(), &fx.mint_a);
+    let take = ix(accounts, take_args(RECEIVE));
+    let stranger = fx.stranger.insecure_clone();
+
+    // 错误码是固定的数值, 客户端依赖它解码: EscrowError::TakerAtaBWrongMint
+    assert_error(send(&mut fx.ctx, &[take], &[&stranger]).await, 6091u32);
+    assert_eq!(
+        fetch_escrow(&mut fx.ctx, &escrow).await.unwrap().amount,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn wrong_token_program_surfaces_its_own_error() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    // fixture 的 mint 都属于 SPL Token, 传入 Token-2022 时返回 EscrowError::WrongTokenProgram
+    let mut accounts = take_accounts(&fx, &fx.taker.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.token_program_a = token_2022::ID;
+    let take = ix(accounts, take_args(RECEIVE));
+    let taker = fx.taker.insecure_clone();
+    assert_error(send(&mut fx.ctx, &[take], &[&taker]).await, 6090u32);
+
+    let mut accounts = refund_accounts(&fx, &fx.maker.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.token_program = token_2022::ID;
+    let refund = ix(accounts, instruction::Refund { force: false });
+    let maker = fx.maker.insecure_clone();
+    assert_error(send(&mut fx.ctx, &[refund], &[&maker]).await, 6090u32);
+
+    assert_eq!(
+        fetch_escrow(&mut fx.ctx, &escrow).await.unwrap().amount,
+        AMOUNT
+    );
+}
+
+// 用 maker 四分之一的 token A 创建托管, start_time 和 expiry 为 0 时不限制
+async fn make_timed(fx: &mut Fixture, seed: u64, start_time: i64, expiry: i64) -> Pubkey {
+    let maker = fx.maker.insecure_clone();
+    let make = ix(
+        make_accounts(fx, &maker.pubkey(), seed),
+        instruction::Make {
+            start_time,
+            expiry,
+            ..make_args(seed, RECEIVE, AMOUNT / 4)
+        },
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    pda::find_escrow_address(&maker.pubkey(), seed).0
+}
+
+#[tokio::test]
+async fn take_failures_surface_pinned_error_codes() {
+    let mut fx = setup().await;
+    let taker = fx.taker.insecure_clone();
+    let maker = fx.maker.insecure_clone();
+    let mut clock: Clock = fx.ctx.banks_client.get_sysvar().await.unwrap();
+    let now = clock.unix_timestamp;
+
+    // 错误码是固定的数值, 客户端依赖它解码, 这里直接断言数值而不是枚举
+    // EscrowError::OfferNotStarted
+    let pending = make_timed(&mut fx, 1, now + 60, 0).await;
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &pending),
+        take_args(RECEIVE),
+    );
+    assert_error(send(&mut fx.ctx, &[take], &[&taker]).await, 6024u32);
+
+    // EscrowError::EscrowExpired: 过期之后和 refund_expired 之前也不能按原价成交
+    let expiring = make_timed(&mut fx, 2, 0, now + 60).await;
+    clock.unix_timestamp = now + 120;
+    fx.ctx.set_sysvar(&clock);
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &expiring),
+        take_args(RECEIVE),
+    );
+    assert_error(send(&mut fx.ctx, &[take], &[&taker]).await, 6092u32);
+
+    // EscrowError::SelfTrade: maker 持有 token B 也不能成交自己的托管
+    let escrow = make_timed(&mut fx, 3, 0, 0).await;
+    fund_ata(&mut fx.ctx, &fx.mint_b, &maker.pubkey(), AMOUNT).await;
+    let take = ix(
+        take_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    assert_error(send(&mut fx.ctx, &[take], &[&maker]).await, 6010u32);
+
+    // EscrowError::InvalidVault: 伪造的 vault 的 mint 和 authority 都正确, 只有地址不是 make 时记录的
+    let forged = Pubkey::new_unique();
+    let len = spl_token::state::Account::LEN;
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(rent.minimum_balance(len), len, &spl_token::ID);
+    let mut data = vec![0; len];
+    let state = spl_token::state::Account {
+        mint: fx.mint_a,
+        owner: escrow,
+        amount: AMOUNT / 4,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    spl_token::state::Account::pack(state, &mut data).unwrap();
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(&forged, &account);
+    let mut accounts = take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow);
+    accounts.vault = forged;
+    let take = ix(accounts, take_args(RECEIVE));
+    assert_error(send(&mut fx.ctx, &[take], &[&taker]).await, 6087u32);
+
+    // EscrowError::EmptyVault: 模拟 permanent delegate 销毁了 vault 中的全部 token A
+    let vault = get_associated_token_address(&escrow, &fx.mint_a);
+    let mut account = fx
+        .ctx
+        .banks_client
+        .get_account(vault)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut state = spl_token::state::Account::unpack(&account.data).unwrap();
+    state.amount = 0;
+    spl_token::state::Account::pack(state, &mut account.data).unwrap();
+    fx.ctx
+        .set_account(&vault, &AccountSharedData::from(account));
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    assert_error(send(&mut fx.ctx, &[take], &[&taker]).await, 6027u32);
+}
+
+#[tokio::test]
+async fn take_rejects_forged_escrow() {
+    let mut fx = setup().await;
+    let real = make(&mut fx, 1).await;
+
+    // 在其他地址伪造一个数据完全相同的 escrow 账户, owner 为托管程序, 再为它创建空的 vault
+    let state = fetch_escrow(&mut fx.ctx, &real).await.unwrap();
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    let forged = Pubkey::new_unique();
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &ID);
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(&forged, &account);
+    let mint_a = fx.mint_a;
+    fund_ata(&mut fx.ctx, &mint_a, &forged, 0).await;
+
+    let take = ix(
+        take_accounts(&fx, &fx.taker.pubkey(), &fx.maker.pubkey(), &forged),
+        take_args(RECEIVE),
+    );
+    let taker = fx.taker.insecure_clone();
+
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        ErrorCode::ConstraintSeeds,
+    );
+}
+
+#[tokio::test]
+async fn take_without_ata_programs_when_token_accounts_exist() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+    let (mint_b, maker) = (fx.mint_b, fx.maker.pubkey());
+    fund_ata(&mut fx.ctx, &mint_b, &maker, 0).await;
+
+    // stranger 已经有 token A 的 ATA, maker 也已经有 token B 的 ATA, 不需要创建账户
+    let mut accounts = take_accounts(&fx, &fx.stranger.pubkey(), &maker, &escrow);
+    accounts.associated_token_program = None;
+    accounts.system_program = None;
+    let take = ix(accounts, take_args(RECEIVE));
+    let stranger = fx.stranger.insecure_clone();
+    send(&mut fx.ctx, &[take], &[&stranger]).await.unwrap();
+
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker, &fx.mint_b).await,
+        RECEIVE
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &fx.stranger.pubkey(), &fx.mint_a).await,
+        AMOUNT * 2
+    );
+}
+
+#[tokio::test]
+async fn take_without_ata_programs_rejects_missing_token_accounts() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    // maker 还没有 token B 的 ATA, 缺少创建它所需要的程序
+    let mut accounts = take_accounts(&fx, &fx.stranger.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.associated_token_program = None;
+    accounts.system_program = None;
+    let take = ix(accounts, take_args(RECEIVE));
+    let stranger = fx.stranger.insecure_clone();
+
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&stranger]).await,
+        EscrowError::MissingAtaPrograms,
+    );
+}
+
+#[tokio::test]
+async fn take_rejects_malicious_ata_accounts() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+    let taker = fx.taker.insecure_clone();
+    let taker_ata_a = get_associated_token_address(&taker.pubkey(), &fx.mint_a);
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+
+    // 在 taker 的 ATA 地址预先放置一个属于其他程序的账户
+    let len = spl_token::state::Account::LEN;
+    let account = AccountSharedData::new(rent.minimum_balance(len), len, &ID);
+    fx.ctx.set_account(&taker_ata_a, &account);
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &fx.maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::InvalidAtaAccount,
+    );
+
+    // 属于 token 程序, 但数据中的 owner 是 stranger, 转入的 token A 会落到 stranger 手里
+    let forged = spl_token::state::Account {
+        mint: fx.mint_a,
+        owner: fx.stranger.pubkey(),
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut account = AccountSharedData::new(rent.minimum_balance(len), len, &spl_token::ID);
+    let mut data = vec![0; len];
+    spl_token::state::Account::pack(forged, &mut data).unwrap();
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(&taker_ata_a, &account);
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &fx.maker.pubkey(), &escrow),
+        take_args(RECEIVE + 1),
+    );
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::InvalidAtaAccount,
+    );
+
+    // 用 stranger 已经存在的 token B 账户冒充 maker 的 ATA 收款
+    let mut accounts = take_accounts(&fx, &fx.stranger.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.maker_ata_b = Some(get_associated_token_address(
+        &fx.stranger.pubkey(),
+        &fx.mint_b,
+    ));
+    let take = ix(accounts, take_args(RECEIVE));
+    let stranger = fx.stranger.insecure_clone();
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&stranger]).await,
+        EscrowError::InvalidAtaAccount,
+    );
+    assert_eq!(
+        fetch_escrow(&mut fx.ctx, &escrow).await.unwrap().amount,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn refund_rejects_non_maker() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    // stranger 既不是 maker 也不是托管的 refund_delegate, 签名后同样不能退还
+    let refund = ix(
+        refund_accounts(&fx, &fx.stranger.pubkey(), &fx.maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    let stranger = fx.stranger.insecure_clone();
+
+    assert_error(
+        send(&mut fx.ctx, &[refund], &[&stranger]).await,
+        EscrowError::UnauthorizedRefund,
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn make_falls_back_to_the_maker_as_rent_payer() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+
+    // 不传 rent_payer 时由 maker 支付并记录为 rent_payer
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, RECEIVE, AMOUNT / 2),
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.rent_payer, maker.pubkey());
+
+    // 旧客户端把 maker 自己作为 rent_payer 传入, 结果相同
+    let mut accounts = make_accounts(&fx, &maker.pubkey(), 2);
+    accounts.rent_payer = Some(maker.pubkey());
+    let make = ix(accounts, make_args(2, RECEIVE, AMOUNT / 2));
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 2).0;
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.rent_payer, maker.pubkey());
+}
+
+#[tokio::test]
+async fn refund_rejects_rent_payer_of_another_escrow() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    // maker 签名, 但把租金指向 stranger
+    let mut accounts = refund_accounts(&fx, &fx.maker.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.rent_payer = fx.stranger.pubkey();
+    let refund = ix(accounts, instruction::Refund { force: false });
+    let maker = fx.maker.insecure_clone();
+
+    assert_error(
+        send(&mut fx.ctx, &[refund], &[&maker]).await,
+        EscrowError::InvalidRentPayer,
+    );
+}
+
+#[tokio::test]
+async fn seed_can_be_reused_only_after_close() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+    let maker = fx.maker.insecure_clone();
+
+    // 托管存在时同一个 seed 不能再次 make, system program 返回 AccountAlreadyInUse(0)
+    let remake = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, RECEIVE, 1),
+    );
+    assert_error(send(&mut fx.ctx, &[remake], &[&maker]).await, 0u32);
+
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+
+    // 关闭之后同一个地址可以重新创建, 数据是新的托管
+    let remake = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, 2 * RECEIVE, AMOUNT / 2),
+    );
+    send(&mut fx.ctx, &[remake], &[&maker]).await.unwrap();
+
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.amount, AMOUNT / 2);
+    assert_eq!(state.deposited, AMOUNT / 2);
+    assert_eq!(state.receive, 2 * RECEIVE);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+        AMOUNT / 2
+    );
+}
+
+#[tokio::test]
+async fn second_take_in_the_same_slot_fails() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+    let (taker, stranger) = (fx.taker.insecure_clone(), fx.stranger.insecure_clone());
+
+    let first = ix(
+        take_accounts(&fx, &taker.pubkey(), &fx.maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    let second = ix(
+        take_accounts(&fx, &stranger.pubkey(), &fx.maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    send(&mut fx.ctx, &[first], &[&taker]).await.unwrap();
+
+    // 两笔交易使用同一个 blockhash, 在同一个 slot 中处理; 第一笔成交关闭了 escrow, 第二笔找不到托管账户
+    let result = send(&mut fx.ctx, &[second], &[&stranger]).await;
+    assert_error(result, ErrorCode::AccountNotInitialized);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &stranger.pubkey(), &fx.mint_b).await,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn refund_batch_reverts_when_one_escrow_fails() {
+    let mut fx = setup().await;
+    let (maker, stranger) = (fx.maker.insecure_clone(), fx.stranger.insecure_clone());
+    let mut escrows = Vec::new();
+    for seed in [1, 2] {
+        let make = ix(
+            make_accounts(&fx, &maker.pubkey(), seed),
+            make_args(seed, RECEIVE, AMOUNT / 2),
+        );
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+        escrows.push(pda::find_escrow_address(&maker.pubkey(), seed).0);
+    }
+    let make = ix(
+        make_accounts(&fx, &stranger.pubkey(), 1),
+        make_args(1, RECEIVE, AMOUNT),
+    );
+    send(&mut fx.ctx, &[make], &[&stranger]).await.unwrap();
+    let foreign = pda::find_escrow_address(&stranger.pubkey(), 1).0;
+
+    // 最后一个托管属于 stranger, 前两个已经退还的托管也一起回滚
+    let refund = refund_batch_ix(&fx, &maker.pubkey(), &[escrows[0], escrows[1], foreign]);
+    assert_error(
+        send(&mut fx.ctx, &[refund], &[&maker]).await,
+        EscrowError::InvalidMaker,
+    );
+    for escrow in &escrows {
+        assert!(fetch_escrow(&mut fx.ctx, escrow).await.is_some());
+        assert_eq!(
+            token_balance(&mut fx.ctx, escrow, &fx.mint_a).await,
+            AMOUNT / 2
+        );
+    }
+
+    let refund = refund_batch_ix(&fx, &maker.pubkey(), &escrows);
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+    for escrow in &escrows {
+        assert!(fetch_escrow(&mut fx.ctx, escrow).await.is_none());
+    }
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn arbiter_timeout_lets_either_party_unwind() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let arbiter = Keypair::new();
+
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        instruction::Make {
+            arbiter: arbiter.pubkey(),
+            ..make_args(1, RECEIVE, AMOUNT)
+        },
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+
+    let lock = ix(
+        lock_take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::LockTake {
+            max_receive: RECEIVE,
+            proof: vec![],
+        },
+    );
+    send(&mut fx.ctx, &[lock], &[&taker]).await.unwrap();
+    let locked_at = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap().locked_at;
+
+    // 期限之前任何一方单独签名都不能退回
+    let early = ix(
+        release_accounts(
+            &fx,
+            &taker.pubkey(),
+            &maker.pubkey(),
+            &taker.pubkey(),
+            &escrow,
+        ),
+        instruction::ReleaseToMaker {},
+    );
+    assert_error(
+        send(&mut fx.ctx, &[early], &[&taker]).await,
+        EscrowError::UnauthorizedRelease,
+    );
+
+    let mut clock: Clock = fx.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = locked_at + Escrow::ARBITRATION_TIMEOUT + 1;
+    fx.ctx.set_sysvar(&clock);
+
+    // 超时之后也不能单独完成交易, 但 maker 可以单独退回
+    let complete = ix(
+        release_accounts(
+            &fx,
+            &taker.pubkey(),
+            &maker.pubkey(),
+            &taker.pubkey(),
+            &escrow,
+        ),
+        instruction::ReleaseToTaker {},
+    );
+    assert_error(
+        send(&mut fx.ctx, &[complete], &[&taker]).await,
+        EscrowError::UnauthorizedRelease,
+    );
+    let release = ix(
+        release_accounts(
+            &fx,
+            &maker.pubkey(),
+            &maker.pubkey(),
+            &taker.pubkey(),
+            &escrow,
+        ),
+        instruction::ReleaseToMaker {},
+    );
+    send(&mut fx.ctx, &[release], &[&maker]).await.unwrap();
+
+    // 双方各自取回, 托管和两个 vault 都被关闭
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_b).await,
+        AMOUNT
+    );
+    let vault_b = get_associated_token_address(&escrow, &fx.mint_b);
+    assert!(fx
+        .ctx
+        .banks_client
+        .get_account(vault_b)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+// 允许预约 60 秒的托管, taker 支付 BOND 预约
+const BOND: u64 = 100_000_000;
+
+async fn make_reservable(fx: &mut Fixture) -> Pubkey {
+    let maker = fx.maker.insecure_clone();
+    let make = ix(
+        make_accounts(fx, &maker.pubkey(), 1),
+        instruction::Make {
+            reservation_window: 60,
+            ..make_args(1, RECEIVE, AMOUNT)
+        },
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+
+    let taker = fx.taker.insecure_clone();
+    let reserve = ix(
+        reserve_accounts(&taker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Reserve {
+            bond_lamports: BOND,
+            proof: vec![],
+        },
+    );
+    send(&mut fx.ctx, &[reserve], &[&taker]).await.unwrap();
+    escrow
+}
+
+fn settle_ix(fx: &Fixture, taker: &Pubkey, escrow: &Pubkey) -> Instruction {
+    let take = take_args(RECEIVE);
+    ix(
+        accounts::Settle {
+            take: take_accounts(fx, taker, &fx.maker.pubkey(), escrow),
+        },
+        instruction::Settle {
+            max_receive: take.max_receive,
+            expected_amount_a: take.expected_amount_a,
+            proof: take.proof,
+            preimage: take.preimage,
+            write_receipt: take.write_receipt,
+        },
+    )
+}
+
+// escrow 账户超出免租金的 lamports, 即其中的保证金
+async fn escrow_surplus(fx: &mut Fixture, escrow: &Pubkey) -> u64 {
+    let account = fx
+        .ctx
+        .banks_client
+        .get_account(*escrow)
+        .await
+        .unwrap()
+        .unwrap();
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    account.lamports - rent.minimum_balance(account.data.len())
+}
+
+#[tokio::test]
+async fn reservation_blocks_others_and_settle_returns_the_bond() {
+    let mut fx = setup().await;
+    let (maker, taker, stranger) = (
+        fx.maker.insecure_clone(),
+        fx.taker.insecure_clone(),
+        fx.stranger.insecure_clone(),
+    );
+    let escrow = make_reservable(&mut fx).await;
+
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.reserved_taker, taker.pubkey());
+    assert_eq!(state.bond, BOND);
+    assert_eq!(escrow_surplus(&mut fx, &escrow).await, BOND);
+
+    // 预约期间其他 taker 不能成交, maker 不能退还, 预约的 taker 也必须通过 settle
+    let take = ix(
+        take_accounts(&fx, &stranger.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&stranger]).await,
+        EscrowError::EscrowReserved,
+    );
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    assert_error(
+        send(&mut fx.ctx, &[refund], &[&maker]).await,
+        EscrowError::EscrowReserved,
+    );
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::EscrowReserved,
+    );
+    let settle = settle_ix(&fx, &stranger.pubkey(), &escrow);
+    assert_error(
+        send(&mut fx.ctx, &[settle], &[&stranger]).await,
+        EscrowError::UnauthorizedTaker,
+    );
+
+    // settle 成交并退还保证金, taker 另外支付了 token A 的 ATA 租金
+    let before = fx
+        .ctx
+        .banks_client
+        .get_balance(taker.pubkey())
+        .await
+        .unwrap();
+    let settle = settle_ix(&fx, &taker.pubkey(), &escrow);
+    send(&mut fx.ctx, &[settle], &[&taker]).await.unwrap();
+
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let ata_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(taker.pubkey())
+            .await
+            .unwrap(),
+        before + BOND - ata_rent
+    );
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn expired_reservation_forfeits_the_bond_and_reopens_the_escrow() {
+    let mut fx = setup().await;
+    let (maker, taker, stranger) = (
+        fx.maker.insecure_clone(),
+        fx.taker.insecure_clone(),
+        fx.stranger.insecure_clone(),
+    );
+    let escrow = make_reservable(&mut fx).await;
+    let expiry = fetch_escrow(&mut fx.ctx, &escrow)
+        .await
+        .unwrap()
+        .reservation_expiry;
+
+    let early = ix(
+        expire_reservation_accounts(&stranger.pubkey(), &maker.pubkey(), &escrow),
+        instruction::ExpireReservation {},
+    );
+    assert_error(
+        send(&mut fx.ctx, &[early], &[&stranger]).await,
+        EscrowError::ReservationNotExpired,
+    );
+
+    let mut clock: Clock = fx.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = expiry;
+    fx.ctx.set_sysvar(&clock);
+
+    // 截止之后 taker 不能再 settle, 任何人都可以让保证金归 maker
+    let settle = settle_ix(&fx, &taker.pubkey(), &escrow);
+    assert_error(
+        send(&mut fx.ctx, &[settle], &[&taker]).await,
+        EscrowError::ReservationExpired,
+    );
+    let before = fx
+        .ctx
+        .banks_client
+        .get_balance(maker.pubkey())
+        .await
+        .unwrap();
+    let expire = ix(
+        expire_reservation_accounts(&taker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::ExpireReservation {},
+    );
+    send(&mut fx.ctx, &[expire], &[&taker]).await.unwrap();
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(maker.pubkey())
+            .await
+            .unwrap(),
+        before + BOND
+    );
+
+    // escrow 只剩下免租金的 lamports, 重新开放给其他 taker
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.reserved_taker, Pubkey::default());
+    assert_eq!(state.bond, 0);
+    assert_eq!(escrow_surplus(&mut fx, &escrow).await, 0);
+    let take = ix(
+        take_accounts(&fx, &stranger.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    send(&mut fx.ctx, &[take], &[&stranger]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn take_pays_with_a_listed_alternative_mint() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    // 另一种稳定币的 decimals 不同, 同样数量的 token A 对应不同的 receive
+    const ALT_RECEIVE: u64 = 500_000;
+    let listed = create_mint(&mut fx.ctx).await;
+    let unlisted = create_mint(&mut fx.ctx).await;
+    for mint in [&listed, &unlisted] {
+        fund_ata(&mut fx.ctx, mint, &taker.pubkey(), ALT_RECEIVE).await;
+    }
+
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        instruction::Make {
+            alt_payments: vec![PaymentMint {
+                mint: listed,
+                receive: ALT_RECEIVE,
+            }],
+            ..make_args(1, RECEIVE, AMOUNT)
+        },
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+
+    let take_in = |fx: &Fixture, mint: &Pubkey| {
+        ix(
+            accounts::Take {
+                mint_b: *mint,
+                taker_ata_b: get_associated_token_address(&taker.pubkey(), mint),
+                maker_ata_b: Some(get_associated_token_address(&maker.pubkey(), mint)),
+                ..take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &escrow)
+            },
+            take_args(ALT_RECEIVE),
+        )
+    };
+    let take = take_in(&fx, &unlisted);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::InvalidMintB,
+    );
+    let take = take_in(&fx, &listed);
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+
+    // maker 收到第二种 mint, taker 的 mint_b 没有被动用
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &listed).await,
+        ALT_RECEIVE
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_b).await,
+        AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+}
+
+// 创建 MAX_BASKET_LEGS 个 mint 并给 maker 充值, 返回 mint 和每种 token 存入的数量
+async fn basket_legs(fx: &mut Fixture) -> Vec<(Pubkey, u64)> {
+    let maker = fx.maker.pubkey();
+    let mut legs = Vec::new();
+    for i in 1..=MAX_BASKET_LEGS as u64 {
+        let mint = create_mint(&mut fx.ctx).await;
+        fund_ata(&mut fx.ctx, &mint, &maker, AMOUNT).await;
+        legs.push((mint, i * 100));
+    }
+    legs
+}
+
+#[tokio::test]
+async fn basket_at_the_cap_is_taken_in_one_transaction() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let legs = basket_legs(&mut fx).await;
+    let mints: Vec<Pubkey> = legs.iter().map(|(mint, _)| *mint).collect();
+
+    let make = make_basket_ix(&fx, &maker.pubkey(), 1, RECEIVE, &legs);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let basket = pda::find_basket_address(&maker.pubkey(), 1).0;
+    for (mint, amount) in &legs {
+        assert_eq!(token_balance(&mut fx.ctx, &basket, mint).await, *amount);
+    }
+
+    // 少传一种 token 时拒绝, 不能只取走篮子的一部分
+    let partial = take_basket_ix(&fx, &taker.pubkey(), &maker.pubkey(), 1, &mints[1..]);
+    assert_error(
+        send(&mut fx.ctx, &[partial], &[&taker]).await,
+        EscrowError::InvalidBasketAccounts,
+    );
+
+    // taker 还没有任何一种 token 的 ATA, 全部由 take_basket 创建
+    let take = take_basket_ix(&fx, &taker.pubkey(), &maker.pubkey(), 1, &mints);
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+
+    for (mint, amount) in &legs {
+        assert_eq!(
+            token_balance(&mut fx.ctx, &taker.pubkey(), mint).await,
+            *amount
+        );
+        let vault = get_associated_token_address(&basket, mint);
+        assert!(fx
+            .ctx
+            .banks_client
+            .get_account(vault)
+            .await
+            .unwrap()
+            .is_none());
+    }
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        RECEIVE
+    );
+    assert!(fx
+        .ctx
+        .banks_client
+        .get_account(basket)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn basket_refund_returns_every_leg_and_closes_empty_vaults() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let legs = basket_legs(&mut fx).await;
+    let mints: Vec<Pubkey> = legs.iter().map(|(mint, _)| *mint).collect();
+
+    let make = make_basket_ix(&fx, &maker.pubkey(), 1, RECEIVE, &legs);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let basket = pda::find_basket_address(&maker.pubkey(), 1).0;
+
+    // 模拟 permanent delegate 销毁了第一个 vault 中的全部 token
+    let empty = get_associated_token_address(&basket, &mints[0]);
+    let mut account = fx
+        .ctx
+        .banks_client
+        .get_account(empty)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut vault = spl_token::state::Account::unpack(&account.data).unwrap();
+    vault.amount = 0;
+    spl_token::state::Account::pack(vault, &mut account.data).unwrap();
+    fx.ctx
+        .set_account(&empty, &AccountSharedData::from(account));
+
+    let refund = refund_basket_ix(&maker.pubkey(), 1, &mints);
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &mints[0]).await,
+        AMOUNT - legs[0].1
+    );
+    for mint in &mints[1..] {
+        assert_eq!(
+            token_balance(&mut fx.ctx, &maker.pubkey(), mint).await,
+            AMOUNT
+        );
+    }
+    for mint in &mints {
+        let vault = get_associated_token_address(&basket, mint);
+        assert!(fx
+            .ctx
+            .banks_client
+            .get_account(vault)
+            .await
+            .unwrap()
+            .is_none());
+    }
+    assert!(fx
+        .ctx
+        .banks_client
+        .get_account(basket)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+// 循环托管每次存入的数量和价格, maker 的余额足够存入 1 + max_refills 次
+const RECURRING_AMOUNT: u64 = AMOUNT / 4;
+const RECURRING_RECEIVE: u64 = RECEIVE / 4;
+
+async fn make_recurring(fx: &mut Fixture, seed: u64, max_refills: u8) -> Pubkey {
+    let maker = fx.maker.insecure_clone();
+    let make = ix(
+        make_accounts(fx, &maker.pubkey(), seed),
+        instruction::Make {
+            recurring: true,
+            max_refills,
+            ..make_args(seed, RECURRING_RECEIVE, RECURRING_AMOUNT)
+        },
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    pda::find_escrow_address(&maker.pubkey(), seed).0
+}
+
+async fn maker_ata_a(fx: &mut Fixture) -> spl_token::state::Account {
+    let address = get_associated_token_address(&fx.maker.pubkey(), &fx.mint_a);
+    let account = fx
+        .ctx
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    spl_token::state::Account::unpack(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn recurring_escrow_refills_until_exhausted() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let escrow = make_recurring(&mut fx, 1, 2).await;
+
+    // make 把之后两次存入的额度授权给 escrow PDA
+    let account = maker_ata_a(&mut fx).await;
+    assert_eq!(account.delegate, Some(escrow).into());
+    assert_eq!(account.delegated_amount, 2 * RECURRING_AMOUNT);
+    assert_eq!(account.amount, AMOUNT - RECURRING_AMOUNT);
+
+    for round in 1..=3u64 {
+        // max_receive 每轮不同, 避免交易重复
+        let take = ix(
+            take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+            take_args(RECURRING_RECEIVE + round),
+        );
+        send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+        assert_eq!(
+            token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+            round * RECURRING_AMOUNT
+        );
+
+        match round {
+            // 前两次成交后重新存入, escrow 和 vault 保持打开
+            1 | 2 => {
+                let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+                assert_eq!(state.max_refills, 2 - round as u8);
+                assert_eq!(state.amount, RECURRING_AMOUNT);
+                assert_eq!(
+                    token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+                    RECURRING_AMOUNT
+                );
+                let account = maker_ata_a(&mut fx).await;
+                assert_eq!(account.delegated_amount, (2 - round) * RECURRING_AMOUNT);
+                assert_eq!(account.amount, AMOUNT - (round + 1) * RECURRING_AMOUNT);
+            }
+            // 次数用完后正常关闭
+            _ => assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none()),
+        }
+    }
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        3 * RECURRING_RECEIVE
+    );
+}
+
+#[tokio::test]
+async fn recurring_escrow_closes_when_the_allowance_is_revoked() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+
+    // recurring 和 max_refills 必须同时设置
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        instruction::Make {
+            recurring: true,
+            ..make_args(1, RECURRING_RECEIVE, RECURRING_AMOUNT)
+        },
+    );
+    assert_error(
+        send(&mut fx.ctx, &[make], &[&maker]).await,
+        EscrowError::InvalidRecurring,
+    );
+
+    let escrow = make_recurring(&mut fx, 2, 3).await;
+    let revoke = spl_token::instruction::revoke(
+        &spl_token::id(),
+        &get_associated_token_address(&maker.pubkey(), &fx.mint_a),
+        &maker.pubkey(),
+        &[],
+    )
+    .unwrap();
+    send(&mut fx.ctx, &[revoke], &[&maker]).await.unwrap();
+
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECURRING_RECEIVE),
+    );
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+
+    // 没有额度时不重新存入, maker 的余额不变
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(maker_ata_a(&mut fx).await.amount, AMOUNT - RECURRING_AMOUNT);
+}
+
+async fn fetch_user_stats(fx: &mut Fixture, wallet: &Pubkey) -> UserStats {
+    let address = pda::find_user_stats_address(wallet).0;
+    let account = fx
+        .ctx
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    UserStats::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn fee_tier_applies_from_the_taker_trade_count() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    const TIER_RECEIVE: u64 = 300;
+    let fee_vault_b = fund_ata(
+        &mut fx.ctx,
+        &fx.mint_b,
+        &pda::find_fee_authority_address().0,
+        0,
+    )
+    .await;
+
+    // 基础 10%, 成交过 1 次之后 5%, 2 次之后免手续费
+    let (admin, config) = (fx.ctx.payer.pubkey(), pda::find_config_address().0);
+    let set_fee = ix(
+        accounts::SetFee { admin, config },
+        instruction::SetFee { fee_bps: 1_000 },
+    );
+    let set_tiers = ix(
+        accounts::SetFeeTiers { admin, config },
+        instruction::SetFeeTiers {
+            tiers: vec![
+                FeeTier {
+                    min_trades: 1,
+                    fee_bps: 500,
+                },
+                FeeTier {
+                    min_trades: 2,
+                    fee_bps: 0,
+                },
+            ],
+        },
+    );
+    let init_maker_stats = ix(
+        accounts::InitUserStats {
+            wallet: maker.pubkey(),
+            user_stats: pda::find_user_stats_address(&maker.pubkey()).0,
+            system_program: system_program::ID,
+        },
+        instruction::InitUserStats {},
+    );
+    send(&mut fx.ctx, &[set_fee, set_tiers], &[]).await.unwrap();
+    send(&mut fx.ctx, &[init_maker_stats], &[&maker])
+        .await
+        .unwrap();
+
+    let take_with_stats = |fx: &Fixture, seed: u64, taker_stats: bool| {
+        let escrow = pda::find_escrow_address(&maker.pubkey(), seed).0;
+        ix(
+            accounts::Take {
+                fee_vault_b: Some(fee_vault_b),
+                taker_stats: taker_stats.then(|| pda::find_user_stats_address(&taker.pubkey()).0),
+                maker_stats: Some(pda::find_user_stats_address(&maker.pubkey()).0),
+                ..take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &escrow)
+            },
+            take_args(TIER_RECEIVE),
+        )
+    };
+
+    for seed in 1..=3 {
+        let make = ix(
+            make_accounts(&fx, &maker.pubkey(), seed),
+            make_args(seed, TIER_RECEIVE, RECEIVE / 2),
+        );
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    }
+
+    // 启用档位后必须传入 taker 的统计账户
+    let take = take_with_stats(&fx, 1, false);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::MissingUserStats,
+    );
+
+    // 每次成交按之前的次数收费: 0 次 10%, 正好 1 次 5%, 正好 2 次 0
+    let mut collected = 0;
+    for (seed, fee) in [(1, 30), (2, 15), (3, 0)] {
+        let take = take_with_stats(&fx, seed, true);
+        send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+        collected += fee;
+        assert_eq!(
+            token_balance(
+                &mut fx.ctx,
+                &pda::find_fee_authority_address().0,
+                &fx.mint_b
+            )
+            .await,
+            collected
+        );
+    }
+
+    // 两边都计入 3 次成交和全部 token B
+    for wallet in [taker.pubkey(), maker.pubkey()] {
+        let stats = fetch_user_stats(&mut fx, &wallet).await;
+        assert_eq!(stats.wallet, wallet);
+        assert_eq!(stats.trades, 3);
+        assert_eq!(stats.volume_b, 3 * TIER_RECEIVE);
+    }
+}
+
+#[tokio::test]
+async fn listing_fee_goes_to_the_treasury_unless_the_maker_is_exempt() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let treasury = fx.stranger.pubkey();
+    // 高于空账户的免租金额, treasury 不存在时转入也能成功
+    const LISTING_FEE: u64 = 1_000_000;
+
+    let (admin, config) = (fx.ctx.payer.pubkey(), pda::find_config_address().0);
+    let set_listing_fee = ix(
+        accounts::SetListingFee { admin, config },
+        instruction::SetListingFee {
+            listing_fee_lamports: LISTING_FEE,
+            treasury,
+        },
+    );
+    send(&mut fx.ctx, &[set_listing_fee], &[]).await.unwrap();
+
+    let make_with = |fx: &Fixture, seed: u64, treasury: Option<Pubkey>, exempt: bool| {
+        ix(
+            accounts::Make {
+                treasury,
+                exempt_list: exempt.then(|| pda::find_exempt_list_address().0),
+                ..make_accounts(fx, &maker.pubkey(), seed)
+            },
+            make_args(seed, RECEIVE, AMOUNT / 4),
+        )
+    };
+
+    let make = make_with(&fx, 1, None, false);
+    assert_error(
+        send(&mut fx.ctx, &[make], &[&maker]).await,
+        EscrowError::MissingTreasury,
+    );
+    let make = make_with(&fx, 1, Some(Pubkey::new_unique()), false);
+    assert_error(
+        send(&mut fx.ctx, &[make], &[&maker]).await,
+        EscrowError::InvalidTreasury,
+    );
+
+    // maker 作为 rent_payer 支付挂单费
+    let before = fx.ctx.banks_client.get_balance(treasury).await.unwrap();
+    let make = make_with(&fx, 1, Some(treasury), false);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    assert_eq!(
+        fx.ctx.banks_client.get_balance(treasury).await.unwrap(),
+        before + LISTING_FEE
+    );
+
+    // 豁免的 maker 不需要传入 treasury
+    let exempt = ix(
+        accounts::UpdateExemptList {
+            admin,
+            config,
+            exempt_list: pda::find_exempt_list_address().0,
+            system_program: system_program::ID,
+        },
+        instruction::AddExemptWallet {
+            wallet: maker.pubkey(),
+        },
+    );
+    send(&mut fx.ctx, &[exempt], &[]).await.unwrap();
+    let make = make_with(&fx, 2, None, true);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let make = make_with(&fx, 3, Some(treasury), true);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    assert_eq!(
+        fx.ctx.banks_client.get_balance(treasury).await.unwrap(),
+        before + LISTING_FEE
+    );
+}
+
+#[tokio::test]
+async fn sweep_abandoned_refunds_the_maker_and_splits_the_rent() {
+    let mut fx = setup().await;
+    let (maker, taker, stranger) = (
+        fx.maker.insecure_clone(),
+        fx.taker.insecure_clone(),
+        fx.stranger.insecure_clone(),
+    );
+    // 两年后被视为放弃, cranker 分得 25% 的租金
+    const ABANDON_SECONDS: i64 = 2 * 365 * 24 * 60 * 60;
+    const CRANKER_BPS: u64 = 2_500;
+
+    let set_policy = ix(
+        accounts::SetAbandonPolicy {
+            admin: fx.ctx.payer.pubkey(),
+            config: pda::find_config_address().0,
+        },
+        instruction::SetAbandonPolicy {
+            abandon_seconds: ABANDON_SECONDS,
+            abandon_cranker_bps: CRANKER_BPS as u16,
+        },
+    );
+    send(&mut fx.ctx, &[set_policy], &[]).await.unwrap();
+
+    let escrow = make(&mut fx, 1).await;
+    let created_at = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap().created_at;
+    let sweep = |fx: &Fixture, cranker: &Keypair| {
+        ix(
+            sweep_abandoned_accounts(fx, &cranker.pubkey(), &maker.pubkey(), &escrow),
+            instruction::SweepAbandoned {},
+        )
+    };
+
+    // 刚好满两年时还不算放弃
+    let mut clock: Clock = fx.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = created_at + ABANDON_SECONDS;
+    fx.ctx.set_sysvar(&clock);
+    let early = sweep(&fx, &taker);
+    assert_error(
+        send(&mut fx.ctx, &[early], &[&taker]).await,
+        EscrowError::EscrowNotAbandoned,
+    );
+    clock.unix_timestamp += 1;
+    fx.ctx.set_sysvar(&clock);
+
+    // maker 的 ATA 已经清空并关闭, 由 cranker 重新创建
+    let maker_ata_a = get_associated_token_address(&maker.pubkey(), &fx.mint_a);
+    let close = spl_token::instruction::close_account(
+        &spl_token::ID,
+        &maker_ata_a,
+        &maker.pubkey(),
+        &maker.pubkey(),
+        &[],
+    )
+    .unwrap();
+    send(&mut fx.ctx, &[close], &[&maker]).await.unwrap();
+
+    let vault = get_associated_token_address(&escrow, &fx.mint_a);
+    let mut balances = Vec::new();
+    for account in [escrow, vault, maker.pubkey(), stranger.pubkey()] {
+        balances.push(fx.ctx.banks_client.get_balance(account).await.unwrap());
+    }
+    let [escrow_rent, vault_rent, maker_before, cranker_before] = balances[..] else {
+        unreachable!()
+    };
+    let sweep = sweep(&fx, &stranger);
+    send(&mut fx.ctx, &[sweep], &[&stranger]).await.unwrap();
+
+    // token A 全部退还给 maker, 回收的租金按比例向下取整分给 cranker, 其余归 maker
+    let reclaimed = escrow_rent + vault_rent;
+    let cranker_share = reclaimed * CRANKER_BPS / 10_000;
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let ata_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(stranger.pubkey())
+            .await
+            .unwrap(),
+        cranker_before + cranker_share - ata_rent
+    );
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(maker.pubkey())
+            .await
+            .unwrap(),
+        maker_before + reclaimed - cranker_share
+    );
+}
+
+#[tokio::test]
+async fn sweep_abandoned_shares_only_the_rent_of_a_pre_funded_escrow() {
+    let mut fx = setup().await;
+    let (maker, stranger) = (fx.maker.insecure_clone(), fx.stranger.insecure_clone());
+    const ABANDON_SECONDS: i64 = 60;
+    const CRANKER_BPS: u64 = 2_500;
+    const DONATION: u64 = 10_000_000;
+
+    let set_policy = ix(
+        accounts::SetAbandonPolicy {
+            admin: fx.ctx.payer.pubkey(),
+            config: pda::find_config_address().0,
+        },
+        instruction::SetAbandonPolicy {
+            abandon_seconds: ABANDON_SECONDS,
+            abandon_cranker_bps: CRANKER_BPS as u16,
+        },
+    );
+    send(&mut fx.ctx, &[set_policy], &[]).await.unwrap();
+
+    // make 之前有人向 escrow 地址转入 lamports, 它们不是租金, 不能分给 cranker
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let donate = system_instruction::transfer(&fx.ctx.payer.pubkey(), &escrow, DONATION);
+    send(&mut fx.ctx, &[donate], &[]).await.unwrap();
+    make(&mut fx, 1).await;
+
+    let created_at = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap().created_at;
+    let mut clock: Clock = fx.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = created_at + ABANDON_SECONDS + 1;
+    fx.ctx.set_sysvar(&clock);
+
+    let vault = get_associated_token_address(&escrow, &fx.mint_a);
+    let mut balances = Vec::new();
+    for account in [escrow, vault, maker.pubkey(), stranger.pubkey()] {
+        balances.push(fx.ctx.banks_client.get_balance(account).await.unwrap());
+    }
+    let [escrow_lamports, vault_rent, maker_before, cranker_before] = balances[..] else {
+        unreachable!()
+    };
+    let sweep = ix(
+        sweep_abandoned_accounts(&fx, &stranger.pubkey(), &maker.pubkey(), &escrow),
+        instruction::SweepAbandoned {},
+    );
+    send(&mut fx.ctx, &[sweep], &[&stranger]).await.unwrap();
+
+    // cranker 只分到两个账户免租金额的 25%, 转入的其余 lamports 全部还给 maker
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let escrow_rent = rent.minimum_balance(Escrow::SPACE);
+    assert!(escrow_lamports > escrow_rent);
+    let cranker_share = (escrow_rent + vault_rent) * CRANKER_BPS / 10_000;
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(stranger.pubkey())
+            .await
+            .unwrap(),
+        cranker_before + cranker_share
+    );
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(maker.pubkey())
+            .await
+            .unwrap(),
+        maker_before + escrow_lamports + vault_rent - cranker_share
+    );
+}
+
+#[tokio::test]
+async fn recover_vault_returns_tokens_from_an_orphaned_vault() {
+    let mut fx = setup().await;
+    let (maker, stranger) = (fx.maker.insecure_clone(), fx.stranger.insecure_clone());
+    for seed in [1, 2] {
+        let make = ix(
+            make_accounts(&fx, &maker.pubkey(), seed),
+            make_args(seed, RECEIVE, AMOUNT / 2),
+        );
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    }
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let vault = get_associated_token_address(&escrow, &fx.mint_a);
+
+    // escrow 仍然存在时不能绕过 refund 取回
+    let recover = ix(
+        recover_vault_accounts(&fx, &maker.pubkey(), 2),
+        instruction::RecoverVault { seed: 2 },
+    );
+    assert_error(
+        send(&mut fx.ctx, &[recover], &[&maker]).await,
+        EscrowError::EscrowNotOrphaned,
+    );
+
+    // 模拟只部分执行的交易: escrow 数据账户被关闭, vault 仍然持有 token A
+    fx.ctx.set_account(&escrow, &AccountSharedData::default());
+
+    // 其他人用自己的地址派生出的是另一个 PDA, 不是 vault 的 authority
+    let forged = ix(
+        accounts::RecoverVault {
+            maker: stranger.pubkey(),
+            registry: pda::find_registry_address(&stranger.pubkey()).0,
+            maker_ata_a: get_associated_token_address(&stranger.pubkey(), &fx.mint_a),
+            ..recover_vault_accounts(&fx, &maker.pubkey(), 1)
+        },
+        instruction::RecoverVault { seed: 1 },
+    );
+    assert!(send(&mut fx.ctx, &[forged], &[&stranger]).await.is_err());
+
+    let rent = fx.ctx.banks_client.get_balance(vault).await.unwrap();
+    let before = fx
+        .ctx
+        .banks_client
+        .get_balance(maker.pubkey())
+        .await
+        .unwrap();
+    let recover = ix(
+        recover_vault_accounts(&fx, &maker.pubkey(), 1),
+        instruction::RecoverVault { seed: 1 },
+    );
+    send(&mut fx.ctx, &[recover], &[&maker]).await.unwrap();
+
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT / 2
+    );
+    assert!(fx
+        .ctx
+        .banks_client
+        .get_account(vault)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        fx.ctx
+            .banks_client
+            .get_balance(maker.pubkey())
+            .await
+            .unwrap(),
+        before + rent
+    );
+}
+
+#[tokio::test]
+async fn make_accepts_a_pre_funded_escrow_address() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+
+    // 有人提前向 escrow 地址转入 lamports, anchor 的 init 不能再用 create_account
+    // 转入的数量要高于空账户的免租金额, 否则系统程序拒绝转账
+    const DONATION: u64 = 10_000_000;
+    let donate = system_instruction::transfer(&fx.ctx.payer.pubkey(), &escrow, DONATION);
+    send(&mut fx.ctx, &[donate], &[]).await.unwrap();
+
+    let make_ix = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, RECEIVE, AMOUNT),
+    );
+    send(&mut fx.ctx, &[make_ix], &[&maker]).await.unwrap();
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.maker, maker.pubkey());
+    assert_eq!(state.amount, AMOUNT);
+
+    // 转入的 lamports 已经超过免租金额, anchor 不再补足租金; 关闭时全部还给 rent_payer
+    assert_eq!(
+        fx.ctx.banks_client.get_balance(escrow).await.unwrap(),
+        DONATION
+    );
+}
+
+#[tokio::test]
+async fn donated_vault_tokens_go_back_to_the_maker() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+
+    // 有人提前创建 vault 并转入 token A, make 仍然可以创建托管
+    const DONATION: u64 = 300;
+    let mint_a = fx.mint_a;
+    let vault = fund_ata(&mut fx.ctx, &mint_a, &escrow, DONATION).await;
+    make(&mut fx, 1).await;
+
+    // 转入的代币不计入 amount, 价格仍然按 maker 存入的数量计算
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.amount, AMOUNT);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+        AMOUNT + DONATION
+    );
+
+    // taker 只买到 amount, 多余的代币在关闭 vault 时退还给 maker
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        DONATION
+    );
+    assert!(fx
+        .ctx
+        .banks_client
+        .get_account(vault)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn take_rejects_a_vault_other_than_the_recorded_one() {
+    let mut fx = setup().await;
+    let escrow = make(&mut fx, 1).await;
+
+    // 伪造一个同样属于 escrow 的 token A 账户, mint 和 authority 都正确, 只有地址和记录的 vault 不同
+    let forged = Pubkey::new_unique();
+    let len = spl_token::state::Account::LEN;
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(rent.minimum_balance(len), len, &spl_token::ID);
+    let mut data = vec![0; len];
+    let state = spl_token::state::Account {
+        mint: fx.mint_a,
+        owner: escrow,
+        amount: AMOUNT,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    spl_token::state::Account::pack(state, &mut data).unwrap();
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(&forged, &account);
+
+    let mut accounts = take_accounts(&fx, &fx.taker.pubkey(), &fx.maker.pubkey(), &escrow);
+    accounts.vault = forged;
+    let take = ix(accounts, take_args(RECEIVE));
+    let taker = fx.taker.insecure_clone();
+
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::InvalidVault,
+    );
+}
+
+// 把当前版本的托管改写为版本 1 的布局: 去掉版本 2 之后加入的字段和 version 字节, without_vault 时再去掉最后的 vault 字段
+async fn downgrade_to_v1(fx: &mut Fixture, escrow: &Pubkey, without_vault: bool) {
+    let mut account = fx
+        .ctx
+        .banks_client
+        .get_account(*escrow)
+        .await
+        .unwrap()
+        .unwrap();
+    account.data.truncate(Escrow::V2_SPACE);
+    account.data.remove(Escrow::DISCRIMINATOR.len());
+    if without_vault {
+        account.data.truncate(Escrow::V1_SPACE_WITHOUT_VAULT);
+    }
+    fx.ctx
+        .set_account(escrow, &AccountSharedData::from(account));
+}
+
+#[tokio::test]
+async fn migrate_escrow_upgrades_v1_escrows() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    for seed in [1, 2] {
+        let make = ix(
+            make_accounts(&fx, &maker.pubkey(), seed),
+            make_args(seed, RECEIVE, AMOUNT / 2),
+        );
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    }
+    let first = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let second = pda::find_escrow_address(&maker.pubkey(), 2).0;
+    let vault = fetch_escrow(&mut fx.ctx, &first).await.unwrap().vault;
+    assert_eq!(vault, get_associated_token_address(&first, &fx.mint_a));
+
+    // first 模拟加入 vault 字段之前创建的托管, second 已经有 vault 但没有 version 字节
+    downgrade_to_v1(&mut fx, &first, true).await;
+    downgrade_to_v1(&mut fx, &second, false).await;
+
+    let take = ix(
+        take_accounts(&fx, &fx.taker.pubkey(), &fx.maker.pubkey(), &first),
+        take_args(RECEIVE),
+    );
+    let taker = fx.taker.insecure_clone();
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::MigrationRequired,
+    );
+    let refund = ix(
+        refund_accounts(&fx, &fx.maker.pubkey(), &fx.maker.pubkey(), &second),
+        instruction::Refund { force: false },
+    );
+    assert_error(
+        send(&mut fx.ctx, &[refund], &[&maker]).await,
+        EscrowError::MigrationRequired,
+    );
+
+    // 任何人都可以迁移, 扩容的租金由 payer 支付; 迁移之后不能再次迁移
+    let stranger = fx.stranger.insecure_clone();
+    let migrate = |payer: &Keypair, escrow: Pubkey, mint_a: Pubkey| {
+        ix(
+            accounts::MigrateEscrow {
+                payer: payer.pubkey(),
+                escrow,
+                mint_a,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            },
+            instruction::MigrateEscrow {},
+        )
+    };
+    assert_error(
+        send(
+            &mut fx.ctx,
+            &[migrate(&stranger, first, fx.mint_b)],
+            &[&stranger],
+        )
+        .await,
+        EscrowError::InvalidMintA,
+    );
+    let ixs = [
+        migrate(&stranger, first, fx.mint_a),
+        migrate(&stranger, second, fx.mint_a),
+    ];
+    send(&mut fx.ctx, &ixs, &[&stranger]).await.unwrap();
+    assert_error(
+        send(&mut fx.ctx, &[migrate(&taker, first, fx.mint_a)], &[&taker]).await,
+        EscrowError::EscrowNotLegacy,
+    );
+
+    // 扩容到当前版本的大小并补足免租金的余额
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    for escrow in [first, second] {
+        let account = fx
+            .ctx
+            .banks_client
+            .get_account(escrow)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.data.len(), Escrow::SPACE);
+        assert_eq!(account.lamports, rent.minimum_balance(Escrow::SPACE));
+        let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+        assert_eq!(state.version, Escrow::VERSION);
+        assert_eq!(
+            state.vault,
+            get_associated_token_address(&escrow, &fx.mint_a)
+        );
+    }
+
+    let take = ix(
+        take_accounts(&fx, &fx.taker.pubkey(), &fx.maker.pubkey(), &first),
+        take_args(RECEIVE + 1),
+    );
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+    // force 只影响冻结的 vault, 这里只用来和上面失败的交易区分
+    let refund = ix(
+        refund_accounts(&fx, &fx.maker.pubkey(), &fx.maker.pubkey(), &second),
+        instruction::Refund { force: true },
+    );
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &first).await.is_none());
+    assert!(fetch_escrow(&mut fx.ctx, &second).await.is_none());
+}
+
+// 在 feed 地址上放置一个 pyth-solana-receiver 拥有并经过全部签名验证的 PriceUpdateV2 账户
+async fn set_price(fx: &mut Fixture, feed: &Pubkey, price: i64, conf: u64, publish_time: i64) {
+    let mut data = PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(Pubkey::new_unique().as_ref()); // write_authority
+    data.push(1); // VerificationLevel::Full
+    data.extend_from_slice(&[0; 32]); // feed_id
+    data.extend_from_slice(&price.to_le_bytes());
+    data.extend_from_slice(&conf.to_le_bytes());
+    data.extend_from_slice(&(-2i32).to_le_bytes());
+    data.extend_from_slice(&publish_time.to_le_bytes());
+    data.extend_from_slice(&[0; 32]);
+
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(
+        rent.minimum_balance(data.len()),
+        data.len(),
+        &PYTH_RECEIVER_ID,
+    );
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(feed, &account);
+}
+
+#[tokio::test]
+async fn oracle_escrow_takes_at_the_feed_price() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let taker = fx.taker.insecure_clone();
+    let feed = Pubkey::new_unique();
+    let make_oracle = |fx: &Fixture, max_staleness: i64| {
+        ix(
+            make_accounts(fx, &maker.pubkey(), 1),
+            instruction::MakeOracle {
+                seed: 1,
+                amount: AMOUNT,
+                price_feed: feed,
+                quote_exponent: -2,
+                max_staleness,
+            },
+        )
+    };
+
+    // 价格最多允许一小时前发布
+    let make = make_oracle(&fx, Escrow::MAX_ORACLE_STALENESS + 1);
+    assert_error(
+        send(&mut fx.ctx, &[make], &[&maker]).await,
+        EscrowError::InvalidOracleConfig,
+    );
+    let make = make_oracle(&fx, 60);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert!(state.is_oracle_priced());
+    assert_eq!(state.receive, 0);
+
+    let take_with = |fx: &Fixture, price_feed: Option<Pubkey>, max_receive: u64| {
+        ix(
+            accounts::Take {
+                price_feed,
+                ..take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &escrow)
+            },
+            take_args(max_receive),
+        )
+    };
+
+    // 必须传入 make_oracle 记录的 price feed
+    let now = fx
+        .ctx
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap()
+        .unix_timestamp;
+    set_price(&mut fx, &feed, 40, 0, now).await;
+    for price_feed in [None, Some(Pubkey::new_unique())] {
+        let take = take_with(&fx, price_feed, u64::MAX);
+        assert_error(
+            send(&mut fx.ctx, &[take], &[&taker]).await,
+            EscrowError::InvalidPriceFeed,
+        );
+    }
+
+    // 超过 max_staleness 的价格和超过 1% 的置信区间都不能成交
+    set_price(&mut fx, &feed, 40, 0, now - 61).await;
+    let take = take_with(&fx, Some(feed), u64::MAX);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::StalePrice,
+    );
+    set_price(&mut fx, &feed, 40, 1, now).await;
+    let take = take_with(&fx, Some(feed), u64::MAX);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::PriceConfidenceTooWide,
+    );
+
+    // 1 个 token A 值 0.40 个 token B, 1000 个 token A 需要 400 个 token B; max_receive 仍然限制滑点
+    set_price(&mut fx, &feed, 40, 0, now).await;
+    let take = take_with(&fx, Some(feed), 399);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::SlippageExceeded,
+    );
+    let take = take_with(&fx, Some(feed), 400);
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        400
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn price_band_rejects_fat_finger_prices() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let taker = fx.taker.insecure_clone();
+    let escrow = make(&mut fx, 1).await;
+    let feed = Pubkey::new_unique();
+
+    // 1000 个 token A 换 500 个 token B, 允许偏离预言机价格 5%
+    let set_band = ix(
+        set_price_band_accounts(&maker.pubkey(), &escrow),
+        instruction::SetPriceBand {
+            price_feed: feed,
+            quote_exponent: -2,
+            max_staleness: 60,
+            max_deviation_bps: 500,
+        },
+    );
+    send(&mut fx.ctx, &[set_band], &[&maker]).await.unwrap();
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert!(state.has_price_band() && !state.is_oracle_priced());
+
+    let take_with = |fx: &Fixture, price_feed: Option<Pubkey>| {
+        ix(
+            accounts::Take {
+                price_feed,
+                ..take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &escrow)
+            },
+            take_args(RECEIVE),
+        )
+    };
+    let now = fx
+        .ctx
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap()
+        .unix_timestamp;
+
+    // 不传 price feed, 传入其他地址上同样有效的价格, 或者记录的地址上不属于 Pyth receiver 的账户都会被拒绝
+    let other = Pubkey::new_unique();
+    set_price(&mut fx, &other, 50, 0, now).await;
+    set_price(&mut fx, &feed, 50, 0, now).await;
+    let mut forged = fx
+        .ctx
+        .banks_client
+        .get_account(feed)
+        .await
+        .unwrap()
+        .unwrap();
+    forged.owner = spl_token::ID;
+    fx.ctx.set_account(&feed, &AccountSharedData::from(forged));
+    for price_feed in [None, Some(other), Some(feed)] {
+        let take = take_with(&fx, price_feed);
+        assert_error(
+            send(&mut fx.ctx, &[take], &[&taker]).await,
+            EscrowError::InvalidPriceFeed,
+        );
+    }
+
+    // 预言机价格 0.60 时 500 个 token B 比公允价格 600 低了 16%, 超出区间
+    set_price(&mut fx, &feed, 60, 0, now).await;
+    let take = take_with(&fx, Some(feed));
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::PriceOutOfBand,
+    );
+    // 价格回到 0.52 时在区间内, 仍然按 receive 成交
+    set_price(&mut fx, &feed, 52, 0, now).await;
+    let take = take_with(&fx, Some(feed));
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        RECEIVE
+    );
+}
+
+// 铸造一个 decimals 为 0 的 NFT 给 owner
+async fn mint_nft(fx: &mut Fixture, owner: &Pubkey) -> Pubkey {
+    let mint = create_mint_with_decimals(&mut fx.ctx, 0).await;
+    fund_ata(&mut fx.ctx, &mint, owner, 1).await;
+    mint
+}
+
+#[tokio::test]
+async fn collection_escrow_takes_any_verified_nft() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let taker = fx.taker.insecure_clone();
+    let stranger = fx.stranger.insecure_clone();
+    let collection = mint_nft(&mut fx, &stranger.pubkey()).await;
+    let make_collection = |fx: &Fixture, mint_b: Pubkey| {
+        ix(
+            accounts::Make {
+                mint_b,
+                ..make_accounts(fx, &maker.pubkey(), 1)
+            },
+            instruction::MakeCollection {
+                seed: 1,
+                amount: AMOUNT,
+            },
+        )
+    };
+
+    // collection 必须是 NFT 的 mint
+    let make = make_collection(&fx, fx.mint_b);
+    assert_error(
+        send(&mut fx.ctx, &[make], &[&maker]).await,
+        EscrowError::NotAnNft,
+    );
+    let make = make_collection(&fx, collection);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.collection, collection);
+    assert!(state.is_collection_offer());
+
+    // 持有 collection NFT 本身的 stranger 也不能通过 take 成交
+    let take = ix(
+        accounts::Take {
+            mint_b: collection,
+            taker_ata_b: get_associated_token_address(&stranger.pubkey(), &collection),
+            maker_ata_b: Some(get_associated_token_address(&maker.pubkey(), &collection)),
+            ..take_accounts(&fx, &stranger.pubkey(), &maker.pubkey(), &escrow)
+        },
+        take_args(1),
+    );
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&stranger]).await,
+        EscrowError::CollectionOffer,
+    );
+
+    // 没有 Metadata, 未验证或者属于其他 collection 的 NFT 都被拒绝
+    let nft = mint_nft(&mut fx, &taker.pubkey()).await;
+    let take_collection = |fx: &Fixture, maker: &Pubkey, escrow: &Pubkey| {
+        ix(
+            take_collection_accounts(fx, &taker.pubkey(), maker, escrow, &nft),
+            instruction::TakeCollection {
+                expected_amount_a: AMOUNT,
+            },
+        )
+    };
+    let take = take_collection(&fx, &maker.pubkey(), &escrow);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::InvalidNftMetadata,
+    );
+    for collection in [
+        Some((false, collection)),
+        Some((true, Pubkey::new_unique())),
+        None,
+    ] {
+        set_metadata(&mut fx, &nft, 0, collection).await;
+        let take = take_collection(&fx, &maker.pubkey(), &escrow);
+        assert_error(
+            send(&mut fx.ctx, &[take], &[&taker]).await,
+            EscrowError::CollectionNotVerified,
+        );
+    }
+
+    // 普通托管不能通过 take_collection 成交, maker 的 token A 已经存入, 由 stranger 创建
+    set_metadata(&mut fx, &nft, 0, Some((true, collection))).await;
+    let make = ix(
+        make_accounts(&fx, &stranger.pubkey(), 2),
+        make_args(2, RECEIVE, AMOUNT),
+    );
+    send(&mut fx.ctx, &[make], &[&stranger]).await.unwrap();
+    let plain = pda::find_escrow_address(&stranger.pubkey(), 2).0;
+    let take = take_collection(&fx, &stranger.pubkey(), &plain);
+    assert_error(
+        send(&mut fx.ctx, &[take], &[&taker]).await,
+        EscrowError::NotCollectionOffer,
+    );
+
+    // 经过验证的 NFT 换取全部 token A, NFT 转给 maker
+    let take = take_collection(&fx, &maker.pubkey(), &escrow);
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(token_balance(&mut fx.ctx, &maker.pubkey(), &nft).await, 1);
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+
+    // 成交关闭的托管从 maker 的索引中移除
+    let registry = fx
+        .ctx
+        .banks_client
+        .get_account(pda::find_registry_address(&maker.pubkey()).0)
+        .await
+        .unwrap()
+        .unwrap();
+    let registry = MakerRegistry::try_deserialize(&mut registry.data.as_slice()).unwrap();
+    assert!(!registry.seeds.contains(&1));
+}
+
+#[tokio::test]
+async fn royalties_go_to_verified_creators_on_nft_takes() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let taker = fx.taker.insecure_clone();
+    fx.mint_a = mint_nft(&mut fx, &maker.pubkey()).await;
+    let make = ix(make_accounts(&fx, &maker.pubkey(), 1), make_args(1, 990, 1));
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let enforce = ix(
+        set_royalty_enforcement_accounts(&fx, &maker.pubkey(), &escrow),
+        instruction::SetRoyaltyEnforcement {
+            enforce_royalties: true,
+        },
+    );
+
+    // mint A 没有 Metadata 时不能开启
+    assert_error(
+        send(&mut fx.ctx, std::slice::from_ref(&enforce), &[&maker]).await,
+        EscrowError::InvalidNftMetadata,
+    );
+
+    // 两个 verified creator 按 60/40 分配, 未验证的 creator 不分配版税
+    let (first, second) = (Keypair::new().pubkey(), Keypair::new().pubkey());
+    let creators = [
+        (first, true, 60),
+        (second, true, 40),
+        (Pubkey::new_unique(), false, 0),
+    ];
+    let nft = fx.mint_a;
+    set_metadata_with_creators(&mut fx, &nft, 0, None, &creators).await;
+    send(&mut fx.ctx, &[enforce], &[&maker]).await.unwrap();
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert!(state.enforce_royalties);
+    let first_ata = fund_ata(&mut fx.ctx, &fx.mint_b, &first, 0).await;
+    let second_ata = fund_ata(&mut fx.ctx, &fx.mint_b, &second, 0).await;
+
+    // 不传 Metadata, 或者 creator 的 ATA 顺序错误时拒绝成交
+    let metadata = AccountMeta::new_readonly(metadata_address(&nft), false);
+    for royalty_accounts in [
+        vec![],
+        vec![
+            metadata.clone(),
+            AccountMeta::new(second_ata, false),
+            AccountMeta::new(first_ata, false),
+        ],
+    ] {
+        let mut take = ix(
+            take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+            take_args(990),
+        );
+        take.accounts.extend(royalty_accounts);
+        assert_error(
+            send(&mut fx.ctx, &[take], &[&taker]).await,
+            EscrowError::InvalidRoyaltyAccounts,
+        );
+    }
+
+    // 990 的 5% 是 49, 按 60/40 分别是 29.4 和 19.6, 余数 1 给第一个 creator
+    let mut take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(990),
+    );
+    take.accounts.extend([
+        metadata,
+        AccountMeta::new(first_ata, false),
+        AccountMeta::new(second_ata, false),
+    ]);
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+    assert_eq!(token_balance(&mut fx.ctx, &first, &fx.mint_b).await, 30);
+    assert_eq!(token_balance(&mut fx.ctx, &second, &fx.mint_b).await, 19);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        990 - 49
+    );
+    assert_eq!(token_balance(&mut fx.ctx, &taker.pubkey(), &nft).await, 1);
+}
+
+// 去掉和 seed 相关的字段之后的账户数据, 用来比较同样条款的两个托管
+fn escrow_terms(mut state: Escrow) -> Vec<u8> {
+    state.seed = 0;
+    state.bump = 0;
+    state.vault = Pubkey::default();
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    data
+}
+
+#[tokio::test]
+async fn v2_instructions_match_the_positional_ones() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let taker = fx.taker.insecure_clone();
+    let delegate = fx.stranger.pubkey();
+    let expiry = i64::MAX;
+    let make_v2 = |fx: &Fixture, reserved: [u8; 29]| {
+        ix(
+            make_v2_accounts(fx, &maker.pubkey(), 2),
+            instruction::MakeV2 {
+                args: MakeArgs {
+                    seed: 2,
+                    receive: RECEIVE,
+                    amount: AMOUNT / 2,
+                    expiry: Some(expiry),
+                    allowed_taker: Some(taker.pubkey()),
+                    refund_delegate: Some(delegate),
+                    reserved,
+                    ..Default::default()
+                },
+            },
+        )
+    };
+
+    // reserved 留给以后的参数, 不为 0 时拒绝
+    let mut reserved = [0; 29];
+    reserved[28] = 1;
+    let rejected = make_v2(&fx, reserved);
+    assert_error(
+        send(&mut fx.ctx, &[rejected], &[&maker]).await,
+        EscrowError::ReservedArgsNotZero,
+    );
+
+    // 同一个交易中用两个版本创建条款相同的托管, 除了 seed 之外的数据完全相同
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        instruction::Make {
+            expiry,
+            allowed_taker: taker.pubkey(),
+            refund_delegate: delegate,
+            ..make_args(1, RECEIVE, AMOUNT / 2)
+        },
+    );
+    let make_v2 = make_v2(&fx, [0; 29]);
+    send(&mut fx.ctx, &[make, make_v2], &[&maker])
+        .await
+        .unwrap();
+    let first = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let second = pda::find_escrow_address(&maker.pubkey(), 2).0;
+    let state = fetch_escrow(&mut fx.ctx, &first).await.unwrap();
+    let state_v2 = fetch_escrow(&mut fx.ctx, &second).await.unwrap();
+    assert_eq!(state_v2.seed, 2);
+    assert_eq!(state_v2.refund_delegate, delegate);
+    assert_eq!(escrow_terms(state), escrow_terms(state_v2));
+
+    // take_v2 的 max_receive 和 expected_amount_a 和 take 的检查相同
+    let take_v2 = |fx: &Fixture, max_receive: u64| {
+        ix(
+            take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &second),
+            instruction::TakeV2 {
+                args: TakeArgs {
+                    max_receive: Some(max_receive),
+                    expected_amount_a: Some(AMOUNT / 2),
+                    ..Default::default()
+                },
+            },
+        )
+    };
+    let rejected = take_v2(&fx, RECEIVE - 1);
+    assert_error(
+        send(&mut fx.ctx, &[rejected], &[&taker]).await,
+        EscrowError::SlippageExceeded,
+    );
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &first),
+        instruction::Take {
+            expected_amount_a: AMOUNT / 2,
+            ..take_args(RECEIVE)
+        },
+    );
+    let take_v2 = take_v2(&fx, RECEIVE);
+    send(&mut fx.ctx, &[take, take_v2], &[&taker])
+        .await
+        .unwrap();
+
+    for escrow in [first, second] {
+        assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    }
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        2 * RECEIVE
+    );
+}
+
+#[tokio::test]
+async fn full_balance_make_deposits_the_live_balance() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let maker_ata_a = get_associated_token_address(&maker.pubkey(), &fx.mint_a);
+
+    // 客户端按 AMOUNT 的余额构造交易之后 maker 又收到 500 个 token A, 在同一个交易中先于 make 执行
+    let incoming = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        &fx.mint_a,
+        &maker_ata_a,
+        &fx.ctx.payer.pubkey(),
+        &[],
+        500,
+    )
+    .unwrap();
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, RECEIVE, Escrow::FULL_BALANCE),
+    );
+    send(&mut fx.ctx, &[incoming, make], &[&maker])
+        .await
+        .unwrap();
+
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.amount, AMOUNT + 500);
+    assert_eq!(state.deposited, AMOUNT + 500);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+        AMOUNT + 500
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        0
+    );
+
+    // ATA 已经没有余额时拒绝 sentinel
+    let make = ix(
+        make_accounts(&fx, &maker.pubkey(), 2),
+        make_args(2, RECEIVE, Escrow::FULL_BALANCE),
+    );
+    assert_error(
+        send(&mut fx.ctx, &[make], &[&maker]).await,
+        EscrowError::EmptyMakerBalance,
+    );
+}
+
+#[tokio::test]
+async fn take_v2_tips_the_maker_on_top_of_receive() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    const TIP_RECEIVE: u64 = 200;
+    let fee_authority = pda::find_fee_authority_address().0;
+    let fee_vault_b = fund_ata(&mut fx.ctx, &fx.mint_b, &fee_authority, 0).await;
+
+    // 手续费 10%
+    let (admin, config) = (fx.ctx.payer.pubkey(), pda::find_config_address().0);
+    let set_fee = ix(
+        accounts::SetFee { admin, config },
+        instruction::SetFee { fee_bps: 1_000 },
+    );
+    send(&mut fx.ctx, &[set_fee], &[]).await.unwrap();
+    for seed in 1..=3 {
+        let make = ix(
+            make_accounts(&fx, &maker.pubkey(), seed),
+            make_args(seed, TIP_RECEIVE, AMOUNT / 4),
+        );
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    }
+
+    let take_v2 = |fx: &Fixture, seed: u64, tip: u64| {
+        let escrow = pda::find_escrow_address(&maker.pubkey(), seed).0;
+        ix(
+            accounts::Take {
+                fee_vault_b: Some(fee_vault_b),
+                ..take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &escrow)
+            },
+            instruction::TakeV2 {
+                args: TakeArgs {
+                    max_receive: Some(TIP_RECEIVE),
+                    tip,
+                    ..Default::default()
+                },
+            },
+        )
+    };
+
+    // receive 加上 tip 溢出时拒绝成交
+    let overflow = take_v2(&fx, 1, u64::MAX);
+    assert_error(
+        send(&mut fx.ctx, &[overflow], &[&taker]).await,
+        EscrowError::MathOverflow,
+    );
+
+    // 没有小费时和 take 相同; 小费不受 max_receive 限制, 默认不收取手续费, 全部转给 maker
+    // 开启 fee_on_tips 之后小费和成交价格一起按 10% 收取手续费
+    let (mut maker_b, mut fees) = (0, 0);
+    for (seed, tip, fee) in [(1, 0, 20), (2, 50, 20), (3, 50, 25)] {
+        if seed == 3 {
+            let set_fee_on_tips = ix(
+                accounts::SetFeeOnTips { admin, config },
+                instruction::SetFeeOnTips { fee_on_tips: true },
+            );
+            send(&mut fx.ctx, &[set_fee_on_tips], &[]).await.unwrap();
+        }
+        let take = take_v2(&fx, seed, tip);
+        send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+
+        maker_b += TIP_RECEIVE + tip - fee;
+        fees += fee;
+        assert_eq!(
+            token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+            maker_b
+        );
+        assert_eq!(
+            token_balance(&mut fx.ctx, &fee_authority, &fx.mint_b).await,
+            fees
+        );
+    }
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_b).await,
+        AMOUNT - 3 * TIP_RECEIVE - 100
+    );
+}
+
+// 创建设置了 freeze authority 的 mint, reject_freezable 为 true 时不能用于托管
+async fn create_freezable_mint(ctx: &mut ProgramTestContext) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &ctx.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &ctx.payer.pubkey(),
+            Some(&ctx.payer.pubkey()),
+            6,
+        )
+        .unwrap(),
+    ];
+    send(ctx, &ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+#[tokio::test]
+async fn make_v2_fills_unspecified_args_from_prefs() {
+    let mut fx = setup().await;
+    let maker = fx.maker.insecure_clone();
+    let stranger = fx.stranger.insecure_clone();
+    let payout = stranger.pubkey();
+    let freezable = create_freezable_mint(&mut fx.ctx).await;
+    let (prefs, _) = pda::find_prefs_address(&maker.pubkey());
+    let set_prefs = |owner: &Pubkey, expiry_duration: i64| {
+        ix(
+            accounts::SetPrefs {
+                maker: *owner,
+                prefs: pda::find_prefs_address(owner).0,
+                system_program: system_program::ID,
+            },
+            instruction::SetPrefs {
+                expiry_duration,
+                receive_to: payout,
+                reject_freezable: true,
+            },
+        )
+    };
+    let make_v2 = |fx: &Fixture, args: MakeArgs, prefs: Option<Pubkey>| {
+        ix(
+            accounts::MakeV2 {
+                make: accounts::Make {
+                    mint_b: freezable,
+                    ..make_accounts(fx, &maker.pubkey(), args.seed)
+                },
+                prefs,
+                ..make_v2_accounts(fx, &maker.pubkey(), args.seed)
+            },
+            instruction::MakeV2 { args },
+        )
+    };
+    let args = |seed: u64| MakeArgs {
+        seed,
+        receive: RECEIVE,
+        amount: AMOUNT / 4,
+        ..Default::default()
+    };
+
+    // 有效期不能是负数
+    let negative = set_prefs(&maker.pubkey(), -1);
+    assert_error(
+        send(&mut fx.ctx, &[negative], &[&maker]).await,
+        EscrowError::InvalidExpiry,
+    );
+    let set = set_prefs(&maker.pubkey(), 3_600);
+    let set_stranger = set_prefs(&stranger.pubkey(), 0);
+    send(&mut fx.ctx, &[set, set_stranger], &[&maker, &stranger])
+        .await
+        .unwrap();
+
+    // 只能使用 maker 自己的 prefs
+    let stranger_prefs = pda::find_prefs_address(&stranger.pubkey()).0;
+    let other = make_v2(&fx, args(1), Some(stranger_prefs));
+    assert_error(
+        send(&mut fx.ctx, &[other], &[&maker]).await,
+        ErrorCode::ConstraintSeeds,
+    );
+
+    // 没有指定的 reject_freezable 来自 prefs, mint_b 设置了 freeze authority
+    let rejected = make_v2(&fx, args(1), Some(prefs));
+    assert_error(
+        send(&mut fx.ctx, &[rejected], &[&maker]).await,
+        EscrowError::FreezableMintRejected,
+    );
+
+    // 其他没有指定的参数同样来自 prefs, 过期时间从创建时开始计算
+    let filled = make_v2(
+        &fx,
+        MakeArgs {
+            reject_freezable: Some(false),
+            ..args(1)
+        },
+        Some(prefs),
+    );
+    send(&mut fx.ctx, &[filled], &[&maker]).await.unwrap();
+    let now = fx
+        .ctx
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap()
+        .unix_timestamp;
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.expiry, now + 3_600);
+    assert_eq!(state.receive_to, payout);
+
+    // 指定的值始终优先, 包括表示默认值的 Some(0) 和 Some(Pubkey::default())
+    let explicit = make_v2(
+        &fx,
+        MakeArgs {
+            expiry: Some(0),
+            receive_to: Some(Pubkey::default()),
+            reject_freezable: Some(false),
+            ..args(2)
+        },
+        Some(prefs),
+    );
+    // 不传 prefs 时和 make 相同, 不读取 maker 的默认参数
+    let without = make_v2(&fx, args(3), None);
+    send(&mut fx.ctx, &[explicit, without], &[&maker])
+        .await
+        .unwrap();
+    for seed in [2, 3] {
+        let escrow = pda::find_escrow_address(&maker.pubkey(), seed).0;
+        let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+        assert_eq!(state.expiry, 0);
+        assert_eq!(state.receive_to, maker.pubkey());
+    }
+
+    // 关闭后租金还给 maker, 已经创建的托管不受影响
+    let balance = fx
+        .ctx
+        .banks_client
+        .get_balance(maker.pubkey())
+        .await
+        .unwrap();
+    let rent = fx.ctx.banks_client.get_balance(prefs).await.unwrap();
+    let close = ix(
+        accounts::ClosePrefs {
+            maker: maker.pubkey(),
+            prefs,
+        },
+        instruction::ClosePrefs {},
+    );
+    send(&mut fx.ctx, &[close], &[&maker]).await.unwrap();
+    let account = fx.ctx.banks_client.get_account(prefs).await.unwrap();
+    assert!(account.is_none());
+    let refunded = fx
+        .ctx
+        .banks_client
+        .get_balance(maker.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(refunded, balance + rent);
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_some());
+}
+
+async fn fetch_pair_index(fx: &mut Fixture, address: Pubkey) -> PairIndex {
+    let account = fx
+        .ctx
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    PairIndex::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn pair_index_follows_make_take_and_refund() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let pair_index = pda::find_pair_index_address(&fx.mint_a, &fx.mint_b).0;
+    let escrow = |seed: u64| pda::find_escrow_address(&maker.pubkey(), seed).0;
+    let make_v2 = |fx: &Fixture, seed: u64, receive: u64, index: Option<Pubkey>| {
+        ix(
+            accounts::MakeV2 {
+                pair_index: index,
+                ..make_v2_accounts(fx, &maker.pubkey(), seed)
+            },
+            instruction::MakeV2 {
+                args: MakeArgs {
+                    seed,
+                    receive,
+                    amount: AMOUNT / 4,
+                    pair_index: true,
+                    tag: seed as u8,
+                    ..Default::default()
+                },
+            },
+        )
+    };
+
+    // pair_index 模式必须传入索引账户
+    let missing = make_v2(&fx, 1, 500, None);
+    assert_error(
+        send(&mut fx.ctx, &[missing], &[&maker]).await,
+        EscrowError::MissingPairIndex,
+    );
+
+    // 第一次 make 创建索引, 之后的 make 更新计数; seed 2 的单价 300 / 250 最低
+    for (seed, receive) in [(1, 500), (2, 300), (3, 900)] {
+        let make = make_v2(&fx, seed, receive, Some(pair_index));
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    }
+    // 不以 pair_index 模式创建的托管不计入
+    let unindexed = ix(
+        make_accounts(&fx, &maker.pubkey(), 4),
+        make_args(4, RECEIVE, AMOUNT / 4),
+    );
+    send(&mut fx.ctx, &[unindexed], &[&maker]).await.unwrap();
+
+    let index = fetch_pair_index(&mut fx, pair_index).await;
+    assert_eq!((index.mint_a, index.mint_b), (fx.mint_a, fx.mint_b));
+    assert_eq!(index.open_escrows, 3);
+    assert_eq!(
+        (index.best_escrow, index.best_receive, index.best_deposited),
+        (escrow(2), 300, AMOUNT / 4)
+    );
+
+    // tag 在账户数据中的偏移量固定, 索引服务可以直接 memcmp
+    for seed in [3, 4] {
+        let account = fx
+            .ctx
+            .banks_client
+            .get_account(escrow(seed))
+            .await
+            .unwrap()
+            .unwrap();
+        let tag = if seed == 4 { 0 } else { seed as u8 };
+        assert_eq!(account.data.len(), Escrow::SPACE);
+        assert_eq!(account.data[Escrow::TAG_OFFSET], tag);
+        let state = Escrow::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert_eq!(state.tag, tag);
+        assert_eq!(state.pair_indexed, seed != 4);
+    }
+
+    // 最低价格的托管成交后清空 best_escrow
+    let take = |fx: &Fixture, seed: u64| {
+        ix(
+            accounts::Take {
+                pair_index: Some(pair_index),
+                ..take_accounts(fx, &taker.pubkey(), &maker.pubkey(), &escrow(seed))
+            },
+            take_args(u64::MAX),
+        )
+    };
+    let take_cheapest = take(&fx, 2);
+    send(&mut fx.ctx, &[take_cheapest], &[&taker])
+        .await
+        .unwrap();
+    let index = fetch_pair_index(&mut fx, pair_index).await;
+    assert_eq!(index.open_escrows, 2);
+    assert_eq!(index.best_escrow, Pubkey::default());
+
+    // 计入了索引的托管关闭时必须传入索引, 否则 open_escrows 会一直计入已经关闭的托管
+    let refund = |fx: &Fixture, seed: u64, index: Option<Pubkey>| {
+        ix(
+            accounts::Refund {
+                pair_index: index,
+                ..refund_accounts(fx, &maker.pubkey(), &maker.pubkey(), &escrow(seed))
+            },
+            instruction::Refund { force: false },
+        )
+    };
+    let missing = refund(&fx, 1, None);
+    assert_error(
+        send(&mut fx.ctx, &[missing], &[&maker]).await,
+        EscrowError::MissingPairIndex,
+    );
+    assert_eq!(fetch_pair_index(&mut fx, pair_index).await.open_escrows, 2);
+
+    let refunded = refund(&fx, 3, Some(pair_index));
+    send(&mut fx.ctx, &[refunded], &[&maker]).await.unwrap();
+    assert_eq!(fetch_pair_index(&mut fx, pair_index).await.open_escrows, 1);
+
+    // 没有计入索引的托管成交时即使传入索引也不更新
+    let take_unindexed = take(&fx, 4);
+    send(&mut fx.ctx, &[take_unindexed], &[&taker])
+        .await
+        .unwrap();
+    assert_eq!(fetch_pair_index(&mut fx, pair_index).await.open_escrows, 1);
+}
+
+#[tokio::test]
+async fn pair_index_follows_relist_withdraw_and_streaming() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let pair_index = pda::find_pair_index_address(&fx.mint_a, &fx.mint_b).0;
+    let escrow = |seed: u64| pda::find_escrow_address(&maker.pubkey(), seed).0;
+    for (seed, receive) in [(1, 500), (2, 300), (3, STREAM_RECEIVE)] {
+        let make = ix(
+            accounts::MakeV2 {
+                pair_index: Some(pair_index),
+                ..make_v2_accounts(&fx, &maker.pubkey(), seed)
+            },
+            instruction::MakeV2 {
+                args: MakeArgs {
+                    seed,
+                    receive,
+                    amount: AMOUNT / 4,
+                    pair_index: true,
+                    ..Default::default()
+                },
+            },
+        );
+        send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    }
+    assert_eq!(
+        fetch_pair_index(&mut fx, pair_index).await.best_escrow,
+        escrow(2)
+    );
+
+    // relist 用新托管替换旧托管, 计数不变, 新价格仍然最低时成为 best_escrow
+    let relist = ix(
+        accounts::Relist {
+            pair_index: Some(pair_index),
+            ..relist_accounts(&fx, &maker.pubkey(), &escrow(2), 5)
+        },
+        instruction::Relist {
+            new_seed: 5,
+            new_receive: 200,
+        },
+    );
+    send(&mut fx.ctx, &[relist], &[&maker]).await.unwrap();
+    let index = fetch_pair_index(&mut fx, pair_index).await;
+    assert_eq!(index.open_escrows, 3);
+    assert_eq!((index.best_escrow, index.best_receive), (escrow(5), 200));
+
+    // 全部取回关闭托管
+    let withdraw = ix(
+        accounts::WithdrawPartial {
+            pair_index: Some(pair_index),
+            ..withdraw_partial_accounts(&fx, &maker.pubkey(), &escrow(1))
+        },
+        instruction::WithdrawPartial {
+            amount_a: AMOUNT / 4,
+        },
+    );
+    send(&mut fx.ctx, &[withdraw], &[&maker]).await.unwrap();
+    assert_eq!(fetch_pair_index(&mut fx, pair_index).await.open_escrows, 2);
+
+    // 第一期就付清的分期成交关闭托管
+    let set_window = ix(
+        accounts::SetStreamWindow {
+            maker: maker.pubkey(),
+            escrow: escrow(3),
+        },
+        instruction::SetStreamWindow {
+            stream_window: 3_600,
+        },
+    );
+    send(&mut fx.ctx, &[set_window], &[&maker]).await.unwrap();
+    let stream = ix(
+        accounts::TakeStreaming {
+            pair_index: Some(pair_index),
+            ..take_streaming_accounts(&fx, &taker.pubkey(), &escrow(3))
+        },
+        instruction::TakeStreaming {
+            initial_b: STREAM_RECEIVE,
+            max_receive: STREAM_RECEIVE,
+            proof: vec![],
+        },
+    );
+    send(&mut fx.ctx, &[stream], &[&taker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow(3)).await.is_none());
+    let index = fetch_pair_index(&mut fx, pair_index).await;
+    assert_eq!(index.open_escrows, 1);
+    assert_eq!(index.best_escrow, escrow(5));
+}
+
+#[tokio::test]
+async fn cancel_records_reason_and_retains_escrow() {
+    let mut fx = setup().await;
+    let (maker, taker, stranger) = (
+        fx.maker.insecure_clone(),
+        fx.taker.insecure_clone(),
+        fx.stranger.insecure_clone(),
+    );
+    let cancel = |fx: &Fixture, escrow: &Pubkey, reason: u8, retain: bool| {
+        ix(
+            refund_accounts(fx, &maker.pubkey(), &maker.pubkey(), escrow),
+            instruction::Cancel { reason, retain },
+        )
+    };
+
+    // 不认识的原因代码被拒绝
+    let escrow = make(&mut fx, 1).await;
+    let unknown = cancel(&fx, &escrow, 4, false);
+    let result = send(&mut fx.ctx, &[unknown], &[&maker]).await;
+    assert_error(result, EscrowError::InvalidCancelReason);
+
+    // 不保留时和 refund 一样关闭托管
+    let pulled = cancel(&fx, &escrow, CancelReason::Pulled as u8, false);
+    send(&mut fx.ctx, &[pulled], &[&maker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+
+    // 保留时 token A 同样退还, 托管记录原因和 slot
+    let escrow = make(&mut fx, 2).await;
+    let repriced = cancel(&fx, &escrow, CancelReason::Repriced as u8, true);
+    send(&mut fx.ctx, &[repriced], &[&maker]).await.unwrap();
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.status, EscrowStatus::Cancelled);
+    assert_eq!(state.cancel_reason, CancelReason::Repriced as u8);
+    assert!(state.cancelled_slot > 0);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+
+    // 即使有人重新创建并充值 vault, 取消的托管也不能成交或再次退还
+    fund_ata(&mut fx.ctx, &fx.mint_a, &escrow, AMOUNT).await;
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    let result = send(&mut fx.ctx, &[take], &[&taker]).await;
+    assert_error(result, EscrowError::EscrowFrozen);
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    let result = send(&mut fx.ctx, &[refund], &[&maker]).await;
+    assert_error(result, EscrowError::InvalidEscrowStatus);
+
+    // 保留期结束之前不能关闭, 之后任何人都可以关闭, 租金还给 rent_payer
+    let close = ix(
+        accounts::CloseCancelled {
+            closer: stranger.pubkey(),
+            rent_payer: maker.pubkey(),
+            escrow,
+        },
+        instruction::CloseCancelled {},
+    );
+    let result = send(&mut fx.ctx, std::slice::from_ref(&close), &[&stranger]).await;
+    assert_error(result, EscrowError::CancelRetentionActive);
+
+    fx.ctx
+        .warp_to_slot(state.cancelled_slot + Escrow::CANCEL_RETENTION_SLOTS)
+        .unwrap();
+    let lamports = |account: Option<solana_sdk::account::Account>| account.unwrap().lamports;
+    let before = lamports(
+        fx.ctx
+            .banks_client
+            .get_account(maker.pubkey())
+            .await
+            .unwrap(),
+    );
+    let rent = lamports(fx.ctx.banks_client.get_account(escrow).await.unwrap());
+    send(&mut fx.ctx, &[close], &[&stranger]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    let after = lamports(
+        fx.ctx
+            .banks_client
+            .get_account(maker.pubkey())
+            .await
+            .unwrap(),
+    );
+    assert_eq!(after, before + rent);
+}
+
+#[tokio::test]
+async fn maker_pause_blocks_takes_but_not_refund() {
+    let mut fx = setup().await;
+    let (maker, taker, stranger) = (
+        fx.maker.insecure_clone(),
+        fx.taker.insecure_clone(),
+        fx.stranger.insecure_clone(),
+    );
+    let toggle = |signer: &Pubkey, escrow: &Pubkey, paused: bool| {
+        let accounts = accounts::SetMakerPaused {
+            maker: *signer,
+            escrow: *escrow,
+        };
+        if paused {
+            ix(accounts, instruction::PauseOffer {})
+        } else {
+            ix(accounts, instruction::ResumeOffer {})
+        }
+    };
+    let escrow = make(&mut fx, 1).await;
+
+    // 只有 maker 可以暂停, 其他账户推导出的托管地址不匹配
+    let result = send(
+        &mut fx.ctx,
+        &[toggle(&stranger.pubkey(), &escrow, true)],
+        &[&stranger],
+    )
+    .await;
+    assert_error(result, ErrorCode::ConstraintSeeds);
+
+    // 暂停期间 take 失败, 托管和 vault 保持不变
+    let pause = toggle(&maker.pubkey(), &escrow, true);
+    send(&mut fx.ctx, &[pause], &[&maker]).await.unwrap();
+    assert!(
+        fetch_escrow(&mut fx.ctx, &escrow)
+            .await
+            .unwrap()
+            .maker_paused
+    );
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    let result = send(&mut fx.ctx, &[take], &[&taker]).await;
+    assert_error(result, EscrowError::OfferPausedByMaker);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await,
+        AMOUNT
+    );
+
+    // 其他账户同样不能恢复
+    let result = send(
+        &mut fx.ctx,
+        &[toggle(&stranger.pubkey(), &escrow, false)],
+        &[&stranger],
+    )
+    .await;
+    assert_error(result, ErrorCode::ConstraintSeeds);
+
+    // 恢复之后照常成交; 换一个 max_receive, 避免和失败的交易完全相同
+    let resume = toggle(&maker.pubkey(), &escrow, false);
+    send(&mut fx.ctx, &[resume], &[&maker]).await.unwrap();
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(u64::MAX),
+    );
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+
+    // 暂停中的托管仍然可以退还
+    let escrow = make(&mut fx, 2).await;
+    let pause = toggle(&maker.pubkey(), &escrow, true);
+    send(&mut fx.ctx, &[pause], &[&maker]).await.unwrap();
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+}
+
+// 托管出售 AMOUNT 个 token A, 价格 300 个 token B, 单价不是整数, 每一期释放的 token A 都需要取整
+const STREAM_RECEIVE: u64 = 300;
+
+async fn make_streaming(fx: &mut Fixture, seed: u64, stream_window: i64) -> Pubkey {
+    let maker = fx.maker.insecure_clone();
+    let make = ix(
+        make_accounts(fx, &maker.pubkey(), seed),
+        make_args(seed, STREAM_RECEIVE, AMOUNT),
+    );
+    let escrow = pda::find_escrow_address(&maker.pubkey(), seed).0;
+    let set_window = ix(
+        accounts::SetStreamWindow {
+            maker: maker.pubkey(),
+            escrow,
+        },
+        instruction::SetStreamWindow { stream_window },
+    );
+    send(&mut fx.ctx, &[make, set_window], &[&maker])
+        .await
+        .unwrap();
+    escrow
+}
+
+#[tokio::test]
+async fn streaming_take_releases_token_a_per_installment() {
+    let mut fx = setup().await;
+    let (maker, taker, stranger) = (
+        fx.maker.insecure_clone(),
+        fx.taker.insecure_clone(),
+        fx.stranger.insecure_clone(),
+    );
+    let start = |fx: &Fixture, escrow: &Pubkey, initial_b: u64, max_receive: u64| {
+        ix(
+            take_streaming_accounts(fx, &taker.pubkey(), escrow),
+            instruction::TakeStreaming {
+                initial_b,
+                max_receive,
+                proof: vec![],
+            },
+        )
+    };
+    let pay = |fx: &Fixture, payer: &Pubkey, escrow: &Pubkey, amount_b: u64| {
+        ix(
+            take_streaming_accounts(fx, payer, escrow),
+            instruction::StreamPayment { amount_b },
+        )
+    };
+
+    // maker 没有设置 stream_window 时不能分期成交
+    let disabled = make_streaming(&mut fx, 1, 0).await;
+    let disabled = start(&fx, &disabled, 300, 300);
+    let result = send(&mut fx.ctx, &[disabled], &[&taker]).await;
+    assert_error(result, EscrowError::StreamingDisabled);
+
+    // 锁定的总额超过 max_receive, 或者第一期不到总额的 25% 都会失败
+    let escrow = make_streaming(&mut fx, 2, 3_600).await;
+    let expensive = start(&fx, &escrow, 75, 299);
+    let result = send(&mut fx.ctx, &[expensive], &[&taker]).await;
+    assert_error(result, EscrowError::SlippageExceeded);
+    let small = start(&fx, &escrow, 74, 300);
+    let result = send(&mut fx.ctx, &[small], &[&taker]).await;
+    assert_error(result, EscrowError::InitialPaymentTooSmall);
+
+    // 第一期 75 个 token B 释放 1000 * 75 / 300 = 250 个 token A
+    let first = start(&fx, &escrow, 75, 300);
+    send(&mut fx.ctx, &[first], &[&taker]).await.unwrap();
+    let state = fetch_escrow(&mut fx.ctx, &escrow).await.unwrap();
+    assert_eq!(state.status, EscrowStatus::Streaming);
+    assert_eq!(state.locked_taker, taker.pubkey());
+    assert_eq!((state.stream_receive, state.paid_b), (STREAM_RECEIVE, 75));
+    assert_eq!(state.amount, 750);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        250
+    );
+
+    // 只有 locked_taker 可以继续支付, 截止之前 maker 不能退还, 也不能修改 stream_window
+    let stranger_pays_100 = pay(&fx, &stranger.pubkey(), &escrow, 100);
+    let result = send(&mut fx.ctx, &[stranger_pays_100], &[&stranger]).await;
+    assert_error(result, EscrowError::UnauthorizedTaker);
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    let result = send(&mut fx.ctx, &[refund], &[&maker]).await;
+    assert_error(result, EscrowError::StreamActive);
+    let set_window = ix(
+        accounts::SetStreamWindow {
+            maker: maker.pubkey(),
+            escrow,
+        },
+        instruction::SetStreamWindow { stream_window: 60 },
+    );
+    let result = send(&mut fx.ctx, &[set_window], &[&maker]).await;
+    assert_error(result, EscrowError::InvalidEscrowStatus);
+
+    // 第二期 100 个释放 750 * 100 / 225 = 333.3, 向下取整为 333
+    // 协议暂停时仍然可以继续支付, 不会因此错过 stream_deadline
+    let admin = fx.ctx.payer.pubkey();
+    let set_paused = move |paused: bool| {
+        let accounts = accounts::SetPaused {
+            admin,
+            config: pda::find_config_address().0,
+        };
+        if paused {
+            ix(accounts, instruction::Pause {})
+        } else {
+            ix(accounts, instruction::Unpause {})
+        }
+    };
+    let pause = set_paused(true);
+    send(&mut fx.ctx, &[pause], &[]).await.unwrap();
+    let taker_pays_100 = pay(&fx, &taker.pubkey(), &escrow, 100);
+    send(&mut fx.ctx, &[taker_pays_100], &[&taker])
+        .await
+        .unwrap();
+    let unpause = set_paused(false);
+    send(&mut fx.ctx, &[unpause], &[]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        583
+    );
+
+    // 超过未付部分的支付被拒绝, 付清剩余的 125 个释放剩余的全部 417 个并关闭托管
+    let taker_pays_126 = pay(&fx, &taker.pubkey(), &escrow, 126);
+    let result = send(&mut fx.ctx, &[taker_pays_126], &[&taker]).await;
+    assert_error(result, EscrowError::InvalidAmount);
+    let taker_pays_125 = pay(&fx, &taker.pubkey(), &escrow, 125);
+    send(&mut fx.ctx, &[taker_pays_125], &[&taker])
+        .await
+        .unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        STREAM_RECEIVE
+    );
+}
+
+#[tokio::test]
+async fn streaming_installments_use_the_taker_fee_tier() {
+    let mut fx = setup().await;
+    let taker = fx.taker.insecure_clone();
+    let fee_authority = pda::find_fee_authority_address().0;
+    let fee_vault_b = fund_ata(&mut fx.ctx, &fx.mint_b, &fee_authority, 0).await;
+
+    // 基础 10%, 成交过 1 次之后免手续费
+    let (admin, config) = (fx.ctx.payer.pubkey(), pda::find_config_address().0);
+    let set_fee = ix(
+        accounts::SetFee { admin, config },
+        instruction::SetFee { fee_bps: 1_000 },
+    );
+    let set_tiers = ix(
+        accounts::SetFeeTiers { admin, config },
+        instruction::SetFeeTiers {
+            tiers: vec![FeeTier {
+                min_trades: 1,
+                fee_bps: 0,
+            }],
+        },
+    );
+    send(&mut fx.ctx, &[set_fee, set_tiers], &[]).await.unwrap();
+
+    let escrow = make_streaming(&mut fx, 1, 3_600).await;
+    let taker_stats = pda::find_user_stats_address(&taker.pubkey()).0;
+    let with_stats = |fx: &Fixture, taker_stats: Option<Pubkey>| accounts::TakeStreaming {
+        fee_vault_b: Some(fee_vault_b),
+        taker_stats,
+        ..take_streaming_accounts(fx, &taker.pubkey(), &escrow)
+    };
+    let start = |accounts: accounts::TakeStreaming| {
+        ix(
+            accounts,
+            instruction::TakeStreaming {
+                initial_b: STREAM_RECEIVE / 2,
+                max_receive: STREAM_RECEIVE,
+                proof: vec![],
+            },
+        )
+    };
+
+    // 启用档位后每一期都必须传入 taker 的统计账户
+    let missing = start(with_stats(&fx, None));
+    let result = send(&mut fx.ctx, &[missing], &[&taker]).await;
+    assert_error(result, EscrowError::MissingUserStats);
+    let first = start(with_stats(&fx, Some(taker_stats)));
+    send(&mut fx.ctx, &[first], &[&taker]).await.unwrap();
+    let stats = fetch_user_stats(&mut fx, &taker.pubkey()).await;
+    assert_eq!((stats.wallet, stats.trades), (taker.pubkey(), 0));
+
+    // 付清之前不计入成交, 两期都按 0 次成交收取 10%
+    let rest = ix(
+        with_stats(&fx, Some(taker_stats)),
+        instruction::StreamPayment {
+            amount_b: STREAM_RECEIVE / 2,
+        },
+    );
+    send(&mut fx.ctx, &[rest], &[&taker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &fee_authority, &fx.mint_b).await,
+        STREAM_RECEIVE / 10
+    );
+    let stats = fetch_user_stats(&mut fx, &taker.pubkey()).await;
+    assert_eq!(stats.trades, 1);
+    assert_eq!(stats.volume_b, STREAM_RECEIVE);
+}
+
+#[tokio::test]
+async fn stalled_stream_is_reclaimed_by_the_maker_after_the_deadline() {
+    let mut fx = setup().await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let escrow = make_streaming(&mut fx, 1, 60).await;
+    let start = ix(
+        take_streaming_accounts(&fx, &taker.pubkey(), &escrow),
+        instruction::TakeStreaming {
+            initial_b: 75,
+            max_receive: STREAM_RECEIVE,
+            proof: vec![],
+        },
+    );
+    send(&mut fx.ctx, &[start], &[&taker]).await.unwrap();
+    let deadline = fetch_escrow(&mut fx.ctx, &escrow)
+        .await
+        .unwrap()
+        .stream_deadline;
+
+    // 截止时间当时仍然可以支付
+    let mut clock: Clock = fx.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = deadline;
+    fx.ctx.set_sysvar(&clock);
+    let pay = |fx: &Fixture, amount_b: u64| {
+        ix(
+            take_streaming_accounts(fx, &taker.pubkey(), &escrow),
+            instruction::StreamPayment { amount_b },
+        )
+    };
+    let on_time = pay(&fx, 1);
+    send(&mut fx.ctx, &[on_time], &[&taker]).await.unwrap();
+
+    // 截止之后 taker 不能继续支付, maker 取回还没有释放的 token A, 已经收到的 token B 不退还
+    clock.unix_timestamp = deadline + 1;
+    fx.ctx.set_sysvar(&clock);
+    let late = pay(&fx, 2);
+    let result = send(&mut fx.ctx, &[late], &[&taker]).await;
+    assert_error(result, EscrowError::StreamDeadlinePassed);
+
+    let released = token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await;
+    // 第二期 1 个释放 750 * 1 / 225 = 3.3, 向下取整为 3
+    assert_eq!(released, 253);
+    let refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        AMOUNT - released
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        76
+    );
+}
+
+fn update_blocklist(fx: &Fixture, mint: Pubkey, blocked: bool) -> Instruction {
+    let accounts = accounts::UpdateBlocklist {
+        admin: fx.ctx.payer.pubkey(),
+        config: pda::find_config_address().0,
+        mint_blocklist: pda::find_mint_blocklist_address().0,
+        system_program: system_program::ID,
+    };
+    if blocked {
+        ix(accounts, instruction::AddBlockedMint { mint })
+    } else {
+        ix(accounts, instruction::RemoveBlockedMint { mint })
+    }
+}
+
+#[tokio::test]
+async fn blocked_mint_rejects_every_fill_path() {
+    let mut fx = setup().await;
+    let taker = fx.taker.insecure_clone();
+    let escrow = make_streaming(&mut fx, 1, 3_600).await;
+
+    // 黑名单是必须传入的账户, 从未添加过时是空的系统账户, 不影响成交
+    let block = update_blocklist(&fx, fx.mint_b, true);
+    send(&mut fx.ctx, &[block], &[]).await.unwrap();
+
+    let take = ix(
+        take_accounts(&fx, &taker.pubkey(), &fx.maker.pubkey(), &escrow),
+        take_args(u64::MAX),
+    );
+    let result = send(&mut fx.ctx, std::slice::from_ref(&take), &[&taker]).await;
+    assert_error(result, EscrowError::MintBlocked);
+    let streaming = ix(
+        take_streaming_accounts(&fx, &taker.pubkey(), &escrow),
+        instruction::TakeStreaming {
+            initial_b: STREAM_RECEIVE,
+            max_receive: STREAM_RECEIVE,
+            proof: vec![],
+        },
+    );
+    let result = send(&mut fx.ctx, &[streaming], &[&taker]).await;
+    assert_error(result, EscrowError::MintBlocked);
+
+    // 黑名单必须是 seeds 派生的账户, 不能换成其他账户跳过检查
+    let mut bypass = take.clone();
+    let blocklist = pda::find_mint_blocklist_address().0;
+    for meta in bypass
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == blocklist)
+    {
+        meta.pubkey = system_program::ID;
+    }
+    let result = send(&mut fx.ctx, &[bypass], &[&taker]).await;
+    assert_error(result, ErrorCode::ConstraintSeeds);
+
+    // 解封后照常成交
+    let unblock = update_blocklist(&fx, fx.mint_b, false);
+    send(&mut fx.ctx, &[unblock, take], &[&taker])
+        .await
+        .unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+}
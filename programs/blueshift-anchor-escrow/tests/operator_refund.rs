@@ -0,0 +1,154 @@
+// 需要 sbf 版本的托管程序和 mock-router, 通过 `cargo test-sbf` 运行
+// mock-router 代表运营方的程序, forward 原样转发 operator_refund, forward_signed 另外用它的 [OPERATOR_SEED] PDA 签名
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use anchor_lang::solana_program::{instruction::AccountMeta, system_instruction};
+use blueshift_anchor_escrow::{accounts, errors::EscrowError, instruction, pda, MakeArgs};
+use common::*;
+use solana_sdk::{instruction::Instruction, pubkey, pubkey::Pubkey, signature::Signer};
+
+const MOCK_ROUTER_ID: Pubkey = pubkey!("H9rbLyowovQAw6yEAjYSBM5buj1uadunqxihTdatwnF9");
+
+// 每个托管存入 maker 的四分之一 token A
+const OPERATOR_AMOUNT: u64 = AMOUNT / 4;
+
+async fn make_with_operators(fx: &mut Fixture, seed: u64, allow_operators: bool) -> Pubkey {
+    let maker = fx.maker.insecure_clone();
+    let make = ix(
+        make_v2_accounts(fx, &maker.pubkey(), seed),
+        instruction::MakeV2 {
+            args: MakeArgs {
+                seed,
+                receive: RECEIVE,
+                amount: OPERATOR_AMOUNT,
+                allow_operators,
+                ..Default::default()
+            },
+        },
+    );
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    pda::find_escrow_address(&maker.pubkey(), seed).0
+}
+
+fn operator_refund(fx: &Fixture, operator: &Pubkey, escrow: &Pubkey) -> Instruction {
+    ix(
+        accounts::OperatorRefund {
+            refund: refund_accounts(fx, operator, &fx.maker.pubkey(), escrow),
+            config: pda::find_config_address().0,
+        },
+        instruction::OperatorRefund {},
+    )
+}
+
+// mock-router 的 forward(0) 和 forward_signed(1): 第一个账户是被调用的程序, 之后是转发的账户, 数据是 borsh 编码的 Vec<u8>
+fn forward(inner: Instruction, signed: bool) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(inner.program_id, false)];
+    accounts.extend(inner.accounts);
+    let mut data = vec![signed as u8];
+    data.extend_from_slice(&(inner.data.len() as u32).to_le_bytes());
+    data.extend_from_slice(&inner.data);
+    Instruction {
+        program_id: MOCK_ROUTER_ID,
+        accounts,
+        data,
+    }
+}
+
+fn set_operators(fx: &Fixture, operators: Vec<Pubkey>) -> Instruction {
+    ix(
+        accounts::SetOperators {
+            admin: fx.ctx.payer.pubkey(),
+            config: pda::find_config_address().0,
+        },
+        instruction::SetOperators { operators },
+    )
+}
+
+#[tokio::test]
+async fn operators_refund_opted_in_escrows_to_the_maker() {
+    let mut fx = setup_with_programs(&[("mock_router", MOCK_ROUTER_ID)]).await;
+    let operator = fx.stranger.insecure_clone();
+    let unregistered = fx.taker.insecure_clone();
+    let opted_in = make_with_operators(&mut fx, 1, true).await;
+    let opted_out = make_with_operators(&mut fx, 2, false).await;
+    let routed = make_with_operators(&mut fx, 3, true).await;
+
+    // 默认没有运营方, 即使托管允许也不能代为退还; 换一个托管, 避免和之后成功的交易完全相同
+    let refund = operator_refund(&fx, &operator.pubkey(), &routed);
+    let result = send(&mut fx.ctx, &[refund], &[&operator]).await;
+    assert_error(result, EscrowError::UnauthorizedOperator);
+
+    // 运营方的数量有上限
+    let too_many = set_operators(&fx, (0..5).map(|_| Pubkey::new_unique()).collect());
+    let result = send(&mut fx.ctx, &[too_many], &[]).await;
+    assert_error(result, EscrowError::InvalidOperators);
+
+    let register = set_operators(&fx, vec![operator.pubkey(), MOCK_ROUTER_ID]);
+    send(&mut fx.ctx, &[register], &[]).await.unwrap();
+
+    // maker 没有设置 allow_operators 的托管拒绝运营方
+    let refund = operator_refund(&fx, &operator.pubkey(), &opted_out);
+    let result = send(&mut fx.ctx, &[refund], &[&operator]).await;
+    assert_error(result, EscrowError::OperatorsNotAllowed);
+
+    // 没有登记的签名账户不是运营方
+    let refund = operator_refund(&fx, &unregistered.pubkey(), &opted_in);
+    let result = send(&mut fx.ctx, &[refund], &[&unregistered]).await;
+    assert_error(result, EscrowError::UnauthorizedOperator);
+
+    // 运营方也不能直接调用 refund
+    let refund = ix(
+        refund_accounts(&fx, &operator.pubkey(), &fx.maker.pubkey(), &opted_in),
+        instruction::Refund { force: false },
+    );
+    let result = send(&mut fx.ctx, &[refund], &[&operator]).await;
+    assert_error(result, EscrowError::UnauthorizedRefund);
+
+    // 登记的运营方退还之后 token A 回到 maker, 运营方的余额不变
+    let refund = operator_refund(&fx, &operator.pubkey(), &opted_in);
+    send(&mut fx.ctx, &[refund], &[&operator]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &opted_in).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &fx.maker.pubkey(), &fx.mint_a).await,
+        AMOUNT - 2 * OPERATOR_AMOUNT
+    );
+    assert_eq!(
+        token_balance(&mut fx.ctx, &operator.pubkey(), &fx.mint_a).await,
+        AMOUNT
+    );
+
+    // 登记的程序转发任意签名者的调用都不算运营方, 否则任何人都可以通过它退还托管
+    let refund = forward(operator_refund(&fx, &unregistered.pubkey(), &routed), false);
+    let result = send(&mut fx.ctx, &[refund], &[&unregistered]).await;
+    assert_error(result, EscrowError::UnauthorizedOperator);
+
+    // 只有程序自己的 [OPERATOR_SEED] PDA 签名时才是运营方; PDA 作为 authority 是可写的签名者, 先给它转入 lamports
+    let authority = pda::find_operator_authority_address(&MOCK_ROUTER_ID).0;
+    let fund = system_instruction::transfer(&fx.ctx.payer.pubkey(), &authority, 1_000_000);
+    let mut inner = operator_refund(&fx, &authority, &routed);
+    // PDA 没有私钥, 外层交易中不是签名者, 由 forward_signed 在 CPI 中签名
+    for meta in inner
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == authority)
+    {
+        meta.is_signer = false;
+    }
+    let refund = forward(inner, true);
+    send(&mut fx.ctx, &[fund, refund], &[]).await.unwrap();
+    assert!(fetch_escrow(&mut fx.ctx, &routed).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &fx.maker.pubkey(), &fx.mint_a).await,
+        AMOUNT - OPERATOR_AMOUNT
+    );
+
+    // 移除之后同一个运营方不能再代为退还
+    let unregister = set_operators(&fx, vec![]);
+    send(&mut fx.ctx, &[unregister], &[]).await.unwrap();
+    let later = make_with_operators(&mut fx, 4, true).await;
+    let refund = operator_refund(&fx, &operator.pubkey(), &later);
+    let result = send(&mut fx.ctx, &[refund], &[&operator]).await;
+    assert_error(result, EscrowError::UnauthorizedOperator);
+}
@@ -0,0 +1,243 @@
+// 需要 sbf 版本的托管程序和 mock-token-metadata, 通过 `cargo test-sbf` 运行
+// mock 部署在 Token Metadata 的地址上, rule set 只允许列表中的程序拥有的账户参与转账
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use anchor_lang::solana_program::{
+    instruction::AccountMeta, program_pack::Pack, system_instruction, sysvar,
+};
+use anchor_spl::{associated_token::get_associated_token_address, token::spl_token};
+use blueshift_anchor_escrow::{
+    errors::EscrowError,
+    instruction,
+    metadata::{METADATA_SEED, TOKEN_METADATA_ID, TOKEN_STANDARD_PROGRAMMABLE},
+    pda,
+    pnft::PNFT_ACCOUNTS,
+    ID,
+};
+use common::*;
+use solana_sdk::{
+    account::AccountSharedData,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const AUTH_RULES_ID: Pubkey = pubkey!("auth9SigNpDKz4sJJ1DfCTuZrZNSAgh9sFD3rboVmgg");
+
+// spl-token 的 AccountFrozen 和 mock 的 RuleSetViolation
+const ACCOUNT_FROZEN: u32 = spl_token::error::TokenError::AccountFrozen as u32;
+const RULE_SET_VIOLATION: u32 = 6002;
+
+async fn setup_pnft() -> Fixture {
+    setup_with_programs(&[("mock_token_metadata", TOKEN_METADATA_ID)]).await
+}
+
+fn edition_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            METADATA_SEED,
+            TOKEN_METADATA_ID.as_ref(),
+            mint.as_ref(),
+            b"edition",
+        ],
+        &TOKEN_METADATA_ID,
+    )
+    .0
+}
+
+fn token_record_address(mint: &Pubkey, token: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            METADATA_SEED,
+            TOKEN_METADATA_ID.as_ref(),
+            mint.as_ref(),
+            b"token_record",
+            token.as_ref(),
+        ],
+        &TOKEN_METADATA_ID,
+    )
+    .0
+}
+
+// 创建 maker 持有的 pNFT 并作为 fx.mint_a: freeze authority 是 edition PDA, maker 的 ATA 和 Token Metadata 一样被冻结
+async fn mint_pnft(fx: &mut Fixture) {
+    let mint = Keypair::new();
+    let rent = fx.ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &fx.ctx.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &fx.ctx.payer.pubkey(),
+            Some(&edition_address(&mint.pubkey())),
+            0,
+        )
+        .unwrap(),
+    ];
+    send(&mut fx.ctx, &ixs, &[&mint]).await.unwrap();
+
+    let maker = fx.maker.pubkey();
+    let ata = fund_ata(&mut fx.ctx, &mint.pubkey(), &maker, 1).await;
+    let mut account = fx.ctx.banks_client.get_account(ata).await.unwrap().unwrap();
+    let mut state = spl_token::state::Account::unpack(&account.data).unwrap();
+    state.state = spl_token::state::AccountState::Frozen;
+    spl_token::state::Account::pack(state, &mut account.data).unwrap();
+    fx.ctx.set_account(&ata, &AccountSharedData::from(account));
+
+    set_metadata(fx, &mint.pubkey(), TOKEN_STANDARD_PROGRAMMABLE, None).await;
+    fx.mint_a = mint.pubkey();
+}
+
+// 创建只允许 programs 拥有的账户参与转账的 rule set, 数据是 borsh 编码的 Vec<Pubkey>
+async fn set_rule_set(fx: &mut Fixture, programs: &[Pubkey]) -> Pubkey {
+    let mut data = (programs.len() as u32).to_le_bytes().to_vec();
+    for program in programs {
+        data.extend_from_slice(program.as_ref());
+    }
+
+    let rule_set = Pubkey::new_unique();
+    let mut account = AccountSharedData::new(LAMPORTS_PER_SOL, data.len(), &AUTH_RULES_ID);
+    account.set_data_from_slice(&data);
+    fx.ctx.set_account(&rule_set, &account);
+    rule_set
+}
+
+// 从 owner 的 ATA 转到 destination_owner 的 ATA 的 pNFT 账户, 放在 remaining_accounts 的最前面
+fn pnft_accounts(
+    fx: &Fixture,
+    owner: &Pubkey,
+    destination_owner: &Pubkey,
+    rule_set: &Pubkey,
+) -> [AccountMeta; PNFT_ACCOUNTS] {
+    let mint = &fx.mint_a;
+    let source = get_associated_token_address(owner, mint);
+    let destination = get_associated_token_address(destination_owner, mint);
+    [
+        AccountMeta::new_readonly(TOKEN_METADATA_ID, false),
+        AccountMeta::new(metadata_address(mint), false),
+        AccountMeta::new_readonly(edition_address(mint), false),
+        AccountMeta::new(token_record_address(mint, &source), false),
+        AccountMeta::new(token_record_address(mint, &destination), false),
+        AccountMeta::new_readonly(sysvar::instructions::ID, false),
+        AccountMeta::new_readonly(AUTH_RULES_ID, false),
+        AccountMeta::new_readonly(*rule_set, false),
+    ]
+}
+
+async fn is_frozen(fx: &mut Fixture, owner: &Pubkey) -> bool {
+    let address = get_associated_token_address(owner, &fx.mint_a);
+    let account = fx.ctx.banks_client.get_account(address).await.unwrap();
+    spl_token::state::Account::unpack(&account.unwrap().data)
+        .unwrap()
+        .is_frozen()
+}
+
+#[tokio::test]
+async fn pnft_round_trip_through_take() {
+    let mut fx = setup_pnft().await;
+    mint_pnft(&mut fx).await;
+    let (maker, taker) = (fx.maker.insecure_clone(), fx.taker.insecure_clone());
+    let rule_set = set_rule_set(&mut fx, &[ID]).await;
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+
+    // 冻结的 maker ATA 不能用 transfer_checked 存入
+    let mut make = ix(
+        make_accounts(&fx, &maker.pubkey(), 1),
+        make_args(1, RECEIVE, 1),
+    );
+    let result = send(&mut fx.ctx, &[make.clone()], &[&maker]).await;
+    assert_error(result, ACCOUNT_FROZEN);
+
+    // 通过 Token Metadata 存入后 vault 同样被冻结
+    make.accounts
+        .extend(pnft_accounts(&fx, &maker.pubkey(), &escrow, &rule_set));
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+    assert_eq!(token_balance(&mut fx.ctx, &escrow, &fx.mint_a).await, 1);
+    assert!(is_frozen(&mut fx, &escrow).await);
+
+    // 成交时 vault 由 Token Metadata 解冻转出, 不返回 VaultFrozen; taker 新创建的 ATA 收到后被冻结
+    let mut take = ix(
+        take_accounts(&fx, &taker.pubkey(), &maker.pubkey(), &escrow),
+        take_args(RECEIVE),
+    );
+    take.accounts
+        .extend(pnft_accounts(&fx, &escrow, &taker.pubkey(), &rule_set));
+    send(&mut fx.ctx, &[take], &[&taker]).await.unwrap();
+
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &taker.pubkey(), &fx.mint_a).await,
+        1
+    );
+    assert!(is_frozen(&mut fx, &taker.pubkey()).await);
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_b).await,
+        RECEIVE
+    );
+    let vault = get_associated_token_address(&escrow, &fx.mint_a);
+    let vault = fx.ctx.banks_client.get_account(vault).await.unwrap();
+    assert!(vault.is_none());
+}
+
+#[tokio::test]
+async fn pnft_round_trip_through_refund() {
+    let mut fx = setup_pnft().await;
+    mint_pnft(&mut fx).await;
+    let maker = fx.maker.insecure_clone();
+    let escrow = pda::find_escrow_address(&maker.pubkey(), 1).0;
+    let make = |fx: &Fixture, args: instruction::Make, rule_set: &Pubkey| {
+        let mut make = ix(make_accounts(fx, &maker.pubkey(), 1), args);
+        make.accounts
+            .extend(pnft_accounts(fx, &maker.pubkey(), &escrow, rule_set));
+        make
+    };
+
+    // rule set 不允许托管程序拥有的 escrow 持有 pNFT
+    let strict = set_rule_set(&mut fx, &[]).await;
+    let strict_make = make(&fx, make_args(1, RECEIVE, 1), &strict);
+    let result = send(&mut fx.ctx, &[strict_make], &[&maker]).await;
+    assert_error(result, RULE_SET_VIOLATION);
+
+    // 重新存入无法经过 Token Metadata
+    let rule_set = set_rule_set(&mut fx, &[ID]).await;
+    let recurring = instruction::Make {
+        recurring: true,
+        max_refills: 1,
+        ..make_args(1, RECEIVE, 1)
+    };
+    let recurring_make = make(&fx, recurring, &rule_set);
+    let result = send(&mut fx.ctx, &[recurring_make], &[&maker]).await;
+    assert_error(result, EscrowError::UnsupportedPnftEscrow);
+
+    let make = make(&fx, make_args(1, RECEIVE, 1), &rule_set);
+    send(&mut fx.ctx, &[make], &[&maker]).await.unwrap();
+
+    // 不传 pNFT 账户时 vault 按被冻结处理
+    let mut refund = ix(
+        refund_accounts(&fx, &maker.pubkey(), &maker.pubkey(), &escrow),
+        instruction::Refund { force: false },
+    );
+    let result = send(&mut fx.ctx, &[refund.clone()], &[&maker]).await;
+    assert_error(result, EscrowError::VaultFrozen);
+
+    refund
+        .accounts
+        .extend(pnft_accounts(&fx, &escrow, &maker.pubkey(), &rule_set));
+    send(&mut fx.ctx, &[refund], &[&maker]).await.unwrap();
+
+    assert!(fetch_escrow(&mut fx.ctx, &escrow).await.is_none());
+    assert_eq!(
+        token_balance(&mut fx.ctx, &maker.pubkey(), &fx.mint_a).await,
+        1
+    );
+    assert!(is_frozen(&mut fx, &maker.pubkey()).await);
+}
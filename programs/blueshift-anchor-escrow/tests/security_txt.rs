@@ -0,0 +1,58 @@
+// 需要 sbf 版本的托管程序, 通过 `cargo test-sbf` 运行
+// 直接读取编译出的 ELF, 防止重构时 security.txt 被 feature 或 cfg 意外去掉
+#![cfg(feature = "test-sbf")]
+
+use solana_security_txt::{SECURITY_TXT_BEGIN, SECURITY_TXT_END};
+use std::{collections::HashMap, path::PathBuf};
+
+// cargo test-sbf 通过 SBF_OUT_DIR 传入输出目录, 和 solana-program-test 查找程序的方式一致
+fn program_elf() -> Vec<u8> {
+    let dir = std::env::var("SBF_OUT_DIR")
+        .or_else(|_| std::env::var("BPF_OUT_DIR"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy"));
+    std::fs::read(dir.join("blueshift_anchor_escrow.so")).expect("build the program first")
+}
+
+// security.txt 是 BEGIN 和 END 之间以 \0 分隔的 key, value 序列
+fn parse_security_txt(elf: &[u8]) -> HashMap<String, String> {
+    let find = |needle: &str| {
+        elf.windows(needle.len())
+            .position(|window| window == needle.as_bytes())
+            .expect("security.txt section missing from the program")
+    };
+    let begin = find(SECURITY_TXT_BEGIN) + SECURITY_TXT_BEGIN.len();
+    let end = find(SECURITY_TXT_END);
+
+    let fields: Vec<&str> = std::str::from_utf8(&elf[begin..end])
+        .unwrap()
+        .split_terminator('\0')
+        .collect();
+    assert_eq!(fields.len() % 2, 0, "every key must have a value");
+    fields
+        .chunks(2)
+        .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+        .collect()
+}
+
+#[test]
+fn program_embeds_security_txt() {
+    let fields = parse_security_txt(&program_elf());
+
+    // security.txt 标准要求的字段
+    for key in ["name", "project_url", "contacts", "policy"] {
+        assert!(
+            fields.get(key).is_some_and(|value| !value.is_empty()),
+            "missing {key}"
+        );
+    }
+    // solana-verify 和浏览器用源码地址和版本把部署的程序对应到仓库
+    assert_eq!(
+        fields["source_code"],
+        "https://github.com/BTBMan/blueshift-anchor-escrow"
+    );
+    assert_eq!(
+        fields["source_release"],
+        concat!("v", env!("CARGO_PKG_VERSION"))
+    );
+}
@@ -0,0 +1,47 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, program::invoke},
+};
+
+// 只在测试中使用的第二个转发程序, 和 mock-router 的 forward 相同, 把指令数据和 remaining_accounts 原样转发给 target_program
+// 用来测试托管的 direct_only: 可信的 mock-router 先调用这个程序, 再由它调用托管程序, 托管程序位于更深的 CPI 中
+
+declare_id!("C6PWCpjqnBW3X4JsghuCxLoqL5VDkPz1wFcQJdAkEbbM");
+
+#[program]
+pub mod mock_relay {
+    use super::*;
+
+    #[instruction(discriminator = 0)]
+    pub fn forward<'info>(
+        ctx: Context<'_, '_, '_, 'info, Forward<'info>>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let accounts = ctx
+            .remaining_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let instruction = Instruction {
+            program_id: ctx.accounts.target_program.key(),
+            accounts,
+            data,
+        };
+
+        let mut account_infos = ctx.remaining_accounts.to_vec();
+        account_infos.push(ctx.accounts.target_program.to_account_info());
+        invoke(&instruction, &account_infos)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Forward<'info> {
+    /// CHECK: 被调用的程序, 由运行时检查是否可执行
+    pub target_program: UncheckedAccount<'info>,
+}
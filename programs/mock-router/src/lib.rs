@@ -0,0 +1,75 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction,
+        program::{invoke, invoke_signed},
+    },
+};
+
+// 只在测试中使用的路由程序, 把指令数据和 remaining_accounts 原样通过 CPI 转发给 target_program
+// 用来测试托管的 direct_only: 托管程序看到的是 CPI 调用, 交易的顶层指令属于这个程序
+// 账户的 signer 和 writable 权限和外层交易中的相同, 外层交易的签名者在 CPI 中仍然是签名者
+
+declare_id!("H9rbLyowovQAw6yEAjYSBM5buj1uadunqxihTdatwnF9");
+
+// 和托管程序的 pda::OPERATOR_SEED 相同, 这个程序登记为运营方时用这个 PDA 签名
+const OPERATOR_SEED: &[u8] = b"operator";
+
+#[program]
+pub mod mock_router {
+    use super::*;
+
+    #[instruction(discriminator = 0)]
+    pub fn forward<'info>(
+        ctx: Context<'_, '_, '_, 'info, Forward<'info>>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let instruction = forwarded(&ctx, data, None);
+
+        let mut account_infos = ctx.remaining_accounts.to_vec();
+        account_infos.push(ctx.accounts.target_program.to_account_info());
+        invoke(&instruction, &account_infos)?;
+
+        Ok(())
+    }
+
+    // 和 forward 相同, 另外用 [OPERATOR_SEED] PDA 签名, 模拟运营方程序调用 operator_refund
+    #[instruction(discriminator = 1)]
+    pub fn forward_signed<'info>(
+        ctx: Context<'_, '_, '_, 'info, Forward<'info>>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let (operator, bump) = Pubkey::find_program_address(&[OPERATOR_SEED], &crate::ID);
+        let instruction = forwarded(&ctx, data, Some(operator));
+
+        let mut account_infos = ctx.remaining_accounts.to_vec();
+        account_infos.push(ctx.accounts.target_program.to_account_info());
+        invoke_signed(&instruction, &account_infos, &[&[OPERATOR_SEED, &[bump]]])?;
+
+        Ok(())
+    }
+}
+
+// 转发给 target_program 的指令, signer 为 Some 时这个账户在 CPI 中也是签名者
+fn forwarded(ctx: &Context<Forward>, data: Vec<u8>, signer: Option<Pubkey>) -> Instruction {
+    let accounts = ctx
+        .remaining_accounts
+        .iter()
+        .map(|account| AccountMeta {
+            pubkey: account.key(),
+            is_signer: account.is_signer || Some(account.key()) == signer,
+            is_writable: account.is_writable,
+        })
+        .collect();
+    Instruction {
+        program_id: ctx.accounts.target_program.key(),
+        accounts,
+        data,
+    }
+}
+
+#[derive(Accounts)]
+pub struct Forward<'info> {
+    /// CHECK: 被调用的程序, 由运行时检查是否可执行
+    pub target_program: UncheckedAccount<'info>,
+}
@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
+
+// 只在测试中使用的兑换程序, 代替 Jupiter 测试 take_with_swap: 收取 user 的 token C, 再铸造 token B 给 destination
+// amount_out 由调用方任意指定, 用来模拟兑换得到的数量不足或者超出的情况
+
+declare_id!("B9R9wzNjLYcBk6NFq7RYChGUYsqRm3mAfxpuwz7dHBsm");
+
+#[program]
+pub mod mock_swap {
+    use super::*;
+
+    #[instruction(discriminator = 0)]
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, amount_out: u64) -> Result<()> {
+        transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program_in.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_in.to_account_info(),
+                    mint: ctx.accounts.mint_in.to_account_info(),
+                    to: ctx.accounts.pool_in.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount_in,
+            ctx.accounts.mint_in.decimals,
+        )?;
+
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program_out.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.mint_out.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.mint_authority.to_account_info(),
+                },
+                &[&[b"mint-authority", &[ctx.bumps.mint_authority]]],
+            ),
+            amount_out,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub user: Signer<'info>,
+
+    pub mint_in: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = mint_in, token::authority = user)]
+    pub user_in: InterfaceAccount<'info, TokenAccount>,
+
+    // 收取 token C 的任意账户
+    #[account(mut, token::mint = mint_in)]
+    pub pool_in: InterfaceAccount<'info, TokenAccount>,
+
+    // 铸币权限必须已经转给 mint_authority
+    #[account(mut)]
+    pub mint_out: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: 只作为 mint_out 的铸币权限签名, 由 seeds 约束地址
+    #[account(seeds = [b"mint-authority"], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    #[account(mut, token::mint = mint_out)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program_in: Interface<'info, TokenInterface>,
+    pub token_program_out: Interface<'info, TokenInterface>,
+}
@@ -0,0 +1,176 @@
+use anchor_lang::{prelude::*, solana_program::sysvar};
+use anchor_spl::token_interface::{
+    freeze_account, thaw_account, transfer_checked, FreezeAccount, Mint, ThawAccount, TokenAccount,
+    TokenInterface, TransferChecked,
+};
+
+// 只在测试中使用的 Token Metadata 程序, 部署在 Token Metadata 的地址上, 代替它测试托管程序的 pNFT 转账
+// 只实现 Transfer(TransferV1): 检查 edition 和 token record 的地址, 按 rule set 检查转账双方,
+// 用 edition PDA 作为 freeze authority 解冻来源账户, 转账之后冻结目标账户; 不创建 token record
+// rule set 是 authorization_rules_program 拥有的账户, 数据是 borsh 编码的 Vec<Pubkey>:
+// 转账双方中不属于系统程序的账户(例如托管程序的 PDA), 它的 owner 程序必须在列表中
+
+declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+#[program]
+pub mod mock_token_metadata {
+    use super::*;
+
+    #[instruction(discriminator = 49)]
+    pub fn transfer(ctx: Context<Transfer>, args: TransferArgs) -> Result<()> {
+        let TransferArgs::V1 { amount, .. } = args;
+
+        let rules = ctx
+            .accounts
+            .authorization_rules
+            .as_ref()
+            .ok_or(MockError::MissingRuleSet)?;
+        let rules_program = ctx
+            .accounts
+            .authorization_rules_program
+            .as_ref()
+            .ok_or(MockError::MissingRuleSet)?;
+        require_keys_eq!(*rules.owner, rules_program.key(), MockError::MissingRuleSet);
+        let allowed = Vec::<Pubkey>::deserialize(&mut &rules.try_borrow_data()?[..])?;
+        for party in [&ctx.accounts.token_owner, &ctx.accounts.destination_owner] {
+            require!(
+                party.owner == &System::id() || allowed.contains(party.owner),
+                MockError::RuleSetViolation
+            );
+        }
+
+        let mint = ctx.accounts.mint.key();
+        let edition_seeds: &[&[u8]] = &[
+            b"metadata",
+            ID.as_ref(),
+            mint.as_ref(),
+            b"edition",
+            &[ctx.bumps.edition],
+        ];
+        let token_program = ctx.accounts.spl_token_program.to_account_info();
+        let edition = ctx.accounts.edition.to_account_info();
+
+        for account in [&ctx.accounts.token, &ctx.accounts.destination_token] {
+            if account.is_frozen() {
+                thaw_account(CpiContext::new_with_signer(
+                    token_program.clone(),
+                    ThawAccount {
+                        account: account.to_account_info(),
+                        mint: ctx.accounts.mint.to_account_info(),
+                        authority: edition.clone(),
+                    },
+                    &[edition_seeds],
+                ))?;
+            }
+        }
+
+        transfer_checked(
+            CpiContext::new(
+                token_program.clone(),
+                TransferChecked {
+                    from: ctx.accounts.token.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.destination_token.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        freeze_account(CpiContext::new_with_signer(
+            token_program,
+            FreezeAccount {
+                account: ctx.accounts.destination_token.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                authority: edition,
+            },
+            &[edition_seeds],
+        ))
+    }
+}
+
+// 和 Token Metadata 的 TransferArgs 编码相同, authorization_data 不使用
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum TransferArgs {
+    V1 {
+        amount: u64,
+        authorization_data: Option<Vec<u8>>,
+    },
+}
+
+// 账户顺序和 Token Metadata 的 TransferV1 相同
+#[derive(Accounts)]
+pub struct Transfer<'info> {
+    #[account(mut, token::mint = mint, token::authority = token_owner)]
+    pub token: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: token 的 owner, 由 token 的约束检查
+    pub token_owner: UncheckedAccount<'info>,
+
+    #[account(mut, token::mint = mint, token::authority = destination_owner)]
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: destination_token 的 owner, 由 destination_token 的约束检查
+    pub destination_owner: UncheckedAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: 只检查地址, 数据由测试写入
+    #[account(mut, seeds = [b"metadata", ID.as_ref(), mint.key().as_ref()], bump)]
+    pub metadata: UncheckedAccount<'info>,
+
+    /// CHECK: mint 的 freeze authority, 由 seeds 约束地址
+    #[account(seeds = [b"metadata", ID.as_ref(), mint.key().as_ref(), b"edition"], bump)]
+    pub edition: UncheckedAccount<'info>,
+
+    /// CHECK: 只检查地址
+    #[account(
+        mut,
+        seeds = [b"metadata", ID.as_ref(), mint.key().as_ref(), b"token_record", token.key().as_ref()],
+        bump
+    )]
+    pub token_record: UncheckedAccount<'info>,
+
+    /// CHECK: 只检查地址
+    #[account(
+        mut,
+        seeds = [b"metadata", ID.as_ref(), mint.key().as_ref(), b"token_record", destination_token.key().as_ref()],
+        bump
+    )]
+    pub destination_token_record: UncheckedAccount<'info>,
+
+    // 只支持 owner 自己转账, 不支持 delegate
+    #[account(address = token_owner.key() @ MockError::InvalidAuthority)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: 由 address 约束
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: UncheckedAccount<'info>,
+
+    pub spl_token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: 不使用
+    pub spl_ata_program: UncheckedAccount<'info>,
+
+    /// CHECK: rule set 的 owner
+    pub authorization_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: 在 transfer 中按 owner 检查并读取
+    pub authorization_rules: Option<UncheckedAccount<'info>>,
+}
+
+#[error_code]
+pub enum MockError {
+    #[msg("Authority is not the token owner")]
+    InvalidAuthority,
+    #[msg("Rule set is missing or not owned by the authorization rules program")]
+    MissingRuleSet,
+    #[msg("Rule set does not allow the transfer")]
+    RuleSetViolation,
+}
@@ -0,0 +1,198 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, memo::Memo, token_interface::TokenInterface};
+use blueshift_anchor_escrow::{
+    cpi::{
+        self,
+        accounts::{Make, MakeV2, Refund},
+    },
+    program::BlueshiftAnchorEscrow,
+    results::MakeResult,
+    MakeArgs,
+};
+
+// 只在测试中使用的示例程序: 以程序的金库 PDA 作为 maker, 通过 CPI 创建和退还托管
+// 金库没有私钥, 由本程序用 invoke_signed 代替它签名, 用来验证托管程序支持 off-curve 的 maker
+
+declare_id!("9cjWXumn9tk5CYx9qq8r7KHwzDHmxA4cT1954ykqyJ88");
+
+#[program]
+pub mod pda_maker {
+    use super::*;
+
+    // 用金库 PDA 中的 token A 创建固定价格的托管, 租金由 payer 支付
+    // 托管程序通过 return data 返回托管的地址和 bump, 这里解码后再原样返回给调用方
+    #[instruction(discriminator = 0)]
+    pub fn make_escrow(
+        ctx: Context<MakeEscrow>,
+        seed: u64,
+        receive: u64,
+        amount: u64,
+    ) -> Result<MakeResult> {
+        let signer_seeds: &[&[&[u8]]] = &[&[b"treasury", &[ctx.bumps.treasury]]];
+
+        let made = cpi::make_v2(
+            CpiContext::new_with_signer(
+                ctx.accounts.escrow_program.to_account_info(),
+                MakeV2 {
+                    make: Make {
+                        maker: ctx.accounts.treasury.to_account_info(),
+                        rent_payer: Some(ctx.accounts.payer.to_account_info()),
+                        escrow: ctx.accounts.escrow.to_account_info(),
+                        mint_a: ctx.accounts.mint_a.to_account_info(),
+                        mint_b: ctx.accounts.mint_b.to_account_info(),
+                        maker_ata_a: Some(ctx.accounts.treasury_ata_a.to_account_info()),
+                        vault: ctx.accounts.vault.to_account_info(),
+                        config: ctx.accounts.config.to_account_info(),
+                        mint_allowlist: ctx.accounts.mint_allowlist.to_account_info(),
+                        mint_blocklist: ctx.accounts.mint_blocklist.to_account_info(),
+                        exempt_list: None,
+                        treasury: None,
+                        stats: ctx.accounts.stats.to_account_info(),
+                        registry: ctx.accounts.registry.to_account_info(),
+                        associated_token_program: ctx
+                            .accounts
+                            .associated_token_program
+                            .to_account_info(),
+                        token_program_a: ctx.accounts.token_program.to_account_info(),
+                        token_program_b: ctx.accounts.token_program.to_account_info(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                        event_authority: ctx.accounts.event_authority.to_account_info(),
+                        program: ctx.accounts.escrow_program.to_account_info(),
+                    },
+                    prefs: None,
+                    pair_index: None,
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            // 其他参数都使用默认值: 不过期, 任何人都可以成交, token B 支付给金库, 只接受 mint_b, 成交后关闭
+            MakeArgs {
+                seed,
+                receive,
+                amount,
+                ..Default::default()
+            },
+        )?
+        .get();
+
+        // 返回的地址就是 seeds 派生的托管地址
+        require_keys_eq!(made.escrow, ctx.accounts.escrow.key());
+        Ok(made)
+    }
+
+    // 退还金库创建的托管, token A 回到金库的 ATA, 租金还给 payer
+    #[instruction(discriminator = 1)]
+    pub fn refund_escrow(ctx: Context<RefundEscrow>) -> Result<()> {
+        let signer_seeds: &[&[&[u8]]] = &[&[b"treasury", &[ctx.bumps.treasury]]];
+
+        cpi::refund(
+            CpiContext::new_with_signer(
+                ctx.accounts.escrow_program.to_account_info(),
+                Refund {
+                    authority: ctx.accounts.treasury.to_account_info(),
+                    maker: ctx.accounts.treasury.to_account_info(),
+                    rent_payer: ctx.accounts.rent_payer.to_account_info(),
+                    escrow: ctx.accounts.escrow.to_account_info(),
+                    mint_a: ctx.accounts.mint_a.to_account_info(),
+                    vault: ctx.accounts.vault.to_account_info(),
+                    maker_ata_a: ctx.accounts.treasury_ata_a.to_account_info(),
+                    stats: ctx.accounts.stats.to_account_info(),
+                    registry: ctx.accounts.registry.to_account_info(),
+                    pair_index: None,
+                    associated_token_program: ctx
+                        .accounts
+                        .associated_token_program
+                        .to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                    memo_program: ctx.accounts.memo_program.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    event_authority: ctx.accounts.event_authority.to_account_info(),
+                    program: ctx.accounts.escrow_program.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            false,
+        )
+    }
+}
+
+// 托管相关的账户都由托管程序校验, 这里只约束金库的地址
+#[derive(Accounts)]
+pub struct MakeEscrow<'info> {
+    // 支付 escrow 和 vault 的租金, 即托管的 rent_payer
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // 金库 PDA, 作为托管的 maker
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: SystemAccount<'info>,
+
+    /// CHECK: 由托管程序创建, 地址由托管程序的 seeds 约束
+    #[account(mut)]
+    pub escrow: UncheckedAccount<'info>,
+    /// CHECK: 由托管程序校验
+    pub mint_a: UncheckedAccount<'info>,
+    /// CHECK: 由托管程序校验
+    pub mint_b: UncheckedAccount<'info>,
+    /// CHECK: 金库的 token A ATA, 由托管程序校验
+    #[account(mut)]
+    pub treasury_ata_a: UncheckedAccount<'info>,
+    /// CHECK: 由托管程序创建
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: 托管程序的配置账户, 由托管程序校验
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: 托管程序的 mint 白名单, 由托管程序校验
+    pub mint_allowlist: UncheckedAccount<'info>,
+    /// CHECK: 托管程序的 mint 黑名单, 由托管程序校验
+    pub mint_blocklist: UncheckedAccount<'info>,
+    /// CHECK: 托管程序的统计账户, 由托管程序校验
+    #[account(mut)]
+    pub stats: UncheckedAccount<'info>,
+    /// CHECK: 金库的托管索引, 由托管程序校验
+    #[account(mut)]
+    pub registry: UncheckedAccount<'info>,
+    /// CHECK: 托管程序记录事件使用的 PDA, 由托管程序校验
+    pub event_authority: UncheckedAccount<'info>,
+
+    pub escrow_program: Program<'info, BlueshiftAnchorEscrow>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>, // mint A 和 mint B 都由这个 token 程序管理
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundEscrow<'info> {
+    // 金库 PDA, 作为托管的 maker
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: SystemAccount<'info>,
+
+    /// CHECK: 托管的 rent_payer, 由托管程序的 has_one 约束
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+    /// CHECK: 由托管程序校验
+    #[account(mut)]
+    pub escrow: UncheckedAccount<'info>,
+    /// CHECK: 由托管程序校验
+    pub mint_a: UncheckedAccount<'info>,
+    /// CHECK: 由托管程序校验
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: 金库的 token A ATA, 由托管程序校验
+    #[account(mut)]
+    pub treasury_ata_a: UncheckedAccount<'info>,
+    /// CHECK: 托管程序的统计账户, 由托管程序校验
+    #[account(mut)]
+    pub stats: UncheckedAccount<'info>,
+    /// CHECK: 金库的托管索引, 由托管程序校验
+    #[account(mut)]
+    pub registry: UncheckedAccount<'info>,
+    /// CHECK: 托管程序记录事件使用的 PDA, 由托管程序校验
+    pub event_authority: UncheckedAccount<'info>,
+
+    pub escrow_program: Program<'info, BlueshiftAnchorEscrow>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,404 @@
+// 需要 sbf 版本的托管程序和示例程序, 通过 `cargo test-sbf` 运行
+#![cfg(feature = "test-sbf")]
+
+use anchor_lang::{
+    solana_program::{
+        instruction::Instruction, program::MAX_RETURN_DATA, program_pack::Pack, system_instruction,
+    },
+    AccountDeserialize, AnchorDeserialize, AnchorSerialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address, spl_associated_token_account::instruction as ata_instruction,
+    },
+    token::spl_token,
+};
+use blueshift_anchor_escrow::{results::MakeResult, state::Escrow};
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+// 用 solana-program-test 同时运行托管程序和示例程序, 比较 CPI 写入的托管和直接调用 make 写入的托管
+// anchor 的 invoke_signed 只能在链上执行, 因此两个程序都加载 sbf 版本而不是 native processor
+
+fn treasury() -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury"], &pda_maker::ID).0
+}
+
+fn escrow_address(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
+        &blueshift_anchor_escrow::ID,
+    )
+    .0
+}
+
+fn escrow_pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &blueshift_anchor_escrow::ID).0
+}
+
+async fn send(ctx: &mut ProgramTestContext, ixs: &[Instruction], signers: &[&Keypair]) {
+    try_send(ctx, ixs, signers).await.unwrap();
+}
+
+async fn try_send(
+    ctx: &mut ProgramTestContext,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let tx =
+        Transaction::new_signed_with_payer(ixs, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    ctx.banks_client.process_transaction(tx).await
+}
+
+// 发送示例程序的指令并解码它转发的托管程序 return data
+async fn send_returning<T: AnchorDeserialize>(
+    ctx: &mut ProgramTestContext,
+    ixs: &[Instruction],
+) -> T {
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        blockhash,
+    );
+    let outcome = ctx
+        .banks_client
+        .process_transaction_with_metadata(tx)
+        .await
+        .unwrap();
+    outcome.result.unwrap();
+    let return_data = outcome.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, pda_maker::ID);
+    // 运行时会去掉 return data 末尾的 0, 补齐后再解码
+    let mut data = return_data.data;
+    data.resize(MAX_RETURN_DATA, 0);
+    T::deserialize(&mut data.as_slice()).unwrap()
+}
+
+// 创建 6 位小数的 SPL Token mint, 铸币权限属于 ctx.payer
+async fn create_mint(ctx: &mut ProgramTestContext) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &ctx.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &ctx.payer.pubkey(),
+            None,
+            6,
+        )
+        .unwrap(),
+    ];
+    send(ctx, &ixs, &[&mint]).await;
+    mint.pubkey()
+}
+
+// 为 owner 创建 ATA 并铸造 amount 个 token
+async fn fund_ata(
+    ctx: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let ata = get_associated_token_address(owner, mint);
+    let ixs = [
+        ata_instruction::create_associated_token_account(
+            &ctx.payer.pubkey(),
+            owner,
+            mint,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::mint_to(
+            &spl_token::ID,
+            mint,
+            &ata,
+            &ctx.payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap(),
+    ];
+    send(ctx, &ixs, &[]).await;
+    ata
+}
+
+async fn fetch_escrow(ctx: &mut ProgramTestContext, address: &Pubkey) -> Option<Escrow> {
+    let account = ctx.banks_client.get_account(*address).await.unwrap()?;
+    Some(Escrow::try_deserialize(&mut account.data.as_slice()).unwrap())
+}
+
+async fn token_balance(ctx: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let account = ctx
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+struct Setup {
+    ctx: ProgramTestContext,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    config: Pubkey,
+    stats: Pubkey,
+    event_authority: Pubkey,
+}
+
+async fn setup() -> Setup {
+    let mut program_test =
+        ProgramTest::new("blueshift_anchor_escrow", blueshift_anchor_escrow::ID, None);
+    program_test.add_program("pda_maker", pda_maker::ID, None);
+    let mut ctx = program_test.start_with_context().await;
+
+    let mint_a = create_mint(&mut ctx).await;
+    let mint_b = create_mint(&mut ctx).await;
+    let config = escrow_pda(&[b"config"]);
+    let stats = escrow_pda(&[b"stats"]);
+    let event_authority = escrow_pda(&[b"__event_authority"]);
+
+    // 协议配置是全局唯一的, 由 ctx.payer 作为 admin 初始化, 手续费为 0
+    let initialize_config = Instruction {
+        program_id: blueshift_anchor_escrow::ID,
+        accounts: blueshift_anchor_escrow::accounts::InitializeConfig {
+            admin: ctx.payer.pubkey(),
+            config,
+            fee_authority: escrow_pda(&[b"fee_authority"]),
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: blueshift_anchor_escrow::instruction::InitializeConfig { fee_bps: 0 }.data(),
+    };
+    send(&mut ctx, &[initialize_config], &[]).await;
+
+    Setup {
+        ctx,
+        mint_a,
+        mint_b,
+        config,
+        stats,
+        event_authority,
+    }
+}
+
+#[tokio::test]
+async fn cpi_make_matches_direct_make_and_refunds() {
+    let Setup {
+        mut ctx,
+        mint_a,
+        mint_b,
+        config,
+        stats,
+        event_authority,
+    } = setup().await;
+    let seed = 7;
+    let (receive, amount) = (500, 1_000);
+
+    // 普通钱包直接调用 make
+    let maker = Keypair::new();
+    let maker_ata_a = fund_ata(&mut ctx, &mint_a, &maker.pubkey(), amount).await;
+    let direct_escrow = escrow_address(&maker.pubkey(), seed);
+    let make = Instruction {
+        program_id: blueshift_anchor_escrow::ID,
+        accounts: blueshift_anchor_escrow::accounts::Make {
+            maker: maker.pubkey(),
+            rent_payer: Some(ctx.payer.pubkey()),
+            escrow: direct_escrow,
+            mint_a,
+            mint_b,
+            maker_ata_a: Some(maker_ata_a),
+            vault: get_associated_token_address(&direct_escrow, &mint_a),
+            config,
+            mint_allowlist: escrow_pda(&[b"mint_allowlist"]),
+            mint_blocklist: escrow_pda(&[b"mint_blocklist"]),
+            exempt_list: None,
+            treasury: None,
+            stats,
+            registry: escrow_pda(&[b"registry", maker.pubkey().as_ref()]),
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program_a: spl_token::ID,
+            token_program_b: spl_token::ID,
+            system_program: anchor_lang::system_program::ID,
+            event_authority,
+            program: blueshift_anchor_escrow::ID,
+        }
+        .to_account_metas(None),
+        data: blueshift_anchor_escrow::instruction::Make {
+            seed,
+            receive,
+            amount,
+            expiry: 0,
+            allowed_taker: Pubkey::default(),
+            taker_allowlist_root: [0; 32],
+            hashlock: [0; 32],
+            start_time: 0,
+            reject_freezable: false,
+            receive_to: Pubkey::default(),
+            refund_delegate: Pubkey::default(),
+            commit_delay_slots: 0,
+            arbiter: Pubkey::default(),
+            reservation_window: 0,
+            alt_payments: vec![],
+            recurring: false,
+            max_refills: 0,
+        }
+        .data(),
+    };
+    send(&mut ctx, &[make], &[&maker]).await;
+
+    // 金库 PDA 通过示例程序的 CPI 调用 make
+    let treasury = treasury();
+    let treasury_ata_a = fund_ata(&mut ctx, &mint_a, &treasury, amount).await;
+    let cpi_escrow = escrow_address(&treasury, seed);
+    let cpi_vault = get_associated_token_address(&cpi_escrow, &mint_a);
+    let make_escrow = Instruction {
+        program_id: pda_maker::ID,
+        accounts: pda_maker::accounts::MakeEscrow {
+            payer: ctx.payer.pubkey(),
+            treasury,
+            escrow: cpi_escrow,
+            mint_a,
+            mint_b,
+            treasury_ata_a,
+            vault: cpi_vault,
+            config,
+            mint_allowlist: escrow_pda(&[b"mint_allowlist"]),
+            mint_blocklist: escrow_pda(&[b"mint_blocklist"]),
+            stats,
+            registry: escrow_pda(&[b"registry", treasury.as_ref()]),
+            event_authority,
+            escrow_program: blueshift_anchor_escrow::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program: spl_token::ID,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: pda_maker::instruction::MakeEscrow {
+            seed,
+            receive,
+            amount,
+        }
+        .data(),
+    };
+    let made: MakeResult = send_returning(&mut ctx, &[make_escrow]).await;
+    assert_eq!(
+        made,
+        MakeResult {
+            escrow: cpi_escrow,
+            bump: Pubkey::find_program_address(
+                &[b"escrow", treasury.as_ref(), &seed.to_le_bytes()],
+                &blueshift_anchor_escrow::ID,
+            )
+            .1,
+        }
+    );
+
+    // 除了 maker 相关的字段和 bump, 两种方式写入的托管完全相同
+    let direct = fetch_escrow(&mut ctx, &direct_escrow).await.unwrap();
+    let mut through_cpi = fetch_escrow(&mut ctx, &cpi_escrow).await.unwrap();
+    assert_eq!(through_cpi.maker, treasury);
+    assert_eq!(through_cpi.receive_to, treasury);
+    assert_eq!(through_cpi.rent_payer, ctx.payer.pubkey());
+    through_cpi.maker = direct.maker;
+    through_cpi.receive_to = direct.receive_to;
+    through_cpi.bump = direct.bump;
+    assert_eq!(
+        through_cpi.try_to_vec().unwrap(),
+        direct.try_to_vec().unwrap()
+    );
+    assert_eq!(token_balance(&mut ctx, &cpi_vault).await, amount);
+
+    // 金库 PDA 通过 CPI 退还托管, token A 回到金库的 ATA
+    let refund_escrow = Instruction {
+        program_id: pda_maker::ID,
+        accounts: pda_maker::accounts::RefundEscrow {
+            treasury,
+            rent_payer: ctx.payer.pubkey(),
+            escrow: cpi_escrow,
+            mint_a,
+            vault: cpi_vault,
+            treasury_ata_a,
+            stats,
+            registry: escrow_pda(&[b"registry", treasury.as_ref()]),
+            event_authority,
+            escrow_program: blueshift_anchor_escrow::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program: spl_token::ID,
+            memo_program: anchor_spl::memo::ID,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: pda_maker::instruction::RefundEscrow {}.data(),
+    };
+    send(&mut ctx, &[refund_escrow], &[]).await;
+
+    assert!(fetch_escrow(&mut ctx, &cpi_escrow).await.is_none());
+    assert_eq!(token_balance(&mut ctx, &treasury_ata_a).await, amount);
+}
+
+#[tokio::test]
+async fn rejects_cpi_make_for_another_treasury() {
+    let Setup {
+        mut ctx,
+        mint_a,
+        mint_b,
+        config,
+        stats,
+        event_authority,
+    } = setup().await;
+
+    // 示例程序只能为自己的金库签名, 传入其他 maker 的 escrow 地址会被托管程序的 seeds 拒绝
+    let treasury = treasury();
+    let treasury_ata_a = fund_ata(&mut ctx, &mint_a, &treasury, 1_000).await;
+    let other_escrow = escrow_address(&ctx.payer.pubkey(), 1);
+    let make_escrow = Instruction {
+        program_id: pda_maker::ID,
+        accounts: pda_maker::accounts::MakeEscrow {
+            payer: ctx.payer.pubkey(),
+            treasury,
+            escrow: other_escrow,
+            mint_a,
+            mint_b,
+            treasury_ata_a,
+            vault: get_associated_token_address(&other_escrow, &mint_a),
+            config,
+            mint_allowlist: escrow_pda(&[b"mint_allowlist"]),
+            mint_blocklist: escrow_pda(&[b"mint_blocklist"]),
+            stats,
+            registry: escrow_pda(&[b"registry", treasury.as_ref()]),
+            event_authority,
+            escrow_program: blueshift_anchor_escrow::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            token_program: spl_token::ID,
+            system_program: anchor_lang::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: pda_maker::instruction::MakeEscrow {
+            seed: 1,
+            receive: 500,
+            amount: 1_000,
+        }
+        .data(),
+    };
+
+    assert!(try_send(&mut ctx, &[make_escrow], &[]).await.is_err());
+    assert_eq!(token_balance(&mut ctx, &treasury_ata_a).await, 1_000);
+}
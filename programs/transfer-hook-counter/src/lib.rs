@@ -0,0 +1,105 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::{
+    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+// 只在测试中使用的 transfer hook, 记录 mint 的每一次转账, 用来验证托管程序的 CPI 正确传入了 hook 需要的账户
+
+declare_id!("87XV5YqTdkCREiispLoAH3rttKgyytS2B6AXJPUF4iQ");
+
+#[program]
+pub mod transfer_hook_counter {
+    use super::*;
+
+    // 创建 extra-account-metas 账户和计数器, 每次转账额外需要的账户只有计数器
+    #[instruction(discriminator = 0)]
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        let extra_account_metas = [ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"counter".to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+            ],
+            false,
+            true,
+        )?];
+
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_metas.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
+
+        ctx.accounts.counter.set_inner(Counter {
+            count: 0,
+            bump: ctx.bumps.counter,
+        });
+
+        Ok(())
+    }
+
+    // Token-2022 在每次 transfer_checked 时调用
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn execute(ctx: Context<Execute>, _amount: u64) -> Result<()> {
+        ctx.accounts.counter.count += 1;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: 由 ExtraAccountMetaList::init 写入, 地址由 seeds 约束
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(1)?,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump,
+    )]
+    pub extra_account_metas: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Counter::INIT_SPACE + Counter::DISCRIMINATOR.len(),
+        seeds = [b"counter", mint.key().as_ref()],
+        bump,
+    )]
+    pub counter: Account<'info, Counter>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// 账户顺序由 transfer hook 接口规定
+#[derive(Accounts)]
+pub struct Execute<'info> {
+    /// CHECK: 转出的 token 账户
+    pub source: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: 转入的 token 账户
+    pub destination: UncheckedAccount<'info>,
+    /// CHECK: 转出账户的 owner 或 delegate
+    pub authority: UncheckedAccount<'info>,
+    /// CHECK: 地址由 seeds 约束
+    #[account(seeds = [b"extra-account-metas", mint.key().as_ref()], bump)]
+    pub extra_account_metas: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"counter", mint.key().as_ref()], bump = counter.bump)]
+    pub counter: Account<'info, Counter>,
+}
+
+#[account(discriminator = 1)]
+#[derive(InitSpace)]
+pub struct Counter {
+    pub count: u64,
+    pub bump: u8,
+}
@@ -0,0 +1,258 @@
+use anchor_lang::prelude::Pubkey;
+use blueshift_anchor_escrow::errors::EscrowError;
+use solana_rpc_client_api::{
+    client_error::{Error as ClientError, ErrorKind},
+    request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+pub type Result<T> = std::result::Result<T, SdkError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SdkError {
+    // 托管程序返回的自定义错误, 从交易日志或交易错误中解码
+    #[error("escrow program error: {0}")]
+    Program(EscrowError),
+    // 其他 RPC 或交易错误, 保留原始错误
+    #[error(transparent)]
+    Rpc(Box<ClientError>),
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("account {0} is not a valid escrow program account")]
+    InvalidAccount(Pubkey),
+    // 发送交易之前发现缺少必须已经存在的 token 账户, 例如 maker 的 token A ATA
+    #[error("token account {0} does not exist")]
+    MissingTokenAccount(Pubkey),
+    // make_for_sol 创建的托管只能用 take_for_sol 成交, SDK 暂不支持
+    #[error("escrow {0} is paid in SOL and must be taken with take_for_sol")]
+    SolEscrow(Pubkey),
+    // make_collection 创建的托管只能用 take_collection 成交, SDK 暂不支持
+    #[error("escrow {0} accepts any NFT of a collection and must be taken with take_collection")]
+    CollectionEscrow(Pubkey),
+}
+
+impl From<ClientError> for SdkError {
+    fn from(error: ClientError) -> Self {
+        // 优先使用预检模拟的日志, 其中包含 anchor 打印的错误码
+        if let ErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation),
+            ..
+        }) = error.kind()
+        {
+            if let Some(escrow_error) = simulation.logs.as_deref().and_then(decode_error) {
+                return Self::Program(escrow_error);
+            }
+        }
+
+        // 跳过预检时只能拿到交易错误中的自定义错误码
+        if let Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) =
+            error.get_transaction_error()
+        {
+            if let Some(escrow_error) = escrow_error(code) {
+                return Self::Program(escrow_error);
+            }
+        }
+
+        Self::Rpc(Box::new(error))
+    }
+}
+
+// anchor 的自定义错误码从 6000 开始, 按 EscrowError 中变体的声明顺序递增
+const ERROR_CODE_OFFSET: u32 = 6000;
+
+// 和 errors.rs 中的声明顺序一致, 新增错误时需要同步追加
+const ESCROW_ERRORS: [EscrowError; 130] = [
+    EscrowError::InvalidAmount,
+    EscrowError::InvalidMaker,
+    EscrowError::InvalidMintA,
+    EscrowError::InvalidMintB,
+    EscrowError::InvalidExpiry,
+    EscrowError::NoExpiry,
+    EscrowError::EscrowNotExpired,
+    EscrowError::MathOverflow,
+    EscrowError::UnauthorizedTaker,
+    EscrowError::IdenticalMints,
+    EscrowError::SelfTrade,
+    EscrowError::InvalidDutchAuction,
+    EscrowError::DutchAuctionReprice,
+    EscrowError::SlippageExceeded,
+    EscrowError::VaultAmountMismatch,
+    EscrowError::InvalidFee,
+    EscrowError::InvalidAdmin,
+    EscrowError::MissingFeeVault,
+    EscrowError::ProtocolPaused,
+    EscrowError::InvalidPendingAdmin,
+    EscrowError::SelfReferral,
+    EscrowError::NotOnAllowlist,
+    EscrowError::InvalidCounterOffer,
+    EscrowError::InvalidPreimage,
+    EscrowError::OfferNotStarted,
+    EscrowError::InvalidStartTime,
+    EscrowError::InvalidSeed,
+    EscrowError::EmptyVault,
+    EscrowError::InsufficientTakerBalance,
+    EscrowError::InsufficientMakerBalance,
+    EscrowError::NonTransferableMint,
+    EscrowError::FreezableMintRejected,
+    EscrowError::VaultFrozen,
+    EscrowError::NotSolEscrow,
+    EscrowError::MissingMakerAta,
+    EscrowError::MissingUnwrapAccount,
+    EscrowError::NotNativeMint,
+    EscrowError::InvalidRentPayer,
+    EscrowError::InvalidReceiveTo,
+    EscrowError::MissingTakerAccount,
+    EscrowError::DelegatedTakerAccount,
+    EscrowError::InvalidReceiptAccount,
+    EscrowError::UnauthorizedReceiptCloser,
+    EscrowError::RegistryFull,
+    EscrowError::RegistryNotEmpty,
+    EscrowError::MintNotAllowed,
+    EscrowError::MintBlocked,
+    EscrowError::EscrowFrozen,
+    EscrowError::InvalidEscrowStatus,
+    EscrowError::MintsDoNotMatch,
+    EscrowError::PricesDoNotCross,
+    EscrowError::UnmatchableEscrow,
+    EscrowError::InvalidBatchSize,
+    EscrowError::InvalidBatchAccounts,
+    EscrowError::DuplicateBatchSeed,
+    EscrowError::UnauthorizedRefund,
+    EscrowError::InvalidOrderSignature,
+    EscrowError::OrderExpired,
+    EscrowError::InsufficientDelegation,
+    EscrowError::CommitRequired,
+    EscrowError::CommitMismatch,
+    EscrowError::CommitTooEarly,
+    EscrowError::InvalidCommitDelay,
+    EscrowError::InvalidArbiter,
+    EscrowError::ArbiterRequired,
+    EscrowError::UnauthorizedRelease,
+    EscrowError::InvalidReservationWindow,
+    EscrowError::ReservationsDisabled,
+    EscrowError::EscrowReserved,
+    EscrowError::NotReserved,
+    EscrowError::InvalidBond,
+    EscrowError::ReservationExpired,
+    EscrowError::ReservationNotExpired,
+    EscrowError::InvalidPaymentMints,
+    EscrowError::InvalidBasketSize,
+    EscrowError::InvalidBasketAccounts,
+    EscrowError::DuplicateBasketMint,
+    EscrowError::InvalidRecurring,
+    EscrowError::InvalidFeeTiers,
+    EscrowError::MissingUserStats,
+    EscrowError::InvalidTreasury,
+    EscrowError::MissingTreasury,
+    EscrowError::InvalidAbandonPolicy,
+    EscrowError::EscrowNotAbandoned,
+    EscrowError::EscrowNotOrphaned,
+    EscrowError::InvalidAtaAccount,
+    EscrowError::MissingAtaPrograms,
+    EscrowError::InvalidVault,
+    EscrowError::EscrowNotLegacy,
+    EscrowError::MigrationRequired,
+    EscrowError::WrongTokenProgram,
+    EscrowError::TakerAtaBWrongMint,
+    EscrowError::EscrowExpired,
+    EscrowError::InvalidOracleConfig,
+    EscrowError::InvalidPriceFeed,
+    EscrowError::StalePrice,
+    EscrowError::PriceConfidenceTooWide,
+    EscrowError::OraclePriced,
+    EscrowError::PriceOutOfBand,
+    EscrowError::PriceBandRequiresTake,
+    EscrowError::InvalidSwapRouter,
+    EscrowError::InsufficientSwapOutput,
+    EscrowError::CollectionOffer,
+    EscrowError::NotCollectionOffer,
+    EscrowError::InvalidNftMetadata,
+    EscrowError::CollectionNotVerified,
+    EscrowError::NotAnNft,
+    EscrowError::InvalidPnftAccounts,
+    EscrowError::UnsupportedPnftEscrow,
+    EscrowError::RoyaltiesRequireTake,
+    EscrowError::InvalidRoyaltyAccounts,
+    EscrowError::RoyaltiesUnsupported,
+    EscrowError::ReservedArgsNotZero,
+    EscrowError::EmptyMakerBalance,
+    EscrowError::MissingPairIndex,
+    EscrowError::InvalidCancelReason,
+    EscrowError::CancelRetentionActive,
+    EscrowError::CpiNotAllowed,
+    EscrowError::MissingInstructionsSysvar,
+    EscrowError::InvalidTrustedRouters,
+    EscrowError::OfferPausedByMaker,
+    EscrowError::InvalidStreamWindow,
+    EscrowError::StreamingDisabled,
+    EscrowError::StreamingUnsupported,
+    EscrowError::InitialPaymentTooSmall,
+    EscrowError::StreamDeadlinePassed,
+    EscrowError::StreamActive,
+    EscrowError::InvalidOperators,
+    EscrowError::OperatorsNotAllowed,
+    EscrowError::UnauthorizedOperator,
+    EscrowError::RecurringRequiresTake,
+];
+
+// 把自定义错误码转换为 EscrowError, 不是托管程序的错误码时返回 None
+pub fn escrow_error(code: u32) -> Option<EscrowError> {
+    let index = code.checked_sub(ERROR_CODE_OFFSET)?;
+    ESCROW_ERRORS.get(index as usize).copied()
+}
+
+// 从交易日志中找到 anchor 打印的 "Error Number: 6013." 并解码
+pub fn decode_error(logs: &[String]) -> Option<EscrowError> {
+    logs.iter().find_map(|line| {
+        let (_, rest) = line.split_once("Error Number: ")?;
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        escrow_error(digits.parse().ok()?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_table_matches_anchor_codes() {
+        for (index, error) in ESCROW_ERRORS.iter().enumerate() {
+            assert_eq!(u32::from(*error), ERROR_CODE_OFFSET + index as u32);
+        }
+        assert!(escrow_error(ERROR_CODE_OFFSET - 1).is_none());
+        assert!(escrow_error(ERROR_CODE_OFFSET + ESCROW_ERRORS.len() as u32).is_none());
+    }
+
+    #[test]
+    fn error_codes_are_pinned() {
+        // 已经发布的错误码不能改变, 重新排序或在中间插入变体都会让这里失败
+        assert_eq!(u32::from(EscrowError::InvalidAmount), 6000);
+        assert_eq!(u32::from(EscrowError::SlippageExceeded), 6013);
+        assert_eq!(u32::from(EscrowError::MigrationRequired), 6089);
+        assert_eq!(u32::from(EscrowError::WrongTokenProgram), 6090);
+        assert_eq!(u32::from(EscrowError::TakerAtaBWrongMint), 6091);
+        assert_eq!(u32::from(EscrowError::EscrowExpired), 6092);
+    }
+
+    #[test]
+    fn decodes_anchor_error_log() {
+        let logs = vec![
+            "Program 22222222222222222222222222222222222222222222 invoke [1]".to_string(),
+            "Program log: Instruction: Take".to_string(),
+            "Program log: AnchorError occurred. Error Code: SlippageExceeded. Error Number: 6013. Error Message: Slippage exceeded.".to_string(),
+        ];
+
+        let error = decode_error(&logs).unwrap();
+        assert_eq!(u32::from(error), u32::from(EscrowError::SlippageExceeded));
+    }
+
+    #[test]
+    fn ignores_logs_without_escrow_errors() {
+        // anchor 框架的错误码(例如账户约束)不属于 EscrowError
+        let logs = vec![
+            "Program log: AnchorError caused by account: escrow. Error Code: ConstraintSeeds. Error Number: 2006. Error Message: A seeds constraint was violated.".to_string(),
+        ];
+
+        assert!(decode_error(&logs).is_none());
+    }
+}
@@ -0,0 +1,523 @@
+// 托管程序的 Rust 客户端: 负责查询账户, 决定需要传入或创建哪些 ATA, 添加计算预算指令并发送交易
+// 只覆盖固定价格和荷兰拍卖的 make / take / refund, 其他指令使用托管程序的 accounts 和 instruction 模块构造
+
+mod error;
+
+pub use error::{decode_error, escrow_error, Result, SdkError};
+// 托管程序的 mainnet, devnet 或 localnet feature 选中的程序 ID, 和链上程序的 declare_id 一致
+pub use blueshift_anchor_escrow::ID;
+
+use anchor_lang::{
+    prelude::Pubkey, solana_program::sysvar, system_program, AccountDeserialize, Discriminator,
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        self, get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    memo,
+    token::spl_token::native_mint,
+};
+use blueshift_anchor_escrow::{
+    accounts, instruction,
+    pda::{
+        find_config_address, find_escrow_address, find_exempt_list_address,
+        find_fee_authority_address, find_mint_allowlist_address, find_mint_blocklist_address,
+        find_pair_index_address, find_registry_address, find_stats_address,
+        find_user_stats_address,
+    },
+    state::{Config, Escrow},
+};
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+
+// Escrow 账户数据中 maker 和 tag 字段的偏移量, 和程序中定义的布局保持一致
+pub const MAKER_OFFSET: usize = Escrow::MAKER_OFFSET;
+pub const TAG_OFFSET: usize = Escrow::TAG_OFFSET;
+
+pub struct EscrowClient {
+    rpc: RpcClient,
+    compute_unit_limit: Option<u32>,
+    compute_unit_price: Option<u64>,
+}
+
+impl EscrowClient {
+    pub fn new(rpc: RpcClient) -> Self {
+        Self {
+            rpc,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+        }
+    }
+
+    // 每笔交易前面加上计算单元上限和优先费(micro-lamports / 计算单元)
+    pub fn with_compute_budget(mut self, units: u32, micro_lamports: u64) -> Self {
+        self.compute_unit_limit = Some(units);
+        self.compute_unit_price = Some(micro_lamports);
+        self
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    // 用 maker 的 amount 个 token A 创建固定价格的托管, maker 自己支付租金并接收 token B
+    pub fn make(
+        &self,
+        maker: &Keypair,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        seed: u64,
+        receive: u64,
+        amount: u64,
+    ) -> Result<Signature> {
+        let token_program_a = self.owner(mint_a)?;
+        let token_program_b = self.owner(mint_b)?;
+        let (escrow, _) = find_escrow_address(&maker.pubkey(), seed);
+
+        // native mint 直接从 maker 的 SOL 余额包装存入, 其他 mint 要求 maker 已经持有这个 ATA
+        let maker_ata_a = if *mint_a == native_mint::ID {
+            None
+        } else {
+            let ata = get_associated_token_address_with_program_id(
+                &maker.pubkey(),
+                mint_a,
+                &token_program_a,
+            );
+            self.require_account(&ata)?;
+            Some(ata)
+        };
+
+        // 收取挂单费时传入 treasury, 豁免列表存在时一起传入, 列表中的 maker 不会被收取
+        let config: Config = self.fetch(&find_config_address().0)?;
+        let (exempt_list, treasury) = if config.listing_fee_lamports == 0 {
+            (None, None)
+        } else {
+            let exempt_list = find_exempt_list_address().0;
+            (
+                self.account_exists(&exempt_list)?.then_some(exempt_list),
+                Some(config.treasury),
+            )
+        };
+
+        let make = Instruction {
+            program_id: ID,
+            accounts: accounts::Make {
+                maker: maker.pubkey(),
+                rent_payer: None,
+                escrow,
+                mint_a: *mint_a,
+                mint_b: *mint_b,
+                maker_ata_a,
+                vault: get_associated_token_address_with_program_id(
+                    &escrow,
+                    mint_a,
+                    &token_program_a,
+                ),
+                config: find_config_address().0,
+                mint_allowlist: find_mint_allowlist_address().0,
+                mint_blocklist: find_mint_blocklist_address().0,
+                exempt_list,
+                treasury,
+                stats: find_stats_address().0,
+                registry: find_registry_address(&maker.pubkey()).0,
+                associated_token_program: associated_token::ID,
+                token_program_a,
+                token_program_b,
+                system_program: system_program::ID,
+                event_authority: event_authority(),
+                program: ID,
+            }
+            .to_account_metas(None),
+            data: instruction::Make {
+                seed,
+                receive,
+                amount,
+                expiry: 0,
+                allowed_taker: Pubkey::default(),
+                taker_allowlist_root: [0; 32],
+                hashlock: [0; 32],
+                start_time: 0,
+                reject_freezable: false,
+                receive_to: Pubkey::default(),
+                refund_delegate: Pubkey::default(),
+                commit_delay_slots: 0,
+                arbiter: Pubkey::default(),
+                reservation_window: 0,
+                alt_payments: vec![],
+                recurring: false,
+                max_refills: 0,
+            }
+            .data(),
+        };
+
+        self.send(vec![make], maker)
+    }
+
+    // 成交托管中剩余的全部 token A, 支付的 token B 超过 max_receive 时失败
+    // 在查询和成交之间托管被部分成交时也会失败, 不会按新的数量成交
+    pub fn take(&self, taker: &Keypair, escrow: &Pubkey, max_receive: u64) -> Result<Signature> {
+        let state = self.fetch_escrow(escrow)?;
+        if state.is_sol_mode() {
+            return Err(SdkError::SolEscrow(*escrow));
+        }
+        if state.is_collection_offer() {
+            return Err(SdkError::CollectionEscrow(*escrow));
+        }
+        let config: Config = self.fetch(&find_config_address().0)?;
+        let token_program_a = self.owner(&state.mint_a)?;
+        let token_program_b = self.owner(&state.mint_b)?;
+        let ata_b = |owner: &Pubkey| {
+            get_associated_token_address_with_program_id(owner, &state.mint_b, &token_program_b)
+        };
+
+        // taker 用自己的 token B ATA 支付, 必须已经存在
+        let taker_ata_b = ata_b(&taker.pubkey());
+        self.require_account(&taker_ata_b)?;
+
+        let mut ixs = Vec::new();
+
+        // 收取手续费时必须传入协议的手续费 ATA, 还没有创建时由 taker 创建
+        let fee_authority = find_fee_authority_address().0;
+        let fee_vault_b = (config.fee_bps > 0).then(|| ata_b(&fee_authority));
+        if let Some(fee_vault_b) = fee_vault_b {
+            if !self.account_exists(&fee_vault_b)? {
+                ixs.push(create_associated_token_account_idempotent(
+                    &taker.pubkey(),
+                    &fee_authority,
+                    &state.mint_b,
+                    &token_program_b,
+                ));
+            }
+        }
+
+        // token B 是 wSOL 时选择通过 escrow 的临时账户解包给 receive_to, 否则转入 receive_to 的 ATA
+        // 解包时临时账户总是需要创建, 租金在成交后还给 taker
+        // taker 和 maker 的其他 ATA 不存在时由程序创建, 不需要提前处理
+        let (maker_ata_b, unwrap_ata_b) = if state.mint_b == native_mint::ID {
+            (None, Some(ata_b(escrow)))
+        } else {
+            (Some(ata_b(&state.receive_to)), None)
+        };
+        let ata_a = |owner: &Pubkey| {
+            get_associated_token_address_with_program_id(owner, &state.mint_a, &token_program_a)
+        };
+
+        // 只在启用手续费档位时传入 taker 的统计账户, 第一次传入时由 taker 支付租金
+        let taker_stats =
+            (config.fee_tier_count > 0).then(|| find_user_stats_address(&taker.pubkey()).0);
+
+        // 接收 token 的 ATA 都已经存在, 不传 taker_stats 也不解包时, 不需要传 ATA 程序和系统程序
+        // 解包时 taker 还要通过系统程序把 SOL 转给 receive_to
+        let mut needs_programs = taker_stats.is_some() || unwrap_ata_b.is_some();
+        for ata in [ata_a(&taker.pubkey()), ata_a(&state.maker)]
+            .iter()
+            .chain(&maker_ata_b)
+            .chain(&unwrap_ata_b)
+        {
+            needs_programs |= !self.account_exists(ata)?;
+        }
+
+        ixs.push(Instruction {
+            program_id: ID,
+            accounts: accounts::Take {
+                taker: taker.pubkey(),
+                payer: taker.pubkey(),
+                maker: state.maker,
+                rent_payer: state.rent_payer,
+                receive_to: state.receive_to,
+                escrow: *escrow,
+                mint_a: state.mint_a,
+                mint_b: state.mint_b,
+                vault: state.vault,
+                taker_ata_a: Some(ata_a(&taker.pubkey())),
+                taker_token_a: None,
+                maker_ata_a: ata_a(&state.maker),
+                taker_ata_b,
+                maker_ata_b,
+                unwrap_ata_b,
+                config: find_config_address().0,
+                mint_blocklist: find_mint_blocklist_address().0,
+                stats: find_stats_address().0,
+                taker_stats,
+                maker_stats: None,
+                fee_authority,
+                fee_vault_b,
+                referrer_ata_b: None,
+                receipt: None,
+                registry: find_registry_address(&state.maker).0,
+                pair_index: state
+                    .pair_indexed
+                    .then(|| find_pair_index_address(&state.mint_a, &state.mint_b).0),
+                price_feed: state.requires_price_feed().then_some(state.price_feed),
+                instructions_sysvar: state.direct_only.then_some(sysvar::instructions::ID),
+                associated_token_program: needs_programs.then_some(associated_token::ID),
+                token_program_a,
+                token_program_b,
+                memo_program: memo::ID,
+                system_program: needs_programs.then_some(system_program::ID),
+                event_authority: event_authority(),
+                program: ID,
+            }
+            .to_account_metas(None),
+            data: instruction::Take {
+                max_receive,
+                expected_amount_a: state.amount,
+                proof: vec![],
+                preimage: vec![],
+                write_receipt: false,
+            }
+            .data(),
+        });
+
+        self.send(ixs, taker)
+    }
+
+    // 退还托管, token A 回到 maker 的 ATA, 租金还给托管记录的 rent_payer
+    // authority 是 maker 本人或托管记录的 refund_delegate
+    pub fn refund(&self, authority: &Keypair, escrow: &Pubkey) -> Result<Signature> {
+        let state = self.fetch_escrow(escrow)?;
+        let token_program = self.owner(&state.mint_a)?;
+        let ata_a = |owner: &Pubkey| {
+            get_associated_token_address_with_program_id(owner, &state.mint_a, &token_program)
+        };
+
+        let refund = Instruction {
+            program_id: ID,
+            accounts: accounts::Refund {
+                authority: authority.pubkey(),
+                maker: state.maker,
+                rent_payer: state.rent_payer,
+                escrow: *escrow,
+                mint_a: state.mint_a,
+                vault: state.vault,
+                maker_ata_a: ata_a(&state.maker),
+                stats: find_stats_address().0,
+                registry: find_registry_address(&state.maker).0,
+                pair_index: state
+                    .pair_indexed
+                    .then(|| find_pair_index_address(&state.mint_a, &state.mint_b).0),
+                associated_token_program: associated_token::ID,
+                token_program,
+                memo_program: memo::ID,
+                system_program: system_program::ID,
+                event_authority: event_authority(),
+                program: ID,
+            }
+            .to_account_metas(None),
+            data: instruction::Refund { force: false }.data(),
+        };
+
+        self.send(vec![refund], authority)
+    }
+
+    pub fn fetch_escrow(&self, escrow: &Pubkey) -> Result<Escrow> {
+        self.fetch(escrow)
+    }
+
+    // 查询 maker 创建的所有托管, 由 RPC 节点按 discriminator 和 maker 字段过滤
+    pub fn escrows_by_maker(&self, maker: &Pubkey) -> Result<Vec<(Pubkey, Escrow)>> {
+        self.escrows_matching(Memcmp::new_base58_encoded(MAKER_OFFSET, maker.as_ref()))
+    }
+
+    // 查询 make_v2 设置了 tag 的所有托管, 版本 7 之前的托管需要先迁移才会被找到
+    pub fn escrows_by_tag(&self, tag: u8) -> Result<Vec<(Pubkey, Escrow)>> {
+        self.escrows_matching(Memcmp::new_base58_encoded(TAG_OFFSET, &[tag]))
+    }
+
+    fn escrows_matching(&self, filter: Memcmp) -> Result<Vec<(Pubkey, Escrow)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, Escrow::DISCRIMINATOR)),
+                RpcFilterType::Memcmp(filter),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+
+        self.rpc
+            .get_program_accounts_with_config(&ID, config)?
+            .into_iter()
+            .map(|(address, account)| {
+                Escrow::try_deserialize(&mut account.data.as_slice())
+                    .map(|escrow| (address, escrow))
+                    .map_err(|_| SdkError::InvalidAccount(address))
+            })
+            .collect()
+    }
+
+    fn fetch<T: AccountDeserialize>(&self, address: &Pubkey) -> Result<T> {
+        let data = self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .ok_or(SdkError::AccountNotFound(*address))?
+            .data;
+
+        T::try_deserialize(&mut data.as_slice()).map_err(|_| SdkError::InvalidAccount(*address))
+    }
+
+    fn account_exists(&self, address: &Pubkey) -> Result<bool> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .is_some())
+    }
+
+    fn require_account(&self, address: &Pubkey) -> Result<()> {
+        if self.account_exists(address)? {
+            Ok(())
+        } else {
+            Err(SdkError::MissingTokenAccount(*address))
+        }
+    }
+
+    // mint 账户的 owner 就是管理它的 token 程序(SPL Token 或 Token-2022)
+    fn owner(&self, address: &Pubkey) -> Result<Pubkey> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .ok_or(SdkError::AccountNotFound(*address))?
+            .owner)
+    }
+
+    fn send(&self, ixs: Vec<Instruction>, payer: &Keypair) -> Result<Signature> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(
+            &self.with_budget(ixs),
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        );
+
+        Ok(self.rpc.send_and_confirm_transaction(&tx)?)
+    }
+
+    // 计算预算指令必须放在交易的最前面
+    fn with_budget(&self, ixs: Vec<Instruction>) -> Vec<Instruction> {
+        let limit = self
+            .compute_unit_limit
+            .map(ComputeBudgetInstruction::set_compute_unit_limit);
+        let price = self
+            .compute_unit_price
+            .map(ComputeBudgetInstruction::set_compute_unit_price);
+
+        limit.into_iter().chain(price).chain(ixs).collect()
+    }
+}
+
+// #[event_cpi] 记录事件使用的 PDA
+fn event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &ID).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+    use blueshift_anchor_escrow::state::{EscrowStatus, PaymentMint};
+
+    fn client() -> EscrowClient {
+        EscrowClient::new(RpcClient::new("http://127.0.0.1:8899".to_string()))
+    }
+
+    #[test]
+    fn filter_offsets_match_account_layout() {
+        let maker = Pubkey::new_unique();
+        let escrow = Escrow {
+            version: Escrow::VERSION,
+            seed: u64::MAX,
+            maker,
+            rent_payer: Pubkey::new_unique(),
+            receive_to: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            receive: 1,
+            deposited: 2,
+            amount: 2,
+            expiry: 0,
+            start_time: 0,
+            created_at: 0,
+            commit_delay_slots: 0,
+            reservation_window: 0,
+            allowed_taker: Pubkey::default(),
+            refund_delegate: Pubkey::default(),
+            arbiter: Pubkey::default(),
+            taker_allowlist_root: [0; 32],
+            hashlock: [0; 32],
+            decay_start: 0,
+            decay_end: 0,
+            end_receive: 0,
+            alt_payments: [PaymentMint::default(); 3],
+            locked_taker: Pubkey::default(),
+            locked_at: 0,
+            reserved_taker: Pubkey::default(),
+            reservation_expiry: 0,
+            bond: 0,
+            recurring: false,
+            max_refills: 0,
+            status: EscrowStatus::Open,
+            status_before_freeze: EscrowStatus::Open,
+            bump: 255,
+            vault: Pubkey::new_unique(),
+            price_feed: Pubkey::default(),
+            quote_exponent: 0,
+            max_staleness: 0,
+            max_deviation_bps: 0,
+            collection: Pubkey::default(),
+            enforce_royalties: false,
+            tag: 7,
+            pair_indexed: false,
+            cancel_reason: 0,
+            cancelled_slot: 0,
+            direct_only: false,
+            maker_paused: false,
+            stream_window: 0,
+            stream_receive: 0,
+            paid_b: 0,
+            stream_deadline: 0,
+            allow_operators: false,
+        };
+
+        let mut data = Vec::new();
+        escrow.try_serialize(&mut data).unwrap();
+        assert_eq!(&data[..Escrow::DISCRIMINATOR.len()], Escrow::DISCRIMINATOR);
+        assert_eq!(&data[MAKER_OFFSET..MAKER_OFFSET + 32], maker.as_ref());
+        assert_eq!(data[TAG_OFFSET], 7);
+    }
+
+    #[test]
+    fn compute_budget_instructions_come_first() {
+        let ix = Instruction::new_with_bytes(ID, &[0], vec![]);
+
+        assert_eq!(client().with_budget(vec![ix.clone()]), vec![ix.clone()]);
+
+        let ixs = client()
+            .with_compute_budget(200_000, 1_000)
+            .with_budget(vec![ix.clone()]);
+        assert_eq!(
+            ixs,
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                ix,
+            ]
+        );
+    }
+}
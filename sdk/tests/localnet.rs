@@ -0,0 +1,241 @@
+// 需要本地验证节点并在声明的程序 ID 上部署托管程序, 通过 `cargo test -p escrow-sdk --features localnet` 运行, 例如:
+// solana-test-validator --reset --bpf-program Ew2CvRfUbSSUfe5DurrYJm1xXCxhKskHoB7GkL1Gcggj target/deploy/blueshift_anchor_escrow.so
+#![cfg(feature = "localnet")]
+
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{program_pack::Pack, system_instruction},
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address, spl_associated_token_account::instruction as ata_instruction,
+    },
+    token::spl_token,
+};
+use blueshift_anchor_escrow::{errors::EscrowError, pda};
+use escrow_sdk::{EscrowClient, SdkError};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+const RPC_URL: &str = "http://127.0.0.1:8899";
+
+fn send(rpc: &RpcClient, ixs: &[Instruction], payer: &Keypair, signers: &[&Keypair]) {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&payer.pubkey()),
+        &all_signers,
+        rpc.get_latest_blockhash().unwrap(),
+    );
+    rpc.send_and_confirm_transaction(&tx).unwrap();
+}
+
+fn funded_keypair(rpc: &RpcClient) -> Keypair {
+    let keypair = Keypair::new();
+    let signature = rpc
+        .request_airdrop(&keypair.pubkey(), 10 * LAMPORTS_PER_SOL)
+        .unwrap();
+    while !rpc.confirm_transaction(&signature).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    keypair
+}
+
+// 创建 6 位小数的 SPL Token mint, 铸币权限属于 authority
+fn create_mint(rpc: &RpcClient, authority: &Keypair) -> Pubkey {
+    let mint = Keypair::new();
+    let ixs = [
+        system_instruction::create_account(
+            &authority.pubkey(),
+            &mint.pubkey(),
+            rpc.get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+                .unwrap(),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &authority.pubkey(),
+            None,
+            6,
+        )
+        .unwrap(),
+    ];
+    send(rpc, &ixs, authority, &[&mint]);
+    mint.pubkey()
+}
+
+// 为 owner 创建 ATA 并铸造 amount 个 token
+fn fund_ata(rpc: &RpcClient, authority: &Keypair, mint: &Pubkey, owner: &Pubkey, amount: u64) {
+    let ixs = [
+        ata_instruction::create_associated_token_account(
+            &authority.pubkey(),
+            owner,
+            mint,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::mint_to(
+            &spl_token::ID,
+            mint,
+            &get_associated_token_address(owner, mint),
+            &authority.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap(),
+    ];
+    send(rpc, &ixs, authority, &[]);
+}
+
+fn token_balance(rpc: &RpcClient, owner: &Pubkey, mint: &Pubkey) -> u64 {
+    let account = rpc
+        .get_account(&get_associated_token_address(owner, mint))
+        .unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+// 协议配置是全局唯一的, 节点上还没有时以 admin 初始化, 手续费为 0
+fn ensure_config(rpc: &RpcClient, admin: &Keypair) {
+    let config = pda::find_config_address().0;
+    if rpc.get_account(&config).is_ok() {
+        return;
+    }
+
+    let initialize_config = Instruction {
+        program_id: blueshift_anchor_escrow::ID,
+        accounts: blueshift_anchor_escrow::accounts::InitializeConfig {
+            admin: admin.pubkey(),
+            config,
+            fee_authority: pda::find_fee_authority_address().0,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: blueshift_anchor_escrow::instruction::InitializeConfig { fee_bps: 0 }.data(),
+    };
+    send(rpc, &[initialize_config], admin, &[]);
+}
+
+struct Setup {
+    client: EscrowClient,
+    maker: Keypair,
+    taker: Keypair,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+}
+
+fn setup() -> Setup {
+    let rpc = RpcClient::new_with_commitment(RPC_URL.to_string(), CommitmentConfig::confirmed());
+    let maker = funded_keypair(&rpc);
+    let taker = funded_keypair(&rpc);
+    ensure_config(&rpc, &maker);
+
+    let mint_a = create_mint(&rpc, &maker);
+    let mint_b = create_mint(&rpc, &maker);
+    fund_ata(&rpc, &maker, &mint_a, &maker.pubkey(), 1_000);
+    fund_ata(&rpc, &maker, &mint_b, &taker.pubkey(), 1_000);
+
+    Setup {
+        client: EscrowClient::new(rpc).with_compute_budget(400_000, 1),
+        maker,
+        taker,
+        mint_a,
+        mint_b,
+    }
+}
+
+#[test]
+fn make_then_take_through_the_sdk() {
+    let Setup {
+        client,
+        maker,
+        taker,
+        mint_a,
+        mint_b,
+    } = setup();
+    let seed = 42;
+
+    client
+        .make(&maker, &mint_a, &mint_b, seed, 500, 1_000)
+        .unwrap();
+
+    let (escrow, _) = pda::find_escrow_address(&maker.pubkey(), seed);
+    let state = client.fetch_escrow(&escrow).unwrap();
+    assert_eq!(state.maker, maker.pubkey());
+    assert_eq!(state.amount, 1_000);
+    assert_eq!(state.receive, 500);
+
+    let by_maker = client.escrows_by_maker(&maker.pubkey()).unwrap();
+    assert_eq!(by_maker.len(), 1);
+    assert_eq!(by_maker[0].0, escrow);
+    assert!(client.escrows_by_maker(&taker.pubkey()).unwrap().is_empty());
+
+    // max_receive 低于价格时程序返回的错误解码为 SlippageExceeded, 托管保持不变
+    match client.take(&taker, &escrow, 499) {
+        Err(SdkError::Program(error)) => {
+            assert_eq!(u32::from(error), u32::from(EscrowError::SlippageExceeded))
+        }
+        other => panic!("expected SlippageExceeded, got {other:?}"),
+    }
+    assert_eq!(client.fetch_escrow(&escrow).unwrap().amount, 1_000);
+
+    client.take(&taker, &escrow, 500).unwrap();
+
+    assert!(matches!(
+        client.fetch_escrow(&escrow),
+        Err(SdkError::AccountNotFound(address)) if address == escrow
+    ));
+    assert_eq!(token_balance(client.rpc(), &taker.pubkey(), &mint_a), 1_000);
+    assert_eq!(token_balance(client.rpc(), &taker.pubkey(), &mint_b), 500);
+    assert_eq!(token_balance(client.rpc(), &maker.pubkey(), &mint_b), 500);
+}
+
+#[test]
+fn make_then_refund_through_the_sdk() {
+    let Setup {
+        client,
+        maker,
+        mint_a,
+        mint_b,
+        ..
+    } = setup();
+
+    client
+        .make(&maker, &mint_a, &mint_b, 7, 500, 1_000)
+        .unwrap();
+    let (escrow, _) = pda::find_escrow_address(&maker.pubkey(), 7);
+    assert_eq!(token_balance(client.rpc(), &maker.pubkey(), &mint_a), 0);
+
+    client.refund(&maker, &escrow).unwrap();
+
+    assert!(client.escrows_by_maker(&maker.pubkey()).unwrap().is_empty());
+    assert_eq!(token_balance(client.rpc(), &maker.pubkey(), &mint_a), 1_000);
+}
+
+#[test]
+fn make_requires_an_existing_token_a_account() {
+    let Setup {
+        client,
+        taker,
+        mint_a,
+        mint_b,
+        ..
+    } = setup();
+
+    // taker 没有 token A 的 ATA, 在发送交易之前就会被拒绝
+    assert!(matches!(
+        client.make(&taker, &mint_a, &mint_b, 1, 500, 1_000),
+        Err(SdkError::MissingTokenAccount(address))
+            if address == get_associated_token_address(&taker.pubkey(), &mint_a)
+    ));
+}
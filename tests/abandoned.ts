@@ -0,0 +1,123 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_2022_PROGRAM_ID } from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  provider,
+  requireMemos,
+  sleep,
+  tokenBalance,
+} from './utils';
+
+// cranker 分得 30% 的租金
+const CRANKER_BPS = 3_000;
+
+// 设置放弃的时长和 cranker 的分成, 使用 provider 钱包作为 admin
+const setAbandonPolicy = (seconds: number, crankerBps = CRANKER_BPS) =>
+  program.methods
+    .setAbandonPolicy(new BN(seconds), crankerBps)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+
+describe('sweep abandoned', () => {
+  let fx: Fixture;
+  let cranker: Keypair;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    cranker = await fundedKeypair();
+  });
+
+  afterEach(async () => {
+    await setAbandonPolicy(0, 0);
+  });
+
+  const sweep = (
+    escrow: { escrow: PublicKey; vault: PublicKey },
+    rentPayer = fx.maker.publicKey
+  ) =>
+    program.methods
+      .sweepAbandoned()
+      .accountsPartial({
+        refund: {
+          authority: cranker.publicKey,
+          maker: fx.maker.publicKey,
+          rentPayer,
+          escrow: escrow.escrow,
+          mintA: fx.mintA,
+          vault: escrow.vault,
+          makerAtaA: fx.makerAtaA,
+          pairIndex: null,
+          tokenProgram: fx.tokenProgramA,
+        },
+      })
+      .signers([cranker])
+      .rpc();
+
+  it('refunds the maker and splits the rent with the cranker', async () => {
+    // 由 provider 钱包支付交易费, 余额的变化只有租金
+    const rentPayer = await fundedKeypair();
+    await setAbandonPolicy(1);
+    const escrow = await makeEscrow(fx, { amount: 1_000, rentPayer });
+    await sleep(3_000);
+
+    const reclaimed =
+      (await connection.getBalance(escrow.escrow)) +
+      (await connection.getBalance(escrow.vault));
+    const crankerBefore = await connection.getBalance(cranker.publicKey);
+    const payerBefore = await connection.getBalance(rentPayer.publicKey);
+    await sweep(escrow, rentPayer.publicKey);
+
+    const share = Math.floor((reclaimed * CRANKER_BPS) / 10_000);
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+    expect(await connection.getAccountInfo(escrow.escrow)).to.be.null;
+    expect(await connection.getAccountInfo(escrow.vault)).to.be.null;
+    expect(await connection.getBalance(cranker.publicKey)).to.equal(
+      crankerBefore + share
+    );
+    expect(await connection.getBalance(rentPayer.publicKey)).to.equal(
+      payerBefore + reclaimed - share
+    );
+  });
+
+  it('sweeps into a maker ATA that requires memos', async () => {
+    fx = await createFixture(
+      1_000_000,
+      1_000_000,
+      TOKEN_2022_PROGRAM_ID,
+      TOKEN_2022_PROGRAM_ID
+    );
+    await setAbandonPolicy(1);
+    const escrow = await makeEscrow(fx, { amount: 1_000 });
+    await requireMemos(fx.maker, fx.mintA);
+    await sleep(3_000);
+
+    await sweep(escrow);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+    expect(await connection.getAccountInfo(escrow.escrow)).to.be.null;
+  });
+
+  it('rejects a sweep before the period or while disabled', async () => {
+    await setAbandonPolicy(600);
+    const escrow = await makeEscrow(fx);
+    await expectError(sweep(escrow), 'EscrowNotAbandoned');
+
+    await setAbandonPolicy(0);
+    await expectError(sweep(escrow), 'EscrowNotAbandoned');
+  });
+
+  it('rejects a cranker share above 100%', async () => {
+    await expectError(setAbandonPolicy(1, 10_001), 'InvalidAbandonPolicy');
+    await expectError(setAbandonPolicy(-1), 'InvalidAbandonPolicy');
+  });
+});
@@ -0,0 +1,106 @@
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  configPda,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  program,
+  provider,
+  setFee,
+} from './utils';
+
+describe('admin transfer', () => {
+  let newAdmin: Keypair;
+
+  const proposeAdmin = (pendingAdmin: PublicKey, admin?: Keypair) =>
+    program.methods
+      .proposeAdmin(pendingAdmin)
+      .accountsPartial({ admin: admin?.publicKey ?? provider.wallet.publicKey })
+      .signers(admin ? [admin] : [])
+      .rpc();
+
+  const acceptAdmin = (pendingAdmin?: Keypair) =>
+    program.methods
+      .acceptAdmin()
+      .accountsPartial({
+        pendingAdmin: pendingAdmin?.publicKey ?? provider.wallet.publicKey,
+      })
+      .signers(pendingAdmin ? [pendingAdmin] : [])
+      .rpc();
+
+  const fetchConfig = () => program.account.config.fetch(configPda);
+
+  before(async () => {
+    await ensureConfig();
+  });
+
+  beforeEach(async () => {
+    newAdmin = await fundedKeypair();
+  });
+
+  afterEach(async () => {
+    // 其他测试依赖 provider 钱包是 admin, 如果转移成功则转回去
+    const config = await fetchConfig();
+    if (config.admin.equals(newAdmin.publicKey)) {
+      await proposeAdmin(provider.wallet.publicKey, newAdmin);
+      await acceptAdmin();
+    } else if (!config.pendingAdmin.equals(PublicKey.default)) {
+      await proposeAdmin(PublicKey.default);
+    }
+  });
+
+  it('keeps the old admin in power until acceptance', async () => {
+    await proposeAdmin(newAdmin.publicKey);
+
+    const config = await fetchConfig();
+    expect(config.pendingAdmin.toBase58()).to.equal(
+      newAdmin.publicKey.toBase58()
+    );
+    expect(config.admin.toBase58()).to.equal(
+      provider.wallet.publicKey.toBase58()
+    );
+
+    // 旧 admin 仍然可以修改配置, 新 admin 还不行
+    await setFee(0);
+    await expectError(
+      program.methods
+        .setFee(0)
+        .accountsPartial({ admin: newAdmin.publicKey })
+        .signers([newAdmin])
+        .rpc(),
+      'InvalidAdmin'
+    );
+  });
+
+  it('swaps the admin and clears the pending slot on accept', async () => {
+    await proposeAdmin(newAdmin.publicKey);
+
+    await acceptAdmin(newAdmin);
+
+    const config = await fetchConfig();
+    expect(config.admin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+    expect(config.pendingAdmin.equals(PublicKey.default)).to.be.true;
+
+    // 旧 admin 失去权限
+    await expectError(setFee(0), 'InvalidAdmin');
+  });
+
+  it('rejects acceptance by a third party', async () => {
+    await proposeAdmin(newAdmin.publicKey);
+    const other = await fundedKeypair();
+
+    await expectError(acceptAdmin(other), 'InvalidPendingAdmin');
+  });
+
+  it('rejects acceptance when nothing is pending', async () => {
+    await expectError(acceptAdmin(newAdmin), 'InvalidPendingAdmin');
+  });
+
+  it('rejects a proposal from a non-admin', async () => {
+    await expectError(
+      proposeAdmin(newAdmin.publicKey, newAdmin),
+      'InvalidAdmin'
+    );
+  });
+});
@@ -0,0 +1,112 @@
+import { getOrCreateAssociatedTokenAccount, mintTo } from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  buildAllowlist,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('taker allowlist', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  // 给另一个 taker 准备 token B
+  async function fundTaker(taker: Keypair) {
+    const takerAtaB = await getOrCreateAssociatedTokenAccount(
+      connection,
+      taker,
+      fx.mintB,
+      taker.publicKey
+    );
+    await mintTo(
+      connection,
+      fx.taker,
+      fx.mintB,
+      takerAtaB.address,
+      fx.taker,
+      1_000_000
+    );
+  }
+
+  it('stores the root on make', async () => {
+    const { root } = buildAllowlist([fx.taker.publicKey]);
+
+    const { escrow } = await makeEscrow(fx, { takerAllowlistRoot: root });
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.takerAllowlistRoot).to.deep.equal(root);
+  });
+
+  it('lets a listed taker fill with a valid proof', async () => {
+    // 奇数个叶子, 最后一个节点会被直接提升
+    const others = Array.from({ length: 4 }, () => Keypair.generate());
+    const takers = [...others.map((k) => k.publicKey), fx.taker.publicKey];
+    const allowlist = buildAllowlist(takers);
+    const { escrow } = await makeEscrow(fx, {
+      receive: 500,
+      takerAllowlistRoot: allowlist.root,
+    });
+
+    await takeEscrow(fx, escrow, fx.taker, {
+      proof: allowlist.proofFor(fx.taker.publicKey),
+    });
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      500n
+    );
+  });
+
+  it('rejects an unlisted taker even with a listed proof', async () => {
+    const outsider = await fundedKeypair();
+    await fundTaker(outsider);
+    const allowlist = buildAllowlist([
+      fx.taker.publicKey,
+      Keypair.generate().publicKey,
+      Keypair.generate().publicKey,
+    ]);
+    const { escrow } = await makeEscrow(fx, {
+      takerAllowlistRoot: allowlist.root,
+    });
+
+    await expectError(
+      takeEscrow(fx, escrow, outsider, {
+        proof: allowlist.proofFor(fx.taker.publicKey),
+      }),
+      'NotOnAllowlist'
+    );
+    expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+  });
+
+  it('rejects a listed taker without a proof', async () => {
+    const allowlist = buildAllowlist([
+      fx.taker.publicKey,
+      Keypair.generate().publicKey,
+    ]);
+    const { escrow } = await makeEscrow(fx, {
+      takerAllowlistRoot: allowlist.root,
+    });
+
+    await expectError(takeEscrow(fx, escrow), 'NotOnAllowlist');
+  });
+
+  it('ignores the proof when no root is set', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+});
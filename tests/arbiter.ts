@@ -0,0 +1,206 @@
+import { BN } from '@coral-xyz/anchor';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('arbiter', () => {
+  let fx: Fixture;
+  let arbiter: Keypair;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    arbiter = await fundedKeypair();
+  });
+
+  const makeArbitrated = () =>
+    makeEscrow(fx, {
+      amount: 1_000,
+      receive: 800,
+      arbiter: arbiter.publicKey,
+    });
+
+  const lockTake = (
+    escrow: PublicKey,
+    taker = fx.taker,
+    takerAtaB = ata(fx.mintB, taker.publicKey, fx.tokenProgramB)
+  ) =>
+    program.methods
+      .lockTake(new BN(800), [])
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        takerAtaB,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .signers([taker])
+      .rpc();
+
+  // 两个方向的 ATA 都传入, 程序只使用对应方向的两个
+  const release = (
+    toTaker: boolean,
+    escrow: PublicKey,
+    authority: Keypair,
+    coSigner: Keypair | null = null
+  ) =>
+    (toTaker
+      ? program.methods.releaseToTaker()
+      : program.methods.releaseToMaker()
+    )
+      .accountsPartial({
+        authority: authority.publicKey,
+        coSigner: coSigner?.publicKey ?? null,
+        maker: fx.maker.publicKey,
+        taker: fx.taker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        feeVaultB: null,
+        takerStats: null,
+        makerStats: null,
+        pairIndex: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .signers(coSigner ? [authority, coSigner] : [authority])
+      .rpc();
+
+  const vaultB = (escrow: PublicKey) => ata(fx.mintB, escrow, fx.tokenProgramB);
+
+  it('locks token B instead of paying the maker', async () => {
+    const { escrow, vault } = await makeArbitrated();
+    const takerB = await tokenBalance(fx.takerAtaB);
+
+    await expectError(takeEscrow(fx, escrow), 'ArbiterRequired');
+    await lockTake(escrow);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.status).to.deep.equal({ locked: {} });
+    expect(state.lockedTaker.toBase58()).to.equal(
+      fx.taker.publicKey.toBase58()
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+    expect(await tokenBalance(vaultB(escrow))).to.equal(800n);
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(takerB - 800n);
+
+    // 锁定之后不能再锁定, maker 也不能直接退还
+    await expectError(lockTake(escrow), 'InvalidEscrowStatus');
+    await expectError(refundEscrow(fx, escrow), 'InvalidEscrowStatus');
+  });
+
+  it('lets the arbiter complete the trade', async () => {
+    const { escrow, vault } = await makeArbitrated();
+    await lockTake(escrow);
+
+    await release(true, escrow, arbiter);
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    // 仲裁人没有收到任何 token
+    for (const mint of [fx.mintA, fx.mintB]) {
+      const arbiterAta = ata(mint, arbiter.publicKey);
+      expect(await connection.getAccountInfo(arbiterAta)).to.be.null;
+    }
+    for (const account of [escrow, vault, vaultB(escrow)]) {
+      expect(await connection.getAccountInfo(account)).to.be.null;
+    }
+  });
+
+  it('lets the arbiter return both legs', async () => {
+    const { escrow } = await makeArbitrated();
+    const makerA = await tokenBalance(fx.makerAtaA);
+    const takerB = await tokenBalance(fx.takerAtaB);
+    await lockTake(escrow);
+
+    await release(false, escrow, arbiter);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(makerA + 1_000n);
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(takerB);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vaultB(escrow))).to.be.null;
+  });
+
+  it('lets the maker and taker settle together', async () => {
+    const { escrow } = await makeArbitrated();
+    await lockTake(escrow);
+
+    await release(true, escrow, fx.maker, fx.taker);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('rejects a single party or an outsider before the timeout', async () => {
+    const { escrow } = await makeArbitrated();
+    await lockTake(escrow);
+    const outsider = await fundedKeypair();
+
+    for (const toTaker of [true, false]) {
+      await expectError(
+        release(toTaker, escrow, fx.maker),
+        'UnauthorizedRelease'
+      );
+      await expectError(
+        release(toTaker, escrow, fx.taker),
+        'UnauthorizedRelease'
+      );
+      await expectError(
+        release(toTaker, escrow, outsider, fx.maker),
+        'UnauthorizedRelease'
+      );
+    }
+    expect(await tokenBalance(vaultB(escrow))).to.equal(800n);
+  });
+
+  it('keeps refund available until the escrow is locked', async () => {
+    const { escrow } = await makeArbitrated();
+
+    await refundEscrow(fx, escrow);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('rejects an arbiter who is a party to the trade', async () => {
+    await expectError(
+      makeEscrow(fx, { amount: 1_000, arbiter: fx.maker.publicKey }),
+      'InvalidArbiter'
+    );
+    await expectError(
+      makeEscrow(fx, {
+        amount: 1_000,
+        arbiter: arbiter.publicKey,
+        receiveTo: arbiter.publicKey,
+      }),
+      'InvalidArbiter'
+    );
+
+    // 仲裁人不能自己锁定再决定结算方向, escrow 约束先于 token 账户检查
+    const { escrow } = await makeArbitrated();
+    await expectError(
+      lockTake(escrow, arbiter, fx.takerAtaB),
+      'InvalidArbiter'
+    );
+  });
+});
@@ -0,0 +1,201 @@
+import { BN } from '@coral-xyz/anchor';
+import {
+  createMint,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import { AccountMeta, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  program,
+  randomSeed,
+  tokenBalance,
+} from './utils';
+
+// 和 state.rs 中的 MAX_BASKET_LEGS 一致
+const MAX_BASKET_LEGS = 4;
+
+describe('basket', () => {
+  let fx: Fixture;
+  let mints: PublicKey[];
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+
+    mints = [];
+    for (let i = 0; i < MAX_BASKET_LEGS; i++) {
+      const mint = await createMint(
+        connection,
+        fx.maker,
+        fx.maker.publicKey,
+        null,
+        6
+      );
+      const account = await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.maker,
+        mint,
+        fx.maker.publicKey
+      );
+      await mintTo(
+        connection,
+        fx.maker,
+        mint,
+        account.address,
+        fx.maker,
+        1_000
+      );
+      mints.push(mint);
+    }
+  });
+
+  const findBasket = (seed: BN) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('basket'),
+        fx.maker.publicKey.toBuffer(),
+        seed.toArrayLike(Buffer, 'le', 8),
+      ],
+      program.programId
+    )[0];
+
+  // 每种 token 依次传入 mint, 以及 owner 的两个 token 账户
+  const legAccounts = (owners: PublicKey[], legMints = mints) =>
+    legMints.flatMap((mint): AccountMeta[] => [
+      { pubkey: mint, isSigner: false, isWritable: false },
+      ...owners.map((owner) => ({
+        pubkey: ata(mint, owner),
+        isSigner: false,
+        isWritable: true,
+      })),
+    ]);
+
+  const makeBasket = async (amounts: number[], receive = 800) => {
+    const seed = randomSeed();
+    const basket = findBasket(seed);
+    await program.methods
+      .makeBasket(
+        seed,
+        new BN(receive),
+        amounts.map((amount) => new BN(amount))
+      )
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        basket,
+        mintB: fx.mintB,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .remainingAccounts(
+        legAccounts(
+          [fx.maker.publicKey, basket],
+          mints.slice(0, amounts.length)
+        )
+      )
+      .signers([fx.maker])
+      .rpc();
+    return basket;
+  };
+
+  const takeBasket = (basket: PublicKey, legMints = mints) =>
+    program.methods
+      .takeBasket(U64_MAX)
+      .accountsPartial({
+        taker: fx.taker.publicKey,
+        maker: fx.maker.publicKey,
+        basket,
+        mintB: fx.mintB,
+        takerAtaB: fx.takerAtaB,
+        feeVaultB: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .remainingAccounts(legAccounts([basket, fx.taker.publicKey], legMints))
+      .signers([fx.taker])
+      .rpc();
+
+  const refundBasket = (basket: PublicKey) =>
+    program.methods
+      .refundBasket()
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        basket,
+        tokenProgram: fx.tokenProgramA,
+      })
+      .remainingAccounts(legAccounts([basket, fx.maker.publicKey]))
+      .signers([fx.maker])
+      .rpc();
+
+  it('sweeps every leg to the taker at the cap', async () => {
+    const amounts = [100, 200, 300, 400];
+    const basket = await makeBasket(amounts);
+
+    const state = await program.account.escrowBasket.fetch(basket);
+    expect(state.legCount).to.equal(MAX_BASKET_LEGS);
+    expect(state.legs.map((leg) => leg.amount.toNumber())).to.deep.equal(
+      amounts
+    );
+
+    await expectError(
+      takeBasket(basket, mints.slice(1)),
+      'InvalidBasketAccounts'
+    );
+    await takeBasket(basket);
+
+    for (const [i, mint] of mints.entries()) {
+      expect(await tokenBalance(ata(mint, fx.taker.publicKey))).to.equal(
+        BigInt(amounts[i])
+      );
+      expect(await connection.getAccountInfo(ata(mint, basket))).to.be.null;
+    }
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    expect(await connection.getAccountInfo(basket)).to.be.null;
+  });
+
+  it('refunds every leg and closes every vault', async () => {
+    const basket = await makeBasket([100, 200, 300, 400]);
+
+    await refundBasket(basket);
+
+    for (const mint of mints) {
+      expect(await tokenBalance(ata(mint, fx.maker.publicKey))).to.equal(
+        1_000n
+      );
+      expect(await connection.getAccountInfo(ata(mint, basket))).to.be.null;
+    }
+    expect(await connection.getAccountInfo(basket)).to.be.null;
+  });
+
+  it('rejects empty baskets and repeated mints', async () => {
+    await expectError(makeBasket([]), 'InvalidBasketSize');
+
+    const seed = randomSeed();
+    const basket = findBasket(seed);
+    const repeated = [mints[0], mints[0]];
+    await expectError(
+      program.methods
+        .makeBasket(seed, new BN(800), [new BN(100), new BN(100)])
+        .accountsPartial({
+          maker: fx.maker.publicKey,
+          basket,
+          mintB: fx.mintB,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .remainingAccounts(legAccounts([fx.maker.publicKey, basket], repeated))
+        .signers([fx.maker])
+        .rpc(),
+      'DuplicateBasketMint'
+    );
+  });
+});
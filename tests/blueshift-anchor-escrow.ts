@@ -1,17 +1,119 @@
-import * as anchor from '@coral-xyz/anchor';
-import { Program } from '@coral-xyz/anchor';
-import { BlueshiftAnchorEscrow } from '../target/types/blueshift_anchor_escrow';
+import { getOrCreateAssociatedTokenAccount, mintTo } from '@solana/spl-token';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
 
 describe('blueshift-anchor-escrow', () => {
-  // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  let fx: Fixture;
 
-  const program = anchor.workspace
-    .blueshiftAnchorEscrow as Program<BlueshiftAnchorEscrow>;
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('stores the deposited amount at make', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.amount.toNumber()).to.equal(1_000);
+    expect(state.deposited.toNumber()).to.equal(1_000);
+    expect(state.receive.toNumber()).to.equal(500);
+  });
+
+  it('rejects an escrow swapping a mint for itself', async () => {
+    await expectError(makeEscrow({ ...fx, mintB: fx.mintA }), 'IdenticalMints');
+  });
+
+  it('deposits the whole maker balance', async () => {
+    fx = await createFixture(1_000);
+
+    const { vault } = await makeEscrow(fx, { amount: 1_000 });
+
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(0n);
+  });
+
+  it('rejects a deposit above the maker balance', async () => {
+    fx = await createFixture(1_000);
+
+    await expectError(
+      makeEscrow(fx, { amount: 1_001 }),
+      'InsufficientMakerBalance'
+    );
+  });
+
+  it('swaps token A for token B on take', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      500n
+    );
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('rejects a maker taking their own escrow', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const makerAtaB = await getOrCreateAssociatedTokenAccount(
+      connection,
+      fx.maker,
+      fx.mintB,
+      fx.maker.publicKey
+    );
+    await mintTo(
+      connection,
+      fx.taker,
+      fx.mintB,
+      makerAtaB.address,
+      fx.taker,
+      1_000_000
+    );
+
+    await expectError(takeEscrow(fx, escrow, fx.maker), 'SelfTrade');
+
+    await refundEscrow(fx, escrow);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('returns tokens donated to the vault to the maker on take', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+    await mintTo(connection, fx.maker, fx.mintA, vault, fx.maker, 250);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n - 1_000n + 250n);
+  });
+
+  it('sweeps the whole vault including donations on refund', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+    await mintTo(connection, fx.maker, fx.mintA, vault, fx.maker, 250);
+
+    await refundEscrow(fx, escrow);
 
-  it('Is initialized!', async () => {
-    // Add your test here.
-    // const tx = await program.methods.initialize().rpc();
-    // console.log("Your transaction signature", tx);
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_250n);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
   });
 });
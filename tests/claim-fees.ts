@@ -0,0 +1,156 @@
+import { BN } from '@coral-xyz/anchor';
+import {
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  U64_MAX,
+  connection,
+  ensureConfig,
+  expectError,
+  feeAuthorityPda,
+  fundedKeypair,
+  program,
+  provider,
+  tokenBalance,
+} from './utils';
+
+describe('claim_fees', () => {
+  // 直接向手续费 ATA 铸造代币, 模拟 take 累积的手续费
+  async function fundFeeVault(tokenProgram: PublicKey, amount: number) {
+    const payer = await fundedKeypair();
+    const mint = await createMint(
+      connection,
+      payer,
+      payer.publicKey,
+      null,
+      6,
+      Keypair.generate(),
+      undefined,
+      tokenProgram
+    );
+    const feeVault = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mint,
+        feeAuthorityPda,
+        true,
+        undefined,
+        undefined,
+        tokenProgram
+      )
+    ).address;
+    const destination = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mint,
+        payer.publicKey,
+        false,
+        undefined,
+        undefined,
+        tokenProgram
+      )
+    ).address;
+    await mintTo(
+      connection,
+      payer,
+      mint,
+      feeVault,
+      payer,
+      amount,
+      [],
+      undefined,
+      tokenProgram
+    );
+
+    return { mint, feeVault, destination };
+  }
+
+  const claimFees = (
+    mint: PublicKey,
+    destination: PublicKey,
+    amount: BN,
+    tokenProgram: PublicKey,
+    admin?: Keypair
+  ) =>
+    program.methods
+      .claimFees(amount)
+      .accountsPartial({
+        admin: admin?.publicKey ?? provider.wallet.publicKey,
+        mint,
+        destination,
+        tokenProgram,
+      })
+      .signers(admin ? [admin] : [])
+      .rpc();
+
+  before(async () => {
+    await ensureConfig();
+  });
+
+  it('leaves the remainder after a partial claim', async () => {
+    const { mint, feeVault, destination } = await fundFeeVault(
+      TOKEN_PROGRAM_ID,
+      1_000
+    );
+
+    await claimFees(mint, destination, new BN(400), TOKEN_PROGRAM_ID);
+
+    expect(await tokenBalance(feeVault)).to.equal(600n);
+    expect(await tokenBalance(destination)).to.equal(400n);
+  });
+
+  it('claims everything with u64::MAX', async () => {
+    const { mint, feeVault, destination } = await fundFeeVault(
+      TOKEN_PROGRAM_ID,
+      1_000
+    );
+
+    await claimFees(mint, destination, U64_MAX, TOKEN_PROGRAM_ID);
+
+    expect(await tokenBalance(feeVault)).to.equal(0n);
+    expect(await tokenBalance(destination)).to.equal(1_000n);
+  });
+
+  it('claims from a Token-2022 fee vault', async () => {
+    const { mint, feeVault, destination } = await fundFeeVault(
+      TOKEN_2022_PROGRAM_ID,
+      1_000
+    );
+
+    await claimFees(mint, destination, new BN(250), TOKEN_2022_PROGRAM_ID);
+
+    expect(await tokenBalance(feeVault)).to.equal(750n);
+    expect(await tokenBalance(destination)).to.equal(250n);
+  });
+
+  it('rejects more than the fee vault holds', async () => {
+    const { mint, destination } = await fundFeeVault(TOKEN_PROGRAM_ID, 1_000);
+
+    await expectError(
+      claimFees(mint, destination, new BN(1_001), TOKEN_PROGRAM_ID),
+      'InvalidAmount'
+    );
+  });
+
+  it('rejects a non-admin', async () => {
+    const { mint, feeVault, destination } = await fundFeeVault(
+      TOKEN_PROGRAM_ID,
+      1_000
+    );
+    const other = await fundedKeypair();
+
+    await expectError(
+      claimFees(mint, destination, U64_MAX, TOKEN_PROGRAM_ID, other),
+      'InvalidAdmin'
+    );
+    expect(await tokenBalance(feeVault)).to.equal(1_000n);
+  });
+});
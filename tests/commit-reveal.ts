@@ -0,0 +1,177 @@
+import { BN } from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import { createHash, randomBytes } from 'crypto';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  makeEscrow,
+  program,
+  refundEscrow,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+const DELAY_SLOTS = 10;
+
+// 和链上 TakeCommit::hash 相同: sha256(taker ‖ escrow ‖ salt)
+const commitHash = (taker: PublicKey, escrow: PublicKey, salt: Buffer) =>
+  createHash('sha256')
+    .update(Buffer.concat([taker.toBuffer(), escrow.toBuffer(), salt]))
+    .digest();
+
+const findCommit = (taker: PublicKey, hash: Buffer) =>
+  PublicKey.findProgramAddressSync(
+    [Buffer.from('commit'), taker.toBuffer(), hash],
+    program.programId
+  )[0];
+
+// 等到 commit 之后至少经过 slots 个 slot
+async function waitSlots(commit: PublicKey, slots: number) {
+  const { slot } = await program.account.takeCommit.fetch(commit);
+  while ((await connection.getSlot()) < slot.toNumber() + slots) {
+    await sleep(400);
+  }
+}
+
+describe('commit-reveal', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  const commitTake = async (escrow: PublicKey, salt: Buffer) => {
+    const hash = commitHash(fx.taker.publicKey, escrow, salt);
+    await program.methods
+      .commitTake(Array.from(hash))
+      .accountsPartial({ taker: fx.taker.publicKey })
+      .signers([fx.taker])
+      .rpc();
+    return findCommit(fx.taker.publicKey, hash);
+  };
+
+  const revealTake = (escrow: PublicKey, commit: PublicKey, salt: Buffer) =>
+    program.methods
+      .revealTake(
+        Array.from(salt),
+        U64_MAX,
+        new BN(0),
+        [],
+        Buffer.alloc(0),
+        false
+      )
+      .accountsPartial({
+        take: {
+          taker: fx.taker.publicKey,
+          payer: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          takerTokenA: null,
+          takerAtaB: ata(fx.mintB, fx.taker.publicKey, fx.tokenProgramB),
+          feeVaultB: null,
+          referrerAtaB: null,
+          unwrapAtaB: null,
+          receipt: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          priceFeed: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        },
+        commit,
+      })
+      .signers([fx.taker])
+      .rpc();
+
+  it('fills only after the delay has elapsed', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 800,
+      commitDelaySlots: DELAY_SLOTS,
+    });
+    await expectError(takeEscrow(fx, escrow), 'CommitRequired');
+
+    const salt = randomBytes(32);
+    const commit = await commitTake(escrow, salt);
+    await expectError(revealTake(escrow, commit, salt), 'CommitTooEarly');
+
+    await waitSlots(commit, DELAY_SLOTS);
+    await revealTake(escrow, commit, salt);
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    // 承诺只能使用一次, 账户和托管一起关闭
+    expect(await connection.getAccountInfo(commit)).to.be.null;
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('rejects a salt that does not match the commitment', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      commitDelaySlots: DELAY_SLOTS,
+    });
+    const commit = await commitTake(escrow, randomBytes(32));
+    await waitSlots(commit, DELAY_SLOTS);
+
+    await expectError(
+      revealTake(escrow, commit, randomBytes(32)),
+      'CommitMismatch'
+    );
+    // 同一个承诺不能用于另一个托管
+    const other = await makeEscrow(fx, {
+      amount: 1_000,
+      commitDelaySlots: DELAY_SLOTS,
+    });
+    await expectError(
+      revealTake(other.escrow, commit, randomBytes(32)),
+      'CommitMismatch'
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('keeps refund open and lets the taker reclaim the commit', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      commitDelaySlots: DELAY_SLOTS,
+    });
+    const salt = randomBytes(32);
+    const commit = await commitTake(escrow, salt);
+
+    await refundEscrow(fx, escrow);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+
+    const lamports = await connection.getBalance(fx.taker.publicKey);
+    await program.methods
+      .closeCommit()
+      .accountsPartial({ taker: fx.taker.publicKey, commit })
+      .signers([fx.taker])
+      .rpc();
+    expect(await connection.getAccountInfo(commit)).to.be.null;
+    expect(await connection.getBalance(fx.taker.publicKey)).to.be.greaterThan(
+      lamports
+    );
+  });
+
+  it('rejects a delay above the maximum', async () => {
+    await expectError(
+      makeEscrow(fx, { amount: 1_000, commitDelaySlots: 151 }),
+      'InvalidCommitDelay'
+    );
+  });
+});
@@ -0,0 +1,197 @@
+import { BN } from '@coral-xyz/anchor';
+import {
+  TOKEN_PROGRAM_ID,
+  getAccount,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  setFrozen,
+  tokenBalance,
+} from './utils';
+
+describe('counter offers', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const findCounter = (escrow: PublicKey, taker: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from('counter'), escrow.toBuffer(), taker.toBuffer()],
+      program.programId
+    )[0];
+
+  // 给另一个 taker 准备 token B
+  async function fundTaker(taker: Keypair) {
+    const takerAtaB = await getOrCreateAssociatedTokenAccount(
+      connection,
+      taker,
+      fx.mintB,
+      taker.publicKey
+    );
+    await mintTo(
+      connection,
+      fx.taker,
+      fx.mintB,
+      takerAtaB.address,
+      fx.taker,
+      1_000_000
+    );
+  }
+
+  const proposeCounter = (escrow: PublicKey, taker: Keypair, receive: number) =>
+    program.methods
+      .proposeCounter(new BN(receive))
+      .accountsPartial({
+        taker: taker.publicKey,
+        escrow,
+        mintB: fx.mintB,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([taker])
+      .rpc();
+
+  const acceptCounter = (
+    escrow: PublicKey,
+    taker: PublicKey,
+    maker = fx.maker
+  ) =>
+    program.methods
+      .acceptCounter()
+      .accountsPartial({
+        maker: maker.publicKey,
+        taker,
+        escrow,
+        counterOffer: findCounter(escrow, taker),
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        feeVaultB: null,
+        pairIndex: null,
+        tokenProgramA: TOKEN_PROGRAM_ID,
+        tokenProgramB: TOKEN_PROGRAM_ID,
+      })
+      .signers([maker])
+      .rpc();
+
+  const cancelCounter = (escrow: PublicKey, taker: Keypair) =>
+    program.methods
+      .cancelCounter()
+      .accountsPartial({
+        taker: taker.publicKey,
+        counterOffer: findCounter(escrow, taker.publicKey),
+        takerAtaB: ata(fx.mintB, taker.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([taker])
+      .rpc();
+
+  it('records the proposal without moving any tokens', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 1_000 });
+
+    await proposeCounter(escrow, fx.taker, 800);
+
+    const counter = await program.account.counterOffer.fetch(
+      findCounter(escrow, fx.taker.publicKey)
+    );
+    expect(counter.receive.toNumber()).to.equal(800);
+    expect(counter.taker.toBase58()).to.equal(fx.taker.publicKey.toBase58());
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n);
+    expect((await program.account.escrow.fetch(escrow)).status).to.deep.equal({
+      pendingCounter: {},
+    });
+  });
+
+  it('swaps at the counter price and orphans the other proposals', async () => {
+    const other = await fundedKeypair();
+    await fundTaker(other);
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 1_000,
+    });
+
+    await proposeCounter(escrow, fx.taker, 800);
+    await proposeCounter(escrow, other, 900);
+
+    await acceptCounter(escrow, fx.taker.publicKey);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n - 800n);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(
+      await connection.getAccountInfo(findCounter(escrow, fx.taker.publicKey))
+    ).to.be.null;
+
+    // 托管已经关闭, 另一个还价无法再被接受, 只能由 taker 取消并取回租金
+    await expectError(
+      acceptCounter(escrow, other.publicKey),
+      'AccountNotInitialized'
+    );
+    await cancelCounter(escrow, other);
+    expect(
+      await connection.getAccountInfo(findCounter(escrow, other.publicKey))
+    ).to.be.null;
+  });
+
+  it('revokes the delegation when the taker cancels', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await proposeCounter(escrow, fx.taker, 800);
+
+    await cancelCounter(escrow, fx.taker);
+
+    const takerAtaB = await getAccount(connection, fx.takerAtaB);
+    expect(takerAtaB.delegate).to.be.null;
+    await expectError(
+      acceptCounter(escrow, fx.taker.publicKey),
+      'AccountNotInitialized'
+    );
+  });
+
+  it('rejects acceptance by someone other than the maker', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await proposeCounter(escrow, fx.taker, 800);
+    const other = await fundedKeypair();
+
+    await expectError(
+      acceptCounter(escrow, fx.taker.publicKey, other),
+      'ConstraintSeeds'
+    );
+  });
+
+  it('rejects a counter from the maker', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await expectError(proposeCounter(escrow, fx.maker, 800), 'SelfTrade');
+  });
+
+  it('rejects a counter on a frozen escrow', async () => {
+    await ensureConfig();
+    const { escrow } = await makeEscrow(fx);
+    await setFrozen(escrow, true);
+
+    await expectError(proposeCounter(escrow, fx.taker, 800), 'EscrowFrozen');
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.statusBeforeFreeze).to.deep.equal({ open: {} });
+    expect(
+      await connection.getAccountInfo(findCounter(escrow, fx.taker.publicKey))
+    ).to.be.null;
+  });
+});
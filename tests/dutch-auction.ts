@@ -0,0 +1,100 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  createFixture,
+  expectError,
+  fetchEvents,
+  findEscrow,
+  nowSeconds,
+  program,
+  randomSeed,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('dutch auction', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const makeDutch = async (
+    startReceive: number,
+    endReceive: number,
+    decayStart: number,
+    decayEnd: number
+  ) => {
+    const seed = randomSeed();
+    await program.methods
+      .makeDutch(
+        seed,
+        new BN(startReceive),
+        new BN(endReceive),
+        new BN(1_000),
+        new BN(decayStart),
+        new BN(decayEnd)
+      )
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        rentPayer: fx.maker.publicKey,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        exemptList: null,
+        treasury: null,
+        tokenProgramA: TOKEN_PROGRAM_ID,
+        tokenProgramB: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.maker])
+      .rpc();
+    return findEscrow(fx.maker.publicKey, seed);
+  };
+
+  const makerReceived = () => tokenBalance(ata(fx.mintB, fx.maker.publicKey));
+
+  it('charges the start price before the decay window', async () => {
+    const now = nowSeconds();
+    const escrow = await makeDutch(10_000, 1_000, now + 600, now + 1_200);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await makerReceived()).to.equal(10_000n);
+  });
+
+  it('charges an interpolated price during the decay window', async () => {
+    const now = nowSeconds();
+    const escrow = await makeDutch(10_000, 1_000, now - 300, now + 300);
+
+    const signature = await takeEscrow(fx, escrow);
+
+    // 大约在衰减窗口的中间成交, 允许本地时钟有少量偏差
+    const paid = Number(await makerReceived());
+    expect(paid).to.be.lessThan(10_000);
+    expect(paid).to.be.greaterThan(1_000);
+    expect(Math.abs(paid - 5_500)).to.be.lessThan(500);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.data.effectiveReceive.toNumber()).to.equal(paid);
+  });
+
+  it('charges the end price after the decay window', async () => {
+    const now = nowSeconds();
+    const escrow = await makeDutch(10_000, 1_000, now - 1_200, now - 600);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await makerReceived()).to.equal(1_000n);
+  });
+
+  it('rejects an end price above the start price', async () => {
+    const now = nowSeconds();
+
+    await expectError(
+      makeDutch(1_000, 10_000, now, now + 600),
+      'InvalidDutchAuction'
+    );
+  });
+});
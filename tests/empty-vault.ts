@@ -0,0 +1,89 @@
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  burn,
+  createInitializePermanentDelegateInstruction,
+  createMint,
+} from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixtureWithMints,
+  createMintWithExtensions,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// 创建一个 maker 为 permanent delegate 的 Token-2022 mint
+const createPermanentDelegateMint = (maker: Keypair) =>
+  createMintWithExtensions(maker, [ExtensionType.PermanentDelegate], (mint) => [
+    createInitializePermanentDelegateInstruction(
+      mint,
+      maker.publicKey,
+      TOKEN_2022_PROGRAM_ID
+    ),
+  ]);
+
+// 两个 mint 都使用 Token-2022, 并且 mint A 带有 permanent delegate 扩展
+async function createToken2022Fixture(): Promise<Fixture> {
+  const maker = await fundedKeypair();
+  const taker = await fundedKeypair();
+
+  const mintA = await createPermanentDelegateMint(maker);
+  const mintB = await createMint(
+    connection,
+    taker,
+    taker.publicKey,
+    null,
+    6,
+    undefined,
+    undefined,
+    TOKEN_2022_PROGRAM_ID
+  );
+
+  return createFixtureWithMints(
+    maker,
+    taker,
+    mintA,
+    mintB,
+    TOKEN_2022_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID
+  );
+}
+
+describe('empty vault', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createToken2022Fixture();
+  });
+
+  it('rejects take once the vault has been drained', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 1_000,
+    });
+
+    // permanent delegate 可以绕过托管直接销毁 vault 中的 token A
+    await burn(
+      connection,
+      fx.maker,
+      vault,
+      fx.mintA,
+      fx.maker,
+      1_000,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    expect(await tokenBalance(vault)).to.equal(0n);
+
+    await expectError(takeEscrow(fx, escrow), 'EmptyVault');
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n);
+  });
+});
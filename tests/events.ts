@@ -0,0 +1,94 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  cancelEscrow,
+  createFixture,
+  fetchEvents,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+} from './utils';
+
+describe('events', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('emits a MakeEvent with the deposit', async () => {
+    const { escrow, signature } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+
+    const [event] = await fetchEvents(signature);
+    expect(event.name).to.equal('makeEvent');
+    expect(event.data.escrow.toBase58()).to.equal(escrow.toBase58());
+    expect(event.data.maker.toBase58()).to.equal(
+      fx.maker.publicKey.toBase58()
+    );
+    expect(event.data.mintA.toBase58()).to.equal(fx.mintA.toBase58());
+    expect(event.data.mintB.toBase58()).to.equal(fx.mintB.toBase58());
+    expect(event.data.amount.toNumber()).to.equal(1_000);
+    expect(event.data.receive.toNumber()).to.equal(500);
+    expect(event.data.timestamp.toNumber()).to.be.greaterThan(0);
+  });
+
+  it('emits a TakeEvent with the actual transfers', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+
+    const signature = await takeEscrow(fx, escrow);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.name).to.equal('takeEvent');
+    expect(event.data.escrow.toBase58()).to.equal(escrow.toBase58());
+    expect(event.data.taker.toBase58()).to.equal(
+      fx.taker.publicKey.toBase58()
+    );
+    expect(event.data.amountA.toNumber()).to.equal(1_000);
+    expect(event.data.amountB.toNumber()).to.equal(500);
+  });
+
+  it('emits a RefundEvent with the returned amount', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    const signature = await refundEscrow(fx, escrow);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.name).to.equal('refundEvent');
+    expect(event.data.escrow.toBase58()).to.equal(escrow.toBase58());
+    expect(event.data.amount.toNumber()).to.equal(1_000);
+  });
+
+  it('emits a CancelEvent with the reason code', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    const signature = await cancelEscrow(fx, escrow, 0);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.name).to.equal('cancelEvent');
+    expect(event.data.escrow.toBase58()).to.equal(escrow.toBase58());
+    expect(event.data.reason).to.deep.equal({ pulled: {} });
+    expect(event.data.retained).to.equal(false);
+    expect(event.data.amount.toNumber()).to.equal(1_000);
+    expect(await program.account.escrow.fetchNullable(escrow)).to.be.null;
+  });
+
+  it('keeps a cancelled escrow readable when retained', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    const signature = await cancelEscrow(fx, escrow, 2, true);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.name).to.equal('cancelEvent');
+    expect(event.data.reason).to.deep.equal({ repriced: {} });
+    expect(event.data.status).to.deep.equal({ cancelled: {} });
+    expect(event.data.retained).to.equal(true);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.status).to.deep.equal({ cancelled: {} });
+    expect(state.cancelReason).to.equal(2);
+  });
+});
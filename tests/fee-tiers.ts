@@ -0,0 +1,218 @@
+import { BN } from '@coral-xyz/anchor';
+import { getOrCreateAssociatedTokenAccount } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  connection,
+  createFixture,
+  expectError,
+  feeAuthorityPda,
+  findUserStats,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  provider,
+  setFee,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// 替换全部手续费档位, 使用 provider 钱包作为 admin
+const setFeeTiers = (tiers: { minTrades: number; feeBps: number }[]) =>
+  program.methods
+    .setFeeTiers(
+      tiers.map(({ minTrades, feeBps }) => ({
+        minTrades: new BN(minTrades),
+        feeBps,
+      }))
+    )
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+
+describe('fee tiers', () => {
+  let fx: Fixture;
+  let feeVaultB: PublicKey;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    feeVaultB = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.taker,
+        fx.mintB,
+        feeAuthorityPda,
+        true
+      )
+    ).address;
+    // 基础 10%, 成交过 1 次之后 5%, 2 次之后免手续费
+    await setFee(1_000);
+    await setFeeTiers([
+      { minTrades: 1, feeBps: 500 },
+      { minTrades: 2, feeBps: 0 },
+    ]);
+  });
+
+  afterEach(async () => {
+    await setFeeTiers([]);
+    await setFee(0);
+  });
+
+  it('switches tier exactly at each min_trades', async () => {
+    const takerStats = findUserStats(fx.taker.publicKey);
+
+    for (const fee of [100n, 50n, 0n]) {
+      const before = await tokenBalance(feeVaultB);
+      const { escrow } = await makeEscrow(fx, { receive: 1_000 });
+      await takeEscrow(fx, escrow, fx.taker, { feeVaultB, takerStats });
+      expect((await tokenBalance(feeVaultB)) - before).to.equal(fee);
+    }
+
+    const stats = await program.account.userStats.fetch(takerStats);
+    expect(stats.wallet.toBase58()).to.equal(fx.taker.publicKey.toBase58());
+    expect(stats.trades.toNumber()).to.equal(3);
+    expect(stats.volumeB.toNumber()).to.equal(3_000);
+  });
+
+  it('requires taker stats and counts makers who opted in', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 1_000 });
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { feeVaultB }),
+      'MissingUserStats'
+    );
+
+    await program.methods
+      .initUserStats()
+      .accountsPartial({ wallet: fx.maker.publicKey })
+      .signers([fx.maker])
+      .rpc();
+    const makerStats = findUserStats(fx.maker.publicKey);
+    await takeEscrow(fx, escrow, fx.taker, {
+      feeVaultB,
+      takerStats: findUserStats(fx.taker.publicKey),
+      makerStats,
+    });
+
+    const stats = await program.account.userStats.fetch(makerStats);
+    expect(stats.trades.toNumber()).to.equal(1);
+    expect(stats.volumeB.toNumber()).to.equal(1_000);
+  });
+
+  it('applies the taker tier to every partial fill', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 1_000 });
+    const takerStats = findUserStats(fx.taker.publicKey);
+    const takePartial = (amountA: number, stats: PublicKey | null) =>
+      program.methods
+        .takePartial(new BN(amountA), U64_MAX, [], Buffer.alloc(0))
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          feeVaultB,
+          referrerAtaB: null,
+          takerStats: stats,
+          makerStats: null,
+          pairIndex: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([fx.taker])
+        .rpc();
+
+    await expectError(takePartial(500, null), 'MissingUserStats');
+
+    // 每次部分成交都计入一次: 第一次 10%, 第二次 5%
+    for (const fee of [50n, 25n]) {
+      const before = await tokenBalance(feeVaultB);
+      await takePartial(500, takerStats);
+      expect((await tokenBalance(feeVaultB)) - before).to.equal(fee);
+    }
+
+    const stats = await program.account.userStats.fetch(takerStats);
+    expect(stats.trades.toNumber()).to.equal(2);
+    expect(stats.volumeB.toNumber()).to.equal(1_000);
+  });
+
+  it('applies the taker tier to release_to_taker', async () => {
+    const arbiter = await fundedKeypair();
+    const takerStats = findUserStats(fx.taker.publicKey);
+    const arbitrated = async () => {
+      const { escrow } = await makeEscrow(fx, {
+        receive: 1_000,
+        arbiter: arbiter.publicKey,
+      });
+      await program.methods
+        .lockTake(new BN(1_000), [])
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          takerAtaB: fx.takerAtaB,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([fx.taker])
+        .rpc();
+      return escrow;
+    };
+    const release = (escrow: PublicKey, stats: PublicKey | null) =>
+      program.methods
+        .releaseToTaker()
+        .accountsPartial({
+          authority: arbiter.publicKey,
+          coSigner: null,
+          maker: fx.maker.publicKey,
+          taker: fx.taker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          feeVaultB,
+          takerStats: stats,
+          makerStats: null,
+          pairIndex: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([arbiter])
+        .rpc();
+
+    const first = await arbitrated();
+    await expectError(release(first, null), 'MissingUserStats');
+
+    // 和 take 一样计入成交次数: 第一次 10%, 第二次 5%
+    for (const [escrow, fee] of [
+      [first, 100n],
+      [await arbitrated(), 50n],
+    ] as const) {
+      const before = await tokenBalance(feeVaultB);
+      await release(escrow, takerStats);
+      expect((await tokenBalance(feeVaultB)) - before).to.equal(fee);
+    }
+
+    const stats = await program.account.userStats.fetch(takerStats);
+    expect(stats.trades.toNumber()).to.equal(2);
+    expect(stats.volumeB.toNumber()).to.equal(2_000);
+  });
+
+  it('rejects tiers that are unordered or raise the fee', async () => {
+    for (const tiers of [
+      [
+        { minTrades: 5, feeBps: 500 },
+        { minTrades: 5, feeBps: 250 },
+      ],
+      [
+        { minTrades: 1, feeBps: 250 },
+        { minTrades: 5, feeBps: 500 },
+      ],
+      [{ minTrades: 1, feeBps: 10_001 }],
+      [1, 2, 3, 4, 5].map((minTrades) => ({ minTrades, feeBps: 0 })),
+    ]) {
+      await expectError(setFeeTiers(tiers), 'InvalidFeeTiers');
+    }
+  });
+});
@@ -0,0 +1,166 @@
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  freezeAccount,
+  getAccount,
+} from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixtureWithMints,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  nowSeconds,
+  program,
+  refundEscrow,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// mint A 和 mint B 的铸币权限分别属于 maker 和 taker, 需要时把 freeze authority 也交给它们
+async function createFreezeFixture(
+  freezeA: boolean,
+  freezeB: boolean
+): Promise<Fixture> {
+  const maker = await fundedKeypair();
+  const taker = await fundedKeypair();
+  const mint = (authority: Keypair, freeze: boolean) =>
+    createMint(
+      connection,
+      authority,
+      authority.publicKey,
+      freeze ? authority.publicKey : null,
+      6
+    );
+
+  return createFixtureWithMints(
+    maker,
+    taker,
+    await mint(maker, freezeA),
+    await mint(taker, freezeB),
+    TOKEN_PROGRAM_ID,
+    TOKEN_PROGRAM_ID
+  );
+}
+
+describe('freeze authority', () => {
+  describe('reject_freezable', () => {
+    it('rejects a freezable mint A', async () => {
+      const fx = await createFreezeFixture(true, false);
+
+      await expectError(
+        makeEscrow(fx, { rejectFreezable: true }),
+        'FreezableMintRejected'
+      );
+    });
+
+    it('rejects a freezable mint B', async () => {
+      const fx = await createFreezeFixture(false, true);
+
+      await expectError(
+        makeEscrow(fx, { rejectFreezable: true }),
+        'FreezableMintRejected'
+      );
+    });
+
+    it('accepts mints without a freeze authority', async () => {
+      const fx = await createFreezeFixture(false, false);
+
+      const { escrow } = await makeEscrow(fx, { rejectFreezable: true });
+
+      expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+    });
+
+    it('allows freezable mints by default', async () => {
+      const fx = await createFreezeFixture(true, true);
+
+      const { escrow } = await makeEscrow(fx);
+
+      expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+    });
+  });
+
+  describe('frozen vault', () => {
+    let fx: Fixture;
+
+    beforeEach(async () => {
+      fx = await createFreezeFixture(true, false);
+    });
+
+    const makeAndFreeze = async () => {
+      const { escrow, vault } = await makeEscrow(fx);
+      await freezeAccount(connection, fx.maker, vault, fx.mintA, fx.maker);
+      return { escrow, vault };
+    };
+
+    it('rejects take with VaultFrozen', async () => {
+      const { escrow } = await makeAndFreeze();
+
+      await expectError(takeEscrow(fx, escrow), 'VaultFrozen');
+    });
+
+    it('rejects refund with VaultFrozen', async () => {
+      const { escrow } = await makeAndFreeze();
+
+      await expectError(refundEscrow(fx, escrow), 'VaultFrozen');
+    });
+
+    it('rejects refund_expired with VaultFrozen', async () => {
+      const { escrow, vault } = await makeEscrow(fx, {
+        expiry: nowSeconds() + 2,
+      });
+      await freezeAccount(connection, fx.maker, vault, fx.mintA, fx.maker);
+      await sleep(4_000);
+      const cranker = await fundedKeypair();
+
+      await expectError(
+        program.methods
+          .refundExpired()
+          .accountsPartial({
+            refund: {
+              authority: cranker.publicKey,
+              maker: fx.maker.publicKey,
+              escrow,
+              mintA: fx.mintA,
+              vault,
+              makerAtaA: fx.makerAtaA,
+              pairIndex: null,
+              tokenProgram: TOKEN_PROGRAM_ID,
+            },
+          })
+          .signers([cranker])
+          .rpc(),
+        'VaultFrozen'
+      );
+    });
+
+    it('closes only the escrow when forced', async () => {
+      const { escrow, vault } = await makeAndFreeze();
+      const rent = await connection.getBalance(escrow);
+      const before = await connection.getBalance(fx.maker.publicKey);
+
+      await refundEscrow(fx, escrow, [], true);
+
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+      expect(await connection.getBalance(fx.maker.publicKey)).to.be.greaterThan(
+        before + rent - 10_000
+      );
+      const frozenVault = await getAccount(connection, vault);
+      expect(frozenVault.isFrozen).to.be.true;
+      expect(frozenVault.amount).to.equal(1_000n);
+    });
+
+    it('ignores force when the vault is not frozen', async () => {
+      const { escrow, vault } = await makeEscrow(fx);
+
+      await refundEscrow(fx, escrow, [], true);
+
+      expect(await connection.getAccountInfo(vault)).to.be.null;
+      expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+    });
+  });
+});
@@ -0,0 +1,81 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  setFrozen,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('escrow freezing', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  it('rejects take while frozen', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await setFrozen(escrow, true);
+
+    expect((await program.account.escrow.fetch(escrow)).status).to.deep.equal({
+      frozen: {},
+    });
+    await expectError(takeEscrow(fx, escrow), 'EscrowFrozen');
+  });
+
+  it('still lets the maker refund while frozen', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+    const before = await tokenBalance(fx.makerAtaA);
+    await setFrozen(escrow, true);
+
+    await refundEscrow(fx, escrow);
+
+    expect((await tokenBalance(fx.makerAtaA)) - before).to.equal(1_000n);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('allows take again after unfreezing', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await setFrozen(escrow, true);
+    await setFrozen(escrow, false);
+
+    await takeEscrow(fx, escrow);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('restores the previous status and rejects a second freeze', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await setFrozen(escrow, true);
+
+    await expectError(setFrozen(escrow, true), 'InvalidEscrowStatus');
+    await setFrozen(escrow, false);
+
+    expect((await program.account.escrow.fetch(escrow)).status).to.deep.equal({
+      open: {},
+    });
+    await expectError(setFrozen(escrow, false), 'InvalidEscrowStatus');
+  });
+
+  it('only lets the admin freeze', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const stranger = await fundedKeypair();
+
+    await expectError(
+      program.methods
+        .freezeEscrow()
+        .accountsPartial({ admin: stranger.publicKey, escrow })
+        .signers([stranger])
+        .rpc(),
+      'InvalidAdmin'
+    );
+  });
+});
@@ -0,0 +1,99 @@
+import {
+  PublicKey,
+  TransactionMessage,
+  VersionedTransaction,
+} from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  configPda,
+  connection,
+  createFixture,
+  ensureConfig,
+  makeEscrow,
+  nowSeconds,
+  program,
+  provider,
+} from './utils';
+
+describe('get_escrow', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  // 和钱包一样用未签名的交易模拟, 不需要任何签名
+  const simulate = async (escrow: PublicKey) => {
+    const ix = await program.methods
+      .getEscrow()
+      .accountsPartial({ escrow })
+      .instruction();
+    const { blockhash } = await connection.getLatestBlockhash();
+    const message = new TransactionMessage({
+      payerKey: provider.wallet.publicKey,
+      recentBlockhash: blockhash,
+      instructions: [ix],
+    }).compileToV0Message();
+    const { value } = await connection.simulateTransaction(
+      new VersionedTransaction(message),
+      { sigVerify: false }
+    );
+    return value;
+  };
+
+  it('returns the escrow and its computed fields', async () => {
+    const now = nowSeconds();
+    const { escrow, seed } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+      startTime: now + 600,
+      expiry: now + 1_200,
+    });
+    const before = await connection.getAccountInfo(escrow);
+
+    const view = await program.methods
+      .getEscrow()
+      .accountsPartial({ escrow })
+      .view();
+
+    expect(view.address.toBase58()).to.equal(escrow.toBase58());
+    expect(view.escrow.seed.eq(seed)).to.be.true;
+    expect(view.escrow.maker.toBase58()).to.equal(
+      fx.maker.publicKey.toBase58()
+    );
+    expect(view.escrow.status).to.deep.equal({ open: {} });
+    expect(view.currentReceive.toNumber()).to.equal(500);
+    expect(view.amountB.toNumber()).to.equal(500);
+    expect(view.started).to.be.false;
+    expect(view.expired).to.be.false;
+    expect((await connection.getAccountInfo(escrow)).data).to.deep.equal(
+      before.data
+    );
+  });
+
+  it('decodes the return data of a raw simulation', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 300 });
+
+    const result = await simulate(escrow);
+
+    expect(result.err).to.be.null;
+    expect(result.returnData.programId).to.equal(program.programId.toBase58());
+    const data = Buffer.from(result.returnData.data[0], 'base64');
+    // 运行时会去掉 return data 末尾的 0, 补齐之后再用 IDL 解码
+    const view = program.coder.types.decode(
+      'EscrowView',
+      Buffer.concat([data, Buffer.alloc(1_024 - data.length)])
+    );
+    expect(view.escrow.receive.toNumber()).to.equal(300);
+    expect(view.started).to.be.true;
+  });
+
+  it('rejects an account that is not an escrow', async () => {
+    const result = await simulate(configPda);
+
+    expect(result.err).to.not.be.null;
+    expect(result.logs.join('\n')).to.contain('AccountDiscriminatorMismatch');
+  });
+});
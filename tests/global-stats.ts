@@ -0,0 +1,107 @@
+import { BN } from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  createFixture,
+  makeEscrow,
+  program,
+  refundEscrow,
+  statsPda,
+  takeEscrow,
+} from './utils';
+
+// 统计账户是全局的, 其他测试文件也会修改它, 这里只比较一串操作前后的差值
+async function fetchStats() {
+  const stats = await program.account.globalStats.fetch(statsPda);
+  return {
+    created: stats.totalEscrowsCreated.toNumber(),
+    open: stats.openEscrows.toNumber(),
+    takes: stats.totalTakes.toNumber(),
+    refunds: stats.totalRefunds.toNumber(),
+  };
+}
+
+describe('global stats', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('counts makes, takes and refunds', async () => {
+    // 统计账户在第一次 make 时创建
+    const first = await makeEscrow(fx, { amount: 100 });
+    const before = await fetchStats();
+
+    const second = await makeEscrow(fx, { amount: 100 });
+    const third = await makeEscrow(fx, { amount: 100 });
+    expect(await fetchStats()).to.deep.equal({
+      ...before,
+      created: before.created + 2,
+      open: before.open + 2,
+    });
+
+    await takeEscrow(fx, first.escrow);
+    await refundEscrow(fx, second.escrow);
+    expect(await fetchStats()).to.deep.equal({
+      created: before.created + 2,
+      open: before.open,
+      takes: before.takes + 1,
+      refunds: before.refunds + 1,
+    });
+
+    await takeEscrow(fx, third.escrow);
+    const after = await fetchStats();
+    expect(after.open).to.equal(before.open - 1);
+    expect(after.takes).to.equal(before.takes + 2);
+  });
+
+  it('counts the last partial fill and a full withdrawal', async () => {
+    const partial = await makeEscrow(fx, { amount: 1_000 });
+    const withdrawn = await makeEscrow(fx, { amount: 1_000 });
+    const takePartial = (escrow: PublicKey) =>
+      program.methods
+        .takePartial(new BN(500), U64_MAX, [], Buffer.alloc(0))
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          feeVaultB: null,
+          referrerAtaB: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([fx.taker])
+        .rpc();
+    const before = await fetchStats();
+
+    // 部分成交不计入, 关闭托管的最后一次成交计入一次
+    await takePartial(partial.escrow);
+    expect(await fetchStats()).to.deep.equal(before);
+    await takePartial(partial.escrow);
+    await program.methods
+      .withdrawPartial(new BN(1_000))
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        escrow: withdrawn.escrow,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: fx.tokenProgramA,
+      })
+      .signers([fx.maker])
+      .rpc();
+    expect(await fetchStats()).to.deep.equal({
+      ...before,
+      open: before.open - 2,
+      takes: before.takes + 1,
+      refunds: before.refunds + 1,
+    });
+  });
+});
@@ -0,0 +1,84 @@
+import { createHash, randomBytes } from 'crypto';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  fetchEvents,
+  makeEscrow,
+  nowSeconds,
+  program,
+  refundEscrow,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('htlc', () => {
+  let fx: Fixture;
+  let preimage: Buffer;
+  let hashlock: number[];
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    preimage = randomBytes(32);
+    hashlock = Array.from(createHash('sha256').update(preimage).digest());
+  });
+
+  it('takes with the correct preimage and emits it', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      hashlock,
+      expiry: nowSeconds() + 60,
+    });
+
+    const signature = await takeEscrow(fx, escrow, fx.taker, { preimage });
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    const [event] = await fetchEvents(signature);
+    expect(Buffer.from(event.data.preimage).equals(preimage)).to.be.true;
+  });
+
+  it('rejects a wrong preimage', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      hashlock,
+      expiry: nowSeconds() + 60,
+    });
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { preimage: randomBytes(32) }),
+      'InvalidPreimage'
+    );
+    await expectError(takeEscrow(fx, escrow), 'InvalidPreimage');
+  });
+
+  it('blocks the maker refund until the timeout', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      hashlock,
+      expiry: nowSeconds() + 2,
+    });
+
+    await expectError(refundEscrow(fx, escrow), 'EscrowNotExpired');
+
+    await sleep(3_000);
+    await refundEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+  });
+
+  it('requires an expiry', async () => {
+    await expectError(makeEscrow(fx, { hashlock }), 'InvalidExpiry');
+  });
+
+  it('stores the hashlock on make', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      hashlock,
+      expiry: nowSeconds() + 60,
+    });
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.hashlock).to.deep.equal(hashlock);
+  });
+});
@@ -0,0 +1,75 @@
+import { BN } from '@coral-xyz/anchor';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  ensureConfig,
+  exemptListPda,
+  expectError,
+  makeEscrow,
+  program,
+  provider,
+} from './utils';
+
+// 高于空账户的免租金额, 新的 treasury 第一次收款也能成功
+const LISTING_FEE = 1_000_000;
+
+// 设置挂单费和 treasury, 使用 provider 钱包作为 admin
+const setListingFee = (lamports: number, treasury = PublicKey.default) =>
+  program.methods
+    .setListingFee(new BN(lamports), treasury)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+
+describe('listing fee', () => {
+  let fx: Fixture;
+  let treasury: PublicKey;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    treasury = Keypair.generate().publicKey;
+    await setListingFee(LISTING_FEE, treasury);
+  });
+
+  afterEach(async () => {
+    await setListingFee(0);
+  });
+
+  it('charges every make into the treasury', async () => {
+    await expectError(makeEscrow(fx), 'MissingTreasury');
+
+    await makeEscrow(fx, { treasury });
+    expect(await connection.getBalance(treasury)).to.equal(LISTING_FEE);
+
+    await makeEscrow(fx, { treasury });
+    expect(await connection.getBalance(treasury)).to.equal(2 * LISTING_FEE);
+  });
+
+  it('rejects a treasury other than the configured one', async () => {
+    await expectError(
+      makeEscrow(fx, { treasury: Keypair.generate().publicKey }),
+      'InvalidTreasury'
+    );
+  });
+
+  it('skips the fee for exempt wallets', async () => {
+    await program.methods
+      .addExemptWallet(fx.maker.publicKey)
+      .accountsPartial({ admin: provider.wallet.publicKey })
+      .rpc();
+
+    await makeEscrow(fx, { exemptList: exemptListPda });
+    await makeEscrow(fx, { exemptList: exemptListPda, treasury });
+    expect(await connection.getBalance(treasury)).to.equal(0);
+
+    await program.methods
+      .removeExemptWallet(fx.maker.publicKey)
+      .accountsPartial({ admin: provider.wallet.publicKey })
+      .rpc();
+    await makeEscrow(fx, { exemptList: exemptListPda, treasury });
+    expect(await connection.getBalance(treasury)).to.equal(LISTING_FEE);
+  });
+});
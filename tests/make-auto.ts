@@ -0,0 +1,100 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  EMPTY_ROOT,
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  findEscrow,
+  makeEscrow,
+  program,
+} from './utils';
+
+describe('make_auto', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const findMakerCounter = (maker: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from('counter'), maker.toBuffer()],
+      program.programId
+    )[0];
+
+  // 计数器不存在时下一个 seed 为 0
+  async function nextSeed(): Promise<BN> {
+    const counter = await program.account.makerCounter.fetchNullable(
+      findMakerCounter(fx.maker.publicKey)
+    );
+    return counter?.nextSeed ?? new BN(0);
+  }
+
+  async function makeAuto(seed?: BN) {
+    return program.methods
+      .makeAuto(
+        seed ?? (await nextSeed()),
+        new BN(1_000),
+        new BN(1_000),
+        new BN(0),
+        PublicKey.default,
+        EMPTY_ROOT,
+        EMPTY_ROOT,
+        new BN(0)
+      )
+      .accountsPartial({
+        make: {
+          maker: fx.maker.publicKey,
+          rentPayer: fx.maker.publicKey,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          exemptList: null,
+          treasury: null,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        },
+      })
+      .signers([fx.maker])
+      .rpc();
+  }
+
+  it('creates escrows back-to-back at consecutive seeds', async () => {
+    for (let i = 0; i < 3; i++) {
+      await makeAuto();
+    }
+
+    const counter = await program.account.makerCounter.fetch(
+      findMakerCounter(fx.maker.publicKey)
+    );
+    expect(counter.nextSeed.toNumber()).to.equal(3);
+    expect(counter.maker.toBase58()).to.equal(fx.maker.publicKey.toBase58());
+
+    // 所有 seed 小于 next_seed 的托管都可以直接枚举出来
+    const escrows = [0, 1, 2].map((i) =>
+      findEscrow(fx.maker.publicKey, new BN(i))
+    );
+    expect(new Set(escrows.map((e) => e.toBase58())).size).to.equal(3);
+    for (const escrow of escrows) {
+      const state = await program.account.escrow.fetch(escrow);
+      expect(state.maker.toBase58()).to.equal(fx.maker.publicKey.toBase58());
+    }
+  });
+
+  it('rejects a stale seed', async () => {
+    await makeAuto();
+
+    await expectError(makeAuto(new BN(0)), 'InvalidSeed');
+  });
+
+  it('keeps the classic make working alongside', async () => {
+    const { escrow } = await makeEscrow(fx, { seed: new BN(1_000_000) });
+    await makeAuto();
+
+    expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+    expect((await nextSeed()).toNumber()).to.equal(1);
+  });
+});
@@ -0,0 +1,132 @@
+import { BN } from '@coral-xyz/anchor';
+import { AccountMeta } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  findEscrow,
+  findRegistry,
+  makeEscrow,
+  program,
+  randomSeed,
+  tokenBalance,
+} from './utils';
+
+interface MakeEntry {
+  seed: BN;
+  receive: BN;
+  amount: BN;
+}
+
+describe('make_batch', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  // 每一档 1000 个 token A, 按 receives 的价格挂单
+  const ladder = (receives: number[]): MakeEntry[] =>
+    receives.map((receive) => ({
+      seed: randomSeed(),
+      receive: new BN(receive),
+      amount: new BN(1_000),
+    }));
+
+  // 按 entries 的顺序排列的 [escrow, vault] 账户
+  const levelAccounts = (entries: MakeEntry[]): AccountMeta[] =>
+    entries
+      .flatMap(({ seed }) => {
+        const escrow = findEscrow(fx.maker.publicKey, seed);
+        return [escrow, ata(fx.mintA, escrow, fx.tokenProgramA)];
+      })
+      .map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+  const makeBatch = (
+    entries: MakeEntry[],
+    accounts: AccountMeta[] = levelAccounts(entries)
+  ) =>
+    program.methods
+      .makeBatch(entries)
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        rentPayer: fx.maker.publicKey,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        makerAtaA: fx.makerAtaA,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .remainingAccounts(accounts)
+      .signers([fx.maker])
+      .rpc();
+
+  it('creates every level of the ladder', async () => {
+    const entries = ladder([500, 550, 600]);
+
+    await makeBatch(entries);
+
+    for (const { seed, receive } of entries) {
+      const escrow = findEscrow(fx.maker.publicKey, seed);
+      const state = await program.account.escrow.fetch(escrow);
+      expect(state.receive.eq(receive)).to.be.true;
+      expect(state.amount.toNumber()).to.equal(1_000);
+      expect(state.status).to.deep.equal({ open: {} });
+      expect(await tokenBalance(ata(fx.mintA, escrow))).to.equal(1_000n);
+    }
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n - 3_000n);
+
+    const { seeds } = await program.account.makerRegistry.fetch(
+      findRegistry(fx.maker.publicKey)
+    );
+    expect(seeds.map((seed) => seed.toString())).to.deep.equal(
+      entries.map(({ seed }) => seed.toString())
+    );
+  });
+
+  it('rejects accounts that are not in entry order', async () => {
+    const entries = ladder([500, 550]);
+    const [escrow0, vault0, escrow1, vault1] = levelAccounts(entries);
+
+    await expectError(
+      makeBatch(entries, [escrow1, vault1, escrow0, vault0]),
+      'InvalidBatchAccounts'
+    );
+    for (const { pubkey } of [escrow0, escrow1]) {
+      expect(await connection.getAccountInfo(pubkey)).to.be.null;
+    }
+  });
+
+  it('creates nothing when one level fails', async () => {
+    // 最后一档的 seed 已经被占用, 前两档创建之后整笔交易仍然回滚
+    const { seed } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const entries = ladder([550, 600]);
+    entries.push({ seed, receive: new BN(650), amount: new BN(1_000) });
+    const balance = await tokenBalance(fx.makerAtaA);
+
+    await expectError(makeBatch(entries), 'already in use');
+
+    for (const { seed } of entries.slice(0, 2)) {
+      const escrow = findEscrow(fx.maker.publicKey, seed);
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+    }
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(balance);
+    const { seeds } = await program.account.makerRegistry.fetch(
+      findRegistry(fx.maker.publicKey)
+    );
+    expect(seeds.map((open) => open.toString())).to.deep.equal([
+      seed.toString(),
+    ]);
+  });
+
+  it('rejects a batch over the size limit', async () => {
+    const entries = ladder(Array(11).fill(500));
+
+    await expectError(makeBatch(entries, []), 'InvalidBatchSize');
+  });
+});
@@ -0,0 +1,63 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  findRegistry,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+} from './utils';
+
+describe('maker registry', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  async function fetchSeeds() {
+    const registry = findRegistry(fx.maker.publicKey);
+    const { seeds } = await program.account.makerRegistry.fetch(registry);
+    return seeds.map((seed) => seed.toString());
+  }
+
+  const closeRegistry = () =>
+    program.methods
+      .closeRegistry()
+      .accountsPartial({ maker: fx.maker.publicKey })
+      .signers([fx.maker])
+      .rpc();
+
+  it('lists the seeds of open escrows', async () => {
+    const first = await makeEscrow(fx, { amount: 100 });
+    const second = await makeEscrow(fx, { amount: 100 });
+    const third = await makeEscrow(fx, { amount: 100 });
+    expect(await fetchSeeds()).to.deep.equal([
+      first.seed.toString(),
+      second.seed.toString(),
+      third.seed.toString(),
+    ]);
+
+    await takeEscrow(fx, first.escrow);
+    await refundEscrow(fx, third.escrow);
+    expect(await fetchSeeds()).to.deep.equal([second.seed.toString()]);
+  });
+
+  it('closes only once every escrow is closed', async () => {
+    const registry = findRegistry(fx.maker.publicKey);
+    const { escrow } = await makeEscrow(fx, { amount: 100 });
+
+    await expectError(closeRegistry(), 'RegistryNotEmpty');
+
+    await refundEscrow(fx, escrow);
+    await closeRegistry();
+    expect(await connection.getAccountInfo(registry)).to.be.null;
+
+    // 下一次 make 重新创建索引
+    const next = await makeEscrow(fx, { amount: 100 });
+    expect(await fetchSeeds()).to.deep.equal([next.seed.toString()]);
+  });
+});
@@ -0,0 +1,229 @@
+import { getOrCreateAssociatedTokenAccount } from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  feeAuthorityPda,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  setFee,
+  tokenBalance,
+} from './utils';
+
+describe('match_escrows', () => {
+  let fx: Fixture;
+  // taker 作为 maker 用 token B 换 token A 的反方向 fixture
+  let reversed: Fixture;
+  let matcher: Keypair;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    reversed = {
+      maker: fx.taker,
+      taker: fx.maker,
+      mintA: fx.mintB,
+      mintB: fx.mintA,
+      makerAtaA: fx.takerAtaB,
+      takerAtaB: fx.makerAtaA,
+      tokenProgramA: fx.tokenProgramB,
+      tokenProgramB: fx.tokenProgramA,
+    };
+    matcher = await fundedKeypair();
+
+    // 双方接收对方 token 的 ATA 必须已经存在
+    await getOrCreateAssociatedTokenAccount(
+      connection,
+      fx.maker,
+      fx.mintB,
+      fx.maker.publicKey
+    );
+    await getOrCreateAssociatedTokenAccount(
+      connection,
+      fx.taker,
+      fx.mintA,
+      fx.taker.publicKey
+    );
+  });
+
+  const matcherAtas = async () => ({
+    a: (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        matcher,
+        fx.mintA,
+        matcher.publicKey
+      )
+    ).address,
+    b: (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        matcher,
+        fx.mintB,
+        matcher.publicKey
+      )
+    ).address,
+  });
+
+  const matchEscrows = (
+    escrowX: PublicKey,
+    escrowY: PublicKey,
+    surplusTo: { a: PublicKey; b: PublicKey } = { a: null, b: null },
+    makerY = fx.taker.publicKey,
+    feeVaults: { a: PublicKey; b: PublicKey } = { a: null, b: null }
+  ) =>
+    program.methods
+      .matchEscrows()
+      .accountsPartial({
+        matcher: matcher.publicKey,
+        makerX: fx.maker.publicKey,
+        makerY,
+        rentPayerX: fx.maker.publicKey,
+        rentPayerY: makerY,
+        escrowX,
+        escrowY,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        vaultX: ata(fx.mintA, escrowX),
+        vaultY: ata(fx.mintB, escrowY),
+        makerXAtaA: fx.makerAtaA,
+        makerXAtaB: ata(fx.mintB, fx.maker.publicKey),
+        makerYAtaA: ata(fx.mintA, makerY),
+        makerYAtaB: ata(fx.mintB, makerY),
+        matcherAtaA: surplusTo.a,
+        matcherAtaB: surplusTo.b,
+        feeVaultA: feeVaults.a,
+        feeVaultB: feeVaults.b,
+        pairIndexX: null,
+        pairIndexY: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .signers([matcher])
+      .rpc();
+
+  it('settles an exact cross', async () => {
+    const x = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const y = await makeEscrow(reversed, { amount: 500, receive: 1_000 });
+
+    await matchEscrows(x.escrow, y.escrow);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      500n
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    for (const account of [x.escrow, x.vault, y.escrow, y.vault]) {
+      expect(await connection.getAccountInfo(account)).to.be.null;
+    }
+  });
+
+  it('pays the surplus of a crossing to the matcher', async () => {
+    // x 只要 400 B, y 只要 800 A, 差额为 200 A 和 100 B
+    const x = await makeEscrow(fx, { amount: 1_000, receive: 400 });
+    const y = await makeEscrow(reversed, { amount: 500, receive: 800 });
+    const surplusTo = await matcherAtas();
+
+    await matchEscrows(x.escrow, y.escrow, surplusTo);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      400n
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      800n
+    );
+    expect(await tokenBalance(surplusTo.a)).to.equal(200n);
+    expect(await tokenBalance(surplusTo.b)).to.equal(100n);
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n - 1_000n);
+  });
+
+  it('returns the surplus to the makers without matcher accounts', async () => {
+    const x = await makeEscrow(fx, { amount: 1_000, receive: 400 });
+    const y = await makeEscrow(reversed, { amount: 500, receive: 800 });
+
+    await matchEscrows(x.escrow, y.escrow);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n - 800n);
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n - 400n);
+  });
+
+  describe('with a protocol fee', () => {
+    beforeEach(async () => {
+      await setFee(1_000);
+    });
+
+    afterEach(async () => {
+      await setFee(0);
+    });
+
+    const feeVaults = async () => {
+      const vault = async (mint: PublicKey) =>
+        (
+          await getOrCreateAssociatedTokenAccount(
+            connection,
+            matcher,
+            mint,
+            feeAuthorityPda,
+            true
+          )
+        ).address;
+      return { a: await vault(fx.mintA), b: await vault(fx.mintB) };
+    };
+
+    it('charges the fee on both legs', async () => {
+      const x = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+      const y = await makeEscrow(reversed, { amount: 500, receive: 1_000 });
+      const vaults = await feeVaults();
+      const before = {
+        a: await tokenBalance(vaults.a),
+        b: await tokenBalance(vaults.b),
+      };
+
+      await matchEscrows(x.escrow, y.escrow, undefined, undefined, vaults);
+
+      // 10% 的手续费由收款的 maker 承担
+      expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+        450n
+      );
+      expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+        900n
+      );
+      expect((await tokenBalance(vaults.a)) - before.a).to.equal(100n);
+      expect((await tokenBalance(vaults.b)) - before.b).to.equal(50n);
+    });
+
+    it('requires the fee vaults', async () => {
+      const x = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+      const y = await makeEscrow(reversed, { amount: 500, receive: 1_000 });
+
+      await expectError(matchEscrows(x.escrow, y.escrow), 'MissingFeeVault');
+    });
+  });
+
+  it('rejects prices that do not cross', async () => {
+    // x 要 600 B, y 只出售 500 B
+    const x = await makeEscrow(fx, { amount: 1_000, receive: 600 });
+    const y = await makeEscrow(reversed, { amount: 500, receive: 1_000 });
+
+    await expectError(matchEscrows(x.escrow, y.escrow), 'PricesDoNotCross');
+    expect(await connection.getAccountInfo(x.escrow)).to.not.be.null;
+    expect(await connection.getAccountInfo(y.escrow)).to.not.be.null;
+  });
+
+  it('rejects two escrows on the same side', async () => {
+    const x = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const other = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+
+    await expectError(
+      matchEscrows(x.escrow, other.escrow, undefined, fx.maker.publicKey),
+      'MintsDoNotMatch'
+    );
+  });
+});
@@ -0,0 +1,51 @@
+import { TOKEN_2022_PROGRAM_ID } from '@solana/spl-token';
+import { expect } from 'chai';
+import {
+  Fixture,
+  createFixture,
+  makeEscrow,
+  refundEscrow,
+  requireMemos,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('memo-required destinations', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture(
+      1_000_000,
+      1_000_000,
+      TOKEN_2022_PROGRAM_ID,
+      TOKEN_2022_PROGRAM_ID
+    );
+  });
+
+  it('pays a maker whose token B account requires memos', async () => {
+    const makerAtaB = await requireMemos(fx.maker, fx.mintB);
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(makerAtaB)).to.equal(500n);
+  });
+
+  it('pays a taker whose token A account requires memos', async () => {
+    const takerAtaA = await requireMemos(fx.taker, fx.mintA);
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(takerAtaA)).to.equal(1_000n);
+  });
+
+  it('refunds into a maker account that requires memos', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+    await requireMemos(fx.maker, fx.mintA);
+
+    await refundEscrow(fx, escrow);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+  });
+});
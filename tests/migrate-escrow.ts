@@ -0,0 +1,45 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+} from './utils';
+
+// 旧布局的托管只能在升级之前创建, 本地验证器无法构造, 完整的迁移流程见 lifecycle.rs
+describe('migrate_escrow', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('records the version and the vault at make', async () => {
+    const { escrow, vault } = await makeEscrow(fx);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.version).to.equal(12);
+    expect(state.vault.equals(vault)).to.be.true;
+    expect(vault.equals(ata(fx.mintA, escrow, fx.tokenProgramA))).to.be.true;
+  });
+
+  it('rejects an escrow at the current version', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await expectError(
+      program.methods
+        .migrateEscrow()
+        .accountsPartial({
+          payer: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          tokenProgram: fx.tokenProgramA,
+        })
+        .signers([fx.maker])
+        .rpc(),
+      'EscrowNotLegacy'
+    );
+  });
+});
@@ -0,0 +1,74 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  mintAllowlistPda,
+  program,
+  refundEscrow,
+  setAllowedMints,
+  takeEscrow,
+} from './utils';
+
+describe('mint allowlist', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  // 白名单是全局的, 每个测试结束后关闭, 不影响其他测试文件
+  afterEach(async () => {
+    await setAllowedMints(false, []);
+  });
+
+  it('only escrows listed mints while enabled', async () => {
+    await setAllowedMints(true, [fx.mintA]);
+    await expectError(makeEscrow(fx, { amount: 100 }), 'MintNotAllowed');
+
+    await setAllowedMints(true, [fx.mintA, fx.mintB]);
+    const { escrow } = await makeEscrow(fx, { amount: 100 });
+    expect(await program.account.escrow.fetch(escrow)).to.not.be.null;
+  });
+
+  it('ignores the list while disabled', async () => {
+    await setAllowedMints(false, [fx.mintA]);
+    await makeEscrow(fx, { amount: 100 });
+  });
+
+  it('keeps existing escrows takeable and refundable', async () => {
+    const taken = await makeEscrow(fx, { amount: 100 });
+    const refunded = await makeEscrow(fx, { amount: 100 });
+
+    // 两个 mint 都不在新的列表中
+    await setAllowedMints(true, []);
+
+    await takeEscrow(fx, taken.escrow);
+    await refundEscrow(fx, refunded.escrow);
+    expect(await connection.getAccountInfo(taken.escrow)).to.be.null;
+    expect(await connection.getAccountInfo(refunded.escrow)).to.be.null;
+  });
+
+  it('resizes the account to fit the list', async () => {
+    const fetchMints = async () => {
+      const { mints } =
+        await program.account.mintAllowlist.fetch(mintAllowlistPda);
+      return mints.map((mint) => mint.toBase58());
+    };
+    const dataLength = async () =>
+      (await connection.getAccountInfo(mintAllowlistPda)).data.length;
+
+    await setAllowedMints(true, [fx.mintA, fx.mintB]);
+    expect(await fetchMints()).to.deep.equal([
+      fx.mintA.toBase58(),
+      fx.mintB.toBase58(),
+    ]);
+    const longer = await dataLength();
+
+    await setAllowedMints(true, [fx.mintB]);
+    expect(await fetchMints()).to.deep.equal([fx.mintB.toBase58()]);
+    expect(await dataLength()).to.equal(longer - 32);
+  });
+});
@@ -0,0 +1,96 @@
+import { BN } from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  mintBlocklistPda,
+  program,
+  refundEscrow,
+  setBlocked,
+  takeEscrow,
+} from './utils';
+
+describe('mint blocklist', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  // 黑名单是全局的, 每个测试结束后解封, 不影响其他测试文件
+  afterEach(async () => {
+    await setBlocked(fx.mintA, false);
+    await setBlocked(fx.mintB, false);
+  });
+
+  const fetchBlocked = async () => {
+    const { mints } = await program.account.mintBlocklist.fetch(
+      mintBlocklistPda
+    );
+    return mints.map((mint) => mint.toBase58());
+  };
+
+  // 黑名单是必须传入的账户, 不指定时由 anchor 按 seeds 解析, 调用方不能跳过检查
+  it('rejects make with a blocked mint', async () => {
+    await setBlocked(fx.mintB, true);
+
+    await expectError(makeEscrow(fx, { amount: 100 }), 'MintBlocked');
+  });
+
+  it('refunds but never fills escrows in a blocked mint', async () => {
+    const taken = await makeEscrow(fx, { amount: 100 });
+    const partial = await makeEscrow(fx, { amount: 100 });
+    const refunded = await makeEscrow(fx, { amount: 100 });
+
+    await setBlocked(fx.mintA, true);
+
+    await expectError(takeEscrow(fx, taken.escrow, fx.taker), 'MintBlocked');
+    await expectError(
+      program.methods
+        .takePartial(new BN(50), U64_MAX, [], Buffer.alloc(0))
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow: partial.escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          feeVaultB: null,
+          referrerAtaB: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([fx.taker])
+        .rpc(),
+      'MintBlocked'
+    );
+    await refundEscrow(fx, partial.escrow);
+    await refundEscrow(fx, taken.escrow);
+    await refundEscrow(fx, refunded.escrow);
+    expect(await connection.getAccountInfo(taken.escrow)).to.be.null;
+    expect(await connection.getAccountInfo(refunded.escrow)).to.be.null;
+  });
+
+  it('adds and removes mints idempotently', async () => {
+    await setBlocked(fx.mintA, true);
+    await setBlocked(fx.mintA, true);
+    expect(await fetchBlocked()).to.include(fx.mintA.toBase58());
+    expect(
+      (await fetchBlocked()).filter((mint) => mint === fx.mintA.toBase58())
+    ).to.have.length(1);
+
+    await setBlocked(fx.mintA, false);
+    await setBlocked(fx.mintA, false);
+    expect(await fetchBlocked()).to.not.include(fx.mintA.toBase58());
+
+    // 解封后可以正常成交
+    const { escrow } = await makeEscrow(fx, { amount: 100 });
+    await takeEscrow(fx, escrow, fx.taker);
+  });
+});
@@ -0,0 +1,102 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('mixed token programs', () => {
+  const pairs: [string, PublicKey, PublicKey][] = [
+    ['Token-2022 for legacy', TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID],
+    ['legacy for Token-2022', TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID],
+  ];
+
+  for (const [name, tokenProgramA, tokenProgramB] of pairs) {
+    describe(name, () => {
+      let fx: Fixture;
+
+      beforeEach(async () => {
+        fx = await createFixture(
+          1_000_000,
+          1_000_000,
+          tokenProgramA,
+          tokenProgramB
+        );
+      });
+
+      it('swaps on take', async () => {
+        const { escrow, vault } = await makeEscrow(fx, {
+          amount: 1_000,
+          receive: 500,
+        });
+
+        await takeEscrow(fx, escrow);
+
+        expect(
+          await tokenBalance(ata(fx.mintA, fx.taker.publicKey, tokenProgramA))
+        ).to.equal(1_000n);
+        expect(
+          await tokenBalance(ata(fx.mintB, fx.maker.publicKey, tokenProgramB))
+        ).to.equal(500n);
+        expect(await connection.getAccountInfo(vault)).to.be.null;
+      });
+
+      it('swaps on take_partial', async () => {
+        const { escrow, vault } = await makeEscrow(fx, {
+          amount: 1_000,
+          receive: 1_000,
+        });
+
+        await program.methods
+          .takePartial(new BN(400), U64_MAX, [], Buffer.alloc(0))
+          .accountsPartial({
+            taker: fx.taker.publicKey,
+            maker: fx.maker.publicKey,
+            escrow,
+            mintA: fx.mintA,
+            mintB: fx.mintB,
+            feeVaultB: null,
+            referrerAtaB: null,
+            takerStats: null,
+            makerStats: null,
+            pairIndex: null,
+            tokenProgramA,
+            tokenProgramB,
+          })
+          .signers([fx.taker])
+          .rpc();
+
+        expect(await tokenBalance(vault)).to.equal(600n);
+        expect(
+          await tokenBalance(ata(fx.mintB, fx.maker.publicKey, tokenProgramB))
+        ).to.equal(400n);
+      });
+
+      it('rejects the programs passed the wrong way round', async () => {
+        const { escrow } = await makeEscrow(fx);
+
+        await expectError(
+          takeEscrow(
+            {
+              ...fx,
+              tokenProgramA: tokenProgramB,
+              tokenProgramB: tokenProgramA,
+            },
+            escrow
+          ),
+          'WrongTokenProgram'
+        );
+      });
+    });
+  }
+});
@@ -0,0 +1,162 @@
+import { BN } from '@coral-xyz/anchor';
+import {
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import {
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  sendAndConfirmTransaction,
+} from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  EMPTY_ROOT,
+  Fixture,
+  ata,
+  connection,
+  ensureConfig,
+  findEscrow,
+  fundedKeypair,
+  program,
+  randomSeed,
+  refundEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// maker 直接用 SOL 出售, 没有提前创建 wSOL ATA; taker 持有普通的 token B
+async function createNativeFixture(): Promise<Fixture> {
+  await ensureConfig();
+  const maker = await fundedKeypair();
+  const taker = await fundedKeypair();
+
+  const mintB = await createMint(connection, taker, taker.publicKey, null, 6);
+  const takerAtaB = await getOrCreateAssociatedTokenAccount(
+    connection,
+    taker,
+    mintB,
+    taker.publicKey
+  );
+  await mintTo(connection, taker, mintB, takerAtaB.address, taker, 1_000_000);
+
+  return {
+    maker,
+    taker,
+    mintA: NATIVE_MINT,
+    mintB,
+    makerAtaA: ata(NATIVE_MINT, maker.publicKey),
+    takerAtaB: takerAtaB.address,
+    tokenProgramA: TOKEN_PROGRAM_ID,
+    tokenProgramB: TOKEN_PROGRAM_ID,
+  };
+}
+
+describe('native SOL as token A', () => {
+  const amount = LAMPORTS_PER_SOL;
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createNativeFixture();
+  });
+
+  // makerAtaA 传 null, 由程序把 maker 的 lamports 包装进 vault
+  const makeWithSol = async () => {
+    const seed = randomSeed();
+    await program.methods
+      .make(
+        seed,
+        new BN(1_000),
+        new BN(amount),
+        new BN(0),
+        PublicKey.default,
+        EMPTY_ROOT,
+        EMPTY_ROOT,
+        new BN(0),
+        false,
+        PublicKey.default,
+        PublicKey.default,
+        new BN(0),
+        PublicKey.default,
+        new BN(0),
+        [],
+        false,
+        0
+      )
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        rentPayer: fx.maker.publicKey,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        exemptList: null,
+        treasury: null,
+        makerAtaA: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .signers([fx.maker])
+      .rpc();
+
+    const escrow = findEscrow(fx.maker.publicKey, seed);
+    return { escrow, vault: ata(NATIVE_MINT, escrow) };
+  };
+
+  it('wraps the maker lamports into the vault', async () => {
+    const before = await connection.getBalance(fx.maker.publicKey);
+
+    const { escrow, vault } = await makeWithSol();
+
+    expect(await tokenBalance(vault)).to.equal(BigInt(amount));
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.amount.toNumber()).to.equal(amount);
+    expect(await connection.getBalance(fx.maker.publicKey)).to.be.lessThan(
+      before - amount
+    );
+  });
+
+  it('pays the taker in wSOL on take', async () => {
+    const { escrow, vault } = await makeWithSol();
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(NATIVE_MINT, fx.taker.publicKey))).to.equal(
+      BigInt(amount)
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('refunds the maker in wSOL', async () => {
+    const { escrow } = await makeWithSol();
+
+    await refundEscrow(fx, escrow);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(BigInt(amount));
+  });
+
+  it('syncs lamports sent straight to the vault before take', async () => {
+    const { escrow, vault } = await makeWithSol();
+    const tx = new Transaction().add(
+      SystemProgram.transfer({
+        fromPubkey: fx.taker.publicKey,
+        toPubkey: vault,
+        lamports: 5_000,
+      })
+    );
+    await sendAndConfirmTransaction(connection, tx, [fx.taker]);
+
+    await takeEscrow(fx, escrow);
+
+    // 多余的 lamports 和其他多余代币一样退还给 maker
+    expect(await tokenBalance(ata(NATIVE_MINT, fx.taker.publicKey))).to.equal(
+      BigInt(amount)
+    );
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(5_000n);
+  });
+});
@@ -0,0 +1,65 @@
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createInitializeNonTransferableMintInstruction,
+  createMint,
+} from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import {
+  connection,
+  createFixtureWithMints,
+  createMintWithExtensions,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+} from './utils';
+
+// 这类 mint 的 token 只能铸造和销毁, 不能在账户之间转移
+const createNonTransferableMint = (authority: Keypair) =>
+  createMintWithExtensions(
+    authority,
+    [ExtensionType.NonTransferable],
+    (mint) => [
+      createInitializeNonTransferableMintInstruction(
+        mint,
+        TOKEN_2022_PROGRAM_ID
+      ),
+    ]
+  );
+
+describe('non-transferable mints', () => {
+  it('rejects a non-transferable mint A', async () => {
+    const maker = await fundedKeypair();
+    const taker = await fundedKeypair();
+    const mintA = await createNonTransferableMint(maker);
+    const mintB = await createMint(connection, taker, taker.publicKey, null, 6);
+    const fx = await createFixtureWithMints(
+      maker,
+      taker,
+      mintA,
+      mintB,
+      TOKEN_2022_PROGRAM_ID,
+      TOKEN_PROGRAM_ID
+    );
+
+    await expectError(makeEscrow(fx), 'NonTransferableMint');
+  });
+
+  it('rejects a non-transferable mint B', async () => {
+    const maker = await fundedKeypair();
+    const taker = await fundedKeypair();
+    const mintA = await createMint(connection, maker, maker.publicKey, null, 6);
+    const mintB = await createNonTransferableMint(taker);
+    const fx = await createFixtureWithMints(
+      maker,
+      taker,
+      mintA,
+      mintB,
+      TOKEN_PROGRAM_ID,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await expectError(makeEscrow(fx), 'NonTransferableMint');
+  });
+});
@@ -0,0 +1,233 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  configPda,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  nowSeconds,
+  program,
+  randomSeed,
+  refundEscrow,
+  setFee,
+  setPaused,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('pause', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  afterEach(async () => {
+    await setPaused(false);
+  });
+
+  it('blocks make', async () => {
+    await setPaused(true);
+
+    await expectError(makeEscrow(fx), 'ProtocolPaused');
+  });
+
+  it('blocks make_dutch', async () => {
+    await setPaused(true);
+    const now = nowSeconds();
+
+    await expectError(
+      program.methods
+        .makeDutch(
+          randomSeed(),
+          new BN(2_000),
+          new BN(1_000),
+          new BN(1_000),
+          new BN(now),
+          new BN(now + 60)
+        )
+        .accountsPartial({
+          maker: fx.maker.publicKey,
+          rentPayer: fx.maker.publicKey,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          exemptList: null,
+          treasury: null,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .signers([fx.maker])
+        .rpc(),
+      'ProtocolPaused'
+    );
+  });
+
+  it('blocks take', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await setPaused(true);
+
+    await expectError(takeEscrow(fx, escrow), 'ProtocolPaused');
+    expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+  });
+
+  it('blocks take_partial', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await setPaused(true);
+
+    await expectError(
+      program.methods
+        .takePartial(new BN(500), U64_MAX, [], Buffer.alloc(0))
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          feeVaultB: null,
+          referrerAtaB: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .signers([fx.taker])
+        .rpc(),
+      'ProtocolPaused'
+    );
+  });
+
+  it('still allows refund', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+    await setPaused(true);
+
+    await refundEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+  });
+
+  it('still allows refund_expired', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      expiry: nowSeconds() + 2,
+    });
+    await setPaused(true);
+    await sleep(3_000);
+    const cranker = await fundedKeypair();
+
+    await program.methods
+      .refundExpired()
+      .accountsPartial({
+        refund: {
+          authority: cranker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          vault,
+          makerAtaA: fx.makerAtaA,
+          pairIndex: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      })
+      .signers([cranker])
+      .rpc();
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('still allows update_receive', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 500 });
+    await setPaused(true);
+
+    await program.methods
+      .updateReceive(new BN(800))
+      .accountsPartial({ maker: fx.maker.publicKey, escrow })
+      .signers([fx.maker])
+      .rpc();
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.receive.toNumber()).to.equal(800);
+  });
+
+  it('blocks top_up', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+    await setPaused(true);
+
+    await expectError(
+      program.methods
+        .topUp(new BN(500))
+        .accountsPartial({
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([fx.maker])
+        .rpc(),
+      'ProtocolPaused'
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('still allows withdraw_partial', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+    await setPaused(true);
+
+    await program.methods
+      .withdrawPartial(new BN(400))
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.maker])
+      .rpc();
+
+    expect(await tokenBalance(vault)).to.equal(600n);
+  });
+
+  it('still allows set_fee', async () => {
+    await setPaused(true);
+
+    await setFee(10);
+    await setFee(0);
+
+    expect((await program.account.config.fetch(configPda)).feeBps).to.equal(0);
+  });
+
+  it('resumes make and take after unpause', async () => {
+    await setPaused(true);
+    await setPaused(false);
+
+    const { escrow } = await makeEscrow(fx, { receive: 700 });
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      700n
+    );
+  });
+
+  it('rejects pause from a non-admin', async () => {
+    const other = await fundedKeypair();
+
+    await expectError(
+      program.methods
+        .pause()
+        .accountsPartial({ admin: other.publicKey })
+        .signers([other])
+        .rpc(),
+      'InvalidAdmin'
+    );
+    const config = await program.account.config.fetch(configPda);
+    expect(config.paused).to.be.false;
+  });
+});
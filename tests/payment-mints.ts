@@ -0,0 +1,117 @@
+import { BN } from '@coral-xyz/anchor';
+import {
+  createMint,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('payment mints', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  // 创建一个 decimals 不同的稳定币, 给 taker 充值
+  const fundedMint = async (decimals = 9) => {
+    const mint = await createMint(
+      connection,
+      fx.taker,
+      fx.taker.publicKey,
+      null,
+      decimals
+    );
+    const account = await getOrCreateAssociatedTokenAccount(
+      connection,
+      fx.taker,
+      mint,
+      fx.taker.publicKey
+    );
+    await mintTo(
+      connection,
+      fx.taker,
+      mint,
+      account.address,
+      fx.taker,
+      1_000_000_000
+    );
+    return mint;
+  };
+
+  // 用另一种 mint 代替 fixture 的 mint B 成交
+  const takeIn = (escrow: PublicKey, mintB: PublicKey) =>
+    takeEscrow({ ...fx, mintB }, escrow);
+
+  it('fills with the second listed mint at its own price', async () => {
+    const mintC = await fundedMint();
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 800,
+      altPayments: [{ mint: mintC, receive: new BN(800_000) }],
+    });
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.altPayments[0].mint.toBase58()).to.equal(mintC.toBase58());
+    expect(state.altPayments[0].receive.toNumber()).to.equal(800_000);
+
+    await takeIn(escrow, mintC);
+
+    expect(await tokenBalance(ata(mintC, fx.maker.publicKey))).to.equal(
+      800_000n
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    // maker 没有收到任何 mint B
+    const makerAtaB = ata(fx.mintB, fx.maker.publicKey);
+    expect(await connection.getAccountInfo(makerAtaB)).to.be.null;
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('rejects an unlisted mint', async () => {
+    const [listed, unlisted] = [await fundedMint(), await fundedMint()];
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      altPayments: [{ mint: listed, receive: new BN(1_000) }],
+    });
+
+    await expectError(takeIn(escrow, unlisted), 'InvalidMintB');
+    // 主 mint B 仍然按原价成交
+    await takeEscrow(fx, escrow);
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      1_000n
+    );
+  });
+
+  it('rejects duplicate or unpriced payment mints', async () => {
+    const mintC = await fundedMint();
+    for (const altPayments of [
+      [{ mint: fx.mintB, receive: new BN(1_000) }],
+      [
+        { mint: mintC, receive: new BN(1_000) },
+        { mint: mintC, receive: new BN(2_000) },
+      ],
+      [{ mint: mintC, receive: new BN(0) }],
+    ]) {
+      await expectError(
+        makeEscrow(fx, { amount: 1_000, altPayments }),
+        'InvalidPaymentMints'
+      );
+    }
+  });
+});
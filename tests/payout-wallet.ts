@@ -0,0 +1,109 @@
+import { BN } from '@coral-xyz/anchor';
+import { getOrCreateAssociatedTokenAccount } from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('payout wallet', () => {
+  let fx: Fixture;
+  let treasury: PublicKey;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    treasury = Keypair.generate().publicKey;
+  });
+
+  // maker 自己的 token B ATA 已经存在, 用来检查 taker 不能把 token B 转到这里
+  const createMakerAtaB = async () =>
+    (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.maker,
+        fx.mintB,
+        fx.maker.publicKey
+      )
+    ).address;
+
+  const take = (
+    escrow: PublicKey,
+    receiveTo: PublicKey,
+    makerAtaB: PublicKey
+  ) =>
+    program.methods
+      .take(U64_MAX, new BN(0), [], Buffer.alloc(0), false)
+      .accountsPartial({
+        taker: fx.taker.publicKey,
+        payer: fx.taker.publicKey,
+        maker: fx.maker.publicKey,
+        receiveTo,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        takerTokenA: null,
+        takerAtaB: fx.takerAtaB,
+        makerAtaB,
+        feeVaultB: null,
+        referrerAtaB: null,
+        unwrapAtaB: null,
+        receipt: null,
+        takerStats: null,
+        makerStats: null,
+        pairIndex: null,
+        priceFeed: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .signers([fx.taker])
+      .rpc();
+
+  it('defaults the payout wallet to the maker', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.receiveTo.equals(fx.maker.publicKey)).to.be.true;
+  });
+
+  it('pays token B to the designated wallet', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      receive: 500,
+      receiveTo: treasury,
+    });
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintB, treasury))).to.equal(500n);
+    const makerAtaB = ata(fx.mintB, fx.maker.publicKey);
+    expect(await connection.getAccountInfo(makerAtaB)).to.be.null;
+  });
+
+  it('rejects a token B account owned by someone else', async () => {
+    const { escrow } = await makeEscrow(fx, { receiveTo: treasury });
+    const makerAtaB = await createMakerAtaB();
+
+    await expectError(
+      take(escrow, treasury, makerAtaB),
+      'ConstraintTokenOwner'
+    );
+  });
+
+  it('rejects a payout wallet other than the stored one', async () => {
+    const { escrow } = await makeEscrow(fx, { receiveTo: treasury });
+    const makerAtaB = await createMakerAtaB();
+
+    await expectError(
+      take(escrow, fx.maker.publicKey, makerAtaB),
+      'InvalidReceiveTo'
+    );
+  });
+});
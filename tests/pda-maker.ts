@@ -0,0 +1,153 @@
+import * as anchor from '@coral-xyz/anchor';
+import { BN, Program } from '@coral-xyz/anchor';
+import { getOrCreateAssociatedTokenAccount, mintTo } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import { PdaMaker } from '../target/types/pda_maker';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  configPda,
+  connection,
+  createFixture,
+  ensureConfig,
+  findEscrow,
+  findRegistry,
+  mintAllowlistPda,
+  mintBlocklistPda,
+  program,
+  randomSeed,
+  statsPda,
+  tokenBalance,
+} from './utils';
+
+const pdaMaker = anchor.workspace.pdaMaker as Program<PdaMaker>;
+
+// 示例程序的金库 PDA, 没有私钥, 由示例程序通过 invoke_signed 签名
+const treasury = PublicKey.findProgramAddressSync(
+  [Buffer.from('treasury')],
+  pdaMaker.programId
+)[0];
+
+const eventAuthority = PublicKey.findProgramAddressSync(
+  [Buffer.from('__event_authority')],
+  program.programId
+)[0];
+
+describe('PDA maker through CPI', () => {
+  let fx: Fixture;
+  let treasuryAtaA: PublicKey;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+
+    // 金库是 off-curve 地址, 创建 ATA 时需要 allowOwnerOffCurve
+    treasuryAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.maker,
+        fx.mintA,
+        treasury,
+        true
+      )
+    ).address;
+    await mintTo(connection, fx.maker, fx.mintA, treasuryAtaA, fx.maker, 1_000);
+  });
+
+  // 由示例程序代替金库签名创建托管, 租金由 fixture 的 maker 支付
+  async function makeFromTreasury(seed: BN) {
+    const escrow = findEscrow(treasury, seed);
+    await pdaMaker.methods
+      .makeEscrow(seed, new BN(500), new BN(1_000))
+      .accountsPartial({
+        payer: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        treasuryAtaA,
+        vault: ata(fx.mintA, escrow),
+        config: configPda,
+        mintAllowlist: mintAllowlistPda,
+        mintBlocklist: mintBlocklistPda,
+        stats: statsPda,
+        registry: findRegistry(treasury),
+        eventAuthority,
+        escrowProgram: program.programId,
+        tokenProgram: fx.tokenProgramA,
+      })
+      .signers([fx.maker])
+      .rpc();
+    return escrow;
+  }
+
+  it('creates an escrow owned by the treasury', async () => {
+    const escrow = await makeFromTreasury(randomSeed());
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.maker.equals(treasury)).to.be.true;
+    expect(state.rentPayer.equals(fx.maker.publicKey)).to.be.true;
+    expect(await tokenBalance(ata(fx.mintA, escrow))).to.equal(1_000n);
+    expect(await tokenBalance(treasuryAtaA)).to.equal(0n);
+  });
+
+  it('pays token B into an ATA of the off-curve maker', async () => {
+    const escrow = await makeFromTreasury(randomSeed());
+
+    await program.methods
+      .take(U64_MAX, new BN(0), [], Buffer.alloc(0), false)
+      .accountsPartial({
+        taker: fx.taker.publicKey,
+        payer: fx.taker.publicKey,
+        maker: treasury,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        takerTokenA: null,
+        takerAtaB: fx.takerAtaB,
+        feeVaultB: null,
+        referrerAtaB: null,
+        unwrapAtaB: null,
+        receipt: null,
+        takerStats: null,
+        makerStats: null,
+        pairIndex: null,
+        priceFeed: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .signers([fx.taker])
+      .rpc();
+
+    expect(await tokenBalance(ata(fx.mintB, treasury))).to.equal(500n);
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+  });
+
+  it('refunds the escrow through CPI', async () => {
+    const escrow = await makeFromTreasury(randomSeed());
+    const vault = ata(fx.mintA, escrow);
+
+    await pdaMaker.methods
+      .refundEscrow()
+      .accountsPartial({
+        rentPayer: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        vault,
+        treasuryAtaA,
+        stats: statsPda,
+        registry: findRegistry(treasury),
+        eventAuthority,
+        escrowProgram: program.programId,
+        tokenProgram: fx.tokenProgramA,
+      })
+      .rpc();
+
+    expect(await tokenBalance(treasuryAtaA)).to.equal(1_000n);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+});
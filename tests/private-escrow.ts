@@ -0,0 +1,81 @@
+import { getOrCreateAssociatedTokenAccount, mintTo } from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+} from './utils';
+
+describe('private escrows', () => {
+  let fx: Fixture;
+  let stranger: Keypair;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+
+    // 陌生人同样持有 token B, 只会因为不是指定的 taker 而失败
+    stranger = await fundedKeypair();
+    const strangerAtaB = await getOrCreateAssociatedTokenAccount(
+      connection,
+      stranger,
+      fx.mintB,
+      stranger.publicKey
+    );
+    await mintTo(
+      connection,
+      fx.taker,
+      fx.mintB,
+      strangerAtaB.address,
+      fx.taker,
+      1_000_000
+    );
+  });
+
+  it('lets anyone take an open escrow', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await takeEscrow(fx, escrow, stranger);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('lets the designated taker take a restricted escrow', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      allowedTaker: fx.taker.publicKey,
+    });
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.allowedTaker.toBase58()).to.equal(
+      fx.taker.publicKey.toBase58()
+    );
+
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('rejects a stranger on a restricted escrow', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      allowedTaker: fx.taker.publicKey,
+    });
+
+    await expectError(takeEscrow(fx, escrow, stranger), 'UnauthorizedTaker');
+  });
+
+  it('still lets the maker refund a restricted escrow', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      allowedTaker: fx.taker.publicKey,
+    });
+
+    await refundEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+});
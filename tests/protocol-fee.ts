@@ -0,0 +1,186 @@
+import * as anchor from '@coral-xyz/anchor';
+import { BN } from '@coral-xyz/anchor';
+import {
+  TOKEN_PROGRAM_ID,
+  getOrCreateAssociatedTokenAccount,
+} from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  configPda,
+  connection,
+  createFixture,
+  expectError,
+  feeAuthorityPda,
+  fetchEvents,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  provider,
+  setFee,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// spl-token 的 TransferChecked 指令编号
+const TRANSFER_CHECKED = 12;
+
+// 统计交易中通过 CPI 调用的 TransferChecked 次数
+async function countTransfers(signature: string) {
+  await connection.confirmTransaction(signature, 'confirmed');
+  const tx = await connection.getTransaction(signature, {
+    commitment: 'confirmed',
+    maxSupportedTransactionVersion: 0,
+  });
+  const accountKeys = tx.transaction.message.getAccountKeys();
+
+  return tx.meta.innerInstructions
+    .flatMap((inner) => inner.instructions)
+    .filter((ix) => accountKeys.get(ix.programIdIndex).equals(TOKEN_PROGRAM_ID))
+    .filter(
+      (ix) => anchor.utils.bytes.bs58.decode(ix.data)[0] === TRANSFER_CHECKED
+    ).length;
+}
+
+describe('protocol fee', () => {
+  let fx: Fixture;
+  let feeVaultB: PublicKey;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    feeVaultB = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.taker,
+        fx.mintB,
+        feeAuthorityPda,
+        true
+      )
+    ).address;
+  });
+
+  afterEach(async () => {
+    await setFee(0);
+  });
+
+  it('initializes the config with the provider as admin', async () => {
+    const config = await program.account.config.fetch(configPda);
+
+    expect(config.admin.toBase58()).to.equal(
+      provider.wallet.publicKey.toBase58()
+    );
+  });
+
+  it('charges nothing and makes no extra transfer at 0 bps', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 1_000 });
+
+    const signature = await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(feeVaultB)).to.equal(0n);
+    // 只有 token B 给 maker 和 token A 给 taker 两次转账
+    expect(await countTransfers(signature)).to.equal(2);
+  });
+
+  it('splits the payment between maker and fee vault', async () => {
+    await setFee(25);
+    const { escrow } = await makeEscrow(fx, { receive: 10_000 });
+
+    const signature = await takeEscrow(fx, escrow, fx.taker, { feeVaultB });
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      9_975n
+    );
+    expect(await tokenBalance(feeVaultB)).to.equal(25n);
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n - 10_000n);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.data.amountB.toNumber()).to.equal(10_000);
+    expect(event.data.fee.toNumber()).to.equal(25);
+  });
+
+  it('rounds the fee down in favor of the maker', async () => {
+    await setFee(30);
+    const { escrow } = await makeEscrow(fx, { receive: 1_999 });
+
+    await takeEscrow(fx, escrow, fx.taker, { feeVaultB });
+
+    // 1_999 * 30 / 10_000 = 5.997
+    expect(await tokenBalance(feeVaultB)).to.equal(5n);
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      1_994n
+    );
+  });
+
+  it('does not need a fee vault when the fee rounds to zero', async () => {
+    await setFee(30);
+    const { escrow } = await makeEscrow(fx, { receive: 333 });
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      333n
+    );
+  });
+
+  it('fails when a fee is due but no fee vault is passed', async () => {
+    await setFee(100);
+    const { escrow } = await makeEscrow(fx, { receive: 1_000 });
+
+    await expectError(takeEscrow(fx, escrow), 'MissingFeeVault');
+  });
+
+  it('charges the fee on every partial fill', async () => {
+    await setFee(100);
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 2_000,
+    });
+
+    await program.methods
+      .takePartial(new BN(500), U64_MAX, [], Buffer.alloc(0))
+      .accountsPartial({
+        taker: fx.taker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        feeVaultB,
+        referrerAtaB: null,
+        takerStats: null,
+        makerStats: null,
+        pairIndex: null,
+        tokenProgramA: TOKEN_PROGRAM_ID,
+        tokenProgramB: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.taker])
+      .rpc();
+
+    expect(await tokenBalance(feeVaultB)).to.equal(10n);
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      990n
+    );
+  });
+
+  it('rejects set_fee from a non-admin', async () => {
+    const other = await fundedKeypair();
+
+    await expectError(
+      program.methods
+        .setFee(50)
+        .accountsPartial({ admin: other.publicKey })
+        .signers([other])
+        .rpc(),
+      'InvalidAdmin'
+    );
+  });
+
+  it('rejects a fee above 100%', async () => {
+    await expectError(setFee(10_001), 'InvalidFee');
+  });
+});
@@ -0,0 +1,115 @@
+import { getOrCreateAssociatedTokenAccount } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  feeAuthorityPda,
+  findReceipt,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  setFee,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('trade receipt', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  afterEach(async () => {
+    await setFee(0);
+  });
+
+  const closeReceipt = (receipt: PublicKey, closer = fx.maker) =>
+    program.methods
+      .closeReceipt()
+      .accountsPartial({
+        closer: closer.publicKey,
+        taker: fx.taker.publicKey,
+        receipt,
+      })
+      .signers([closer])
+      .rpc();
+
+  it('records the amounts that were transferred', async () => {
+    await setFee(100);
+    const feeVaultB = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.taker,
+        fx.mintB,
+        feeAuthorityPda,
+        true
+      )
+    ).address;
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const takerBalanceB = await tokenBalance(fx.takerAtaB);
+    const feeVaultBalance = await tokenBalance(feeVaultB);
+
+    await takeEscrow(fx, escrow, fx.taker, { feeVaultB, writeReceipt: true });
+
+    const receipt = await program.account.tradeReceipt.fetch(
+      findReceipt(escrow)
+    );
+    expect(receipt.escrow.equals(escrow)).to.be.true;
+    expect(receipt.maker.equals(fx.maker.publicKey)).to.be.true;
+    expect(receipt.taker.equals(fx.taker.publicKey)).to.be.true;
+    expect(receipt.mintA.equals(fx.mintA)).to.be.true;
+    expect(receipt.mintB.equals(fx.mintB)).to.be.true;
+    expect(
+      await tokenBalance(ata(fx.mintA, fx.taker.publicKey, fx.tokenProgramA))
+    ).to.equal(BigInt(receipt.amountA.toString()));
+    expect(takerBalanceB - (await tokenBalance(fx.takerAtaB))).to.equal(
+      BigInt(receipt.amountB.toString())
+    );
+    expect((await tokenBalance(feeVaultB)) - feeVaultBalance).to.equal(
+      BigInt(receipt.fee.toString())
+    );
+    expect(receipt.amountA.toNumber()).to.equal(1_000);
+    expect(receipt.amountB.toNumber()).to.equal(500);
+    expect(receipt.fee.toNumber()).to.equal(5);
+    expect(receipt.timestamp.toNumber()).to.be.greaterThan(0);
+  });
+
+  it('writes nothing unless a receipt is requested', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(findReceipt(escrow))).to.be.null;
+  });
+
+  it('refunds the rent to the taker when the maker closes it', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await takeEscrow(fx, escrow, fx.taker, { writeReceipt: true });
+    const receipt = findReceipt(escrow);
+    const rent = (await connection.getAccountInfo(receipt)).lamports;
+    const takerLamports = await connection.getBalance(fx.taker.publicKey);
+
+    await closeReceipt(receipt);
+
+    expect(await connection.getAccountInfo(receipt)).to.be.null;
+    expect(await connection.getBalance(fx.taker.publicKey)).to.equal(
+      takerLamports + rent
+    );
+  });
+
+  it('rejects a closer who was not part of the trade', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await takeEscrow(fx, escrow, fx.taker, { writeReceipt: true });
+    const stranger = await fundedKeypair();
+
+    await expectError(
+      closeReceipt(findReceipt(escrow), stranger),
+      'UnauthorizedReceiptCloser'
+    );
+  });
+});
@@ -0,0 +1,59 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+} from './utils';
+
+// 孤立的 vault 只能在 escrow 数据账户消失后出现, 本地验证器无法构造, 完整流程见 lifecycle.rs
+describe('recover_vault', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('rejects recovering a vault whose escrow still exists', async () => {
+    const { seed, escrow } = await makeEscrow(fx);
+
+    await expectError(
+      program.methods
+        .recoverVault(seed)
+        .accountsPartial({
+          maker: fx.maker.publicKey,
+          mintA: fx.mintA,
+          tokenProgram: fx.tokenProgramA,
+        })
+        .signers([fx.maker])
+        .rpc(),
+      'EscrowNotOrphaned'
+    );
+
+    await refundEscrow(fx, escrow);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('derives the escrow from the signer', async () => {
+    const { seed } = await makeEscrow(fx);
+    const stranger = await fundedKeypair();
+
+    // stranger 的 seeds 派生出另一个 PDA, 那里没有 vault
+    await expectError(
+      program.methods
+        .recoverVault(seed)
+        .accountsPartial({
+          maker: stranger.publicKey,
+          mintA: fx.mintA,
+          tokenProgram: fx.tokenProgramA,
+        })
+        .signers([stranger])
+        .rpc(),
+      'AccountNotInitialized'
+    );
+  });
+});
@@ -0,0 +1,137 @@
+import { BN } from '@coral-xyz/anchor';
+import { createRevokeInstruction, getAccount } from '@solana/spl-token';
+import { Transaction, sendAndConfirmTransaction } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('recurring escrows', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  const makerAtaA = () => ata(fx.mintA, fx.maker.publicKey, fx.tokenProgramA);
+
+  it('approves the escrow for every refill at make', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      recurring: true,
+      maxRefills: 3,
+    });
+
+    const account = await getAccount(
+      connection,
+      makerAtaA(),
+      undefined,
+      fx.tokenProgramA
+    );
+    expect(account.delegate?.toBase58()).to.equal(escrow.toBase58());
+    expect(account.delegatedAmount).to.equal(3_000n);
+  });
+
+  it('refills after each take until the refills run out', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      recurring: true,
+      maxRefills: 2,
+    });
+
+    for (let round = 1; round <= 3; round++) {
+      // maxReceive 每轮不同, 避免交易重复
+      await takeEscrow(fx, escrow, fx.taker, {
+        maxReceive: new BN(1_000 + round),
+      });
+      expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+        BigInt(round * 1_000)
+      );
+
+      if (round < 3) {
+        const state = await program.account.escrow.fetch(escrow);
+        expect(state.maxRefills).to.equal(2 - round);
+        expect(await tokenBalance(vault)).to.equal(1_000n);
+      } else {
+        expect(await connection.getAccountInfo(escrow)).to.be.null;
+      }
+    }
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      3_000n
+    );
+  });
+
+  it('closes normally once the allowance is revoked', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      recurring: true,
+      maxRefills: 2,
+    });
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        createRevokeInstruction(
+          makerAtaA(),
+          fx.maker.publicKey,
+          [],
+          fx.tokenProgramA
+        )
+      ),
+      [fx.maker]
+    );
+    const before = await tokenBalance(makerAtaA());
+
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await tokenBalance(makerAtaA())).to.equal(before);
+  });
+
+  it('rejects take_partial on a recurring escrow', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      recurring: true,
+      maxRefills: 2,
+    });
+
+    await expectError(
+      program.methods
+        .takePartial(new BN(1_000), new BN(1_000), [], Buffer.alloc(0))
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          feeVaultB: null,
+          referrerAtaB: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([fx.taker])
+        .rpc(),
+      'RecurringRequiresTake'
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('rejects refills without the recurring flag', async () => {
+    await expectError(
+      makeEscrow(fx, { amount: 1_000, maxRefills: 2 }),
+      'InvalidRecurring'
+    );
+  });
+});
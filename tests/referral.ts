@@ -0,0 +1,119 @@
+import { getOrCreateAssociatedTokenAccount } from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  feeAuthorityPda,
+  fetchEvents,
+  makeEscrow,
+  setFee,
+  setReferral,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('referral fee', () => {
+  let fx: Fixture;
+  let feeVaultB: PublicKey;
+  let referrerAtaB: PublicKey;
+
+  const makerBalanceB = () => tokenBalance(ata(fx.mintB, fx.maker.publicKey));
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    feeVaultB = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.taker,
+        fx.mintB,
+        feeAuthorityPda,
+        true
+      )
+    ).address;
+    referrerAtaB = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        fx.taker,
+        fx.mintB,
+        Keypair.generate().publicKey
+      )
+    ).address;
+
+    await setFee(100);
+    await setReferral(5_000);
+  });
+
+  afterEach(async () => {
+    await setFee(0);
+    await setReferral(0);
+  });
+
+  it('splits the fee with the referrer when one is passed', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 10_000 });
+
+    const signature = await takeEscrow(fx, escrow, fx.taker, {
+      feeVaultB,
+      referrerAtaB,
+    });
+
+    expect(await makerBalanceB()).to.equal(9_900n);
+    expect(await tokenBalance(feeVaultB)).to.equal(50n);
+    expect(await tokenBalance(referrerAtaB)).to.equal(50n);
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n - 10_000n);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.data.fee.toNumber()).to.equal(100);
+    expect(event.data.referralFee.toNumber()).to.equal(50);
+  });
+
+  it('sends the full fee to the protocol without a referrer', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 10_000 });
+
+    await takeEscrow(fx, escrow, fx.taker, { feeVaultB });
+
+    expect(await makerBalanceB()).to.equal(9_900n);
+    expect(await tokenBalance(feeVaultB)).to.equal(100n);
+    expect(await tokenBalance(referrerAtaB)).to.equal(0n);
+  });
+
+  it('keeps a 1 unit fee when the referral share rounds down', async () => {
+    // 100 * 100 / 10_000 = 1, 1 * 5_000 / 10_000 = 0.5
+    const { escrow } = await makeEscrow(fx, { receive: 100 });
+
+    await takeEscrow(fx, escrow, fx.taker, { feeVaultB, referrerAtaB });
+
+    expect(await makerBalanceB()).to.equal(99n);
+    expect(await tokenBalance(feeVaultB)).to.equal(1n);
+    expect(await tokenBalance(referrerAtaB)).to.equal(0n);
+  });
+
+  it('skips the fee vault when the referrer gets it all', async () => {
+    await setReferral(10_000);
+    const { escrow } = await makeEscrow(fx, { receive: 100 });
+
+    await takeEscrow(fx, escrow, fx.taker, { referrerAtaB });
+
+    expect(await makerBalanceB()).to.equal(99n);
+    expect(await tokenBalance(referrerAtaB)).to.equal(1n);
+  });
+
+  it('rejects the taker as their own referrer', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 10_000 });
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, {
+        feeVaultB,
+        referrerAtaB: fx.takerAtaB,
+      }),
+      'SelfReferral'
+    );
+  });
+
+  it('rejects a referral share above 100%', async () => {
+    await expectError(setReferral(10_001), 'InvalidFee');
+  });
+});
@@ -0,0 +1,69 @@
+import { AccountMeta, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  makeEscrow,
+  program,
+  tokenBalance,
+} from './utils';
+
+describe('refund_batch', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  // 按顺序排列的 [escrow, vault] 账户, vault 都是 fx.mintA 的 ATA
+  const pairAccounts = (escrows: PublicKey[]): AccountMeta[] =>
+    escrows
+      .flatMap((escrow) => [escrow, ata(fx.mintA, escrow, fx.tokenProgramA)])
+      .map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+  const refundBatch = (escrows: PublicKey[]) =>
+    program.methods
+      .refundBatch(escrows.length)
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        rentPayer: fx.maker.publicKey,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: fx.tokenProgramA,
+      })
+      .remainingAccounts(pairAccounts(escrows))
+      .signers([fx.maker])
+      .rpc();
+
+  it('refunds every escrow in the batch', async () => {
+    const escrows = [];
+    for (const amount of [1_000, 2_000, 3_000]) {
+      escrows.push((await makeEscrow(fx, { amount })).escrow);
+    }
+    const balance = await tokenBalance(fx.makerAtaA);
+
+    await refundBatch(escrows);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(balance + 6_000n);
+    for (const { pubkey } of pairAccounts(escrows)) {
+      expect(await connection.getAccountInfo(pubkey)).to.be.null;
+    }
+  });
+
+  it('refunds nothing when one escrow belongs to another maker', async () => {
+    // 第一个托管验证通过并退还之后, 第二个托管的失败让整笔交易回滚
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+    const foreign = await makeEscrow(await createFixture());
+    const balance = await tokenBalance(fx.makerAtaA);
+
+    await expectError(refundBatch([escrow, foreign.escrow]), 'InvalidMaker');
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(balance);
+    expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+  });
+});
@@ -0,0 +1,90 @@
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('refund_delegate', () => {
+  let fx: Fixture;
+  let delegate: Keypair;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    delegate = await fundedKeypair();
+  });
+
+  const setDelegate = (
+    escrow: PublicKey,
+    newDelegate: PublicKey,
+    signer = fx.maker
+  ) =>
+    program.methods
+      .setDelegate(newDelegate)
+      .accountsPartial({ maker: signer.publicKey, escrow })
+      .signers([signer])
+      .rpc();
+
+  it('lets the delegate refund to the maker', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      refundDelegate: delegate.publicKey,
+    });
+    const balance = await tokenBalance(fx.makerAtaA);
+    const rent = await connection.getBalance(fx.maker.publicKey);
+
+    await refundEscrow(fx, escrow, [], false, delegate);
+
+    // token A 和租金都回到 maker, delegate 只支付交易费
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(balance + 1_000n);
+    expect(await connection.getBalance(fx.maker.publicKey)).to.be.greaterThan(
+      rent
+    );
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('lets the maker set and revoke the delegate', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    await setDelegate(escrow, delegate.publicKey);
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.refundDelegate.toBase58()).to.equal(
+      delegate.publicKey.toBase58()
+    );
+
+    // delegate 不能修改自己的授权, seeds 由签名者派生
+    await expectError(
+      setDelegate(escrow, Keypair.generate().publicKey, delegate),
+      'ConstraintSeeds'
+    );
+
+    await setDelegate(escrow, PublicKey.default);
+    await expectError(
+      refundEscrow(fx, escrow, [], false, delegate),
+      'UnauthorizedRefund'
+    );
+  });
+
+  it('rejects a signer that is neither the maker nor the delegate', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      refundDelegate: delegate.publicKey,
+    });
+
+    await expectError(
+      refundEscrow(fx, escrow, [], false, await fundedKeypair()),
+      'UnauthorizedRefund'
+    );
+    expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+  });
+});
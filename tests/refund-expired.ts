@@ -0,0 +1,123 @@
+import {
+  TOKEN_PROGRAM_ID,
+  getOrCreateAssociatedTokenAccount,
+} from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  nowSeconds,
+  program,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('refund_expired', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const crank = (
+    escrow: { escrow: PublicKey; vault: PublicKey },
+    cranker: Keypair,
+    makerAtaA = fx.makerAtaA
+  ) =>
+    program.methods
+      .refundExpired()
+      .accountsPartial({
+        refund: {
+          authority: cranker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow: escrow.escrow,
+          mintA: fx.mintA,
+          vault: escrow.vault,
+          makerAtaA,
+          pairIndex: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+      })
+      .signers([cranker])
+      .rpc();
+
+  it('lets anyone refund to the maker after expiry', async () => {
+    const escrow = await makeEscrow(fx, {
+      amount: 1_000,
+      expiry: nowSeconds() + 2,
+    });
+    const cranker = await fundedKeypair();
+    await sleep(4_000);
+
+    await crank(escrow, cranker);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+    expect(await connection.getAccountInfo(escrow.escrow)).to.be.null;
+    expect(await connection.getAccountInfo(escrow.vault)).to.be.null;
+  });
+
+  it('rejects a take after expiry', async () => {
+    const escrow = await makeEscrow(fx, { expiry: nowSeconds() + 2 });
+    await sleep(4_000);
+
+    await expectError(takeEscrow(fx, escrow.escrow), 'EscrowExpired');
+    expect(await tokenBalance(escrow.vault)).to.equal(1_000n);
+  });
+
+  it('rejects a crank before expiry', async () => {
+    const escrow = await makeEscrow(fx, { expiry: nowSeconds() + 600 });
+    const cranker = await fundedKeypair();
+
+    await expectError(crank(escrow, cranker), 'EscrowNotExpired');
+  });
+
+  it('rejects a crank on an escrow without expiry', async () => {
+    const escrow = await makeEscrow(fx, { expiry: 0 });
+    const cranker = await fundedKeypair();
+
+    await expectError(crank(escrow, cranker), 'NoExpiry');
+  });
+
+  it('cannot redirect funds away from the maker ATA', async () => {
+    const escrow = await makeEscrow(fx, { expiry: nowSeconds() + 2 });
+    const cranker = await fundedKeypair();
+    const crankerAtaA = await getOrCreateAssociatedTokenAccount(
+      connection,
+      cranker,
+      fx.mintA,
+      cranker.publicKey
+    );
+    await sleep(4_000);
+
+    await expectError(
+      crank(escrow, cranker, crankerAtaA.address),
+      'ConstraintTokenOwner'
+    );
+  });
+
+  it('keeps the maker refund usable before expiry', async () => {
+    const escrow = await makeEscrow(fx, { expiry: nowSeconds() + 600 });
+
+    await program.methods
+      .refund(false)
+      .accountsPartial({
+        authority: fx.maker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow: escrow.escrow,
+        mintA: fx.mintA,
+        vault: escrow.vault,
+        pairIndex: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.maker])
+      .rpc();
+
+    expect(await connection.getAccountInfo(escrow.escrow)).to.be.null;
+  });
+});
@@ -0,0 +1,107 @@
+import { BN } from '@coral-xyz/anchor';
+import { getOrCreateAssociatedTokenAccount, mintTo } from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('relayer pays for take', () => {
+  let fx: Fixture;
+  let relayer: Keypair;
+  let ataRent: number;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    relayer = await fundedKeypair();
+    ataRent = await connection.getMinimumBalanceForRentExemption(165);
+  });
+
+  it('charges the ATA rent to the relayer', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const relayerBefore = await connection.getBalance(relayer.publicKey);
+    const takerBefore = await connection.getBalance(fx.taker.publicKey);
+
+    await takeEscrow(fx, escrow, fx.taker, { payer: relayer });
+
+    // relayer 创建了 taker 的 token A ATA 和 maker 的 token B ATA
+    expect(await connection.getBalance(relayer.publicKey)).to.equal(
+      relayerBefore - 2 * ataRent
+    );
+    expect(await connection.getBalance(fx.taker.publicKey)).to.equal(
+      takerBefore
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n - 500n);
+  });
+
+  it('falls back to the taker without a relayer', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const takerBefore = await connection.getBalance(fx.taker.publicKey);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getBalance(fx.taker.publicKey)).to.equal(
+      takerBefore - 2 * ataRent
+    );
+  });
+
+  it('never takes token B from the relayer', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const relayerAtaB = await getOrCreateAssociatedTokenAccount(
+      connection,
+      relayer,
+      fx.mintB,
+      relayer.publicKey
+    );
+    await mintTo(
+      connection,
+      fx.taker,
+      fx.mintB,
+      relayerAtaB.address,
+      fx.taker,
+      1_000_000
+    );
+
+    await expectError(
+      program.methods
+        .take(U64_MAX, new BN(0), [], Buffer.alloc(0), false)
+        .accountsPartial({
+          taker: fx.taker.publicKey,
+          payer: relayer.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          takerTokenA: null,
+          takerAtaB: relayerAtaB.address,
+          feeVaultB: null,
+          referrerAtaB: null,
+          unwrapAtaB: null,
+          receipt: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          priceFeed: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        })
+        .signers([fx.taker, relayer])
+        .rpc(),
+      'ConstraintTokenOwner'
+    );
+    expect(await tokenBalance(relayerAtaB.address)).to.equal(1_000_000n);
+  });
+});
@@ -0,0 +1,129 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  findEscrow,
+  findRegistry,
+  fundedKeypair,
+  makeEscrow,
+  nowSeconds,
+  program,
+  randomSeed,
+  setFrozen,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('relist', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  const relist = (
+    escrow: PublicKey,
+    newSeed: BN,
+    newReceive: number,
+    maker: Keypair = fx.maker
+  ) =>
+    program.methods
+      .relist(newSeed, new BN(newReceive))
+      .accountsPartial({
+        maker: maker.publicKey,
+        rentPayer: maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([maker])
+      .rpc();
+
+  it('moves the vault without touching the maker wallet', async () => {
+    const { escrow, vault, seed } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+    const makerBalance = await tokenBalance(fx.makerAtaA);
+    const newSeed = randomSeed();
+    const newEscrow = findEscrow(fx.maker.publicKey, newSeed);
+
+    const signature = await relist(escrow, newSeed, 800);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(await tokenBalance(ata(fx.mintA, newEscrow))).to.equal(1_000n);
+    const state = await program.account.escrow.fetch(newEscrow);
+    expect(state.receive.toNumber()).to.equal(800);
+    expect(state.amount.toNumber()).to.equal(1_000);
+    expect(state.mintB.toBase58()).to.equal(fx.mintB.toBase58());
+
+    // 旧 vault 只转给新 vault, maker 的 token A 账户根本不在交易中
+    await connection.confirmTransaction(signature, 'confirmed');
+    const tx = await connection.getTransaction(signature, {
+      commitment: 'confirmed',
+      maxSupportedTransactionVersion: 0,
+    });
+    const keys = tx.transaction.message.getAccountKeys().staticAccountKeys;
+    expect(keys.map((key) => key.toBase58())).to.not.include(
+      fx.makerAtaA.toBase58()
+    );
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(makerBalance);
+
+    const { seeds } = await program.account.makerRegistry.fetch(
+      findRegistry(fx.maker.publicKey)
+    );
+    const listed = seeds.map((open) => open.toString());
+    expect(listed).to.include(newSeed.toString());
+    expect(listed).to.not.include(seed.toString());
+  });
+
+  it('fills the new escrow at the new price', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const newSeed = randomSeed();
+    await relist(escrow, newSeed, 700);
+
+    await takeEscrow(fx, findEscrow(fx.maker.publicKey, newSeed));
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      700n
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+  });
+
+  it('rejects a relist by anyone but the maker', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const stranger = await fundedKeypair();
+
+    await expectError(
+      relist(escrow, randomSeed(), 800, stranger),
+      'ConstraintSeeds'
+    );
+  });
+
+  it('rejects a frozen escrow', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await setFrozen(escrow, true);
+
+    await expectError(relist(escrow, randomSeed(), 800), 'EscrowFrozen');
+  });
+
+  it('rejects an expired escrow', async () => {
+    const { escrow } = await makeEscrow(fx, { expiry: nowSeconds() + 2 });
+    await sleep(3_000);
+
+    await expectError(relist(escrow, randomSeed(), 800), 'EscrowExpired');
+  });
+});
@@ -0,0 +1,95 @@
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+} from './utils';
+
+describe('rent payer', () => {
+  let fx: Fixture;
+  let sponsor: Keypair;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+    sponsor = await fundedKeypair();
+  });
+
+  it('lets a sponsor pay the rent at make', async () => {
+    const makerBefore = await connection.getBalance(fx.maker.publicKey);
+    const sponsorBefore = await connection.getBalance(sponsor.publicKey);
+
+    const { escrow, vault } = await makeEscrow(fx, { rentPayer: sponsor });
+
+    const rent =
+      (await connection.getBalance(escrow)) +
+      (await connection.getBalance(vault));
+    expect(await connection.getBalance(sponsor.publicKey)).to.equal(
+      sponsorBefore - rent
+    );
+    expect(await connection.getBalance(fx.maker.publicKey)).to.equal(
+      makerBefore
+    );
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.rentPayer.equals(sponsor.publicKey)).to.be.true;
+  });
+
+  it('returns the rent to the sponsor on refund', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { rentPayer: sponsor });
+    const rent =
+      (await connection.getBalance(escrow)) +
+      (await connection.getBalance(vault));
+    const sponsorBefore = await connection.getBalance(sponsor.publicKey);
+    const makerBefore = await connection.getBalance(fx.maker.publicKey);
+
+    await refundEscrow(fx, escrow);
+
+    expect(await connection.getBalance(sponsor.publicKey)).to.equal(
+      sponsorBefore + rent
+    );
+    expect(await connection.getBalance(fx.maker.publicKey)).to.equal(
+      makerBefore
+    );
+  });
+
+  it('returns the rent to the sponsor on take', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { rentPayer: sponsor });
+    const rent =
+      (await connection.getBalance(escrow)) +
+      (await connection.getBalance(vault));
+    const sponsorBefore = await connection.getBalance(sponsor.publicKey);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getBalance(sponsor.publicKey)).to.equal(
+      sponsorBefore + rent
+    );
+  });
+
+  it('rejects a different rent payer on close', async () => {
+    const { escrow } = await makeEscrow(fx, { rentPayer: sponsor });
+
+    await expectError(
+      program.methods
+        .refund(false)
+        .accountsPartial({
+          authority: fx.maker.publicKey,
+          maker: fx.maker.publicKey,
+          rentPayer: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          pairIndex: null,
+          tokenProgram: fx.tokenProgramA,
+        })
+        .signers([fx.maker])
+        .rpc(),
+      'InvalidRentPayer'
+    );
+  });
+});
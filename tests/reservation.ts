@@ -0,0 +1,204 @@
+import { BN } from '@coral-xyz/anchor';
+import { Keypair, LAMPORTS_PER_SOL, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  buildAllowlist,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+const BOND = LAMPORTS_PER_SOL / 10;
+
+describe('reservation', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+  });
+
+  const reserve = (
+    escrow: PublicKey,
+    taker = fx.taker,
+    bond = BOND,
+    proof: number[][] = []
+  ) =>
+    program.methods
+      .reserve(new BN(bond), proof)
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+      })
+      .signers([taker])
+      .rpc();
+
+  const settle = (escrow: PublicKey, taker = fx.taker) =>
+    program.methods
+      .settle(U64_MAX, new BN(0), [], Buffer.alloc(0), false)
+      .accountsPartial({
+        take: {
+          taker: taker.publicKey,
+          payer: taker.publicKey,
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          mintB: fx.mintB,
+          takerTokenA: null,
+          takerAtaB: ata(fx.mintB, taker.publicKey, fx.tokenProgramB),
+          feeVaultB: null,
+          referrerAtaB: null,
+          unwrapAtaB: null,
+          receipt: null,
+          takerStats: null,
+          makerStats: null,
+          pairIndex: null,
+          priceFeed: null,
+          tokenProgramA: fx.tokenProgramA,
+          tokenProgramB: fx.tokenProgramB,
+        },
+      })
+      .signers([taker])
+      .rpc();
+
+  const expire = (escrow: PublicKey, cranker: Keypair) =>
+    program.methods
+      .expireReservation()
+      .accountsPartial({
+        cranker: cranker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+      })
+      .signers([cranker])
+      .rpc();
+
+  // escrow 账户中超出免租金的 lamports, 即保证金
+  const surplus = async (escrow: PublicKey) => {
+    const account = await connection.getAccountInfo(escrow);
+    const rent = await connection.getMinimumBalanceForRentExemption(
+      account.data.length
+    );
+    return account.lamports - rent;
+  };
+
+  it('blocks other takers and the maker while reserved', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      reservationWindow: 60,
+    });
+    await reserve(escrow);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.reservedTaker.toBase58()).to.equal(
+      fx.taker.publicKey.toBase58()
+    );
+    expect(state.bond.toNumber()).to.equal(BOND);
+    expect(await surplus(escrow)).to.equal(BOND);
+
+    const other = await fundedKeypair();
+    await expectError(reserve(escrow, other), 'EscrowReserved');
+    await expectError(refundEscrow(fx, escrow), 'EscrowReserved');
+    // 预约的 taker 也必须通过 settle 成交, 保证金才能退还
+    await expectError(takeEscrow(fx, escrow), 'EscrowReserved');
+  });
+
+  it('settles for the reserved taker and returns the bond', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 800,
+      reservationWindow: 60,
+    });
+    await reserve(escrow);
+    const lamports = await connection.getBalance(fx.taker.publicKey);
+
+    await settle(escrow);
+
+    const takerAtaA = ata(fx.mintA, fx.taker.publicKey);
+    expect(await tokenBalance(takerAtaA)).to.equal(1_000n);
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    // taker 取回保证金, 只支付了 token A 的 ATA 租金
+    const ataRent = await connection.getBalance(takerAtaA);
+    expect(await connection.getBalance(fx.taker.publicKey)).to.equal(
+      lamports + BOND - ataRent
+    );
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('forfeits the bond to the maker after the window', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      reservationWindow: 2,
+    });
+    await reserve(escrow);
+    const cranker = await fundedKeypair();
+    await expectError(expire(escrow, cranker), 'ReservationNotExpired');
+
+    await sleep(4_000);
+    await expectError(settle(escrow), 'ReservationExpired');
+
+    const lamports = await connection.getBalance(fx.maker.publicKey);
+    await expire(escrow, cranker);
+    expect(await connection.getBalance(fx.maker.publicKey)).to.equal(
+      lamports + BOND
+    );
+
+    // 托管重新开放, escrow 账户仍然免租金
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.reservedTaker.toBase58()).to.equal(
+      PublicKey.default.toBase58()
+    );
+    expect(await surplus(escrow)).to.equal(0);
+    await takeEscrow(fx, escrow);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('rejects reservations the maker did not allow', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+    await expectError(reserve(escrow), 'ReservationsDisabled');
+
+    await expectError(
+      makeEscrow(fx, { amount: 1_000, reservationWindow: 86_401 }),
+      'InvalidReservationWindow'
+    );
+  });
+
+  it('only lets allowlisted takers reserve', async () => {
+    const allowlist = buildAllowlist([
+      fx.taker.publicKey,
+      Keypair.generate().publicKey,
+    ]);
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      reservationWindow: 60,
+      takerAllowlistRoot: allowlist.root,
+    });
+
+    // 不在名单中的 taker 不能用预约挡住名单中的 taker
+    const outsider = await fundedKeypair();
+    await expectError(
+      reserve(escrow, outsider, 1, allowlist.proofFor(fx.taker.publicKey)),
+      'NotOnAllowlist'
+    );
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.bond.toNumber()).to.equal(0);
+
+    const proof = allowlist.proofFor(fx.taker.publicKey);
+    await reserve(escrow, fx.taker, BOND, proof);
+    expect(await surplus(escrow)).to.equal(BOND);
+  });
+});
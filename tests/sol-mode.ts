@@ -0,0 +1,122 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  findEscrow,
+  makeEscrow,
+  program,
+  randomSeed,
+  refundEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('native SOL payment', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const makeForSol = async (receiveLamports: number, amount = 1_000) => {
+    const seed = randomSeed();
+    await program.methods
+      .makeForSol(seed, new BN(receiveLamports), new BN(amount))
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        rentPayer: fx.maker.publicKey,
+        mintA: fx.mintA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.maker])
+      .rpc();
+
+    const escrow = findEscrow(fx.maker.publicKey, seed);
+    return { escrow, vault: ata(fx.mintA, escrow) };
+  };
+
+  const takeForSol = (escrow: PublicKey, maxReceive = U64_MAX) =>
+    program.methods
+      .takeForSol(maxReceive, new BN(0))
+      .accountsPartial({
+        taker: fx.taker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.taker])
+      .rpc();
+
+  it('records the SOL sentinel as mint B', async () => {
+    const { escrow, vault } = await makeForSol(5_000_000);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.mintB.equals(PublicKey.default)).to.be.true;
+    expect(state.receive.toNumber()).to.equal(5_000_000);
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('moves lamports to the maker and token A to the taker', async () => {
+    const receive = 5_000_000;
+    const { escrow, vault } = await makeForSol(receive);
+    const escrowRent = await connection.getBalance(escrow);
+    const vaultRent = await connection.getBalance(vault);
+    const ataRent = await connection.getMinimumBalanceForRentExemption(165);
+    const makerBefore = await connection.getBalance(fx.maker.publicKey);
+    const takerBefore = await connection.getBalance(fx.taker.publicKey);
+
+    await takeForSol(escrow);
+
+    // 交易费由 provider 钱包支付, maker 还会收回 escrow 和 vault 的租金, taker 额外支付自己 token A ATA 的租金
+    expect(await connection.getBalance(fx.maker.publicKey)).to.equal(
+      makerBefore + receive + escrowRent + vaultRent
+    );
+    expect(await connection.getBalance(fx.taker.publicKey)).to.equal(
+      takerBefore - receive - ataRent
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('enforces the slippage limit', async () => {
+    const { escrow } = await makeForSol(5_000_000);
+
+    await expectError(
+      takeForSol(escrow, new BN(4_999_999)),
+      'SlippageExceeded'
+    );
+  });
+
+  it('rejects take on a SOL escrow', async () => {
+    const { escrow } = await makeForSol(5_000_000);
+
+    await expectError(takeEscrow(fx, escrow), 'InvalidMintB');
+  });
+
+  it('rejects take_for_sol on a token escrow', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await expectError(takeForSol(escrow), 'NotSolEscrow');
+  });
+
+  it('refunds a SOL escrow like any other', async () => {
+    const { escrow } = await makeForSol(5_000_000);
+
+    await refundEscrow(fx, escrow);
+
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+});
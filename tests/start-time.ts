@@ -0,0 +1,68 @@
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  nowSeconds,
+  program,
+  refundEscrow,
+  sleep,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('start time', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('cannot be taken before the start time but can after it', async () => {
+    const startTime = nowSeconds() + 3;
+    const { escrow } = await makeEscrow(fx, { receive: 500, startTime });
+
+    await expectError(takeEscrow(fx, escrow), 'OfferNotStarted');
+
+    // 本地验证器无法直接修改时钟, 等待时间越过开始时间
+    await sleep(5_000);
+    await takeEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      500n
+    );
+  });
+
+  it('lets the maker refund while waiting', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      startTime: nowSeconds() + 60,
+    });
+
+    await refundEscrow(fx, escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+  });
+
+  it('stores the start time on make', async () => {
+    const startTime = nowSeconds() + 60;
+    const { escrow } = await makeEscrow(fx, { startTime });
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.startTime.toNumber()).to.equal(startTime);
+  });
+
+  it('rejects a start time after the expiry', async () => {
+    const expiry = nowSeconds() + 60;
+
+    await expectError(
+      makeEscrow(fx, { expiry, startTime: expiry }),
+      'InvalidStartTime'
+    );
+  });
+});
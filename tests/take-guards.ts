@@ -0,0 +1,75 @@
+import { BN } from '@coral-xyz/anchor';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('take guards', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  it('succeeds when the price equals the bound', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 500 });
+
+    await takeEscrow(fx, escrow, fx.taker, { maxReceive: new BN(500) });
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      500n
+    );
+  });
+
+  it('fails when the price is one unit above the bound', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 501 });
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { maxReceive: new BN(500) }),
+      'SlippageExceeded'
+    );
+    expect(await connection.getAccountInfo(escrow)).to.not.be.null;
+  });
+
+  it('fails when the vault holds a different amount than expected', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { expectedAmountA: 999 }),
+      'VaultAmountMismatch'
+    );
+  });
+
+  it('succeeds when the expected amount matches', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    await takeEscrow(fx, escrow, fx.taker, { expectedAmountA: 1_000 });
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+  });
+
+  it('takes whatever is there with the zero sentinel', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_234 });
+
+    await takeEscrow(fx, escrow, fx.taker, { expectedAmountA: 0 });
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_234n
+    );
+  });
+
+  it('reports a taker holding one unit less than the price', async () => {
+    fx = await createFixture(1_000_000, 999);
+    const { escrow, vault } = await makeEscrow(fx, { receive: 1_000 });
+
+    await expectError(takeEscrow(fx, escrow), 'InsufficientTakerBalance');
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+});
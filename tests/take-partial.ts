@@ -0,0 +1,136 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+  tokenBalance,
+} from './utils';
+
+describe('take_partial', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const takePartial = (
+    escrow: PublicKey,
+    amountA: number,
+    maxReceive: BN = U64_MAX
+  ) =>
+    program.methods
+      .takePartial(new BN(amountA), maxReceive, [], Buffer.alloc(0))
+      .accountsPartial({
+        taker: fx.taker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        feeVaultB: null,
+        referrerAtaB: null,
+        takerStats: null,
+        makerStats: null,
+        pairIndex: null,
+        tokenProgramA: TOKEN_PROGRAM_ID,
+        tokenProgramB: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.taker])
+      .rpc();
+
+  it('fills pro-rata and keeps the escrow open', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 3_000,
+    });
+
+    await takePartial(escrow, 250);
+
+    expect(await tokenBalance(vault)).to.equal(750n);
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      250n
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      750n
+    );
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.deposited.toNumber()).to.equal(1_000);
+    expect(state.receive.toNumber()).to.equal(3_000);
+    expect(state.status).to.deep.equal({ partiallyFilled: {} });
+  });
+
+  it('rounds the token B owed up in favour of the maker', async () => {
+    // 单价为 1 / 3, 买 1 个 token A 向上取整需要支付 1 个 token B
+    const { escrow } = await makeEscrow(fx, { amount: 3_000, receive: 1_000 });
+
+    await takePartial(escrow, 1);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      1n
+    );
+  });
+
+  it('closes the escrow and vault on the final fill', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 2_000,
+    });
+
+    await takePartial(escrow, 400);
+    await takePartial(escrow, 600);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      2_000n
+    );
+  });
+
+  it('sweeps dust that would be worth zero token B', async () => {
+    // 单价为 1 / 10, 剩下 5 个 token A 不值 1 个 token B, 应该被一并取走
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 100,
+    });
+
+    await takePartial(escrow, 995);
+
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+  });
+
+  it('rejects zero and oversized fills', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    await expectError(takePartial(escrow, 0), 'InvalidAmount');
+    await expectError(takePartial(escrow, 1_001), 'InvalidAmount');
+  });
+
+  it('rejects fills that cost more than max_receive', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 3_000,
+    });
+
+    // 250 个 token A 需要支付 750 个 token B
+    await expectError(
+      takePartial(escrow, 250, new BN(749)),
+      'SlippageExceeded'
+    );
+    await takePartial(escrow, 250, new BN(750));
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      750n
+    );
+  });
+});
@@ -0,0 +1,197 @@
+import { BN } from '@coral-xyz/anchor';
+import { approve } from '@solana/spl-token';
+import {
+  Ed25519Program,
+  Keypair,
+  PublicKey,
+  TransactionInstruction,
+} from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  nowSeconds,
+  program,
+  randomSeed,
+  tokenBalance,
+} from './utils';
+
+// taker 用 SPL approve 授权给这个 PDA, take_with_sig 用它的签名转出 token B
+const takeDelegate = PublicKey.findProgramAddressSync(
+  [Buffer.from('take_delegate')],
+  program.programId
+)[0];
+
+interface Order {
+  escrow: PublicKey;
+  maxReceive: BN;
+  expiry: BN;
+  nonce: BN;
+}
+
+// 和链上 order::order_message 相同的布局
+const orderMessage = (order: Order) =>
+  Buffer.concat([
+    program.programId.toBuffer(),
+    order.escrow.toBuffer(),
+    order.maxReceive.toArrayLike(Buffer, 'le', 8),
+    order.expiry.toTwos(64).toArrayLike(Buffer, 'le', 8),
+    order.nonce.toArrayLike(Buffer, 'le', 8),
+  ]);
+
+const signOrder = (signer: Keypair, order: Order) =>
+  Ed25519Program.createInstructionWithPrivateKey({
+    privateKey: signer.secretKey,
+    message: orderMessage(order),
+  });
+
+describe('take_with_sig', () => {
+  let fx: Fixture;
+  let relayer: Keypair;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    relayer = await fundedKeypair();
+    await approve(
+      connection,
+      fx.taker,
+      fx.takerAtaB,
+      takeDelegate,
+      fx.taker,
+      10_000
+    );
+  });
+
+  const newOrder = (escrow: PublicKey, expiry = 0): Order => ({
+    escrow,
+    maxReceive: new BN(1_000),
+    expiry: new BN(expiry),
+    nonce: randomSeed(),
+  });
+
+  // relayer 提交 order, verify 是紧挨在前面的 ed25519 指令; escrow 默认是订单中的托管
+  const takeWithSig = (
+    order: Order,
+    verify: TransactionInstruction | null,
+    escrow = order.escrow
+  ) =>
+    program.methods
+      .takeWithSig(order.maxReceive, order.expiry, order.nonce)
+      .accountsPartial({
+        relayer: relayer.publicKey,
+        taker: fx.taker.publicKey,
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        mintB: fx.mintB,
+        takerAtaB: fx.takerAtaB,
+        feeVaultB: null,
+        takerStats: null,
+        makerStats: null,
+        pairIndex: null,
+        tokenProgramA: fx.tokenProgramA,
+        tokenProgramB: fx.tokenProgramB,
+      })
+      .preInstructions(verify ? [verify] : [])
+      .signers([relayer])
+      .rpc();
+
+  it('fills the escrow without a signature from the taker', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 800,
+    });
+    const order = newOrder(escrow);
+    const lamports = await connection.getBalance(fx.taker.publicKey);
+
+    await takeWithSig(order, signOrder(fx.taker, order));
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+    // taker 不需要支付交易费和 ATA 的租金
+    expect(await connection.getBalance(fx.taker.publicKey)).to.equal(lamports);
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+  });
+
+  it('rejects missing, foreign and tampered signatures', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+    const order = newOrder(escrow);
+
+    await expectError(takeWithSig(order, null), 'InvalidOrderSignature');
+    // relayer 自己对订单签名
+    await expectError(
+      takeWithSig(order, signOrder(relayer, order)),
+      'InvalidOrderSignature'
+    );
+    // 提交的 max_receive 和 taker 签名的不同
+    await expectError(
+      takeWithSig(
+        { ...order, maxReceive: order.maxReceive.addn(1) },
+        signOrder(fx.taker, order)
+      ),
+      'InvalidOrderSignature'
+    );
+
+    // 签名字节被篡改时 ed25519 程序在执行前拒绝整笔交易
+    // ed25519 指令的数据中签名紧挨在消息之前
+    const corrupted = signOrder(fx.taker, order);
+    const signatureEnd = corrupted.data.length - orderMessage(order).length;
+    corrupted.data[signatureEnd - 1] ^= 1;
+    const failed = await takeWithSig(order, corrupted).then(
+      () => false,
+      () => true
+    );
+    expect(failed).to.be.true;
+
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('rejects an order signed for another escrow', async () => {
+    const signed = await makeEscrow(fx, { amount: 1_000 });
+    const other = await makeEscrow(fx, { amount: 1_000 });
+    const order = newOrder(signed.escrow);
+
+    await expectError(
+      takeWithSig(order, signOrder(fx.taker, order), other.escrow),
+      'InvalidOrderSignature'
+    );
+    expect(await tokenBalance(other.vault)).to.equal(1_000n);
+  });
+
+  it('rejects a replayed order after the maker reuses the seed', async () => {
+    const seed = randomSeed();
+    const { escrow } = await makeEscrow(fx, { seed, amount: 1_000 });
+    const order = newOrder(escrow);
+    await takeWithSig(order, signOrder(fx.taker, order));
+
+    // 相同 seed 的新托管地址不变, 订单的 nonce 账户已经存在
+    const { vault } = await makeEscrow(fx, { seed, amount: 1_000 });
+    await expectError(
+      takeWithSig(order, signOrder(fx.taker, order)),
+      'already in use'
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('rejects an expired order', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+    const order = newOrder(escrow, nowSeconds() - 60);
+
+    await expectError(
+      takeWithSig(order, signOrder(fx.taker, order)),
+      'OrderExpired'
+    );
+  });
+});
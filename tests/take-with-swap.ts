@@ -0,0 +1,175 @@
+import * as anchor from '@coral-xyz/anchor';
+import { BN, Program } from '@coral-xyz/anchor';
+import {
+  AuthorityType,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  setAuthority,
+} from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import { MockSwap } from '../target/types/mock_swap';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  connection,
+  createFixtureWithMints,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  provider,
+  takeAccounts,
+  tokenBalance,
+} from './utils';
+
+const swapProgram = anchor.workspace.mockSwap as Program<MockSwap>;
+
+// mock swap 用这个 PDA 铸造 token B, 测试把 mint B 的铸币权限转给它
+const mintAuthority = PublicKey.findProgramAddressSync(
+  [Buffer.from('mint-authority')],
+  swapProgram.programId
+)[0];
+
+// 设置 take_with_swap 允许的路由程序, 使用 provider 钱包作为 admin
+const setSwapRouter = (router: PublicKey) =>
+  program.methods
+    .setSwapRouter(router)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+
+describe('take with swap', () => {
+  let fx: Fixture;
+  let mintC: PublicKey;
+  let takerAtaC: PublicKey;
+  let poolC: PublicKey;
+
+  beforeEach(async () => {
+    const maker = await fundedKeypair();
+    const taker = await fundedKeypair();
+    const createMintOf = (authority: Keypair) =>
+      createMint(connection, authority, authority.publicKey, null, 6);
+    const mintA = await createMintOf(maker);
+    const mintB = await createMintOf(taker);
+    // taker 先持有 fixture 铸造的 token B, 之后 mint B 只能由 mock swap 铸造
+    fx = await createFixtureWithMints(
+      maker,
+      taker,
+      mintA,
+      mintB,
+      TOKEN_PROGRAM_ID,
+      TOKEN_PROGRAM_ID
+    );
+    await setAuthority(
+      connection,
+      taker,
+      mintB,
+      taker,
+      AuthorityType.MintTokens,
+      mintAuthority
+    );
+
+    // taker 用来支付的 token C, 兑换时转入 poolC
+    mintC = await createMintOf(taker);
+    takerAtaC = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        taker,
+        mintC,
+        taker.publicKey
+      )
+    ).address;
+    await mintTo(connection, taker, mintC, takerAtaC, taker, 1_000_000);
+    poolC = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        taker,
+        mintC,
+        provider.wallet.publicKey
+      )
+    ).address;
+  });
+
+  afterEach(async () => {
+    await setSwapRouter(PublicKey.default);
+  });
+
+  // 用 amountIn 个 token C 换 amountOut 个 token B 的路由, 账户和数据原样传给 take_with_swap
+  const route = async (amountIn: number, amountOut: number) => {
+    const ix = await swapProgram.methods
+      .swap(new BN(amountIn), new BN(amountOut))
+      .accountsPartial({
+        user: fx.taker.publicKey,
+        mintIn: mintC,
+        userIn: takerAtaC,
+        poolIn: poolC,
+        mintOut: fx.mintB,
+        destination: fx.takerAtaB,
+        tokenProgramIn: TOKEN_PROGRAM_ID,
+        tokenProgramOut: TOKEN_PROGRAM_ID,
+      })
+      .instruction();
+    return { data: ix.data, accounts: ix.keys };
+  };
+
+  const takeWithSwap = async (
+    escrow: PublicKey,
+    amountIn: number,
+    amountOut: number,
+    swapProgramId = swapProgram.programId
+  ) => {
+    const { data, accounts } = await route(amountIn, amountOut);
+    return program.methods
+      .takeWithSwap(data, U64_MAX, new BN(0), [], Buffer.alloc(0), false)
+      .accountsPartial({
+        take: takeAccounts(fx, escrow),
+        swapProgram: swapProgramId,
+      })
+      .remainingAccounts(accounts)
+      .signers([fx.taker])
+      .rpc();
+  };
+
+  it('is disabled until the admin sets a router', async () => {
+    const { escrow } = await makeEscrow(fx);
+    await expectError(takeWithSwap(escrow, 500, 1_000), 'InvalidSwapRouter');
+  });
+
+  it('pays with token C and leaves the surplus with the taker', async () => {
+    await setSwapRouter(swapProgram.programId);
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 1_000 });
+    const balanceB = await tokenBalance(fx.takerAtaB);
+
+    await takeWithSwap(escrow, 500, 1_200);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(balanceB + 200n);
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(poolC)).to.equal(500n);
+  });
+
+  it('rejects a swap that does not cover the price', async () => {
+    await setSwapRouter(swapProgram.programId);
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 1_000 });
+
+    // taker 原有的 token B 足够支付, 但兑换只得到了 999 个
+    await expectError(takeWithSwap(escrow, 500, 999), 'InsufficientSwapOutput');
+  });
+
+  it('rejects a router other than the configured one', async () => {
+    await setSwapRouter(swapProgram.programId);
+    const { escrow } = await makeEscrow(fx);
+
+    await expectError(
+      takeWithSwap(escrow, 500, 1_000, TOKEN_PROGRAM_ID),
+      'InvalidSwapRouter'
+    );
+  });
+});
@@ -0,0 +1,143 @@
+import {
+  AuthorityType,
+  approve,
+  createAssociatedTokenAccount,
+  createAccount,
+  mintTo,
+  setAuthority,
+} from '@solana/spl-token';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('taker token accounts', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  // 用新的 keypair 作为地址创建非 ATA 的 token 账户
+  const createTokenAccount = (mint: PublicKey, owner: Keypair) =>
+    createAccount(
+      connection,
+      fx.taker,
+      mint,
+      owner.publicKey,
+      Keypair.generate()
+    );
+
+  it('pays token A into a non-ATA account of the taker', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const takerTokenA = await createTokenAccount(fx.mintA, fx.taker);
+
+    await takeEscrow(fx, escrow, fx.taker, { takerTokenA });
+
+    expect(await tokenBalance(takerTokenA)).to.equal(1_000n);
+    // 没有创建 taker 的 token A ATA
+    const takerAtaA = ata(fx.mintA, fx.taker.publicKey);
+    expect(await connection.getAccountInfo(takerAtaA)).to.be.null;
+  });
+
+  it('rejects a token A account owned by someone else', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+    const stranger = await fundedKeypair();
+    const takerTokenA = await createTokenAccount(fx.mintA, stranger);
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { takerTokenA }),
+      'ConstraintTokenOwner'
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+
+  it('rejects a token A account with a delegate', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const takerTokenA = await createTokenAccount(fx.mintA, fx.taker);
+    const delegate = Keypair.generate();
+    await approve(
+      connection,
+      fx.taker,
+      takerTokenA,
+      delegate.publicKey,
+      fx.taker,
+      1
+    );
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { takerTokenA }),
+      'DelegatedTakerAccount'
+    );
+  });
+
+  it('rejects a token A account with a close authority', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const takerTokenA = await createTokenAccount(fx.mintA, fx.taker);
+    await setAuthority(
+      connection,
+      fx.taker,
+      takerTokenA,
+      fx.taker,
+      AuthorityType.CloseAccount,
+      Keypair.generate().publicKey
+    );
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { takerTokenA }),
+      'DelegatedTakerAccount'
+    );
+  });
+
+  it('pays token B from a non-ATA account of the taker', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const takerAtaB = await createTokenAccount(fx.mintB, fx.taker);
+    await mintTo(connection, fx.taker, fx.mintB, takerAtaB, fx.taker, 500);
+
+    await takeEscrow(fx, escrow, fx.taker, { takerAtaB });
+
+    expect(await tokenBalance(takerAtaB)).to.equal(0n);
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n);
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      500n
+    );
+  });
+
+  it('takes without the ATA programs when the ATAs exist', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    const takerAtaA = await createAssociatedTokenAccount(
+      connection,
+      fx.taker,
+      fx.mintA,
+      fx.taker.publicKey
+    );
+    await createAssociatedTokenAccount(
+      connection,
+      fx.taker,
+      fx.mintB,
+      fx.maker.publicKey
+    );
+
+    await takeEscrow(fx, escrow, fx.taker, { skipAtaPrograms: true });
+    expect(await tokenBalance(takerAtaA)).to.equal(1_000n);
+  });
+
+  it('rejects a take without the ATA programs for a missing ATA', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+
+    await expectError(
+      takeEscrow(fx, escrow, fx.taker, { skipAtaPrograms: true }),
+      'MissingAtaPrograms'
+    );
+    expect(await tokenBalance(vault)).to.equal(1_000n);
+  });
+});
@@ -0,0 +1,113 @@
+import { BN } from '@coral-xyz/anchor';
+import {
+  TOKEN_PROGRAM_ID,
+  createMintToInstruction,
+} from '@solana/spl-token';
+import { PublicKey, TransactionInstruction } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  U64_MAX,
+  ata,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('top_up', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const topUp = (
+    escrow: PublicKey,
+    additionalAmount: BN | number,
+    preInstructions: TransactionInstruction[] = []
+  ) =>
+    program.methods
+      .topUp(new BN(additionalAmount))
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .preInstructions(preInstructions)
+      .signers([fx.maker])
+      .rpc();
+
+  it('adds token A at the same unit price', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+
+    await topUp(escrow, 1_000);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.amount.toNumber()).to.equal(2_000);
+    expect(state.receive.toNumber()).to.equal(1_000);
+    expect(await tokenBalance(vault)).to.equal(2_000n);
+  });
+
+  it('makes a take pay the scaled total', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 500 });
+    await topUp(escrow, 500);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_500n
+    );
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      750n
+    );
+  });
+
+  it('rejects a zero top-up', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await expectError(topUp(escrow, 0), 'InvalidAmount');
+  });
+
+  it('errors instead of wrapping when receive overflows', async () => {
+    const { escrow } = await makeEscrow(fx, {
+      amount: 1,
+      receive: U64_MAX,
+    });
+
+    await expectError(topUp(escrow, 1), 'MathOverflow');
+  });
+
+  it('adds the live maker balance for the u64::MAX sentinel', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+    const makerAtaA = ata(fx.mintA, fx.maker.publicKey);
+    const balance = await tokenBalance(makerAtaA);
+
+    // 客户端构造交易之后 maker 又收到 1_000 个 token A, 在同一个交易中先于 top_up 执行
+    const incoming = createMintToInstruction(
+      fx.mintA,
+      makerAtaA,
+      fx.maker.publicKey,
+      1_000
+    );
+    await topUp(escrow, U64_MAX, [incoming]);
+
+    const state = await program.account.escrow.fetch(escrow);
+    const expected = 1_000n + balance + 1_000n;
+    expect(state.amount.toString()).to.equal(expected.toString());
+    expect(await tokenBalance(vault)).to.equal(expected);
+    expect(await tokenBalance(makerAtaA)).to.equal(0n);
+
+    // 余额已经全部存入, 再次使用 sentinel 没有可以存入的 token A
+    await expectError(topUp(escrow, U64_MAX), 'EmptyMakerBalance');
+  });
+});
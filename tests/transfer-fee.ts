@@ -0,0 +1,160 @@
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+} from '@solana/spl-token';
+import { Keypair } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixtureWithMints,
+  createMintWithExtensions,
+  fetchEvents,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// 创建一个带有 TransferFee 扩展的 Token-2022 mint, 铸币权限属于 authority
+const createTransferFeeMint = (
+  authority: Keypair,
+  feeBps: number,
+  maxFee: bigint
+) =>
+  createMintWithExtensions(
+    authority,
+    [ExtensionType.TransferFeeConfig],
+    (mint) => [
+      createInitializeTransferFeeConfigInstruction(
+        mint,
+        authority.publicKey,
+        authority.publicKey,
+        feeBps,
+        maxFee,
+        TOKEN_2022_PROGRAM_ID
+      ),
+    ]
+  );
+
+// 和 Token-2022 的计算方式一致: 向上取整, 不超过 maxFee
+const transferFee = (amount: bigint, feeBps: number, maxFee: bigint) => {
+  const fee = (amount * BigInt(feeBps) + 9_999n) / 10_000n;
+  return fee < maxFee ? fee : maxFee;
+};
+
+describe('transfer fee', () => {
+  const rates: [string, number, bigint][] = [
+    ['1%', 100, 1_000_000n],
+    ['2.5%', 250, 1_000_000n],
+    ['10% capped at 5', 1_000, 5n],
+  ];
+
+  for (const [name, feeBps, maxFee] of rates) {
+    describe(`mint A at ${name}`, () => {
+      let fx: Fixture;
+
+      beforeEach(async () => {
+        const maker = await fundedKeypair();
+        const taker = await fundedKeypair();
+        const mintA = await createTransferFeeMint(maker, feeBps, maxFee);
+        const mintB = await createMint(
+          connection,
+          taker,
+          taker.publicKey,
+          null,
+          6
+        );
+        fx = await createFixtureWithMints(
+          maker,
+          taker,
+          mintA,
+          mintB,
+          TOKEN_2022_PROGRAM_ID,
+          TOKEN_PROGRAM_ID
+        );
+      });
+
+      it('records the net deposit and reports both legs', async () => {
+        const deposit = 10_000n;
+        const inVault = deposit - transferFee(deposit, feeBps, maxFee);
+        const toTaker = inVault - transferFee(inVault, feeBps, maxFee);
+
+        const { escrow, vault, signature } = await makeEscrow(fx, {
+          amount: Number(deposit),
+          receive: 1_000,
+        });
+
+        expect(await tokenBalance(vault)).to.equal(inVault);
+        const state = await program.account.escrow.fetch(escrow);
+        expect(state.amount.toString()).to.equal(inVault.toString());
+        const [makeEvent] = await fetchEvents(signature);
+        expect(makeEvent.data.amount.toString()).to.equal(deposit.toString());
+        expect(makeEvent.data.netAmount.toString()).to.equal(
+          inVault.toString()
+        );
+
+        const takeSignature = await takeEscrow(fx, escrow);
+
+        expect(
+          await tokenBalance(
+            ata(fx.mintA, fx.taker.publicKey, TOKEN_2022_PROGRAM_ID)
+          )
+        ).to.equal(toTaker);
+        const [takeEvent] = await fetchEvents(takeSignature);
+        expect(takeEvent.data.amountA.toString()).to.equal(inVault.toString());
+        expect(takeEvent.data.netAmountA.toString()).to.equal(
+          toTaker.toString()
+        );
+      });
+    });
+
+    describe(`mint B at ${name}`, () => {
+      let fx: Fixture;
+
+      beforeEach(async () => {
+        const maker = await fundedKeypair();
+        const taker = await fundedKeypair();
+        const mintA = await createMint(
+          connection,
+          maker,
+          maker.publicKey,
+          null,
+          6
+        );
+        const mintB = await createTransferFeeMint(taker, feeBps, maxFee);
+        fx = await createFixtureWithMints(
+          maker,
+          taker,
+          mintA,
+          mintB,
+          TOKEN_PROGRAM_ID,
+          TOKEN_2022_PROGRAM_ID
+        );
+      });
+
+      it('reports the gross payment and what the maker nets', async () => {
+        const receive = 10_000n;
+        const toMaker = receive - transferFee(receive, feeBps, maxFee);
+        const { escrow } = await makeEscrow(fx, { receive: Number(receive) });
+
+        const signature = await takeEscrow(fx, escrow);
+
+        expect(await tokenBalance(fx.takerAtaB)).to.equal(1_000_000n - receive);
+        expect(
+          await tokenBalance(
+            ata(fx.mintB, fx.maker.publicKey, TOKEN_2022_PROGRAM_ID)
+          )
+        ).to.equal(toMaker);
+        const [event] = await fetchEvents(signature);
+        expect(event.data.amountB.toString()).to.equal(receive.toString());
+        expect(event.data.netAmountB.toString()).to.equal(toMaker.toString());
+      });
+    });
+  }
+});
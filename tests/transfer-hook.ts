@@ -0,0 +1,213 @@
+import * as anchor from '@coral-xyz/anchor';
+import { BN, Program } from '@coral-xyz/anchor';
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createInitializeTransferHookInstruction,
+  createMint,
+} from '@solana/spl-token';
+import { AccountMeta, Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import { TransferHookCounter } from '../target/types/transfer_hook_counter';
+import {
+  Fixture,
+  connection,
+  createFixtureWithMints,
+  createMintWithExtensions,
+  fundedKeypair,
+  makeEscrow,
+  nowSeconds,
+  program,
+  refundEscrow,
+  sleep,
+  takeEscrow,
+} from './utils';
+
+const hookProgram = anchor.workspace
+  .transferHookCounter as Program<TransferHookCounter>;
+
+const findCounter = (mint: PublicKey) =>
+  PublicKey.findProgramAddressSync(
+    [Buffer.from('counter'), mint.toBuffer()],
+    hookProgram.programId
+  )[0];
+
+// 每次转账 Token-2022 都会调用 hook, hook 需要的账户由调用方通过 remaining accounts 传入
+function hookAccounts(mint: PublicKey): AccountMeta[] {
+  const extraAccountMetas = PublicKey.findProgramAddressSync(
+    [Buffer.from('extra-account-metas'), mint.toBuffer()],
+    hookProgram.programId
+  )[0];
+
+  return [
+    { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: extraAccountMetas, isSigner: false, isWritable: false },
+    { pubkey: findCounter(mint), isSigner: false, isWritable: true },
+  ];
+}
+
+// 创建一个带有 TransferHook 扩展的 Token-2022 mint 并初始化 hook 的计数器
+async function createHookedMint(authority: Keypair): Promise<PublicKey> {
+  const mint = await createMintWithExtensions(
+    authority,
+    [ExtensionType.TransferHook],
+    (mint) => [
+      createInitializeTransferHookInstruction(
+        mint,
+        authority.publicKey,
+        hookProgram.programId,
+        TOKEN_2022_PROGRAM_ID
+      ),
+    ]
+  );
+
+  await hookProgram.methods
+    .initialize()
+    .accountsPartial({ payer: authority.publicKey, mint })
+    .signers([authority])
+    .rpc();
+
+  return mint;
+}
+
+const hookCount = async (mint: PublicKey) =>
+  (await hookProgram.account.counter.fetch(findCounter(mint))).count.toNumber();
+
+describe('transfer hook', () => {
+  describe('on mint A', () => {
+    let fx: Fixture;
+
+    beforeEach(async () => {
+      const maker = await fundedKeypair();
+      const taker = await fundedKeypair();
+      const mintA = await createHookedMint(maker);
+      const mintB = await createMint(
+        connection,
+        taker,
+        taker.publicKey,
+        null,
+        6
+      );
+      fx = await createFixtureWithMints(
+        maker,
+        taker,
+        mintA,
+        mintB,
+        TOKEN_2022_PROGRAM_ID,
+        TOKEN_PROGRAM_ID
+      );
+    });
+
+    it('runs the hook on the deposit and on take', async () => {
+      const remainingAccounts = hookAccounts(fx.mintA);
+
+      const { escrow } = await makeEscrow(fx, { remainingAccounts });
+      expect(await hookCount(fx.mintA)).to.equal(1);
+
+      await takeEscrow(fx, escrow, fx.taker, { remainingAccounts });
+      expect(await hookCount(fx.mintA)).to.equal(2);
+    });
+
+    it('runs the hook on refund', async () => {
+      const remainingAccounts = hookAccounts(fx.mintA);
+      const { escrow } = await makeEscrow(fx, { remainingAccounts });
+
+      await refundEscrow(fx, escrow, remainingAccounts);
+
+      expect(await hookCount(fx.mintA)).to.equal(2);
+    });
+
+    it('runs the hook on withdraw_partial and refund_expired', async () => {
+      const remainingAccounts = hookAccounts(fx.mintA);
+      const { escrow } = await makeEscrow(fx, { remainingAccounts });
+
+      await program.methods
+        .withdrawPartial(new BN(400))
+        .accountsPartial({
+          maker: fx.maker.publicKey,
+          escrow,
+          mintA: fx.mintA,
+          pairIndex: null,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .remainingAccounts(remainingAccounts)
+        .signers([fx.maker])
+        .rpc();
+      expect(await hookCount(fx.mintA)).to.equal(2);
+
+      const expiring = await makeEscrow(fx, {
+        expiry: nowSeconds() + 2,
+        remainingAccounts,
+      });
+      await sleep(4_000);
+      const cranker = await fundedKeypair();
+      await program.methods
+        .refundExpired()
+        .accountsPartial({
+          refund: {
+            authority: cranker.publicKey,
+            maker: fx.maker.publicKey,
+            escrow: expiring.escrow,
+            mintA: fx.mintA,
+            vault: expiring.vault,
+            makerAtaA: fx.makerAtaA,
+            pairIndex: null,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+          },
+        })
+        .remainingAccounts(remainingAccounts)
+        .signers([cranker])
+        .rpc();
+
+      expect(await hookCount(fx.mintA)).to.equal(4);
+      expect(await connection.getAccountInfo(expiring.escrow)).to.be.null;
+    });
+
+    it('fails without the hook accounts', async () => {
+      let failed = false;
+      try {
+        await makeEscrow(fx);
+      } catch {
+        failed = true;
+      }
+      expect(failed).to.be.true;
+    });
+  });
+
+  describe('on mint B', () => {
+    let fx: Fixture;
+
+    beforeEach(async () => {
+      const maker = await fundedKeypair();
+      const taker = await fundedKeypair();
+      const mintA = await createMint(
+        connection,
+        maker,
+        maker.publicKey,
+        null,
+        6
+      );
+      const mintB = await createHookedMint(taker);
+      fx = await createFixtureWithMints(
+        maker,
+        taker,
+        mintA,
+        mintB,
+        TOKEN_PROGRAM_ID,
+        TOKEN_2022_PROGRAM_ID
+      );
+    });
+
+    it('runs the hook when the taker pays the maker', async () => {
+      const { escrow } = await makeEscrow(fx);
+      expect(await hookCount(fx.mintB)).to.equal(0);
+
+      await takeEscrow(fx, escrow, fx.taker, {
+        remainingAccounts: hookAccounts(fx.mintB),
+      });
+
+      expect(await hookCount(fx.mintB)).to.equal(1);
+    });
+  });
+});
@@ -0,0 +1,113 @@
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  findEscrow,
+  findRegistry,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  refundEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('transfer_maker', () => {
+  let fx: Fixture;
+  let newMaker: Keypair;
+
+  beforeEach(async () => {
+    await ensureConfig();
+    fx = await createFixture();
+    newMaker = await fundedKeypair();
+  });
+
+  const transferMaker = (escrow: PublicKey, to: Keypair = newMaker) =>
+    program.methods
+      .transferMaker()
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        newMaker: to.publicKey,
+        rentPayer: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: fx.tokenProgramA,
+      })
+      .signers([fx.maker, to])
+      .rpc();
+
+  it('moves the escrow under the new maker', async () => {
+    const { escrow, vault, seed } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+    const newEscrow = findEscrow(newMaker.publicKey, seed);
+
+    await transferMaker(escrow);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(await tokenBalance(ata(fx.mintA, newEscrow))).to.equal(1_000n);
+    const state = await program.account.escrow.fetch(newEscrow);
+    expect(state.maker.toBase58()).to.equal(newMaker.publicKey.toBase58());
+    expect(state.receiveTo.toBase58()).to.equal(newMaker.publicKey.toBase58());
+    expect(state.rentPayer.toBase58()).to.equal(newMaker.publicKey.toBase58());
+    expect(state.receive.toNumber()).to.equal(500);
+
+    const oldRegistry = await program.account.makerRegistry.fetch(
+      findRegistry(fx.maker.publicKey)
+    );
+    expect(oldRegistry.seeds).to.be.empty;
+    const newRegistry = await program.account.makerRegistry.fetch(
+      findRegistry(newMaker.publicKey)
+    );
+    expect(newRegistry.seeds.map((open) => open.toString())).to.deep.equal([
+      seed.toString(),
+    ]);
+  });
+
+  it('pays the new maker when the escrow is taken', async () => {
+    const { escrow, seed } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+    await transferMaker(escrow);
+
+    await takeEscrow(
+      { ...fx, maker: newMaker },
+      findEscrow(newMaker.publicKey, seed)
+    );
+
+    expect(await tokenBalance(ata(fx.mintB, newMaker.publicKey))).to.equal(
+      500n
+    );
+    expect(await tokenBalance(ata(fx.mintA, fx.taker.publicKey))).to.equal(
+      1_000n
+    );
+  });
+
+  it('leaves the old maker without any power over the escrow', async () => {
+    const { escrow, seed } = await makeEscrow(fx, { amount: 1_000 });
+    await transferMaker(escrow);
+    const newEscrow = findEscrow(newMaker.publicKey, seed);
+    const balance = await tokenBalance(fx.makerAtaA);
+
+    await expectError(refundEscrow(fx, newEscrow), 'ConstraintSeeds');
+    await expectError(
+      transferMaker(newEscrow, await fundedKeypair()),
+      'ConstraintSeeds'
+    );
+
+    await refundEscrow({ ...fx, maker: newMaker }, newEscrow);
+    expect(await tokenBalance(ata(fx.mintA, newMaker.publicKey))).to.equal(
+      1_000n
+    );
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(balance);
+  });
+});
@@ -0,0 +1,121 @@
+import {
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createMint,
+  createSyncNativeInstruction,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import {
+  LAMPORTS_PER_SOL,
+  SystemProgram,
+  Transaction,
+  sendAndConfirmTransaction,
+} from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  connection,
+  createFixture,
+  ensureConfig,
+  expectError,
+  fundedKeypair,
+  makeEscrow,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+// maker 出售普通的 token A 换取 SOL, taker 持有已经包装好的 wSOL
+async function createWsolFixture(): Promise<Fixture> {
+  await ensureConfig();
+  const maker = await fundedKeypair();
+  const taker = await fundedKeypair();
+
+  const mintA = await createMint(connection, maker, maker.publicKey, null, 6);
+  const makerAtaA = await getOrCreateAssociatedTokenAccount(
+    connection,
+    maker,
+    mintA,
+    maker.publicKey
+  );
+  await mintTo(connection, maker, mintA, makerAtaA.address, maker, 1_000_000);
+
+  const takerAtaB = await getOrCreateAssociatedTokenAccount(
+    connection,
+    taker,
+    NATIVE_MINT,
+    taker.publicKey
+  );
+  const wrap = new Transaction().add(
+    SystemProgram.transfer({
+      fromPubkey: taker.publicKey,
+      toPubkey: takerAtaB.address,
+      lamports: LAMPORTS_PER_SOL,
+    }),
+    createSyncNativeInstruction(takerAtaB.address)
+  );
+  await sendAndConfirmTransaction(connection, wrap, [taker]);
+
+  return {
+    maker,
+    taker,
+    mintA,
+    mintB: NATIVE_MINT,
+    makerAtaA: makerAtaA.address,
+    takerAtaB: takerAtaB.address,
+    tokenProgramA: TOKEN_PROGRAM_ID,
+    tokenProgramB: TOKEN_PROGRAM_ID,
+  };
+}
+
+describe('unwrap wSOL for the maker', () => {
+  const receive = LAMPORTS_PER_SOL / 2;
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createWsolFixture();
+  });
+
+  it('pays the maker in SOL on take when asked to unwrap', async () => {
+    // 由另一个账户支付 escrow 和 vault 的租金, maker 的余额变化只有 receive
+    const rentPayer = await fundedKeypair();
+    const { escrow } = await makeEscrow(fx, { receive, rentPayer });
+    const unwrapAtaB = ata(NATIVE_MINT, escrow);
+    const before = await connection.getBalance(fx.maker.publicKey);
+
+    await takeEscrow(fx, escrow, fx.taker, { unwrapAtaB });
+
+    // 临时账户的租金还给支付它的 taker, maker 只收到 receive
+    expect(await connection.getBalance(fx.maker.publicKey)).to.equal(
+      before + receive
+    );
+    expect(await tokenBalance(fx.takerAtaB)).to.equal(
+      BigInt(LAMPORTS_PER_SOL - receive)
+    );
+    expect(await connection.getAccountInfo(unwrapAtaB)).to.be.null;
+    const makerWsol = ata(NATIVE_MINT, fx.maker.publicKey);
+    expect(await connection.getAccountInfo(makerWsol)).to.be.null;
+  });
+
+  it('pays wSOL into the maker ATA without the unwrap account', async () => {
+    const { escrow } = await makeEscrow(fx, { receive });
+
+    await takeEscrow(fx, escrow);
+
+    const makerWsol = ata(NATIVE_MINT, fx.maker.publicKey);
+    expect(await tokenBalance(makerWsol)).to.equal(BigInt(receive));
+  });
+
+  it('rejects an unwrap account for other mints', async () => {
+    const tokenFx = await createFixture();
+    const { escrow } = await makeEscrow(tokenFx);
+
+    await expectError(
+      takeEscrow(tokenFx, escrow, tokenFx.taker, {
+        unwrapAtaB: ata(tokenFx.mintB, escrow),
+      }),
+      'NotNativeMint'
+    );
+  });
+});
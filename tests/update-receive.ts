@@ -0,0 +1,72 @@
+import { BN } from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  ata,
+  createFixture,
+  expectError,
+  fetchEvents,
+  fundedKeypair,
+  makeEscrow,
+  program,
+  takeEscrow,
+  tokenBalance,
+} from './utils';
+
+describe('update_receive', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const updateReceive = (
+    escrow: PublicKey,
+    newReceive: number,
+    maker = fx.maker
+  ) =>
+    program.methods
+      .updateReceive(new BN(newReceive))
+      .accountsPartial({ maker: maker.publicKey, escrow })
+      .signers([maker])
+      .rpc();
+
+  it('reprices an open offer and emits an UpdateEvent', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 500 });
+
+    const signature = await updateReceive(escrow, 800);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.receive.toNumber()).to.equal(800);
+
+    const [event] = await fetchEvents(signature);
+    expect(event.name).to.equal('updateEvent');
+    expect(event.data.oldReceive.toNumber()).to.equal(500);
+    expect(event.data.newReceive.toNumber()).to.equal(800);
+  });
+
+  it('makes a later take pay the new amount', async () => {
+    const { escrow } = await makeEscrow(fx, { receive: 500 });
+    await updateReceive(escrow, 800);
+
+    await takeEscrow(fx, escrow);
+
+    expect(await tokenBalance(ata(fx.mintB, fx.maker.publicKey))).to.equal(
+      800n
+    );
+  });
+
+  it('rejects a zero price', async () => {
+    const { escrow } = await makeEscrow(fx);
+
+    await expectError(updateReceive(escrow, 0), 'InvalidAmount');
+  });
+
+  it('rejects a reprice by anyone but the maker', async () => {
+    const { escrow } = await makeEscrow(fx);
+    const stranger = await fundedKeypair();
+
+    await expectError(updateReceive(escrow, 1, stranger), 'InvalidMaker');
+  });
+});
@@ -0,0 +1,679 @@
+import * as anchor from '@coral-xyz/anchor';
+import { BN, Program } from '@coral-xyz/anchor';
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createEnableRequiredMemoTransfersInstruction,
+  createInitializeMintInstruction,
+  createMint,
+  createReallocateInstruction,
+  getAssociatedTokenAddressSync,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from '@solana/spl-token';
+import {
+  AccountMeta,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  sendAndConfirmTransaction,
+} from '@solana/web3.js';
+import { expect } from 'chai';
+import { createHash } from 'crypto';
+import { BlueshiftAnchorEscrow } from '../target/types/blueshift_anchor_escrow';
+
+anchor.setProvider(anchor.AnchorProvider.env());
+
+export const provider = anchor.getProvider() as anchor.AnchorProvider;
+export const connection = provider.connection;
+export const program = anchor.workspace
+  .blueshiftAnchorEscrow as Program<BlueshiftAnchorEscrow>;
+
+// 创建一个有 SOL 余额的新钱包
+export async function fundedKeypair(sol = 10): Promise<Keypair> {
+  const keypair = Keypair.generate();
+  const signature = await connection.requestAirdrop(
+    keypair.publicKey,
+    sol * LAMPORTS_PER_SOL
+  );
+  await connection.confirmTransaction(signature, 'confirmed');
+  return keypair;
+}
+
+export function randomSeed(): BN {
+  return new BN(Math.floor(Math.random() * Number.MAX_SAFE_INTEGER));
+}
+
+export function findEscrow(maker: PublicKey, seed: BN): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('escrow'), maker.toBuffer(), seed.toArrayLike(Buffer, 'le', 8)],
+    program.programId
+  )[0];
+}
+
+// take 时可选写入的成交记录
+export function findReceipt(escrow: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('receipt'), escrow.toBuffer()],
+    program.programId
+  )[0];
+}
+
+// maker 仍未关闭的托管的 seed 索引
+export function findRegistry(maker: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('registry'), maker.toBuffer()],
+    program.programId
+  )[0];
+}
+
+// 钱包的累计成交统计, 用于选择手续费档位
+export function findUserStats(wallet: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('user_stats'), wallet.toBuffer()],
+    program.programId
+  )[0];
+}
+
+export const configPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('config')],
+  program.programId
+)[0];
+
+export const feeAuthorityPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('fee_authority')],
+  program.programId
+)[0];
+
+export const statsPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('stats')],
+  program.programId
+)[0];
+
+export const mintAllowlistPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('mint_allowlist')],
+  program.programId
+)[0];
+
+export const mintBlocklistPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('mint_blocklist')],
+  program.programId
+)[0];
+
+export const exemptListPda = PublicKey.findProgramAddressSync(
+  [Buffer.from('exempt_list')],
+  program.programId
+)[0];
+
+// 协议配置是全局唯一的, 第一次使用时由 provider 钱包作为 admin 初始化, 手续费为 0
+export async function ensureConfig() {
+  if (await connection.getAccountInfo(configPda)) {
+    return;
+  }
+  await program.methods
+    .initializeConfig(0)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+}
+
+// 修改协议手续费, 使用 provider 钱包作为 admin
+export async function setFee(feeBps: number) {
+  await program.methods
+    .setFee(feeBps)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+}
+
+// 修改 referrer 分得的手续费比例, 使用 provider 钱包作为 admin
+export async function setReferral(referralBps: number) {
+  await program.methods
+    .setReferral(referralBps)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+}
+
+// 暂停或恢复协议, 使用 provider 钱包作为 admin
+export async function setPaused(paused: boolean) {
+  const method = paused ? program.methods.pause() : program.methods.unpause();
+  await method.accountsPartial({ admin: provider.wallet.publicKey }).rpc();
+}
+
+// 替换 mint 白名单并设置是否启用, 使用 provider 钱包作为 admin
+export async function setAllowedMints(enabled: boolean, mints: PublicKey[]) {
+  await program.methods
+    .setAllowedMints(enabled, mints)
+    .accountsPartial({ admin: provider.wallet.publicKey })
+    .rpc();
+}
+
+// 封禁或解封 mint, 使用 provider 钱包作为 admin
+export async function setBlocked(mint: PublicKey, blocked: boolean) {
+  const method = blocked
+    ? program.methods.addBlockedMint(mint)
+    : program.methods.removeBlockedMint(mint);
+  await method.accountsPartial({ admin: provider.wallet.publicKey }).rpc();
+}
+
+// 冻结或解冻托管, 使用 provider 钱包作为 admin
+export async function setFrozen(escrow: PublicKey, frozen: boolean) {
+  const method = frozen
+    ? program.methods.freezeEscrow()
+    : program.methods.unfreezeEscrow();
+  await method
+    .accountsPartial({ admin: provider.wallet.publicKey, escrow })
+    .rpc();
+}
+
+export function ata(
+  mint: PublicKey,
+  owner: PublicKey,
+  tokenProgram = TOKEN_PROGRAM_ID
+): PublicKey {
+  return getAssociatedTokenAddressSync(mint, owner, true, tokenProgram);
+}
+
+export async function tokenBalance(address: PublicKey): Promise<bigint> {
+  const balance = await connection.getTokenAccountBalance(address);
+  return BigInt(balance.value.amount);
+}
+
+export const nowSeconds = () => Math.floor(Date.now() / 1000);
+
+export const sleep = (ms: number) =>
+  new Promise((resolve) => setTimeout(resolve, ms));
+
+// 创建(或复用) owner 的 Token-2022 ATA, 扩容出 MemoTransfer 扩展并要求转入时必须带 memo
+export async function requireMemos(owner: Keypair, mint: PublicKey) {
+  const { address } = await getOrCreateAssociatedTokenAccount(
+    connection,
+    owner,
+    mint,
+    owner.publicKey,
+    false,
+    undefined,
+    undefined,
+    TOKEN_2022_PROGRAM_ID
+  );
+
+  const tx = new Transaction().add(
+    createReallocateInstruction(
+      address,
+      owner.publicKey,
+      [ExtensionType.MemoTransfer],
+      owner.publicKey,
+      [],
+      TOKEN_2022_PROGRAM_ID
+    ),
+    createEnableRequiredMemoTransfersInstruction(
+      address,
+      owner.publicKey,
+      [],
+      TOKEN_2022_PROGRAM_ID
+    )
+  );
+  await sendAndConfirmTransaction(connection, tx, [owner]);
+
+  return address;
+}
+
+// 创建一个带有扩展的 Token-2022 mint, 铸币权限属于 authority
+// initExtensions 返回初始化各个扩展的指令, 它们必须在初始化 mint 之前执行
+export async function createMintWithExtensions(
+  authority: Keypair,
+  extensions: ExtensionType[],
+  initExtensions: (mint: PublicKey) => TransactionInstruction[]
+): Promise<PublicKey> {
+  const mint = Keypair.generate();
+  const mintLen = getMintLen(extensions);
+  const lamports = await connection.getMinimumBalanceForRentExemption(mintLen);
+
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: authority.publicKey,
+      newAccountPubkey: mint.publicKey,
+      space: mintLen,
+      lamports,
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    ...initExtensions(mint.publicKey),
+    createInitializeMintInstruction(
+      mint.publicKey,
+      6,
+      authority.publicKey,
+      null,
+      TOKEN_2022_PROGRAM_ID
+    )
+  );
+  await sendAndConfirmTransaction(connection, tx, [authority, mint]);
+
+  return mint.publicKey;
+}
+
+// 两个 mint, maker 持有 token A, taker 持有 token B
+export interface Fixture {
+  maker: Keypair;
+  taker: Keypair;
+  mintA: PublicKey;
+  mintB: PublicKey;
+  makerAtaA: PublicKey;
+  takerAtaB: PublicKey;
+  // 两个 mint 各自的 token 程序, 可以一个是 spl-token 另一个是 Token-2022
+  tokenProgramA: PublicKey;
+  tokenProgramB: PublicKey;
+}
+
+export async function createFixture(
+  amountA = 1_000_000,
+  amountB = 1_000_000,
+  tokenProgramA = TOKEN_PROGRAM_ID,
+  tokenProgramB = TOKEN_PROGRAM_ID
+): Promise<Fixture> {
+  const maker = await fundedKeypair();
+  const taker = await fundedKeypair();
+
+  const mintA = await createMint(
+    connection,
+    maker,
+    maker.publicKey,
+    null,
+    6,
+    undefined,
+    undefined,
+    tokenProgramA
+  );
+  const mintB = await createMint(
+    connection,
+    taker,
+    taker.publicKey,
+    null,
+    6,
+    undefined,
+    undefined,
+    tokenProgramB
+  );
+
+  return createFixtureWithMints(
+    maker,
+    taker,
+    mintA,
+    mintB,
+    tokenProgramA,
+    tokenProgramB,
+    amountA,
+    amountB
+  );
+}
+
+// 使用已经创建好的 mint(例如带有 Token-2022 扩展的 mint)生成 fixture, mint A 和 mint B 的铸币权限分别属于 maker 和 taker
+export async function createFixtureWithMints(
+  maker: Keypair,
+  taker: Keypair,
+  mintA: PublicKey,
+  mintB: PublicKey,
+  tokenProgramA: PublicKey,
+  tokenProgramB: PublicKey,
+  amountA = 1_000_000,
+  amountB = 1_000_000
+): Promise<Fixture> {
+  await ensureConfig();
+
+  const makerAtaA = (
+    await getOrCreateAssociatedTokenAccount(
+      connection,
+      maker,
+      mintA,
+      maker.publicKey,
+      false,
+      undefined,
+      undefined,
+      tokenProgramA
+    )
+  ).address;
+  const takerAtaB = (
+    await getOrCreateAssociatedTokenAccount(
+      connection,
+      taker,
+      mintB,
+      taker.publicKey,
+      false,
+      undefined,
+      undefined,
+      tokenProgramB
+    )
+  ).address;
+
+  await mintTo(
+    connection,
+    maker,
+    mintA,
+    makerAtaA,
+    maker,
+    amountA,
+    [],
+    undefined,
+    tokenProgramA
+  );
+  await mintTo(
+    connection,
+    taker,
+    mintB,
+    takerAtaB,
+    taker,
+    amountB,
+    [],
+    undefined,
+    tokenProgramB
+  );
+
+  return {
+    maker,
+    taker,
+    mintA,
+    mintB,
+    makerAtaA,
+    takerAtaB,
+    tokenProgramA,
+    tokenProgramB,
+  };
+}
+
+// 全 0 的默克尔根表示不限制 taker, 全 0 的 hashlock 表示普通托管
+export const EMPTY_ROOT: number[] = Array(32).fill(0);
+
+// 和链上 merkle 模块相同的哈希规则: 叶子前缀 0, 内部节点前缀 1 并对子节点排序
+const sha256 = (...parts: Buffer[]) =>
+  createHash('sha256').update(Buffer.concat(parts)).digest();
+
+export const leafHash = (taker: PublicKey) =>
+  sha256(Buffer.from([0]), taker.toBuffer());
+
+const nodeHash = (a: Buffer, b: Buffer) =>
+  Buffer.compare(a, b) <= 0
+    ? sha256(Buffer.from([1]), a, b)
+    : sha256(Buffer.from([1]), b, a);
+
+// 构建允许列表的默克尔树, 奇数个节点时最后一个节点直接提升到上一层
+export function buildAllowlist(takers: PublicKey[]) {
+  let level = takers.map(leafHash);
+  let positions = takers.map((_, i) => i);
+  const proofs: Buffer[][] = takers.map(() => []);
+
+  while (level.length > 1) {
+    positions = positions.map((position, i) => {
+      const sibling = position ^ 1;
+      if (sibling < level.length) {
+        proofs[i].push(level[sibling]);
+      }
+      return Math.floor(position / 2);
+    });
+    const next: Buffer[] = [];
+    for (let i = 0; i < level.length; i += 2) {
+      next.push(
+        i + 1 < level.length ? nodeHash(level[i], level[i + 1]) : level[i]
+      );
+    }
+    level = next;
+  }
+
+  return {
+    root: Array.from(level[0]),
+    proofFor: (taker: PublicKey) =>
+      proofs[takers.findIndex((t) => t.equals(taker))].map((node) =>
+        Array.from(node)
+      ),
+  };
+}
+
+export interface MakeParams {
+  seed?: BN;
+  receive?: number | BN;
+  amount?: number | BN;
+  expiry?: number;
+  allowedTaker?: PublicKey;
+  takerAllowlistRoot?: number[];
+  hashlock?: number[];
+  startTime?: number;
+  rejectFreezable?: boolean;
+  // 替 maker 支付 escrow 和 vault 租金的账户, 默认是 maker 自己
+  rentPayer?: Keypair;
+  // 接收 token B 的钱包, 默认是 maker
+  receiveTo?: PublicKey;
+  // 可以代替 maker 退还托管的地址, 默认不设置
+  refundDelegate?: PublicKey;
+  // 非 0 时开启 commit-reveal, taker 需要等待的 slot 数
+  commitDelaySlots?: number;
+  // 仲裁人, 设置后 taker 只能通过 lock_take 锁定, 默认不设置
+  arbiter?: PublicKey;
+  // 非 0 时允许 taker 预约, 预约的时长(秒), 默认不允许
+  reservationWindow?: number;
+  // 除 mint B 以外也接受的支付 mint, 每个都有固定的 receive, 默认不设置
+  altPayments?: { mint: PublicKey; receive: BN }[];
+  // 循环托管成交后重新存入的次数, 非 0 时需要 recurring, 默认不循环
+  recurring?: boolean;
+  maxRefills?: number;
+  // 挂单费豁免列表和接收挂单费的账户, 不收取挂单费时不需要传
+  exemptList?: PublicKey;
+  treasury?: PublicKey;
+  // mint 带有 transfer hook 时需要传入的额外账户
+  remainingAccounts?: AccountMeta[];
+}
+
+// 调用 make 并返回 escrow 地址和 seed
+export async function makeEscrow(fx: Fixture, params: MakeParams = {}) {
+  const seed = params.seed ?? randomSeed();
+  const rentPayer = params.rentPayer ?? fx.maker;
+  const signature = await program.methods
+    .make(
+      seed,
+      new BN(params.receive ?? 1_000),
+      new BN(params.amount ?? 1_000),
+      new BN(params.expiry ?? 0),
+      params.allowedTaker ?? PublicKey.default,
+      params.takerAllowlistRoot ?? EMPTY_ROOT,
+      params.hashlock ?? EMPTY_ROOT,
+      new BN(params.startTime ?? 0),
+      params.rejectFreezable ?? false,
+      params.receiveTo ?? PublicKey.default,
+      params.refundDelegate ?? PublicKey.default,
+      new BN(params.commitDelaySlots ?? 0),
+      params.arbiter ?? PublicKey.default,
+      new BN(params.reservationWindow ?? 0),
+      params.altPayments ?? [],
+      params.recurring ?? false,
+      params.maxRefills ?? 0
+    )
+    .accountsPartial({
+      maker: fx.maker.publicKey,
+      rentPayer: rentPayer.publicKey,
+      mintA: fx.mintA,
+      mintB: fx.mintB,
+      exemptList: params.exemptList ?? null,
+      treasury: params.treasury ?? null,
+      tokenProgramA: fx.tokenProgramA,
+      tokenProgramB: fx.tokenProgramB,
+    })
+    .remainingAccounts(params.remainingAccounts ?? [])
+    .signers(rentPayer === fx.maker ? [fx.maker] : [fx.maker, rentPayer])
+    .rpc();
+
+  const escrow = findEscrow(fx.maker.publicKey, seed);
+  const vault = ata(fx.mintA, escrow, fx.tokenProgramA);
+  return { seed, escrow, vault, signature };
+}
+
+export const U64_MAX = new BN('18446744073709551615');
+
+export interface TakeParams {
+  maxReceive?: BN;
+  expectedAmountA?: number;
+  feeVaultB?: PublicKey;
+  referrerAtaB?: PublicKey;
+  // taker 已有的任意 token A 账户, 传入时代替 ATA 接收 token A
+  takerTokenA?: PublicKey;
+  // 支付 token B 的账户, 默认是 taker 的 ATA
+  takerAtaB?: PublicKey;
+  proof?: number[][];
+  preimage?: Buffer;
+  // token B 是 wSOL 时 escrow 拥有的临时账户, 传入时 maker 直接收到 SOL
+  unwrapAtaB?: PublicKey;
+  // 替 taker 支付 ATA 租金的 relayer, 默认是 taker 自己
+  payer?: Keypair;
+  // 为 true 时写入成交记录, 租金由 taker 支付
+  writeReceipt?: boolean;
+  // 托管计入了交易对索引时传入, 成交后从索引中移除, 默认不传
+  pairIndex?: PublicKey;
+  // 按 Pyth 价格成交的托管必须传入 make_oracle 记录的 price feed, 默认不传
+  priceFeed?: PublicKey;
+  // 成交统计账户, 启用手续费档位时必须传入 taker 的, 默认都不传
+  takerStats?: PublicKey;
+  makerStats?: PublicKey;
+  // 为 true 时不传 associated token 和 system 程序, 接收 token 的 ATA 必须已经存在
+  skipAtaPrograms?: boolean;
+  remainingAccounts?: AccountMeta[];
+}
+
+// take 的账户列表, take_with_swap 等嵌套 Take 的指令也使用它
+export function takeAccounts(
+  fx: Fixture,
+  escrow: PublicKey,
+  taker = fx.taker,
+  params: TakeParams = {}
+) {
+  const payer = params.payer ?? taker;
+  return {
+    taker: taker.publicKey,
+    payer: payer.publicKey,
+    maker: fx.maker.publicKey,
+    escrow,
+    mintA: fx.mintA,
+    mintB: fx.mintB,
+    ...(params.takerTokenA ? { takerAtaA: null } : {}),
+    takerTokenA: params.takerTokenA ?? null,
+    takerAtaB:
+      params.takerAtaB ?? ata(fx.mintB, taker.publicKey, fx.tokenProgramB),
+    feeVaultB: params.feeVaultB ?? null,
+    referrerAtaB: params.referrerAtaB ?? null,
+    ...(params.unwrapAtaB ? { makerAtaB: null } : {}),
+    unwrapAtaB: params.unwrapAtaB ?? null,
+    receipt: params.writeReceipt ? findReceipt(escrow) : null,
+    takerStats: params.takerStats ?? null,
+    makerStats: params.makerStats ?? null,
+    pairIndex: params.pairIndex ?? null,
+    priceFeed: params.priceFeed ?? null,
+    ...(params.skipAtaPrograms
+      ? { associatedTokenProgram: null, systemProgram: null }
+      : {}),
+    tokenProgramA: fx.tokenProgramA,
+    tokenProgramB: fx.tokenProgramB,
+  };
+}
+
+// 调用 take, 默认使用 fixture 中的 taker, 不限制价格也不校验 token A 数量, 不传手续费和 referrer 账户
+export async function takeEscrow(
+  fx: Fixture,
+  escrow: PublicKey,
+  taker = fx.taker,
+  params: TakeParams = {}
+) {
+  const payer = params.payer ?? taker;
+  return program.methods
+    .take(
+      params.maxReceive ?? U64_MAX,
+      new BN(params.expectedAmountA ?? 0),
+      params.proof ?? [],
+      params.preimage ?? Buffer.alloc(0),
+      params.writeReceipt ?? false
+    )
+    .accountsPartial(takeAccounts(fx, escrow, taker, params))
+    .remainingAccounts(params.remainingAccounts ?? [])
+    .signers(payer === taker ? [taker] : [taker, payer])
+    .rpc();
+}
+
+// 调用 refund, 默认由 fixture 中的 maker 签名, force 为 true 时允许放弃冻结的 vault
+// authority 可以换成托管的 refund_delegate, token A 仍然退还给 maker
+export async function refundEscrow(
+  fx: Fixture,
+  escrow: PublicKey,
+  remainingAccounts: AccountMeta[] = [],
+  force = false,
+  authority = fx.maker
+) {
+  return program.methods
+    .refund(force)
+    .accountsPartial({
+      authority: authority.publicKey,
+      maker: fx.maker.publicKey,
+      escrow,
+      mintA: fx.mintA,
+      pairIndex: null,
+      tokenProgram: fx.tokenProgramA,
+    })
+    .remainingAccounts(remainingAccounts)
+    .signers([authority])
+    .rpc();
+}
+
+// cancel 使用和 refund 相同的账户, reason 是 CancelReason 的取值
+export async function cancelEscrow(
+  fx: Fixture,
+  escrow: PublicKey,
+  reason: number,
+  retain = false
+) {
+  return program.methods
+    .cancel(reason, retain)
+    .accountsPartial({
+      authority: fx.maker.publicKey,
+      maker: fx.maker.publicKey,
+      escrow,
+      mintA: fx.mintA,
+      pairIndex: null,
+      tokenProgram: fx.tokenProgramA,
+    })
+    .signers([fx.maker])
+    .rpc();
+}
+
+// emit_cpi! 记录的事件是一条 self-CPI 指令, 数据以固定的 8 字节 EVENT_IX_TAG 开头
+const EVENT_IX_TAG = Buffer.from('e445a52e51cb9a1d', 'hex');
+
+// 从交易的 inner instructions 中解析出本程序通过 emit_cpi! 发出的事件
+export async function fetchEvents(signature: string) {
+  await connection.confirmTransaction(signature, 'confirmed');
+  const tx = await connection.getTransaction(signature, {
+    commitment: 'confirmed',
+    maxSupportedTransactionVersion: 0,
+  });
+  const accountKeys = tx.transaction.message.getAccountKeys();
+
+  return tx.meta.innerInstructions
+    .flatMap((inner) => inner.instructions)
+    .filter((ix) =>
+      accountKeys.get(ix.programIdIndex).equals(program.programId)
+    )
+    .map((ix) => Buffer.from(anchor.utils.bytes.bs58.decode(ix.data)))
+    .filter((data) => data.subarray(0, 8).equals(EVENT_IX_TAG))
+    .map((data) =>
+      program.coder.events.decode(
+        anchor.utils.bytes.base64.encode(data.subarray(8))
+      )
+    );
+}
+
+// 断言交易失败并且错误码(或日志)包含指定的名称
+export async function expectError(promise: Promise<unknown>, code: string) {
+  try {
+    await promise;
+  } catch (err) {
+    const actual = err?.error?.errorCode?.code ?? String(err);
+    expect(actual).to.contain(code);
+    return;
+  }
+  expect.fail(`expected transaction to fail with ${code}`);
+}
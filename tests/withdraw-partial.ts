@@ -0,0 +1,75 @@
+import { BN } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { PublicKey } from '@solana/web3.js';
+import { expect } from 'chai';
+import {
+  Fixture,
+  connection,
+  createFixture,
+  expectError,
+  makeEscrow,
+  program,
+  tokenBalance,
+} from './utils';
+
+describe('withdraw_partial', () => {
+  let fx: Fixture;
+
+  beforeEach(async () => {
+    fx = await createFixture();
+  });
+
+  const withdrawPartial = (escrow: PublicKey, amountA: number) =>
+    program.methods
+      .withdrawPartial(new BN(amountA))
+      .accountsPartial({
+        maker: fx.maker.publicKey,
+        escrow,
+        mintA: fx.mintA,
+        pairIndex: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([fx.maker])
+      .rpc();
+
+  it('shrinks the offer and reduces receive pro-rata', async () => {
+    const { escrow, vault } = await makeEscrow(fx, {
+      amount: 1_000,
+      receive: 500,
+    });
+
+    await withdrawPartial(escrow, 400);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.amount.toNumber()).to.equal(600);
+    expect(state.receive.toNumber()).to.equal(300);
+    expect(await tokenBalance(vault)).to.equal(600n);
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n - 600n);
+  });
+
+  it('rounds receive up so remaining tokens are never free', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000, receive: 1 });
+
+    await withdrawPartial(escrow, 999);
+
+    const state = await program.account.escrow.fetch(escrow);
+    expect(state.amount.toNumber()).to.equal(1);
+    expect(state.receive.toNumber()).to.equal(1);
+  });
+
+  it('closes everything like refund when emptied', async () => {
+    const { escrow, vault } = await makeEscrow(fx, { amount: 1_000 });
+
+    await withdrawPartial(escrow, 1_000);
+
+    expect(await connection.getAccountInfo(escrow)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(await tokenBalance(fx.makerAtaA)).to.equal(1_000_000n);
+  });
+
+  it('rejects amounts larger than the offer', async () => {
+    const { escrow } = await makeEscrow(fx, { amount: 1_000 });
+
+    await expectError(withdrawPartial(escrow, 1_001), 'InvalidAmount');
+  });
+});